@@ -1,13 +1,20 @@
+use std::future::Future;
+use std::time::Instant;
+
 use async_trait::async_trait;
+use tokio::task::{AbortHandle, JoinHandle};
 
 use crate::AgentValue;
 
 use super::askit::ASKit;
+use super::capability::AgentCapability;
 use super::config::AgentConfigs;
 use super::context::AgentContext;
 use super::data::AgentData;
 use super::error::AgentError;
+use super::process::ProcessAgent;
 use super::runtime::runtime;
+use super::substitution;
 
 #[derive(Debug, Default, Clone, PartialEq)]
 pub enum AgentStatus {
@@ -22,6 +29,8 @@ pub enum AgentMessage {
         ctx: AgentContext,
         pin: String,
         data: AgentData,
+        queued_at: Instant,
+        delivery_id: Option<u64>,
     },
     Config {
         configs: AgentConfigs,
@@ -58,13 +67,26 @@ pub trait Agent {
         self.askit().get_global_configs(self.def_name())
     }
 
+    fn check_capability(&self, capability: AgentCapability) -> Result<(), AgentError> {
+        self.askit().check_capability(self.def_name(), capability)
+    }
+
+    /// Reads an environment variable, which needs the `Env` capability.
+    fn env_var(&self, name: &str) -> Result<Option<String>, AgentError> {
+        self.askit().env_var(self.def_name(), name)
+    }
+
     fn flow_name(&self) -> &str;
 
     fn set_flow_name(&mut self, flow_name: String);
 
-    fn start(&mut self) -> Result<(), AgentError>;
+    async fn start(&mut self) -> Result<(), AgentError>;
+
+    async fn stop(&mut self) -> Result<(), AgentError>;
 
-    fn stop(&mut self) -> Result<(), AgentError>;
+    fn state(&self) -> Option<AgentValue>;
+
+    fn restore_state(&mut self, state: AgentValue) -> Result<(), AgentError>;
 
     async fn process(
         &mut self,
@@ -86,6 +108,13 @@ pub struct AsAgentData {
     pub def_name: String,
     pub flow_name: String,
     pub configs: Option<AgentConfigs>,
+
+    // configs with their ${env:VAR} and ${board:name} references replaced, or the error
+    // of a reference the agent may not resolve
+    pub(crate) resolved_configs: Option<Result<AgentConfigs, AgentError>>,
+
+    // background tasks aborted when the agent stops
+    pub(crate) tasks: Vec<JoinHandle<()>>,
 }
 
 impl AsAgentData {
@@ -97,8 +126,56 @@ impl AsAgentData {
             def_name,
             flow_name: String::new(),
             configs,
+            resolved_configs: None,
+            tasks: Vec::new(),
+        }
+    }
+
+    /// Spawns background work of the agent, such as a poller or a listener.
+    /// The task is aborted when the agent stops.
+    pub fn spawn_task<F>(&mut self, fut: F) -> AbortHandle
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.tasks.retain(|task| !task.is_finished());
+        let task = runtime().spawn(fut);
+        let abort_handle = task.abort_handle();
+        self.tasks.push(task);
+        abort_handle
+    }
+
+    pub(crate) fn abort_tasks(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.abort();
         }
     }
+
+    // Keeps None when the configs have no references.
+    pub(crate) fn resolve_configs(&mut self) {
+        self.resolved_configs = self
+            .configs
+            .as_ref()
+            .filter(|configs| substitution::has_refs(configs))
+            .map(|configs| substitution::resolve_configs(&self.askit, &self.def_name, configs));
+    }
+}
+
+/// Constructor and `AsAgentData` accessors of an agent struct, implemented by
+/// `#[derive(AskitAgent)]`. `#[askit_agent]` on the `impl AsAgent` block uses them
+/// for `new`, `data` and `mut_data`.
+pub trait AgentBoilerplate {
+    fn new_agent(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        configs: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError>
+    where
+        Self: Sized;
+
+    fn agent_data(&self) -> &AsAgentData;
+
+    fn agent_data_mut(&mut self) -> &mut AsAgentData;
 }
 
 #[async_trait]
@@ -128,6 +205,27 @@ pub trait AsAgent {
         Ok(())
     }
 
+    /// Awaited when the agent starts, for setup such as connecting clients.
+    /// The default calls `start()`.
+    async fn start_async(&mut self) -> Result<(), AgentError> {
+        self.start()
+    }
+
+    /// Awaited when the agent stops. The default calls `stop()`.
+    async fn stop_async(&mut self) -> Result<(), AgentError> {
+        self.stop()
+    }
+
+    /// Internal state to be saved in a flow checkpoint.
+    fn state(&self) -> Option<AgentValue> {
+        None
+    }
+
+    /// Restores the state saved by `state()`. It is called right after `start()`.
+    fn restore_state(&mut self, _state: AgentValue) -> Result<(), AgentError> {
+        Ok(())
+    }
+
     async fn process(
         &mut self,
         _ctx: AgentContext,
@@ -168,12 +266,21 @@ impl<T: AsAgent + Send + Sync> Agent for T {
     }
 
     fn configs(&self) -> Result<&AgentConfigs, AgentError> {
-        self.data().configs.as_ref().ok_or(AgentError::NoConfig)
+        let data = self.data();
+        match &data.resolved_configs {
+            Some(Ok(configs)) => Ok(configs),
+            Some(Err(AgentError::PermissionDenied(def_name, capability))) => Err(
+                AgentError::PermissionDenied(def_name.clone(), capability.clone()),
+            ),
+            Some(Err(e)) => Err(AgentError::InvalidConfig(e.to_string())),
+            None => data.configs.as_ref().ok_or(AgentError::NoConfig),
+        }
     }
 
     fn set_config(&mut self, key: String, value: AgentValue) -> Result<(), AgentError> {
         if let Some(configs) = &mut self.mut_data().configs {
             configs.set(key.clone(), value.clone());
+            self.mut_data().resolve_configs();
             self.configs_changed()?;
         }
         Ok(())
@@ -181,6 +288,7 @@ impl<T: AsAgent + Send + Sync> Agent for T {
 
     fn set_configs(&mut self, configs: AgentConfigs) -> Result<(), AgentError> {
         self.mut_data().configs = Some(configs);
+        self.mut_data().resolve_configs();
         self.configs_changed()
     }
 
@@ -192,35 +300,63 @@ impl<T: AsAgent + Send + Sync> Agent for T {
         self.mut_data().flow_name = flow_name.clone();
     }
 
-    fn start(&mut self) -> Result<(), AgentError> {
+    async fn start(&mut self) -> Result<(), AgentError> {
         self.mut_data().status = AgentStatus::Start;
+        self.mut_data().resolve_configs();
+
+        // a reference the agent may not resolve fails the start, not the first input
+        if let Some(Err(_)) = &self.data().resolved_configs
+            && let Err(e) = self.configs()
+        {
+            self.askit()
+                .emit_agent_error(self.id().to_string(), e.to_string());
+            return Err(e);
+        }
 
-        if let Err(e) = self.start() {
+        if let Err(e) = self.start_async().await {
             self.askit()
                 .emit_agent_error(self.id().to_string(), e.to_string());
             return Err(e);
         }
 
+        if let Some(state) = self.askit().take_agent_state(self.id()) {
+            self.restore_state(state)?;
+        }
+
         Ok(())
     }
 
-    fn stop(&mut self) -> Result<(), AgentError> {
+    async fn stop(&mut self) -> Result<(), AgentError> {
         self.mut_data().status = AgentStatus::Stop;
-        self.stop()?;
+        let result = self.stop_async().await;
+        self.mut_data().abort_tasks();
+        result?;
         self.mut_data().status = AgentStatus::Init;
         Ok(())
     }
 
+    fn state(&self) -> Option<AgentValue> {
+        self.state()
+    }
+
+    fn restore_state(&mut self, state: AgentValue) -> Result<(), AgentError> {
+        self.restore_state(state)
+    }
+
     async fn process(
         &mut self,
         ctx: AgentContext,
         pin: String,
         data: AgentData,
     ) -> Result<(), AgentError> {
-        if let Err(e) = self.process(ctx, pin, data).await {
+        // boards referenced by the configs may have changed
+        if self.data().resolved_configs.is_some() {
+            self.mut_data().resolve_configs();
+        }
+        if let Err(e) = self.process(ctx, pin.clone(), data).await {
             self.askit()
                 .emit_agent_error(self.id().to_string(), e.to_string());
-            return Err(e);
+            return Err(e.in_agent(self.id(), pin));
         }
         Ok(())
     }
@@ -254,26 +390,11 @@ pub fn agent_new(
             .clone();
     }
 
-    let default_config = def.default_configs.clone();
-    let configs = match (default_config, configs) {
-        (Some(def_cfg), Some(mut cfg)) => {
-            for (k, v) in def_cfg.iter() {
-                if !cfg.contains_key(k) {
-                    cfg.set(k.clone(), v.value.clone());
-                }
-            }
-            Some(cfg)
-        }
-        (Some(def_cfg), None) => {
-            let mut cfg = AgentConfigs::default();
-            for (k, v) in def_cfg.iter() {
-                cfg.set(k.clone(), v.value.clone());
-            }
-            Some(cfg)
-        }
-        (None, Some(cfg)) => Some(cfg),
-        (None, None) => None,
-    };
+    let configs = Some(def.materialize_configs(configs));
+
+    if def.process.is_some() {
+        return new_agent_boxed::<ProcessAgent>(askit, agent_id, def_name.to_string(), configs);
+    }
 
     if let Some(new_boxed) = def.new_boxed {
         return new_boxed(askit, agent_id, def_name.to_string(), configs);
@@ -291,3 +412,139 @@ pub fn agent_new(
         _ => return Err(AgentError::UnknownDefKind(def.kind.to_string()).into()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct ConnectingAgent {
+        data: AsAgentData,
+        connected: bool,
+    }
+
+    #[async_trait]
+    impl AsAgent for ConnectingAgent {
+        fn new(
+            askit: ASKit,
+            id: String,
+            def_name: String,
+            config: Option<AgentConfigs>,
+        ) -> Result<Self, AgentError> {
+            Ok(Self {
+                data: AsAgentData::new(askit, id, def_name, config),
+                connected: false,
+            })
+        }
+
+        fn data(&self) -> &AsAgentData {
+            &self.data
+        }
+
+        fn mut_data(&mut self) -> &mut AsAgentData {
+            &mut self.data
+        }
+
+        async fn start_async(&mut self) -> Result<(), AgentError> {
+            tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+            self.connected = true;
+            Ok(())
+        }
+
+        async fn stop_async(&mut self) -> Result<(), AgentError> {
+            tokio::task::yield_now().await;
+            self.connected = false;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_start_stop() {
+        let askit = ASKit::new();
+        let mut agent =
+            <ConnectingAgent as Agent>::new(askit, "1".into(), "test".into(), None).unwrap();
+
+        Agent::start(&mut agent).await.unwrap();
+        assert!(agent.connected);
+        assert_eq!(agent.status(), &AgentStatus::Start);
+
+        Agent::stop(&mut agent).await.unwrap();
+        assert!(!agent.connected);
+        assert_eq!(agent.status(), &AgentStatus::Init);
+    }
+
+    #[tokio::test]
+    async fn test_tasks_aborted_on_stop() {
+        let askit = ASKit::new();
+        let mut agent =
+            <ConnectingAgent as Agent>::new(askit, "1".into(), "test".into(), None).unwrap();
+        Agent::start(&mut agent).await.unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<()>(1);
+        agent.data.spawn_task(async move {
+            let _tx = tx;
+            std::future::pending::<()>().await;
+        });
+        assert_eq!(agent.data.tasks.len(), 1);
+
+        Agent::stop(&mut agent).await.unwrap();
+        assert!(agent.data.tasks.is_empty());
+
+        // the sender is dropped with the aborted task
+        let closed = tokio::time::timeout(std::time::Duration::from_secs(1), rx.recv()).await;
+        assert_eq!(closed, Ok(None));
+    }
+
+    #[derive(crate::AskitAgent)]
+    #[askit(kind = "Test", name = "test_derived", title = "Derived")]
+    #[askit(inputs("in"), outputs("out"))]
+    #[askit(
+        config(integer, "step", 2),
+        config(string, "label"),
+        config(unit, "reset")
+    )]
+    struct DerivedAgent {
+        data: AsAgentData,
+        count: i64,
+    }
+
+    #[crate::askit_agent]
+    #[async_trait]
+    impl AsAgent for DerivedAgent {
+        async fn process(
+            &mut self,
+            _ctx: AgentContext,
+            _pin: String,
+            _data: AgentData,
+        ) -> Result<(), AgentError> {
+            self.count += 1;
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_derived_agent() {
+        let def = DerivedAgent::agent_definition();
+        assert_eq!(def.name, "test_derived");
+        assert_eq!(def.title.as_deref(), Some("Derived"));
+        assert_eq!(def.inputs, Some(vec!["in".to_string()]));
+        assert_eq!(def.outputs, Some(vec!["out".to_string()]));
+        let configs = def.default_configs.unwrap();
+        assert_eq!(configs.len(), 3);
+        assert_eq!(configs[0].1.value, AgentValue::integer(2));
+
+        let askit = ASKit::new();
+        let mut agent =
+            <DerivedAgent as Agent>::new(askit, "1".into(), "test_derived".into(), None).unwrap();
+        assert_eq!(agent.id(), "1");
+        assert_eq!(agent.count, 0);
+        AsAgent::process(
+            &mut agent,
+            AgentContext::new(),
+            "in".into(),
+            AgentData::unit(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(agent.count, 1);
+    }
+}
@@ -1,18 +1,52 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicUsize;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use tokio::sync::{Mutex as AsyncMutex, mpsc};
+use tokio::sync::{Mutex as AsyncMutex, OwnedSemaphorePermit, mpsc, oneshot};
 
 use crate::agent::{Agent, AgentMessage, AgentStatus, agent_new};
+use crate::board::{self, BoardHistory, BoardObserver, BoardSubscribers};
 use crate::board_agent;
+use crate::capability::{AgentCapability, AgentCapabilityPolicy};
+use crate::clock::AgentClock;
 use crate::config::{AgentConfigs, AgentConfigsMap};
 use crate::context::AgentContext;
-use crate::data::AgentData;
-use crate::definition::{AgentDefaultConfigs, AgentDefinition, AgentDefinitions};
+use crate::data::{AgentData, AgentValue};
+use crate::debug::{self, Breakpoint, FlowDebugState, PendingInput};
+use crate::definition::{AgentDefaultConfigs, AgentDefinition, AgentDefinitions, AgentExample};
+use crate::delivery::{self, DeliveryPolicy, FlowDeliveryState, UnackedDelivery};
+use crate::diff::{FlowChange, FlowDiff, diff_flows};
 use crate::error::AgentError;
-use crate::flow::{self, AgentFlow, AgentFlowEdge, AgentFlowNode, AgentFlows};
+use crate::flow::{
+    self, AgentFlow, AgentFlowCheckpoint, AgentFlowEdge, AgentFlowNode, AgentFlows, SubFlowCopy,
+    SubFlowCopyOptions,
+};
+use crate::flow_watch::{self, FlowWatcher};
+use crate::kind::{AgentKindDefinition, AgentKindDefinitions};
 use crate::message::{self, AgentEventMessage};
+use crate::native_thread::{self, DEFAULT_THREAD_JOIN_TIMEOUT};
+use crate::note;
+use crate::notification::Notification;
+use crate::pause::{self, DEFAULT_PAUSE_BUFFER_LIMIT, PausedFlow};
+use crate::probe::{EdgeProbe, ProbeRecord};
+use crate::profile::{self, FlowProfile, FlowProfileReport};
+use crate::quota::{self, FlowQuota, FlowQuotaState, QuotaViolation};
+use crate::request::{self, PendingRequest};
+use crate::routing::EdgeRoutes;
+use crate::saga;
+use crate::secret::SecretProvider;
+use crate::stats::{self, FlowCounter};
+use crate::testing::{self, FlowTest, FlowTestReport, OutputTaps};
+use crate::transaction;
+use crate::usage::{
+    AgentUsage, BudgetAction, ModelPricing, UsageBudget, UsageLedger, UsageRecord, UsageTotals,
+    usage_day,
+};
+
+/// Input port receiving the values of `ASKit::submit_input`.
+pub static SUBMIT_PIN: &str = "$submit";
 
 #[derive(Clone)]
 pub struct ASKit {
@@ -23,24 +57,102 @@ pub struct ASKit {
     // agent id -> sender
     pub(crate) agent_txs: Arc<Mutex<HashMap<String, AgentMessageSender>>>,
 
+    // agent id -> thread of the native-thread agent
+    pub(crate) agent_threads: Arc<Mutex<HashMap<String, std::thread::JoinHandle<()>>>>,
+
     // board name -> [board out agent id]
     pub(crate) board_out_agents: Arc<Mutex<HashMap<String, Vec<String>>>>,
 
     // board name -> data
     pub(crate) board_data: Arc<Mutex<HashMap<String, AgentData>>>,
 
-    // sourece agent id -> [target agent id / source handle / target handle]
-    pub(crate) edges: Arc<Mutex<HashMap<String, Vec<(String, String, String)>>>>,
+    // board name -> last values of the board
+    pub(crate) board_histories: Arc<Mutex<HashMap<String, BoardHistory>>>,
+
+    // subscriber id -> (board name pattern, observer)
+    pub(crate) board_subscribers: Arc<Mutex<BoardSubscribers>>,
+
+    // environment variable -> value, read by the references in configs
+    pub(crate) env_cache: Arc<Mutex<HashMap<String, Option<String>>>>,
+
+    // sourece agent id -> [target agent id / source handle / target handle / edge id]
+    pub(crate) edges: Arc<Mutex<HashMap<String, Vec<(String, String, String, String)>>>>,
+
+    // ids of the agents whose outputs are suppressed
+    pub(crate) muted_agents: Arc<Mutex<HashSet<String>>>,
+
+    // edge id -> last sequence number
+    pub(crate) edge_seqs: Arc<Mutex<HashMap<String, u64>>>,
+
+    // disabled and weighted edges
+    pub(crate) edge_routes: Arc<Mutex<EdgeRoutes>>,
 
     // agent def name -> agent definition
     pub(crate) defs: Arc<Mutex<AgentDefinitions>>,
 
+    // locale of the definitions given to hosts
+    pub(crate) locale: Arc<Mutex<Option<String>>>,
+
+    // kind name -> kind definition
+    pub(crate) kinds: Arc<Mutex<AgentKindDefinitions>>,
+
     // agent flows
     pub(crate) flows: Arc<Mutex<AgentFlows>>,
 
     // agent def name -> config
     pub(crate) global_configs_map: Arc<Mutex<HashMap<String, AgentConfigs>>>,
 
+    // capabilities allowed for agents
+    pub(crate) capability_policy: Arc<Mutex<AgentCapabilityPolicy>>,
+
+    // source of the secrets read by agents
+    pub(crate) secret_provider: Arc<Mutex<Option<Arc<dyn SecretProvider>>>>,
+
+    // flow name -> quota and its usage
+    pub(crate) flow_quotas: Arc<Mutex<HashMap<String, FlowQuotaState>>>,
+
+    // usage of paid providers, pricing and budgets
+    pub(crate) usage: Arc<Mutex<UsageLedger>>,
+
+    // agent id -> state restored when the agent starts
+    pub(crate) agent_states: Arc<Mutex<HashMap<String, AgentValue>>>,
+
+    // flow name -> debug mode state
+    pub(crate) flow_debug: Arc<Mutex<HashMap<String, FlowDebugState>>>,
+
+    // edge id -> probe
+    pub(crate) edge_probes: Arc<Mutex<HashMap<String, EdgeProbe>>>,
+
+    // flow name -> inputs buffered while the flow is paused
+    pub(crate) paused_flows: Arc<Mutex<HashMap<String, PausedFlow>>>,
+
+    // flow name -> timings while profiling
+    pub(crate) flow_profiles: Arc<Mutex<HashMap<String, FlowProfile>>>,
+
+    // flow name -> inputs processed since the last stats event
+    pub(crate) flow_counters: Arc<Mutex<HashMap<String, FlowCounter>>>,
+
+    // interval of the FlowStats events, None when they are off
+    pub(crate) flow_stats_interval: Arc<Mutex<Option<Duration>>>,
+
+    // time a full agent queue blocks a send before SlowConsumer, None when it is off
+    pub(crate) slow_consumer_threshold: Arc<Mutex<Option<Duration>>>,
+
+    // agent id -> outputs recorded for a flow test
+    pub(crate) output_taps: Arc<Mutex<OutputTaps>>,
+
+    // correlation id -> request waiting for its response
+    pub(crate) pending_requests: Arc<Mutex<HashMap<usize, PendingRequest>>>,
+
+    // flow name -> inputs waiting for acknowledgment
+    pub(crate) flow_deliveries: Arc<Mutex<HashMap<String, FlowDeliveryState>>>,
+
+    // time source of the time-based agents
+    pub(crate) clock: Arc<Mutex<AgentClock>>,
+
+    // watched directory -> task reloading its flow files
+    pub(crate) flow_watchers: Arc<Mutex<HashMap<PathBuf, FlowWatcher>>>,
+
     // message sender
     pub(crate) tx: Arc<Mutex<Option<mpsc::Sender<AgentEventMessage>>>>,
 
@@ -53,12 +165,38 @@ impl ASKit {
         Self {
             agents: Default::default(),
             agent_txs: Default::default(),
+            agent_threads: Default::default(),
             board_out_agents: Default::default(),
             board_data: Default::default(),
+            board_histories: Default::default(),
+            board_subscribers: Default::default(),
+            env_cache: Default::default(),
             edges: Default::default(),
+            muted_agents: Default::default(),
+            edge_seqs: Default::default(),
+            edge_routes: Default::default(),
             defs: Default::default(),
+            locale: Default::default(),
+            kinds: Default::default(),
             flows: Default::default(),
             global_configs_map: Default::default(),
+            capability_policy: Default::default(),
+            secret_provider: Default::default(),
+            flow_quotas: Default::default(),
+            usage: Default::default(),
+            agent_states: Default::default(),
+            flow_debug: Default::default(),
+            edge_probes: Default::default(),
+            paused_flows: Default::default(),
+            flow_profiles: Default::default(),
+            flow_deliveries: Default::default(),
+            flow_counters: Default::default(),
+            flow_stats_interval: Default::default(),
+            slow_consumer_threshold: Default::default(),
+            output_taps: Default::default(),
+            pending_requests: Default::default(),
+            clock: Default::default(),
+            flow_watchers: Default::default(),
             tx: Arc::new(Mutex::new(None)),
             observers: Default::default(),
         }
@@ -80,10 +218,12 @@ impl ASKit {
 
     fn register_agents(&self) {
         board_agent::register_agents(self);
+        note::register_agents(self);
     }
 
     pub async fn ready(&self) -> Result<(), AgentError> {
         self.spawn_message_loop()?;
+        stats::spawn_stats_loop(self);
         self.start_agent_flows().await?;
         Ok(())
     }
@@ -93,12 +233,45 @@ impl ASKit {
         *tx_lock = None;
     }
 
+    /// Stops all agents and quits, waiting up to `timeout` for the threads of native-thread agents.
+    pub async fn shutdown(&self, timeout: Duration) -> Result<(), AgentError> {
+        for (_, watcher) in self.flow_watchers.lock().unwrap().drain() {
+            watcher.abort();
+        }
+
+        let deadline = Instant::now() + timeout;
+        let agent_ids: Vec<String> = self.agents.lock().unwrap().keys().cloned().collect();
+        for agent_id in agent_ids {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if let Err(e) = self.stop_agent_with_timeout(&agent_id, remaining).await {
+                log::error!("Failed to stop agent {}: {}", agent_id, e);
+            }
+        }
+        self.quit();
+
+        let running = self.get_native_thread_agents();
+        if !running.is_empty() {
+            return Err(AgentError::Other(format!(
+                "Threads of agents did not stop: {}",
+                running.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Ids of the native-thread agents whose threads have not been joined.
+    pub fn get_native_thread_agents(&self) -> Vec<String> {
+        let mut agent_ids: Vec<String> =
+            self.agent_threads.lock().unwrap().keys().cloned().collect();
+        agent_ids.sort();
+        agent_ids
+    }
+
     pub fn register_agent(&self, def: AgentDefinition) {
         let def_name = def.name.clone();
         let def_global_configs = def.global_configs.clone();
 
-        let mut defs = self.defs.lock().unwrap();
-        defs.insert(def.name.clone(), def);
+        self.defs.lock().unwrap().insert(def.name.clone(), def);
 
         // if there is a global config, set it
         if let Some(def_global_configs) = def_global_configs {
@@ -106,18 +279,74 @@ impl ASKit {
             for (key, config_entry) in def_global_configs.iter() {
                 new_configs.set(key.clone(), config_entry.value.clone());
             }
-            self.set_global_configs(def_name, new_configs);
+            self.set_global_configs(def_name.clone(), new_configs);
         }
+
+        self.emit_definitions_changed(def_name);
+    }
+
+    /// Removes the definition, such as the one of an unloaded plugin.
+    ///
+    /// Agents already created from it keep running. Returns the definition if it was registered.
+    pub fn unregister_agent(&self, def_name: &str) -> Option<AgentDefinition> {
+        let def = self.defs.lock().unwrap().remove(def_name)?;
+        self.emit_definitions_changed(def_name.to_string());
+        Some(def)
     }
 
+    /// Definitions to be sent to hosts, localized to the locale if one is set.
     pub fn get_agent_definitions(&self) -> AgentDefinitions {
+        let locale = self.get_locale();
         let defs = self.defs.lock().unwrap();
-        defs.clone()
+        match locale {
+            Some(locale) => defs
+                .iter()
+                .map(|(name, def)| (name.clone(), def.localized(&locale)))
+                .collect(),
+            None => defs.clone(),
+        }
     }
 
     pub fn get_agent_definition(&self, def_name: &str) -> Option<AgentDefinition> {
+        let locale = self.get_locale();
+        let defs = self.defs.lock().unwrap();
+        let def = defs.get(def_name)?;
+        match locale {
+            Some(locale) => Some(def.localized(&locale)),
+            None => Some(def.clone()),
+        }
+    }
+
+    /// Examples bundled with the definition.
+    pub fn get_agent_examples(&self, def_name: &str) -> Vec<AgentExample> {
         let defs = self.defs.lock().unwrap();
-        defs.get(def_name).cloned()
+        defs.get(def_name)
+            .and_then(|def| def.examples.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn get_locale(&self) -> Option<String> {
+        self.locale.lock().unwrap().clone()
+    }
+
+    /// Sets the locale of the titles and descriptions of the definitions, such as "ja".
+    pub fn set_locale(&self, locale: Option<String>) {
+        *self.locale.lock().unwrap() = locale;
+    }
+
+    /// Definitions matching the query, most relevant first.
+    ///
+    /// An empty query returns all the definitions, sorted by name.
+    pub fn search_definitions(&self, query: &str) -> Vec<AgentDefinition> {
+        let mut matches = self
+            .get_agent_definitions()
+            .into_values()
+            .filter_map(|def| def.search_score(query).map(|score| (score, def)))
+            .collect::<Vec<_>>();
+        matches.sort_by(|(a_score, a), (b_score, b)| {
+            b_score.cmp(a_score).then_with(|| a.name.cmp(&b.name))
+        });
+        matches.into_iter().map(|(_, def)| def).collect()
     }
 
     pub fn get_agent_default_configs(&self, def_name: &str) -> Option<AgentDefaultConfigs> {
@@ -128,6 +357,43 @@ impl ASKit {
         def.default_configs.clone()
     }
 
+    // kinds
+
+    pub fn register_kind(&self, def: AgentKindDefinition) {
+        let mut kinds = self.kinds.lock().unwrap();
+        kinds.insert(def.name.clone(), def);
+    }
+
+    pub fn get_kind_definitions(&self) -> AgentKindDefinitions {
+        let kinds = self.kinds.lock().unwrap();
+        kinds.clone()
+    }
+
+    pub fn get_kind_definition(&self, kind: &str) -> Option<AgentKindDefinition> {
+        let kinds = self.kinds.lock().unwrap();
+        kinds.get(kind).cloned()
+    }
+
+    /// Validates the data against the schema of its kind, if the kind is registered with one.
+    pub fn validate_data(&self, data: &AgentData) -> Result<(), AgentError> {
+        let Some(def) = self.get_kind_definition(&data.kind) else {
+            return Ok(());
+        };
+        def.validate(&data.value.to_json())
+    }
+
+    /// [`AgentData::from_json_with_kind`] that validates the value against the schema of the kind.
+    pub fn data_from_json_with_kind(
+        &self,
+        kind: &str,
+        value: serde_json::Value,
+    ) -> Result<AgentData, AgentError> {
+        if let Some(def) = self.get_kind_definition(kind) {
+            def.validate(&value)?;
+        }
+        AgentData::from_json_with_kind(kind, value)
+    }
+
     // // flow
 
     pub fn get_agent_flows(&self) -> AgentFlows {
@@ -165,6 +431,31 @@ impl ASKit {
         // insert renamed flow
         flow.set_name(new_name.clone());
         flows.insert(new_name.clone(), flow);
+
+        // move the quota to the new name
+        let mut flow_quotas = self.flow_quotas.lock().unwrap();
+        if let Some(mut state) = flow_quotas.remove(old_name) {
+            state.renamed(self, &new_name);
+            flow_quotas.insert(new_name.clone(), state);
+        }
+
+        // move the debug mode to the new name
+        let mut flow_debug = self.flow_debug.lock().unwrap();
+        if let Some(state) = flow_debug.remove(old_name) {
+            flow_debug.insert(new_name.clone(), state);
+        }
+
+        // move the paused state to the new name
+        let mut paused_flows = self.paused_flows.lock().unwrap();
+        if let Some(paused) = paused_flows.remove(old_name) {
+            paused_flows.insert(new_name.clone(), paused);
+        }
+
+        // move the profile to the new name
+        let mut flow_profiles = self.flow_profiles.lock().unwrap();
+        if let Some(profile) = flow_profiles.remove(old_name) {
+            flow_profiles.insert(new_name.clone(), profile);
+        }
         Ok(new_name)
     }
 
@@ -213,6 +504,9 @@ impl ASKit {
 
     pub fn add_agent_flow(&self, agent_flow: &AgentFlow) -> Result<(), AgentError> {
         let name = agent_flow.name();
+        let mut agent_flow = agent_flow.clone();
+        agent_flow.validate()?;
+        self.migrate_agent_flow(&mut agent_flow);
 
         // add the given flow into flows
         {
@@ -240,6 +534,28 @@ impl ASKit {
         Ok(())
     }
 
+    /// Migrates the nodes saved by older versions of their agents.
+    pub fn migrate_agent_flow(&self, agent_flow: &mut AgentFlow) {
+        let mut nodes = agent_flow.nodes().clone();
+        for node in nodes.iter_mut() {
+            let Some(def) = self.get_agent_definition(&node.def_name) else {
+                continue;
+            };
+            match node.migrate(&def) {
+                Ok(true) => {}
+                Ok(false) => log::warn!(
+                    "Agent {} ({}) was saved with version {:?}, but version {:?} is registered",
+                    node.id,
+                    node.def_name,
+                    node.version,
+                    def.version
+                ),
+                Err(e) => log::error!("Failed to migrate agent {}: {}", node.id, e),
+            }
+        }
+        agent_flow.set_nodes(nodes);
+    }
+
     pub async fn remove_agent_flow(&self, flow_name: &str) -> Result<(), AgentError> {
         let flow = {
             let mut flows = self.flows.lock().unwrap();
@@ -252,13 +568,19 @@ impl ASKit {
         flow.stop(self).await?;
 
         // Remove all nodes and edges associated with the flow
-        for node in flow.nodes() {
+        for node in flow.nodes().iter().filter(|node| !node.is_note()) {
             self.remove_agent(&node.id).await?;
         }
         for edge in flow.edges() {
             self.remove_edge(edge);
         }
 
+        self.flow_quotas.lock().unwrap().remove(flow_name);
+        self.flow_debug.lock().unwrap().remove(flow_name);
+        self.paused_flows.lock().unwrap().remove(flow_name);
+        self.flow_profiles.lock().unwrap().remove(flow_name);
+        self.flow_deliveries.lock().unwrap().remove(flow_name);
+
         Ok(())
     }
 
@@ -277,6 +599,38 @@ impl ASKit {
         AgentFlowNode::new(&def)
     }
 
+    /// Creates a node of the definition with a fresh ULID and the default configs, overridden
+    /// by `configs`, and adds it to the flow.
+    pub fn new_node(
+        &self,
+        flow_name: &str,
+        def_name: &str,
+        configs: Option<AgentConfigs>,
+    ) -> Result<AgentFlowNode, AgentError> {
+        let mut node = self.new_agent_flow_node(def_name)?;
+        node.id = loop {
+            let id = flow::new_ulid();
+            let taken = self.agents.lock().unwrap().contains_key(&id)
+                || self
+                    .flows
+                    .lock()
+                    .unwrap()
+                    .values()
+                    .any(|flow| flow.get_node(&id).is_some());
+            if !taken {
+                break id;
+            }
+        };
+        if let Some(configs) = configs {
+            let node_configs = node.configs.get_or_insert_with(AgentConfigs::new);
+            for (key, value) in configs {
+                node_configs.set(key, value);
+            }
+        }
+        self.add_agent_flow_nodes(flow_name, vec![node.clone()])?;
+        Ok(node)
+    }
+
     pub fn add_agent_flow_node(
         &self,
         flow_name: &str,
@@ -291,11 +645,67 @@ impl ASKit {
         Ok(())
     }
 
+    /// Adds the nodes to the flow at once. Either all of them are added or none is.
+    pub fn add_agent_flow_nodes(
+        &self,
+        flow_name: &str,
+        nodes: Vec<AgentFlowNode>,
+    ) -> Result<(), AgentError> {
+        {
+            let mut flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get_mut(flow_name) else {
+                return Err(AgentError::FlowNotFound(flow_name.to_string()));
+            };
+            let mut agents = self.agents.lock().unwrap();
+
+            // create every agent before touching the flow
+            let mut ids = HashSet::new();
+            let mut new_agents = Vec::new();
+            for node in &nodes {
+                if agents.contains_key(&node.id)
+                    || flow.get_node(&node.id).is_some()
+                    || !ids.insert(node.id.as_str())
+                {
+                    return Err(AgentError::AgentAlreadyExists(node.id.to_string()));
+                }
+                if node.is_note() {
+                    continue;
+                }
+                let Ok(mut agent) = agent_new(
+                    self.clone(),
+                    node.id.clone(),
+                    &node.def_name,
+                    node.configs.clone(),
+                ) else {
+                    return Err(AgentError::AgentCreationFailed(node.id.to_string()));
+                };
+                agent.set_flow_name(flow_name.to_string());
+                new_agents.push((node.id.clone(), agent, node.muted));
+            }
+
+            let mut muted_agents = self.muted_agents.lock().unwrap();
+            for (id, agent, muted) in new_agents {
+                if muted {
+                    muted_agents.insert(id.clone());
+                }
+                agents.insert(id, Arc::new(AsyncMutex::new(agent)));
+            }
+            for node in nodes {
+                flow.add_node(node);
+            }
+        }
+        self.emit_flow_changed(flow_name.to_string());
+        Ok(())
+    }
+
     pub(crate) fn add_agent(
         &self,
         flow_name: &str,
         node: &AgentFlowNode,
     ) -> Result<(), AgentError> {
+        if node.is_note() {
+            return Ok(());
+        }
         let mut agents = self.agents.lock().unwrap();
         if agents.contains_key(&node.id) {
             return Err(AgentError::AgentAlreadyExists(node.id.to_string()));
@@ -308,6 +718,9 @@ impl ASKit {
         ) {
             agent.set_flow_name(flow_name.to_string());
             agents.insert(node.id.clone(), Arc::new(AsyncMutex::new(agent)));
+            if node.muted {
+                self.muted_agents.lock().unwrap().insert(node.id.clone());
+            }
         } else {
             return Err(AgentError::AgentCreationFailed(node.id.to_string()));
         }
@@ -323,12 +736,63 @@ impl ASKit {
         let Some(flow) = flows.get_mut(flow_name) else {
             return Err(AgentError::FlowNotFound(flow_name.to_string()));
         };
+        for node_id in [&edge.source, &edge.target] {
+            if flow.get_node(node_id).is_some_and(|node| node.is_note()) {
+                return Err(AgentError::InvalidFlow(
+                    flow_name.to_string(),
+                    format!("note {} cannot have edges", node_id),
+                ));
+            }
+        }
         flow.add_edge(edge.clone());
         self.add_edge(edge)?;
         Ok(())
     }
 
-    pub(crate) fn add_edge(&self, edge: &AgentFlowEdge) -> Result<(), AgentError> {
+    /// Adds the edges to the flow at once. Either all of them are added or none is.
+    pub fn add_agent_flow_edges(
+        &self,
+        flow_name: &str,
+        edges: Vec<AgentFlowEdge>,
+    ) -> Result<(), AgentError> {
+        {
+            let mut flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get_mut(flow_name) else {
+                return Err(AgentError::FlowNotFound(flow_name.to_string()));
+            };
+
+            // check every edge before touching the flow
+            let mut keys = HashSet::new();
+            for edge in &edges {
+                for node_id in [&edge.source, &edge.target] {
+                    if flow.get_node(node_id).is_some_and(|node| node.is_note()) {
+                        return Err(AgentError::InvalidFlow(
+                            flow_name.to_string(),
+                            format!("note {} cannot have edges", node_id),
+                        ));
+                    }
+                }
+                self.check_edge(edge)?;
+                if !keys.insert((
+                    &edge.source,
+                    &edge.target,
+                    &edge.source_handle,
+                    &edge.target_handle,
+                )) {
+                    return Err(AgentError::EdgeAlreadyExists);
+                }
+            }
+
+            for edge in edges {
+                self.add_edge(&edge)?;
+                flow.add_edge(edge);
+            }
+        }
+        self.emit_flow_changed(flow_name.to_string());
+        Ok(())
+    }
+
+    fn check_edge(&self, edge: &AgentFlowEdge) -> Result<(), AgentError> {
         // check if the source agent exists
         {
             let agents = self.agents.lock().unwrap();
@@ -345,22 +809,31 @@ impl ASKit {
             return Err(AgentError::EmptyTargetHandle);
         }
 
-        let mut edges = self.edges.lock().unwrap();
-        if let Some(targets) = edges.get_mut(&edge.source) {
-            if targets
+        let edges = self.edges.lock().unwrap();
+        if edges.get(&edge.source).is_some_and(|targets| {
+            targets
                 .iter()
-                .any(|(target, source_handle, target_handle)| {
+                .any(|(target, source_handle, target_handle, _)| {
                     *target == edge.target
                         && *source_handle == edge.source_handle
                         && *target_handle == edge.target_handle
                 })
-            {
-                return Err(AgentError::EdgeAlreadyExists);
-            }
+        }) {
+            return Err(AgentError::EdgeAlreadyExists);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn add_edge(&self, edge: &AgentFlowEdge) -> Result<(), AgentError> {
+        self.check_edge(edge)?;
+
+        let mut edges = self.edges.lock().unwrap();
+        if let Some(targets) = edges.get_mut(&edge.source) {
             targets.push((
                 edge.target.clone(),
                 edge.source_handle.clone(),
                 edge.target_handle.clone(),
+                edge.id.clone(),
             ));
         } else {
             edges.insert(
@@ -369,9 +842,11 @@ impl ASKit {
                     edge.target.clone(),
                     edge.source_handle.clone(),
                     edge.target_handle.clone(),
+                    edge.id.clone(),
                 )],
             );
         }
+        self.edge_routes.lock().unwrap().set_edge(edge);
         Ok(())
     }
 
@@ -380,26 +855,152 @@ impl ASKit {
         flow_name: &str,
         node_id: &str,
     ) -> Result<(), AgentError> {
-        {
+        let is_note = {
             let mut flows = self.flows.lock().unwrap();
             let Some(flow) = flows.get_mut(flow_name) else {
                 return Err(AgentError::FlowNotFound(flow_name.to_string()));
             };
+            let is_note = flow.get_node(node_id).is_some_and(|node| node.is_note());
             flow.remove_node(node_id);
+            is_note
+        };
+        if !is_note {
+            self.remove_agent(node_id).await?;
+        }
+        Ok(())
+    }
+
+    /// Removes the nodes and edges from the flow at once. Nothing is removed if one of them
+    /// is not in the flow.
+    pub async fn remove_many(
+        &self,
+        flow_name: &str,
+        node_ids: &[String],
+        edge_ids: &[String],
+    ) -> Result<(), AgentError> {
+        let agent_ids = {
+            let mut flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get_mut(flow_name) else {
+                return Err(AgentError::FlowNotFound(flow_name.to_string()));
+            };
+            if let Some(node_id) = node_ids.iter().find(|id| flow.get_node(id).is_none()) {
+                return Err(AgentError::AgentNotFound(node_id.to_string()));
+            }
+            if let Some(edge_id) = edge_ids
+                .iter()
+                .find(|id| !flow.edges().iter().any(|edge| edge.id == **id))
+            {
+                return Err(AgentError::EdgeNotFound(edge_id.to_string()));
+            }
+
+            for edge_id in edge_ids {
+                if let Some(edge) = flow.remove_edge(edge_id) {
+                    self.remove_edge(&edge);
+                }
+            }
+            let mut agent_ids = Vec::new();
+            for node_id in node_ids {
+                if flow.get_node(node_id).is_some_and(|node| !node.is_note()) {
+                    agent_ids.push(node_id.clone());
+                }
+                flow.remove_node(node_id);
+            }
+            agent_ids
+        };
+        for agent_id in agent_ids {
+            self.remove_agent(&agent_id).await?;
+        }
+        self.emit_flow_changed(flow_name.to_string());
+        Ok(())
+    }
+
+    /// Enables or disables the node.
+    ///
+    /// A disabled node is stopped right away. An enabled node is started if another node
+    /// of the flow is running.
+    pub async fn set_node_enabled(
+        &self,
+        flow_name: &str,
+        node_id: &str,
+        enabled: bool,
+    ) -> Result<(), AgentError> {
+        let others = {
+            let mut flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get_mut(flow_name) else {
+                return Err(AgentError::FlowNotFound(flow_name.to_string()));
+            };
+            let Some(node) = flow.get_node_mut(node_id) else {
+                return Err(AgentError::AgentNotFound(node_id.to_string()));
+            };
+            node.enabled = enabled;
+            flow.nodes()
+                .iter()
+                .filter(|node| node.enabled && node.id != node_id)
+                .map(|node| node.id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        if !enabled {
+            return self.stop_agent(node_id).await;
+        }
+        for other in others {
+            if self.is_agent_running(&other).await {
+                return self.start_agent(node_id).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Mutes or unmutes the node. A muted agent keeps processing, but its outputs are dropped.
+    pub fn set_node_muted(
+        &self,
+        flow_name: &str,
+        node_id: &str,
+        muted: bool,
+    ) -> Result<(), AgentError> {
+        {
+            let mut flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get_mut(flow_name) else {
+                return Err(AgentError::FlowNotFound(flow_name.to_string()));
+            };
+            let Some(node) = flow.get_node_mut(node_id) else {
+                return Err(AgentError::AgentNotFound(node_id.to_string()));
+            };
+            node.muted = muted;
+        }
+        let mut muted_agents = self.muted_agents.lock().unwrap();
+        if muted {
+            muted_agents.insert(node_id.to_string());
+        } else {
+            muted_agents.remove(node_id);
         }
-        self.remove_agent(node_id).await?;
         Ok(())
     }
 
+    async fn is_agent_running(&self, agent_id: &str) -> bool {
+        let agent = self.agents.lock().unwrap().get(agent_id).cloned();
+        match agent {
+            Some(agent) => *agent.lock().await.status() == AgentStatus::Start,
+            None => false,
+        }
+    }
+
     pub(crate) async fn remove_agent(&self, agent_id: &str) -> Result<(), AgentError> {
         self.stop_agent(agent_id).await?;
 
         // remove from edges
         {
             let mut edges = self.edges.lock().unwrap();
+            let mut edge_seqs = self.edge_seqs.lock().unwrap();
             let mut sources_to_remove = Vec::new();
             for (source, targets) in edges.iter_mut() {
-                targets.retain(|(target, _, _)| target != agent_id);
+                targets.retain(|(target, _, _, edge_id)| {
+                    if target == agent_id {
+                        edge_seqs.remove(edge_id);
+                        return false;
+                    }
+                    true
+                });
                 if targets.is_empty() {
                     sources_to_remove.push(source.clone());
                 }
@@ -407,7 +1008,9 @@ impl ASKit {
             for source in sources_to_remove {
                 edges.remove(&source);
             }
-            edges.remove(agent_id);
+            for (_, _, _, edge_id) in edges.remove(agent_id).unwrap_or_default() {
+                edge_seqs.remove(&edge_id);
+            }
         }
 
         // remove from agents
@@ -415,6 +1018,11 @@ impl ASKit {
             let mut agents = self.agents.lock().unwrap();
             agents.remove(agent_id);
         }
+        self.agent_states.lock().unwrap().remove(agent_id);
+        self.muted_agents.lock().unwrap().remove(agent_id);
+        for state in self.flow_deliveries.lock().unwrap().values_mut() {
+            state.remove_agent(agent_id);
+        }
 
         Ok(())
     }
@@ -431,10 +1039,50 @@ impl ASKit {
         Ok(())
     }
 
+    /// Enables or disables the edge at runtime.
+    pub fn set_edge_enabled(
+        &self,
+        flow_name: &str,
+        edge_id: &str,
+        enabled: bool,
+    ) -> Result<(), AgentError> {
+        self.update_edge(flow_name, edge_id, |edge| edge.disabled = !enabled)
+    }
+
+    /// Sets the share of the outputs going through the edge. See `AgentFlowEdge::weight`.
+    pub fn set_edge_weight(
+        &self,
+        flow_name: &str,
+        edge_id: &str,
+        weight: Option<f64>,
+    ) -> Result<(), AgentError> {
+        self.update_edge(flow_name, edge_id, |edge| edge.weight = weight)
+    }
+
+    fn update_edge<F>(&self, flow_name: &str, edge_id: &str, f: F) -> Result<(), AgentError>
+    where
+        F: FnOnce(&mut AgentFlowEdge),
+    {
+        let mut flows = self.flows.lock().unwrap();
+        let Some(flow) = flows.get_mut(flow_name) else {
+            return Err(AgentError::FlowNotFound(flow_name.to_string()));
+        };
+        let Some(edge) = flow.get_edge_mut(edge_id) else {
+            return Err(AgentError::EdgeNotFound(edge_id.to_string()));
+        };
+        f(edge);
+        self.edge_routes.lock().unwrap().set_edge(edge);
+        Ok(())
+    }
+
     pub(crate) fn remove_edge(&self, edge: &AgentFlowEdge) {
+        self.edge_routes.lock().unwrap().remove_edge(edge);
+        self.edge_probes.lock().unwrap().remove(&edge.id);
+        self.edge_seqs.lock().unwrap().remove(&edge.id);
+
         let mut edges = self.edges.lock().unwrap();
         if let Some(targets) = edges.get_mut(&edge.source) {
-            targets.retain(|(target, source_handle, target_handle)| {
+            targets.retain(|(target, source_handle, target_handle, _)| {
                 *target != edge.target
                     || *source_handle != edge.source_handle
                     || *target_handle != edge.target_handle
@@ -447,12 +1095,59 @@ impl ASKit {
 
     pub fn copy_sub_flow(
         &self,
-        nodes: &Vec<AgentFlowNode>,
-        edges: &Vec<AgentFlowEdge>,
+        nodes: &[AgentFlowNode],
+        edges: &[AgentFlowEdge],
     ) -> (Vec<AgentFlowNode>, Vec<AgentFlowEdge>) {
         flow::copy_sub_flow(nodes, edges)
     }
 
+    /// Copies the nodes and edges for a paste. See `SubFlowCopyOptions`.
+    pub fn copy_sub_flow_with(
+        &self,
+        nodes: &[AgentFlowNode],
+        edges: &[AgentFlowEdge],
+        options: &SubFlowCopyOptions,
+    ) -> SubFlowCopy {
+        flow::copy_sub_flow_with(nodes, edges, options)
+    }
+
+    /// Exports the flow, configs included, encrypted with the passphrase.
+    #[cfg(feature = "encryption")]
+    pub fn export_flow_encrypted(
+        &self,
+        name: &str,
+        passphrase: &str,
+    ) -> Result<String, AgentError> {
+        let flow = {
+            let flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get(name) else {
+                return Err(AgentError::FlowNotFound(name.to_string()));
+            };
+            flow.clone()
+        };
+        crate::encryption::encrypt_flow(&flow, passphrase)
+    }
+
+    /// Decrypts a flow exported by `export_flow_encrypted` and adds it.
+    ///
+    /// The flow is renamed if its name is taken. Returns the added flow.
+    #[cfg(feature = "encryption")]
+    pub fn import_flow_encrypted(
+        &self,
+        encrypted: &str,
+        passphrase: &str,
+    ) -> Result<AgentFlow, AgentError> {
+        let mut flow = crate::encryption::decrypt_flow(encrypted, passphrase)?;
+        let name = self.unique_flow_name(flow.name());
+        flow.set_name(name.clone());
+        self.add_agent_flow(&flow)?;
+        let flows = self.flows.lock().unwrap();
+        flows
+            .get(&name)
+            .cloned()
+            .ok_or(AgentError::FlowNotFound(name))
+    }
+
     pub async fn start_agent_flow(&self, name: &str) -> Result<(), AgentError> {
         let flow = {
             let flows = self.flows.lock().unwrap();
@@ -485,9 +1180,9 @@ impl ASKit {
             };
             a.clone()
         };
-        let def_name = {
+        let (def_name, flow_name) = {
             let agent = agent.lock().await;
-            agent.def_name().to_string()
+            (agent.def_name().to_string(), agent.flow_name().to_string())
         };
         let uses_native_thread = {
             let defs = self.defs.lock().unwrap();
@@ -501,8 +1196,14 @@ impl ASKit {
             agent.status().clone()
         };
         if agent_status == AgentStatus::Init {
+            self.check_required_configs(agent_id, &def_name, &agent)
+                .await?;
+
             log::info!("Starting agent {}", agent_id);
 
+            // notified when start() of the agent returns
+            let (started_tx, started_rx) = oneshot::channel();
+
             if uses_native_thread {
                 let (tx, rx) = std::sync::mpsc::channel();
 
@@ -511,23 +1212,34 @@ impl ASKit {
                     agent_txs.insert(agent_id.to_string(), AgentMessageSender::Sync(tx.clone()));
                 };
 
+                let askit = self.clone();
                 let agent_id = agent_id.to_string();
-                std::thread::spawn(async move || {
-                    if let Err(e) = agent.lock().await.start() {
+                native_thread::spawn(self, agent_id.clone(), async move {
+                    if let Err(e) = agent.lock().await.start().await {
                         log::error!("Failed to start agent {}: {}", agent_id, e);
                     }
+                    let _ = started_tx.send(());
 
                     while let Ok(message) = rx.recv() {
                         match message {
-                            AgentMessage::Input { ctx, pin, data } => {
-                                agent
-                                    .lock()
-                                    .await
-                                    .process(ctx, pin, data)
-                                    .await
-                                    .unwrap_or_else(|e| {
-                                        log::error!("Process Error {}: {}", agent_id, e);
-                                    });
+                            AgentMessage::Input {
+                                ctx,
+                                pin,
+                                data,
+                                queued_at,
+                                delivery_id,
+                            } => {
+                                let mut agent = agent.lock().await;
+                                askit
+                                    .process_input(
+                                        agent.as_mut(),
+                                        ctx,
+                                        pin,
+                                        data,
+                                        queued_at,
+                                        delivery_id,
+                                    )
+                                    .await;
                             }
                             AgentMessage::Config { configs } => {
                                 agent.lock().await.set_configs(configs).unwrap_or_else(|e| {
@@ -539,7 +1251,7 @@ impl ASKit {
                             }
                         }
                     }
-                });
+                })?;
             } else {
                 let (tx, mut rx) = mpsc::channel(32);
 
@@ -548,26 +1260,37 @@ impl ASKit {
                     agent_txs.insert(agent_id.to_string(), AgentMessageSender::Async(tx.clone()));
                 };
 
+                let askit = self.clone();
                 let agent_id = agent_id.to_string();
                 tokio::spawn(async move {
                     {
                         let mut agent_guard = agent.lock().await;
-                        if let Err(e) = agent_guard.start() {
+                        if let Err(e) = agent_guard.start().await {
                             log::error!("Failed to start agent {}: {}", agent_id, e);
                         }
                     }
+                    let _ = started_tx.send(());
 
                     while let Some(message) = rx.recv().await {
                         match message {
-                            AgentMessage::Input { ctx, pin, data } => {
-                                agent
-                                    .lock()
-                                    .await
-                                    .process(ctx, pin, data)
-                                    .await
-                                    .unwrap_or_else(|e| {
-                                        log::error!("Process Error {}: {}", agent_id, e);
-                                    });
+                            AgentMessage::Input {
+                                ctx,
+                                pin,
+                                data,
+                                queued_at,
+                                delivery_id,
+                            } => {
+                                let mut agent = agent.lock().await;
+                                askit
+                                    .process_input(
+                                        agent.as_mut(),
+                                        ctx,
+                                        pin,
+                                        data,
+                                        queued_at,
+                                        delivery_id,
+                                    )
+                                    .await;
                             }
                             AgentMessage::Config { configs } => {
                                 agent.lock().await.set_configs(configs).unwrap_or_else(|e| {
@@ -582,11 +1305,92 @@ impl ASKit {
                     }
                 });
             }
-        }
+
+            // the sender is dropped if the agent task ends without starting
+            let _ = started_rx.await;
+
+            delivery::redeliver_unacked(self, &flow_name, agent_id).await;
+        }
         Ok(())
     }
 
+    // Processes an input taken off the queue of the agent, and reports how it went
+    // to the quota, transaction, delivery, saga, profile and stats of its flow.
+    async fn process_input(
+        &self,
+        agent: &mut (dyn Agent + Send + Sync),
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+        queued_at: Instant,
+        delivery_id: Option<u64>,
+    ) {
+        let agent_id = agent.id().to_string();
+        quota::release_input(self, agent.flow_name(), &data);
+        let started_at = Instant::now();
+        let (ctx, staged) = transaction::begin(ctx);
+        let saga_ctx = ctx.clone();
+        let result = if ctx.is_expired() {
+            // waited in the queue past its deadline
+            Err(AgentError::DeadlineExceeded(agent_id.clone()))
+        } else {
+            agent.process(ctx, pin, data).await
+        };
+        if let Err(e) = &result {
+            log::error!("Process Error {}", e);
+        }
+        transaction::finish(self, &agent_id, &staged, &result).await;
+        let redelivered = delivery::complete(self, agent.flow_name(), delivery_id, &result);
+        if let Err(e) = &result
+            && !redelivered
+        {
+            saga::compensate(self, &saga_ctx, e).await;
+        }
+        profile::record(
+            self,
+            agent.flow_name(),
+            &agent_id,
+            agent.def_name(),
+            queued_at,
+            started_at,
+        );
+        stats::record(self, agent.flow_name(), result.is_ok());
+    }
+
+    // Refuses to start the agent while its required configs are empty.
+    async fn check_required_configs(
+        &self,
+        agent_id: &str,
+        def_name: &str,
+        agent: &AsyncMutex<Box<dyn Agent + Send + Sync + 'static>>,
+    ) -> Result<(), AgentError> {
+        let Some(def) = self.get_agent_definition(def_name) else {
+            return Ok(());
+        };
+        let global_configs = self.get_global_configs(def_name);
+        let missing = {
+            let agent = agent.lock().await;
+            def.missing_required_configs(agent.configs().ok(), global_configs.as_ref())
+        };
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(AgentError::MissingRequiredConfig(
+            agent_id.to_string(),
+            missing.join(", "),
+        ))
+    }
+
     pub async fn stop_agent(&self, agent_id: &str) -> Result<(), AgentError> {
+        self.stop_agent_with_timeout(agent_id, DEFAULT_THREAD_JOIN_TIMEOUT)
+            .await
+    }
+
+    async fn stop_agent_with_timeout(
+        &self,
+        agent_id: &str,
+        thread_timeout: Duration,
+    ) -> Result<(), AgentError> {
         let agent = {
             let agents = self.agents.lock().unwrap();
             let Some(a) = agents.get(agent_id) else {
@@ -628,12 +1432,90 @@ impl ASKit {
                 }
             }
 
-            agent.lock().await.stop()?;
+            // the loop of a native-thread agent ends on the stop message
+            native_thread::join(self, agent_id, thread_timeout).await;
+
+            agent.lock().await.stop().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Takes the states of the agents in the flow.
+    pub async fn checkpoint_flow(
+        &self,
+        flow_name: &str,
+    ) -> Result<AgentFlowCheckpoint, AgentError> {
+        let node_ids = {
+            let flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get(flow_name) else {
+                return Err(AgentError::FlowNotFound(flow_name.to_string()));
+            };
+            flow.nodes()
+                .iter()
+                .map(|node| node.id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let mut states = HashMap::new();
+        for node_id in node_ids {
+            let agent = {
+                let agents = self.agents.lock().unwrap();
+                let Some(a) = agents.get(&node_id) else {
+                    continue;
+                };
+                a.clone()
+            };
+            if let Some(state) = agent.lock().await.state() {
+                states.insert(node_id, state);
+            }
+        }
+
+        Ok(AgentFlowCheckpoint {
+            flow_name: flow_name.to_string(),
+            states,
+        })
+    }
+
+    /// Restores the states of a checkpoint.
+    ///
+    /// Running agents are restored immediately, and the others when they start.
+    pub async fn restore_flow(&self, checkpoint: &AgentFlowCheckpoint) -> Result<(), AgentError> {
+        if !self
+            .flows
+            .lock()
+            .unwrap()
+            .contains_key(&checkpoint.flow_name)
+        {
+            return Err(AgentError::FlowNotFound(checkpoint.flow_name.clone()));
         }
 
+        for (agent_id, state) in &checkpoint.states {
+            let agent = {
+                let agents = self.agents.lock().unwrap();
+                agents.get(agent_id).cloned()
+            };
+            let Some(agent) = agent else {
+                log::warn!("Agent {} in the checkpoint is not found", agent_id);
+                continue;
+            };
+            let mut agent = agent.lock().await;
+            if *agent.status() == AgentStatus::Start {
+                agent.restore_state(state.clone())?;
+            } else {
+                self.agent_states
+                    .lock()
+                    .unwrap()
+                    .insert(agent_id.clone(), state.clone());
+            }
+        }
         Ok(())
     }
 
+    pub(crate) fn take_agent_state(&self, agent_id: &str) -> Option<AgentValue> {
+        self.agent_states.lock().unwrap().remove(agent_id)
+    }
+
     pub async fn set_agent_configs(
         &self,
         agent_id: String,
@@ -707,6 +1589,724 @@ impl ASKit {
         global_configs_map.clone()
     }
 
+    // Capabilities
+
+    /// Reads the environment variables referenced by `${env:VAR}` in configs again.
+    pub fn clear_env_cache(&self) {
+        self.env_cache.lock().unwrap().clear();
+    }
+
+    /// Reads an environment variable for an agent of the definition, which needs the
+    /// `Env` capability.
+    pub fn env_var(&self, def_name: &str, name: &str) -> Result<Option<String>, AgentError> {
+        self.check_capability(def_name, AgentCapability::Env)?;
+        Ok(self.cached_env_var(name))
+    }
+
+    /// Reads an environment variable for the runtime itself, such as the default URL or
+    /// API key of a provider, unless the host policy denies `Env`.
+    pub fn host_env_var(&self, name: &str) -> Option<String> {
+        if !self
+            .capability_policy
+            .lock()
+            .unwrap()
+            .is_allowed(AgentCapability::Env)
+        {
+            return None;
+        }
+        std::env::var(name).ok()
+    }
+
+    pub(crate) fn cached_env_var(&self, name: &str) -> Option<String> {
+        let mut env_cache = self.env_cache.lock().unwrap();
+        env_cache
+            .entry(name.to_string())
+            .or_insert_with(|| std::env::var(name).ok())
+            .clone()
+    }
+
+    pub fn get_capability_policy(&self) -> AgentCapabilityPolicy {
+        self.capability_policy.lock().unwrap().clone()
+    }
+
+    pub fn set_capability_policy(&self, policy: AgentCapabilityPolicy) {
+        let mut capability_policy = self.capability_policy.lock().unwrap();
+        *capability_policy = policy;
+    }
+
+    /// Checks that the definition declares the capability and the host policy allows it.
+    pub fn check_capability(
+        &self,
+        def_name: &str,
+        capability: AgentCapability,
+    ) -> Result<(), AgentError> {
+        let declared = {
+            let defs = self.defs.lock().unwrap();
+            let Some(def) = defs.get(def_name) else {
+                return Err(AgentError::UnknownDefName(def_name.to_string()));
+            };
+            def.has_capability(capability)
+        };
+        if !declared
+            || !self
+                .capability_policy
+                .lock()
+                .unwrap()
+                .is_allowed(capability)
+        {
+            return Err(AgentError::PermissionDenied(
+                def_name.to_string(),
+                capability.to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // Secrets
+
+    /// Replaces the source of the secrets read by agents.
+    pub fn set_secret_provider(&self, provider: impl SecretProvider + 'static) {
+        *self.secret_provider.lock().unwrap() = Some(Arc::new(provider));
+    }
+
+    /// The secret of the name, or None when the provider has no such secret or no provider
+    /// is set.
+    pub fn get_secret(&self, name: &str) -> Result<Option<String>, AgentError> {
+        let provider = self.secret_provider.lock().unwrap().clone();
+        match provider {
+            Some(provider) => provider.get_secret(name),
+            None => Ok(None),
+        }
+    }
+
+    // Pause
+
+    /// Stops delivering inputs to the agents of the flow without stopping them.
+    ///
+    /// Inputs are buffered until `resume_agent_flow`, up to 1000 of them by default.
+    pub fn pause_agent_flow(&self, name: &str) -> Result<(), AgentError> {
+        self.pause_agent_flow_with_limit(name, DEFAULT_PAUSE_BUFFER_LIMIT)
+    }
+
+    /// Pauses the flow, keeping the newest `buffer_limit` inputs.
+    pub fn pause_agent_flow_with_limit(
+        &self,
+        name: &str,
+        buffer_limit: usize,
+    ) -> Result<(), AgentError> {
+        if !self.flows.lock().unwrap().contains_key(name) {
+            return Err(AgentError::FlowNotFound(name.to_string()));
+        }
+        let mut paused_flows = self.paused_flows.lock().unwrap();
+        paused_flows
+            .entry(name.to_string())
+            .or_insert_with(|| PausedFlow::new(buffer_limit));
+        Ok(())
+    }
+
+    /// Delivers the buffered inputs and resumes the flow.
+    pub async fn resume_agent_flow(&self, name: &str) -> Result<(), AgentError> {
+        if !self.flows.lock().unwrap().contains_key(name) {
+            return Err(AgentError::FlowNotFound(name.to_string()));
+        }
+        pause::resume(self, name).await
+    }
+
+    pub fn is_agent_flow_paused(&self, name: &str) -> bool {
+        self.paused_flows.lock().unwrap().contains_key(name)
+    }
+
+    /// Number of inputs waiting for the flow to be resumed.
+    pub fn get_buffered_input_count(&self, name: &str) -> usize {
+        let paused_flows = self.paused_flows.lock().unwrap();
+        paused_flows
+            .get(name)
+            .map(|paused| paused.buffered_len())
+            .unwrap_or(0)
+    }
+
+    // Blue/green deployment
+
+    /// Loads a new version of a running flow and starts it on standby.
+    ///
+    /// The standby flow gets new node ids, so both versions can run side by side, and it
+    /// discards its inputs until `swap_agent_flow`. Returns the name of the standby flow.
+    pub async fn add_standby_agent_flow(
+        &self,
+        agent_flow: &AgentFlow,
+    ) -> Result<String, AgentError> {
+        let name = self.unique_flow_name(&format!("{}-standby", agent_flow.name()));
+        let (nodes, edges) = flow::copy_sub_flow(agent_flow.nodes(), agent_flow.edges());
+        let mut standby = agent_flow.clone();
+        standby.set_name(name.clone());
+        standby.set_nodes(nodes);
+        standby.set_edges(edges);
+
+        self.add_agent_flow(&standby)?;
+        self.paused_flows
+            .lock()
+            .unwrap()
+            .insert(name.clone(), PausedFlow::standby());
+        if let Err(e) = self.start_agent_flow(&name).await {
+            self.remove_agent_flow(&name).await?;
+            return Err(e);
+        }
+        Ok(name)
+    }
+
+    /// Routes the traffic of the flow to its standby version and retires the old version.
+    ///
+    /// The standby flow takes over the name, the quota and the delivery policy of the
+    /// old one. Inputs still queued in the old version are dropped.
+    pub async fn swap_agent_flow(&self, name: &str, standby_name: &str) -> Result<(), AgentError> {
+        {
+            let flows = self.flows.lock().unwrap();
+            for flow_name in [name, standby_name] {
+                if !flows.contains_key(flow_name) {
+                    return Err(AgentError::FlowNotFound(flow_name.to_string()));
+                }
+            }
+        }
+
+        pause::swap(self, name, standby_name);
+
+        // inputs held by the quota were for the old agents
+        let quota = self
+            .flow_quotas
+            .lock()
+            .unwrap()
+            .remove(name)
+            .map(|mut state| {
+                state.clear_held();
+                state
+            });
+        let delivery_policy = self.get_delivery_policy(name);
+        self.remove_agent_flow(name).await?;
+
+        self.rename_agent_flow(standby_name, name)?;
+        let node_ids = {
+            let flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get(name) else {
+                return Err(AgentError::FlowNotFound(name.to_string()));
+            };
+            flow.nodes()
+                .iter()
+                .map(|node| node.id.clone())
+                .collect::<Vec<_>>()
+        };
+        for node_id in node_ids {
+            let agent = self.agents.lock().unwrap().get(&node_id).cloned();
+            if let Some(agent) = agent {
+                agent.lock().await.set_flow_name(name.to_string());
+            }
+        }
+
+        if let Some(quota) = quota {
+            self.flow_quotas
+                .lock()
+                .unwrap()
+                .insert(name.to_string(), quota);
+        }
+        if let Some(policy) = delivery_policy {
+            self.set_delivery_policy(name, policy)?;
+        }
+        Ok(())
+    }
+
+    // Hot reload
+
+    /// Applies a new version of the flow in place, keeping the agents that did not change.
+    ///
+    /// Removed nodes and edges go away, added ones are created, and changed configs are
+    /// sent to the running agents. A node whose definition or version changed is created
+    /// again. New nodes are started if the flow is running. A flow not loaded yet is added.
+    /// Returns the changes from the previous version.
+    pub async fn update_agent_flow(&self, agent_flow: &AgentFlow) -> Result<FlowDiff, AgentError> {
+        let name = agent_flow.name().to_string();
+        let mut agent_flow = agent_flow.clone();
+        agent_flow.validate()?;
+        self.migrate_agent_flow(&mut agent_flow);
+
+        let current = self.flows.lock().unwrap().get(&name).cloned();
+        let Some(current) = current else {
+            self.add_agent_flow(&agent_flow)?;
+            return diff_flows(&AgentFlow::new(name), &agent_flow);
+        };
+        let diff = diff_flows(&current, &agent_flow)?;
+        if diff.is_empty() {
+            return Ok(diff);
+        }
+
+        let mut running = false;
+        for node in current.nodes().iter() {
+            if self.is_agent_running(&node.id).await {
+                running = true;
+                break;
+            }
+        }
+
+        // ids of the nodes to remove, to add, and to update in place
+        let mut removed = Vec::new();
+        let mut added = Vec::new();
+        let mut changed = Vec::new();
+        for change in diff.changes.iter() {
+            match change {
+                FlowChange::NodeAdded { id, .. } => added.push(id.clone()),
+                FlowChange::NodeRemoved { id, .. } => removed.push(id.clone()),
+                FlowChange::NodeChanged { id, fields } => {
+                    let (Some(before), Some(after)) =
+                        (current.get_node(id), agent_flow.get_node(id))
+                    else {
+                        continue;
+                    };
+                    if before.def_name != after.def_name || before.version != after.version {
+                        removed.push(id.clone());
+                        added.push(id.clone());
+                    } else {
+                        let configs_changed = fields
+                            .iter()
+                            .any(|field| field.path.starts_with("/configs"));
+                        changed.push((id, configs_changed));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // edges to remove and to add. Removing an agent drops its edges, so the edges
+        // of the recreated nodes are added again.
+        let is_edge_change = |change: &FlowChange, edge_id: &str| match change {
+            FlowChange::EdgeAdded { id, .. }
+            | FlowChange::EdgeRemoved { id, .. }
+            | FlowChange::EdgeChanged { id, .. } => id == edge_id,
+            _ => false,
+        };
+        let old_edges = current
+            .edges()
+            .iter()
+            .filter(|edge| diff.changes.iter().any(|c| is_edge_change(c, &edge.id)))
+            .collect::<Vec<_>>();
+        let new_edges = agent_flow
+            .edges()
+            .iter()
+            .filter(|edge| {
+                diff.changes.iter().any(|c| is_edge_change(c, &edge.id))
+                    || added.contains(&edge.source)
+                    || added.contains(&edge.target)
+            })
+            .collect::<Vec<_>>();
+
+        for edge in old_edges {
+            self.remove_edge(edge);
+        }
+        for id in removed.iter() {
+            if current.get_node(id).is_some_and(|node| !node.is_note()) {
+                self.remove_agent(id).await?;
+            }
+        }
+
+        self.flows
+            .lock()
+            .unwrap()
+            .insert(name.clone(), agent_flow.clone());
+
+        for id in added.iter() {
+            if let Some(node) = agent_flow.get_node(id) {
+                self.add_agent(&name, node)?;
+            }
+        }
+        for edge in new_edges {
+            self.add_edge(edge).unwrap_or_else(|e| {
+                log::error!("Failed to add_edge {}: {}", edge.source, e);
+            });
+        }
+
+        let mut to_start = Vec::new();
+        for (id, configs_changed) in changed {
+            let (Some(before), Some(after)) = (current.get_node(id), agent_flow.get_node(id))
+            else {
+                continue;
+            };
+            if after.is_note() {
+                continue;
+            }
+            if configs_changed {
+                self.set_agent_configs(id.clone(), after.configs.clone().unwrap_or_default())
+                    .await?;
+            }
+            if before.muted != after.muted {
+                let mut muted_agents = self.muted_agents.lock().unwrap();
+                if after.muted {
+                    muted_agents.insert(id.clone());
+                } else {
+                    muted_agents.remove(id);
+                }
+            }
+            if before.enabled && !after.enabled {
+                self.stop_agent(id).await?;
+            } else if !before.enabled && after.enabled {
+                to_start.push(id);
+            }
+        }
+
+        if running {
+            for node in agent_flow.start_order() {
+                if added.contains(&node.id) || to_start.contains(&&node.id) {
+                    self.start_agent(&node.id).await.unwrap_or_else(|e| {
+                        log::error!("Failed to start agent {}: {}", node.id, e);
+                    });
+                }
+            }
+        }
+        Ok(diff)
+    }
+
+    /// Watches the directory and applies the changed flow files with `update_agent_flow`.
+    ///
+    /// The files are checked every `interval`. The flows of a removed file are removed.
+    /// Files present when the watch starts are taken as loaded already. Every reload emits
+    /// `FlowReloaded` or `FlowReloadFailed`.
+    pub fn watch_flow_dir(
+        &self,
+        dir: impl AsRef<Path>,
+        interval: Duration,
+    ) -> Result<(), AgentError> {
+        let dir = dir.as_ref().to_path_buf();
+        let watcher = flow_watch::spawn_watcher(self, &dir, interval)?;
+        if let Some(old) = self.flow_watchers.lock().unwrap().insert(dir, watcher) {
+            old.abort();
+        }
+        Ok(())
+    }
+
+    /// Stops watching the directory.
+    pub fn unwatch_flow_dir(&self, dir: impl AsRef<Path>) {
+        if let Some(watcher) = self.flow_watchers.lock().unwrap().remove(dir.as_ref()) {
+            watcher.abort();
+        }
+    }
+
+    // Debug mode
+
+    /// Turns on the debug mode of the flow, where breakpoints pause the delivery of data.
+    pub fn enable_flow_debug(&self, flow_name: &str) -> Result<(), AgentError> {
+        if !self.flows.lock().unwrap().contains_key(flow_name) {
+            return Err(AgentError::FlowNotFound(flow_name.to_string()));
+        }
+        let mut flow_debug = self.flow_debug.lock().unwrap();
+        flow_debug.entry(flow_name.to_string()).or_default();
+        Ok(())
+    }
+
+    /// Turns off the debug mode of the flow, delivering the pending inputs.
+    pub async fn disable_flow_debug(&self, flow_name: &str) -> Result<(), AgentError> {
+        debug::resume(self, flow_name).await?;
+        self.flow_debug.lock().unwrap().remove(flow_name);
+        Ok(())
+    }
+
+    pub fn is_flow_debug(&self, flow_name: &str) -> bool {
+        self.flow_debug.lock().unwrap().contains_key(flow_name)
+    }
+
+    pub fn add_breakpoint(
+        &self,
+        flow_name: &str,
+        breakpoint: Breakpoint,
+    ) -> Result<(), AgentError> {
+        let mut flow_debug = self.flow_debug.lock().unwrap();
+        let Some(state) = flow_debug.get_mut(flow_name) else {
+            return Err(AgentError::Other(format!(
+                "Flow {} is not in debug mode",
+                flow_name
+            )));
+        };
+        state.add_breakpoint(breakpoint);
+        Ok(())
+    }
+
+    pub fn remove_breakpoint(&self, flow_name: &str, breakpoint: &Breakpoint) {
+        let mut flow_debug = self.flow_debug.lock().unwrap();
+        if let Some(state) = flow_debug.get_mut(flow_name) {
+            state.remove_breakpoint(breakpoint);
+        }
+    }
+
+    pub fn get_breakpoints(&self, flow_name: &str) -> Vec<Breakpoint> {
+        let flow_debug = self.flow_debug.lock().unwrap();
+        flow_debug
+            .get(flow_name)
+            .map(|state| state.breakpoints().clone())
+            .unwrap_or_default()
+    }
+
+    pub fn is_flow_paused(&self, flow_name: &str) -> bool {
+        let flow_debug = self.flow_debug.lock().unwrap();
+        flow_debug
+            .get(flow_name)
+            .is_some_and(|state| state.is_paused())
+    }
+
+    /// Inputs held back while the flow is paused, in delivery order.
+    pub fn get_pending_inputs(&self, flow_name: &str) -> Vec<PendingInput> {
+        let flow_debug = self.flow_debug.lock().unwrap();
+        flow_debug
+            .get(flow_name)
+            .map(|state| state.pending())
+            .unwrap_or_default()
+    }
+
+    /// Delivers the next pending input. The flow stays paused.
+    pub async fn step_flow(&self, flow_name: &str) -> Result<(), AgentError> {
+        debug::step(self, flow_name).await
+    }
+
+    /// Delivers all the pending inputs and resumes the flow.
+    pub async fn continue_flow(&self, flow_name: &str) -> Result<(), AgentError> {
+        debug::resume(self, flow_name).await
+    }
+
+    // Flow stats
+
+    /// Sets how often `ASKitEvent::FlowStats` is emitted for the running flows. The events
+    /// are off by default and with `None`.
+    pub fn set_flow_stats_interval(&self, interval: Option<Duration>) {
+        *self.flow_stats_interval.lock().unwrap() = interval;
+    }
+
+    /// Sets how long a full agent queue may block an edge before a SlowConsumer event.
+    /// The detection is off by default and with None.
+    pub fn set_slow_consumer_threshold(&self, threshold: Option<Duration>) {
+        *self.slow_consumer_threshold.lock().unwrap() = threshold;
+    }
+
+    // Edge probes
+
+    /// Starts recording the last `capacity` payloads passing through the edge.
+    pub fn add_edge_probe(
+        &self,
+        flow_name: &str,
+        edge_id: &str,
+        capacity: usize,
+    ) -> Result<(), AgentError> {
+        let edge = {
+            let flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get(flow_name) else {
+                return Err(AgentError::FlowNotFound(flow_name.to_string()));
+            };
+            let Some(edge) = flow.edges().iter().find(|edge| edge.id == edge_id) else {
+                return Err(AgentError::EdgeNotFound(edge_id.to_string()));
+            };
+            edge.clone()
+        };
+        let mut edge_probes = self.edge_probes.lock().unwrap();
+        edge_probes.insert(edge_id.to_string(), EdgeProbe::new(edge, capacity));
+        Ok(())
+    }
+
+    pub fn remove_edge_probe(&self, edge_id: &str) {
+        let mut edge_probes = self.edge_probes.lock().unwrap();
+        edge_probes.remove(edge_id);
+    }
+
+    /// Payloads recorded by the probe, oldest first.
+    pub fn get_edge_probe_records(&self, edge_id: &str) -> Option<Vec<ProbeRecord>> {
+        let edge_probes = self.edge_probes.lock().unwrap();
+        edge_probes.get(edge_id).map(|probe| probe.records())
+    }
+
+    pub fn clear_edge_probe(&self, edge_id: &str) {
+        let mut edge_probes = self.edge_probes.lock().unwrap();
+        if let Some(probe) = edge_probes.get_mut(edge_id) {
+            probe.clear();
+        }
+    }
+
+    // Clock
+
+    pub fn clock(&self) -> AgentClock {
+        self.clock.lock().unwrap().clone()
+    }
+
+    /// Replaces the clock used by the time-based agents.
+    ///
+    /// Set it before starting the flows. Running timers keep the clock they started with.
+    pub fn set_clock(&self, clock: AgentClock) {
+        *self.clock.lock().unwrap() = clock;
+    }
+
+    // Profiling
+
+    /// Starts measuring process durations and queue latencies of the nodes in the flow.
+    ///
+    /// Any previous measurements of the flow are discarded.
+    pub fn start_flow_profiling(&self, flow_name: &str) -> Result<(), AgentError> {
+        if !self.flows.lock().unwrap().contains_key(flow_name) {
+            return Err(AgentError::FlowNotFound(flow_name.to_string()));
+        }
+        let mut flow_profiles = self.flow_profiles.lock().unwrap();
+        flow_profiles.insert(flow_name.to_string(), FlowProfile::new());
+        Ok(())
+    }
+
+    /// Stops profiling the flow and returns the final report.
+    pub fn stop_flow_profiling(&self, flow_name: &str) -> Option<FlowProfileReport> {
+        let mut flow_profiles = self.flow_profiles.lock().unwrap();
+        flow_profiles
+            .remove(flow_name)
+            .map(|profile| profile.report(flow_name))
+    }
+
+    /// Report of the measurements so far, while the flow is being profiled.
+    pub fn get_flow_profile_report(&self, flow_name: &str) -> Option<FlowProfileReport> {
+        let flow_profiles = self.flow_profiles.lock().unwrap();
+        flow_profiles
+            .get(flow_name)
+            .map(|profile| profile.report(flow_name))
+    }
+
+    // Delivery
+
+    pub fn get_delivery_policy(&self, flow_name: &str) -> Option<DeliveryPolicy> {
+        let flow_deliveries = self.flow_deliveries.lock().unwrap();
+        flow_deliveries
+            .get(flow_name)
+            .map(|state| state.policy().clone())
+    }
+
+    /// Enables the acked delivery mode of the flow.
+    ///
+    /// Inputs retained under the previous policy are dropped.
+    pub fn set_delivery_policy(
+        &self,
+        flow_name: &str,
+        policy: DeliveryPolicy,
+    ) -> Result<(), AgentError> {
+        if !self.flows.lock().unwrap().contains_key(flow_name) {
+            return Err(AgentError::FlowNotFound(flow_name.to_string()));
+        }
+        let mut flow_deliveries = self.flow_deliveries.lock().unwrap();
+        flow_deliveries.insert(flow_name.to_string(), FlowDeliveryState::new(policy));
+        Ok(())
+    }
+
+    pub fn remove_delivery_policy(&self, flow_name: &str) {
+        let mut flow_deliveries = self.flow_deliveries.lock().unwrap();
+        flow_deliveries.remove(flow_name);
+    }
+
+    /// Inputs of the flow that have not been processed successfully yet.
+    pub fn get_unacked_deliveries(&self, flow_name: &str) -> Vec<UnackedDelivery> {
+        let flow_deliveries = self.flow_deliveries.lock().unwrap();
+        flow_deliveries
+            .get(flow_name)
+            .map(|state| state.unacked())
+            .unwrap_or_default()
+    }
+
+    // Quotas
+
+    pub fn get_flow_quota(&self, flow_name: &str) -> Option<FlowQuota> {
+        let flow_quotas = self.flow_quotas.lock().unwrap();
+        flow_quotas
+            .get(flow_name)
+            .map(|state| state.quota().clone())
+    }
+
+    /// Sets the quota of the flow, resetting its current usage. Inputs held back by the
+    /// previous quota stay in order.
+    pub fn set_flow_quota(&self, flow_name: &str, quota: FlowQuota) -> Result<(), AgentError> {
+        if !self.flows.lock().unwrap().contains_key(flow_name) {
+            return Err(AgentError::FlowNotFound(flow_name.to_string()));
+        }
+        let mut flow_quotas = self.flow_quotas.lock().unwrap();
+        match flow_quotas.get_mut(flow_name) {
+            Some(state) => state.replace(quota),
+            None => {
+                flow_quotas.insert(flow_name.to_string(), FlowQuotaState::new(quota));
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the quota of the flow. Inputs held back by it are delivered.
+    pub fn remove_flow_quota(&self, flow_name: &str) {
+        let state = self.flow_quotas.lock().unwrap().remove(flow_name);
+        if let Some(state) = state {
+            quota::release_held(self, flow_name, state);
+        }
+    }
+
+    /// Reserves an LLM call slot for the flow.
+    ///
+    /// The slot is held until the returned permit is dropped.
+    /// Returns `None` when the flow has no limit on concurrent LLM calls.
+    pub async fn acquire_llm_call(
+        &self,
+        flow_name: &str,
+    ) -> Result<Option<OwnedSemaphorePermit>, AgentError> {
+        quota::acquire_llm_call(self, flow_name).await
+    }
+
+    // Usage
+
+    pub fn set_model_pricing(&self, provider: &str, model: &str, pricing: ModelPricing) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.set_pricing(provider, model, pricing);
+    }
+
+    pub fn get_usage_budget(&self, flow_name: &str) -> Option<UsageBudget> {
+        let usage = self.usage.lock().unwrap();
+        usage.budget(flow_name).cloned()
+    }
+
+    pub fn set_usage_budget(&self, flow_name: &str, budget: UsageBudget) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.set_budget(flow_name, budget);
+    }
+
+    pub fn remove_usage_budget(&self, flow_name: &str) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.remove_budget(flow_name);
+    }
+
+    /// Usage of the flow today (UTC).
+    pub fn get_flow_usage(&self, flow_name: &str) -> UsageTotals {
+        let usage = self.usage.lock().unwrap();
+        usage.flow_totals(&usage_day(), flow_name)
+    }
+
+    /// Usage of the agent today (UTC).
+    pub fn get_agent_usage(&self, agent_id: &str) -> UsageTotals {
+        let usage = self.usage.lock().unwrap();
+        usage.agent_totals(&usage_day(), agent_id)
+    }
+
+    /// Usage of each agent today (UTC). Past days are dropped when the day changes.
+    pub fn get_usage_records(&self) -> Vec<UsageRecord> {
+        let usage = self.usage.lock().unwrap();
+        usage.records()
+    }
+
+    pub(crate) fn record_usage(&self, flow_name: &str, agent_id: &str, agent_usage: AgentUsage) {
+        let exceeded = {
+            let mut usage = self.usage.lock().unwrap();
+            usage.record(&usage_day(), flow_name, agent_id, &agent_usage)
+        };
+        self.notify_observers(ASKitEvent::AgentUsage(agent_id.to_string(), agent_usage));
+
+        let Some((totals, action)) = exceeded else {
+            return;
+        };
+        log::warn!("Flow {} exceeded its usage budget", flow_name);
+        self.notify_observers(ASKitEvent::BudgetExceeded(flow_name.to_string(), totals));
+        if action == BudgetAction::PauseFlow {
+            self.pause_agent_flow(flow_name).unwrap_or_else(|e| {
+                log::error!("Failed to pause agent flow {}: {}", flow_name, e);
+            });
+        }
+    }
+
     pub(crate) async fn agent_input(
         &self,
         agent_id: String,
@@ -722,9 +2322,9 @@ impl ASKit {
             a.clone()
         };
 
-        let agent_status = {
+        let (agent_status, flow_name) = {
             let agent = agent.lock().await;
-            agent.status().clone()
+            (agent.status().clone(), agent.flow_name().to_string())
         };
         if agent_status != AgentStatus::Start {
             return Ok(());
@@ -737,10 +2337,90 @@ impl ASKit {
             return Ok(());
         }
 
+        let Some(data) = request::resolve(self, &agent_id, &ctx, data) else {
+            return Ok(());
+        };
+
+        let Some((ctx, data)) = pause::buffer_input(self, &flow_name, &agent_id, &pin, ctx, data)
+        else {
+            return Ok(());
+        };
+
+        let Some((ctx, data)) = debug::hold_input(self, &flow_name, &agent_id, &pin, ctx, data)
+        else {
+            return Ok(());
+        };
+
+        self.deliver_input(agent_id, ctx, pin, data).await
+    }
+
+    // Queues the input for the agent, bypassing the debug mode.
+    pub(crate) async fn deliver_input(
+        &self,
+        agent_id: String,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.queue_input(agent_id, ctx, pin, data, None).await
+    }
+
+    // Queues the input, keeping the delivery id of a redelivered input.
+    pub(crate) async fn queue_input(
+        &self,
+        agent_id: String,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+        delivery_id: Option<u64>,
+    ) -> Result<(), AgentError> {
+        let agent = {
+            let agents = self.agents.lock().unwrap();
+            let Some(a) = agents.get(&agent_id) else {
+                return Err(AgentError::AgentNotFound(agent_id.to_string()));
+            };
+            a.clone()
+        };
+        let flow_name = agent.lock().await.flow_name().to_string();
+
+        if ctx.is_expired() {
+            log::info!("Dropped input to {}:{} past its deadline", agent_id, pin);
+            delivery::complete(self, &flow_name, delivery_id, &Ok(()));
+            return Ok(());
+        }
+
+        let Some((ctx, data)) =
+            quota::admit_input(self, &flow_name, &agent_id, &pin, ctx, data, delivery_id)?
+        else {
+            return Ok(());
+        };
+
+        self.push_input(agent_id, &flow_name, ctx, pin, data, delivery_id)
+            .await
+    }
+
+    // Sends the input admitted by the quota to the agent.
+    pub(crate) async fn push_input(
+        &self,
+        agent_id: String,
+        flow_name: &str,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+        delivery_id: Option<u64>,
+    ) -> Result<(), AgentError> {
+        let delivery_id =
+            delivery_id.or_else(|| delivery::retain(self, flow_name, &agent_id, &pin, &ctx, &data));
+        let edge = ctx
+            .sequence()
+            .map(|(stream, _)| stream.to_string())
+            .unwrap_or_else(|| format!("{}:{}", agent_id, pin));
         let message = AgentMessage::Input {
             ctx,
             pin: pin.clone(),
             data,
+            queued_at: Instant::now(),
+            delivery_id,
         };
 
         let tx = {
@@ -757,9 +2437,7 @@ impl ASKit {
                 })?;
             }
             AgentMessageSender::Async(tx) => {
-                tx.send(message).await.map_err(|_| {
-                    AgentError::SendMessageFailed("Failed to send input message".to_string())
-                })?;
+                stats::send_input(self, &tx, message, flow_name, &edge).await?;
             }
         }
         self.emit_agent_input(agent_id.to_string(), pin);
@@ -767,6 +2445,70 @@ impl ASKit {
         Ok(())
     }
 
+    /// Pushes data into an input port of a node, as if it came through an edge.
+    pub async fn inject(
+        &self,
+        flow_name: &str,
+        node_id: &str,
+        port: &str,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let def_name = {
+            let flows = self.flows.lock().unwrap();
+            let Some(flow) = flows.get(flow_name) else {
+                return Err(AgentError::FlowNotFound(flow_name.to_string()));
+            };
+            let Some(node) = flow.nodes().iter().find(|node| node.id == node_id) else {
+                return Err(AgentError::AgentNotFound(node_id.to_string()));
+            };
+            node.def_name.clone()
+        };
+
+        if !port.starts_with("config:")
+            && let Some(def) = self.get_agent_definition(&def_name)
+        {
+            if let Some(inputs) = &def.inputs
+                && !inputs.iter().any(|input| input == port)
+            {
+                return Err(AgentError::PinNotFound(format!(
+                    "{} (inputs of {}: {})",
+                    port,
+                    def_name,
+                    inputs.join(", ")
+                )));
+            }
+            def.check_input(port, &data)?;
+        }
+
+        self.agent_input(
+            node_id.to_string(),
+            AgentContext::new(),
+            port.to_string(),
+            data,
+        )
+        .await
+    }
+
+    /// Runs the flow test on a copy of its flow and reports the outputs that differ.
+    ///
+    /// ASKit must be ready. The clock is simulated during the test unless it already is.
+    pub async fn run_flow_test(&self, test: &FlowTest) -> Result<FlowTestReport, AgentError> {
+        testing::run(self, test).await
+    }
+
+    /// Submits a value entered in the display widget of an interactive agent, such as a form.
+    ///
+    /// The agent receives the value on the reserved [`SUBMIT_PIN`] port.
+    pub async fn submit_input(&self, agent_id: &str, value: AgentValue) -> Result<(), AgentError> {
+        self.agent_input(
+            agent_id.to_string(),
+            AgentContext::new(),
+            SUBMIT_PIN.to_string(),
+            AgentData::from_value(value),
+        )
+        .await
+    }
+
     pub async fn send_agent_out(
         &self,
         agent_id: String,
@@ -777,6 +2519,18 @@ impl ASKit {
         message::send_agent_out(self, agent_id, ctx, pin, data).await
     }
 
+    /// Outputs a request from the agent and waits for the response. See `AgentOutput::request`.
+    pub async fn request(
+        &self,
+        agent_id: String,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+        timeout: Duration,
+    ) -> Result<AgentData, AgentError> {
+        request::request(self, agent_id, ctx, pin, data, timeout).await
+    }
+
     pub fn try_send_agent_out(
         &self,
         agent_id: String,
@@ -787,10 +2541,58 @@ impl ASKit {
         message::try_send_agent_out(self, agent_id, ctx, pin, data)
     }
 
+    pub fn try_send_agent_out_many(
+        &self,
+        agent_id: String,
+        ctx: AgentContext,
+        pin: String,
+        data: Vec<AgentData>,
+    ) -> Result<(), AgentError> {
+        message::try_send_agent_out_many(self, agent_id, ctx, pin, data)
+    }
+
     pub fn write_board_data(&self, name: String, data: AgentData) -> Result<(), AgentError> {
+        board::write(self, &name, data.clone());
         self.try_send_board_out(name, AgentContext::new(), data)
     }
 
+    /// Current value of the board.
+    pub fn get_board_data(&self, name: &str) -> Option<AgentData> {
+        self.board_data.lock().unwrap().get(name).cloned()
+    }
+
+    /// Keeps the last `limit` values written to the board. None stops keeping them.
+    pub fn set_board_history_limit(&self, name: &str, limit: Option<usize>) {
+        board::set_history_limit(self, name, limit);
+    }
+
+    /// Values kept for the board, oldest first.
+    pub fn get_board_history(&self, name: &str) -> Vec<AgentData> {
+        board::history(self, name)
+    }
+
+    /// Calls the observer with the previous and new values whenever a board matching
+    /// the name is written. The name may contain `*` wildcards.
+    pub fn subscribe_board(
+        &self,
+        name: &str,
+        observer: Box<dyn BoardObserver + Sync + Send>,
+    ) -> usize {
+        let subscriber_id = new_observer_id();
+        self.board_subscribers
+            .lock()
+            .unwrap()
+            .insert(subscriber_id, (name.to_string(), observer));
+        subscriber_id
+    }
+
+    pub fn unsubscribe_board(&self, subscriber_id: usize) {
+        self.board_subscribers
+            .lock()
+            .unwrap()
+            .remove(&subscriber_id);
+    }
+
     pub(crate) fn try_send_board_out(
         &self,
         name: String,
@@ -823,6 +2625,14 @@ impl ASKit {
                     } => {
                         message::agent_out(&askit, agent, ctx, pin, data).await;
                     }
+                    AgentOutMany {
+                        agent,
+                        ctx,
+                        pin,
+                        data,
+                    } => {
+                        message::agent_out_many(&askit, agent, ctx, pin, data).await;
+                    }
                     BoardOut { name, ctx, data } => {
                         message::board_out(&askit, name, ctx, data).await;
                     }
@@ -875,6 +2685,58 @@ impl ASKit {
         self.notify_observers(ASKitEvent::Board(name, data));
     }
 
+    pub(crate) fn emit_quota_exceeded(&self, flow_name: String, violation: QuotaViolation) {
+        self.notify_observers(ASKitEvent::QuotaExceeded(flow_name, violation));
+    }
+
+    pub(crate) fn emit_flow_paused(&self, flow_name: String, input: PendingInput) {
+        self.notify_observers(ASKitEvent::FlowPaused(flow_name, input));
+    }
+
+    pub(crate) fn emit_flow_resumed(&self, flow_name: String) {
+        self.notify_observers(ASKitEvent::FlowResumed(flow_name));
+    }
+
+    pub(crate) fn emit_flow_stats(
+        &self,
+        flow_name: String,
+        msgs_per_sec: f64,
+        error_rate: f64,
+        active_agents: usize,
+    ) {
+        self.notify_observers(ASKitEvent::FlowStats(
+            flow_name,
+            msgs_per_sec,
+            error_rate,
+            active_agents,
+        ));
+    }
+
+    pub(crate) fn emit_slow_consumer(&self, flow_name: String, edge: String) {
+        self.notify_observers(ASKitEvent::SlowConsumer(flow_name, edge));
+    }
+
+    pub(crate) fn emit_flow_reloaded(&self, path: String, flow_name: String) {
+        self.notify_observers(ASKitEvent::FlowReloaded(path, flow_name));
+    }
+
+    pub(crate) fn emit_flow_reload_failed(&self, path: String, message: String) {
+        self.notify_observers(ASKitEvent::FlowReloadFailed(path, message));
+    }
+
+    pub(crate) fn emit_flow_changed(&self, flow_name: String) {
+        self.notify_observers(ASKitEvent::FlowChanged(flow_name));
+    }
+
+    pub(crate) fn emit_definitions_changed(&self, def_name: String) {
+        self.notify_observers(ASKitEvent::DefinitionsChanged(def_name));
+    }
+
+    /// Raises a user-facing alert. Agents use `AgentOutput::emit_notification`.
+    pub fn emit_notification(&self, notification: Notification) {
+        self.notify_observers(ASKitEvent::Notification(notification));
+    }
+
     fn notify_observers(&self, event: ASKitEvent) {
         let observers = self.observers.lock().unwrap();
         for (_id, observer) in observers.iter() {
@@ -889,6 +2751,18 @@ pub enum ASKitEvent {
     AgentError(String, String),              // (agent_id, message)
     AgentIn(String, String),                 // (agent_id, pin)
     Board(String, AgentData),                // (board name, data)
+    QuotaExceeded(String, QuotaViolation),   // (flow name, violation)
+    AgentUsage(String, AgentUsage),          // (agent_id, usage)
+    BudgetExceeded(String, UsageTotals),     // (flow name, today's totals)
+    FlowPaused(String, PendingInput),        // (flow name, input at the breakpoint)
+    FlowResumed(String),                     // (flow name)
+    FlowStats(String, f64, f64, usize),      // (flow name, msgs/sec, error rate, active agents)
+    SlowConsumer(String, String),            // (flow name, edge)
+    Notification(Notification),              // (notification)
+    FlowReloaded(String, String),            // (flow file, flow name)
+    FlowReloadFailed(String, String),        // (flow file, message)
+    DefinitionsChanged(String),              // (definition name registered or unregistered)
+    FlowChanged(String),                     // (flow name after a batch of edits)
 }
 
 pub trait ASKitObserver {
@@ -908,3 +2782,191 @@ pub enum AgentMessageSender {
     Sync(std::sync::mpsc::Sender<AgentMessage>),
     Async(mpsc::Sender<AgentMessage>),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret::MapSecretProvider;
+
+    struct ChangedObserver(Arc<Mutex<Vec<String>>>);
+
+    impl ASKitObserver for ChangedObserver {
+        fn notify(&self, event: &ASKitEvent) {
+            if let ASKitEvent::FlowChanged(flow_name) = event {
+                self.0.lock().unwrap().push(flow_name.clone());
+            }
+        }
+    }
+
+    fn node(askit: &ASKit, id: &str) -> AgentFlowNode {
+        let mut node = askit.new_agent_flow_node("core_board_in").unwrap();
+        node.id = id.into();
+        node
+    }
+
+    fn edge(source: &str, target: &str) -> AgentFlowEdge {
+        AgentFlowEdge {
+            id: format!("{}-{}", source, target),
+            source: source.into(),
+            source_handle: "*".into(),
+            target: target.into(),
+            target_handle: "*".into(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch_edits() {
+        let askit = ASKit::init().unwrap();
+        askit.ready().await.unwrap();
+        let changed = Arc::new(Mutex::new(Vec::new()));
+        askit.subscribe(Box::new(ChangedObserver(changed.clone())));
+        askit
+            .add_agent_flow(&AgentFlow::new("flow".into()))
+            .unwrap();
+
+        // a duplicated id rejects the whole batch
+        let nodes = vec![node(&askit, "a"), node(&askit, "a")];
+        assert!(askit.add_agent_flow_nodes("flow", nodes).is_err());
+        assert!(askit.agents.lock().unwrap().is_empty());
+
+        let nodes = vec![node(&askit, "a"), node(&askit, "b"), node(&askit, "c")];
+        askit.add_agent_flow_nodes("flow", nodes).unwrap();
+        assert_eq!(askit.agents.lock().unwrap().len(), 3);
+
+        let edges = vec![edge("a", "b"), edge("x", "c")];
+        assert!(askit.add_agent_flow_edges("flow", edges).is_err());
+        let edges = vec![edge("a", "b"), edge("b", "c")];
+        askit.add_agent_flow_edges("flow", edges).unwrap();
+        let flow = askit.get_agent_flows().remove("flow").unwrap();
+        assert_eq!(flow.edges().len(), 2);
+
+        let missing = ["a".to_string(), "x".to_string()];
+        assert!(askit.remove_many("flow", &missing, &[]).await.is_err());
+        askit
+            .remove_many("flow", &["a".into()], &["a-b".into()])
+            .await
+            .unwrap();
+        let flow = askit.get_agent_flows().remove("flow").unwrap();
+        assert_eq!(flow.nodes().len(), 2);
+        assert_eq!(flow.edges().len(), 1);
+        assert!(!askit.edges.lock().unwrap().contains_key("a"));
+
+        assert_eq!(*changed.lock().unwrap(), ["flow", "flow", "flow"]);
+    }
+
+    #[test]
+    fn test_env_var() {
+        let askit = ASKit::new();
+        askit.register_agent(AgentDefinition::new("test", "plain", None));
+        askit.register_agent(
+            AgentDefinition::new("test", "env", None).capabilities(vec![AgentCapability::Env]),
+        );
+
+        assert!(matches!(
+            askit.env_var("plain", "PATH"),
+            Err(AgentError::PermissionDenied(_, _))
+        ));
+        assert!(askit.env_var("env", "PATH").unwrap().is_some());
+        assert!(askit.host_env_var("PATH").is_some());
+
+        askit.set_capability_policy(AgentCapabilityPolicy::allow_all().deny(AgentCapability::Env));
+        assert!(askit.env_var("env", "PATH").is_err());
+        assert!(askit.host_env_var("PATH").is_none());
+    }
+
+    #[test]
+    fn test_check_capability() {
+        let askit = ASKit::new();
+        askit.register_agent(
+            AgentDefinition::new("test", "net", None).capabilities(vec![AgentCapability::Network]),
+        );
+        let denied = |res: Result<(), AgentError>| matches!(res, Err(AgentError::PermissionDenied(name, _)) if name == "net");
+
+        askit
+            .check_capability("net", AgentCapability::Network)
+            .unwrap();
+        // not declared by the definition
+        assert!(denied(askit.check_capability("net", AgentCapability::Exec)));
+        assert!(matches!(
+            askit.check_capability("missing", AgentCapability::Network),
+            Err(AgentError::UnknownDefName(_))
+        ));
+
+        // forbidden by the policy
+        askit.set_capability_policy(AgentCapabilityPolicy::deny_all());
+        assert!(denied(
+            askit.check_capability("net", AgentCapability::Network)
+        ));
+        askit.set_capability_policy(
+            AgentCapabilityPolicy::deny_all().allow(AgentCapability::Network),
+        );
+        askit
+            .check_capability("net", AgentCapability::Network)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_get_secret() {
+        let askit = ASKit::new();
+        assert_eq!(askit.get_secret("API_KEY").unwrap(), None);
+
+        askit.set_secret_provider(MapSecretProvider::new().with("API_KEY", "key"));
+        assert_eq!(askit.get_secret("API_KEY").unwrap().as_deref(), Some("key"));
+        assert_eq!(askit.get_secret("OTHER").unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_budget_pauses_flow() {
+        let askit = ASKit::init().unwrap();
+        askit.ready().await.unwrap();
+        let mut flow = AgentFlow::new("flow".into());
+        let mut a = node(&askit, "a");
+        a.enabled = true;
+        flow.add_node(a);
+        askit.add_agent_flow(&flow).unwrap();
+        askit.start_agent_flow("flow").await.unwrap();
+        assert!(askit.is_agent_running("a").await);
+        askit.set_usage_budget(
+            "flow",
+            UsageBudget::new()
+                .max_tokens_per_day(100)
+                .action(BudgetAction::PauseFlow),
+        );
+
+        askit.record_usage("flow", "a", AgentUsage::new("openai", "gpt", 80, 40));
+
+        // paused with its agents still running, so that it can be resumed
+        assert!(askit.is_agent_flow_paused("flow"));
+        assert!(askit.is_agent_running("a").await);
+
+        askit.set_usage_budget("flow", UsageBudget::new().max_tokens_per_day(1000));
+        askit.resume_agent_flow("flow").await.unwrap();
+        assert!(!askit.is_agent_flow_paused("flow"));
+        assert!(askit.is_agent_running("a").await);
+    }
+
+    #[tokio::test]
+    async fn test_new_node() {
+        let askit = ASKit::init().unwrap();
+        askit.ready().await.unwrap();
+        askit
+            .add_agent_flow(&AgentFlow::new("flow".into()))
+            .unwrap();
+
+        let mut configs = AgentConfigs::new();
+        configs.set("$board".into(), AgentValue::string("value"));
+        let a = askit
+            .new_node("flow", "core_board_in", Some(configs))
+            .unwrap();
+        let b = askit.new_node("flow", "core_board_in", None).unwrap();
+        assert_ne!(a.id, b.id);
+        assert_eq!(a.configs.unwrap().get_string("$board").unwrap(), "value");
+        assert!(b.configs.unwrap().contains_key("$board"));
+        assert_eq!(askit.agents.lock().unwrap().len(), 2);
+
+        assert!(askit.new_node("missing", "core_board_in", None).is_err());
+        assert!(askit.new_node("flow", "missing", None).is_err());
+        assert_eq!(askit.agents.lock().unwrap().len(), 2);
+    }
+}
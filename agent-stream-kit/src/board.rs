@@ -0,0 +1,161 @@
+use std::collections::{HashMap, VecDeque};
+
+use super::askit::ASKit;
+use super::board_agent::board_name_matches;
+use super::data::AgentData;
+
+/// Change of a board value delivered to the subscribers of the board.
+#[derive(Debug, Clone)]
+pub struct BoardUpdate {
+    pub name: String,
+    pub previous: Option<AgentData>,
+    pub data: AgentData,
+}
+
+pub trait BoardObserver {
+    fn notify(&self, update: &BoardUpdate);
+}
+
+// subscriber id -> (board name pattern, observer)
+pub(crate) type BoardSubscribers = HashMap<usize, (String, Box<dyn BoardObserver + Sync + Send>)>;
+
+// Last values of a board, oldest first
+pub(crate) struct BoardHistory {
+    limit: usize,
+    values: VecDeque<AgentData>,
+}
+
+impl BoardHistory {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            values: VecDeque::new(),
+        }
+    }
+
+    fn push(&mut self, data: AgentData) {
+        if self.limit == 0 {
+            return;
+        }
+        while self.values.len() >= self.limit {
+            self.values.pop_front();
+        }
+        self.values.push_back(data);
+    }
+
+    fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        while self.values.len() > limit {
+            self.values.pop_front();
+        }
+    }
+}
+
+// Stores the value of the board, records it in the history of the board if it has one,
+// and notifies the subscribers whose pattern matches the board name.
+pub(crate) fn write(askit: &ASKit, name: &str, data: AgentData) {
+    let previous = askit
+        .board_data
+        .lock()
+        .unwrap()
+        .insert(name.to_string(), data.clone());
+
+    if let Some(history) = askit.board_histories.lock().unwrap().get_mut(name) {
+        history.push(data.clone());
+    }
+
+    let subscribers = askit.board_subscribers.lock().unwrap();
+    if subscribers.is_empty() {
+        return;
+    }
+    let update = BoardUpdate {
+        name: name.to_string(),
+        previous,
+        data,
+    };
+    for (pattern, observer) in subscribers.values() {
+        if board_name_matches(pattern, name) {
+            observer.notify(&update);
+        }
+    }
+}
+
+// Keeps the last `limit` values of the board. None stops keeping them.
+pub(crate) fn set_history_limit(askit: &ASKit, name: &str, limit: Option<usize>) {
+    let mut histories = askit.board_histories.lock().unwrap();
+    match limit {
+        Some(limit) => histories
+            .entry(name.to_string())
+            .or_insert_with(|| BoardHistory::new(limit))
+            .set_limit(limit),
+        None => {
+            histories.remove(name);
+        }
+    }
+}
+
+pub(crate) fn history(askit: &ASKit, name: &str) -> Vec<AgentData> {
+    askit
+        .board_histories
+        .lock()
+        .unwrap()
+        .get(name)
+        .map(|history| history.values.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    struct UpdateRecorder(Arc<Mutex<Vec<BoardUpdate>>>);
+
+    impl BoardObserver for UpdateRecorder {
+        fn notify(&self, update: &BoardUpdate) {
+            self.0.lock().unwrap().push(update.clone());
+        }
+    }
+
+    #[test]
+    fn test_board_history() {
+        let askit = ASKit::new();
+        set_history_limit(&askit, "temp", Some(2));
+        for n in 1..=3 {
+            write(&askit, "temp", AgentData::integer(n));
+        }
+        let values = history(&askit, "temp")
+            .iter()
+            .map(|data| data.as_i64().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![2, 3]);
+
+        set_history_limit(&askit, "temp", Some(1));
+        assert_eq!(history(&askit, "temp").len(), 1);
+        set_history_limit(&askit, "temp", None);
+        assert!(history(&askit, "temp").is_empty());
+    }
+
+    #[test]
+    fn test_subscribe_board() {
+        let askit = ASKit::new();
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let id = askit.subscribe_board("sensor/*", Box::new(UpdateRecorder(updates.clone())));
+
+        write(&askit, "sensor/temp", AgentData::integer(1));
+        write(&askit, "sensor/temp", AgentData::integer(2));
+        write(&askit, "other", AgentData::integer(3));
+        {
+            let updates = updates.lock().unwrap();
+            assert_eq!(updates.len(), 2);
+            assert!(updates[0].previous.is_none());
+            assert_eq!(updates[1].previous.as_ref().unwrap().as_i64(), Some(1));
+            assert_eq!(updates[1].data.as_i64(), Some(2));
+        }
+
+        askit.unsubscribe_board(id);
+        write(&askit, "sensor/temp", AgentData::integer(4));
+        assert_eq!(updates.lock().unwrap().len(), 2);
+    }
+}
@@ -3,6 +3,7 @@ use std::vec;
 
 use super::agent::{Agent, AsAgent, AsAgentData, new_agent_boxed};
 use super::askit::ASKit;
+use super::board;
 use super::config::AgentConfigs;
 use super::context::AgentContext;
 use super::data::AgentData;
@@ -70,10 +71,7 @@ impl AsAgent for BoardInAgent {
             board_name = pin.clone();
         }
         let askit = self.askit();
-        {
-            let mut board_data = askit.board_data.lock().unwrap();
-            board_data.insert(board_name.clone(), data.clone());
-        }
+        board::write(askit, &board_name, data.clone());
         askit.try_send_board_out(board_name.clone(), ctx, data.clone())?;
 
         Ok(())
@@ -161,6 +159,26 @@ impl AsAgent for BoardOutAgent {
     }
 }
 
+// Whether the board name matches the name a Board Out agent subscribes to.
+// "*" in the pattern matches any sequence of characters, e.g. "sensor/*".
+pub(crate) fn board_name_matches(pattern: &str, name: &str) -> bool {
+    if !pattern.contains('*') {
+        return pattern == name;
+    }
+    let mut parts = pattern.split('*').collect::<Vec<_>>();
+    let last = parts.pop().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(parts.remove(0)) else {
+        return false;
+    };
+    for part in parts {
+        let Some(i) = rest.find(part) else {
+            return false;
+        };
+        rest = &rest[i + part.len()..];
+    }
+    rest.ends_with(last)
+}
+
 static CONFIG_BOARD_NAME: &str = "$board";
 
 pub fn register_agents(askit: &ASKit) {
@@ -189,6 +207,29 @@ pub fn register_agents(askit: &ASKit) {
         .title("Board Out")
         .category("Core")
         .outputs(vec!["*"])
-        .string_config_with(CONFIG_BOARD_NAME, "", |entry| entry.title("Board Name")),
+        .string_config_with(CONFIG_BOARD_NAME, "", |entry| {
+            entry
+                .title("Board Name")
+                .description("* matches any characters, e.g. sensor/*")
+        }),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_board_name_matches() {
+        assert!(board_name_matches("sensor", "sensor"));
+        assert!(!board_name_matches("sensor", "sensor/temp"));
+        assert!(board_name_matches("sensor/*", "sensor/temp"));
+        assert!(board_name_matches("sensor/*", "sensor/"));
+        assert!(!board_name_matches("sensor/*", "sensors/temp"));
+        assert!(board_name_matches("*", "anything"));
+        assert!(board_name_matches("*/temp", "room/temp"));
+        assert!(board_name_matches("a*b*c", "a-b-b-c"));
+        assert!(!board_name_matches("a*bc", "abc-c"));
+        assert!(!board_name_matches("ab*ba", "aba"));
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// Side effects an agent may perform outside of the flow.
+///
+/// Agents declare the capabilities they need in their `AgentDefinition`,
+/// and hosts restrict them with an `AgentCapabilityPolicy`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentCapability {
+    /// Reading and writing files.
+    Filesystem,
+    /// Connecting to other hosts.
+    Network,
+    /// Running other programs.
+    Exec,
+    /// Reading environment variables, also through `${env:VAR}` in configs.
+    Env,
+}
+
+impl std::fmt::Display for AgentCapability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AgentCapability::Filesystem => "filesystem",
+            AgentCapability::Network => "network",
+            AgentCapability::Exec => "exec",
+            AgentCapability::Env => "env",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Host-side policy deciding which capabilities agents are allowed to use.
+///
+/// The default policy allows everything.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentCapabilityPolicy {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allowed: Option<BTreeSet<AgentCapability>>,
+}
+
+impl AgentCapabilityPolicy {
+    pub fn allow_all() -> Self {
+        Self { allowed: None }
+    }
+
+    pub fn deny_all() -> Self {
+        Self {
+            allowed: Some(BTreeSet::new()),
+        }
+    }
+
+    pub fn allow(mut self, capability: AgentCapability) -> Self {
+        if let Some(allowed) = self.allowed.as_mut() {
+            allowed.insert(capability);
+        }
+        self
+    }
+
+    pub fn deny(mut self, capability: AgentCapability) -> Self {
+        let allowed = self.allowed.get_or_insert_with(|| {
+            [
+                AgentCapability::Filesystem,
+                AgentCapability::Network,
+                AgentCapability::Exec,
+                AgentCapability::Env,
+            ]
+            .into()
+        });
+        allowed.remove(&capability);
+        self
+    }
+
+    pub fn is_allowed(&self, capability: AgentCapability) -> bool {
+        match &self.allowed {
+            Some(allowed) => allowed.contains(&capability),
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use tokio::sync::oneshot;
+
+/// Source of time for time-based agents.
+///
+/// The real clock sleeps in real time. A simulated clock only moves forward when
+/// [`AgentClock::advance`] is called, so tests and offline replays can fast-forward
+/// timers deterministically.
+#[derive(Clone, Default)]
+pub struct AgentClock {
+    simulated: Option<Arc<Mutex<SimulatedClock>>>,
+}
+
+struct SimulatedClock {
+    now: SystemTime,
+    // (deadline, id) -> waker of the sleeping task
+    timers: BTreeMap<(SystemTime, usize), oneshot::Sender<()>>,
+    next_id: usize,
+}
+
+impl AgentClock {
+    pub fn real() -> Self {
+        Self::default()
+    }
+
+    /// A clock stopped at `start` until it is advanced.
+    pub fn simulated(start: SystemTime) -> Self {
+        Self {
+            simulated: Some(Arc::new(Mutex::new(SimulatedClock {
+                now: start,
+                timers: BTreeMap::new(),
+                next_id: 0,
+            }))),
+        }
+    }
+
+    pub fn is_simulated(&self) -> bool {
+        self.simulated.is_some()
+    }
+
+    pub fn now(&self) -> SystemTime {
+        match &self.simulated {
+            Some(clock) => clock.lock().unwrap().now,
+            None => SystemTime::now(),
+        }
+    }
+
+    pub async fn sleep(&self, duration: Duration) {
+        if self.simulated.is_none() {
+            tokio::time::sleep(duration).await;
+            return;
+        }
+        self.sleep_until(self.now() + duration).await;
+    }
+
+    /// Sleeps until the clock reaches `deadline`. Returns immediately if it is already past.
+    pub async fn sleep_until(&self, deadline: SystemTime) {
+        let Some(clock) = &self.simulated else {
+            let duration = deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default();
+            tokio::time::sleep(duration).await;
+            return;
+        };
+        let rx = {
+            let mut clock = clock.lock().unwrap();
+            if deadline <= clock.now {
+                return;
+            }
+            let (tx, rx) = oneshot::channel();
+            clock.next_id += 1;
+            let id = clock.next_id;
+            clock.timers.insert((deadline, id), tx);
+            rx
+        };
+        // the sender is dropped when the clock is dropped
+        let _ = rx.await;
+    }
+
+    /// Moves a simulated clock forward, waking the sleeping tasks in deadline order.
+    ///
+    /// Woken tasks get a chance to run before the next timer fires, so timers they
+    /// set within the advanced period fire too. Does nothing for the real clock.
+    pub async fn advance(&self, duration: Duration) {
+        let Some(clock) = &self.simulated else {
+            return;
+        };
+        let target = clock.lock().unwrap().now + duration;
+        loop {
+            let timer = {
+                let mut clock = clock.lock().unwrap();
+                match clock.timers.first_key_value() {
+                    Some(((deadline, _), _)) if *deadline <= target => {
+                        let ((deadline, _), tx) = clock.timers.pop_first().unwrap();
+                        clock.now = deadline;
+                        Some(tx)
+                    }
+                    _ => {
+                        clock.now = target;
+                        None
+                    }
+                }
+            };
+            let Some(tx) = timer else {
+                break;
+            };
+            let _ = tx.send(());
+            for _ in 0..YIELDS_PER_TIMER {
+                tokio::task::yield_now().await;
+            }
+        }
+    }
+
+    /// Number of tasks sleeping on a simulated clock.
+    pub fn pending_timers(&self) -> usize {
+        self.simulated
+            .as_ref()
+            .map(|clock| clock.lock().unwrap().timers.len())
+            .unwrap_or(0)
+    }
+}
+
+const YIELDS_PER_TIMER: usize = 8;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_simulated_clock() {
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let clock = AgentClock::simulated(start);
+        let ticks = Arc::new(AtomicUsize::new(0));
+
+        let task = {
+            let clock = clock.clone();
+            let ticks = ticks.clone();
+            tokio::spawn(async move {
+                let mut deadline = clock.now();
+                loop {
+                    deadline += Duration::from_secs(10);
+                    clock.sleep_until(deadline).await;
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        };
+        tokio::task::yield_now().await;
+        assert_eq!(clock.pending_timers(), 1);
+
+        clock.advance(Duration::from_secs(5)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), 0);
+
+        clock.advance(Duration::from_secs(60)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), 6);
+        assert_eq!(clock.now(), start + Duration::from_secs(65));
+
+        task.abort();
+    }
+}
@@ -1,8 +1,10 @@
 use std::collections::{BTreeMap, HashMap};
 
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
-use crate::data::AgentValue;
+use crate::data::{AgentValue, AgentValueMap};
 use crate::error::AgentError;
 
 pub type AgentConfigsMap = HashMap<String, AgentConfigs>;
@@ -26,14 +28,31 @@ impl AgentConfigs {
     pub fn get(&self, key: &str) -> Result<&AgentValue, AgentError> {
         self.0
             .get(key)
-            .ok_or_else(|| AgentError::UnknownConfig(key.to_string()))
+            .ok_or_else(|| AgentError::ConfigMissing(key.to_string()))
+    }
+
+    // Fails with ConfigMissing or ConfigTypeMismatch.
+    fn get_as<'a, T>(
+        &'a self,
+        key: &str,
+        type_name: &str,
+        f: impl FnOnce(&'a AgentValue) -> Option<T>,
+    ) -> Result<T, AgentError> {
+        f(self.get(key)?)
+            .ok_or_else(|| AgentError::ConfigTypeMismatch(key.to_string(), type_name.to_string()))
+    }
+
+    /// Deserializes the config into `T`. A string config is parsed as JSON.
+    pub fn get_parsed<T: DeserializeOwned>(&self, key: &str) -> Result<T, AgentError> {
+        let parsed = match self.get(key)? {
+            AgentValue::String(s) => serde_json::from_str(s),
+            value => serde_json::from_value(value.to_json()),
+        };
+        parsed.map_err(|e| AgentError::InvalidConfig(format!("{}: {}", key, e)))
     }
 
     pub fn get_bool(&self, key: &str) -> Result<bool, AgentError> {
-        self.0
-            .get(key)
-            .and_then(|v| v.as_bool())
-            .ok_or_else(|| AgentError::UnknownConfig(key.to_string()))
+        self.get_as(key, "boolean", |v| v.as_bool())
     }
 
     pub fn get_bool_or(&self, key: &str, default: bool) -> bool {
@@ -45,10 +64,7 @@ impl AgentConfigs {
     }
 
     pub fn get_integer(&self, key: &str) -> Result<i64, AgentError> {
-        self.0
-            .get(key)
-            .and_then(|v| v.as_i64())
-            .ok_or_else(|| AgentError::UnknownConfig(key.to_string()))
+        self.get_as(key, "integer", |v| v.as_i64())
     }
 
     pub fn get_integer_or(&self, key: &str, default: i64) -> i64 {
@@ -60,10 +76,7 @@ impl AgentConfigs {
     }
 
     pub fn get_number(&self, key: &str) -> Result<f64, AgentError> {
-        self.0
-            .get(key)
-            .and_then(|v| v.as_f64())
-            .ok_or_else(|| AgentError::UnknownConfig(key.to_string()))
+        self.get_as(key, "number", |v| v.as_f64())
     }
 
     pub fn get_number_or(&self, key: &str, default: f64) -> f64 {
@@ -74,12 +87,28 @@ impl AgentConfigs {
         self.get_number(key).unwrap_or_default()
     }
 
+    pub fn get_datetime(&self, key: &str) -> Result<DateTime<Utc>, AgentError> {
+        self.get_as(key, "datetime", |v| v.as_datetime())
+    }
+
+    pub fn get_datetime_or(&self, key: &str, default: DateTime<Utc>) -> DateTime<Utc> {
+        self.get_datetime(key).unwrap_or(default)
+    }
+
+    pub fn get_duration(&self, key: &str) -> Result<TimeDelta, AgentError> {
+        self.get_as(key, "duration", |v| v.as_duration())
+    }
+
+    pub fn get_duration_or(&self, key: &str, default: TimeDelta) -> TimeDelta {
+        self.get_duration(key).unwrap_or(default)
+    }
+
+    pub fn get_duration_or_default(&self, key: &str) -> TimeDelta {
+        self.get_duration(key).unwrap_or_default()
+    }
+
     pub fn get_string(&self, key: &str) -> Result<String, AgentError> {
-        self.0
-            .get(key)
-            .and_then(|v| v.as_str())
-            .map(|v| v.to_string())
-            .ok_or_else(|| AgentError::UnknownConfig(key.to_string()))
+        self.get_as(key, "string", |v| v.as_str().map(|v| v.to_string()))
     }
 
     pub fn get_string_or(&self, key: &str, default: impl Into<String>) -> String {
@@ -99,10 +128,7 @@ impl AgentConfigs {
     }
 
     pub fn get_array(&self, key: &str) -> Result<&Vec<AgentValue>, AgentError> {
-        self.0
-            .get(key)
-            .and_then(|v| v.as_array())
-            .ok_or_else(|| AgentError::UnknownConfig(key.to_string()))
+        self.get_as(key, "array", |v| v.as_array())
     }
 
     pub fn get_array_or<'a>(
@@ -124,25 +150,22 @@ impl AgentConfigs {
             .unwrap_or_default()
     }
 
-    pub fn get_object(&self, key: &str) -> Result<&BTreeMap<String, AgentValue>, AgentError> {
-        self.0
-            .get(key)
-            .and_then(|v| v.as_object())
-            .ok_or_else(|| AgentError::UnknownConfig(key.to_string()))
+    pub fn get_object(&self, key: &str) -> Result<&AgentValueMap<String, AgentValue>, AgentError> {
+        self.get_as(key, "object", |v| v.as_object())
     }
 
     pub fn get_object_or<'a>(
         &'a self,
         key: &str,
-        default: &'a BTreeMap<String, AgentValue>,
-    ) -> &'a BTreeMap<String, AgentValue> {
+        default: &'a AgentValueMap<String, AgentValue>,
+    ) -> &'a AgentValueMap<String, AgentValue> {
         self.0
             .get(key)
             .and_then(|v| v.as_object())
             .unwrap_or(default)
     }
 
-    pub fn get_object_or_default(&self, key: &str) -> BTreeMap<String, AgentValue> {
+    pub fn get_object_or_default(&self, key: &str) -> AgentValueMap<String, AgentValue> {
         self.0
             .get(key)
             .and_then(|v| v.as_object())
@@ -168,3 +191,43 @@ impl<'a> IntoIterator for &'a AgentConfigs {
         self.0.iter()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Options {
+        temperature: f64,
+    }
+
+    #[test]
+    fn test_typed_getters() {
+        let mut configs = AgentConfigs::new();
+        configs.set("count".into(), AgentValue::integer(3));
+        configs.set(
+            "options".into(),
+            AgentValue::string(r#"{"temperature": 0.5}"#),
+        );
+
+        assert_eq!(configs.get_integer("count").unwrap(), 3);
+        assert!(matches!(
+            configs.get_integer("missing"),
+            Err(AgentError::ConfigMissing(key)) if key == "missing"
+        ));
+        assert!(matches!(
+            configs.get_string("count"),
+            Err(AgentError::ConfigTypeMismatch(key, type_)) if key == "count" && type_ == "string"
+        ));
+
+        let options: Options = configs.get_parsed("options").unwrap();
+        assert_eq!(options, Options { temperature: 0.5 });
+        assert!(configs.get_parsed::<Options>("count").is_err());
+
+        let mut object = AgentValueMap::new();
+        object.insert("temperature".to_string(), AgentValue::number(1.0));
+        configs.set("options".into(), AgentValue::object(object));
+        let options: Options = configs.get_parsed("options").unwrap();
+        assert_eq!(options.temperature, 1.0);
+    }
+}
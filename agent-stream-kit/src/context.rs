@@ -1,14 +1,17 @@
 use std::{
     collections::BTreeMap,
     sync::{
-        Arc,
+        Arc, Mutex,
         atomic::{AtomicUsize, Ordering},
     },
+    time::{Duration, SystemTime},
 };
 
 use serde::{Deserialize, Serialize};
 
-use super::data::AgentValue;
+use super::data::{AgentData, AgentValue};
+use super::saga::{Compensation, VAR_COMPENSATION_ERROR};
+use super::transaction::{AgentTransaction, TransactionSlot};
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct AgentContext {
@@ -16,6 +19,20 @@ pub struct AgentContext {
 
     #[serde(skip_serializing_if = "Option::is_none")]
     vars: Option<Arc<BTreeMap<String, AgentValue>>>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deadline: Option<SystemTime>,
+
+    // (stream of the edge, sequence number)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sequence: Option<(String, u64)>,
+
+    // oldest first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compensations: Option<Arc<Vec<Compensation>>>,
+
+    #[serde(skip)]
+    transaction: Option<Arc<Mutex<TransactionSlot>>>,
 }
 
 impl AgentContext {
@@ -23,6 +40,10 @@ impl AgentContext {
         Self {
             id: new_id(),
             vars: None,
+            deadline: None,
+            sequence: None,
+            compensations: None,
+            transaction: None,
         }
     }
 
@@ -46,12 +67,213 @@ impl AgentContext {
         Self {
             id: self.id,
             vars: Some(Arc::new(vars)),
+            deadline: self.deadline,
+            sequence: self.sequence.clone(),
+            compensations: self.compensations.clone(),
+            transaction: self.transaction.clone(),
+        }
+    }
+
+    /// Name of the board the data was written to, for data from a Board Out agent.
+    pub fn board_name(&self) -> Option<&str> {
+        self.get_var(VAR_BOARD_NAME)
+            .and_then(|value| value.as_str())
+    }
+
+    pub(crate) fn with_board_name(&self, name: &str) -> Self {
+        self.with_var(VAR_BOARD_NAME.to_string(), AgentValue::string(name))
+    }
+
+    /// Id of the request the data answers, set by `AgentOutput::request`.
+    pub fn correlation_id(&self) -> Option<usize> {
+        self.get_var(VAR_CORRELATION_ID)
+            .and_then(|value| value.as_u64())
+            .map(|id| id as usize)
+    }
+
+    pub(crate) fn with_correlation_id(&self, id: usize) -> Self {
+        self.with_var(VAR_CORRELATION_ID.to_string(), AgentValue::from(id as u64))
+    }
+
+    /// Position of the data in the batch it was output with and the size of the batch,
+    /// for data from `AgentOutput::emit_many`.
+    pub fn batch(&self) -> Option<(usize, usize)> {
+        let index = self.get_var(VAR_BATCH_INDEX)?.as_u64()?;
+        let size = self.get_var(VAR_BATCH_SIZE)?.as_u64()?;
+        Some((index as usize, size as usize))
+    }
+
+    pub(crate) fn with_batch(&self, index: usize, size: usize) -> Self {
+        self.with_var(VAR_BATCH_INDEX.to_string(), AgentValue::from(index as u64))
+            .with_var(VAR_BATCH_SIZE.to_string(), AgentValue::from(size as u64))
+    }
+
+    // Sequence
+
+    /// Stream of the edge the input came through and its number on the edge, counted from 1.
+    ///
+    /// The runtime delivers the messages of an edge in the order they are sent.
+    pub fn sequence(&self) -> Option<(&str, u64)> {
+        self.sequence
+            .as_ref()
+            .map(|(stream, seq)| (stream.as_str(), *seq))
+    }
+
+    pub(crate) fn with_sequence(&self, stream: String, seq: u64) -> Self {
+        let mut ctx = self.clone();
+        ctx.sequence = Some((stream, seq));
+        ctx
+    }
+
+    // Deadline
+
+    pub fn deadline(&self) -> Option<SystemTime> {
+        self.deadline
+    }
+
+    /// Sets the time by which the work started from this context should be done.
+    ///
+    /// The deadline is carried along to the downstream agents, and the runtime drops
+    /// inputs whose deadline has passed. An earlier deadline already set is kept.
+    pub fn with_deadline(&self, deadline: SystemTime) -> Self {
+        let mut ctx = self.clone();
+        ctx.deadline = Some(match self.deadline {
+            Some(current) => current.min(deadline),
+            None => deadline,
+        });
+        ctx
+    }
+
+    /// Sets the deadline `timeout` from now.
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        self.with_deadline(SystemTime::now() + timeout)
+    }
+
+    /// Time left until the deadline, zero once it has passed. `None` without a deadline.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| {
+            deadline
+                .duration_since(SystemTime::now())
+                .unwrap_or_default()
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.remaining()
+            .is_some_and(|remaining| remaining.is_zero())
+    }
+
+    // Compensation
+
+    /// Registers the undo data of a step, to be sent to the `handler` agent on its
+    /// [`COMPENSATE_PIN`](crate::COMPENSATE_PIN) port if a later step fails.
+    ///
+    /// The records are carried along to the downstream agents. When `process` of one of
+    /// them returns an error, the runtime sends the records to their handlers in reverse
+    /// order of registration.
+    pub fn with_compensation(&self, handler: impl Into<String>, data: AgentData) -> Self {
+        let mut compensations = match &self.compensations {
+            Some(compensations) => compensations.as_ref().clone(),
+            None => Vec::new(),
+        };
+        compensations.push(Compensation::new(handler.into(), data));
+        let mut ctx = self.clone();
+        ctx.compensations = Some(Arc::new(compensations));
+        ctx
+    }
+
+    /// Compensation records registered so far, oldest first.
+    pub fn compensations(&self) -> &[Compensation] {
+        self.compensations
+            .as_ref()
+            .map(|compensations| compensations.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Error of the failed step, for the data received on the compensate port.
+    pub fn compensation_error(&self) -> Option<&str> {
+        self.get_var(VAR_COMPENSATION_ERROR)
+            .and_then(|value| value.as_str())
+    }
+
+    pub(crate) fn without_compensations(&self) -> Self {
+        let mut ctx = self.clone();
+        ctx.compensations = None;
+        ctx
+    }
+
+    // Transaction
+
+    /// Stages the outputs sent while the returned guard is alive.
+    ///
+    /// They are delivered only after `process` returns `Ok`, so downstream agents never
+    /// act on partial results of a failed `process`.
+    pub fn transaction(&self) -> AgentTransaction {
+        AgentTransaction::new(self.transaction.clone())
+    }
+
+    pub(crate) fn transaction_slot(&self) -> Option<&Arc<Mutex<TransactionSlot>>> {
+        self.transaction.as_ref()
+    }
+
+    pub(crate) fn with_transaction_slot(&self, slot: Option<Arc<Mutex<TransactionSlot>>>) -> Self {
+        Self {
+            id: self.id,
+            vars: self.vars.clone(),
+            deadline: self.deadline,
+            sequence: self.sequence.clone(),
+            compensations: self.compensations.clone(),
+            transaction: slot,
         }
     }
 }
 
+static VAR_BOARD_NAME: &str = "$board";
+static VAR_CORRELATION_ID: &str = "$correlation_id";
+static VAR_BATCH_INDEX: &str = "$batch_index";
+static VAR_BATCH_SIZE: &str = "$batch_size";
+
 static CONTEXT_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
 
 fn new_id() -> usize {
     CONTEXT_ID_COUNTER.fetch_add(1, Ordering::Relaxed)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_deadline() {
+        let ctx = AgentContext::new();
+        assert!(ctx.remaining().is_none());
+        assert!(!ctx.is_expired());
+
+        let ctx = ctx.with_timeout(Duration::from_secs(60));
+        let remaining = ctx.remaining().unwrap();
+        assert!(remaining > Duration::from_secs(59) && remaining <= Duration::from_secs(60));
+
+        // the earlier deadline wins, and derived contexts keep it
+        let ctx = ctx.with_timeout(Duration::from_secs(600));
+        assert!(ctx.remaining().unwrap() <= Duration::from_secs(60));
+        let ctx = ctx.with_var("key".into(), AgentValue::integer(1));
+        assert!(ctx.deadline().is_some());
+
+        let expired = ctx.with_deadline(SystemTime::UNIX_EPOCH);
+        assert!(expired.is_expired());
+        assert_eq!(expired.remaining(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_context_batch() {
+        let ctx = AgentContext::new();
+        assert!(ctx.batch().is_none());
+
+        let ctx = ctx.with_batch(2, 5);
+        assert_eq!(ctx.batch(), Some((2, 5)));
+        assert_eq!(
+            ctx.with_sequence("a:out>b:in".into(), 3).batch(),
+            Some((2, 5))
+        );
+    }
+}
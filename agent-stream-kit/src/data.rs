@@ -1,4 +1,9 @@
-use std::{collections::BTreeMap, sync::Arc};
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use chrono::{DateTime, TimeDelta, Utc};
 
 #[cfg(feature = "image")]
 use photon_rs::PhotonImage;
@@ -12,7 +17,7 @@ use super::error::AgentError;
 
 const IMAGE_BASE64_PREFIX: &str = "data:image/png;base64,";
 
-#[derive(Debug, Clone, PartialEq, Serialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub struct AgentData {
     pub kind: String,
     pub value: AgentValue,
@@ -54,6 +59,20 @@ impl AgentData {
         }
     }
 
+    pub fn datetime(value: DateTime<Utc>) -> Self {
+        AgentData {
+            kind: "datetime".to_string(),
+            value: AgentValue::datetime(value),
+        }
+    }
+
+    pub fn duration(value: TimeDelta) -> Self {
+        AgentData {
+            kind: "duration".to_string(),
+            value: AgentValue::duration(value),
+        }
+    }
+
     #[cfg(feature = "image")]
     pub fn image(value: PhotonImage) -> Self {
         AgentData {
@@ -191,11 +210,24 @@ impl AgentData {
         self.value.as_i64()
     }
 
+    #[allow(unused)]
+    pub fn as_u64(&self) -> Option<u64> {
+        self.value.as_u64()
+    }
+
     #[allow(unused)]
     pub fn as_f64(&self) -> Option<f64> {
         self.value.as_f64()
     }
 
+    pub fn as_datetime(&self) -> Option<DateTime<Utc>> {
+        self.value.as_datetime()
+    }
+
+    pub fn as_duration(&self) -> Option<TimeDelta> {
+        self.value.as_duration()
+    }
+
     pub fn as_str(&self) -> Option<&str> {
         self.value.as_str()
     }
@@ -230,6 +262,11 @@ impl AgentData {
         self.value.get_i64(key)
     }
 
+    #[allow(unused)]
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.value.get_u64(key)
+    }
+
     #[allow(unused)]
     pub fn get_f64(&self, key: &str) -> Option<f64> {
         self.value.get_f64(key)
@@ -286,6 +323,13 @@ pub enum AgentValue {
     Integer(i64),
     Number(f64),
 
+    // Integers above i64::MAX, such as IDs from external APIs
+    Unsigned(u64),
+
+    // Mapped to ISO 8601 strings in JSON
+    DateTime(DateTime<Utc>),
+    Duration(TimeDelta),
+
     // Larger data structures use reference counting
     String(Arc<String>),
 
@@ -297,7 +341,13 @@ pub enum AgentValue {
     Object(Arc<AgentValueMap<String, AgentValue>>),
 }
 
-pub type AgentValueMap<S, T> = BTreeMap<S, T>;
+/// Map of object values. Keys are sorted, or kept in insertion order with the `preserve_order` feature.
+#[cfg(not(feature = "preserve_order"))]
+pub type AgentValueMap<S, T> = std::collections::BTreeMap<S, T>;
+
+/// Map of object values. Keys are sorted, or kept in insertion order with the `preserve_order` feature.
+#[cfg(feature = "preserve_order")]
+pub type AgentValueMap<S, T> = indexmap::IndexMap<S, T>;
 
 impl AgentValue {
     pub fn unit() -> Self {
@@ -316,10 +366,26 @@ impl AgentValue {
         AgentValue::Number(value)
     }
 
+    /// An integer value. It is stored as `Unsigned` only when it does not fit in i64.
+    pub fn unsigned(value: u64) -> Self {
+        match i64::try_from(value) {
+            Ok(i) => AgentValue::Integer(i),
+            Err(_) => AgentValue::Unsigned(value),
+        }
+    }
+
     pub fn string(value: impl Into<String>) -> Self {
         AgentValue::String(Arc::new(value.into()))
     }
 
+    pub fn datetime(value: DateTime<Utc>) -> Self {
+        AgentValue::DateTime(value)
+    }
+
+    pub fn duration(value: TimeDelta) -> Self {
+        AgentValue::Duration(value)
+    }
+
     #[cfg(feature = "image")]
     pub fn image(value: PhotonImage) -> Self {
         AgentValue::Image(Arc::new(value))
@@ -374,6 +440,8 @@ impl AgentValue {
             serde_json::Value::Number(n) => {
                 if let Some(i) = n.as_i64() {
                     Ok(AgentValue::Integer(i))
+                } else if let Some(u) = n.as_u64() {
+                    Ok(AgentValue::Unsigned(u))
                 } else if let Some(f) = n.as_f64() {
                     Ok(AgentValue::Number(f))
                 } else {
@@ -440,6 +508,8 @@ impl AgentValue {
                 serde_json::Value::Number(n) => {
                     if let Some(i) = n.as_i64() {
                         Ok(AgentValue::Integer(i))
+                    } else if let Some(u) = n.as_u64() {
+                        Ok(AgentValue::Unsigned(u))
                     } else if let Some(f) = n.as_f64() {
                         Ok(AgentValue::Integer(f as i64))
                     } else {
@@ -451,6 +521,8 @@ impl AgentValue {
                     for n in a {
                         if let Some(i) = n.as_i64() {
                             agent_arr.push(AgentValue::Integer(i));
+                        } else if let Some(u) = n.as_u64() {
+                            agent_arr.push(AgentValue::Unsigned(u));
                         } else if let Some(f) = n.as_f64() {
                             agent_arr.push(AgentValue::Integer(f as i64));
                         } else {
@@ -521,12 +593,46 @@ impl AgentValue {
                 }
                 _ => Err(AgentError::InvalidValue("image".into())),
             },
+            "datetime" => match value {
+                serde_json::Value::String(s) => parse_datetime(&s)
+                    .map(AgentValue::DateTime)
+                    .ok_or_else(|| AgentError::InvalidValue("datetime".into())),
+                serde_json::Value::Array(a) => {
+                    let mut agent_arr = Vec::new();
+                    for v in a {
+                        let Some(dt) = v.as_str().and_then(parse_datetime) else {
+                            return Err(AgentError::InvalidArrayValue("datetime".into()));
+                        };
+                        agent_arr.push(AgentValue::DateTime(dt));
+                    }
+                    Ok(AgentValue::Array(Arc::new(agent_arr)))
+                }
+                _ => Err(AgentError::InvalidValue("datetime".into())),
+            },
+            "duration" => match value {
+                serde_json::Value::String(s) => parse_duration(&s)
+                    .map(AgentValue::Duration)
+                    .ok_or_else(|| AgentError::InvalidValue("duration".into())),
+                serde_json::Value::Array(a) => {
+                    let mut agent_arr = Vec::new();
+                    for v in a {
+                        let Some(d) = v.as_str().and_then(parse_duration) else {
+                            return Err(AgentError::InvalidArrayValue("duration".into()));
+                        };
+                        agent_arr.push(AgentValue::Duration(d));
+                    }
+                    Ok(AgentValue::Array(Arc::new(agent_arr)))
+                }
+                _ => Err(AgentError::InvalidValue("duration".into())),
+            },
             _ => match value {
                 serde_json::Value::Null => Ok(AgentValue::Unit),
                 serde_json::Value::Bool(b) => Ok(AgentValue::Boolean(b)),
                 serde_json::Value::Number(n) => {
                     if let Some(i) = n.as_i64() {
                         Ok(AgentValue::Integer(i))
+                    } else if let Some(u) = n.as_u64() {
+                        Ok(AgentValue::Unsigned(u))
                     } else if let Some(f) = n.as_f64() {
                         Ok(AgentValue::Number(f))
                     } else {
@@ -559,6 +665,9 @@ impl AgentValue {
             AgentValue::Boolean(b) => (*b).into(),
             AgentValue::Integer(i) => (*i).into(),
             AgentValue::Number(n) => (*n).into(),
+            AgentValue::Unsigned(u) => (*u).into(),
+            AgentValue::DateTime(dt) => format_datetime(dt).into(),
+            AgentValue::Duration(d) => format_duration(d).into(),
             AgentValue::String(s) => s.as_str().into(),
             #[cfg(feature = "image")]
             AgentValue::Image(img) => img.get_base64().into(),
@@ -602,7 +711,7 @@ impl AgentValue {
 
     #[allow(unused)]
     pub fn is_integer(&self) -> bool {
-        matches!(self, AgentValue::Integer(_))
+        matches!(self, AgentValue::Integer(_) | AgentValue::Unsigned(_))
     }
 
     #[allow(unused)]
@@ -610,6 +719,14 @@ impl AgentValue {
         matches!(self, AgentValue::Number(_))
     }
 
+    pub fn is_datetime(&self) -> bool {
+        matches!(self, AgentValue::DateTime(_))
+    }
+
+    pub fn is_duration(&self) -> bool {
+        matches!(self, AgentValue::Duration(_))
+    }
+
     #[allow(unused)]
     pub fn is_string(&self) -> bool {
         matches!(self, AgentValue::String(_))
@@ -642,6 +759,16 @@ impl AgentValue {
         match self {
             AgentValue::Integer(i) => Some(*i),
             AgentValue::Number(n) => Some(*n as i64),
+            AgentValue::Unsigned(u) => i64::try_from(*u).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            AgentValue::Integer(i) => u64::try_from(*i).ok(),
+            AgentValue::Number(n) if *n >= 0.0 => Some(*n as u64),
+            AgentValue::Unsigned(u) => Some(*u),
             _ => None,
         }
     }
@@ -650,6 +777,117 @@ impl AgentValue {
         match self {
             AgentValue::Integer(i) => Some(*i as f64),
             AgentValue::Number(n) => Some(*n),
+            AgentValue::Unsigned(u) => Some(*u as f64),
+            _ => None,
+        }
+    }
+
+    /// Converts a numeric value to i64, failing instead of truncating or wrapping.
+    pub fn to_i64(&self) -> Result<i64, AgentError> {
+        match self {
+            AgentValue::Integer(i) => Some(*i),
+            AgentValue::Unsigned(u) => i64::try_from(*u).ok(),
+            AgentValue::Number(n) => float_to_integer(*n).and_then(|i| i64::try_from(i).ok()),
+            _ => None,
+        }
+        .ok_or_else(|| AgentError::InvalidValue("i64".into()))
+    }
+
+    /// Converts a numeric value to u64, failing instead of truncating or wrapping.
+    pub fn to_u64(&self) -> Result<u64, AgentError> {
+        match self {
+            AgentValue::Integer(i) => u64::try_from(*i).ok(),
+            AgentValue::Unsigned(u) => Some(*u),
+            AgentValue::Number(n) => float_to_integer(*n).and_then(|i| u64::try_from(i).ok()),
+            _ => None,
+        }
+        .ok_or_else(|| AgentError::InvalidValue("u64".into()))
+    }
+
+    /// Converts a numeric value to f64, failing when an integer cannot be represented exactly.
+    pub fn to_f64(&self) -> Result<f64, AgentError> {
+        match self {
+            AgentValue::Integer(i) => Some(*i as f64).filter(|f| *f as i128 == *i as i128),
+            AgentValue::Unsigned(u) => Some(*u as f64).filter(|f| *f as i128 == *u as i128),
+            AgentValue::Number(n) => Some(*n),
+            _ => None,
+        }
+        .ok_or_else(|| AgentError::InvalidValue("f64".into()))
+    }
+
+    /// Compares values of the same kind. Integers and numbers compare by their numeric value.
+    pub fn compare(&self, other: &AgentValue) -> Option<Ordering> {
+        match (self, other) {
+            (AgentValue::Unit, AgentValue::Unit) => Some(Ordering::Equal),
+            (AgentValue::Boolean(b1), AgentValue::Boolean(b2)) => Some(b1.cmp(b2)),
+            (AgentValue::String(s1), AgentValue::String(s2)) => Some(s1.cmp(s2)),
+            (AgentValue::DateTime(dt1), AgentValue::DateTime(dt2)) => Some(dt1.cmp(dt2)),
+            (AgentValue::Duration(d1), AgentValue::Duration(d2)) => Some(d1.cmp(d2)),
+            _ => match (self.as_i128(), other.as_i128()) {
+                (Some(i1), Some(i2)) => Some(i1.cmp(&i2)),
+                _ if self.is_numeric() && other.is_numeric() => {
+                    self.as_f64()?.partial_cmp(&other.as_f64()?)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    /// Deep equality where numbers may differ by up to `epsilon`.
+    pub fn approx_eq(&self, other: &AgentValue, epsilon: f64) -> bool {
+        match (self, other) {
+            (AgentValue::Array(a1), AgentValue::Array(a2)) => {
+                a1.len() == a2.len()
+                    && a1
+                        .iter()
+                        .zip(a2.iter())
+                        .all(|(v1, v2)| v1.approx_eq(v2, epsilon))
+            }
+            (AgentValue::Object(o1), AgentValue::Object(o2)) => {
+                o1.len() == o2.len()
+                    && o1
+                        .iter()
+                        .all(|(k, v1)| o2.get(k).is_some_and(|v2| v1.approx_eq(v2, epsilon)))
+            }
+            (AgentValue::Number(_), _) | (_, AgentValue::Number(_))
+                if self.is_numeric() && other.is_numeric() =>
+            {
+                let (n1, n2) = (self.as_f64().unwrap(), other.as_f64().unwrap());
+                n1 == n2 || (n1 - n2).abs() <= epsilon
+            }
+            _ => self == other,
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            AgentValue::Integer(_) | AgentValue::Number(_) | AgentValue::Unsigned(_)
+        )
+    }
+
+    fn as_i128(&self) -> Option<i128> {
+        match self {
+            AgentValue::Integer(i) => Some(*i as i128),
+            AgentValue::Unsigned(u) => Some(*u as i128),
+            _ => None,
+        }
+    }
+
+    /// The datetime value, or an ISO 8601 string parsed as a datetime.
+    pub fn as_datetime(&self) -> Option<DateTime<Utc>> {
+        match self {
+            AgentValue::DateTime(dt) => Some(*dt),
+            AgentValue::String(s) => parse_datetime(s),
+            _ => None,
+        }
+    }
+
+    /// The duration value, or an ISO 8601 string such as `PT1H30M` parsed as a duration.
+    pub fn as_duration(&self) -> Option<TimeDelta> {
+        match self {
+            AgentValue::Duration(d) => Some(*d),
+            AgentValue::String(s) => parse_duration(s),
             _ => None,
         }
     }
@@ -698,6 +936,11 @@ impl AgentValue {
         self.get(key).and_then(|v| v.as_i64())
     }
 
+    #[allow(unused)]
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get(key).and_then(|v| v.as_u64())
+    }
+
     #[allow(unused)]
     pub fn get_f64(&self, key: &str) -> Option<f64> {
         self.get(key).and_then(|v| v.as_f64())
@@ -724,11 +967,168 @@ impl AgentValue {
         self.get(key).and_then(|v| v.as_array())
     }
 
+    /// Number of items in an array or entries in an object. Other values have no items.
+    pub fn len(&self) -> usize {
+        match self {
+            AgentValue::Array(a) => a.len(),
+            AgentValue::Object(o) => o.len(),
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Iterates over the items of an array, failing for other values.
+    pub fn iter_array(&self) -> Result<std::slice::Iter<'_, AgentValue>, AgentError> {
+        self.as_array()
+            .map(|a| a.iter())
+            .ok_or_else(|| AgentError::InvalidValue("array".into()))
+    }
+
+    /// Iterates over the entries of an object, failing for other values.
+    pub fn iter_object(
+        &self,
+    ) -> Result<impl Iterator<Item = (&str, &AgentValue)> + '_, AgentError> {
+        self.as_object()
+            .map(|o| o.iter().map(|(k, v)| (k.as_str(), v)))
+            .ok_or_else(|| AgentError::InvalidValue("object".into()))
+    }
+
+    /// Entries of an object. Unlike `iter_object`, other values just have no entries.
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &AgentValue)> + '_ {
+        self.as_object()
+            .into_iter()
+            .flat_map(|o| o.iter().map(|(k, v)| (k.as_str(), v)))
+    }
+
+    /// Item of an array at `index`, failing for other values or when out of range.
+    pub fn try_index(&self, index: usize) -> Result<&AgentValue, AgentError> {
+        self.as_array()
+            .ok_or_else(|| AgentError::InvalidValue("array".into()))?
+            .get(index)
+            .ok_or_else(|| AgentError::InvalidPath(format!("/{}", index)))
+    }
+
+    /// Field of an object, failing for other values or when the key is missing.
+    pub fn try_get(&self, key: &str) -> Result<&AgentValue, AgentError> {
+        self.as_object()
+            .ok_or_else(|| AgentError::InvalidValue("object".into()))?
+            .get(key)
+            .ok_or_else(|| AgentError::InvalidPath(key.to_string()))
+    }
+
+    /// Looks up a value by a JSON Pointer such as `/a/b/0`.
+    pub fn pointer(&self, pointer: &str) -> Option<&AgentValue> {
+        let mut value = self;
+        for token in pointer_tokens(pointer)? {
+            value = match value {
+                AgentValue::Object(o) => o.get(&token)?,
+                AgentValue::Array(a) => a.get(token.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Mutable version of [`AgentValue::pointer`]. Shared objects and arrays on the path are copied.
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut AgentValue> {
+        let mut value = self;
+        for token in pointer_tokens(pointer)? {
+            value = match value {
+                AgentValue::Object(o) => Arc::make_mut(o).get_mut(&token)?,
+                AgentValue::Array(a) => Arc::make_mut(a).get_mut(token.parse::<usize>().ok()?)?,
+                _ => return None,
+            };
+        }
+        Some(value)
+    }
+
+    /// Sets the value at `path`, creating missing objects on the way.
+    ///
+    /// `path` is a JSON Pointer, or a single key when it does not start with `/`.
+    /// In arrays, `-` or the length of the array appends.
+    pub fn set_path(&mut self, path: &str, new_value: AgentValue) -> Result<(), AgentError> {
+        let tokens = if path.starts_with('/') || path.is_empty() {
+            pointer_tokens(path).ok_or_else(|| AgentError::InvalidPath(path.to_string()))?
+        } else {
+            vec![path.to_string()]
+        };
+
+        let mut value = self;
+        for token in tokens {
+            if value.is_unit() {
+                *value = AgentValue::object_default();
+            }
+            value = match value {
+                AgentValue::Object(o) => Arc::make_mut(o).entry(token).or_default(),
+                AgentValue::Array(a) => {
+                    let a = Arc::make_mut(a);
+                    let index = if token == "-" {
+                        a.len()
+                    } else {
+                        token
+                            .parse::<usize>()
+                            .ok()
+                            .filter(|i| *i <= a.len())
+                            .ok_or_else(|| AgentError::InvalidPath(path.to_string()))?
+                    };
+                    if index == a.len() {
+                        a.push(AgentValue::unit());
+                    }
+                    &mut a[index]
+                }
+                _ => return Err(AgentError::InvalidPath(path.to_string())),
+            };
+        }
+        *value = new_value;
+        Ok(())
+    }
+
+    /// Returns the value with `new_value` set at `path`. See [`AgentValue::set_path`].
+    pub fn with_field(
+        mut self,
+        path: &str,
+        new_value: impl Into<AgentValue>,
+    ) -> Result<Self, AgentError> {
+        self.set_path(path, new_value.into())?;
+        Ok(self)
+    }
+
+    /// Deep merges `other` into the value.
+    ///
+    /// Objects are merged key by key. Any other value is replaced by the one in `other`.
+    pub fn merge(mut self, other: AgentValue) -> Self {
+        self.merge_from(other);
+        self
+    }
+
+    fn merge_from(&mut self, other: AgentValue) {
+        match (self, other) {
+            (AgentValue::Object(o), AgentValue::Object(other)) => {
+                let o = Arc::make_mut(o);
+                let other = Arc::try_unwrap(other).unwrap_or_else(|other| (*other).clone());
+                for (key, value) in other {
+                    match o.get_mut(&key) {
+                        Some(current) => current.merge_from(value),
+                        None => {
+                            o.insert(key, value);
+                        }
+                    }
+                }
+            }
+            (this, other) => *this = other,
+        }
+    }
+
     pub fn kind(&self) -> String {
         match self {
             AgentValue::Unit => "unit".to_string(),
             AgentValue::Boolean(_) => "boolean".to_string(),
-            AgentValue::Integer(_) => "integer".to_string(),
+            AgentValue::Integer(_) | AgentValue::Unsigned(_) => "integer".to_string(),
+            AgentValue::DateTime(_) => "datetime".to_string(),
+            AgentValue::Duration(_) => "duration".to_string(),
             AgentValue::Number(_) => "number".to_string(),
             AgentValue::String(_) => "string".to_string(),
             #[cfg(feature = "image")]
@@ -745,6 +1145,104 @@ impl AgentValue {
     }
 }
 
+// Integral value of a float, if it has no fractional part.
+fn float_to_integer(n: f64) -> Option<i128> {
+    (n.is_finite() && n.fract() == 0.0 && n.abs() < 2f64.powi(64)).then_some(n as i128)
+}
+
+fn format_datetime(dt: &DateTime<Utc>) -> String {
+    dt.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)
+}
+
+fn parse_datetime(s: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(s)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+// ISO 8601 duration with hours, minutes and seconds, e.g. `PT1H30M` or `-PT0.5S`.
+fn format_duration(d: &TimeDelta) -> String {
+    let sign = if *d < TimeDelta::zero() { "-" } else { "" };
+    let d = d.abs();
+    let secs = d.num_seconds();
+    let nanos = d.subsec_nanos();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    let mut out = format!("{}PT", sign);
+    if hours > 0 {
+        out.push_str(&format!("{}H", hours));
+    }
+    if minutes > 0 {
+        out.push_str(&format!("{}M", minutes));
+    }
+    if nanos > 0 {
+        let frac = format!("{:09}", nanos);
+        out.push_str(&format!("{}.{}S", seconds, frac.trim_end_matches('0')));
+    } else if seconds > 0 || (hours == 0 && minutes == 0) {
+        out.push_str(&format!("{}S", seconds));
+    }
+    out
+}
+
+// Parses ISO 8601 durations made of weeks, days, hours, minutes and seconds.
+// Years and months are rejected since their length varies.
+fn parse_duration(s: &str) -> Option<TimeDelta> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let s = s.strip_prefix('P')?;
+
+    let mut total = TimeDelta::zero();
+    let mut has_part = false;
+    let mut in_time = false;
+    let mut number = String::new();
+    for c in s.chars() {
+        match c {
+            'T' if !in_time && number.is_empty() => in_time = true,
+            '0'..='9' | '.' => number.push(c),
+            _ => {
+                if number.is_empty() {
+                    return None;
+                }
+                let part = match (in_time, c) {
+                    (false, 'W') => TimeDelta::try_weeks(number.parse().ok()?)?,
+                    (false, 'D') => TimeDelta::try_days(number.parse().ok()?)?,
+                    (true, 'H') => TimeDelta::try_hours(number.parse().ok()?)?,
+                    (true, 'M') => TimeDelta::try_minutes(number.parse().ok()?)?,
+                    (true, 'S') => {
+                        let secs: f64 = number.parse().ok()?;
+                        TimeDelta::from_std(std::time::Duration::try_from_secs_f64(secs).ok()?)
+                            .ok()?
+                    }
+                    _ => return None,
+                };
+                total = total.checked_add(&part)?;
+                has_part = true;
+                number.clear();
+            }
+        }
+    }
+    if !has_part || !number.is_empty() {
+        return None;
+    }
+    Some(if negative { -total } else { total })
+}
+
+// Splits a JSON Pointer into unescaped reference tokens.
+fn pointer_tokens(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+    let pointer = pointer.strip_prefix('/')?;
+    Some(
+        pointer
+            .split('/')
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .collect(),
+    )
+}
+
 impl Default for AgentValue {
     fn default() -> Self {
         AgentValue::Unit
@@ -757,7 +1255,17 @@ impl PartialEq for AgentValue {
             (AgentValue::Unit, AgentValue::Unit) => true,
             (AgentValue::Boolean(b1), AgentValue::Boolean(b2)) => b1 == b2,
             (AgentValue::Integer(i1), AgentValue::Integer(i2)) => i1 == i2,
-            (AgentValue::Number(n1), AgentValue::Number(n2)) => n1 == n2,
+            // NaN equals NaN so that any value equals itself
+            (AgentValue::Number(n1), AgentValue::Number(n2)) => {
+                n1 == n2 || (n1.is_nan() && n2.is_nan())
+            }
+            (AgentValue::Unsigned(u1), AgentValue::Unsigned(u2)) => u1 == u2,
+            (AgentValue::Integer(_), AgentValue::Unsigned(_))
+            | (AgentValue::Unsigned(_), AgentValue::Integer(_)) => {
+                self.as_i128() == other.as_i128()
+            }
+            (AgentValue::DateTime(dt1), AgentValue::DateTime(dt2)) => dt1 == dt2,
+            (AgentValue::Duration(d1), AgentValue::Duration(d2)) => d1 == d2,
             (AgentValue::String(s1), AgentValue::String(s2)) => s1 == s2,
             #[cfg(feature = "image")]
             (AgentValue::Image(i1), AgentValue::Image(i2)) => {
@@ -772,6 +1280,70 @@ impl PartialEq for AgentValue {
     }
 }
 
+impl Eq for AgentValue {}
+
+impl Hash for AgentValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            AgentValue::Unit => state.write_u8(0),
+            AgentValue::Boolean(b) => {
+                state.write_u8(1);
+                b.hash(state);
+            }
+            // Integer and Unsigned holding the same number are equal
+            AgentValue::Integer(_) | AgentValue::Unsigned(_) => {
+                state.write_u8(2);
+                self.as_i128().hash(state);
+            }
+            AgentValue::Number(n) => {
+                state.write_u8(3);
+                let n = if *n == 0.0 {
+                    0.0
+                } else if n.is_nan() {
+                    f64::NAN
+                } else {
+                    *n
+                };
+                n.to_bits().hash(state);
+            }
+            AgentValue::DateTime(dt) => {
+                state.write_u8(4);
+                dt.hash(state);
+            }
+            AgentValue::Duration(d) => {
+                state.write_u8(5);
+                d.hash(state);
+            }
+            AgentValue::String(s) => {
+                state.write_u8(6);
+                s.hash(state);
+            }
+            // Pixels are not hashed. Equal images still hash the same.
+            #[cfg(feature = "image")]
+            AgentValue::Image(img) => {
+                state.write_u8(7);
+                img.get_width().hash(state);
+                img.get_height().hash(state);
+            }
+            AgentValue::Array(a) => {
+                state.write_u8(8);
+                a.hash(state);
+            }
+            // Independent of the key order, as ordered maps compare regardless of it
+            AgentValue::Object(o) => {
+                state.write_u8(9);
+                state.write_usize(o.len());
+                let sum = o.iter().fold(0u64, |sum, entry| {
+                    let mut hasher = DefaultHasher::new();
+                    entry.hash(&mut hasher);
+                    sum.wrapping_add(hasher.finish())
+                });
+                state.write_u64(sum);
+            }
+        }
+    }
+}
+
 impl Serialize for AgentValue {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -782,6 +1354,9 @@ impl Serialize for AgentValue {
             AgentValue::Boolean(b) => serializer.serialize_bool(*b),
             AgentValue::Integer(i) => serializer.serialize_i64(*i),
             AgentValue::Number(n) => serializer.serialize_f64(*n),
+            AgentValue::Unsigned(u) => serializer.serialize_u64(*u),
+            AgentValue::DateTime(dt) => serializer.serialize_str(&format_datetime(dt)),
+            AgentValue::Duration(d) => serializer.serialize_str(&format_duration(d)),
             AgentValue::String(s) => serializer.serialize_str(s),
             #[cfg(feature = "image")]
             AgentValue::Image(img) => serializer.serialize_str(&img.get_base64()),
@@ -839,6 +1414,12 @@ impl From<i64> for AgentValue {
     }
 }
 
+impl From<u64> for AgentValue {
+    fn from(value: u64) -> Self {
+        AgentValue::unsigned(value)
+    }
+}
+
 impl From<f64> for AgentValue {
     fn from(value: f64) -> Self {
         AgentValue::number(value)
@@ -857,6 +1438,70 @@ impl From<&str> for AgentValue {
     }
 }
 
+impl<T: Into<AgentValue>> From<Vec<T>> for AgentValue {
+    fn from(value: Vec<T>) -> Self {
+        AgentValue::array(value.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<AgentValue>> From<std::collections::BTreeMap<String, T>> for AgentValue {
+    fn from(value: std::collections::BTreeMap<String, T>) -> Self {
+        AgentValue::object(value.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
+/// `None` becomes unit.
+impl<T: Into<AgentValue>> From<Option<T>> for AgentValue {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or_default()
+    }
+}
+
+impl TryFrom<AgentValue> for bool {
+    type Error = AgentError;
+
+    fn try_from(value: AgentValue) -> Result<Self, Self::Error> {
+        value
+            .as_bool()
+            .ok_or_else(|| AgentError::InvalidValue("boolean".into()))
+    }
+}
+
+impl TryFrom<AgentValue> for i64 {
+    type Error = AgentError;
+
+    fn try_from(value: AgentValue) -> Result<Self, Self::Error> {
+        value.to_i64()
+    }
+}
+
+impl TryFrom<AgentValue> for u64 {
+    type Error = AgentError;
+
+    fn try_from(value: AgentValue) -> Result<Self, Self::Error> {
+        value.to_u64()
+    }
+}
+
+impl TryFrom<AgentValue> for f64 {
+    type Error = AgentError;
+
+    fn try_from(value: AgentValue) -> Result<Self, Self::Error> {
+        value.to_f64()
+    }
+}
+
+impl TryFrom<AgentValue> for String {
+    type Error = AgentError;
+
+    fn try_from(value: AgentValue) -> Result<Self, Self::Error> {
+        match value {
+            AgentValue::String(s) => Ok(Arc::unwrap_or_clone(s)),
+            _ => Err(AgentError::InvalidValue("string".into())),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2072,6 +2717,79 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_agent_value_iterators() {
+        let array = AgentValue::array(vec![AgentValue::integer(1), AgentValue::integer(2)]);
+        assert_eq!(array.len(), 2);
+        assert!(!array.is_empty());
+        let sum: i64 = array.iter_array().unwrap().filter_map(|v| v.as_i64()).sum();
+        assert_eq!(sum, 3);
+        assert_eq!(array.try_index(1).unwrap().as_i64(), Some(2));
+        assert!(matches!(
+            array.try_index(2),
+            Err(AgentError::InvalidPath(_))
+        ));
+        assert!(array.iter_object().is_err());
+        assert_eq!(array.entries().count(), 0);
+
+        let obj = AgentValue::object(
+            [
+                ("key1".to_string(), AgentValue::string("string1")),
+                ("key2".to_string(), AgentValue::integer(2)),
+            ]
+            .into(),
+        );
+        assert_eq!(obj.len(), 2);
+        let keys = obj
+            .iter_object()
+            .unwrap()
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["key1", "key2"]);
+        assert_eq!(obj.entries().count(), 2);
+        assert_eq!(obj.try_get("key1").unwrap().as_str(), Some("string1"));
+        assert!(matches!(
+            obj.try_get("key3"),
+            Err(AgentError::InvalidPath(_))
+        ));
+        assert!(matches!(obj.try_index(0), Err(AgentError::InvalidValue(_))));
+
+        let integer = AgentValue::integer(42);
+        assert!(integer.is_empty());
+        assert!(integer.iter_array().is_err());
+    }
+
+    #[test]
+    fn test_agent_value_conversions() {
+        let array = AgentValue::from(vec![1, 2, 3]);
+        assert_eq!(
+            array,
+            AgentValue::array(vec![
+                AgentValue::integer(1),
+                AgentValue::integer(2),
+                AgentValue::integer(3)
+            ])
+        );
+
+        let obj = AgentValue::from(std::collections::BTreeMap::from([
+            ("name".to_string(), AgentValue::from("alice")),
+            ("tags".to_string(), AgentValue::from(vec!["a", "b"])),
+            ("age".to_string(), AgentValue::from(None::<i64>)),
+        ]));
+        assert_eq!(obj.get_str("name"), Some("alice"));
+        assert_eq!(obj.get_array("tags").map(|a| a.len()), Some(2));
+        assert!(obj.get("age").unwrap().is_unit());
+        assert_eq!(AgentValue::from(Some(1.5)), AgentValue::number(1.5));
+
+        assert_eq!(i64::try_from(AgentValue::integer(42)).unwrap(), 42);
+        assert_eq!(u64::try_from(AgentValue::integer(42)).unwrap(), 42);
+        assert!(u64::try_from(AgentValue::integer(-1)).is_err());
+        assert_eq!(f64::try_from(AgentValue::number(0.5)).unwrap(), 0.5);
+        assert!(bool::try_from(AgentValue::boolean(true)).unwrap());
+        assert_eq!(String::try_from(AgentValue::string("s")).unwrap(), "s");
+        assert!(String::try_from(AgentValue::integer(1)).is_err());
+    }
+
     #[test]
     fn test_agent_value_default() {
         assert_eq!(AgentValue::default(), AgentValue::Unit);
@@ -2339,4 +3057,202 @@ mod tests {
         let restored: Person = agent_data.to_deserialize().unwrap();
         assert_eq!(restored, person);
     }
+
+    #[test]
+    fn test_agent_value_path_access() {
+        let value = AgentValue::from_json(json!({"a": {"b": [1, {"c/d": "x"}]}})).unwrap();
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("/a/b/0"), Some(&AgentValue::integer(1)));
+        assert_eq!(value.pointer("/a/b/1/c~1d"), Some(&AgentValue::string("x")));
+        assert_eq!(value.pointer("/a/b/2"), None);
+        assert_eq!(value.pointer("a"), None);
+
+        let shared = value.clone();
+        let updated = value
+            .with_field("/a/b/0", 2)
+            .unwrap()
+            .with_field("/a/b/-", "y")
+            .unwrap()
+            .with_field("/x/y", true)
+            .unwrap()
+            .with_field("z", "top")
+            .unwrap();
+        assert_eq!(
+            updated.to_json(),
+            json!({"a": {"b": [2, {"c/d": "x"}, "y"]}, "x": {"y": true}, "z": "top"})
+        );
+        // the original value is not affected
+        assert_eq!(shared.pointer("/a/b/0"), Some(&AgentValue::integer(1)));
+
+        assert!(updated.clone().with_field("/z/0", 1).is_err());
+        assert!(updated.clone().with_field("/a/b/9", 1).is_err());
+
+        let mut updated = updated;
+        *updated.pointer_mut("/x/y").unwrap() = AgentValue::boolean(false);
+        assert_eq!(updated.pointer("/x/y"), Some(&AgentValue::boolean(false)));
+
+        let merged = updated.merge(AgentValue::from_json(json!({"a": {"c": 3}, "z": 1})).unwrap());
+        assert_eq!(merged.pointer("/a/c"), Some(&AgentValue::integer(3)));
+        assert_eq!(merged.pointer("/a/b/0"), Some(&AgentValue::integer(2)));
+        assert_eq!(merged.pointer("/z"), Some(&AgentValue::integer(1)));
+    }
+
+    #[test]
+    fn test_agent_value_unsigned() {
+        let id = u64::MAX - 1;
+        let value = AgentValue::from_json(json!(id)).unwrap();
+        assert_eq!(value, AgentValue::Unsigned(id));
+        assert_eq!(value.kind(), "integer");
+        assert!(value.is_integer());
+        assert_eq!(value.as_u64(), Some(id));
+        assert_eq!(value.as_i64(), None);
+        assert_eq!(value.to_json(), json!(id));
+        assert_eq!(serde_json::to_string(&value).unwrap(), id.to_string());
+        assert_eq!(
+            AgentValue::from_kind_json("integer", json!([1, id])).unwrap(),
+            AgentValue::array(vec![AgentValue::integer(1), AgentValue::Unsigned(id)])
+        );
+
+        // small values stay Integer
+        assert_eq!(AgentValue::unsigned(5), AgentValue::Integer(5));
+        assert_eq!(AgentValue::from(5u64), AgentValue::Unsigned(5));
+    }
+
+    #[test]
+    fn test_agent_value_checked_conversions() {
+        let big = AgentValue::unsigned(u64::MAX);
+        assert!(big.to_i64().is_err());
+        assert_eq!(big.to_u64().unwrap(), u64::MAX);
+        assert!(big.to_f64().is_err());
+
+        assert_eq!(AgentValue::integer(-1).to_i64().unwrap(), -1);
+        assert!(AgentValue::integer(-1).to_u64().is_err());
+        assert_eq!(AgentValue::number(3.0).to_i64().unwrap(), 3);
+        assert!(AgentValue::number(3.5).to_i64().is_err());
+        assert!(AgentValue::number(f64::NAN).to_u64().is_err());
+        assert_eq!(AgentValue::integer(3).to_f64().unwrap(), 3.0);
+        assert!(AgentValue::integer(i64::MAX).to_f64().is_err());
+        assert!(AgentValue::string("1").to_i64().is_err());
+    }
+
+    #[test]
+    fn test_agent_value_compare() {
+        let big = AgentValue::unsigned(u64::MAX);
+        assert_eq!(
+            big.compare(&AgentValue::integer(i64::MAX)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            AgentValue::integer(-1).compare(&AgentValue::unsigned(u64::MAX)),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            AgentValue::integer(2).compare(&AgentValue::number(1.5)),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            AgentValue::string("a").compare(&AgentValue::string("b")),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            AgentValue::string("1").compare(&AgentValue::integer(1)),
+            None
+        );
+        assert_eq!(AgentValue::Unsigned(5), AgentValue::integer(5));
+    }
+
+    #[cfg(feature = "preserve_order")]
+    #[test]
+    fn test_agent_value_preserve_order() {
+        let value = AgentValue::from_json(json!({"b": 1, "a": 2, "c": {"z": 1, "y": 2}})).unwrap();
+        assert_eq!(
+            serde_json::to_string(&value).unwrap(),
+            r#"{"b":1,"a":2,"c":{"z":1,"y":2}}"#
+        );
+    }
+
+    #[test]
+    fn test_agent_value_datetime_duration() {
+        let dt = DateTime::parse_from_rfc3339("2025-01-02T03:04:05.5+09:00")
+            .unwrap()
+            .with_timezone(&Utc);
+        let data = AgentData::datetime(dt);
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"datetime","value":"2025-01-01T18:04:05.500Z"}"#
+        );
+        let restored: AgentData = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, data);
+        assert_eq!(
+            AgentValue::string("2025-01-01T18:04:05.5Z").as_datetime(),
+            Some(dt)
+        );
+
+        let d = TimeDelta::minutes(90) + TimeDelta::milliseconds(250);
+        let data = AgentData::duration(d);
+        assert_eq!(data.value.to_json(), json!("PT1H30M0.25S"));
+        let restored: AgentData =
+            serde_json::from_str(&serde_json::to_string(&data).unwrap()).unwrap();
+        assert_eq!(restored.as_duration(), Some(d));
+
+        assert_eq!(
+            AgentValue::duration(TimeDelta::zero()).to_json(),
+            json!("PT0S")
+        );
+        assert_eq!(
+            AgentValue::duration(-TimeDelta::seconds(5)).to_json(),
+            json!("-PT5S")
+        );
+        assert_eq!(
+            AgentValue::string("P1DT2H").as_duration(),
+            Some(TimeDelta::hours(26))
+        );
+        assert_eq!(
+            AgentValue::string("P1W").as_duration(),
+            Some(TimeDelta::weeks(1))
+        );
+        assert_eq!(AgentValue::string("P1M").as_duration(), None);
+        assert_eq!(AgentValue::string("PT").as_duration(), None);
+        assert!(AgentValue::from_kind_json("duration", json!("1h")).is_err());
+
+        let later = AgentValue::datetime(dt + TimeDelta::seconds(1));
+        assert_eq!(
+            AgentValue::datetime(dt).compare(&later),
+            Some(Ordering::Less)
+        );
+        assert_eq!(
+            AgentValue::duration(d).compare(&AgentValue::duration(TimeDelta::hours(1))),
+            Some(Ordering::Greater)
+        );
+    }
+
+    #[test]
+    fn test_agent_value_hash_and_approx_eq() {
+        use std::collections::HashSet;
+
+        let value = |json| AgentValue::from_json(json).unwrap();
+        let mut set = HashSet::new();
+        set.insert(value(json!({"a": [1, 2.5, "x"], "b": null})));
+        set.insert(value(json!({"b": null, "a": [1, 2.5, "x"]})));
+        set.insert(AgentValue::number(f64::NAN));
+        set.insert(AgentValue::number(f64::NAN));
+        set.insert(AgentValue::number(0.0));
+        set.insert(AgentValue::number(-0.0));
+        set.insert(AgentValue::Unsigned(7));
+        set.insert(AgentValue::integer(7));
+        assert_eq!(set.len(), 4);
+
+        let mut set = HashSet::new();
+        set.insert(AgentData::string("a"));
+        set.insert(AgentData::string("a"));
+        set.insert(AgentData::integer(1));
+        assert_eq!(set.len(), 2);
+
+        let v1 = value(json!({"x": [0.1, 1], "y": "s"}));
+        let v2 = value(json!({"x": [0.1000001, 1.0], "y": "s"}));
+        assert!(v1.approx_eq(&v2, 1e-6));
+        assert!(!v1.approx_eq(&v2, 1e-8));
+        assert!(!v1.approx_eq(&value(json!({"x": [0.1, 1]})), 1.0));
+    }
 }
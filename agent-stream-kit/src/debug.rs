@@ -0,0 +1,218 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use super::askit::ASKit;
+use super::context::AgentContext;
+use super::data::AgentData;
+use super::error::AgentError;
+
+/// Pauses a flow in debug mode when data is delivered to the node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Breakpoint {
+    pub node_id: String,
+
+    /// Input port of the node. Any port when `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+}
+
+impl Breakpoint {
+    pub fn new(node_id: impl Into<String>) -> Self {
+        Self {
+            node_id: node_id.into(),
+            port: None,
+        }
+    }
+
+    pub fn port(mut self, port: impl Into<String>) -> Self {
+        self.port = Some(port.into());
+        self
+    }
+
+    fn matches(&self, agent_id: &str, pin: &str) -> bool {
+        self.node_id == agent_id && self.port.as_ref().is_none_or(|port| port == pin)
+    }
+}
+
+/// Input held back while a flow is paused.
+#[derive(Debug, Clone)]
+pub struct PendingInput {
+    pub id: usize,
+    pub agent_id: String,
+    pub pin: String,
+    pub ctx: AgentContext,
+    pub data: AgentData,
+}
+
+// Debug mode of a flow
+#[derive(Default)]
+pub(crate) struct FlowDebugState {
+    breakpoints: Vec<Breakpoint>,
+    paused: bool,
+    pending: VecDeque<PendingInput>,
+    next_id: usize,
+}
+
+impl FlowDebugState {
+    pub(crate) fn breakpoints(&self) -> &Vec<Breakpoint> {
+        &self.breakpoints
+    }
+
+    pub(crate) fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    pub(crate) fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) {
+        self.breakpoints.retain(|b| b != breakpoint);
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub(crate) fn pending(&self) -> Vec<PendingInput> {
+        self.pending.iter().cloned().collect()
+    }
+
+    // Queues the input when the flow is paused or a breakpoint is hit.
+    // Returns the input back when it can be delivered now.
+    fn hold(
+        &mut self,
+        agent_id: &str,
+        pin: &str,
+        ctx: AgentContext,
+        data: AgentData,
+    ) -> Result<(AgentContext, AgentData), Box<PendingInput>> {
+        let hit = !self.paused && self.breakpoints.iter().any(|b| b.matches(agent_id, pin));
+        if !self.paused && !hit {
+            return Ok((ctx, data));
+        }
+        self.paused = true;
+        self.next_id += 1;
+        let input = PendingInput {
+            id: self.next_id,
+            agent_id: agent_id.to_string(),
+            pin: pin.to_string(),
+            ctx,
+            data,
+        };
+        self.pending.push_back(input.clone());
+        Err(Box::new(input))
+    }
+}
+
+// Called by agent_input before the input is queued for the agent.
+pub(crate) fn hold_input(
+    askit: &ASKit,
+    flow_name: &str,
+    agent_id: &str,
+    pin: &str,
+    ctx: AgentContext,
+    data: AgentData,
+) -> Option<(AgentContext, AgentData)> {
+    let held = {
+        let mut states = askit.flow_debug.lock().unwrap();
+        let Some(state) = states.get_mut(flow_name) else {
+            return Some((ctx, data));
+        };
+        let was_paused = state.paused;
+        match state.hold(agent_id, pin, ctx, data) {
+            Ok(input) => return Some(input),
+            Err(input) => (!was_paused).then_some(*input),
+        }
+    };
+    if let Some(input) = held {
+        log::info!(
+            "Flow {} paused at {}:{}",
+            flow_name,
+            input.agent_id,
+            input.pin
+        );
+        askit.emit_flow_paused(flow_name.to_string(), input);
+    }
+    None
+}
+
+/// Delivers the first pending input and keeps the flow paused.
+pub(crate) async fn step(askit: &ASKit, flow_name: &str) -> Result<(), AgentError> {
+    let input = {
+        let mut states = askit.flow_debug.lock().unwrap();
+        let Some(state) = states.get_mut(flow_name) else {
+            return Err(AgentError::Other(format!(
+                "Flow {} is not in debug mode",
+                flow_name
+            )));
+        };
+        state.pending.pop_front()
+    };
+    if let Some(input) = input {
+        deliver(askit, input).await;
+    }
+    Ok(())
+}
+
+/// Delivers all the pending inputs and resumes the flow.
+pub(crate) async fn resume(askit: &ASKit, flow_name: &str) -> Result<(), AgentError> {
+    let pending = {
+        let mut states = askit.flow_debug.lock().unwrap();
+        let Some(state) = states.get_mut(flow_name) else {
+            return Ok(());
+        };
+        state.paused = false;
+        std::mem::take(&mut state.pending)
+    };
+    for input in pending {
+        deliver(askit, input).await;
+    }
+    askit.emit_flow_resumed(flow_name.to_string());
+    Ok(())
+}
+
+async fn deliver(askit: &ASKit, input: PendingInput) {
+    let agent_id = input.agent_id.clone();
+    askit
+        .deliver_input(input.agent_id, input.ctx, input.pin, input.data)
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("Failed to send message to {}: {}", agent_id, e);
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hold_input() {
+        let mut state = FlowDebugState::default();
+        state.add_breakpoint(Breakpoint::new("2").port("in"));
+
+        assert!(
+            state
+                .hold("1", "in", AgentContext::new(), AgentData::unit())
+                .is_ok()
+        );
+        assert!(
+            state
+                .hold("2", "reset", AgentContext::new(), AgentData::unit())
+                .is_ok()
+        );
+
+        let input = state
+            .hold("2", "in", AgentContext::new(), AgentData::integer(1))
+            .unwrap_err();
+        assert_eq!(input.id, 1);
+        assert!(state.is_paused());
+
+        // everything is held while paused
+        assert!(
+            state
+                .hold("1", "in", AgentContext::new(), AgentData::unit())
+                .is_err()
+        );
+        assert_eq!(state.pending().len(), 2);
+    }
+}
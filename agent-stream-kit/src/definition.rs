@@ -1,13 +1,17 @@
 use std::collections::HashMap;
 use std::ops::Not;
 
+use chrono::{DateTime, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
 
 use super::agent::Agent;
 use super::askit::ASKit;
+use super::capability::AgentCapability;
 use super::config::AgentConfigs;
-use super::data::AgentValue;
+use super::data::{AgentData, AgentValue};
 use super::error::AgentError;
+use super::flow::AgentFlow;
+use super::process::ProcessCommand;
 
 pub type AgentDefinitions = HashMap<String, AgentDefinition>;
 
@@ -23,15 +27,43 @@ pub struct AgentDefinition {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 
+    /// Titles by locale, such as "ja" or "pt-BR".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_l10n: Option<L10nMap>,
+
+    /// Descriptions by locale.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_l10n: Option<L10nMap>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub category: Option<String>,
 
+    /// Labels to filter the palette by, such as "llm" or "io".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+
+    /// Extra words the agent can be found by.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keywords: Option<Vec<String>>,
+
+    /// Icon name or URL shown by the host.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub inputs: Option<Vec<String>>,
 
     #[serde(skip_serializing_if = "Option::is_none")]
     pub outputs: Option<Vec<String>>,
 
+    /// Input port -> documentation of the port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub input_docs: Option<HashMap<String, AgentPortDoc>>,
+
+    /// Output port -> documentation of the port
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_docs: Option<HashMap<String, AgentPortDoc>>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_configs: Option<AgentDefaultConfigs>,
 
@@ -44,10 +76,30 @@ pub struct AgentDefinition {
     #[serde(default, skip_serializing_if = "<&bool>::not")]
     pub native_thread: bool,
 
+    /// Runs the agent in a child process instead of `new_boxed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<ProcessCommand>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub capabilities: Option<Vec<AgentCapability>>,
+
+    /// Version of the agent. Flow nodes record it when they are created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub examples: Option<Vec<AgentExample>>,
+
     #[serde(skip)]
     pub new_boxed: Option<AgentNewBoxedFn>,
+
+    #[serde(skip)]
+    pub migrate_configs: Option<AgentMigrateConfigsFn>,
 }
 
+/// Locale -> localized text
+pub type L10nMap = HashMap<String, String>;
+
 pub type AgentDefaultConfigs = Vec<(String, AgentConfigEntry)>;
 pub type AgentGlobalConfigs = Vec<(String, AgentConfigEntry)>;
 
@@ -64,10 +116,20 @@ pub struct AgentConfigEntry {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
 
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_l10n: Option<L10nMap>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description_l10n: Option<L10nMap>,
+
     /// Indicates whether this configuration entry should be hidden from the user interface.
     /// If set to `true`, the entry will be hidden. The default behavior is to show the entry.
     #[serde(default, skip_serializing_if = "<&bool>::not")]
     pub hidden: bool,
+
+    /// The node does not start while the value is empty.
+    #[serde(default, skip_serializing_if = "<&bool>::not")]
+    pub required: bool,
 }
 
 pub type AgentDisplayConfigs = Vec<(String, AgentDisplayConfigEntry)>;
@@ -85,6 +147,10 @@ pub struct AgentDisplayConfigEntry {
 
     #[serde(default, skip_serializing_if = "<&bool>::not")]
     pub hide_title: bool,
+
+    /// JSON Schema of the value an interactive widget submits with `ASKit::submit_input`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<serde_json::Value>,
 }
 
 // #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -95,6 +161,95 @@ pub struct AgentDisplayConfigEntry {
 //     pub dir: Option<String>,
 // }
 
+/// Documentation of an input or output port, shown by editors as a tooltip.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct AgentPortDoc {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Kind of the data the port takes or sends, such as "message".
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub example: Option<AgentValue>,
+}
+
+impl AgentPortDoc {
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn kind(mut self, kind: &str) -> Self {
+        self.kind = Some(kind.into());
+        self
+    }
+
+    pub fn example<V: Into<AgentValue>>(mut self, example: V) -> Self {
+        self.example = Some(example.into());
+        self
+    }
+}
+
+/// Example usage of an agent, for editors and smoke tests.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct AgentExample {
+    pub title: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Mini flow showing the agent wired to others.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flow: Option<AgentFlow>,
+
+    /// Sample values sent to the input ports, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inputs: Vec<(String, AgentValue)>,
+
+    /// Values expected on the output ports for the sample inputs.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<(String, AgentValue)>,
+}
+
+impl AgentExample {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: title.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn flow(mut self, flow: AgentFlow) -> Self {
+        self.flow = Some(flow);
+        self
+    }
+
+    pub fn input<V: Into<AgentValue>>(mut self, pin: &str, value: V) -> Self {
+        self.inputs.push((pin.into(), value.into()));
+        self
+    }
+
+    pub fn output<V: Into<AgentValue>>(mut self, pin: &str, value: V) -> Self {
+        self.outputs.push((pin.into(), value.into()));
+        self
+    }
+}
+
 pub type AgentNewBoxedFn = fn(
     askit: ASKit,
     id: String,
@@ -102,6 +257,10 @@ pub type AgentNewBoxedFn = fn(
     configs: Option<AgentConfigs>,
 ) -> Result<Box<dyn Agent + Send + Sync>, AgentError>;
 
+/// Converts the configs saved by an older version of the agent.
+pub type AgentMigrateConfigsFn =
+    fn(from_version: u32, configs: AgentConfigs) -> Result<AgentConfigs, AgentError>;
+
 impl AgentDefinition {
     pub fn new(
         kind: impl Into<String>,
@@ -126,11 +285,44 @@ impl AgentDefinition {
         self
     }
 
+    pub fn with_title_l10n<K, V>(mut self, map: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.title_l10n = Some(l10n_map(map));
+        self
+    }
+
+    pub fn with_description_l10n<K, V>(mut self, map: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.description_l10n = Some(l10n_map(map));
+        self
+    }
+
     pub fn category(mut self, category: &str) -> Self {
         self.category = Some(category.into());
         self
     }
 
+    pub fn tags(mut self, tags: Vec<&str>) -> Self {
+        self.tags = Some(tags.into_iter().map(|x| x.into()).collect());
+        self
+    }
+
+    pub fn keywords(mut self, keywords: Vec<&str>) -> Self {
+        self.keywords = Some(keywords.into_iter().map(|x| x.into()).collect());
+        self
+    }
+
+    pub fn icon(mut self, icon: &str) -> Self {
+        self.icon = Some(icon.into());
+        self
+    }
+
     pub fn inputs(mut self, inputs: Vec<&str>) -> Self {
         self.inputs = Some(inputs.into_iter().map(|x| x.into()).collect());
         self
@@ -141,6 +333,49 @@ impl AgentDefinition {
         self
     }
 
+    pub fn with_input_doc<F>(mut self, port: &str, f: F) -> Self
+    where
+        F: FnOnce(AgentPortDoc) -> AgentPortDoc,
+    {
+        self.input_docs
+            .get_or_insert_with(HashMap::new)
+            .insert(port.into(), f(AgentPortDoc::default()));
+        self
+    }
+
+    pub fn with_output_doc<F>(mut self, port: &str, f: F) -> Self
+    where
+        F: FnOnce(AgentPortDoc) -> AgentPortDoc,
+    {
+        self.output_docs
+            .get_or_insert_with(HashMap::new)
+            .insert(port.into(), f(AgentPortDoc::default()));
+        self
+    }
+
+    pub fn input_doc(&self, port: &str) -> Option<&AgentPortDoc> {
+        self.input_docs.as_ref().and_then(|docs| docs.get(port))
+    }
+
+    pub fn output_doc(&self, port: &str) -> Option<&AgentPortDoc> {
+        self.output_docs.as_ref().and_then(|docs| docs.get(port))
+    }
+
+    /// Checks the data against the kind documented for the input port.
+    /// Ports without a documented kind take any data.
+    pub fn check_input(&self, port: &str, data: &AgentData) -> Result<(), AgentError> {
+        let Some(kind) = self.input_doc(port).and_then(|doc| doc.kind.as_ref()) else {
+            return Ok(());
+        };
+        if kind == "*" || *kind == data.kind {
+            return Ok(());
+        }
+        Err(AgentError::InvalidValue(format!(
+            "{}: port {} takes {} data, got {}",
+            self.name, port, kind, data.kind
+        )))
+    }
+
     // Default Configs
 
     pub fn default_configs(mut self, configs: Vec<(&str, AgentConfigEntry)>) -> Self {
@@ -204,6 +439,32 @@ impl AgentDefinition {
         self.number_config(key, 0.0)
     }
 
+    pub fn datetime_config(self, key: &str, default: DateTime<Utc>) -> Self {
+        self.datetime_config_with(key, default, |entry| entry)
+    }
+
+    pub fn datetime_config_with<F>(self, key: &str, default: DateTime<Utc>, f: F) -> Self
+    where
+        F: FnOnce(AgentConfigEntry) -> AgentConfigEntry,
+    {
+        self.config_type_with(key, AgentValue::datetime(default), "datetime", f)
+    }
+
+    pub fn duration_config(self, key: &str, default: TimeDelta) -> Self {
+        self.duration_config_with(key, default, |entry| entry)
+    }
+
+    pub fn duration_config_with<F>(self, key: &str, default: TimeDelta, f: F) -> Self
+    where
+        F: FnOnce(AgentConfigEntry) -> AgentConfigEntry,
+    {
+        self.config_type_with(key, AgentValue::duration(default), "duration", f)
+    }
+
+    pub fn duration_config_default(self, key: &str) -> Self {
+        self.duration_config(key, TimeDelta::zero())
+    }
+
     pub fn string_config(self, key: &str, default: impl Into<String>) -> Self {
         self.string_config_with(key, default, |entry| entry)
     }
@@ -526,6 +787,158 @@ impl AgentDefinition {
         self.native_thread = true;
         self
     }
+
+    /// Runs the agent in a child process, isolating the host from its crashes.
+    pub fn use_process(mut self, command: ProcessCommand) -> Self {
+        self.process = Some(command);
+        self
+    }
+
+    pub fn capabilities(mut self, capabilities: Vec<AgentCapability>) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    pub fn has_capability(&self, capability: AgentCapability) -> bool {
+        self.capabilities
+            .as_ref()
+            .is_some_and(|caps| caps.contains(&capability))
+    }
+
+    pub fn version(mut self, version: u32) -> Self {
+        self.version = Some(version);
+        self
+    }
+
+    pub fn example(mut self, example: AgentExample) -> Self {
+        self.examples.get_or_insert_with(Vec::new).push(example);
+        self
+    }
+
+    pub fn migrate_configs(mut self, f: AgentMigrateConfigsFn) -> Self {
+        self.migrate_configs = Some(f);
+        self
+    }
+
+    /// Copy with the title and description, and those of the config entries, in the locale.
+    ///
+    /// Texts without a translation for the locale are left as they are.
+    pub fn localized(&self, locale: &str) -> Self {
+        let mut def = self.clone();
+        if let Some(title) = l10n_lookup(&self.title_l10n, locale) {
+            def.title = Some(title);
+        }
+        if let Some(description) = l10n_lookup(&self.description_l10n, locale) {
+            def.description = Some(description);
+        }
+        for configs in [&mut def.default_configs, &mut def.global_configs]
+            .into_iter()
+            .flatten()
+        {
+            for (_, entry) in configs.iter_mut() {
+                *entry = entry.localized(locale);
+            }
+        }
+        def
+    }
+
+    /// Keys of the required configs that are empty, the global ones included.
+    pub fn missing_required_configs(
+        &self,
+        configs: Option<&AgentConfigs>,
+        global_configs: Option<&AgentConfigs>,
+    ) -> Vec<String> {
+        let missing = |entries: &Option<Vec<(String, AgentConfigEntry)>>,
+                       configs: Option<&AgentConfigs>| {
+            entries
+                .iter()
+                .flatten()
+                .filter(|(_, entry)| entry.required)
+                .filter(|(key, entry)| {
+                    let value = configs
+                        .and_then(|configs| configs.get(key).ok())
+                        .unwrap_or(&entry.value);
+                    is_empty_config_value(value)
+                })
+                .map(|(key, _)| key.clone())
+                .collect::<Vec<_>>()
+        };
+        let mut keys = missing(&self.default_configs, configs);
+        keys.extend(missing(&self.global_configs, global_configs));
+        keys
+    }
+
+    /// The default configs overridden by `overrides`. An override of another type than its
+    /// entry is converted when possible, e.g. "3" for an integer config.
+    pub fn materialize_configs(&self, overrides: Option<AgentConfigs>) -> AgentConfigs {
+        let entries = self.default_configs.as_deref().unwrap_or_default();
+        let mut configs = AgentConfigs::new();
+        for (key, entry) in entries {
+            configs.set(key.clone(), entry.value.clone());
+        }
+        for (key, value) in overrides.into_iter().flatten() {
+            let type_ = entries
+                .iter()
+                .find(|(k, _)| *k == key)
+                .and_then(|(_, entry)| entry.type_.as_deref());
+            let value = coerce_config_value(type_, value);
+            configs.set(key, value);
+        }
+        configs
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags
+            .as_ref()
+            .is_some_and(|tags| tags.iter().any(|t| t.eq_ignore_ascii_case(tag)))
+    }
+
+    /// Relevance of the definition to a search query, or `None` if it does not match.
+    ///
+    /// Every word of the query must match the name, title, category, tags, keywords or
+    /// description. Exact and prefix matches of the name and title rank highest.
+    pub fn search_score(&self, query: &str) -> Option<u32> {
+        let name = self.name.to_lowercase();
+        let title = self.title.as_deref().unwrap_or_default().to_lowercase();
+        let category = self.category.as_deref().unwrap_or_default().to_lowercase();
+        let description = self
+            .description
+            .as_deref()
+            .unwrap_or_default()
+            .to_lowercase();
+        let lower = |words: &Option<Vec<String>>| {
+            words
+                .iter()
+                .flatten()
+                .map(|w| w.to_lowercase())
+                .collect::<Vec<_>>()
+        };
+        let tags = lower(&self.tags);
+        let keywords = lower(&self.keywords);
+
+        let mut score = 0;
+        for term in query.split_whitespace().map(|t| t.to_lowercase()) {
+            let term_score = if name == term || title == term {
+                100
+            } else if title.starts_with(&term) || name.starts_with(&term) {
+                60
+            } else if tags.contains(&term) {
+                40
+            } else if keywords.iter().any(|k| k.starts_with(&term)) {
+                30
+            } else if title.contains(&term) || name.contains(&term) {
+                20
+            } else if category.contains(&term) {
+                10
+            } else if description.contains(&term) {
+                5
+            } else {
+                return None;
+            };
+            score += term_score;
+        }
+        Some(score)
+    }
 }
 
 impl AgentConfigEntry {
@@ -547,10 +960,93 @@ impl AgentConfigEntry {
         self
     }
 
+    pub fn with_title_l10n<K, V>(mut self, map: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.title_l10n = Some(l10n_map(map));
+        self
+    }
+
+    pub fn with_description_l10n<K, V>(mut self, map: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.description_l10n = Some(l10n_map(map));
+        self
+    }
+
+    pub fn localized(&self, locale: &str) -> Self {
+        let mut entry = self.clone();
+        if let Some(title) = l10n_lookup(&self.title_l10n, locale) {
+            entry.title = Some(title);
+        }
+        if let Some(description) = l10n_lookup(&self.description_l10n, locale) {
+            entry.description = Some(description);
+        }
+        entry
+    }
+
     pub fn hidden(mut self) -> Self {
         self.hidden = true;
         self
     }
+
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+}
+
+// Unit, a blank string or an empty array
+fn is_empty_config_value(value: &AgentValue) -> bool {
+    value.is_unit()
+        || value.as_str().is_some_and(|s| s.trim().is_empty())
+        || value.as_array().is_some_and(|arr| arr.is_empty())
+}
+
+fn coerce_config_value(type_: Option<&str>, value: AgentValue) -> AgentValue {
+    let coerced = match (type_, &value) {
+        (Some("boolean"), AgentValue::String(s)) => s.trim().parse().ok().map(AgentValue::boolean),
+        (Some("integer"), AgentValue::Number(_)) => value.to_i64().ok().map(AgentValue::integer),
+        (Some("integer"), AgentValue::String(s)) => s.trim().parse().ok().map(AgentValue::integer),
+        (Some("number"), AgentValue::Integer(_) | AgentValue::Unsigned(_)) => {
+            value.as_f64().map(AgentValue::number)
+        }
+        (Some("number"), AgentValue::String(s)) => s.trim().parse().ok().map(AgentValue::number),
+        (Some("string" | "text" | "password"), AgentValue::Boolean(b)) => {
+            Some(AgentValue::string(b.to_string()))
+        }
+        (Some("string" | "text" | "password"), AgentValue::Integer(i)) => {
+            Some(AgentValue::string(i.to_string()))
+        }
+        (Some("string" | "text" | "password"), AgentValue::Number(n)) => {
+            Some(AgentValue::string(n.to_string()))
+        }
+        _ => None,
+    };
+    coerced.unwrap_or(value)
+}
+
+fn l10n_map<K, V>(map: impl IntoIterator<Item = (K, V)>) -> L10nMap
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    map.into_iter().map(|(k, v)| (k.into(), v.into())).collect()
+}
+
+// Looks up the locale, then its language, e.g. "pt-BR" then "pt".
+fn l10n_lookup(map: &Option<L10nMap>, locale: &str) -> Option<String> {
+    let map = map.as_ref()?;
+    map.get(locale)
+        .or_else(|| {
+            let language = locale.split(['-', '_']).next()?;
+            map.get(language)
+        })
+        .cloned()
 }
 
 impl AgentDisplayConfigEntry {
@@ -577,6 +1073,11 @@ impl AgentDisplayConfigEntry {
         self.description = Some(description.into());
         self
     }
+
+    pub fn schema(mut self, schema: serde_json::Value) -> Self {
+        self.schema = Some(schema);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -634,6 +1135,106 @@ mod tests {
         assert_eq!(entry.1.hide_title, true);
     }
 
+    #[test]
+    fn test_search_score() {
+        let def = AgentDefinition::new("test", "std_http_request", None)
+            .title("HTTP Request")
+            .category("Std/Web")
+            .description("Sends a request to a URL")
+            .tags(vec!["io", "web"])
+            .keywords(vec!["fetch", "rest"]);
+
+        assert!(def.search_score("http").unwrap() > def.search_score("fetch").unwrap());
+        assert!(def.search_score("fetch").unwrap() > def.search_score("url").unwrap());
+        assert!(def.search_score("WEB request").is_some());
+        assert!(def.search_score("http sql").is_none());
+        assert_eq!(def.search_score(""), Some(0));
+        assert!(def.has_tag("IO"));
+    }
+
+    #[test]
+    fn test_localized() {
+        let def = AgentDefinition::new("test", "echo", None)
+            .title("Echo")
+            .with_title_l10n([("ja", "エコー"), ("pt-BR", "Eco")])
+            .string_config_with("text", "", |entry| {
+                entry.title("Text").with_title_l10n([("ja", "テキスト")])
+            });
+
+        let ja = def.localized("ja-JP");
+        assert_eq!(ja.title.as_deref(), Some("エコー"));
+        let entry = &ja.default_configs.as_ref().unwrap()[0].1;
+        assert_eq!(entry.title.as_deref(), Some("テキスト"));
+
+        assert_eq!(def.localized("pt-BR").title.as_deref(), Some("Eco"));
+        assert_eq!(def.localized("fr").title.as_deref(), Some("Echo"));
+    }
+
+    #[test]
+    fn test_missing_required_configs() {
+        let def = AgentDefinition::new("test", "chat", None)
+            .string_config_with("model", "", |entry| entry.required())
+            .string_config_default("system")
+            .string_global_config_with("api_key", "", |entry| entry.required());
+        assert_eq!(
+            def.missing_required_configs(None, None),
+            vec!["model", "api_key"]
+        );
+
+        let mut configs = AgentConfigs::new();
+        configs.set("model".into(), AgentValue::string("gpt"));
+        let mut global_configs = AgentConfigs::new();
+        global_configs.set("api_key".into(), AgentValue::string(" "));
+        assert_eq!(
+            def.missing_required_configs(Some(&configs), Some(&global_configs)),
+            vec!["api_key"]
+        );
+    }
+
+    #[test]
+    fn test_agent_example() {
+        let def = AgentDefinition::new("test", "echo", None).example(
+            AgentExample::new("Echo a string")
+                .input("in", "hello")
+                .output("out", "hello"),
+        );
+        let json = serde_json::to_value(&def).unwrap();
+        assert_eq!(
+            json["examples"],
+            serde_json::json!([{
+                "title": "Echo a string",
+                "inputs": [["in", "hello"]],
+                "outputs": [["out", "hello"]],
+            }])
+        );
+    }
+
+    #[test]
+    fn test_port_docs() {
+        let def = AgentDefinition::new("test", "chat", None)
+            .inputs(vec!["message"])
+            .with_input_doc("message", |doc| {
+                doc.title("Message")
+                    .description("Message to reply to")
+                    .kind("message")
+            });
+        let json = serde_json::to_value(&def).unwrap();
+        assert_eq!(
+            json["input_docs"]["message"],
+            serde_json::json!({
+                "title": "Message",
+                "description": "Message to reply to",
+                "kind": "message",
+            })
+        );
+
+        assert!(
+            def.check_input("message", &AgentData::string("hi"))
+                .is_err()
+        );
+        assert!(def.check_input("other", &AgentData::string("hi")).is_ok());
+    }
+
     #[test]
     fn test_serialize_agent_definition() {
         let def = AgentDefinition::new(
@@ -684,6 +1285,52 @@ mod tests {
         assert_eq!(entry.1.hide_title, true);
     }
 
+    #[test]
+    fn test_agent_definition_capabilities() {
+        let def = AgentDefinition::new("test", "read", None)
+            .capabilities(vec![AgentCapability::Filesystem]);
+        assert!(def.has_capability(AgentCapability::Filesystem));
+        assert!(!def.has_capability(AgentCapability::Network));
+
+        let json = serde_json::to_string(&def).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"test","name":"read","capabilities":["filesystem"]}"#
+        );
+        let def: AgentDefinition = serde_json::from_str(&json).unwrap();
+        assert_eq!(def.capabilities.unwrap(), vec![AgentCapability::Filesystem]);
+    }
+
+    #[test]
+    fn test_agent_definition_version() {
+        fn migrate(
+            from_version: u32,
+            mut configs: AgentConfigs,
+        ) -> Result<AgentConfigs, AgentError> {
+            assert_eq!(from_version, 1);
+            let name = configs.get_string_or_default("name");
+            configs.set("title".to_string(), AgentValue::string(name));
+            Ok(configs)
+        }
+
+        let def_v1 = AgentDefinition::new("test", "greet", None)
+            .version(1)
+            .string_config("name", "world");
+        let mut node = crate::flow::AgentFlowNode::new(&def_v1).unwrap();
+        assert_eq!(node.version, Some(1));
+
+        let def_v2 = def_v1.clone().version(2);
+        assert!(!node.clone().migrate(&def_v2).unwrap());
+
+        let def_v2 = def_v2.migrate_configs(migrate);
+        assert!(node.migrate(&def_v2).unwrap());
+        assert_eq!(node.version, Some(2));
+        assert_eq!(
+            node.configs.unwrap().get_string_or_default("title"),
+            "world"
+        );
+    }
+
     #[test]
     fn test_default_config_helpers() {
         let custom_object_value =
@@ -919,4 +1566,63 @@ mod tests {
         })
         .integer_display_config_with("hide_title_value", |entry| entry.hide_title())
     }
+
+    #[test]
+    fn test_materialize_configs() {
+        let def = AgentDefinition::new("test", "materialize", None)
+            .integer_config("count", 1)
+            .number_config("ratio", 0.5)
+            .boolean_config("flag", false)
+            .string_config("name", "a");
+
+        let configs = def.materialize_configs(None);
+        assert_eq!(configs.get_integer("count").unwrap(), 1);
+        assert_eq!(configs.get_string("name").unwrap(), "a");
+
+        let mut overrides = AgentConfigs::new();
+        overrides.set("count".into(), AgentValue::string("3"));
+        overrides.set("ratio".into(), AgentValue::integer(2));
+        overrides.set("flag".into(), AgentValue::string("true"));
+        overrides.set("name".into(), AgentValue::integer(7));
+        overrides.set("extra".into(), AgentValue::string("x"));
+        let configs = def.materialize_configs(Some(overrides));
+        assert_eq!(configs.get("count").unwrap(), &AgentValue::integer(3));
+        assert_eq!(configs.get("ratio").unwrap(), &AgentValue::number(2.0));
+        assert_eq!(configs.get("flag").unwrap(), &AgentValue::boolean(true));
+        assert_eq!(configs.get("name").unwrap(), &AgentValue::string("7"));
+        assert_eq!(configs.get_string("extra").unwrap(), "x");
+
+        // values that cannot be converted are kept as they are
+        let mut overrides = AgentConfigs::new();
+        overrides.set("count".into(), AgentValue::string("many"));
+        let configs = def.materialize_configs(Some(overrides));
+        assert_eq!(configs.get_string("count").unwrap(), "many");
+    }
+
+    #[test]
+    fn test_definitions_changed() {
+        use crate::{ASKitEvent, ASKitObserver};
+        use std::sync::{Arc, Mutex};
+
+        struct DefinitionsObserver(Arc<Mutex<Vec<String>>>);
+
+        impl ASKitObserver for DefinitionsObserver {
+            fn notify(&self, event: &ASKitEvent) {
+                if let ASKitEvent::DefinitionsChanged(def_name) = event {
+                    self.0.lock().unwrap().push(def_name.clone());
+                }
+            }
+        }
+
+        let askit = ASKit::new();
+        let changed = Arc::new(Mutex::new(Vec::new()));
+        askit.subscribe(Box::new(DefinitionsObserver(changed.clone())));
+
+        askit.register_agent(AgentDefinition::new("test", "plugin", None));
+        assert!(askit.get_agent_definition("plugin").is_some());
+        assert!(askit.unregister_agent("plugin").is_some());
+        assert!(askit.get_agent_definition("plugin").is_none());
+        assert!(askit.unregister_agent("plugin").is_none());
+        assert_eq!(*changed.lock().unwrap(), vec!["plugin", "plugin"]);
+    }
 }
@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::askit::ASKit;
+use super::context::AgentContext;
+use super::data::AgentData;
+use super::error::AgentError;
+
+/// At-least-once delivery of the inputs of a flow.
+///
+/// Each input gets a delivery id and is retained until `process` of the receiving
+/// agent returns `Ok`. Failed inputs are redelivered, and inputs still retained when
+/// an agent starts again, for example after a crash, are delivered to it again.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeliveryPolicy {
+    /// Number of deliveries of an input, the first one included.
+    pub max_attempts: u32,
+
+    /// Wait before a failed input is redelivered, in milliseconds.
+    pub retry_delay_ms: u64,
+}
+
+impl Default for DeliveryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            retry_delay_ms: DEFAULT_RETRY_DELAY_MS,
+        }
+    }
+}
+
+impl DeliveryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    pub fn retry_delay_ms(mut self, retry_delay_ms: u64) -> Self {
+        self.retry_delay_ms = retry_delay_ms;
+        self
+    }
+}
+
+const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+const DEFAULT_RETRY_DELAY_MS: u64 = 1000;
+
+/// Input retained until it is acknowledged.
+#[derive(Debug, Clone)]
+pub struct UnackedDelivery {
+    pub id: u64,
+    pub agent_id: String,
+    pub pin: String,
+    pub ctx: AgentContext,
+    pub data: AgentData,
+
+    /// Number of deliveries so far.
+    pub attempts: u32,
+}
+
+// Retained inputs of a flow in the acked delivery mode
+pub(crate) struct FlowDeliveryState {
+    policy: DeliveryPolicy,
+    unacked: BTreeMap<u64, UnackedDelivery>,
+    next_id: u64,
+}
+
+enum Failure {
+    Retry(UnackedDelivery),
+    GiveUp(UnackedDelivery),
+}
+
+impl FlowDeliveryState {
+    pub(crate) fn new(policy: DeliveryPolicy) -> Self {
+        Self {
+            policy,
+            unacked: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+
+    pub(crate) fn policy(&self) -> &DeliveryPolicy {
+        &self.policy
+    }
+
+    pub(crate) fn unacked(&self) -> Vec<UnackedDelivery> {
+        self.unacked.values().cloned().collect()
+    }
+
+    fn retain(&mut self, agent_id: &str, pin: &str, ctx: &AgentContext, data: &AgentData) -> u64 {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.unacked.insert(
+            id,
+            UnackedDelivery {
+                id,
+                agent_id: agent_id.to_string(),
+                pin: pin.to_string(),
+                ctx: ctx.clone(),
+                data: data.clone(),
+                attempts: 1,
+            },
+        );
+        id
+    }
+
+    fn ack(&mut self, id: u64) {
+        self.unacked.remove(&id);
+    }
+
+    fn fail(&mut self, id: u64) -> Option<Failure> {
+        let delivery = self.unacked.get_mut(&id)?;
+        if delivery.attempts >= self.policy.max_attempts {
+            return self.unacked.remove(&id).map(Failure::GiveUp);
+        }
+        delivery.attempts += 1;
+        Some(Failure::Retry(delivery.clone()))
+    }
+
+    // Inputs of the agent to be delivered again, counted as new attempts.
+    fn take_for_restart(&mut self, agent_id: &str) -> Vec<UnackedDelivery> {
+        let max_attempts = self.policy.max_attempts;
+        let mut redeliveries = Vec::new();
+        self.unacked.retain(|_, delivery| {
+            if delivery.agent_id != agent_id {
+                return true;
+            }
+            if delivery.attempts >= max_attempts {
+                log::error!(
+                    "Dropped input {} to {} after {} attempts",
+                    delivery.id,
+                    agent_id,
+                    delivery.attempts
+                );
+                return false;
+            }
+            delivery.attempts += 1;
+            redeliveries.push(delivery.clone());
+            true
+        });
+        redeliveries
+    }
+
+    pub(crate) fn remove_agent(&mut self, agent_id: &str) {
+        self.unacked
+            .retain(|_, delivery| delivery.agent_id != agent_id);
+    }
+}
+
+// Called when an input is queued for an agent.
+// Returns the delivery id when the flow is in the acked delivery mode.
+pub(crate) fn retain(
+    askit: &ASKit,
+    flow_name: &str,
+    agent_id: &str,
+    pin: &str,
+    ctx: &AgentContext,
+    data: &AgentData,
+) -> Option<u64> {
+    let mut states = askit.flow_deliveries.lock().unwrap();
+    let state = states.get_mut(flow_name)?;
+    Some(state.retain(agent_id, pin, ctx, data))
+}
+
+// Called by the agent loops after an input has been processed.
+// Returns true when the failed input is going to be redelivered.
+pub(crate) fn complete(
+    askit: &ASKit,
+    flow_name: &str,
+    delivery_id: Option<u64>,
+    result: &Result<(), AgentError>,
+) -> bool {
+    let Some(id) = delivery_id else {
+        return false;
+    };
+    let (failure, delay) = {
+        let mut states = askit.flow_deliveries.lock().unwrap();
+        let Some(state) = states.get_mut(flow_name) else {
+            return false;
+        };
+        if result.is_ok() {
+            state.ack(id);
+            return false;
+        }
+        (
+            state.fail(id),
+            Duration::from_millis(state.policy.retry_delay_ms),
+        )
+    };
+    match failure {
+        Some(Failure::Retry(delivery)) => {
+            let askit = askit.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                redeliver(&askit, delivery).await;
+            });
+            true
+        }
+        Some(Failure::GiveUp(delivery)) => {
+            let message = format!(
+                "Gave up delivering input to {} after {} attempts",
+                delivery.pin, delivery.attempts
+            );
+            log::error!("{}: {}", delivery.agent_id, message);
+            askit.emit_agent_error(delivery.agent_id, message);
+            false
+        }
+        None => false,
+    }
+}
+
+// Called after an agent has started.
+pub(crate) async fn redeliver_unacked(askit: &ASKit, flow_name: &str, agent_id: &str) {
+    let redeliveries = {
+        let mut states = askit.flow_deliveries.lock().unwrap();
+        let Some(state) = states.get_mut(flow_name) else {
+            return;
+        };
+        state.take_for_restart(agent_id)
+    };
+    for delivery in redeliveries {
+        redeliver(askit, delivery).await;
+    }
+}
+
+async fn redeliver(askit: &ASKit, delivery: UnackedDelivery) {
+    let agent_id = delivery.agent_id.clone();
+    askit
+        .queue_input(
+            delivery.agent_id,
+            delivery.ctx,
+            delivery.pin,
+            delivery.data,
+            Some(delivery.id),
+        )
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("Failed to redeliver input to {}: {}", agent_id, e);
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_delivery_state() {
+        let mut state = FlowDeliveryState::new(DeliveryPolicy::new().max_attempts(2));
+        let ctx = AgentContext::new();
+
+        let first = state.retain("1", "in", &ctx, &AgentData::integer(1));
+        let second = state.retain("1", "in", &ctx, &AgentData::integer(2));
+        let other = state.retain("2", "in", &ctx, &AgentData::integer(3));
+        assert_eq!(state.unacked().len(), 3);
+
+        state.ack(first);
+        assert!(matches!(state.fail(second), Some(Failure::Retry(d)) if d.attempts == 2));
+        assert!(matches!(state.fail(second), Some(Failure::GiveUp(_))));
+        assert!(state.fail(second).is_none());
+
+        let redeliveries = state.take_for_restart("2");
+        assert_eq!(redeliveries.len(), 1);
+        assert_eq!(redeliveries[0].id, other);
+        assert_eq!(redeliveries[0].attempts, 2);
+
+        // out of attempts
+        assert!(state.take_for_restart("2").is_empty());
+        assert!(state.unacked().is_empty());
+    }
+}
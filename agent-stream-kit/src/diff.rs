@@ -0,0 +1,263 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::error::AgentError;
+use super::flow::AgentFlow;
+
+/// Changes between two versions of a flow, from `diff_flows`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlowDiff {
+    pub changes: Vec<FlowChange>,
+}
+
+impl FlowDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FlowChange {
+    /// Name or extensions of the flow itself.
+    FlowChanged {
+        fields: Vec<FieldChange>,
+    },
+    NodeAdded {
+        id: String,
+        node: Value,
+    },
+    NodeRemoved {
+        id: String,
+        node: Value,
+    },
+    NodeChanged {
+        id: String,
+        fields: Vec<FieldChange>,
+    },
+    EdgeAdded {
+        id: String,
+        edge: Value,
+    },
+    EdgeRemoved {
+        id: String,
+        edge: Value,
+    },
+    EdgeChanged {
+        id: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+/// Value at a JSON pointer, such as "/configs/model", before and after.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub path: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Value>,
+}
+
+impl AgentFlow {
+    /// JSON with the nodes and edges sorted by id and the keys of objects sorted, so
+    /// that the same flow always serializes the same way.
+    pub fn to_canonical_json(&self) -> Result<String, AgentError> {
+        let json = serde_json::to_string_pretty(&canonical_value(self)?)
+            .map_err(|e| AgentError::SerializationError(e.to_string()))?;
+        Ok(json)
+    }
+}
+
+fn canonical_value(flow: &AgentFlow) -> Result<Value, AgentError> {
+    let mut value =
+        serde_json::to_value(flow).map_err(|e| AgentError::SerializationError(e.to_string()))?;
+    for key in ["nodes", "edges"] {
+        if let Some(Value::Array(items)) = value.get_mut(key) {
+            items.sort_by(|a, b| id_of(a).cmp(id_of(b)));
+        }
+    }
+    Ok(sort_keys(value))
+}
+
+fn id_of(value: &Value) -> &str {
+    value
+        .get("id")
+        .and_then(|id| id.as_str())
+        .unwrap_or_default()
+}
+
+fn sort_keys(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let sorted = map
+                .into_iter()
+                .map(|(k, v)| (k, sort_keys(v)))
+                .collect::<BTreeMap<_, _>>();
+            Value::Object(sorted.into_iter().collect::<Map<_, _>>())
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(sort_keys).collect()),
+        other => other,
+    }
+}
+
+/// Structured changes turning the flow `a` into `b`. Nodes and edges are matched by id.
+pub fn diff_flows(a: &AgentFlow, b: &AgentFlow) -> Result<FlowDiff, AgentError> {
+    let mut a = canonical_value(a)?;
+    let mut b = canonical_value(b)?;
+    let mut changes = Vec::new();
+
+    let a_nodes = take_items(&mut a, "nodes");
+    let b_nodes = take_items(&mut b, "nodes");
+    let a_edges = take_items(&mut a, "edges");
+    let b_edges = take_items(&mut b, "edges");
+
+    let mut fields = Vec::new();
+    diff_values("", Some(&a), Some(&b), &mut fields);
+    if !fields.is_empty() {
+        changes.push(FlowChange::FlowChanged { fields });
+    }
+
+    diff_items(
+        a_nodes,
+        b_nodes,
+        &mut changes,
+        |id, node| FlowChange::NodeAdded { id, node },
+        |id, node| FlowChange::NodeRemoved { id, node },
+        |id, fields| FlowChange::NodeChanged { id, fields },
+    );
+    diff_items(
+        a_edges,
+        b_edges,
+        &mut changes,
+        |id, edge| FlowChange::EdgeAdded { id, edge },
+        |id, edge| FlowChange::EdgeRemoved { id, edge },
+        |id, fields| FlowChange::EdgeChanged { id, fields },
+    );
+
+    Ok(FlowDiff { changes })
+}
+
+fn take_items(value: &mut Value, key: &str) -> BTreeMap<String, Value> {
+    let Some(Value::Array(items)) = value.as_object_mut().and_then(|obj| obj.remove(key)) else {
+        return BTreeMap::new();
+    };
+    items
+        .into_iter()
+        .map(|item| (id_of(&item).to_string(), item))
+        .collect()
+}
+
+fn diff_items(
+    mut a: BTreeMap<String, Value>,
+    b: BTreeMap<String, Value>,
+    changes: &mut Vec<FlowChange>,
+    added: impl Fn(String, Value) -> FlowChange,
+    removed: impl Fn(String, Value) -> FlowChange,
+    changed: impl Fn(String, Vec<FieldChange>) -> FlowChange,
+) {
+    let mut added_items = Vec::new();
+    for (id, after) in b {
+        match a.remove(&id) {
+            Some(before) => {
+                let mut fields = Vec::new();
+                diff_values("", Some(&before), Some(&after), &mut fields);
+                if !fields.is_empty() {
+                    changes.push(changed(id, fields));
+                }
+            }
+            None => added_items.push(added(id, after)),
+        }
+    }
+    changes.extend(a.into_iter().map(|(id, before)| removed(id, before)));
+    changes.extend(added_items);
+}
+
+fn diff_values(path: &str, a: Option<&Value>, b: Option<&Value>, fields: &mut Vec<FieldChange>) {
+    match (a, b) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let keys = a
+                .keys()
+                .chain(b.keys())
+                .collect::<std::collections::BTreeSet<_>>();
+            for key in keys {
+                let child = format!("{}/{}", path, key.replace('~', "~0").replace('/', "~1"));
+                diff_values(&child, a.get(key), b.get(key), fields);
+            }
+        }
+        (a, b) if a != b => fields.push(FieldChange {
+            path: path.to_string(),
+            before: a.cloned(),
+            after: b.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AgentConfigs;
+    use crate::data::AgentValue;
+    use crate::flow::{AgentFlowEdge, AgentFlowNode};
+
+    fn node(id: &str, model: &str) -> AgentFlowNode {
+        let mut configs = AgentConfigs::new();
+        configs.set("model".into(), AgentValue::string(model));
+        AgentFlowNode {
+            id: id.to_string(),
+            def_name: "chat".to_string(),
+            configs: Some(configs),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let mut a = AgentFlow::new("flow".into());
+        a.set_nodes(vec![node("2", "x"), node("1", "y")]);
+        let mut b = AgentFlow::new("flow".into());
+        b.set_nodes(vec![node("1", "y"), node("2", "x")]);
+        assert_eq!(
+            a.to_canonical_json().unwrap(),
+            b.to_canonical_json().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_diff_flows() {
+        let mut a = AgentFlow::new("flow".into());
+        a.set_nodes(vec![node("1", "x"), node("2", "x")]);
+        let mut b = AgentFlow::new("flow".into());
+        b.set_nodes(vec![node("1", "y"), node("3", "x")]);
+        b.add_edge(AgentFlowEdge {
+            id: "e".into(),
+            source: "1".into(),
+            target: "3".into(),
+            ..Default::default()
+        });
+
+        let diff = diff_flows(&a, &b).unwrap();
+        assert_eq!(diff.changes.len(), 4);
+        assert_eq!(
+            diff.changes[0],
+            FlowChange::NodeChanged {
+                id: "1".into(),
+                fields: vec![FieldChange {
+                    path: "/configs/model".into(),
+                    before: Some("x".into()),
+                    after: Some("y".into()),
+                }],
+            }
+        );
+        assert!(matches!(&diff.changes[1], FlowChange::NodeRemoved { id, .. } if id == "2"));
+        assert!(matches!(&diff.changes[2], FlowChange::NodeAdded { id, .. } if id == "3"));
+        assert!(matches!(&diff.changes[3], FlowChange::EdgeAdded { id, .. } if id == "e"));
+
+        assert!(diff_flows(&a, &a).unwrap().is_empty());
+    }
+}
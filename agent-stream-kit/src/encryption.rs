@@ -0,0 +1,121 @@
+use std::num::NonZeroU32;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::pbkdf2;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use super::error::AgentError;
+use super::flow::AgentFlow;
+
+// Encrypted flow as exported
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedFlow {
+    version: u32,
+    kdf: String,
+    iterations: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Serializes the flow, configs included, and encrypts it with a key derived from
+/// the passphrase (PBKDF2-HMAC-SHA256, AES-256-GCM).
+pub fn encrypt_flow(flow: &AgentFlow, passphrase: &str) -> Result<String, AgentError> {
+    let rng = SystemRandom::new();
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill(&mut salt)
+        .and_then(|_| rng.fill(&mut nonce))
+        .map_err(|_| AgentError::EncryptionError("Failed to generate random bytes".into()))?;
+
+    let key = derive_key(passphrase, &salt, ITERATIONS)?;
+    let mut data =
+        serde_json::to_vec(flow).map_err(|e| AgentError::SerializationError(e.to_string()))?;
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(KDF),
+        &mut data,
+    )
+    .map_err(|_| AgentError::EncryptionError("Failed to encrypt the flow".into()))?;
+
+    let encrypted = EncryptedFlow {
+        version: VERSION,
+        kdf: KDF.to_string(),
+        iterations: ITERATIONS,
+        salt: STANDARD.encode(salt),
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(data),
+    };
+    serde_json::to_string_pretty(&encrypted)
+        .map_err(|e| AgentError::SerializationError(e.to_string()))
+}
+
+/// Decrypts a flow exported by [`encrypt_flow`].
+pub fn decrypt_flow(encrypted: &str, passphrase: &str) -> Result<AgentFlow, AgentError> {
+    let encrypted: EncryptedFlow = serde_json::from_str(encrypted)
+        .map_err(|e| AgentError::SerializationError(e.to_string()))?;
+    if encrypted.version != VERSION || encrypted.kdf != KDF {
+        return Err(AgentError::EncryptionError(format!(
+            "Unsupported format: version {}, {}",
+            encrypted.version, encrypted.kdf
+        )));
+    }
+    let decode = |s: &str| {
+        STANDARD
+            .decode(s)
+            .map_err(|e| AgentError::EncryptionError(e.to_string()))
+    };
+    let salt = decode(&encrypted.salt)?;
+    let nonce = Nonce::try_assume_unique_for_key(&decode(&encrypted.nonce)?)
+        .map_err(|_| AgentError::EncryptionError("Invalid nonce".into()))?;
+    let mut data = decode(&encrypted.ciphertext)?;
+
+    let key = derive_key(passphrase, &salt, encrypted.iterations)?;
+    let plain = key
+        .open_in_place(nonce, Aad::from(KDF), &mut data)
+        .map_err(|_| AgentError::EncryptionError("Wrong passphrase or corrupted data".into()))?;
+    serde_json::from_slice(plain).map_err(|e| AgentError::SerializationError(e.to_string()))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Result<LessSafeKey, AgentError> {
+    let iterations = NonZeroU32::new(iterations)
+        .ok_or_else(|| AgentError::EncryptionError("Invalid iterations".into()))?;
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    let key = UnboundKey::new(&AES_256_GCM, &key)
+        .map_err(|_| AgentError::EncryptionError("Invalid key".into()))?;
+    Ok(LessSafeKey::new(key))
+}
+
+const VERSION: u32 = 1;
+const KDF: &str = "pbkdf2-sha256";
+const ITERATIONS: u32 = 100_000;
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_flow() {
+        let flow = AgentFlow::new("secret".into());
+        let encrypted = encrypt_flow(&flow, "passphrase").unwrap();
+        assert!(!encrypted.contains("secret"));
+
+        let decrypted = decrypt_flow(&encrypted, "passphrase").unwrap();
+        assert_eq!(decrypted.name(), "secret");
+
+        let err = decrypt_flow(&encrypted, "wrong").unwrap_err();
+        assert!(matches!(err, AgentError::EncryptionError(_)));
+    }
+}
@@ -1,3 +1,6 @@
+use std::error::Error as StdError;
+use std::fmt::Display;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -11,12 +14,21 @@ pub enum AgentError {
     #[error("{0}: Agent definition \"{1}\" is invalid")]
     InvalidDefinition(String, String),
 
+    #[error("Invalid agent flow {0}: {1}")]
+    InvalidFlow(String, String),
+
     #[error("Invalid agent flow name: {0}")]
     InvalidFlowName(String),
 
     #[error("Invalid {0} value")]
     InvalidValue(String),
 
+    #[error("Invalid value path: {0}")]
+    InvalidPath(String),
+
+    #[error("Data of kind \"{0}\" does not match its schema: {1}")]
+    SchemaViolation(String, String),
+
     #[error("{0}: Agent definition \"{1}\" is missing")]
     MissingDefinition(String, String),
 
@@ -56,6 +68,9 @@ pub enum AgentError {
     #[error("Edge {0} not found")]
     EdgeNotFound(String),
 
+    #[error("Invalid edge \"{0}\", expected \"node:port -> node:port\"")]
+    InvalidEdge(String),
+
     #[error("Agent flow {0} not found")]
     FlowNotFound(String),
 
@@ -95,8 +110,14 @@ pub enum AgentError {
     #[error("No configuration available")]
     NoConfig,
 
-    #[error("Unknown configuration: {0}")]
-    UnknownConfig(String),
+    #[error("{0}: Missing required configuration: {1}")]
+    MissingRequiredConfig(String, String),
+
+    #[error("Missing configuration: {0}")]
+    ConfigMissing(String),
+
+    #[error("Configuration {0} is not {1}")]
+    ConfigTypeMismatch(String, String), // (key, expected type)
 
     #[error("No global configuration available")]
     NoGlobalConfig,
@@ -104,6 +125,253 @@ pub enum AgentError {
     #[error("Pin not found: {0}")]
     PinNotFound(String),
 
+    #[error("{0}: Permission denied for capability \"{1}\"")]
+    PermissionDenied(String, String),
+
+    #[error("{0}: Quota exceeded for {1}")]
+    QuotaExceeded(String, String),
+
+    #[error("{0}: Deadline exceeded")]
+    DeadlineExceeded(String),
+
+    #[error("Encryption error: {0}")]
+    EncryptionError(String),
+
     #[error("Agent error: {0}")]
     Other(String),
+
+    /// Error of an external library or service, keeping the original error as its source.
+    #[error("{message}: {source}")]
+    External {
+        message: String,
+        retryable: bool,
+        #[source]
+        source: Box<dyn StdError + Send + Sync>,
+    },
+
+    /// Error raised by an agent while it processed an input on the port.
+    #[error("{agent_id}:{port}: {source}")]
+    InAgent {
+        agent_id: String,
+        port: String,
+        #[source]
+        source: Box<AgentError>,
+    },
+}
+
+impl AgentError {
+    /// Wraps an error of a library or service that is not worth retrying.
+    pub fn external(
+        message: impl Into<String>,
+        source: impl StdError + Send + Sync + 'static,
+    ) -> Self {
+        AgentError::External {
+            message: message.into(),
+            retryable: false,
+            source: Box::new(source),
+        }
+    }
+
+    /// Wraps a transient error of a library or service, such as a timeout or a rate limit.
+    pub fn transient(
+        message: impl Into<String>,
+        source: impl StdError + Send + Sync + 'static,
+    ) -> Self {
+        AgentError::External {
+            message: message.into(),
+            retryable: true,
+            source: Box::new(source),
+        }
+    }
+
+    /// Records the agent and the port where the error was raised. Keeps the first ones.
+    pub fn in_agent(self, agent_id: impl Into<String>, port: impl Into<String>) -> Self {
+        if matches!(self, AgentError::InAgent { .. }) {
+            return self;
+        }
+        AgentError::InAgent {
+            agent_id: agent_id.into(),
+            port: port.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Whether the same operation may succeed if it is tried again later.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AgentError::InAgent { source, .. } => source.is_retryable(),
+            AgentError::External { retryable, .. } => *retryable,
+            AgentError::IoError(_)
+            | AgentError::SendMessageFailed(_)
+            | AgentError::QuotaExceeded(_, _)
+            | AgentError::DeadlineExceeded(_) => true,
+            _ => false,
+        }
+    }
+
+    /// Agent that raised the error, if known.
+    pub fn agent_id(&self) -> Option<&str> {
+        match self {
+            AgentError::InAgent { agent_id, .. } => Some(agent_id),
+            _ => None,
+        }
+    }
+
+    /// Input port being processed when the error was raised, if known.
+    pub fn port(&self) -> Option<&str> {
+        match self {
+            AgentError::InAgent { port, .. } => Some(port),
+            _ => None,
+        }
+    }
+
+    /// The error without the agent and port it was raised at.
+    pub fn root(&self) -> &AgentError {
+        match self {
+            AgentError::InAgent { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+}
+
+impl From<std::io::Error> for AgentError {
+    fn from(e: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        let retryable = matches!(
+            e.kind(),
+            ErrorKind::TimedOut
+                | ErrorKind::Interrupted
+                | ErrorKind::WouldBlock
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::ConnectionRefused
+        );
+        AgentError::External {
+            message: "IO error".to_string(),
+            retryable,
+            source: Box::new(e),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AgentError {
+    fn from(e: serde_json::Error) -> Self {
+        AgentError::external("JSON error", e)
+    }
+}
+
+/// Shorthands for turning errors of other libraries into [`AgentError`]s with a message.
+///
+/// `reader.read().ctx_io("Failed to read the file")?` replaces
+/// `map_err(|e| AgentError::IoError(format!("Failed to read the file: {}", e)))?`.
+/// The runtime adds the agent id and the port to errors returned from `process`.
+pub trait ResultExt<T> {
+    fn ctx_invalid_value(self, message: impl Display) -> Result<T, AgentError>;
+
+    fn ctx_invalid_config(self, message: impl Display) -> Result<T, AgentError>;
+
+    fn ctx_io(self, message: impl Display) -> Result<T, AgentError>;
+
+    fn ctx_serialization(self, message: impl Display) -> Result<T, AgentError>;
+
+    fn ctx_other(self, message: impl Display) -> Result<T, AgentError>;
+}
+
+impl<T, E: Display> ResultExt<T> for Result<T, E> {
+    fn ctx_invalid_value(self, message: impl Display) -> Result<T, AgentError> {
+        self.map_err(|e| AgentError::InvalidValue(format!("{}: {}", message, e)))
+    }
+
+    fn ctx_invalid_config(self, message: impl Display) -> Result<T, AgentError> {
+        self.map_err(|e| AgentError::InvalidConfig(format!("{}: {}", message, e)))
+    }
+
+    fn ctx_io(self, message: impl Display) -> Result<T, AgentError> {
+        self.map_err(|e| AgentError::IoError(format!("{}: {}", message, e)))
+    }
+
+    fn ctx_serialization(self, message: impl Display) -> Result<T, AgentError> {
+        self.map_err(|e| AgentError::SerializationError(format!("{}: {}", message, e)))
+    }
+
+    fn ctx_other(self, message: impl Display) -> Result<T, AgentError> {
+        self.map_err(|e| AgentError::Other(format!("{}: {}", message, e)))
+    }
+}
+
+/// `AgentError::InvalidValue` with a formatted message.
+#[macro_export]
+macro_rules! invalid_value {
+    ($($arg:tt)*) => {
+        $crate::AgentError::InvalidValue(format!($($arg)*))
+    };
+}
+
+/// `AgentError::IoError` with a formatted message.
+#[macro_export]
+macro_rules! io_error {
+    ($($arg:tt)*) => {
+        $crate::AgentError::IoError(format!($($arg)*))
+    };
+}
+
+/// Returns early with an `AgentError::InvalidValue` unless the condition holds.
+#[macro_export]
+macro_rules! ensure_valid {
+    ($cond:expr, $($arg:tt)*) => {
+        if !$cond {
+            return Err($crate::invalid_value!($($arg)*));
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_classification() {
+        let io = std::io::Error::new(std::io::ErrorKind::TimedOut, "slow");
+        let err = AgentError::from(io).in_agent("1", "in");
+        assert!(err.is_retryable());
+        assert_eq!(err.agent_id(), Some("1"));
+        assert_eq!(err.port(), Some("in"));
+        assert_eq!(err.to_string(), "1:in: IO error: slow");
+
+        // the source chain reaches the original error
+        let source = err.source().unwrap();
+        assert!(source.source().unwrap().is::<std::io::Error>());
+
+        let err = AgentError::InvalidValue("x".into())
+            .in_agent("1", "in")
+            .in_agent("2", "out");
+        assert!(!err.is_retryable());
+        assert_eq!(err.agent_id(), Some("1"));
+        assert!(matches!(err.root(), AgentError::InvalidValue(_)));
+    }
+
+    fn parse_count(s: &str) -> Result<u32, AgentError> {
+        let count = s.parse::<u32>().ctx_invalid_value("count")?;
+        crate::ensure_valid!(count > 0, "count must be positive, got {}", count);
+        Ok(count)
+    }
+
+    #[test]
+    fn test_result_ext() {
+        assert_eq!(parse_count("3").unwrap(), 3);
+        assert!(matches!(
+            parse_count("x"),
+            Err(AgentError::InvalidValue(message)) if message == "count: invalid digit found in string"
+        ));
+        assert!(matches!(
+            parse_count("0"),
+            Err(AgentError::InvalidValue(message)) if message == "count must be positive, got 0"
+        ));
+        let err: Result<(), _> = Err("denied").ctx_io("Failed to open");
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "IO error: Failed to open: denied"
+        );
+        assert_eq!(io_error!("{} bytes", 3).to_string(), "IO error: 3 bytes");
+    }
 }
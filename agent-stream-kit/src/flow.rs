@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::ops::Not;
 use std::sync::atomic::AtomicUsize;
 
 use serde::{Deserialize, Serialize};
@@ -6,8 +7,10 @@ use serde_json::Value;
 
 use super::askit::ASKit;
 use super::config::AgentConfigs;
+use super::data::AgentValue;
 use super::definition::AgentDefinition;
 use super::error::AgentError;
+use super::note::{CONFIG_TEXT, NOTE_DEF_NAME};
 
 pub type AgentFlows = HashMap<String, AgentFlow>;
 
@@ -17,18 +20,33 @@ pub struct AgentFlow {
 
     nodes: Vec<AgentFlowNode>,
 
+    // Edges can also be written as "source:port -> target:port".
+    #[serde(deserialize_with = "deserialize_edges")]
     edges: Vec<AgentFlowEdge>,
 
+    /// Viewport of the editor, shared by the frontends.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub viewport: Option<FlowViewport>,
+
     #[serde(flatten)]
     pub extensions: HashMap<String, Value>,
 }
 
+/// Pan and zoom of the editor showing a flow.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FlowViewport {
+    pub x: f64,
+    pub y: f64,
+    pub zoom: f64,
+}
+
 impl AgentFlow {
     pub fn new(name: String) -> Self {
         Self {
             name,
             nodes: Vec::new(),
             edges: Vec::new(),
+            viewport: None,
             extensions: HashMap::new(),
         }
     }
@@ -49,6 +67,14 @@ impl AgentFlow {
         self.name = new_name;
     }
 
+    pub fn get_node(&self, node_id: &str) -> Option<&AgentFlowNode> {
+        self.nodes.iter().find(|node| node.id == node_id)
+    }
+
+    pub fn get_node_mut(&mut self, node_id: &str) -> Option<&mut AgentFlowNode> {
+        self.nodes.iter_mut().find(|node| node.id == node_id)
+    }
+
     pub fn add_node(&mut self, node: AgentFlowNode) {
         self.nodes.push(node);
     }
@@ -61,6 +87,10 @@ impl AgentFlow {
         self.nodes = nodes;
     }
 
+    pub fn get_edge_mut(&mut self, edge_id: &str) -> Option<&mut AgentFlowEdge> {
+        self.edges.iter_mut().find(|edge| edge.id == edge_id)
+    }
+
     pub fn add_edge(&mut self, edge: AgentFlowEdge) {
         self.edges.push(edge);
     }
@@ -78,11 +108,11 @@ impl AgentFlow {
         self.edges = edges;
     }
 
+    /// Starts the enabled nodes, sinks first, so that no node sends to one not started yet.
+    ///
+    /// Returns after every node has finished starting.
     pub async fn start(&self, askit: &ASKit) -> Result<(), AgentError> {
-        for agent in self.nodes.iter() {
-            if !agent.enabled {
-                continue;
-            }
+        for agent in self.start_order() {
             askit.start_agent(&agent.id).await.unwrap_or_else(|e| {
                 log::error!("Failed to start agent {}: {}", agent.id, e);
             });
@@ -90,11 +120,9 @@ impl AgentFlow {
         Ok(())
     }
 
+    /// Stops the enabled nodes, sources first.
     pub async fn stop(&self, askit: &ASKit) -> Result<(), AgentError> {
-        for agent in self.nodes.iter() {
-            if !agent.enabled {
-                continue;
-            }
+        for agent in self.start_order().into_iter().rev() {
             askit.stop_agent(&agent.id).await.unwrap_or_else(|e| {
                 log::error!("Failed to stop agent {}: {}", agent.id, e);
             });
@@ -102,6 +130,84 @@ impl AgentFlow {
         Ok(())
     }
 
+    /// Checks that the note nodes are not connected by edges.
+    pub fn validate(&self) -> Result<(), AgentError> {
+        for edge in self.edges.iter() {
+            for node_id in [&edge.source, &edge.target] {
+                if self.get_node(node_id).is_some_and(|node| node.is_note()) {
+                    return Err(AgentError::InvalidFlow(
+                        self.name.clone(),
+                        format!("note {} cannot have edges", node_id),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Enabled nodes in reverse topological order: every node comes after the nodes it sends to.
+    ///
+    /// Nodes in cycles follow in their original order.
+    pub fn start_order(&self) -> Vec<&AgentFlowNode> {
+        let enabled = self
+            .nodes
+            .iter()
+            .filter(|node| node.enabled && !node.is_note())
+            .collect::<Vec<_>>();
+
+        // node id -> ids of the enabled nodes it sends to
+        let mut targets: HashMap<&str, Vec<&str>> = HashMap::new();
+        for edge in self.edges.iter() {
+            if edge.source != edge.target
+                && enabled.iter().any(|node| node.id == edge.source)
+                && enabled.iter().any(|node| node.id == edge.target)
+            {
+                let node_targets = targets.entry(edge.source.as_str()).or_default();
+                if !node_targets.contains(&edge.target.as_str()) {
+                    node_targets.push(edge.target.as_str());
+                }
+            }
+        }
+
+        // node id -> number of its targets not ordered yet
+        let mut pending = enabled
+            .iter()
+            .map(|node| {
+                let count = targets.get(node.id.as_str()).map_or(0, |t| t.len());
+                (node.id.as_str(), count)
+            })
+            .collect::<HashMap<_, _>>();
+        let mut ready = enabled
+            .iter()
+            .filter(|node| pending[node.id.as_str()] == 0)
+            .copied()
+            .collect::<VecDeque<_>>();
+        let mut order = Vec::with_capacity(enabled.len());
+        while let Some(node) = ready.pop_front() {
+            order.push(node);
+            for source in enabled.iter() {
+                if targets
+                    .get(source.id.as_str())
+                    .is_some_and(|t| t.contains(&node.id.as_str()))
+                {
+                    let count = pending.get_mut(source.id.as_str()).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        ready.push_back(source);
+                    }
+                }
+            }
+        }
+
+        // nodes in cycles
+        for node in enabled {
+            if !order.iter().any(|n| n.id == node.id) {
+                order.push(node);
+            }
+        }
+        order
+    }
+
     pub fn disable_all_nodes(&mut self) {
         for node in self.nodes.iter_mut() {
             node.enabled = false;
@@ -119,38 +225,279 @@ impl AgentFlow {
             .map_err(|e| AgentError::SerializationError(e.to_string()))?;
         Ok(flow)
     }
+
+    pub fn builder(name: &str) -> AgentFlowBuilder {
+        AgentFlowBuilder::new(name)
+    }
+}
+
+// AgentFlowBuilder
+
+/// Assembles a flow in code, for tests and flows embedded in applications.
+///
+/// ```ignore
+/// let flow = AgentFlow::builder("chat")
+///     .node("input", "std_string_input", None)
+///     .node("chat", "openai_chat", configs)
+///     .edge("input:string", "chat:message")
+///     .build()?;
+/// ```
+///
+/// Nodes are enabled, and a node with an empty id gets a new one. Edges get new ids.
+/// `build` checks the flow, so mistakes show up there instead of when the flow runs.
+#[derive(Clone, Debug)]
+pub struct AgentFlowBuilder {
+    flow: AgentFlow,
+    // (source, target) as written, to report bad edges in build
+    edges: Vec<(String, String)>,
+}
+
+impl AgentFlowBuilder {
+    pub fn new(name: &str) -> Self {
+        Self {
+            flow: AgentFlow::new(name.to_string()),
+            edges: Vec::new(),
+        }
+    }
+
+    pub fn node(
+        mut self,
+        id: &str,
+        def_name: &str,
+        configs: impl Into<Option<AgentConfigs>>,
+    ) -> Self {
+        let id = if id.is_empty() {
+            new_id()
+        } else {
+            id.to_string()
+        };
+        self.flow.add_node(AgentFlowNode {
+            id,
+            def_name: def_name.to_string(),
+            enabled: true,
+            configs: configs.into(),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Adds a node built elsewhere, e.g. by `ASKit::new_agent_flow_node`.
+    pub fn add_node(mut self, node: AgentFlowNode) -> Self {
+        self.flow.add_node(node);
+        self
+    }
+
+    pub fn note(mut self, text: &str) -> Self {
+        self.flow.add_node(AgentFlowNode::new_note(text));
+        self
+    }
+
+    /// Connects `"node:port"` of the source to `"node:port"` of the target.
+    pub fn edge(mut self, source: &str, target: &str) -> Self {
+        self.edges.push((source.to_string(), target.to_string()));
+        self
+    }
+
+    /// Adds an edge written as `"source:port -> target:port"`.
+    pub fn connect(self, edge: &str) -> Self {
+        let (source, target) = edge.split_once("->").unwrap_or((edge, ""));
+        self.edge(source.trim(), target.trim())
+    }
+
+    pub fn build(self) -> Result<AgentFlow, AgentError> {
+        let mut flow = self.flow;
+        let invalid = |message: String| AgentError::InvalidFlow(flow.name.clone(), message);
+
+        let mut ids = std::collections::HashSet::new();
+        for node in flow.nodes.iter() {
+            if !ids.insert(node.id.as_str()) {
+                return Err(invalid(format!("duplicate node id {}", node.id)));
+            }
+        }
+
+        let mut edges = Vec::with_capacity(self.edges.len());
+        for (source, target) in self.edges.iter() {
+            let ((source, source_handle), (target, target_handle)) =
+                match (split_handle(source), split_handle(target)) {
+                    (Some(source), Some(target)) => (source, target),
+                    _ => {
+                        return Err(invalid(format!(
+                            "edge {} -> {} is not in the form \"node:port\"",
+                            source, target
+                        )));
+                    }
+                };
+            for node_id in [source, target] {
+                if !ids.contains(node_id) {
+                    return Err(invalid(format!("edge refers to unknown node {}", node_id)));
+                }
+            }
+            edges.push(AgentFlowEdge {
+                id: new_id(),
+                source: source.to_string(),
+                source_handle: source_handle.to_string(),
+                target: target.to_string(),
+                target_handle: target_handle.to_string(),
+                ..Default::default()
+            });
+        }
+        flow.edges = edges;
+
+        flow.validate()?;
+        Ok(flow)
+    }
+
+    /// Builds the flow and also checks the agent definitions and ports against the registry.
+    pub fn build_for(self, askit: &ASKit) -> Result<AgentFlow, AgentError> {
+        let flow = self.build()?;
+        for node in flow.nodes.iter().filter(|node| !node.is_note()) {
+            if askit.get_agent_definition(&node.def_name).is_none() {
+                return Err(AgentError::AgentDefinitionNotFound(node.def_name.clone()));
+            }
+        }
+        for edge in flow.edges.iter() {
+            let source = flow.get_node(&edge.source).unwrap();
+            let target = flow.get_node(&edge.target).unwrap();
+            let source_def = askit.get_agent_definition(&source.def_name).unwrap();
+            let target_def = askit.get_agent_definition(&target.def_name).unwrap();
+            if !has_port(&source_def.outputs, &edge.source_handle) {
+                return Err(AgentError::InvalidFlow(
+                    flow.name.clone(),
+                    format!("{} has no output {}", source.id, edge.source_handle),
+                ));
+            }
+            if !has_port(&target_def.inputs, &edge.target_handle) {
+                return Err(AgentError::InvalidFlow(
+                    flow.name.clone(),
+                    format!("{} has no input {}", target.id, edge.target_handle),
+                ));
+            }
+        }
+        Ok(flow)
+    }
+}
+
+// "node:port" -> (node, port)
+fn split_handle(handle: &str) -> Option<(&str, &str)> {
+    handle
+        .split_once(':')
+        .filter(|(node, port)| !node.is_empty() && !port.is_empty())
+}
+
+// "*" matches any port on either side
+fn has_port(ports: &Option<Vec<String>>, port: &str) -> bool {
+    port == "*"
+        || ports
+            .as_ref()
+            .is_some_and(|ports| ports.iter().any(|p| p == port || p == "*"))
+}
+
+/// States of the agents of a flow, taken by `ASKit::checkpoint_flow`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AgentFlowCheckpoint {
+    pub flow_name: String,
+
+    // agent id -> state
+    pub states: HashMap<String, AgentValue>,
+}
+
+impl AgentFlowCheckpoint {
+    pub fn to_json(&self) -> Result<String, AgentError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| AgentError::SerializationError(e.to_string()))?;
+        Ok(json)
+    }
+
+    pub fn from_json(json_str: &str) -> Result<Self, AgentError> {
+        let checkpoint: AgentFlowCheckpoint = serde_json::from_str(json_str)
+            .map_err(|e| AgentError::SerializationError(e.to_string()))?;
+        Ok(checkpoint)
+    }
 }
 
 pub fn copy_sub_flow(
-    nodes: &Vec<AgentFlowNode>,
-    edges: &Vec<AgentFlowEdge>,
+    nodes: &[AgentFlowNode],
+    edges: &[AgentFlowEdge],
 ) -> (Vec<AgentFlowNode>, Vec<AgentFlowEdge>) {
-    let mut new_nodes = Vec::new();
-    let mut node_id_map = HashMap::new();
+    let copy = copy_sub_flow_with(nodes, edges, &SubFlowCopyOptions::default());
+    (copy.nodes, copy.edges)
+}
+
+/// Options of `copy_sub_flow_with`.
+#[derive(Clone, Debug, Default)]
+pub struct SubFlowCopyOptions {
+    /// Keeps the edges with only one end in the copied nodes as `pending_edges`.
+    pub include_boundary_edges: bool,
+
+    /// Names the copies `{prefix}{old id}` instead of using fresh ids, so that every host
+    /// pasting the same selection gets the same ids.
+    pub id_prefix: Option<String>,
+}
+
+/// Result of `copy_sub_flow_with`.
+#[derive(Clone, Debug, Default)]
+pub struct SubFlowCopy {
+    pub nodes: Vec<AgentFlowNode>,
+
+    /// Edges between the copied nodes.
+    pub edges: Vec<AgentFlowEdge>,
+
+    /// Edges crossing the copied boundary. The copied end is remapped, the other end still
+    /// refers to the node outside, for the host to reconnect or drop.
+    pub pending_edges: Vec<AgentFlowEdge>,
+
+    /// Old node id to new node id.
+    pub node_ids: HashMap<String, String>,
+
+    /// Old edge id to new edge id, pending edges included.
+    pub edge_ids: HashMap<String, String>,
+}
+
+/// Copies the nodes and edges with new ids, reporting how the ids were remapped.
+pub fn copy_sub_flow_with(
+    nodes: &[AgentFlowNode],
+    edges: &[AgentFlowEdge],
+    options: &SubFlowCopyOptions,
+) -> SubFlowCopy {
+    let copy_id = |id: &str| match &options.id_prefix {
+        Some(prefix) => format!("{}{}", prefix, id),
+        None => new_id(),
+    };
+
+    let mut copy = SubFlowCopy::default();
     for node in nodes {
-        let new_id = new_id();
-        node_id_map.insert(node.id.clone(), new_id.clone());
         let mut new_node = node.clone();
-        new_node.id = new_id;
-        new_nodes.push(new_node);
+        new_node.id = copy_id(&node.id);
+        copy.node_ids.insert(node.id.clone(), new_node.id.clone());
+        copy.nodes.push(new_node);
     }
 
-    let mut new_edges = Vec::new();
     for edge in edges {
-        let Some(source) = node_id_map.get(&edge.source) else {
-            continue;
-        };
-        let Some(target) = node_id_map.get(&edge.target) else {
-            continue;
+        let source = copy.node_ids.get(&edge.source);
+        let target = copy.node_ids.get(&edge.target);
+        let pending = match (source, target) {
+            (Some(_), Some(_)) => false,
+            (None, None) => continue,
+            _ if options.include_boundary_edges => true,
+            _ => continue,
         };
         let mut new_edge = edge.clone();
-        new_edge.id = new_id();
-        new_edge.source = source.clone();
-        new_edge.target = target.clone();
-        new_edges.push(new_edge);
+        new_edge.id = copy_id(&edge.id);
+        if let Some(source) = source {
+            new_edge.source = source.clone();
+        }
+        if let Some(target) = target {
+            new_edge.target = target.clone();
+        }
+        copy.edge_ids.insert(edge.id.clone(), new_edge.id.clone());
+        if pending {
+            copy.pending_edges.push(new_edge);
+        } else {
+            copy.edges.push(new_edge);
+        }
     }
 
-    (new_nodes, new_edges)
+    copy
 }
 
 // AgentFlowNode
@@ -161,13 +508,54 @@ pub struct AgentFlowNode {
     pub def_name: String,
     pub enabled: bool,
 
+    /// The agent runs, but its outputs are not delivered.
+    #[serde(default, skip_serializing_if = "<&bool>::not")]
+    pub muted: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub configs: Option<AgentConfigs>,
 
+    /// Position and looks of the node in the editor. The runtime ignores it.
+    #[serde(flatten)]
+    pub layout: NodeLayout,
+
     #[serde(flatten)]
     pub extensions: HashMap<String, Value>,
 }
 
+/// UI metadata of a node, saved with the flow so frontends share layouts.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NodeLayout {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<f64>,
+
+    #[serde(default, skip_serializing_if = "<&bool>::not")]
+    pub collapsed: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+impl NodeLayout {
+    pub fn position(mut self, x: f64, y: f64) -> Self {
+        self.x = Some(x);
+        self.y = Some(y);
+        self
+    }
+}
+
 impl AgentFlowNode {
     pub fn new(def: &AgentDefinition) -> Result<Self, AgentError> {
         let configs = if let Some(default_configs) = &def.default_configs {
@@ -184,10 +572,51 @@ impl AgentFlowNode {
             id: new_id(),
             def_name: def.name.clone(),
             enabled: false,
+            muted: false,
+            version: def.version,
             configs,
+            layout: NodeLayout::default(),
             extensions: HashMap::new(),
         })
     }
+
+    /// Note node with the text, documenting a section of the flow.
+    pub fn new_note(text: &str) -> Self {
+        let mut configs = AgentConfigs::new();
+        configs.set(CONFIG_TEXT.to_string(), AgentValue::string(text));
+        Self {
+            id: new_id(),
+            def_name: NOTE_DEF_NAME.to_string(),
+            configs: Some(configs),
+            ..Default::default()
+        }
+    }
+
+    pub fn is_note(&self) -> bool {
+        self.def_name == NOTE_DEF_NAME
+    }
+
+    /// Brings the node up to the version of its definition.
+    ///
+    /// Configs saved by an older version are passed to the migration callback of the definition.
+    /// Returns false when the version mismatch could not be resolved.
+    pub fn migrate(&mut self, def: &AgentDefinition) -> Result<bool, AgentError> {
+        let from_version = self.version.unwrap_or_default();
+        let to_version = def.version.unwrap_or_default();
+        if from_version == to_version {
+            return Ok(true);
+        }
+        if from_version > to_version {
+            return Ok(false);
+        }
+        let Some(migrate_configs) = def.migrate_configs else {
+            return Ok(false);
+        };
+        let configs = self.configs.clone().unwrap_or_default();
+        self.configs = Some(migrate_configs(from_version, configs)?);
+        self.version = def.version;
+        Ok(true)
+    }
 }
 
 static NODE_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
@@ -198,6 +627,31 @@ fn new_id() -> String {
         .to_string();
 }
 
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Generates a ULID: 48 bits of milliseconds and 80 random bits in Crockford base32, so the
+/// ids sort by creation time.
+pub(crate) fn new_ulid() -> String {
+    use std::hash::{BuildHasher, Hasher};
+
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+    let random = |salt: usize| {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_usize(salt);
+        hasher.finish() as u128
+    };
+    let counter = NODE_ID_COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let random = (random(counter) << 16 | random(!counter) & 0xffff) & ((1 << 80) - 1);
+    let value = (millis & ((1 << 48) - 1)) << 80 | random;
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD_BASE32[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
 // AgentFlowEdge
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -207,4 +661,319 @@ pub struct AgentFlowEdge {
     pub source_handle: String,
     pub target: String,
     pub target_handle: String,
+
+    /// A disabled edge delivers nothing.
+    #[serde(default, skip_serializing_if = "<&bool>::not")]
+    pub disabled: bool,
+
+    /// Share of the outputs of the source port among its weighted edges.
+    ///
+    /// Each output goes through only one of the weighted edges, e.g. weights 9 and 1
+    /// split the traffic 90/10. Unweighted edges get every output.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+}
+
+impl AgentFlowEdge {
+    /// Parses the compact form `"source:port -> target:port"`. The edge gets a new id.
+    pub fn parse(edge: &str) -> Result<Self, AgentError> {
+        let invalid = || AgentError::InvalidEdge(edge.to_string());
+        let (source, target) = edge.split_once("->").ok_or_else(invalid)?;
+        let (source, source_handle) = split_handle(source.trim()).ok_or_else(invalid)?;
+        let (target, target_handle) = split_handle(target.trim()).ok_or_else(invalid)?;
+        Ok(AgentFlowEdge {
+            id: new_id(),
+            source: source.to_string(),
+            source_handle: source_handle.to_string(),
+            target: target.to_string(),
+            target_handle: target_handle.to_string(),
+            ..Default::default()
+        })
+    }
+}
+
+/// The compact form `"source:port -> target:port"`.
+impl std::fmt::Display for AgentFlowEdge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{} -> {}:{}",
+            self.source, self.source_handle, self.target, self.target_handle
+        )
+    }
+}
+
+impl std::str::FromStr for AgentFlowEdge {
+    type Err = AgentError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AgentFlowEdge::parse(s)
+    }
+}
+
+fn deserialize_edges<'de, D>(deserializer: D) -> Result<Vec<AgentFlowEdge>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let values = Vec::<Value>::deserialize(deserializer)?;
+    values
+        .into_iter()
+        .map(|value| match value {
+            Value::String(edge) => AgentFlowEdge::parse(&edge),
+            value => serde_json::from_value(value)
+                .map_err(|e| AgentError::SerializationError(e.to_string())),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: &str) -> AgentFlowNode {
+        AgentFlowNode {
+            id: id.to_string(),
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    fn edge(source: &str, target: &str) -> AgentFlowEdge {
+        AgentFlowEdge {
+            id: format!("{}-{}", source, target),
+            source: source.to_string(),
+            source_handle: "out".to_string(),
+            target: target.to_string(),
+            target_handle: "in".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_note_node() {
+        let mut flow = AgentFlow::new("flow".to_string());
+        let note = AgentFlowNode::new_note("Fetches the feed");
+        let note_id = note.id.clone();
+        flow.set_nodes(vec![node("a"), node("b"), note]);
+        flow.set_edges(vec![edge("a", "b")]);
+        assert!(flow.validate().is_ok());
+
+        flow.get_node_mut(&note_id).unwrap().enabled = true;
+        let order = flow
+            .start_order()
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["b", "a"]);
+
+        flow.add_edge(edge("a", &note_id));
+        assert!(flow.validate().is_err());
+
+        let json = flow.to_json().unwrap();
+        let flow = AgentFlow::from_json(&json).unwrap();
+        let note = flow.get_node(&note_id).unwrap();
+        assert!(note.is_note());
+        assert_eq!(
+            note.configs.as_ref().unwrap().get_string("text").unwrap(),
+            "Fetches the feed"
+        );
+    }
+
+    #[test]
+    fn test_flow_builder() {
+        let flow = AgentFlow::builder("flow")
+            .node("a", "source", None)
+            .node("", "sink", AgentConfigs::new())
+            .note("a note")
+            .edge("a:out", "b:in")
+            .build();
+        assert!(matches!(flow, Err(AgentError::InvalidFlow(_, _))));
+
+        let flow = AgentFlow::builder("flow")
+            .node("a", "source", None)
+            .node("b", "sink", AgentConfigs::new())
+            .edge("a:out", "b:in")
+            .build()
+            .unwrap();
+        assert_eq!(flow.nodes().len(), 2);
+        assert!(flow.nodes().iter().all(|node| node.enabled));
+        assert_eq!(flow.edges()[0].source, "a");
+        assert_eq!(flow.edges()[0].source_handle, "out");
+        assert_eq!(flow.edges()[0].target_handle, "in");
+
+        let duplicate = AgentFlow::builder("flow")
+            .node("a", "source", None)
+            .node("a", "sink", None)
+            .build();
+        assert!(duplicate.is_err());
+        let bad_handle = AgentFlow::builder("flow")
+            .node("a", "source", None)
+            .edge("a", "a:in")
+            .build();
+        assert!(bad_handle.is_err());
+
+        let askit = ASKit::new();
+        askit.register_agent(AgentDefinition::new("test", "source", None).outputs(vec!["out"]));
+        askit.register_agent(AgentDefinition::new("test", "sink", None).inputs(vec!["in"]));
+        let builder = AgentFlow::builder("flow")
+            .node("a", "source", None)
+            .node("b", "sink", None);
+        assert!(
+            builder
+                .clone()
+                .edge("a:out", "b:in")
+                .build_for(&askit)
+                .is_ok()
+        );
+        assert!(
+            builder
+                .clone()
+                .edge("a:in", "b:in")
+                .build_for(&askit)
+                .is_err()
+        );
+        assert!(
+            builder
+                .node("c", "missing", None)
+                .build_for(&askit)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_compact_edges() {
+        let edge: AgentFlowEdge = "a:out -> b:in".parse().unwrap();
+        assert_eq!(edge.source, "a");
+        assert_eq!(edge.source_handle, "out");
+        assert_eq!(edge.target, "b");
+        assert_eq!(edge.target_handle, "in");
+        assert_eq!(edge.to_string(), "a:out -> b:in");
+        assert!(AgentFlowEdge::parse("a:out b:in").is_err());
+        assert!(AgentFlowEdge::parse("a -> b:in").is_err());
+
+        let json = r#"{
+            "name": "flow",
+            "nodes": [{"id": "a", "def_name": "source", "enabled": true},
+                      {"id": "b", "def_name": "sink", "enabled": true}],
+            "edges": [
+                "a:out -> b:in",
+                {"id": "e", "source": "b", "source_handle": "out",
+                 "target": "a", "target_handle": "in"}
+            ]
+        }"#;
+        let flow = AgentFlow::from_json(json).unwrap();
+        assert_eq!(flow.edges().len(), 2);
+        assert_eq!(flow.edges()[0].target_handle, "in");
+        assert_eq!(flow.edges()[1].id, "e");
+        assert!(AgentFlow::from_json(&json.replace("a:out -> b:in", "a:out")).is_err());
+
+        let flow = AgentFlow::builder("flow")
+            .node("a", "source", None)
+            .node("b", "sink", None)
+            .connect("a:out -> b:in")
+            .build()
+            .unwrap();
+        assert_eq!(flow.edges()[0].to_string(), "a:out -> b:in");
+    }
+
+    #[test]
+    fn test_layout_round_trip() {
+        let json = r#"{
+            "name": "flow",
+            "nodes": [{"id": "1", "def_name": "echo", "enabled": true,
+                       "x": 10.0, "y": 20.0, "collapsed": true, "color": "red", "extra": 1}],
+            "edges": [],
+            "viewport": {"x": 0.0, "y": 0.0, "zoom": 1.5}
+        }"#;
+        let flow = AgentFlow::from_json(json).unwrap();
+        let node = &flow.nodes()[0];
+        assert_eq!(node.layout.x, Some(10.0));
+        assert!(node.layout.collapsed);
+        assert_eq!(node.layout.color.as_deref(), Some("red"));
+        assert_eq!(node.extensions.len(), 1);
+        assert_eq!(flow.viewport.as_ref().unwrap().zoom, 1.5);
+
+        let round_trip = AgentFlow::from_json(&flow.to_json().unwrap()).unwrap();
+        assert_eq!(round_trip.nodes()[0].layout, node.layout);
+        assert_eq!(round_trip.nodes()[0].extensions, node.extensions);
+        assert_eq!(round_trip.viewport, flow.viewport);
+    }
+
+    #[test]
+    fn test_copy_sub_flow_with() {
+        let nodes = vec![node("a"), node("b")];
+        let edges = vec![
+            edge("a", "b"),
+            edge("x", "a"),
+            edge("b", "y"),
+            edge("x", "y"),
+        ];
+
+        let copy = copy_sub_flow_with(&nodes, &edges, &SubFlowCopyOptions::default());
+        assert_eq!(copy.nodes.len(), 2);
+        assert_eq!(copy.edges.len(), 1);
+        assert!(copy.pending_edges.is_empty());
+        assert_eq!(copy.edges[0].source, copy.node_ids["a"]);
+        assert_eq!(copy.edges[0].target, copy.node_ids["b"]);
+
+        let options = SubFlowCopyOptions {
+            include_boundary_edges: true,
+            id_prefix: Some("p-".to_string()),
+        };
+        let copy = copy_sub_flow_with(&nodes, &edges, &options);
+        assert_eq!(copy.nodes[0].id, "p-a");
+        assert_eq!(copy.edges[0].id, "p-a-b");
+        let pending = copy
+            .pending_edges
+            .iter()
+            .map(|edge| (edge.source.as_str(), edge.target.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(pending, [("x", "p-a"), ("p-b", "y")]);
+        assert_eq!(copy.edge_ids.len(), 3);
+        assert_eq!(copy.edge_ids["x-a"], "p-x-a");
+    }
+
+    #[test]
+    fn test_new_ulid() {
+        let a = new_ulid();
+        let b = new_ulid();
+        assert_eq!(a.len(), 26);
+        assert_ne!(a, b);
+        assert!(a.chars().all(|c| CROCKFORD_BASE32.contains(&(c as u8))));
+    }
+
+    #[test]
+    fn test_start_order() {
+        let mut flow = AgentFlow::new("flow".to_string());
+        flow.set_nodes(vec![
+            node("source"),
+            node("middle"),
+            node("sink"),
+            node("x"),
+            node("y"),
+        ]);
+        flow.set_edges(vec![
+            edge("source", "middle"),
+            edge("middle", "sink"),
+            edge("source", "sink"),
+            edge("x", "y"),
+            edge("y", "x"),
+        ]);
+
+        let order = flow
+            .start_order()
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["sink", "middle", "source", "x", "y"]);
+
+        flow.nodes[2].enabled = false;
+        let order = flow
+            .start_order()
+            .iter()
+            .map(|node| node.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(order, ["middle", "source", "x", "y"]);
+    }
 }
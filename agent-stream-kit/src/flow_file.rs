@@ -0,0 +1,272 @@
+use std::path::{Path, PathBuf};
+
+use serde_json::Value;
+
+use super::error::AgentError;
+use super::flow::AgentFlow;
+
+// Flow files
+//
+// A file holds one flow or several: a JSON array, YAML documents separated by `---`,
+// or a `flows` list. A top-level `include` lists other flow files, relative to the file.
+//
+// ```yaml
+// include: [common/logging.yaml]
+// ---
+// name: chat
+// nodes:
+//   - {id: input, def_name: std_string_input, enabled: true}
+//   - {id: chat, def_name: openai_chat, enabled: true}
+// edges:
+//   - "input:string -> chat:message"
+// ```
+
+/// Loads the flows of a `.json`, `.yaml`/`.yml` or `.toml` file and the files it includes.
+pub fn load_flows(path: impl AsRef<Path>) -> Result<Vec<AgentFlow>, AgentError> {
+    let mut flows = Vec::new();
+    load_file(path.as_ref(), &mut Vec::new(), &mut flows)?;
+    Ok(flows)
+}
+
+/// Saves the flows in the format of the file extension.
+pub fn save_flows(path: impl AsRef<Path>, flows: &[AgentFlow]) -> Result<(), AgentError> {
+    let path = path.as_ref();
+    let content = match format_of(path)? {
+        FlowFormat::Json => match flows {
+            [flow] => flow.to_json()?,
+            flows => serde_json::to_string_pretty(flows)?,
+        },
+        #[cfg(feature = "yaml")]
+        FlowFormat::Yaml => flows
+            .iter()
+            .map(|flow| flow.to_yaml())
+            .collect::<Result<Vec<_>, _>>()?
+            .join("---\n"),
+        #[cfg(feature = "toml")]
+        FlowFormat::Toml => match flows {
+            [flow] => flow.to_toml()?,
+            flows => {
+                let value = serde_json::json!({ "flows": flows });
+                to_toml_string(&value)?
+            }
+        },
+    };
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(feature = "yaml")]
+impl AgentFlow {
+    pub fn to_yaml(&self) -> Result<String, AgentError> {
+        serde_yaml_ng::to_string(self).map_err(|e| AgentError::SerializationError(e.to_string()))
+    }
+
+    pub fn from_yaml(yaml_str: &str) -> Result<Self, AgentError> {
+        serde_yaml_ng::from_str(yaml_str).map_err(|e| AgentError::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(feature = "toml")]
+impl AgentFlow {
+    pub fn to_toml(&self) -> Result<String, AgentError> {
+        to_toml_string(self)
+    }
+
+    pub fn from_toml(toml_str: &str) -> Result<Self, AgentError> {
+        toml::from_str(toml_str).map_err(|e| AgentError::SerializationError(e.to_string()))
+    }
+}
+
+// TOML needs the values of a table before its subtables, which `toml::Value` takes care of.
+#[cfg(feature = "toml")]
+fn to_toml_string<T: serde::Serialize>(value: &T) -> Result<String, AgentError> {
+    toml::Value::try_from(value)
+        .and_then(|value| toml::to_string_pretty(&value))
+        .map_err(|e| AgentError::SerializationError(e.to_string()))
+}
+
+enum FlowFormat {
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "toml")]
+    Toml,
+}
+
+fn format_of(path: &Path) -> Result<FlowFormat, AgentError> {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(FlowFormat::Json),
+        #[cfg(feature = "yaml")]
+        Some("yaml" | "yml") => Ok(FlowFormat::Yaml),
+        #[cfg(feature = "toml")]
+        Some("toml") => Ok(FlowFormat::Toml),
+        _ => Err(AgentError::InvalidValue(format!(
+            "flow file format of {}",
+            path.display()
+        ))),
+    }
+}
+
+// `stack` holds the files being loaded, to catch include cycles.
+fn load_file(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    flows: &mut Vec<AgentFlow>,
+) -> Result<(), AgentError> {
+    let canonical = path.canonicalize()?;
+    if stack.contains(&canonical) {
+        return Err(AgentError::InvalidValue(format!(
+            "include of {}, which includes itself",
+            path.display()
+        )));
+    }
+
+    let content = std::fs::read_to_string(path)?;
+    let documents = parse_documents(format_of(path)?, &content)?;
+
+    stack.push(canonical);
+    let base = path.parent().unwrap_or(Path::new(""));
+    for document in documents {
+        load_document(document, base, stack, flows)?;
+    }
+    stack.pop();
+    Ok(())
+}
+
+fn parse_documents(format: FlowFormat, content: &str) -> Result<Vec<Value>, AgentError> {
+    match format {
+        FlowFormat::Json => Ok(vec![serde_json::from_str(content)?]),
+        #[cfg(feature = "yaml")]
+        FlowFormat::Yaml => {
+            use serde::Deserialize;
+            serde_yaml_ng::Deserializer::from_str(content)
+                .map(Value::deserialize)
+                .filter(|document| !matches!(document, Ok(Value::Null)))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| AgentError::SerializationError(e.to_string()))
+        }
+        #[cfg(feature = "toml")]
+        FlowFormat::Toml => toml::from_str(content)
+            .map(|document| vec![document])
+            .map_err(|e| AgentError::SerializationError(e.to_string())),
+    }
+}
+
+fn load_document(
+    document: Value,
+    base: &Path,
+    stack: &mut Vec<PathBuf>,
+    flows: &mut Vec<AgentFlow>,
+) -> Result<(), AgentError> {
+    let mut object = match document {
+        Value::Array(documents) => {
+            for document in documents {
+                load_document(document, base, stack, flows)?;
+            }
+            return Ok(());
+        }
+        Value::Object(object) => object,
+        _ => {
+            return Err(AgentError::SerializationError(
+                "a flow document must be an object or an array".to_string(),
+            ));
+        }
+    };
+
+    if let Some(include) = object.remove("include") {
+        let paths = match include {
+            Value::String(path) => vec![path],
+            include => serde_json::from_value::<Vec<String>>(include)?,
+        };
+        for include_path in paths {
+            load_file(&base.join(include_path), stack, flows)?;
+        }
+    }
+    if let Some(documents) = object.remove("flows") {
+        load_document(documents, base, stack, flows)?;
+    }
+    if !object.is_empty() {
+        flows.push(serde_json::from_value(Value::Object(object))?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("askit-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_load_json_with_include() {
+        let dir = temp_dir("flow-file");
+        std::fs::write(
+            dir.join("sub.json"),
+            r#"[{"name": "sub1", "nodes": [], "edges": []},
+                {"name": "sub2", "nodes": [], "edges": []}]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("main.json"),
+            r#"{"include": "sub.json", "name": "main", "nodes": [], "edges": []}"#,
+        )
+        .unwrap();
+
+        let flows = load_flows(dir.join("main.json")).unwrap();
+        let names = flows.iter().map(|flow| flow.name()).collect::<Vec<_>>();
+        assert_eq!(names, ["sub1", "sub2", "main"]);
+        assert!(flows[2].extensions.is_empty());
+
+        std::fs::write(dir.join("loop.json"), r#"{"include": ["loop.json"]}"#).unwrap();
+        assert!(load_flows(dir.join("loop.json")).is_err());
+
+        save_flows(dir.join("saved.json"), &flows).unwrap();
+        assert_eq!(load_flows(dir.join("saved.json")).unwrap().len(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_yaml_documents() {
+        let dir = temp_dir("flow-yaml");
+        std::fs::write(
+            dir.join("flows.yaml"),
+            "name: a\nnodes:\n  - {id: '1', def_name: source, enabled: true}\n  - {id: '2', def_name: sink, enabled: true}\nedges:\n  - '1:out -> 2:in'\n---\nname: b\nnodes: []\nedges: []\n",
+        )
+        .unwrap();
+        let flows = load_flows(dir.join("flows.yaml")).unwrap();
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows[0].edges()[0].target_handle, "in");
+
+        save_flows(dir.join("saved.yml"), &flows).unwrap();
+        let saved = load_flows(dir.join("saved.yml")).unwrap();
+        assert_eq!(saved[0].nodes().len(), 2);
+        assert_eq!(saved[1].name(), "b");
+
+        let flow = AgentFlow::from_yaml(&flows[0].to_yaml().unwrap()).unwrap();
+        assert_eq!(flow.edges()[0].source, "1");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_round_trip() {
+        let dir = temp_dir("flow-toml");
+        let flow = AgentFlow::builder("a")
+            .node("1", "source", None)
+            .node("2", "sink", None)
+            .connect("1:out -> 2:in")
+            .build()
+            .unwrap();
+        let flow = AgentFlow::from_toml(&flow.to_toml().unwrap()).unwrap();
+        assert_eq!(flow.nodes().len(), 2);
+
+        save_flows(dir.join("flows.toml"), &[flow.clone(), flow]).unwrap();
+        assert_eq!(load_flows(dir.join("flows.toml")).unwrap().len(), 2);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::askit::ASKit;
+use super::error::AgentError;
+use super::flow_file::load_flows;
+
+// Hot reload
+//
+// A watcher polls the modification times of the flow files in a directory. A changed
+// file is loaded and its flows are applied by `ASKit::update_agent_flow`, so the agents
+// that did not change keep running. Polling needs no platform support and is enough for
+// files edited by hand.
+
+/// Interval of `ASKit::watch_flow_dir` suggested for files edited by hand.
+pub const DEFAULT_FLOW_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(crate) type FlowWatcher = tokio::task::AbortHandle;
+
+// State of a flow file seen by the watcher
+struct WatchedFile {
+    modified: SystemTime,
+
+    // names of the flows loaded from the file
+    flows: Vec<String>,
+}
+
+pub(crate) fn spawn_watcher(
+    askit: &ASKit,
+    dir: &Path,
+    interval: Duration,
+) -> Result<FlowWatcher, AgentError> {
+    let mut files = HashMap::new();
+    for (path, modified) in scan(dir)? {
+        let flows = load_flows(&path)
+            .map(|flows| flows.iter().map(|flow| flow.name().to_string()).collect())
+            .unwrap_or_default();
+        files.insert(path, WatchedFile { modified, flows });
+    }
+
+    let askit = askit.clone();
+    let dir = dir.to_path_buf();
+    let handle = tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(interval).await;
+            if askit.tx.lock().unwrap().is_none() {
+                break;
+            }
+            let current = match scan(&dir) {
+                Ok(current) => current,
+                Err(e) => {
+                    log::warn!("Failed to scan {}: {}", dir.display(), e);
+                    continue;
+                }
+            };
+
+            let removed = files
+                .keys()
+                .filter(|path| !current.contains_key(*path))
+                .cloned()
+                .collect::<Vec<_>>();
+            for path in removed {
+                if let Some(file) = files.remove(&path) {
+                    remove_flows(&askit, &path, &file.flows).await;
+                }
+            }
+
+            for (path, modified) in current {
+                if files
+                    .get(&path)
+                    .is_some_and(|file| file.modified == modified)
+                {
+                    continue;
+                }
+                let old_flows = files
+                    .get(&path)
+                    .map(|file| file.flows.clone())
+                    .unwrap_or_default();
+                let flows = reload(&askit, &path, &old_flows).await;
+                files.insert(
+                    path,
+                    WatchedFile {
+                        modified,
+                        flows: flows.unwrap_or(old_flows),
+                    },
+                );
+            }
+        }
+    });
+    Ok(handle.abort_handle())
+}
+
+// Flow files of the directory and their modification times
+fn scan(dir: &Path) -> Result<HashMap<PathBuf, SystemTime>, AgentError> {
+    let mut files = HashMap::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if !is_flow_file(&path) {
+            continue;
+        }
+        if let Ok(modified) = std::fs::metadata(&path).and_then(|meta| meta.modified()) {
+            files.insert(path, modified);
+        }
+    }
+    Ok(files)
+}
+
+fn is_flow_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| matches!(ext, "json" | "yaml" | "yml" | "toml"))
+}
+
+// Applies the flows of the file, and removes the flows no longer in it. Returns the
+// names of the flows in the file, or None when the file could not be loaded.
+async fn reload(askit: &ASKit, path: &Path, old_flows: &[String]) -> Option<Vec<String>> {
+    let path_str = path.display().to_string();
+    let flows = match load_flows(path) {
+        Ok(flows) => flows,
+        Err(e) => {
+            log::error!("Failed to reload {}: {}", path_str, e);
+            askit.emit_flow_reload_failed(path_str, e.to_string());
+            return None;
+        }
+    };
+
+    let names = flows
+        .iter()
+        .map(|flow| flow.name().to_string())
+        .collect::<Vec<_>>();
+    for flow in flows.iter() {
+        let is_new = !askit.get_agent_flows().contains_key(flow.name());
+        let result = match askit.update_agent_flow(flow).await {
+            Ok(_) if is_new => askit.start_agent_flow(flow.name()).await,
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => {
+                log::info!("Reloaded flow {} from {}", flow.name(), path_str);
+                askit.emit_flow_reloaded(path_str.clone(), flow.name().to_string());
+            }
+            Err(e) => {
+                log::error!("Failed to reload flow {}: {}", flow.name(), e);
+                askit.emit_flow_reload_failed(path_str.clone(), format!("{}: {}", flow.name(), e));
+            }
+        }
+    }
+
+    let dropped = old_flows
+        .iter()
+        .filter(|name| !names.contains(name))
+        .cloned()
+        .collect::<Vec<_>>();
+    remove_flows(askit, path, &dropped).await;
+    Some(names)
+}
+
+async fn remove_flows(askit: &ASKit, path: &Path, names: &[String]) {
+    for name in names {
+        if let Err(e) = askit.remove_agent_flow(name).await {
+            log::error!("Failed to remove flow {}: {}", name, e);
+            askit.emit_flow_reload_failed(path.display().to_string(), format!("{}: {}", name, e));
+        } else {
+            log::info!("Removed flow {} with {}", name, path.display());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use super::*;
+    use crate::askit::{ASKitEvent, ASKitObserver};
+    use crate::config::AgentConfigs;
+    use crate::data::AgentValue;
+    use crate::flow::AgentFlow;
+
+    struct ReloadObserver(Arc<Mutex<Vec<String>>>);
+
+    impl ASKitObserver for ReloadObserver {
+        fn notify(&self, event: &ASKitEvent) {
+            match event {
+                ASKitEvent::FlowReloaded(_, name) => {
+                    self.0.lock().unwrap().push(format!("reloaded {}", name))
+                }
+                ASKitEvent::FlowReloadFailed(..) => {
+                    self.0.lock().unwrap().push("failed".to_string())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn relay_flow(board: &str) -> AgentFlow {
+        let mut builder = AgentFlow::builder("relay");
+        for (id, def_name) in [("in", "core_board_in"), ("out", "core_board_out")] {
+            let mut configs = AgentConfigs::new();
+            configs.set("$board".into(), AgentValue::string(board));
+            builder = builder.node(id, def_name, configs);
+        }
+        builder.edge("in:*", "out:*").build().unwrap()
+    }
+
+    async fn wait_events(events: &Mutex<Vec<String>>, count: usize) {
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while events.lock().unwrap().len() < count && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_watch_flow_dir() {
+        let askit = ASKit::init().unwrap();
+        askit.ready().await.unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        askit.subscribe(Box::new(ReloadObserver(events.clone())));
+
+        let dir = std::env::temp_dir().join(format!("askit-flow-watch-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("relay.json");
+        askit
+            .watch_flow_dir(&dir, Duration::from_millis(20))
+            .unwrap();
+
+        std::fs::write(&path, relay_flow("a").to_json().unwrap()).unwrap();
+        wait_events(&events, 1).await;
+        assert_eq!(*events.lock().unwrap(), ["reloaded relay"]);
+        let agent_in = askit.agents.lock().unwrap().get("in").cloned().unwrap();
+
+        // a config change keeps the agents
+        std::fs::write(&path, relay_flow("b").to_json().unwrap()).unwrap();
+        wait_events(&events, 2).await;
+        assert_eq!(events.lock().unwrap().len(), 2);
+        let flow = askit.get_agent_flows().remove("relay").unwrap();
+        let configs = flow.get_node("in").unwrap().configs.clone().unwrap();
+        assert_eq!(configs.get_string("$board").unwrap(), "b");
+        let agent = askit.agents.lock().unwrap().get("in").cloned().unwrap();
+        assert!(Arc::ptr_eq(&agent_in, &agent));
+        assert_eq!(flow.edges().len(), 1);
+
+        std::fs::write(&path, "{").unwrap();
+        wait_events(&events, 3).await;
+        assert_eq!(events.lock().unwrap()[2], "failed");
+        assert!(askit.get_agent_flows().contains_key("relay"));
+
+        std::fs::remove_file(&path).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(2);
+        while askit.get_agent_flows().contains_key("relay") && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(askit.get_agent_flows().is_empty());
+
+        askit.unwatch_flow_dir(&dir);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::error::AgentError;
+
+pub type AgentKindDefinitions = HashMap<String, AgentKindDefinition>;
+
+/// Documentation and validation of a data kind such as "message".
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AgentKindDefinition {
+    pub name: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// JSON Schema of a value of the kind.
+    ///
+    /// Supported keywords: `type`, `enum`, `const`, `properties`, `required`,
+    /// `additionalProperties`, `items`, `minimum`, `maximum`, `minLength`, `maxLength`,
+    /// `minItems` and `maxItems`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Value>,
+}
+
+impl AgentKindDefinition {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn schema(mut self, schema: Value) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    /// Validates a JSON value against the schema.
+    ///
+    /// Arrays are validated element by element unless the schema itself accepts arrays,
+    /// as data of a kind may hold an array of values of the kind.
+    pub fn validate(&self, value: &Value) -> Result<(), AgentError> {
+        match self.violations(value).into_iter().next() {
+            Some(violation) => Err(AgentError::SchemaViolation(
+                self.name.clone(),
+                violation.message,
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// All the places where the value does not match the schema, as `validate` checks it.
+    pub fn violations(&self, value: &Value) -> Vec<SchemaViolation> {
+        let mut violations = Vec::new();
+        let Some(schema) = &self.schema else {
+            return violations;
+        };
+        match value {
+            Value::Array(arr) if !type_allows(schema, "array") => {
+                for (i, v) in arr.iter().enumerate() {
+                    validate_schema(schema, v, &format!("/{}", i), &mut violations);
+                }
+            }
+            _ => validate_schema(schema, value, "", &mut violations),
+        }
+        violations
+    }
+}
+
+/// A place where a value does not match a schema.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    /// JSON pointer to the value, "/" for the value itself.
+    pub path: String,
+
+    pub message: String,
+}
+
+fn type_allows(schema: &Value, type_name: &str) -> bool {
+    match schema.get("type") {
+        Some(Value::String(t)) => t == type_name,
+        Some(Value::Array(types)) => types.iter().any(|t| t == type_name),
+        _ => false,
+    }
+}
+
+fn type_matches(type_name: &str, value: &Value) -> bool {
+    match type_name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+fn validate_schema(
+    schema: &Value,
+    value: &Value,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) {
+    let at = if path.is_empty() { "/" } else { path };
+
+    match schema.get("type") {
+        Some(Value::String(t)) if !type_matches(t, value) => {
+            push_violation(violations, at, format!("{} is not of type {}", at, t));
+            return;
+        }
+        Some(Value::Array(types))
+            if !types
+                .iter()
+                .filter_map(|t| t.as_str())
+                .any(|t| type_matches(t, value)) =>
+        {
+            push_violation(
+                violations,
+                at,
+                format!("{} does not match any of the types", at),
+            );
+            return;
+        }
+        _ => {}
+    }
+
+    if let Some(Value::Array(options)) = schema.get("enum")
+        && !options.contains(value)
+    {
+        push_violation(
+            violations,
+            at,
+            format!("{} is not one of the allowed values", at),
+        );
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        push_violation(violations, at, format!("{} is not {}", at, expected));
+    }
+
+    if let Some(n) = value.as_f64() {
+        if let Some(min) = schema.get("minimum").and_then(|v| v.as_f64())
+            && n < min
+        {
+            push_violation(violations, at, format!("{} is less than {}", at, min));
+        }
+        if let Some(max) = schema.get("maximum").and_then(|v| v.as_f64())
+            && n > max
+        {
+            push_violation(violations, at, format!("{} is greater than {}", at, max));
+        }
+    }
+
+    if let Some(s) = value.as_str() {
+        let len = s.chars().count() as u64;
+        if let Some(min) = schema.get("minLength").and_then(|v| v.as_u64())
+            && len < min
+        {
+            push_violation(violations, at, format!("{} is shorter than {}", at, min));
+        }
+        if let Some(max) = schema.get("maxLength").and_then(|v| v.as_u64())
+            && len > max
+        {
+            push_violation(violations, at, format!("{} is longer than {}", at, max));
+        }
+    }
+
+    if let Some(arr) = value.as_array() {
+        let len = arr.len() as u64;
+        if let Some(min) = schema.get("minItems").and_then(|v| v.as_u64())
+            && len < min
+        {
+            push_violation(
+                violations,
+                at,
+                format!("{} has fewer than {} items", at, min),
+            );
+        }
+        if let Some(max) = schema.get("maxItems").and_then(|v| v.as_u64())
+            && len > max
+        {
+            push_violation(
+                violations,
+                at,
+                format!("{} has more than {} items", at, max),
+            );
+        }
+        if let Some(items) = schema.get("items") {
+            for (i, item) in arr.iter().enumerate() {
+                validate_schema(items, item, &format!("{}/{}", path, i), violations);
+            }
+        }
+    }
+
+    if let Some(obj) = value.as_object() {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !obj.contains_key(key) {
+                    push_violation(
+                        violations,
+                        &format!("{}/{}", path, key),
+                        format!("{} is missing property \"{}\"", at, key),
+                    );
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, v) in obj {
+            let child = format!("{}/{}", path, key);
+            match properties.and_then(|p| p.get(key)) {
+                Some(property) => validate_schema(property, v, &child, violations),
+                None => {
+                    if schema.get("additionalProperties") == Some(&Value::Bool(false)) {
+                        push_violation(violations, &child, format!("{} is not allowed", child));
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn push_violation(violations: &mut Vec<SchemaViolation>, path: &str, message: String) {
+    violations.push(SchemaViolation {
+        path: path.to_string(),
+        message,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_kind_validate() {
+        let kind = AgentKindDefinition::new("message").schema(json!({
+            "type": "object",
+            "required": ["role", "content"],
+            "properties": {
+                "role": {"enum": ["system", "user", "assistant"]},
+                "content": {"type": "string"},
+                "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2},
+            },
+        }));
+
+        assert!(
+            kind.validate(&json!({"role": "user", "content": "hi"}))
+                .is_ok()
+        );
+        assert!(
+            kind.validate(
+                &json!([{"role": "user", "content": "hi"}, {"role": "assistant", "content": ""}])
+            )
+            .is_ok()
+        );
+
+        let err = kind.validate(&json!({"role": "user"})).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Data of kind \"message\" does not match its schema: / is missing property \"content\""
+        );
+        assert!(
+            kind.validate(&json!({"role": "bot", "content": ""}))
+                .is_err()
+        );
+        assert!(
+            kind.validate(&json!({"role": "user", "content": "", "tags": ["a", 1]}))
+                .is_err()
+        );
+        assert!(
+            kind.validate(&json!([{"role": "user", "content": "hi"}, {"content": 1}]))
+                .is_err()
+        );
+
+        let violations = kind.violations(&json!({"role": "bot", "tags": [1]}));
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| v.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/content", "/role", "/tags/0"]
+        );
+
+        // no schema, anything goes
+        assert!(AgentKindDefinition::new("any").validate(&json!(1)).is_ok());
+    }
+}
@@ -4,31 +4,95 @@
 //! in a stream-based architecture. It includes support for defining agent behaviors, managing
 //! agent flows, handling agent input and output.
 
+extern crate self as agent_stream_kit;
+
 mod agent;
 mod askit;
+mod board;
 mod board_agent;
+mod capability;
+mod clock;
 mod config;
 mod context;
 mod data;
+mod debug;
 mod definition;
+mod delivery;
+mod diff;
+#[cfg(feature = "encryption")]
+mod encryption;
 mod error;
 mod flow;
+mod flow_file;
+mod flow_watch;
+mod kind;
 mod message;
+mod native_thread;
+mod note;
+mod notification;
 mod output;
+mod pause;
+mod probe;
+mod process;
+mod profile;
+mod quota;
+mod request;
+mod routing;
 mod runtime;
+mod saga;
+mod secret;
+mod sequence;
+mod stats;
+mod substitution;
+mod testing;
+mod transaction;
+mod usage;
 
-pub use agent::{Agent, AgentStatus, AsAgent, AsAgentData, new_agent_boxed};
-pub use askit::{ASKit, ASKitEvent, ASKitObserver};
+pub use agent::{Agent, AgentBoilerplate, AgentStatus, AsAgent, AsAgentData, new_agent_boxed};
+pub use askit::{ASKit, ASKitEvent, ASKitObserver, SUBMIT_PIN};
+pub use board::{BoardObserver, BoardUpdate};
+pub use capability::{AgentCapability, AgentCapabilityPolicy};
+pub use clock::AgentClock;
 pub use config::{AgentConfigs, AgentConfigsMap};
 pub use context::AgentContext;
 pub use data::{AgentData, AgentValue, AgentValueMap};
+pub use debug::{Breakpoint, PendingInput};
 pub use definition::{
     AgentConfigEntry, AgentDefaultConfigs, AgentDefinition, AgentDefinitions,
-    AgentDisplayConfigEntry,
+    AgentDisplayConfigEntry, AgentExample, AgentPortDoc, L10nMap,
+};
+pub use delivery::{DeliveryPolicy, UnackedDelivery};
+pub use diff::{FieldChange, FlowChange, FlowDiff, diff_flows};
+#[cfg(feature = "encryption")]
+pub use encryption::{decrypt_flow, encrypt_flow};
+pub use error::{AgentError, ResultExt};
+pub use flow::{
+    AgentFlow, AgentFlowBuilder, AgentFlowCheckpoint, AgentFlowEdge, AgentFlowNode, AgentFlows,
+    FlowViewport, NodeLayout, SubFlowCopy, SubFlowCopyOptions,
 };
-pub use error::AgentError;
-pub use flow::{AgentFlow, AgentFlowEdge, AgentFlowNode, AgentFlows};
+pub use flow_file::{load_flows, save_flows};
+pub use flow_watch::DEFAULT_FLOW_WATCH_INTERVAL;
+pub use kind::{AgentKindDefinition, AgentKindDefinitions, SchemaViolation};
+pub use native_thread::DEFAULT_THREAD_JOIN_TIMEOUT;
+pub use note::NOTE_DEF_NAME;
+pub use notification::{Notification, NotificationLevel};
 pub use output::AgentOutput;
+pub use probe::ProbeRecord;
+pub use process::{ProcessAgent, ProcessCommand, ProcessMessage, serve_process_agent};
+pub use profile::{FlowProfileReport, NodeProfile};
+pub use quota::{FlowQuota, QuotaAction, QuotaViolation};
+pub use saga::{COMPENSATE_PIN, Compensation};
+pub use secret::{MapSecretProvider, SecretProvider};
+pub use sequence::{ReorderBuffer, SequenceStatus, SequenceTracker};
+pub use substitution::substitute;
+pub use testing::{FlowTest, FlowTestFailure, FlowTestOutput, FlowTestReport, FlowTestStep};
+pub use transaction::AgentTransaction;
+pub use usage::{
+    AgentUsage, BudgetAction, ModelPricing, UsageBudget, UsageRecord, UsageTotals, usage_day,
+};
 
 // re-export async_trait
 pub use async_trait::async_trait;
+
+// re-export the derive macros
+pub use askit_derive::{AskitAgent, askit_agent};
@@ -1,7 +1,9 @@
 use super::askit::ASKit;
+use super::board_agent;
 use super::context::AgentContext;
 use super::data::AgentData;
 use super::error::AgentError;
+use super::testing;
 
 #[derive(Clone, Debug)]
 pub enum AgentEventMessage {
@@ -11,6 +13,12 @@ pub enum AgentEventMessage {
         pin: String,
         data: AgentData,
     },
+    AgentOutMany {
+        agent: String,
+        ctx: AgentContext,
+        pin: String,
+        data: Vec<AgentData>,
+    },
     BoardOut {
         name: String,
         ctx: AgentContext,
@@ -57,6 +65,26 @@ pub fn try_send_agent_out(
         })
 }
 
+pub fn try_send_agent_out_many(
+    askit: &ASKit,
+    agent: String,
+    ctx: AgentContext,
+    pin: String,
+    data: Vec<AgentData>,
+) -> Result<(), AgentError> {
+    askit
+        .tx()?
+        .try_send(AgentEventMessage::AgentOutMany {
+            agent,
+            ctx,
+            pin,
+            data,
+        })
+        .map_err(|_| {
+            AgentError::SendMessageFailed("Failed to try_send AgentOutMany message".to_string())
+        })
+}
+
 pub fn try_send_board_out(
     askit: &ASKit,
     name: String,
@@ -79,24 +107,15 @@ pub async fn agent_out(
     pin: String,
     data: AgentData,
 ) {
-    let targets;
-    {
-        let env_edges = env.edges.lock().unwrap();
-        targets = env_edges.get(&source_agent).cloned();
-    }
-
-    if targets.is_none() {
+    if env.muted_agents.lock().unwrap().contains(&source_agent) {
         return;
     }
+    testing::tap(env, &source_agent, &pin, &data);
 
-    for target in targets.unwrap() {
-        let (target_agent, source_pin, target_pin) = target;
+    let targets = edge_targets(env, &source_agent, &pin);
 
-        if source_pin != pin && source_pin != "*" {
-            // Skip if source_handle does not match with the given port.
-            // "*" is a wildcard, and outputs messages of all ports.
-            continue;
-        }
+    for target in targets {
+        let (target_agent, source_pin, target_pin, edge_id) = target;
 
         {
             let env_agents = env.agents.lock().unwrap();
@@ -105,6 +124,15 @@ pub async fn agent_out(
             }
         }
 
+        {
+            let mut env_probes = env.edge_probes.lock().unwrap();
+            for probe in env_probes.values_mut() {
+                if probe.matches(&source_agent, &source_pin, &target_agent, &target_pin) {
+                    probe.record(&data);
+                }
+            }
+        }
+
         let target_pin = if target_pin == "*" {
             // If target_handle is "*", use the port specified by the source agent
             pin.clone()
@@ -112,7 +140,11 @@ pub async fn agent_out(
             target_pin.clone()
         };
 
-        env.agent_input(target_agent.clone(), ctx.clone(), target_pin, data.clone())
+        // number the messages of each edge
+        let seq = next_sequences(env, &edge_id, 1);
+        let ctx = ctx.with_sequence(edge_id, seq);
+
+        env.agent_input(target_agent.clone(), ctx, target_pin, data.clone())
             .await
             .unwrap_or_else(|e| {
                 log::error!("Failed to send message to {}: {}", target_agent, e);
@@ -120,33 +152,141 @@ pub async fn agent_out(
     }
 }
 
+// Processing AgentOutMany message
+//
+// The targets, probes and sequence numbers are looked up once for the whole batch,
+// and the items are delivered to each target in order.
+pub async fn agent_out_many(
+    env: &ASKit,
+    source_agent: String,
+    ctx: AgentContext,
+    pin: String,
+    data: Vec<AgentData>,
+) {
+    if data.is_empty() || env.muted_agents.lock().unwrap().contains(&source_agent) {
+        return;
+    }
+    for item in &data {
+        testing::tap(env, &source_agent, &pin, item);
+    }
+
+    let targets = edge_targets(env, &source_agent, &pin);
+    let size = data.len();
+
+    for target in targets {
+        let (target_agent, source_pin, target_pin, edge_id) = target;
+
+        {
+            let env_agents = env.agents.lock().unwrap();
+            if !env_agents.contains_key(&target_agent) {
+                continue;
+            }
+        }
+
+        {
+            let mut env_probes = env.edge_probes.lock().unwrap();
+            for probe in env_probes.values_mut() {
+                if probe.matches(&source_agent, &source_pin, &target_agent, &target_pin) {
+                    for item in &data {
+                        probe.record(item);
+                    }
+                }
+            }
+        }
+
+        let target_pin = if target_pin == "*" {
+            pin.clone()
+        } else {
+            target_pin.clone()
+        };
+
+        // reserve the sequence numbers of the whole batch
+        let first = next_sequences(env, &edge_id, size as u64);
+
+        for (index, item) in data.iter().enumerate() {
+            let ctx = ctx
+                .with_sequence(edge_id.clone(), first + index as u64)
+                .with_batch(index, size);
+            env.agent_input(target_agent.clone(), ctx, target_pin.clone(), item.clone())
+                .await
+                .unwrap_or_else(|e| {
+                    log::error!("Failed to send message to {}: {}", target_agent, e);
+                });
+        }
+    }
+}
+
+// Reserves the next `count` sequence numbers of the edge, and returns the first one.
+fn next_sequences(env: &ASKit, edge_id: &str, count: u64) -> u64 {
+    let mut env_seqs = env.edge_seqs.lock().unwrap();
+    let seq = env_seqs.entry(edge_id.to_string()).or_insert(0);
+    let first = *seq + 1;
+    *seq += count;
+    first
+}
+
+// Edges from the port of the source agent, after routing.
+fn edge_targets(
+    env: &ASKit,
+    source_agent: &str,
+    pin: &str,
+) -> Vec<(String, String, String, String)> {
+    let targets;
+    {
+        let env_edges = env.edges.lock().unwrap();
+        targets = env_edges.get(source_agent).cloned();
+    }
+
+    let Some(targets) = targets else {
+        return Vec::new();
+    };
+
+    // Skip if source_handle does not match with the given port.
+    // "*" is a wildcard, and outputs messages of all ports.
+    let targets = targets
+        .into_iter()
+        .filter(|(_, source_pin, _, _)| *source_pin == pin || source_pin == "*")
+        .collect::<Vec<_>>();
+    env.edge_routes.lock().unwrap().route(source_agent, targets)
+}
+
 pub async fn board_out(env: &ASKit, name: String, ctx: AgentContext, data: AgentData) {
     let board_nodes;
     {
         let env_board_nodes = env.board_out_agents.lock().unwrap();
-        board_nodes = env_board_nodes.get(&name).cloned();
+        board_nodes = env_board_nodes
+            .iter()
+            .filter(|(pattern, _)| board_agent::board_name_matches(pattern, &name))
+            .flat_map(|(_, nodes)| nodes.iter().cloned())
+            .collect::<Vec<_>>();
     }
-    if let Some(board_nodes) = board_nodes {
+    if !board_nodes.is_empty() {
+        let ctx = ctx.with_board_name(&name);
         for node in board_nodes {
+            if env.muted_agents.lock().unwrap().contains(&node) {
+                continue;
+            }
             // Perhaps we could process this by send_message_to BoardOutAgent
+            testing::tap(env, &node, &name, &data);
 
-            let edges;
+            for (target_agent, _source_pin, target_pin, edge_id) in edge_targets(env, &node, &name)
             {
-                let env_edges = env.edges.lock().unwrap();
-                edges = env_edges.get(&node).cloned();
-            }
-            let Some(edges) = edges else {
-                // edges not found
-                continue;
-            };
-            for (target_agent, _source_handle, target_handle) in edges {
-                let target_pin = if target_handle == "*" {
+                {
+                    let env_agents = env.agents.lock().unwrap();
+                    if !env_agents.contains_key(&target_agent) {
+                        continue;
+                    }
+                }
+
+                let target_pin = if target_pin == "*" {
                     // If target_handle is "*", use the board name
                     name.clone()
                 } else {
-                    target_handle.clone()
+                    target_pin.clone()
                 };
-                env.agent_input(target_agent.clone(), ctx.clone(), target_pin, data.clone())
+                let seq = next_sequences(env, &edge_id, 1);
+                let ctx = ctx.with_sequence(edge_id, seq);
+                env.agent_input(target_agent.clone(), ctx, target_pin, data.clone())
                     .await
                     .unwrap_or_else(|e| {
                         log::error!("Failed to send message to {}: {}", target_agent, e);
@@ -157,3 +297,113 @@ pub async fn board_out(env: &ASKit, name: String, ctx: AgentContext, data: Agent
 
     env.emit_board(name, data);
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::askit::{ASKitEvent, ASKitObserver};
+    use crate::config::AgentConfigs;
+    use crate::data::AgentValue;
+    use crate::flow::{AgentFlow, AgentFlowEdge};
+
+    struct InputObserver(Arc<Mutex<Vec<String>>>);
+
+    impl ASKitObserver for InputObserver {
+        fn notify(&self, event: &ASKitEvent) {
+            if let ASKitEvent::AgentIn(agent_id, _) = event {
+                self.0.lock().unwrap().push(agent_id.clone());
+            }
+        }
+    }
+
+    // Board Out node "out" of the board "x", with edges to the Board In nodes "a" and "b".
+    async fn board_flow() -> (ASKit, Arc<Mutex<Vec<String>>>) {
+        let askit = ASKit::init().unwrap();
+        askit.ready().await.unwrap();
+
+        let mut flow = AgentFlow::new("flow".into());
+        for (id, def_name, board) in [
+            ("out", "core_board_out", "x"),
+            ("a", "core_board_in", "a"),
+            ("b", "core_board_in", "b"),
+        ] {
+            let mut node = askit.new_agent_flow_node(def_name).unwrap();
+            node.id = id.into();
+            node.enabled = true;
+            let mut configs = AgentConfigs::new();
+            configs.set("$board".into(), AgentValue::string(board));
+            node.configs = Some(configs);
+            flow.add_node(node);
+        }
+        for target in ["a", "b"] {
+            flow.add_edge(AgentFlowEdge {
+                id: format!("out-{}", target),
+                source: "out".into(),
+                source_handle: "*".into(),
+                target: target.into(),
+                target_handle: "*".into(),
+                ..Default::default()
+            });
+        }
+        askit.add_agent_flow(&flow).unwrap();
+        askit.start_agent_flow("flow").await.unwrap();
+
+        let inputs = Arc::new(Mutex::new(Vec::new()));
+        askit.subscribe(Box::new(InputObserver(inputs.clone())));
+        (askit, inputs)
+    }
+
+    #[tokio::test]
+    async fn test_board_out_routes_edges() {
+        let (askit, inputs) = board_flow().await;
+        askit.set_edge_enabled("flow", "out-b", false).unwrap();
+
+        board_out(
+            &askit,
+            "x".into(),
+            AgentContext::new(),
+            AgentData::string("hello"),
+        )
+        .await;
+        assert_eq!(*inputs.lock().unwrap(), ["a"]);
+    }
+
+    #[tokio::test]
+    async fn test_board_out_muted() {
+        let (askit, inputs) = board_flow().await;
+        askit.set_node_muted("flow", "out", true).unwrap();
+
+        board_out(
+            &askit,
+            "x".into(),
+            AgentContext::new(),
+            AgentData::string("hello"),
+        )
+        .await;
+        assert!(inputs.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_board_out_sequences() {
+        let (askit, _inputs) = board_flow().await;
+        for _ in 0..2 {
+            board_out(
+                &askit,
+                "x".into(),
+                AgentContext::new(),
+                AgentData::string("hello"),
+            )
+            .await;
+        }
+        assert_eq!(askit.edge_seqs.lock().unwrap().get("out-a"), Some(&2));
+        assert_eq!(askit.edge_seqs.lock().unwrap().get("out-b"), Some(&2));
+
+        // the numbers go away with their edges
+        askit.remove_agent_flow_edge("flow", "out-a").unwrap();
+        assert!(!askit.edge_seqs.lock().unwrap().contains_key("out-a"));
+        askit.remove_agent_flow("flow").await.unwrap();
+        assert!(askit.edge_seqs.lock().unwrap().is_empty());
+    }
+}
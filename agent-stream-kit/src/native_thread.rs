@@ -0,0 +1,90 @@
+use std::future::Future;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use super::askit::ASKit;
+use super::error::AgentError;
+
+/// Time `ASKit::stop_agent` waits for the thread of a native-thread agent to end.
+pub const DEFAULT_THREAD_JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+const JOIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Runs the message loop of a native-thread agent on a named thread tracked by ASKit.
+// The loop is driven by the current runtime so that agents can still use tokio.
+pub(crate) fn spawn<F>(askit: &ASKit, agent_id: String, fut: F) -> Result<(), AgentError>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let runtime = tokio::runtime::Handle::current();
+    let handle = std::thread::Builder::new()
+        .name(format!("askit-agent-{}", agent_id))
+        .spawn(move || runtime.block_on(fut))
+        .map_err(|e| AgentError::Other(format!("Failed to spawn thread of {}: {}", agent_id, e)))?;
+    let old = askit
+        .agent_threads
+        .lock()
+        .unwrap()
+        .insert(agent_id.clone(), handle);
+    if old.is_some() {
+        log::warn!(
+            "Thread of agent {} was replaced before it was joined",
+            agent_id
+        );
+    }
+    Ok(())
+}
+
+// Waits for the thread of the agent after its loop was told to stop.
+// Returns false if the thread did not end in time; it stays tracked then.
+pub(crate) async fn join(askit: &ASKit, agent_id: &str, timeout: Duration) -> bool {
+    let Some(handle) = askit.agent_threads.lock().unwrap().remove(agent_id) else {
+        return true;
+    };
+    match join_handle(handle, timeout).await {
+        Ok(()) => true,
+        Err(handle) => {
+            log::warn!("Thread of agent {} did not stop in {:?}", agent_id, timeout);
+            askit
+                .agent_threads
+                .lock()
+                .unwrap()
+                .insert(agent_id.to_string(), handle);
+            false
+        }
+    }
+}
+
+async fn join_handle(handle: JoinHandle<()>, timeout: Duration) -> Result<(), JoinHandle<()>> {
+    let deadline = Instant::now() + timeout;
+    while !handle.is_finished() {
+        if Instant::now() >= deadline {
+            return Err(handle);
+        }
+        tokio::time::sleep(JOIN_POLL_INTERVAL).await;
+    }
+    if handle.join().is_err() {
+        log::error!("Agent thread panicked");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_join_timeout() {
+        let (tx, rx) = std::sync::mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            let _ = rx.recv();
+        });
+        let handle = join_handle(handle, Duration::from_millis(20))
+            .await
+            .unwrap_err();
+
+        // ends once the loop is told to stop
+        drop(tx);
+        assert!(join_handle(handle, Duration::from_secs(1)).await.is_ok());
+    }
+}
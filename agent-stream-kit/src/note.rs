@@ -0,0 +1,17 @@
+use super::askit::ASKit;
+use super::definition::AgentDefinition;
+
+/// Definition name of the note nodes, which document a flow and never run.
+pub static NOTE_DEF_NAME: &str = "core_note";
+
+pub(crate) static CONFIG_TEXT: &str = "text";
+
+pub fn register_agents(askit: &ASKit) {
+    // Note has no agent. The runtime skips its nodes.
+    askit.register_agent(
+        AgentDefinition::new("Note", NOTE_DEF_NAME, None)
+            .title("Note")
+            .category("Core")
+            .text_config_with(CONFIG_TEXT, "", |entry| entry.title("Text")),
+    );
+}
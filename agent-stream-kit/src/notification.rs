@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+
+/// Severity of a notification.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationLevel {
+    #[default]
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for NotificationLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            NotificationLevel::Info => "info",
+            NotificationLevel::Success => "success",
+            NotificationLevel::Warning => "warning",
+            NotificationLevel::Error => "error",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for NotificationLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(NotificationLevel::Info),
+            "success" => Ok(NotificationLevel::Success),
+            "warning" => Ok(NotificationLevel::Warning),
+            "error" => Ok(NotificationLevel::Error),
+            _ => Err(format!("Unknown notification level: {}", s)),
+        }
+    }
+}
+
+/// User-facing alert raised by a flow.
+///
+/// Unlike agent errors, notifications are meant to be shown to the user of the host
+/// application as they are.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub title: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub body: String,
+
+    /// Agent that raised the notification. Empty when raised by the host.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub agent_id: String,
+
+    /// Time after which the host may dismiss the notification, in milliseconds.
+    /// Kept until the user dismisses it when `None`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dismiss_after_ms: Option<u64>,
+}
+
+impl Notification {
+    pub fn new(level: NotificationLevel, title: impl Into<String>) -> Self {
+        Self {
+            level,
+            title: title.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    pub fn dismiss_after_ms(mut self, ms: u64) -> Self {
+        self.dismiss_after_ms = Some(ms);
+        self
+    }
+}
@@ -1,8 +1,13 @@
+use std::time::Duration;
+
 use crate::error::AgentError;
 
 use super::agent::Agent;
 use super::context::AgentContext;
 use super::data::AgentData;
+use super::notification::Notification;
+use super::transaction;
+use super::usage::AgentUsage;
 
 pub trait AgentOutput {
     fn try_output_raw(
@@ -21,6 +26,56 @@ pub trait AgentOutput {
         self.try_output_raw(ctx, pin.into(), data)
     }
 
+    /// Outputs the items in order as one batch.
+    ///
+    /// The batch goes to the runtime as a single message, so this is much cheaper than
+    /// calling `try_output` for each item. Each item arrives as its own input, and
+    /// `AgentContext::batch` tells its position in the batch.
+    fn emit_many_raw(
+        &self,
+        ctx: AgentContext,
+        pin: String,
+        data: Vec<AgentData>,
+    ) -> Result<(), AgentError>;
+
+    fn emit_many<S: Into<String>>(
+        &self,
+        ctx: AgentContext,
+        pin: S,
+        data: Vec<AgentData>,
+    ) -> Result<(), AgentError> {
+        self.emit_many_raw(ctx, pin.into(), data)
+    }
+
+    /// Outputs the items as a single array, for targets that handle the batch at once.
+    ///
+    /// The kind of the array is the kind of the first item.
+    fn emit_array<S: Into<String>>(
+        &self,
+        ctx: AgentContext,
+        pin: S,
+        data: Vec<AgentData>,
+    ) -> Result<(), AgentError> {
+        let kind = data
+            .first()
+            .map(|item| item.kind.clone())
+            .unwrap_or_else(|| "unit".to_string());
+        let values = data.into_iter().map(|item| item.value).collect();
+        self.try_output_raw(ctx, pin.into(), AgentData::array(kind, values))
+    }
+
+    /// Outputs a request and waits for the first input that responds to it.
+    ///
+    /// The request carries a correlation id in its context. The input coming back to
+    /// this agent with the id is returned here instead of being passed to `process`.
+    fn request<S: Into<String>>(
+        &self,
+        ctx: AgentContext,
+        pin: S,
+        data: AgentData,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<AgentData, AgentError>> + Send + 'static;
+
     fn emit_display_raw(&self, key: String, data: AgentData);
 
     fn emit_display<S: Into<String>>(&self, key: S, data: AgentData) {
@@ -33,6 +88,12 @@ pub trait AgentOutput {
     fn emit_error<S: Into<String>>(&self, message: S) {
         self.emit_error_raw(message.into());
     }
+
+    /// Reports token usage of a paid provider call for cost accounting.
+    fn emit_usage(&self, usage: AgentUsage);
+
+    /// Raises a user-facing alert from the agent.
+    fn emit_notification(&self, notification: Notification);
 }
 
 impl<T: Agent> AgentOutput for T {
@@ -42,10 +103,45 @@ impl<T: Agent> AgentOutput for T {
         pin: String,
         data: AgentData,
     ) -> Result<(), AgentError> {
+        let Some((ctx, pin, data)) = transaction::stage(ctx, pin, data) else {
+            return Ok(());
+        };
         self.askit()
             .try_send_agent_out(self.id().into(), ctx, pin, data)
     }
 
+    fn emit_many_raw(
+        &self,
+        ctx: AgentContext,
+        pin: String,
+        data: Vec<AgentData>,
+    ) -> Result<(), AgentError> {
+        // inside a transaction, the items are staged one by one
+        let data = data
+            .into_iter()
+            .filter_map(|item| transaction::stage(ctx.clone(), pin.clone(), item))
+            .map(|(_, _, item)| item)
+            .collect::<Vec<_>>();
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.askit()
+            .try_send_agent_out_many(self.id().into(), ctx, pin, data)
+    }
+
+    fn request<S: Into<String>>(
+        &self,
+        ctx: AgentContext,
+        pin: S,
+        data: AgentData,
+        timeout: Duration,
+    ) -> impl Future<Output = Result<AgentData, AgentError>> + Send + 'static {
+        let askit = self.askit().clone();
+        let agent_id = self.id().to_string();
+        let pin = pin.into();
+        async move { askit.request(agent_id, ctx, pin, data, timeout).await }
+    }
+
     fn emit_display_raw(&self, key: String, data: AgentData) {
         self.askit()
             .emit_agent_display(self.id().to_string(), key, data);
@@ -55,4 +151,14 @@ impl<T: Agent> AgentOutput for T {
         self.askit()
             .emit_agent_error(self.id().to_string(), message);
     }
+
+    fn emit_usage(&self, usage: AgentUsage) {
+        self.askit()
+            .record_usage(self.flow_name(), self.id(), usage);
+    }
+
+    fn emit_notification(&self, mut notification: Notification) {
+        notification.agent_id = self.id().to_string();
+        self.askit().emit_notification(notification);
+    }
 }
@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+
+use super::askit::ASKit;
+use super::context::AgentContext;
+use super::data::AgentData;
+use super::error::AgentError;
+
+// Inputs held back while a flow is paused for maintenance
+pub(crate) struct PausedFlow {
+    limit: usize,
+    buffered: VecDeque<BufferedInput>,
+    dropped: usize,
+}
+
+struct BufferedInput {
+    agent_id: String,
+    ctx: AgentContext,
+    pin: String,
+    data: AgentData,
+}
+
+impl PausedFlow {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            buffered: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    // Flow on standby, discarding its inputs
+    pub(crate) fn standby() -> Self {
+        Self::new(0)
+    }
+
+    pub(crate) fn buffered_len(&self) -> usize {
+        self.buffered.len()
+    }
+
+    // Keeps the newest inputs when the buffer is full.
+    fn push(&mut self, input: BufferedInput) {
+        if self.limit == 0 {
+            self.dropped += 1;
+            return;
+        }
+        if self.buffered.len() >= self.limit {
+            self.buffered.pop_front();
+            self.dropped += 1;
+        }
+        self.buffered.push_back(input);
+    }
+}
+
+pub(crate) const DEFAULT_PAUSE_BUFFER_LIMIT: usize = 1000;
+
+// Called by agent_input. Returns the input back when the flow is not paused.
+pub(crate) fn buffer_input(
+    askit: &ASKit,
+    flow_name: &str,
+    agent_id: &str,
+    pin: &str,
+    ctx: AgentContext,
+    data: AgentData,
+) -> Option<(AgentContext, AgentData)> {
+    let mut paused_flows = askit.paused_flows.lock().unwrap();
+    let Some(paused) = paused_flows.get_mut(flow_name) else {
+        return Some((ctx, data));
+    };
+    paused.push(BufferedInput {
+        agent_id: agent_id.to_string(),
+        ctx,
+        pin: pin.to_string(),
+        data,
+    });
+    None
+}
+
+// Moves the traffic from the old flow to its standby version in one step.
+// The old flow discards its inputs from then on.
+pub(crate) fn swap(askit: &ASKit, old_name: &str, standby_name: &str) {
+    let mut paused_flows = askit.paused_flows.lock().unwrap();
+    paused_flows.remove(standby_name);
+    paused_flows.insert(old_name.to_string(), PausedFlow::standby());
+}
+
+// Delivers the buffered inputs in arrival order.
+pub(crate) async fn resume(askit: &ASKit, flow_name: &str) -> Result<(), AgentError> {
+    let Some(paused) = askit.paused_flows.lock().unwrap().remove(flow_name) else {
+        return Ok(());
+    };
+    if paused.dropped > 0 {
+        log::warn!(
+            "Dropped {} inputs to {} while it was paused",
+            paused.dropped,
+            flow_name
+        );
+    }
+    for input in paused.buffered {
+        askit
+            .deliver_input(input.agent_id.clone(), input.ctx, input.pin, input.data)
+            .await
+            .unwrap_or_else(|e| {
+                log::error!(
+                    "Failed to deliver buffered input to {}: {}",
+                    input.agent_id,
+                    e
+                );
+            });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(n: i64) -> BufferedInput {
+        BufferedInput {
+            agent_id: "1".to_string(),
+            ctx: AgentContext::new(),
+            pin: "in".to_string(),
+            data: AgentData::integer(n),
+        }
+    }
+
+    #[test]
+    fn test_paused_flow_limit() {
+        let mut paused = PausedFlow::new(2);
+        for n in 1..=3 {
+            paused.push(input(n));
+        }
+        assert_eq!(paused.buffered_len(), 2);
+        assert_eq!(paused.dropped, 1);
+        assert_eq!(paused.buffered[0].data.as_i64(), Some(2));
+    }
+
+    #[test]
+    fn test_swap_standby() {
+        let askit = ASKit::new();
+        askit
+            .paused_flows
+            .lock()
+            .unwrap()
+            .insert("flow-standby".to_string(), PausedFlow::standby());
+
+        swap(&askit, "flow", "flow-standby");
+        assert!(askit.is_agent_flow_paused("flow"));
+        assert!(!askit.is_agent_flow_paused("flow-standby"));
+
+        // the retired flow discards its inputs
+        let ctx = AgentContext::new();
+        let data = AgentData::integer(1);
+        assert!(buffer_input(&askit, "flow", "1", "in", ctx, data).is_none());
+        assert_eq!(askit.get_buffered_input_count("flow"), 0);
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::data::AgentData;
+use super::flow::AgentFlowEdge;
+
+/// Data that passed through a probed edge.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProbeRecord {
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+    pub data: AgentData,
+}
+
+// Last payloads of an edge
+pub(crate) struct EdgeProbe {
+    edge: AgentFlowEdge,
+    capacity: usize,
+    records: VecDeque<ProbeRecord>,
+}
+
+impl EdgeProbe {
+    pub(crate) fn new(edge: AgentFlowEdge, capacity: usize) -> Self {
+        Self {
+            edge,
+            capacity,
+            records: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub(crate) fn matches(
+        &self,
+        source: &str,
+        source_handle: &str,
+        target: &str,
+        target_handle: &str,
+    ) -> bool {
+        self.edge.source == source
+            && self.edge.source_handle == source_handle
+            && self.edge.target == target
+            && self.edge.target_handle == target_handle
+    }
+
+    pub(crate) fn record(&mut self, data: &AgentData) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.records.len() >= self.capacity {
+            self.records.pop_front();
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        self.records.push_back(ProbeRecord {
+            timestamp,
+            data: data.clone(),
+        });
+    }
+
+    pub(crate) fn records(&self) -> Vec<ProbeRecord> {
+        self.records.iter().cloned().collect()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.records.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edge_probe() {
+        let edge = AgentFlowEdge {
+            id: "e1".to_string(),
+            source: "1".to_string(),
+            source_handle: "out".to_string(),
+            target: "2".to_string(),
+            target_handle: "in".to_string(),
+            ..Default::default()
+        };
+        let mut probe = EdgeProbe::new(edge, 2);
+        assert!(probe.matches("1", "out", "2", "in"));
+        assert!(!probe.matches("1", "err", "2", "in"));
+
+        for i in 0..3 {
+            probe.record(&AgentData::integer(i));
+        }
+        let records = probe.records();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].data.as_i64(), Some(1));
+        assert_eq!(records[1].data.as_i64(), Some(2));
+    }
+}
@@ -0,0 +1,338 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use super::agent::{AsAgent, AsAgentData};
+use super::askit::ASKit;
+use super::config::AgentConfigs;
+use super::context::AgentContext;
+use super::data::AgentData;
+use super::error::AgentError;
+use super::output::AgentOutput;
+
+/// Command running an agent in a child process, set by `AgentDefinition::use_process`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessCommand {
+    pub program: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+
+    /// Restarts allowed within a minute before the agent gives up.
+    pub max_restarts: u32,
+}
+
+impl ProcessCommand {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.into(),
+            args: Vec::new(),
+            max_restarts: DEFAULT_MAX_RESTARTS,
+        }
+    }
+
+    pub fn args(mut self, args: Vec<&str>) -> Self {
+        self.args = args.into_iter().map(|x| x.into()).collect();
+        self
+    }
+
+    pub fn max_restarts(mut self, max_restarts: u32) -> Self {
+        self.max_restarts = max_restarts;
+        self
+    }
+}
+
+const DEFAULT_MAX_RESTARTS: u32 = 5;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+
+/// Line of the JSON protocol between ASKit and an agent process over stdio.
+///
+/// ASKit writes `Input` and `Config` to the stdin of the process. The process writes
+/// any number of `Output` for an input, then `Done` or `Error`, to its stdout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ProcessMessage {
+    Input {
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    },
+    Config {
+        configs: AgentConfigs,
+    },
+    Output {
+        pin: String,
+        data: AgentData,
+    },
+    Done,
+    Error {
+        message: String,
+    },
+}
+
+/// Serves an agent in a child process: reads inputs from stdin and writes outputs to stdout.
+///
+/// `handler` returns the outputs of an input. Returns when stdin is closed.
+pub fn serve_process_agent<F>(mut handler: F) -> Result<(), AgentError>
+where
+    F: FnMut(
+        &AgentConfigs,
+        AgentContext,
+        String,
+        AgentData,
+    ) -> Result<Vec<(String, AgentData)>, AgentError>,
+{
+    let stdin = std::io::stdin();
+    let mut stdout = std::io::stdout();
+    let mut configs = AgentConfigs::new();
+    for line in stdin.lock().lines() {
+        let line = line.map_err(|e| AgentError::IoError(e.to_string()))?;
+        let reply = match serde_json::from_str::<ProcessMessage>(&line) {
+            Ok(ProcessMessage::Config {
+                configs: new_configs,
+            }) => {
+                configs = new_configs;
+                continue;
+            }
+            Ok(ProcessMessage::Input { ctx, pin, data }) => {
+                match handler(&configs, ctx, pin, data) {
+                    Ok(outputs) => {
+                        for (pin, data) in outputs {
+                            write_message(&mut stdout, &ProcessMessage::Output { pin, data })?;
+                        }
+                        ProcessMessage::Done
+                    }
+                    Err(e) => ProcessMessage::Error {
+                        message: e.to_string(),
+                    },
+                }
+            }
+            Ok(_) => continue,
+            Err(e) => ProcessMessage::Error {
+                message: e.to_string(),
+            },
+        };
+        write_message(&mut stdout, &reply)?;
+    }
+    Ok(())
+}
+
+fn write_message(writer: &mut impl Write, message: &ProcessMessage) -> Result<(), AgentError> {
+    let line = serde_json::to_string(message)
+        .map_err(|e| AgentError::SerializationError(e.to_string()))?;
+    writeln!(writer, "{}", line)
+        .and_then(|_| writer.flush())
+        .map_err(|e| AgentError::IoError(e.to_string()))
+}
+
+// Running child process and the messages read from its stdout
+struct ProcessHandle {
+    child: Child,
+    stdin: ChildStdin,
+    rx: mpsc::UnboundedReceiver<ProcessMessage>,
+}
+
+impl ProcessHandle {
+    fn spawn(command: &ProcessCommand) -> Result<Self, AgentError> {
+        let mut child = Command::new(&command.program)
+            .args(&command.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| AgentError::IoError(format!("{}: {}", command.program, e)))?;
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+
+        let (tx, rx) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                match serde_json::from_str::<ProcessMessage>(&line) {
+                    Ok(message) => {
+                        if tx.send(message).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => log::warn!("Invalid message from agent process: {}", e),
+                }
+            }
+        });
+        Ok(Self { child, stdin, rx })
+    }
+
+    fn send(&mut self, message: &ProcessMessage) -> Result<(), AgentError> {
+        write_message(&mut self.stdin, message)
+    }
+
+    fn kill(mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Agent whose definition runs it in a child process.
+///
+/// A crashed process is restarted on the next input, up to `max_restarts` times a minute.
+pub struct ProcessAgent {
+    data: AsAgentData,
+    command: ProcessCommand,
+    handle: Option<ProcessHandle>,
+    restarts: Vec<Instant>,
+}
+
+impl ProcessAgent {
+    fn ensure_process(&mut self) -> Result<&mut ProcessHandle, AgentError> {
+        if self.handle.is_none() {
+            let now = Instant::now();
+            self.restarts
+                .retain(|at| now.duration_since(*at) < RESTART_WINDOW);
+            if self.restarts.len() as u32 >= self.command.max_restarts {
+                return Err(AgentError::Other(format!(
+                    "Agent process {} restarted too often",
+                    self.command.program
+                )));
+            }
+            self.restarts.push(now);
+            self.spawn()?;
+        }
+        Ok(self.handle.as_mut().unwrap())
+    }
+
+    fn spawn(&mut self) -> Result<(), AgentError> {
+        let mut handle = ProcessHandle::spawn(&self.command)?;
+        if let Some(configs) = self.data.configs.clone() {
+            handle.send(&ProcessMessage::Config { configs })?;
+        }
+        self.handle = Some(handle);
+        Ok(())
+    }
+
+    fn crashed(&mut self) -> AgentError {
+        if let Some(handle) = self.handle.take() {
+            handle.kill();
+        }
+        AgentError::Other(format!("Agent process {} exited", self.command.program))
+    }
+}
+
+#[async_trait]
+impl AsAgent for ProcessAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        let Some(command) = askit
+            .get_agent_definition(&def_name)
+            .and_then(|def| def.process)
+        else {
+            return Err(AgentError::InvalidDefinition(
+                def_name,
+                "no process command".into(),
+            ));
+        };
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            command,
+            handle: None,
+            restarts: Vec::new(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        self.restarts.clear();
+        self.spawn()
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        if let Some(handle) = self.handle.take() {
+            handle.kill();
+        }
+        Ok(())
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        let Some(configs) = self.data.configs.clone() else {
+            return Ok(());
+        };
+        if let Some(handle) = self.handle.as_mut()
+            && handle.send(&ProcessMessage::Config { configs }).is_err()
+        {
+            return Err(self.crashed());
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let input = ProcessMessage::Input {
+            ctx: ctx.clone(),
+            pin,
+            data,
+        };
+        if self.ensure_process()?.send(&input).is_err() {
+            return Err(self.crashed());
+        }
+        loop {
+            let message = self.handle.as_mut().unwrap().rx.recv().await;
+            match message {
+                Some(ProcessMessage::Output { pin, data }) => {
+                    self.try_output(ctx.clone(), pin, data)?;
+                }
+                Some(ProcessMessage::Done) => return Ok(()),
+                Some(ProcessMessage::Error { message }) => {
+                    return Err(AgentError::Other(message));
+                }
+                Some(_) => {}
+                None => return Err(self.crashed()),
+            }
+        }
+    }
+}
+
+impl Drop for ProcessAgent {
+    fn drop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.kill();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_message_json() {
+        let message = ProcessMessage::Output {
+            pin: "out".into(),
+            data: AgentData::integer(1),
+        };
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"output","pin":"out","data":{"kind":"integer","value":1}}"#
+        );
+        let message: ProcessMessage = serde_json::from_str(r#"{"type":"done"}"#).unwrap();
+        assert!(matches!(message, ProcessMessage::Done));
+    }
+}
@@ -0,0 +1,167 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::askit::ASKit;
+
+/// Timings of a node while its flow was profiled. Durations are in microseconds.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeProfile {
+    pub agent_id: String,
+    pub def_name: String,
+
+    /// Number of processed inputs.
+    pub count: u64,
+
+    pub total_process_us: u64,
+    pub max_process_us: u64,
+
+    /// Time inputs waited in the queue of the node before processing started.
+    pub total_queue_us: u64,
+    pub max_queue_us: u64,
+}
+
+impl NodeProfile {
+    pub fn mean_process_us(&self) -> u64 {
+        self.total_process_us.checked_div(self.count).unwrap_or(0)
+    }
+
+    pub fn mean_queue_us(&self) -> u64 {
+        self.total_queue_us.checked_div(self.count).unwrap_or(0)
+    }
+
+    fn add(&mut self, process: Duration, queue: Duration) {
+        let process = process.as_micros() as u64;
+        let queue = queue.as_micros() as u64;
+        self.count += 1;
+        self.total_process_us += process;
+        self.max_process_us = self.max_process_us.max(process);
+        self.total_queue_us += queue;
+        self.max_queue_us = self.max_queue_us.max(queue);
+    }
+}
+
+/// Profiling result of a flow.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowProfileReport {
+    pub flow_name: String,
+
+    /// Time since profiling was enabled, in microseconds.
+    pub elapsed_us: u64,
+
+    /// Nodes ordered by total processing time, the busiest first.
+    pub nodes: Vec<NodeProfile>,
+}
+
+impl FlowProfileReport {
+    /// The node that spent the most time processing or waiting in its queue.
+    pub fn bottleneck(&self) -> Option<&NodeProfile> {
+        self.nodes
+            .iter()
+            .max_by_key(|node| node.total_process_us + node.total_queue_us)
+    }
+
+    /// The report in the nested `name` / `value` / `children` form used by flame graph viewers.
+    pub fn to_flamegraph_json(&self) -> serde_json::Value {
+        let children = self
+            .nodes
+            .iter()
+            .map(|node| {
+                serde_json::json!({
+                    "name": format!("{} ({})", node.def_name, node.agent_id),
+                    "value": node.total_process_us,
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "name": self.flow_name,
+            "value": self.nodes.iter().map(|node| node.total_process_us).sum::<u64>(),
+            "children": children,
+        })
+    }
+}
+
+pub(crate) struct FlowProfile {
+    started_at: Instant,
+    nodes: HashMap<String, NodeProfile>,
+}
+
+impl FlowProfile {
+    pub(crate) fn new() -> Self {
+        Self {
+            started_at: Instant::now(),
+            nodes: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn report(&self, flow_name: &str) -> FlowProfileReport {
+        let mut nodes = self.nodes.values().cloned().collect::<Vec<_>>();
+        nodes.sort_by_key(|node| Reverse(node.total_process_us));
+        FlowProfileReport {
+            flow_name: flow_name.to_string(),
+            elapsed_us: self.started_at.elapsed().as_micros() as u64,
+            nodes,
+        }
+    }
+}
+
+// Called by the agent loops after an input has been processed.
+pub(crate) fn record(
+    askit: &ASKit,
+    flow_name: &str,
+    agent_id: &str,
+    def_name: &str,
+    queued_at: Instant,
+    started_at: Instant,
+) {
+    let mut profiles = askit.flow_profiles.lock().unwrap();
+    let Some(profile) = profiles.get_mut(flow_name) else {
+        return;
+    };
+    profile
+        .nodes
+        .entry(agent_id.to_string())
+        .or_insert_with(|| NodeProfile {
+            agent_id: agent_id.to_string(),
+            def_name: def_name.to_string(),
+            ..Default::default()
+        })
+        .add(started_at.elapsed(), started_at - queued_at);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_profile_report() {
+        let mut profile = FlowProfile::new();
+        let mut slow = NodeProfile {
+            agent_id: "1".to_string(),
+            def_name: "slow".to_string(),
+            ..Default::default()
+        };
+        slow.add(Duration::from_millis(30), Duration::from_millis(1));
+        slow.add(Duration::from_millis(10), Duration::from_millis(3));
+        let mut fast = NodeProfile {
+            agent_id: "2".to_string(),
+            def_name: "fast".to_string(),
+            ..Default::default()
+        };
+        fast.add(Duration::from_millis(1), Duration::from_millis(50));
+        profile.nodes.insert("1".to_string(), slow);
+        profile.nodes.insert("2".to_string(), fast);
+
+        let report = profile.report("flow");
+        assert_eq!(report.nodes[0].agent_id, "1");
+        assert_eq!(report.nodes[0].mean_process_us(), 20_000);
+        assert_eq!(report.nodes[0].max_queue_us, 3_000);
+        assert_eq!(report.bottleneck().unwrap().agent_id, "2");
+
+        let json = report.to_flamegraph_json();
+        assert_eq!(json["value"], 41_000);
+        assert_eq!(json["children"][1]["name"], "fast (2)");
+    }
+}
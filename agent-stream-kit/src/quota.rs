@@ -0,0 +1,606 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::askit::ASKit;
+use super::context::AgentContext;
+use super::data::{AgentData, AgentValue};
+use super::delivery;
+use super::error::AgentError;
+use super::pause;
+
+/// What the runtime does when a flow exceeds one of its quotas.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaAction {
+    /// Hold the message back until the flow is under its quota again.
+    #[default]
+    Throttle,
+
+    /// Pause the flow, buffering the message and the later ones until it is resumed.
+    PauseFlow,
+
+    /// Drop the message and report an error.
+    Error,
+}
+
+/// The quota that was exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaViolation {
+    MessageRate,
+    BufferedBytes,
+    ConcurrentLlmCalls,
+}
+
+impl std::fmt::Display for QuotaViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            QuotaViolation::MessageRate => "message rate",
+            QuotaViolation::BufferedBytes => "buffered bytes",
+            QuotaViolation::ConcurrentLlmCalls => "concurrent LLM calls",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Resource limits applied to a single flow.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlowQuota {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_messages_per_sec: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_buffered_bytes: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_concurrent_llm_calls: Option<usize>,
+
+    #[serde(default)]
+    pub action: QuotaAction,
+}
+
+impl FlowQuota {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_messages_per_sec(mut self, max: u32) -> Self {
+        self.max_messages_per_sec = Some(max);
+        self
+    }
+
+    pub fn max_buffered_bytes(mut self, max: usize) -> Self {
+        self.max_buffered_bytes = Some(max);
+        self
+    }
+
+    pub fn max_concurrent_llm_calls(mut self, max: usize) -> Self {
+        self.max_concurrent_llm_calls = Some(max);
+        self
+    }
+
+    pub fn action(mut self, action: QuotaAction) -> Self {
+        self.action = action;
+        self
+    }
+}
+
+// Runtime usage of a flow with a quota, timed by the clock of ASKit
+pub(crate) struct FlowQuotaState {
+    quota: FlowQuota,
+    window_start: SystemTime,
+    window_count: u32,
+    buffered_bytes: usize,
+    llm_calls: Option<Arc<Semaphore>>,
+
+    // inputs held back by the throttle, and whether a task is delivering them
+    held: VecDeque<HeldInput>,
+    delivering: bool,
+}
+
+// Input waiting for the throttled flow to be under its quota again
+struct HeldInput {
+    agent_id: String,
+    ctx: AgentContext,
+    pin: String,
+    data: AgentData,
+    delivery_id: Option<u64>,
+    size: usize,
+}
+
+impl FlowQuotaState {
+    pub(crate) fn new(quota: FlowQuota) -> Self {
+        let llm_calls = quota
+            .max_concurrent_llm_calls
+            .map(|max| Arc::new(Semaphore::new(max)));
+        Self {
+            quota,
+            // the first input starts a new window
+            window_start: SystemTime::UNIX_EPOCH,
+            window_count: 0,
+            buffered_bytes: 0,
+            llm_calls,
+            held: VecDeque::new(),
+            delivering: false,
+        }
+    }
+
+    pub(crate) fn quota(&self) -> &FlowQuota {
+        &self.quota
+    }
+
+    // New quota of the flow, keeping the inputs held back by the old one
+    pub(crate) fn replace(&mut self, quota: FlowQuota) {
+        let held = std::mem::take(&mut self.held);
+        let delivering = self.delivering;
+        *self = Self::new(quota);
+        self.held = held;
+        self.delivering = delivering;
+    }
+
+    pub(crate) fn clear_held(&mut self) {
+        self.held.clear();
+    }
+
+    // Called when the state moves to another flow name, as the task delivering the held
+    // inputs looks them up by name.
+    pub(crate) fn renamed(&mut self, askit: &ASKit, flow_name: &str) {
+        self.delivering = false;
+        self.deliver_held(askit, flow_name);
+    }
+
+    fn deliver_held(&mut self, askit: &ASKit, flow_name: &str) {
+        if self.delivering || self.held.is_empty() {
+            return;
+        }
+        self.delivering = true;
+        tokio::spawn(deliver_held(askit.clone(), flow_name.to_string()));
+    }
+}
+
+enum Admission {
+    Accept,
+    Wait(Duration),
+    Reject(QuotaViolation),
+}
+
+static BUFFER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Most inputs held back by a throttled flow. Later inputs are dropped.
+const HELD_INPUT_LIMIT: usize = 1000;
+
+// Called before an input message is queued for an agent of the flow. Returns the input
+// back when it may be queued now.
+//
+// A throttled input is held and queued later by a task of the flow, so that the message
+// loop never waits for a quota. Later inputs of the flow wait behind it in order.
+pub(crate) fn admit_input(
+    askit: &ASKit,
+    flow_name: &str,
+    agent_id: &str,
+    pin: &str,
+    ctx: AgentContext,
+    data: AgentData,
+    delivery_id: Option<u64>,
+) -> Result<Option<(AgentContext, AgentData)>, AgentError> {
+    let violation = {
+        let mut states = askit.flow_quotas.lock().unwrap();
+        let Some(state) = states.get_mut(flow_name) else {
+            return Ok(Some((ctx, data)));
+        };
+        let size = if state.quota.max_buffered_bytes.is_some() {
+            estimate_size(&data.value)
+        } else {
+            0
+        };
+        let admission = if state.held.is_empty() {
+            check_input(state, size, askit.clock().now())
+        } else {
+            Admission::Wait(Duration::ZERO)
+        };
+        match admission {
+            Admission::Accept => return Ok(Some((ctx, data))),
+            Admission::Wait(_) => {
+                if state.held.len() >= HELD_INPUT_LIMIT {
+                    log::warn!(
+                        "Dropped input to {}:{}, too many inputs held by the quota of {}",
+                        agent_id,
+                        pin,
+                        flow_name
+                    );
+                    delivery::complete(askit, flow_name, delivery_id, &Ok(()));
+                    return Ok(None);
+                }
+                state.held.push_back(HeldInput {
+                    agent_id: agent_id.to_string(),
+                    ctx,
+                    pin: pin.to_string(),
+                    data,
+                    delivery_id,
+                    size,
+                });
+                state.deliver_held(askit, flow_name);
+                return Ok(None);
+            }
+            Admission::Reject(violation) => violation,
+        }
+    };
+
+    quota_exceeded(askit, flow_name, violation)?;
+
+    // PauseFlow keeps the input for when the flow is resumed. The buffered input is
+    // delivered again with a new delivery id.
+    let input = pause::buffer_input(askit, flow_name, agent_id, pin, ctx, data);
+    if input.is_none() {
+        delivery::complete(askit, flow_name, delivery_id, &Ok(()));
+    }
+    Ok(input)
+}
+
+// Queues the held inputs of the flow in order, as the quota lets them through.
+//
+// When the quota has been changed to another action since, the held inputs it rejects
+// get that action, e.g. they are dropped under Error.
+async fn deliver_held(askit: ASKit, flow_name: String) {
+    let clock = askit.clock();
+    loop {
+        let next = {
+            let mut states = askit.flow_quotas.lock().unwrap();
+            let Some(state) = states.get_mut(&flow_name) else {
+                return;
+            };
+            let Some(size) = state.held.front().map(|input| input.size) else {
+                state.delivering = false;
+                return;
+            };
+            match check_input(state, size, clock.now()) {
+                Admission::Wait(duration) => Err(duration),
+                Admission::Accept => Ok(state.held.pop_front().map(|input| (input, None))),
+                Admission::Reject(violation) => {
+                    Ok(state.held.pop_front().map(|input| (input, Some(violation))))
+                }
+            }
+        };
+        match next {
+            Ok(Some((input, None))) => deliver(&askit, &flow_name, input).await,
+            Ok(Some((input, Some(violation)))) => {
+                reject_held(&askit, &flow_name, violation, input).await;
+            }
+            Ok(None) => {}
+            Err(duration) => clock.sleep(duration).await,
+        }
+    }
+}
+
+// Applies the action of the quota to a held input it rejects, as admit_input does to
+// a new input.
+async fn reject_held(askit: &ASKit, flow_name: &str, violation: QuotaViolation, input: HeldInput) {
+    if let Err(e) = quota_exceeded(askit, flow_name, violation) {
+        log::warn!("Dropped held input to {}: {}", input.agent_id, e);
+        delivery::complete(askit, flow_name, input.delivery_id, &Ok(()));
+        return;
+    }
+    let buffered = pause::buffer_input(
+        askit,
+        flow_name,
+        &input.agent_id,
+        &input.pin,
+        input.ctx,
+        input.data,
+    );
+    match buffered {
+        Some((ctx, data)) => deliver(askit, flow_name, HeldInput { ctx, data, ..input }).await,
+        None => {
+            delivery::complete(askit, flow_name, input.delivery_id, &Ok(()));
+        }
+    }
+}
+
+async fn deliver(askit: &ASKit, flow_name: &str, input: HeldInput) {
+    askit
+        .push_input(
+            input.agent_id.clone(),
+            flow_name,
+            input.ctx,
+            input.pin,
+            input.data,
+            input.delivery_id,
+        )
+        .await
+        .unwrap_or_else(|e| {
+            log::error!("Failed to deliver held input to {}: {}", input.agent_id, e);
+        });
+}
+
+// Queues the held inputs at once, when the quota of the flow is removed.
+pub(crate) fn release_held(askit: &ASKit, flow_name: &str, state: FlowQuotaState) {
+    if state.held.is_empty() {
+        return;
+    }
+    let askit = askit.clone();
+    let flow_name = flow_name.to_string();
+    tokio::spawn(async move {
+        for input in state.held {
+            deliver(&askit, &flow_name, input).await;
+        }
+    });
+}
+
+fn check_input(state: &mut FlowQuotaState, size: usize, now: SystemTime) -> Admission {
+    let throttle = state.quota.action == QuotaAction::Throttle;
+
+    if let Some(max) = state.quota.max_messages_per_sec {
+        let elapsed = now.duration_since(state.window_start).unwrap_or_default();
+        if elapsed >= Duration::from_secs(1) {
+            state.window_start = now;
+            state.window_count = 0;
+        } else if state.window_count >= max {
+            if throttle {
+                return Admission::Wait(Duration::from_secs(1) - elapsed);
+            }
+            return Admission::Reject(QuotaViolation::MessageRate);
+        }
+    }
+
+    if let Some(max) = state.quota.max_buffered_bytes {
+        // A single oversized message is still let through once the buffer is empty.
+        if state.buffered_bytes > 0 && state.buffered_bytes + size > max {
+            if throttle {
+                return Admission::Wait(BUFFER_POLL_INTERVAL);
+            }
+            return Admission::Reject(QuotaViolation::BufferedBytes);
+        }
+        state.buffered_bytes += size;
+    }
+
+    if state.quota.max_messages_per_sec.is_some() {
+        state.window_count += 1;
+    }
+
+    Admission::Accept
+}
+
+// Called when an agent of the flow picks up an input message.
+pub(crate) fn release_input(askit: &ASKit, flow_name: &str, data: &AgentData) {
+    let mut states = askit.flow_quotas.lock().unwrap();
+    let Some(state) = states.get_mut(flow_name) else {
+        return;
+    };
+    if state.quota.max_buffered_bytes.is_some() {
+        state.buffered_bytes = state
+            .buffered_bytes
+            .saturating_sub(estimate_size(&data.value));
+    }
+}
+
+pub(crate) async fn acquire_llm_call(
+    askit: &ASKit,
+    flow_name: &str,
+) -> Result<Option<OwnedSemaphorePermit>, AgentError> {
+    let (semaphore, action) = {
+        let states = askit.flow_quotas.lock().unwrap();
+        let Some(state) = states.get(flow_name) else {
+            return Ok(None);
+        };
+        let Some(semaphore) = &state.llm_calls else {
+            return Ok(None);
+        };
+        (semaphore.clone(), state.quota.action)
+    };
+
+    if action == QuotaAction::Throttle {
+        let permit = semaphore
+            .acquire_owned()
+            .await
+            .map_err(|e| AgentError::Other(e.to_string()))?;
+        return Ok(Some(permit));
+    }
+
+    match semaphore.try_acquire_owned() {
+        Ok(permit) => Ok(Some(permit)),
+        Err(_) => {
+            quota_exceeded(askit, flow_name, QuotaViolation::ConcurrentLlmCalls)?;
+            Err(AgentError::QuotaExceeded(
+                flow_name.to_string(),
+                QuotaViolation::ConcurrentLlmCalls.to_string(),
+            ))
+        }
+    }
+}
+
+fn quota_exceeded(
+    askit: &ASKit,
+    flow_name: &str,
+    violation: QuotaViolation,
+) -> Result<(), AgentError> {
+    log::warn!("Flow {} exceeded its {} quota", flow_name, violation);
+    askit.emit_quota_exceeded(flow_name.to_string(), violation);
+
+    let action = {
+        let states = askit.flow_quotas.lock().unwrap();
+        states.get(flow_name).map(|state| state.quota.action)
+    };
+    match action {
+        Some(QuotaAction::PauseFlow) => {
+            askit.pause_agent_flow(flow_name).unwrap_or_else(|e| {
+                log::error!("Failed to pause agent flow {}: {}", flow_name, e);
+            });
+            Ok(())
+        }
+        Some(QuotaAction::Error) => Err(AgentError::QuotaExceeded(
+            flow_name.to_string(),
+            violation.to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Rough number of bytes held by a value, used for buffered data accounting.
+pub(crate) fn estimate_size(value: &AgentValue) -> usize {
+    match value {
+        AgentValue::Unit => 0,
+        AgentValue::Boolean(_) => 1,
+        AgentValue::Integer(_) | AgentValue::Number(_) | AgentValue::Unsigned(_) => 8,
+        AgentValue::DateTime(_) | AgentValue::Duration(_) => 12,
+        AgentValue::String(s) => s.len(),
+        #[cfg(feature = "image")]
+        AgentValue::Image(img) => (img.get_width() * img.get_height() * 4) as usize,
+        AgentValue::Array(arr) => arr.iter().map(estimate_size).sum(),
+        AgentValue::Object(obj) => obj.iter().map(|(k, v)| k.len() + estimate_size(v)).sum(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::askit::{ASKitEvent, ASKitObserver};
+    use crate::clock::AgentClock;
+
+    #[test]
+    fn test_rate_quota() {
+        let mut state = FlowQuotaState::new(
+            FlowQuota::new()
+                .max_messages_per_sec(2)
+                .action(QuotaAction::Error),
+        );
+        assert!(matches!(
+            check_input(&mut state, 0, SystemTime::now()),
+            Admission::Accept
+        ));
+        assert!(matches!(
+            check_input(&mut state, 0, SystemTime::now()),
+            Admission::Accept
+        ));
+        assert!(matches!(
+            check_input(&mut state, 0, SystemTime::now()),
+            Admission::Reject(QuotaViolation::MessageRate)
+        ));
+    }
+
+    #[test]
+    fn test_buffered_bytes_quota() {
+        let mut state = FlowQuotaState::new(FlowQuota::new().max_buffered_bytes(10));
+        assert!(matches!(
+            check_input(&mut state, 20, SystemTime::now()),
+            Admission::Accept
+        ));
+        assert!(matches!(
+            check_input(&mut state, 1, SystemTime::now()),
+            Admission::Wait(_)
+        ));
+        state.buffered_bytes = 0;
+        assert!(matches!(
+            check_input(&mut state, 5, SystemTime::now()),
+            Admission::Accept
+        ));
+        assert!(matches!(
+            check_input(&mut state, 5, SystemTime::now()),
+            Admission::Accept
+        ));
+        assert_eq!(state.buffered_bytes, 10);
+    }
+
+    // The quota runs on a simulated clock, so that the tests need not wait for the throttle.
+    fn askit_with_quota(quota: FlowQuota) -> ASKit {
+        let askit = ASKit::new();
+        askit.set_clock(AgentClock::simulated(SystemTime::now()));
+        askit
+            .add_agent_flow(&crate::flow::AgentFlow::new("flow".into()))
+            .unwrap();
+        askit.set_flow_quota("flow", quota).unwrap();
+        askit
+    }
+
+    fn admit(askit: &ASKit, n: i64) -> bool {
+        admit_input(
+            askit,
+            "flow",
+            "1",
+            "in",
+            AgentContext::new(),
+            AgentData::integer(n),
+            None,
+        )
+        .unwrap()
+        .is_some()
+    }
+
+    fn held(askit: &ASKit) -> usize {
+        let states = askit.flow_quotas.lock().unwrap();
+        states.get("flow").unwrap().held.len()
+    }
+
+    struct QuotaObserver(Arc<Mutex<Vec<QuotaViolation>>>);
+
+    impl ASKitObserver for QuotaObserver {
+        fn notify(&self, event: &ASKitEvent) {
+            if let ASKitEvent::QuotaExceeded(_, violation) = event {
+                self.0.lock().unwrap().push(*violation);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_throttle_holds_input() {
+        let askit = askit_with_quota(FlowQuota::new().max_messages_per_sec(1));
+        assert!(admit(&askit, 1));
+
+        // held without waiting, and later inputs wait behind it
+        assert!(!admit(&askit, 2));
+        assert!(!admit(&askit, 3));
+        assert_eq!(held(&askit), 2);
+        assert!(askit.flow_quotas.lock().unwrap()["flow"].delivering);
+
+        // the task of the flow lets one through each second
+        tokio::task::yield_now().await;
+        askit.clock().advance(Duration::from_secs(1)).await;
+        assert_eq!(held(&askit), 1);
+    }
+
+    #[tokio::test]
+    async fn test_held_input_rejected() {
+        let askit = askit_with_quota(FlowQuota::new().max_messages_per_sec(1));
+        let violations = Arc::new(Mutex::new(Vec::new()));
+        askit.subscribe(Box::new(QuotaObserver(violations.clone())));
+        assert!(admit(&askit, 1));
+        assert!(!admit(&askit, 2));
+        assert!(!admit(&askit, 3));
+        tokio::task::yield_now().await;
+
+        // the held inputs get the new action: one is let through, the other is dropped
+        askit
+            .set_flow_quota(
+                "flow",
+                FlowQuota::new()
+                    .max_messages_per_sec(1)
+                    .action(QuotaAction::Error),
+            )
+            .unwrap();
+        askit.clock().advance(Duration::from_secs(1)).await;
+        assert_eq!(held(&askit), 0);
+        assert!(!askit.flow_quotas.lock().unwrap()["flow"].delivering);
+        assert_eq!(*violations.lock().unwrap(), [QuotaViolation::MessageRate]);
+    }
+
+    #[tokio::test]
+    async fn test_pause_flow_action() {
+        let askit = askit_with_quota(
+            FlowQuota::new()
+                .max_messages_per_sec(1)
+                .action(QuotaAction::PauseFlow),
+        );
+        assert!(admit(&askit, 1));
+        assert!(!admit(&askit, 2));
+
+        // paused, not stopped, with the input kept for the resume
+        assert!(askit.is_agent_flow_paused("flow"));
+        assert!(askit.get_agent_flows().contains_key("flow"));
+        assert_eq!(askit.get_buffered_input_count("flow"), 1);
+    }
+}
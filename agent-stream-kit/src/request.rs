@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use super::askit::ASKit;
+use super::context::AgentContext;
+use super::data::AgentData;
+use super::error::AgentError;
+
+// Request waiting for its response
+pub(crate) struct PendingRequest {
+    agent_id: String,
+    tx: oneshot::Sender<AgentData>,
+}
+
+// Sends the request and waits for the first input to the agent that carries its correlation id.
+pub(crate) async fn request(
+    askit: &ASKit,
+    agent_id: String,
+    ctx: AgentContext,
+    pin: String,
+    data: AgentData,
+    timeout: Duration,
+) -> Result<AgentData, AgentError> {
+    let correlation_id = new_correlation_id();
+    let (tx, rx) = oneshot::channel();
+    {
+        let mut pending_requests = askit.pending_requests.lock().unwrap();
+        pending_requests.insert(
+            correlation_id,
+            PendingRequest {
+                agent_id: agent_id.clone(),
+                tx,
+            },
+        );
+    }
+
+    // a deadline of the context bounds the wait too
+    let timeout = ctx
+        .remaining()
+        .map_or(timeout, |remaining| remaining.min(timeout));
+    let ctx = ctx.with_correlation_id(correlation_id);
+
+    let result = match askit.send_agent_out(agent_id.clone(), ctx, pin, data).await {
+        Ok(()) => match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(AgentError::Other(format!(
+                "Request of {} was cancelled",
+                agent_id
+            ))),
+            Err(_) => Err(AgentError::DeadlineExceeded(agent_id)),
+        },
+        Err(e) => Err(e),
+    };
+    askit
+        .pending_requests
+        .lock()
+        .unwrap()
+        .remove(&correlation_id);
+    result
+}
+
+// Called by agent_input before the input is queued for the agent.
+// Returns the data back when it is not a response to a request of the agent.
+pub(crate) fn resolve(
+    askit: &ASKit,
+    agent_id: &str,
+    ctx: &AgentContext,
+    data: AgentData,
+) -> Option<AgentData> {
+    let Some(correlation_id) = ctx.correlation_id() else {
+        return Some(data);
+    };
+    let request = {
+        let mut pending_requests = askit.pending_requests.lock().unwrap();
+        match pending_requests.get(&correlation_id) {
+            Some(request) if request.agent_id == agent_id => {
+                pending_requests.remove(&correlation_id)
+            }
+            _ => None,
+        }
+    };
+    let Some(request) = request else {
+        return Some(data);
+    };
+    // the requester may have timed out in the meantime
+    let _ = request.tx.send(data);
+    None
+}
+
+static CORRELATION_ID_COUNTER: AtomicUsize = AtomicUsize::new(1);
+
+fn new_correlation_id() -> usize {
+    CORRELATION_ID_COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_request() {
+        let askit = ASKit::new();
+        let (tx, mut rx) = oneshot::channel();
+        askit.pending_requests.lock().unwrap().insert(
+            7,
+            PendingRequest {
+                agent_id: "1".to_string(),
+                tx,
+            },
+        );
+
+        let ctx = AgentContext::new();
+        assert!(resolve(&askit, "1", &ctx, AgentData::integer(1)).is_some());
+
+        // the response must come back to the requester
+        let ctx = ctx.with_correlation_id(7);
+        assert!(resolve(&askit, "2", &ctx, AgentData::integer(2)).is_some());
+        assert!(resolve(&askit, "1", &ctx, AgentData::integer(3)).is_none());
+        assert_eq!(rx.try_recv().unwrap().as_i64(), Some(3));
+
+        // only the first response
+        assert!(resolve(&askit, "1", &ctx, AgentData::integer(4)).is_some());
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::{HashMap, HashSet};
+
+use super::flow::AgentFlowEdge;
+
+// (source, source handle, target, target handle)
+type EdgeKey = (String, String, String, String);
+
+fn edge_key(edge: &AgentFlowEdge) -> EdgeKey {
+    (
+        edge.source.clone(),
+        edge.source_handle.clone(),
+        edge.target.clone(),
+        edge.target_handle.clone(),
+    )
+}
+
+// Disabled and weighted edges
+#[derive(Default)]
+pub(crate) struct EdgeRoutes {
+    disabled: HashSet<EdgeKey>,
+    weights: HashMap<EdgeKey, f64>,
+
+    // running totals of the smooth weighted round robin
+    current: HashMap<EdgeKey, f64>,
+}
+
+impl EdgeRoutes {
+    pub(crate) fn set_edge(&mut self, edge: &AgentFlowEdge) {
+        let key = edge_key(edge);
+        if edge.disabled {
+            self.disabled.insert(key.clone());
+        } else {
+            self.disabled.remove(&key);
+        }
+        match edge.weight {
+            Some(weight) => {
+                self.weights.insert(key, weight.max(0.0));
+            }
+            None => {
+                self.weights.remove(&key);
+                self.current.remove(&key);
+            }
+        }
+    }
+
+    pub(crate) fn remove_edge(&mut self, edge: &AgentFlowEdge) {
+        let key = edge_key(edge);
+        self.disabled.remove(&key);
+        self.weights.remove(&key);
+        self.current.remove(&key);
+    }
+
+    // Picks the edges an output of the source goes through, out of the edges matching its port,
+    // given as (target, source handle, target handle, edge id).
+    //
+    // Disabled edges are skipped. Unweighted edges all get the output, while only one
+    // of the weighted edges does, so that each gets its share of the outputs.
+    pub(crate) fn route(
+        &mut self,
+        source: &str,
+        targets: Vec<(String, String, String, String)>,
+    ) -> Vec<(String, String, String, String)> {
+        if self.disabled.is_empty() && self.weights.is_empty() {
+            return targets;
+        }
+
+        let mut routed = Vec::with_capacity(targets.len());
+        let mut weighted = Vec::new();
+        for target in targets {
+            let key = (
+                source.to_string(),
+                target.1.clone(),
+                target.0.clone(),
+                target.2.clone(),
+            );
+            if self.disabled.contains(&key) {
+                continue;
+            }
+            match self.weights.get(&key) {
+                Some(weight) => weighted.push((key, *weight, target)),
+                None => routed.push(target),
+            }
+        }
+
+        let total = weighted.iter().map(|(_, weight, _)| weight).sum::<f64>();
+        if total > 0.0 {
+            // (index, running total) of the edge to pick
+            let mut best: Option<(usize, f64)> = None;
+            for (i, (key, weight, _)) in weighted.iter().enumerate() {
+                let current = self.current.entry(key.clone()).or_default();
+                *current += weight;
+                if best.is_none_or(|(_, max)| *current > max) {
+                    best = Some((i, *current));
+                }
+            }
+            if let Some((best, _)) = best {
+                let (key, _, target) = weighted.swap_remove(best);
+                *self.current.get_mut(&key).unwrap() -= total;
+                routed.push(target);
+            }
+        }
+        routed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(target: &str, weight: Option<f64>) -> AgentFlowEdge {
+        AgentFlowEdge {
+            id: target.to_string(),
+            source: "src".to_string(),
+            source_handle: "out".to_string(),
+            target: target.to_string(),
+            target_handle: "in".to_string(),
+            weight,
+            ..Default::default()
+        }
+    }
+
+    fn target(id: &str) -> (String, String, String, String) {
+        (
+            id.to_string(),
+            "out".to_string(),
+            "in".to_string(),
+            id.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_route_weighted() {
+        let mut routes = EdgeRoutes::default();
+        routes.set_edge(&edge("a", Some(9.0)));
+        routes.set_edge(&edge("b", Some(1.0)));
+        routes.set_edge(&edge("log", None));
+
+        let mut counts = HashMap::new();
+        for _ in 0..100 {
+            let routed = routes.route("src", vec![target("a"), target("b"), target("log")]);
+            assert_eq!(routed.len(), 2);
+            for (id, _, _, _) in routed {
+                *counts.entry(id).or_insert(0) += 1;
+            }
+        }
+        assert_eq!(counts["a"], 90);
+        assert_eq!(counts["b"], 10);
+        assert_eq!(counts["log"], 100);
+
+        let mut disabled = edge("a", Some(9.0));
+        disabled.disabled = true;
+        routes.set_edge(&disabled);
+        let routed = routes.route("src", vec![target("a"), target("b"), target("log")]);
+        assert_eq!(routed, vec![target("log"), target("b")]);
+    }
+}
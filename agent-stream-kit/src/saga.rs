@@ -0,0 +1,106 @@
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::askit::ASKit;
+use super::context::AgentContext;
+use super::data::{AgentData, AgentValue};
+use super::error::AgentError;
+
+/// Reserved input port receiving the compensation records. See [`AgentContext::with_compensation`].
+pub static COMPENSATE_PIN: &str = "$compensate";
+
+/// Undo data of a step that has done its side effect, sent to the handler agent when a
+/// later step fails.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Compensation {
+    /// Id of the agent receiving the data on [`COMPENSATE_PIN`].
+    pub handler: String,
+
+    pub data: AgentData,
+
+    // shared by the copies of the record, so that a step fanned out to failing branches
+    // is compensated once
+    #[serde(skip)]
+    done: Arc<AtomicBool>,
+}
+
+impl Compensation {
+    pub(crate) fn new(handler: String, data: AgentData) -> Self {
+        Self {
+            handler,
+            data,
+            done: Arc::new(AtomicBool::new(false)),
+        }
+    }
+}
+
+// Records not compensated yet, latest first. They are marked as compensated.
+fn take_pending(ctx: &AgentContext) -> Vec<Compensation> {
+    ctx.compensations()
+        .iter()
+        .rev()
+        .filter(|record| !record.done.swap(true, Ordering::AcqRel))
+        .cloned()
+        .collect()
+}
+
+// Called by the agent loops when process fails and the input is not redelivered.
+//
+// The records are queued to their handlers in reverse order of registration, with the
+// context of the failed input, the records removed and the error set.
+pub(crate) async fn compensate(askit: &ASKit, ctx: &AgentContext, error: &AgentError) {
+    let pending = take_pending(ctx);
+    if pending.is_empty() {
+        return;
+    }
+    let handler_ctx = ctx.without_compensations().with_var(
+        VAR_COMPENSATION_ERROR.to_string(),
+        AgentValue::string(error.to_string()),
+    );
+    for record in pending {
+        askit
+            .agent_input(
+                record.handler.clone(),
+                handler_ctx.clone(),
+                COMPENSATE_PIN.to_string(),
+                record.data,
+            )
+            .await
+            .unwrap_or_else(|e| {
+                log::error!("Failed to send compensation to {}: {}", record.handler, e);
+            });
+    }
+}
+
+pub(crate) static VAR_COMPENSATION_ERROR: &str = "$compensation_error";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_pending() {
+        let ctx = AgentContext::new()
+            .with_compensation("a", AgentData::integer(1))
+            .with_compensation("b", AgentData::integer(2));
+        let branch = ctx.with_compensation("c", AgentData::integer(3));
+        assert_eq!(ctx.compensations().len(), 2);
+        assert_eq!(branch.compensations().len(), 3);
+
+        let handlers: Vec<String> = take_pending(&branch)
+            .into_iter()
+            .map(|record| record.handler)
+            .collect();
+        assert_eq!(handlers, vec!["c", "b", "a"]);
+
+        // another failing branch finds the shared steps compensated
+        assert!(take_pending(&ctx).is_empty());
+
+        let handler_ctx = branch.without_compensations();
+        assert!(handler_ctx.compensations().is_empty());
+    }
+}
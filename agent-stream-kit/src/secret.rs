@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use super::error::AgentError;
+
+/// Source of the secrets of agents, such as API keys and credentials.
+///
+/// Hosts set one with `ASKit::set_secret_provider` so that secrets need not be written in
+/// flows or configs, and agents read them by name with `ASKit::get_secret`.
+pub trait SecretProvider: Send + Sync {
+    /// The secret of the name, or None when there is no such secret.
+    fn get_secret(&self, name: &str) -> Result<Option<String>, AgentError>;
+}
+
+/// Secrets kept in memory, such as the ones of a config file.
+#[derive(Debug, Clone, Default)]
+pub struct MapSecretProvider {
+    secrets: HashMap<String, String>,
+}
+
+impl MapSecretProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.insert(name, value);
+        self
+    }
+
+    pub fn insert(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.secrets.insert(name.into(), value.into());
+    }
+}
+
+impl FromIterator<(String, String)> for MapSecretProvider {
+    fn from_iter<T: IntoIterator<Item = (String, String)>>(iter: T) -> Self {
+        Self {
+            secrets: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl SecretProvider for MapSecretProvider {
+    fn get_secret(&self, name: &str) -> Result<Option<String>, AgentError> {
+        Ok(self.secrets.get(name).cloned())
+    }
+}
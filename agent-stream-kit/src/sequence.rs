@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::context::AgentContext;
+use super::data::AgentData;
+
+/// Order of an input compared to the previous ones from the same edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceStatus {
+    /// The context has no sequence number, e.g. the input was injected.
+    Unsequenced,
+
+    InOrder,
+
+    /// Newer than expected. Holds the number of inputs skipped.
+    Gap(u64),
+
+    /// Older than an input already seen, or seen twice.
+    OutOfOrder,
+}
+
+/// Checks the sequence numbers the runtime puts on the contexts of inputs.
+///
+/// Each edge numbers its messages from 1, so a join can tell when the inputs of one of
+/// its edges arrive out of order, e.g. after a redelivery.
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    // stream -> last sequence number
+    last: HashMap<String, u64>,
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn check(&mut self, ctx: &AgentContext) -> SequenceStatus {
+        let Some((stream, seq)) = ctx.sequence() else {
+            return SequenceStatus::Unsequenced;
+        };
+        let last = self.last.get(stream).copied().unwrap_or(0);
+        if seq <= last {
+            return SequenceStatus::OutOfOrder;
+        }
+        self.last.insert(stream.to_string(), seq);
+        if seq == last + 1 {
+            SequenceStatus::InOrder
+        } else {
+            SequenceStatus::Gap(seq - last - 1)
+        }
+    }
+}
+
+/// Holds back inputs that arrive ahead of their turn, and releases them in sequence order.
+#[derive(Debug, Default)]
+pub struct ReorderBuffer {
+    // stream -> next sequence number
+    next: HashMap<String, u64>,
+
+    // stream -> inputs waiting for the ones before them
+    pending: HashMap<String, BTreeMap<u64, (AgentContext, AgentData)>>,
+}
+
+impl ReorderBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the inputs that can be processed now, in order.
+    ///
+    /// Unsequenced inputs are returned right away, and old or duplicate ones are dropped.
+    pub fn push(&mut self, ctx: AgentContext, data: AgentData) -> Vec<(AgentContext, AgentData)> {
+        let Some((stream, seq)) = ctx.sequence() else {
+            return vec![(ctx, data)];
+        };
+        let stream = stream.to_string();
+        let next = self.next.entry(stream.clone()).or_insert(1);
+        if seq < *next {
+            return Vec::new();
+        }
+        let pending = self.pending.entry(stream).or_default();
+        pending.insert(seq, (ctx, data));
+
+        let mut ready = Vec::new();
+        while let Some(input) = pending.remove(next) {
+            ready.push(input);
+            *next += 1;
+        }
+        ready
+    }
+
+    /// Number of inputs waiting for earlier ones.
+    pub fn pending_len(&self) -> usize {
+        self.pending.values().map(|pending| pending.len()).sum()
+    }
+
+    /// Releases the waiting inputs, giving up on the missing ones.
+    pub fn flush(&mut self) -> Vec<(AgentContext, AgentData)> {
+        let mut released = Vec::new();
+        for (stream, pending) in self.pending.iter_mut() {
+            if let Some((last, _)) = pending.last_key_value() {
+                self.next.insert(stream.clone(), last + 1);
+            }
+            released.extend(std::mem::take(pending).into_values());
+        }
+        released
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(seq: u64) -> (AgentContext, AgentData) {
+        (
+            AgentContext::new().with_sequence("1:out>in".to_string(), seq),
+            AgentData::integer(seq as i64),
+        )
+    }
+
+    #[test]
+    fn test_sequence_tracker() {
+        let mut tracker = SequenceTracker::new();
+        assert_eq!(
+            tracker.check(&AgentContext::new()),
+            SequenceStatus::Unsequenced
+        );
+        assert_eq!(tracker.check(&input(1).0), SequenceStatus::InOrder);
+        assert_eq!(tracker.check(&input(4).0), SequenceStatus::Gap(2));
+        assert_eq!(tracker.check(&input(3).0), SequenceStatus::OutOfOrder);
+        assert_eq!(tracker.check(&input(5).0), SequenceStatus::InOrder);
+    }
+
+    #[test]
+    fn test_reorder_buffer() {
+        let mut buffer = ReorderBuffer::new();
+        let seqs = |inputs: Vec<(AgentContext, AgentData)>| {
+            inputs
+                .iter()
+                .map(|(_, data)| data.as_i64().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let (ctx, data) = input(2);
+        assert!(buffer.push(ctx, data).is_empty());
+        let (ctx, data) = input(3);
+        assert!(buffer.push(ctx, data).is_empty());
+        let (ctx, data) = input(1);
+        assert_eq!(seqs(buffer.push(ctx, data)), [1, 2, 3]);
+
+        // duplicate
+        let (ctx, data) = input(2);
+        assert!(buffer.push(ctx, data).is_empty());
+
+        let (ctx, data) = input(6);
+        assert!(buffer.push(ctx, data).is_empty());
+        assert_eq!(buffer.pending_len(), 1);
+        assert_eq!(seqs(buffer.flush()), [6]);
+        let (ctx, data) = input(7);
+        assert_eq!(seqs(buffer.push(ctx, data)), [7]);
+    }
+}
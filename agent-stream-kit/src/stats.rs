@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+
+use super::agent::{AgentMessage, AgentStatus};
+use super::askit::ASKit;
+use super::error::AgentError;
+
+// Inputs processed by a flow since the last stats event
+#[derive(Debug, Default)]
+pub(crate) struct FlowCounter {
+    processed: u64,
+    errors: u64,
+}
+
+impl FlowCounter {
+    // (messages per second, error rate)
+    fn rates(&self, elapsed: Duration) -> (f64, f64) {
+        let secs = elapsed.as_secs_f64();
+        let msgs_per_sec = if secs > 0.0 {
+            self.processed as f64 / secs
+        } else {
+            0.0
+        };
+        let error_rate = if self.processed > 0 {
+            self.errors as f64 / self.processed as f64
+        } else {
+            0.0
+        };
+        (msgs_per_sec, error_rate)
+    }
+}
+
+// how often the stats loop checks the interval while the events are off
+const IDLE_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+// Called by the agent loops after an input has been processed.
+pub(crate) fn record(askit: &ASKit, flow_name: &str, ok: bool) {
+    let mut counters = askit.flow_counters.lock().unwrap();
+    let counter = counters.entry(flow_name.to_string()).or_default();
+    counter.processed += 1;
+    if !ok {
+        counter.errors += 1;
+    }
+}
+
+// Emits FlowStats events for the flows every interval, until ASKit quits.
+pub(crate) fn spawn_stats_loop(askit: &ASKit) {
+    let askit = askit.clone();
+    tokio::spawn(async move {
+        let mut last = Instant::now();
+        loop {
+            let interval = *askit.flow_stats_interval.lock().unwrap();
+            tokio::time::sleep(interval.unwrap_or(IDLE_STATS_INTERVAL)).await;
+            if askit.tx.lock().unwrap().is_none() {
+                break;
+            }
+            let counters = std::mem::take(&mut *askit.flow_counters.lock().unwrap());
+            let elapsed = last.elapsed();
+            last = Instant::now();
+            if interval.is_some() {
+                emit_stats(&askit, counters, elapsed);
+            }
+        }
+    });
+}
+
+// Queues the input for an agent. When the queue of the agent stays full longer than
+// the threshold, a SlowConsumer event names the edge, and the send keeps waiting.
+pub(crate) async fn send_input(
+    askit: &ASKit,
+    tx: &mpsc::Sender<AgentMessage>,
+    message: AgentMessage,
+    flow_name: &str,
+    edge: &str,
+) -> Result<(), AgentError> {
+    let threshold = *askit.slow_consumer_threshold.lock().unwrap();
+    let Some(threshold) = threshold else {
+        return tx.send(message).await.map_err(send_failed);
+    };
+    let permit = match tokio::time::timeout(threshold, tx.reserve()).await {
+        Ok(permit) => permit.map_err(send_failed)?,
+        Err(_) => {
+            log::warn!("Slow consumer on {} in {}", edge, flow_name);
+            askit.emit_slow_consumer(flow_name.to_string(), edge.to_string());
+            tx.reserve().await.map_err(send_failed)?
+        }
+    };
+    permit.send(message);
+    Ok(())
+}
+
+fn send_failed<T>(_: mpsc::error::SendError<T>) -> AgentError {
+    AgentError::SendMessageFailed("Failed to send input message".to_string())
+}
+
+fn emit_stats(askit: &ASKit, counters: HashMap<String, FlowCounter>, elapsed: Duration) {
+    let flows = askit
+        .get_agent_flows()
+        .into_iter()
+        .map(|(name, flow)| {
+            let ids = flow
+                .nodes()
+                .iter()
+                .map(|node| node.id.clone())
+                .collect::<Vec<_>>();
+            (name, ids)
+        })
+        .collect::<Vec<_>>();
+    for (flow_name, node_ids) in flows {
+        let mut active_agents = 0;
+        for node_id in node_ids {
+            let agent = askit.agents.lock().unwrap().get(&node_id).cloned();
+            let Some(agent) = agent else {
+                continue;
+            };
+            // An agent is locked while it processes an input, which may take long, so a
+            // locked agent is counted as active rather than waited for.
+            let active = match agent.try_lock() {
+                Ok(agent) => *agent.status() == AgentStatus::Start,
+                Err(_) => true,
+            };
+            if active {
+                active_agents += 1;
+            }
+        }
+        let counter = counters.get(&flow_name);
+        if active_agents == 0 && counter.is_none() {
+            continue;
+        }
+        let (msgs_per_sec, error_rate) = counter
+            .map(|counter| counter.rates(elapsed))
+            .unwrap_or_default();
+        askit.emit_flow_stats(flow_name, msgs_per_sec, error_rate, active_agents);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::askit::{ASKitEvent, ASKitObserver};
+
+    #[test]
+    fn test_flow_counter_rates() {
+        let askit = ASKit::new();
+        for ok in [true, true, true, false] {
+            record(&askit, "flow", ok);
+        }
+        let counters = askit.flow_counters.lock().unwrap();
+        let (msgs_per_sec, error_rate) = counters["flow"].rates(Duration::from_secs(2));
+        assert_eq!(msgs_per_sec, 2.0);
+        assert_eq!(error_rate, 0.25);
+
+        assert_eq!(
+            FlowCounter::default().rates(Duration::from_secs(1)),
+            (0.0, 0.0)
+        );
+    }
+
+    struct FlowStatsObserver(Arc<Mutex<Vec<(String, usize)>>>);
+
+    impl ASKitObserver for FlowStatsObserver {
+        fn notify(&self, event: &ASKitEvent) {
+            if let ASKitEvent::FlowStats(flow_name, _, _, active_agents) = event {
+                self.0
+                    .lock()
+                    .unwrap()
+                    .push((flow_name.clone(), *active_agents));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_emit_stats_busy_agent() {
+        let askit = ASKit::init().unwrap();
+        askit.ready().await.unwrap();
+        let mut node = askit.new_agent_flow_node("core_board_in").unwrap();
+        node.id = "a".into();
+        node.enabled = true;
+        let mut flow = crate::flow::AgentFlow::new("flow".into());
+        flow.add_node(node);
+        askit.add_agent_flow(&flow).unwrap();
+        askit.start_agent_flow("flow").await.unwrap();
+        let stats = Arc::new(Mutex::new(Vec::new()));
+        askit.subscribe(Box::new(FlowStatsObserver(stats.clone())));
+
+        // an agent busy with an input does not hold back the stats
+        let agent = askit.agents.lock().unwrap().get("a").cloned().unwrap();
+        let _busy = agent.lock().await;
+        emit_stats(&askit, HashMap::new(), Duration::from_secs(1));
+        assert_eq!(*stats.lock().unwrap(), vec![("flow".to_string(), 1)]);
+    }
+
+    struct SlowConsumerObserver(Arc<Mutex<Vec<String>>>);
+
+    impl ASKitObserver for SlowConsumerObserver {
+        fn notify(&self, event: &ASKitEvent) {
+            if let ASKitEvent::SlowConsumer(_, edge) = event {
+                self.0.lock().unwrap().push(edge.clone());
+            }
+        }
+    }
+
+    fn unit_input() -> AgentMessage {
+        AgentMessage::Config {
+            configs: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_consumer() {
+        let askit = ASKit::new();
+        askit.set_slow_consumer_threshold(Some(Duration::from_millis(10)));
+        let edges = Arc::new(Mutex::new(Vec::new()));
+        askit.subscribe(Box::new(SlowConsumerObserver(edges.clone())));
+
+        let (tx, mut rx) = mpsc::channel(1);
+        send_input(&askit, &tx, unit_input(), "flow", "1:out>2:in")
+            .await
+            .unwrap();
+        assert!(edges.lock().unwrap().is_empty());
+
+        // the queue stays full until the consumer catches up
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            while rx.recv().await.is_some() {}
+        });
+        send_input(&askit, &tx, unit_input(), "flow", "1:out>2:in")
+            .await
+            .unwrap();
+        assert_eq!(*edges.lock().unwrap(), vec!["1:out>2:in".to_string()]);
+    }
+}
@@ -0,0 +1,168 @@
+use super::askit::ASKit;
+use super::config::AgentConfigs;
+use super::data::AgentValue;
+use super::error::AgentError;
+
+// References in string configs: ${env:VAR} and ${board:name}
+static REF_START: &str = "${";
+
+pub(crate) fn has_refs(configs: &AgentConfigs) -> bool {
+    configs
+        .into_iter()
+        .any(|(_, value)| value.as_str().is_some_and(|s| s.contains(REF_START)))
+}
+
+// Copy of the configs with the references replaced by their current values.
+// ${env:VAR} needs the Env capability of the agent definition.
+pub(crate) fn resolve_configs(
+    askit: &ASKit,
+    def_name: &str,
+    configs: &AgentConfigs,
+) -> Result<AgentConfigs, AgentError> {
+    let mut resolved = configs.clone();
+    for (key, value) in configs {
+        if let Some(s) = value.as_str()
+            && s.contains(REF_START)
+        {
+            let s = replace_refs(askit, s, |name| askit.env_var(def_name, name))?;
+            resolved.set(key.clone(), AgentValue::string(s));
+        }
+    }
+    Ok(resolved)
+}
+
+/// Replaces `${env:VAR}` and `${board:name}` in the text.
+///
+/// This is for configs of the host, such as the global configs of a daemon, and reads
+/// the environment without checking capabilities. The references in the configs of
+/// agents are resolved only when their definitions declare `AgentCapability::Env`.
+///
+/// Unset variables and empty boards become empty strings. Other references are left as
+/// they are. Environment variables are read once and cached, see
+/// `ASKit::clear_env_cache`.
+pub fn substitute(askit: &ASKit, text: &str) -> String {
+    replace_refs(askit, text, |name| Ok(askit.cached_env_var(name))).unwrap_or_default()
+}
+
+fn replace_refs(
+    askit: &ASKit,
+    text: &str,
+    env_var: impl Fn(&str) -> Result<Option<String>, AgentError>,
+) -> Result<String, AgentError> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(REF_START) {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + REF_START.len()..];
+        let Some(end) = after.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let reference = &after[..end];
+        match lookup(askit, reference, &env_var)? {
+            Some(value) => result.push_str(&value),
+            None => result.push_str(&rest[start..start + REF_START.len() + end + 1]),
+        }
+        rest = &after[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn lookup(
+    askit: &ASKit,
+    reference: &str,
+    env_var: impl Fn(&str) -> Result<Option<String>, AgentError>,
+) -> Result<Option<String>, AgentError> {
+    let Some((scheme, name)) = reference.split_once(':') else {
+        return Ok(None);
+    };
+    match scheme {
+        "env" => Ok(Some(env_var(name)?.unwrap_or_default())),
+        "board" => {
+            let board_data = askit.board_data.lock().unwrap();
+            Ok(Some(match board_data.get(name) {
+                Some(data) => match data.as_str() {
+                    Some(s) => s.to_string(),
+                    None => data.value.to_json().to_string(),
+                },
+                None => String::new(),
+            }))
+        }
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capability::{AgentCapability, AgentCapabilityPolicy};
+    use crate::data::AgentData;
+    use crate::definition::AgentDefinition;
+
+    #[test]
+    fn test_substitute() {
+        let askit = ASKit::new();
+        askit
+            .env_cache
+            .lock()
+            .unwrap()
+            .insert("API_HOST".into(), Some("example.com".into()));
+        askit
+            .board_data
+            .lock()
+            .unwrap()
+            .insert("port".into(), AgentData::integer(8080));
+
+        assert_eq!(
+            substitute(&askit, "https://${env:API_HOST}:${board:port}/v1"),
+            "https://example.com:8080/v1"
+        );
+        assert_eq!(substitute(&askit, "${board:missing}"), "");
+        assert_eq!(substitute(&askit, "${other:x} ${env"), "${other:x} ${env");
+
+        let mut configs = AgentConfigs::new();
+        configs.set("url".into(), AgentValue::string("${env:API_HOST}"));
+        configs.set("count".into(), AgentValue::integer(1));
+        assert!(has_refs(&configs));
+        askit.register_agent(
+            AgentDefinition::new("test", "env", None).capabilities(vec![AgentCapability::Env]),
+        );
+        let resolved = resolve_configs(&askit, "env", &configs).unwrap();
+        assert_eq!(resolved.get_string("url").unwrap(), "example.com");
+        assert_eq!(resolved.get_integer("count").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_resolve_configs_denied() {
+        let askit = ASKit::new();
+        askit
+            .env_cache
+            .lock()
+            .unwrap()
+            .insert("SECRET".into(), Some("secret".into()));
+        askit.register_agent(AgentDefinition::new("test", "plain", None));
+        askit.register_agent(
+            AgentDefinition::new("test", "env", None).capabilities(vec![AgentCapability::Env]),
+        );
+
+        let mut configs = AgentConfigs::new();
+        configs.set("key".into(), AgentValue::string("${env:SECRET}"));
+
+        // not declared by the definition
+        let err = resolve_configs(&askit, "plain", &configs).unwrap_err();
+        assert!(matches!(err, AgentError::PermissionDenied(_, ref cap) if cap == "env"));
+
+        // declared, but denied by the host
+        assert!(resolve_configs(&askit, "env", &configs).is_ok());
+        askit.set_capability_policy(AgentCapabilityPolicy::allow_all().deny(AgentCapability::Env));
+        assert!(matches!(
+            resolve_configs(&askit, "env", &configs),
+            Err(AgentError::PermissionDenied(_, _))
+        ));
+
+        // boards need no capability
+        configs.set("key".into(), AgentValue::string("${board:missing}"));
+        assert!(resolve_configs(&askit, "plain", &configs).is_ok());
+    }
+}
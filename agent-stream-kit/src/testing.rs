@@ -0,0 +1,369 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use super::askit::ASKit;
+use super::clock::AgentClock;
+use super::data::AgentData;
+use super::error::AgentError;
+use super::flow::{self, AgentFlow, SubFlowCopyOptions};
+
+/// Test of a flow: data injected into its nodes and the outputs expected from them.
+///
+/// `ASKit::run_flow_test` runs a copy of the flow on a simulated clock, so `Advance`
+/// steps fire the timers of the flow without waiting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowTest {
+    pub name: String,
+
+    pub flow: AgentFlow,
+
+    #[serde(default)]
+    pub steps: Vec<FlowTestStep>,
+
+    /// Outputs of each node and port in the order they are expected.
+    /// Outputs beyond the expected ones are ignored.
+    #[serde(default)]
+    pub expect: Vec<FlowTestOutput>,
+
+    /// Real time to wait for the expected outputs.
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FlowTestStep {
+    Inject {
+        node: String,
+        port: String,
+        data: AgentData,
+    },
+    Advance {
+        ms: u64,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowTestOutput {
+    pub node: String,
+    pub port: String,
+    pub data: AgentData,
+}
+
+impl FlowTest {
+    pub fn new(name: &str, flow: AgentFlow) -> Self {
+        Self {
+            name: name.into(),
+            flow,
+            steps: Vec::new(),
+            expect: Vec::new(),
+            timeout_ms: default_timeout_ms(),
+        }
+    }
+
+    pub fn inject(mut self, node: &str, port: &str, data: AgentData) -> Self {
+        self.steps.push(FlowTestStep::Inject {
+            node: node.into(),
+            port: port.into(),
+            data,
+        });
+        self
+    }
+
+    pub fn advance(mut self, duration: Duration) -> Self {
+        self.steps.push(FlowTestStep::Advance {
+            ms: duration.as_millis() as u64,
+        });
+        self
+    }
+
+    pub fn expect(mut self, node: &str, port: &str, data: AgentData) -> Self {
+        self.expect.push(FlowTestOutput {
+            node: node.into(),
+            port: port.into(),
+            data,
+        });
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_ms = timeout.as_millis() as u64;
+        self
+    }
+
+    pub fn to_json(&self) -> Result<String, AgentError> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| AgentError::SerializationError(e.to_string()))?;
+        Ok(json)
+    }
+
+    pub fn from_json(json_str: &str) -> Result<Self, AgentError> {
+        let test: FlowTest = serde_json::from_str(json_str)
+            .map_err(|e| AgentError::SerializationError(e.to_string()))?;
+        Ok(test)
+    }
+}
+
+fn default_timeout_ms() -> u64 {
+    5000
+}
+
+/// Output of a node that differs from the expected one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlowTestFailure {
+    pub node: String,
+    pub def_name: String,
+    pub port: String,
+    /// Position among the outputs of the port.
+    pub index: usize,
+    pub expected: AgentData,
+    /// None when the node did not output it in time.
+    pub actual: Option<AgentData>,
+}
+
+impl fmt::Display for FlowTestFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) output #{} on {}: expected {:?}, ",
+            self.node, self.def_name, self.index, self.port, self.expected.value
+        )?;
+        match &self.actual {
+            Some(actual) => write!(f, "got {:?}", actual.value),
+            None => write!(f, "got nothing"),
+        }
+    }
+}
+
+/// Result of `ASKit::run_flow_test`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FlowTestReport {
+    pub name: String,
+
+    /// Upstream nodes first, so the first failure is where the flow starts to diverge.
+    pub failures: Vec<FlowTestFailure>,
+}
+
+impl FlowTestReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Panics with the failures, pointing at the first diverging node. For use in `#[test]`s.
+    pub fn assert(&self) {
+        if let Some(first) = self.failures.first() {
+            let details = self
+                .failures
+                .iter()
+                .map(|failure| format!("  {}", failure))
+                .collect::<Vec<_>>()
+                .join("\n");
+            panic!(
+                "Flow test {} diverged at node {}:\n{}",
+                self.name, first.node, details
+            );
+        }
+    }
+}
+
+// agent id -> (port, data) output by the agent
+pub(crate) type OutputTaps = HashMap<String, Vec<(String, AgentData)>>;
+
+// Called by agent_out and board_out. Records the output if the node is tapped by a test.
+pub(crate) fn tap(askit: &ASKit, agent_id: &str, pin: &str, data: &AgentData) {
+    let mut taps = askit.output_taps.lock().unwrap();
+    if let Some(outputs) = taps.get_mut(agent_id) {
+        outputs.push((pin.to_string(), data.clone()));
+    }
+}
+
+pub(crate) async fn run(askit: &ASKit, test: &FlowTest) -> Result<FlowTestReport, AgentError> {
+    // runs a copy so that the test does not collide with a loaded flow
+    let name = askit.unique_flow_name(&format!("{}-test", test.flow.name()));
+    let copy = flow::copy_sub_flow_with(
+        test.flow.nodes(),
+        test.flow.edges(),
+        &SubFlowCopyOptions::default(),
+    );
+    let node_ids = copy.node_ids;
+    let mut test_flow = test.flow.clone();
+    test_flow.set_name(name.clone());
+    test_flow.set_nodes(copy.nodes);
+    test_flow.set_edges(copy.edges);
+
+    let node_id = |id: &str| {
+        node_ids
+            .get(id)
+            .cloned()
+            .ok_or_else(|| AgentError::AgentNotFound(id.to_string()))
+    };
+    let mut expected: Vec<(String, FlowTestOutput)> = Vec::new();
+    for output in test.expect.iter() {
+        expected.push((node_id(&output.node)?, output.clone()));
+    }
+
+    let clock = askit.clock();
+    if !clock.is_simulated() {
+        askit.set_clock(AgentClock::simulated(SystemTime::now()));
+    }
+    {
+        let mut taps = askit.output_taps.lock().unwrap();
+        for (id, _) in expected.iter() {
+            taps.insert(id.clone(), Vec::new());
+        }
+    }
+
+    let result = async {
+        askit.add_agent_flow(&test_flow)?;
+        askit.start_agent_flow(&name).await?;
+        for step in test.steps.iter() {
+            match step {
+                FlowTestStep::Inject { node, port, data } => {
+                    askit
+                        .inject(&name, &node_id(node)?, port, data.clone())
+                        .await?;
+                }
+                FlowTestStep::Advance { ms } => {
+                    askit.clock().advance(Duration::from_millis(*ms)).await;
+                }
+            }
+        }
+        wait_outputs(askit, &expected, Duration::from_millis(test.timeout_ms)).await;
+        Ok::<(), AgentError>(())
+    }
+    .await;
+
+    let outputs = {
+        let mut taps = askit.output_taps.lock().unwrap();
+        expected
+            .iter()
+            .filter_map(|(id, _)| taps.remove(id).map(|outputs| (id.clone(), outputs)))
+            .collect::<HashMap<_, _>>()
+    };
+    if askit.get_agent_flows().contains_key(&name) {
+        askit.remove_agent_flow(&name).await?;
+    }
+    askit.set_clock(clock);
+    result?;
+
+    // upstream nodes first
+    let order = test_flow
+        .start_order()
+        .iter()
+        .rev()
+        .map(|node| node.id.clone())
+        .collect::<Vec<_>>();
+    let mut failures = Vec::new();
+    for id in order {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (_, output) in expected
+            .iter()
+            .filter(|(expected_id, _)| *expected_id == id)
+        {
+            let index = counts.entry(output.port.as_str()).or_default();
+            let actual = outputs
+                .get(&id)
+                .and_then(|outputs| {
+                    outputs
+                        .iter()
+                        .filter(|(pin, _)| *pin == output.port)
+                        .nth(*index)
+                })
+                .map(|(_, data)| data.clone());
+            if actual.as_ref() != Some(&output.data) {
+                failures.push(FlowTestFailure {
+                    node: output.node.clone(),
+                    def_name: test_flow
+                        .nodes()
+                        .iter()
+                        .find(|node| node.id == id)
+                        .map(|node| node.def_name.clone())
+                        .unwrap_or_default(),
+                    port: output.port.clone(),
+                    index: *index,
+                    expected: output.data.clone(),
+                    actual,
+                });
+            }
+            *index += 1;
+        }
+    }
+    Ok(FlowTestReport {
+        name: test.name.clone(),
+        failures,
+    })
+}
+
+// Waits until every expected output has a counterpart, or the timeout.
+async fn wait_outputs(askit: &ASKit, expected: &[(String, FlowTestOutput)], timeout: Duration) {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let done = {
+            let taps = askit.output_taps.lock().unwrap();
+            let mut counts: HashMap<(&str, &str), usize> = HashMap::new();
+            for (id, output) in expected {
+                *counts.entry((id, &output.port)).or_default() += 1;
+            }
+            counts.iter().all(|((id, port), count)| {
+                taps.get(*id).is_some_and(|outputs| {
+                    outputs.iter().filter(|(pin, _)| pin == port).count() >= *count
+                })
+            })
+        };
+        if done || Instant::now() >= deadline {
+            return;
+        }
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
+
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::AgentConfigs;
+    use crate::data::AgentValue;
+    use crate::flow::AgentFlowNode;
+
+    fn board_node(askit: &ASKit, id: &str, def_name: &str, board: &str) -> AgentFlowNode {
+        let mut node = askit.new_agent_flow_node(def_name).unwrap();
+        node.id = id.into();
+        node.enabled = true;
+        let mut configs = AgentConfigs::new();
+        configs.set("$board".into(), AgentValue::string(board));
+        node.configs = Some(configs);
+        node
+    }
+
+    #[tokio::test]
+    async fn test_run_flow_test() {
+        let askit = ASKit::init().unwrap();
+        askit.ready().await.unwrap();
+
+        let mut flow = AgentFlow::new("relay".into());
+        flow.add_node(board_node(&askit, "in", "core_board_in", "value"));
+        flow.add_node(board_node(&askit, "out", "core_board_out", "value"));
+        let test = FlowTest::new("relay", flow.clone())
+            .inject("in", "*", AgentData::integer(1))
+            .expect("out", "value", AgentData::integer(1))
+            .timeout(Duration::from_secs(1));
+        let test = FlowTest::from_json(&test.to_json().unwrap()).unwrap();
+        let report = askit.run_flow_test(&test).await.unwrap();
+        report.assert();
+        assert!(askit.get_agent_flows().is_empty());
+
+        let test = FlowTest::new("relay", flow)
+            .inject("in", "*", AgentData::integer(1))
+            .expect("out", "value", AgentData::integer(2))
+            .timeout(Duration::from_millis(100));
+        let report = askit.run_flow_test(&test).await.unwrap();
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].node, "out");
+        assert_eq!(report.failures[0].def_name, "core_board_out");
+        assert_eq!(report.failures[0].actual, Some(AgentData::integer(1)));
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::{Arc, Mutex};
+
+use super::askit::ASKit;
+use super::context::AgentContext;
+use super::data::AgentData;
+use super::error::AgentError;
+
+/// Guard returned by [`AgentContext::transaction`].
+///
+/// Outputs sent while the guard is alive are staged, and delivered together once
+/// `process` returns `Ok`. They are discarded when `process` returns an error.
+pub struct AgentTransaction {
+    slot: Option<Arc<Mutex<TransactionSlot>>>,
+}
+
+impl AgentTransaction {
+    pub(crate) fn new(slot: Option<Arc<Mutex<TransactionSlot>>>) -> Self {
+        if let Some(slot) = &slot {
+            slot.lock().unwrap().guards += 1;
+        }
+        Self { slot }
+    }
+
+    /// Whether outputs are staged. It is false outside of `process`.
+    pub fn is_active(&self) -> bool {
+        self.slot
+            .as_ref()
+            .is_some_and(|slot| !slot.lock().unwrap().finished)
+    }
+
+    /// Number of outputs staged so far.
+    pub fn staged_len(&self) -> usize {
+        self.slot
+            .as_ref()
+            .map(|slot| slot.lock().unwrap().staged.len())
+            .unwrap_or(0)
+    }
+
+    /// Discards the outputs staged so far.
+    pub fn rollback(&self) {
+        if let Some(slot) = &self.slot {
+            slot.lock().unwrap().staged.clear();
+        }
+    }
+}
+
+impl Drop for AgentTransaction {
+    fn drop(&mut self) {
+        if let Some(slot) = &self.slot {
+            slot.lock().unwrap().guards -= 1;
+        }
+    }
+}
+
+// Outputs of a process call, shared by the contexts derived from its input context
+#[derive(Debug, Default)]
+pub(crate) struct TransactionSlot {
+    guards: usize,
+    finished: bool,
+    staged: Vec<(AgentContext, String, AgentData)>,
+}
+
+// Called by the agent loops before an input is processed.
+pub(crate) fn begin(ctx: AgentContext) -> (AgentContext, Arc<Mutex<TransactionSlot>>) {
+    let slot = Arc::new(Mutex::new(TransactionSlot::default()));
+    (ctx.with_transaction_slot(Some(slot.clone())), slot)
+}
+
+// Stages the output when a transaction is open on the context.
+// Returns the output back when it should be sent now.
+pub(crate) fn stage(
+    ctx: AgentContext,
+    pin: String,
+    data: AgentData,
+) -> Option<(AgentContext, String, AgentData)> {
+    let Some(slot) = ctx.transaction_slot().cloned() else {
+        return Some((ctx, pin, data));
+    };
+    let mut slot = slot.lock().unwrap();
+    if slot.finished || slot.guards == 0 {
+        return Some((ctx, pin, data));
+    }
+    slot.staged
+        .push((ctx.with_transaction_slot(None), pin, data));
+    None
+}
+
+fn take(slot: &Mutex<TransactionSlot>, commit: bool) -> Vec<(AgentContext, String, AgentData)> {
+    let mut slot = slot.lock().unwrap();
+    slot.finished = true;
+    let staged = std::mem::take(&mut slot.staged);
+    if commit { staged } else { Vec::new() }
+}
+
+// Called by the agent loops after an input has been processed.
+pub(crate) async fn finish(
+    askit: &ASKit,
+    agent_id: &str,
+    slot: &Mutex<TransactionSlot>,
+    result: &Result<(), AgentError>,
+) {
+    for (ctx, pin, data) in take(slot, result.is_ok()) {
+        askit
+            .send_agent_out(agent_id.to_string(), ctx, pin, data)
+            .await
+            .unwrap_or_else(|e| {
+                log::error!("Failed to send staged output of {}: {}", agent_id, e);
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transaction_stage() {
+        let (ctx, slot) = begin(AgentContext::new());
+
+        // no transaction, sent right away
+        assert!(stage(ctx.clone(), "out".into(), AgentData::integer(0)).is_some());
+
+        {
+            let transaction = ctx.transaction();
+            assert!(transaction.is_active());
+            let derived = ctx.with_var("key".into(), 1.into());
+            assert!(stage(derived, "out".into(), AgentData::integer(1)).is_none());
+            assert!(stage(ctx.clone(), "out".into(), AgentData::integer(2)).is_none());
+            assert_eq!(transaction.staged_len(), 2);
+        }
+        assert!(stage(ctx.clone(), "out".into(), AgentData::integer(3)).is_some());
+
+        let staged = take(&slot, true);
+        assert_eq!(staged.len(), 2);
+        assert_eq!(staged[0].2.as_i64(), Some(1));
+        assert!(staged[0].0.transaction_slot().is_none());
+
+        // finished, e.g. a task spawned by process outputs later
+        let transaction = ctx.transaction();
+        assert!(!transaction.is_active());
+        assert!(stage(ctx.clone(), "out".into(), AgentData::integer(4)).is_some());
+    }
+
+    #[test]
+    fn test_transaction_rollback() {
+        let (ctx, slot) = begin(AgentContext::new());
+        let transaction = ctx.transaction();
+        assert!(stage(ctx.clone(), "out".into(), AgentData::integer(1)).is_none());
+        transaction.rollback();
+        assert!(stage(ctx.clone(), "out".into(), AgentData::integer(2)).is_none());
+        assert!(take(&slot, false).is_empty());
+    }
+}
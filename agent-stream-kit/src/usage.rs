@@ -0,0 +1,295 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+/// Token usage of a single call to a paid provider.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgentUsage {
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl AgentUsage {
+    pub fn new(
+        provider: impl Into<String>,
+        model: impl Into<String>,
+        input_tokens: u64,
+        output_tokens: u64,
+    ) -> Self {
+        Self {
+            provider: provider.into(),
+            model: model.into(),
+            input_tokens,
+            output_tokens,
+        }
+    }
+}
+
+/// Price of a model in currency units per million tokens.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl ModelPricing {
+    pub fn new(input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            input_per_million,
+            output_per_million,
+        }
+    }
+
+    pub fn cost(&self, usage: &AgentUsage) -> f64 {
+        (usage.input_tokens as f64 * self.input_per_million
+            + usage.output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Aggregated usage over a number of calls.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub calls: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost: f64,
+}
+
+impl UsageTotals {
+    fn add(&mut self, other: &UsageTotals) {
+        self.calls += other.calls;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cost += other.cost;
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+/// Usage of one agent in one flow on one day (UTC, `YYYY-MM-DD`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageRecord {
+    pub day: String,
+    pub flow_name: String,
+    pub agent_id: String,
+    pub totals: UsageTotals,
+}
+
+/// What happens when a flow goes over its daily budget.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BudgetAction {
+    /// Report `ASKitEvent::BudgetExceeded` only.
+    #[default]
+    Alert,
+
+    /// Also pause the flow, buffering its inputs until it is resumed with
+    /// `ASKit::resume_agent_flow`, after raising the budget for example.
+    PauseFlow,
+}
+
+/// Daily spending limit of a flow.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageBudget {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens_per_day: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_cost_per_day: Option<f64>,
+
+    #[serde(default)]
+    pub action: BudgetAction,
+}
+
+impl UsageBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_tokens_per_day(mut self, max: u64) -> Self {
+        self.max_tokens_per_day = Some(max);
+        self
+    }
+
+    pub fn max_cost_per_day(mut self, max: f64) -> Self {
+        self.max_cost_per_day = Some(max);
+        self
+    }
+
+    pub fn action(mut self, action: BudgetAction) -> Self {
+        self.action = action;
+        self
+    }
+
+    pub fn is_exceeded(&self, totals: &UsageTotals) -> bool {
+        if let Some(max) = self.max_tokens_per_day
+            && totals.total_tokens() > max
+        {
+            return true;
+        }
+        if let Some(max) = self.max_cost_per_day
+            && totals.cost > max
+        {
+            return true;
+        }
+        false
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct UsageLedger {
+    // day of the records, whose totals are dropped when another day comes
+    day: String,
+
+    // (day, flow name, agent id) -> totals
+    records: BTreeMap<(String, String, String), UsageTotals>,
+
+    // "provider/model" -> pricing
+    pricing: HashMap<String, ModelPricing>,
+
+    // flow name -> budget
+    budgets: HashMap<String, UsageBudget>,
+
+    // (day, flow name) already reported as over budget
+    exceeded: HashSet<(String, String)>,
+}
+
+impl UsageLedger {
+    pub(crate) fn set_pricing(&mut self, provider: &str, model: &str, pricing: ModelPricing) {
+        self.pricing
+            .insert(format!("{}/{}", provider, model), pricing);
+    }
+
+    pub(crate) fn budget(&self, flow_name: &str) -> Option<&UsageBudget> {
+        self.budgets.get(flow_name)
+    }
+
+    pub(crate) fn set_budget(&mut self, flow_name: &str, budget: UsageBudget) {
+        self.budgets.insert(flow_name.to_string(), budget);
+        self.exceeded.retain(|(_, name)| name != flow_name);
+    }
+
+    pub(crate) fn remove_budget(&mut self, flow_name: &str) {
+        self.budgets.remove(flow_name);
+    }
+
+    /// Adds the usage on the given day. The records of past days are dropped.
+    ///
+    /// Returns the flow totals and the budget action when the flow has just gone over budget.
+    pub(crate) fn record(
+        &mut self,
+        day: &str,
+        flow_name: &str,
+        agent_id: &str,
+        usage: &AgentUsage,
+    ) -> Option<(UsageTotals, BudgetAction)> {
+        if day > self.day.as_str() {
+            self.records.retain(|(d, _, _), _| d.as_str() >= day);
+            self.exceeded.retain(|(d, _)| d.as_str() >= day);
+            self.day = day.to_string();
+        }
+
+        let cost = self
+            .pricing
+            .get(&format!("{}/{}", usage.provider, usage.model))
+            .map(|pricing| pricing.cost(usage))
+            .unwrap_or_default();
+        self.records
+            .entry((day.to_string(), flow_name.to_string(), agent_id.to_string()))
+            .or_default()
+            .add(&UsageTotals {
+                calls: 1,
+                input_tokens: usage.input_tokens,
+                output_tokens: usage.output_tokens,
+                cost,
+            });
+
+        let budget = self.budgets.get(flow_name)?;
+        let key = (day.to_string(), flow_name.to_string());
+        if self.exceeded.contains(&key) {
+            return None;
+        }
+        let totals = self.flow_totals(day, flow_name);
+        if !budget.is_exceeded(&totals) {
+            return None;
+        }
+        let action = budget.action;
+        self.exceeded.insert(key);
+        Some((totals, action))
+    }
+
+    pub(crate) fn flow_totals(&self, day: &str, flow_name: &str) -> UsageTotals {
+        let mut totals = UsageTotals::default();
+        for ((d, f, _), t) in self.records.iter() {
+            if d == day && f == flow_name {
+                totals.add(t);
+            }
+        }
+        totals
+    }
+
+    pub(crate) fn agent_totals(&self, day: &str, agent_id: &str) -> UsageTotals {
+        let mut totals = UsageTotals::default();
+        for ((d, _, a), t) in self.records.iter() {
+            if d == day && a == agent_id {
+                totals.add(t);
+            }
+        }
+        totals
+    }
+
+    pub(crate) fn records(&self) -> Vec<UsageRecord> {
+        self.records
+            .iter()
+            .map(|((day, flow_name, agent_id), totals)| UsageRecord {
+                day: day.clone(),
+                flow_name: flow_name.clone(),
+                agent_id: agent_id.clone(),
+                totals: totals.clone(),
+            })
+            .collect()
+    }
+}
+
+/// Today's date in UTC as `YYYY-MM-DD`.
+pub fn usage_day() -> String {
+    chrono::Utc::now().date_naive().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_ledger() {
+        let mut ledger = UsageLedger::default();
+        ledger.set_pricing("openai", "gpt", ModelPricing::new(1.0, 2.0));
+        ledger.set_budget("flow", UsageBudget::new().max_tokens_per_day(1_000_000));
+
+        let usage = AgentUsage::new("openai", "gpt", 500_000, 250_000);
+        assert_eq!(ledger.record("2025-01-01", "flow", "a", &usage), None);
+
+        let (totals, action) = ledger.record("2025-01-01", "flow", "b", &usage).unwrap();
+        assert_eq!(totals.calls, 2);
+        assert_eq!(totals.total_tokens(), 1_500_000);
+        assert_eq!(totals.cost, 2.0);
+        assert_eq!(action, BudgetAction::Alert);
+
+        // reported only once a day
+        assert_eq!(ledger.record("2025-01-01", "flow", "b", &usage), None);
+        assert_eq!(ledger.agent_totals("2025-01-01", "b").calls, 2);
+        assert_eq!(ledger.flow_totals("2025-01-02", "flow").calls, 0);
+        assert_eq!(ledger.records().len(), 2);
+
+        // a new day drops the records of the previous one
+        assert_eq!(ledger.record("2025-01-02", "flow", "a", &usage), None);
+        assert_eq!(ledger.records().len(), 1);
+        assert_eq!(ledger.flow_totals("2025-01-01", "flow").calls, 0);
+        assert_eq!(ledger.flow_totals("2025-01-02", "flow").calls, 1);
+    }
+}
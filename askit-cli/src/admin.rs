@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use agent_stream_kit::ASKit;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+
+// Admin API
+//
+// A line protocol on TCP, meant for local tools such as `nc 127.0.0.1 7800`:
+//
+//   flows             names of the flows, one per line
+//   start <flow>      starts a flow
+//   stop <flow>       stops a flow
+//   shutdown          shuts the daemon down
+//
+// Each command is answered by its output and a line of `ok` or `error: <message>`.
+
+/// Serves the admin API until the daemon shuts down. `shutdown` is notified by the
+/// `shutdown` command.
+pub async fn serve(askit: ASKit, address: String, shutdown: Arc<Notify>) -> Result<(), String> {
+    let listener = TcpListener::bind(&address)
+        .await
+        .map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    log::info!("Admin API listening on {}", address);
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    log::error!("Admin API accept failed: {}", e);
+                    continue;
+                }
+            };
+            log::debug!("Admin connection from {}", peer);
+            let askit = askit.clone();
+            let shutdown = shutdown.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_connection(askit, stream, shutdown).await {
+                    log::warn!("Admin connection from {} failed: {}", peer, e);
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+async fn handle_connection(
+    askit: ASKit,
+    stream: TcpStream,
+    shutdown: Arc<Notify>,
+) -> std::io::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    while let Some(line) = lines.next_line().await? {
+        let response = match execute(&askit, line.trim(), &shutdown).await {
+            Ok(output) => format!("{}ok\n", output),
+            Err(e) => format!("error: {}\n", e),
+        };
+        writer.write_all(response.as_bytes()).await?;
+    }
+    Ok(())
+}
+
+// Output of the command, each line terminated by a newline.
+async fn execute(askit: &ASKit, command: &str, shutdown: &Notify) -> Result<String, String> {
+    let (command, arg) = command
+        .split_once(' ')
+        .map(|(command, arg)| (command, arg.trim()))
+        .unwrap_or((command, ""));
+    match (command, arg) {
+        ("flows", "") => {
+            let mut names = askit.get_agent_flows().into_keys().collect::<Vec<_>>();
+            names.sort();
+            Ok(names.into_iter().map(|name| name + "\n").collect())
+        }
+        ("start", name) if !name.is_empty() => askit
+            .start_agent_flow(name)
+            .await
+            .map(|_| String::new())
+            .map_err(|e| e.to_string()),
+        ("stop", name) if !name.is_empty() => askit
+            .stop_agent_flow(name)
+            .await
+            .map(|_| String::new())
+            .map_err(|e| e.to_string()),
+        ("shutdown", "") => {
+            shutdown.notify_one();
+            Ok(String::new())
+        }
+        _ => Err(format!("unknown command: {}", command)),
+    }
+}
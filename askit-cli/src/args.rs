@@ -0,0 +1,111 @@
+use std::path::PathBuf;
+
+use crate::events::EventFilter;
+use crate::packs;
+
+pub const USAGE: &str = "\
+Usage: askit [OPTIONS] <FLOW_FILE>...
+       askit --config <FILE> [OPTIONS] [FLOW_FILE]...
+
+Runs the flows of the files until interrupted.
+
+Options:
+  --config <FILE>      Daemon config file (TOML)
+  --flow <NAME>        Run only the named flow (can be repeated)
+  --packs <LIST>       Comma-separated agent packs to register (default: all)
+  --events <FILTER>    Events to print: all, errors or none (default: all)
+  --mcp                Serve the flows with an mcp_tool_input node as MCP tools on
+                       stdin and stdout, until the client disconnects
+
+Options given here override the config file.
+  -h, --help           Print help
+  -V, --version        Print version";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub config: Option<PathBuf>,
+    pub files: Vec<PathBuf>,
+    pub flows: Vec<String>,
+    pub packs: Option<Vec<String>>,
+    pub events: Option<EventFilter>,
+    pub mcp: bool,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Args {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value =
+                |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "--version" => parsed.version = true,
+                "--config" => parsed.config = Some(PathBuf::from(value("--config")?)),
+                "--flow" => parsed.flows.push(value("--flow")?),
+                "--packs" => {
+                    parsed.packs = Some(
+                        value("--packs")?
+                            .split(',')
+                            .map(|pack| pack.trim().to_string())
+                            .filter(|pack| !pack.is_empty())
+                            .collect(),
+                    )
+                }
+                "--events" => parsed.events = Some(value("--events")?.parse()?),
+                "--mcp" => parsed.mcp = true,
+                _ if arg.starts_with('-') => return Err(format!("Unknown option {}", arg)),
+                _ => parsed.files.push(PathBuf::from(arg)),
+            }
+        }
+        if parsed.files.is_empty() && parsed.config.is_none() && !parsed.help && !parsed.version {
+            return Err("No flow files given".to_string());
+        }
+        Ok(parsed)
+    }
+}
+
+pub fn help() -> String {
+    format!(
+        "{}\n\nAgent packs: {}",
+        USAGE,
+        packs::pack_names().join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args, String> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse(&["a.yaml", "--flow", "chat", "--packs", "std, llm", "b.json"]).unwrap();
+        assert_eq!(
+            args.files,
+            [PathBuf::from("a.yaml"), PathBuf::from("b.json")]
+        );
+        assert_eq!(args.flows, ["chat"]);
+        assert_eq!(args.packs, Some(vec!["std".to_string(), "llm".to_string()]));
+        assert_eq!(args.events, None);
+
+        assert_eq!(
+            parse(&["a.yaml", "--events", "errors"]).unwrap().events,
+            Some(EventFilter::Errors)
+        );
+        assert_eq!(
+            parse(&["--config", "askit.toml"]).unwrap().config,
+            Some(PathBuf::from("askit.toml"))
+        );
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["a.yaml", "--flow"]).is_err());
+        assert!(parse(&["a.yaml", "--verbose"]).is_err());
+        assert!(parse(&["--help"]).unwrap().help);
+        assert!(parse(&["a.yaml", "--mcp"]).unwrap().mcp);
+    }
+}
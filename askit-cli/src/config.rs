@@ -0,0 +1,226 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use agent_stream_kit::{ASKit, AgentConfigs, AgentValue, MapSecretProvider, substitute};
+use serde::Deserialize;
+
+/// Config file of the daemon, in TOML.
+///
+/// ```toml
+/// packs = ["std", "llm"]
+/// flow_dirs = ["flows"]
+/// watch = true
+/// events = "errors"
+/// embedding_cache = "cache/embeddings"
+///
+/// [global_configs.openai_chat]
+/// openai_api_key = "${env:OPENAI_API_KEY}"
+///
+/// [secrets]
+/// AWS_ACCESS_KEY_ID = "${env:AWS_ACCESS_KEY_ID}"
+/// AWS_SECRET_ACCESS_KEY = "${env:AWS_SECRET_ACCESS_KEY}"
+///
+/// [admin]
+/// enabled = true
+/// address = "127.0.0.1:7800"
+///
+/// [openai_server]
+/// enabled = true
+/// address = "127.0.0.1:7801"
+/// ```
+///
+/// Paths are relative to the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Agent packs to register. All the compiled packs when not set.
+    pub packs: Option<Vec<String>>,
+
+    /// Flow files to run.
+    pub flows: Vec<PathBuf>,
+
+    /// Directories whose flow files are run.
+    pub flow_dirs: Vec<PathBuf>,
+
+    /// Reloads the flow files of `flow_dirs` when they change.
+    pub watch: bool,
+
+    /// Events printed to stdout: all, errors or none.
+    pub events: Option<String>,
+
+    /// Global configs by agent definition. `${env:VAR}` in strings is replaced by the
+    /// environment variable, so API keys need not be written in the file.
+    pub global_configs: HashMap<String, AgentConfigs>,
+
+    /// Secrets read by agents by name, such as the credentials of the S3 agents.
+    /// `${env:VAR}` is replaced as in `global_configs`.
+    pub secrets: HashMap<String, String>,
+
+    /// Directory keeping the embeddings of the embeddings agents across runs. Only in
+    /// memory when not set.
+    pub embedding_cache: Option<PathBuf>,
+
+    pub admin: AdminConfig,
+
+    /// OpenAI-compatible API serving the flows with an `openai_server_input` node.
+    pub openai_server: OpenAIServerConfig,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdminConfig {
+    pub enabled: bool,
+    pub address: String,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: DEFAULT_ADMIN_ADDRESS.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OpenAIServerConfig {
+    pub enabled: bool,
+    pub address: String,
+}
+
+impl Default for OpenAIServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: DEFAULT_OPENAI_SERVER_ADDRESS.to_string(),
+        }
+    }
+}
+
+impl DaemonConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let mut config = Self::from_toml(&content)
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+
+        let base = path.parent().unwrap_or(Path::new(""));
+        for path in config
+            .flows
+            .iter_mut()
+            .chain(config.flow_dirs.iter_mut())
+            .chain(config.embedding_cache.iter_mut())
+        {
+            *path = base.join(&path);
+        }
+        Ok(config)
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self, String> {
+        toml::from_str(content).map_err(|e| e.to_string())
+    }
+
+    /// Flow files of `flows` and of `flow_dirs`, the files of each directory in name order.
+    pub fn flow_files(&self) -> Result<Vec<PathBuf>, String> {
+        let mut files = self.flows.clone();
+        for dir in self.flow_dirs.iter() {
+            let entries = std::fs::read_dir(dir)
+                .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+            let mut dir_files = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| is_flow_file(path))
+                .collect::<Vec<_>>();
+            dir_files.sort();
+            files.extend(dir_files);
+        }
+        Ok(files)
+    }
+
+    /// Sets the global configs, with the environment references resolved.
+    pub fn apply_global_configs(&self, askit: &ASKit) {
+        for (def_name, configs) in self.global_configs.iter() {
+            let mut resolved = AgentConfigs::new();
+            for (key, value) in configs {
+                let value = match value.as_str() {
+                    Some(s) => AgentValue::string(substitute(askit, s)),
+                    None => value.clone(),
+                };
+                resolved.set(key.clone(), value);
+            }
+            askit.set_global_configs(def_name.clone(), resolved);
+        }
+    }
+
+    /// Gives the secrets to the agents, with the environment references resolved.
+    pub fn apply_secrets(&self, askit: &ASKit) {
+        let secrets = self
+            .secrets
+            .iter()
+            .map(|(name, value)| (name.clone(), substitute(askit, value)))
+            .collect::<MapSecretProvider>();
+        askit.set_secret_provider(secrets);
+    }
+}
+
+pub fn is_flow_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| FLOW_FILE_EXTENSIONS.contains(&ext))
+}
+
+const DEFAULT_ADMIN_ADDRESS: &str = "127.0.0.1:7800";
+const DEFAULT_OPENAI_SERVER_ADDRESS: &str = "127.0.0.1:7801";
+const FLOW_FILE_EXTENSIONS: &[&str] = &["json", "toml", "yaml", "yml"];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_config() {
+        let config = DaemonConfig::from_toml(
+            r#"
+            packs = ["std"]
+            flow_dirs = ["flows"]
+            watch = true
+            embedding_cache = "cache"
+
+            [global_configs.openai_chat]
+            openai_api_key = "${env:ASKIT_CLI_TEST_KEY}"
+            temperature = 0.5
+
+            [secrets]
+            API_TOKEN = "${env:ASKIT_CLI_TEST_KEY}"
+
+            [admin]
+            enabled = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.packs, Some(vec!["std".to_string()]));
+        assert_eq!(config.flow_dirs, [PathBuf::from("flows")]);
+        assert!(config.watch);
+        assert_eq!(config.embedding_cache, Some(PathBuf::from("cache")));
+        assert!(config.admin.enabled);
+        assert_eq!(config.admin.address, DEFAULT_ADMIN_ADDRESS);
+        assert_eq!(config.openai_server, OpenAIServerConfig::default());
+
+        // SAFETY: no other test reads this variable
+        unsafe { std::env::set_var("ASKIT_CLI_TEST_KEY", "secret") };
+        let askit = ASKit::new();
+        config.apply_global_configs(&askit);
+        let configs = askit.get_global_configs("openai_chat").unwrap();
+        assert_eq!(configs.get_string("openai_api_key").unwrap(), "secret");
+        assert_eq!(configs.get_number("temperature").unwrap(), 0.5);
+        config.apply_secrets(&askit);
+        assert_eq!(
+            askit.get_secret("API_TOKEN").unwrap().as_deref(),
+            Some("secret")
+        );
+
+        assert!(DaemonConfig::from_toml("unknown = 1").is_err());
+    }
+}
@@ -0,0 +1,94 @@
+use agent_stream_kit::{ASKitEvent, ASKitObserver};
+
+/// Which events are printed to stdout.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum EventFilter {
+    None,
+    Errors,
+    #[default]
+    All,
+}
+
+impl std::str::FromStr for EventFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(EventFilter::None),
+            "errors" => Ok(EventFilter::Errors),
+            "all" => Ok(EventFilter::All),
+            _ => Err(format!("Unknown event filter: {}", s)),
+        }
+    }
+}
+
+/// Prints the events of the runtime, one line each.
+pub struct EventPrinter {
+    pub filter: EventFilter,
+}
+
+impl ASKitObserver for EventPrinter {
+    fn notify(&self, event: &ASKitEvent) {
+        let is_error = matches!(
+            event,
+            ASKitEvent::AgentError(..)
+                | ASKitEvent::QuotaExceeded(..)
+                | ASKitEvent::BudgetExceeded(..)
+                | ASKitEvent::FlowReloadFailed(..)
+        );
+        let show = match self.filter {
+            EventFilter::None => false,
+            EventFilter::Errors => is_error,
+            EventFilter::All => true,
+        };
+        if show {
+            println!("{}", format_event(event));
+        }
+    }
+}
+
+pub fn format_event(event: &ASKitEvent) -> String {
+    match event {
+        ASKitEvent::AgentDisplay(agent_id, key, data) => {
+            format!("display {} {}: {}", agent_id, key, data.value.to_json())
+        }
+        ASKitEvent::AgentError(agent_id, message) => format!("error {}: {}", agent_id, message),
+        ASKitEvent::AgentIn(agent_id, pin) => format!("in {}:{}", agent_id, pin),
+        ASKitEvent::Board(name, data) => format!("board {}: {}", name, data.value.to_json()),
+        ASKitEvent::QuotaExceeded(flow_name, violation) => {
+            format!("quota {}: {}", flow_name, violation)
+        }
+        ASKitEvent::AgentUsage(agent_id, usage) => format!(
+            "usage {} {}: {} in, {} out",
+            agent_id, usage.model, usage.input_tokens, usage.output_tokens
+        ),
+        ASKitEvent::BudgetExceeded(flow_name, totals) => {
+            format!("budget {}: {:.4}", flow_name, totals.cost)
+        }
+        ASKitEvent::FlowPaused(flow_name, pending) => {
+            format!(
+                "paused {} at {}:{}",
+                flow_name, pending.agent_id, pending.pin
+            )
+        }
+        ASKitEvent::FlowResumed(flow_name) => format!("resumed {}", flow_name),
+        ASKitEvent::FlowStats(flow_name, rate, error_rate, active) => format!(
+            "stats {}: {:.1} msgs/s, {:.1}% errors, {} active",
+            flow_name,
+            rate,
+            error_rate * 100.0,
+            active
+        ),
+        ASKitEvent::SlowConsumer(flow_name, edge) => format!("slow {}: {}", flow_name, edge),
+        ASKitEvent::Notification(notification) => format!(
+            "notification {} {}: {}",
+            notification.level, notification.title, notification.body
+        ),
+        ASKitEvent::FlowReloaded(path, flow_name) => format!("reloaded {}: {}", flow_name, path),
+        ASKitEvent::FlowReloadFailed(path, message) => {
+            format!("reload failed {}: {}", path, message)
+        }
+        ASKitEvent::DefinitionsChanged(def_name) => format!("definition {}", def_name),
+        ASKitEvent::FlowChanged(flow_name) => format!("changed {}", flow_name),
+    }
+}
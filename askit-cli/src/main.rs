@@ -0,0 +1,225 @@
+//! askit - runs Agent Stream Kit flows headless
+//!
+//! Loads flow files (JSON, YAML or TOML), registers the agent packs compiled in by the
+//! cargo features, runs the flows and prints the events to stdout until interrupted.
+//! Logs go to stderr, controlled by `RUST_LOG`.
+//!
+//! With `--config`, it runs as a daemon set up by the config file, see `DaemonConfig`.
+//!
+//! With `--mcp`, it serves the flows as MCP tools on stdin and stdout instead of printing
+//! the events, and exits when the client disconnects.
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use agent_stream_kit::{
+    ASKit, AgentFlow, DEFAULT_FLOW_WATCH_INTERVAL, DEFAULT_THREAD_JOIN_TIMEOUT, load_flows,
+};
+use tokio::sync::Notify;
+
+mod admin;
+mod args;
+mod config;
+mod events;
+mod packs;
+
+use args::Args;
+use config::DaemonConfig;
+use events::{EventFilter, EventPrinter};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, args::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    if args.help {
+        println!("{}", args::help());
+        return ExitCode::SUCCESS;
+    }
+    if args.version {
+        println!("askit {}", env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn run(args: Args) -> Result<(), String> {
+    let config = match &args.config {
+        Some(path) => DaemonConfig::load(path)?,
+        None => DaemonConfig::default(),
+    };
+    let events = match args.events {
+        // stdout is the MCP transport
+        _ if args.mcp => EventFilter::None,
+        Some(events) => events,
+        None => config
+            .events
+            .as_deref()
+            .map(str::parse::<EventFilter>)
+            .transpose()?
+            .unwrap_or_default(),
+    };
+
+    let askit = ASKit::init().map_err(|e| e.to_string())?;
+    let packs = args.packs.as_ref().or(config.packs.as_ref());
+    packs::register_packs(&askit, packs.map(|packs| packs.as_slice()))?;
+    config.apply_global_configs(&askit);
+    config.apply_secrets(&askit);
+    if let Some(dir) = &config.embedding_cache {
+        set_embedding_cache(dir)?;
+    }
+    askit.subscribe(Box::new(EventPrinter { filter: events }));
+
+    let mut files = config.flow_files()?;
+    files.extend(args.files.iter().cloned());
+    let flows = load_files(&files, &args.flows)?;
+    if flows.is_empty() {
+        return Err("No flows to run".to_string());
+    }
+    for flow in flows.iter() {
+        askit
+            .add_agent_flow(flow)
+            .map_err(|e| format!("Failed to add flow {}: {}", flow.name(), e))?;
+        log::info!("Added flow {}", flow.name());
+    }
+
+    askit.ready().await.map_err(|e| e.to_string())?;
+
+    if config.watch {
+        for dir in config.flow_dirs.iter() {
+            askit
+                .watch_flow_dir(dir, DEFAULT_FLOW_WATCH_INTERVAL)
+                .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+            log::info!("Watching {}", dir.display());
+        }
+    }
+
+    let shutdown = Arc::new(Notify::new());
+    if config.admin.enabled {
+        admin::serve(
+            askit.clone(),
+            config.admin.address.clone(),
+            shutdown.clone(),
+        )
+        .await?;
+    }
+    if config.openai_server.enabled {
+        serve_openai(&askit, &config.openai_server.address).await?;
+    }
+
+    tokio::select! {
+        _ = wait_for_signal() => {}
+        _ = shutdown.notified() => {}
+        result = serve_mcp(&askit, args.mcp) => {
+            if let Err(e) = result {
+                log::error!("{}", e);
+            }
+        }
+    }
+    log::info!("Shutting down");
+    askit
+        .shutdown(DEFAULT_THREAD_JOIN_TIMEOUT)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+// Serves the MCP tools until the client disconnects. Never returns when not enabled.
+async fn serve_mcp(askit: &ASKit, enabled: bool) -> Result<(), String> {
+    if !enabled {
+        return std::future::pending().await;
+    }
+    #[cfg(feature = "llm")]
+    {
+        askit_llm_agents::mcp_server::FlowToolServer::new(askit.clone())
+            .serve_stdio()
+            .await
+            .map_err(|e| e.to_string())
+    }
+    #[cfg(not(feature = "llm"))]
+    {
+        let _ = askit;
+        Err("--mcp needs the llm feature".to_string())
+    }
+}
+
+// Serves the OpenAI-compatible API in the background.
+async fn serve_openai(askit: &ASKit, address: &str) -> Result<(), String> {
+    #[cfg(feature = "llm")]
+    {
+        askit_llm_agents::openai_server::OpenAIServer::new(askit.clone())
+            .serve(address)
+            .await
+            .map_err(|e| e.to_string())?;
+        log::info!("OpenAI server listening on {}", address);
+        Ok(())
+    }
+    #[cfg(not(feature = "llm"))]
+    {
+        let _ = (askit, address);
+        Err("openai_server needs the llm feature".to_string())
+    }
+}
+
+fn set_embedding_cache(dir: &Path) -> Result<(), String> {
+    #[cfg(feature = "llm")]
+    {
+        askit_llm_agents::embedding_cache::EmbeddingCache::global()
+            .set_dir(Some(dir.to_path_buf()));
+        Ok(())
+    }
+    #[cfg(not(feature = "llm"))]
+    {
+        let _ = dir;
+        Err("embedding_cache needs the llm feature".to_string())
+    }
+}
+
+// Flows of the files, only the named ones if any.
+fn load_files(files: &[PathBuf], names: &[String]) -> Result<Vec<AgentFlow>, String> {
+    let mut flows = Vec::new();
+    for file in files {
+        let loaded =
+            load_flows(file).map_err(|e| format!("Failed to load {}: {}", file.display(), e))?;
+        flows.extend(loaded);
+    }
+    if !names.is_empty() {
+        for name in names {
+            if !flows.iter().any(|flow| flow.name() == name) {
+                return Err(format!("Flow {} not found", name));
+            }
+        }
+        flows.retain(|flow| names.iter().any(|name| name == flow.name()));
+    }
+    Ok(flows)
+}
+
+// Ctrl-C, or SIGTERM on unix
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut terminate = signal(SignalKind::terminate()).expect("SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
@@ -0,0 +1,41 @@
+use agent_stream_kit::ASKit;
+
+// (name, register_agents of the pack)
+type Pack = (&'static str, fn(&ASKit));
+
+/// Agent packs compiled into this binary, selected by the cargo features.
+pub const PACKS: &[Pack] = &[
+    #[cfg(feature = "cozodb")]
+    ("cozodb", askit_cozodb_agents::register_agents),
+    #[cfg(feature = "llm")]
+    ("llm", askit_llm_agents::register_agents),
+    #[cfg(feature = "rhai")]
+    ("rhai", askit_rhai_agents::register_agents),
+    #[cfg(feature = "std")]
+    ("std", askit_std_agents::register_agents),
+];
+
+/// Registers the named packs, or all the compiled packs when `names` is `None`.
+pub fn register_packs(askit: &ASKit, names: Option<&[String]>) -> Result<(), String> {
+    if let Some(names) = names {
+        for name in names {
+            if !PACKS.iter().any(|(pack, _)| pack == name) {
+                return Err(format!(
+                    "Unknown agent pack {}. Available: {}",
+                    name,
+                    pack_names().join(", ")
+                ));
+            }
+        }
+    }
+    for (pack, register) in PACKS {
+        if names.is_none_or(|names| names.iter().any(|name| name == pack)) {
+            register(askit);
+        }
+    }
+    Ok(())
+}
+
+pub fn pack_names() -> Vec<&'static str> {
+    PACKS.iter().map(|(pack, _)| *pack).collect()
+}
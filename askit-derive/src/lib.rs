@@ -0,0 +1,296 @@
+//! Derive macros for Agent Stream Kit
+//!
+//! They are re-exported from `agent_stream_kit`, and the generated code refers to it by
+//! that name.
+//!
+//! ```ignore
+//! #[derive(AskitAgent)]
+//! #[askit(kind = "Std", name = "std_counter", title = "Counter", category = "Std/Utils")]
+//! #[askit(inputs("in", "reset"), outputs("count"))]
+//! #[askit(config(integer, "step", 1))]
+//! struct CounterAgent {
+//!     data: AsAgentData,
+//!     count: i64,
+//! }
+//!
+//! #[askit_agent]
+//! #[async_trait]
+//! impl AsAgent for CounterAgent {
+//!     async fn process(...) -> Result<(), AgentError> { ... }
+//! }
+//!
+//! askit.register_agent(CounterAgent::agent_definition());
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Expr, Fields, Ident, ImplItem, ItemImpl, Token, Type, meta::ParseNestedMeta,
+    parse_macro_input, punctuated::Punctuated,
+};
+
+/// Implements `AgentBoilerplate` for an agent struct, and adds `agent_definition()` built
+/// from its `#[askit(...)]` attributes.
+///
+/// The struct needs a field of type `AsAgentData`. The other fields start from `Default`.
+///
+/// Attributes:
+/// - `kind = ..`, `name = ..`: required for `agent_definition()`
+/// - `title`, `description`, `category`, `icon`: same as the `AgentDefinition` builders
+/// - `inputs(..)`, `outputs(..)`: port names
+/// - `config(type, key)`, `config(type, key, default)`: calls `<type>_config_default`
+///   or `<type>_config`, and `unit_config` for `unit`
+/// - `global_config(..)`: the same for global configs
+#[proc_macro_derive(AskitAgent, attributes(askit))]
+pub fn derive_askit_agent(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_derive(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Fills in `new`, `data` and `mut_data` of an `impl AsAgent` block for a struct
+/// with `#[derive(AskitAgent)]`. Put it above `#[async_trait]`.
+#[proc_macro_attribute]
+pub fn askit_agent(_attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut item = parse_macro_input!(item as ItemImpl);
+
+    let defined = item
+        .items
+        .iter()
+        .filter_map(|item| match item {
+            ImplItem::Fn(f) => Some(f.sig.ident.to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let has = |name: &str| defined.iter().any(|d| d == name);
+
+    let mut methods: Vec<ImplItem> = Vec::new();
+    if !has("new") {
+        methods.push(syn::parse_quote! {
+            fn new(
+                askit: ::agent_stream_kit::ASKit,
+                id: String,
+                def_name: String,
+                configs: Option<::agent_stream_kit::AgentConfigs>,
+            ) -> Result<Self, ::agent_stream_kit::AgentError> {
+                <Self as ::agent_stream_kit::AgentBoilerplate>::new_agent(
+                    askit, id, def_name, configs,
+                )
+            }
+        });
+    }
+    if !has("data") {
+        methods.push(syn::parse_quote! {
+            fn data(&self) -> &::agent_stream_kit::AsAgentData {
+                <Self as ::agent_stream_kit::AgentBoilerplate>::agent_data(self)
+            }
+        });
+    }
+    if !has("mut_data") {
+        methods.push(syn::parse_quote! {
+            fn mut_data(&mut self) -> &mut ::agent_stream_kit::AsAgentData {
+                <Self as ::agent_stream_kit::AgentBoilerplate>::agent_data_mut(self)
+            }
+        });
+    }
+    methods.append(&mut item.items);
+    item.items = methods;
+
+    quote!(#item).into()
+}
+
+fn expand_derive(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "AskitAgent can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "AskitAgent needs a struct with named fields",
+        ));
+    };
+
+    let data_field = fields
+        .named
+        .iter()
+        .find(|f| is_as_agent_data(&f.ty))
+        .and_then(|f| f.ident.clone())
+        .ok_or_else(|| {
+            syn::Error::new_spanned(&input.ident, "AskitAgent needs a field of type AsAgentData")
+        })?;
+    let other_fields = fields
+        .named
+        .iter()
+        .filter_map(|f| f.ident.as_ref())
+        .filter(|ident| **ident != data_field);
+
+    let attrs = AgentAttrs::parse(&input)?;
+    let definition = attrs.definition()?.map(|definition| {
+        quote! {
+            impl #impl_generics #name #ty_generics #where_clause {
+                /// Definition of the agent built from its `#[askit(...)]` attributes.
+                pub fn agent_definition() -> ::agent_stream_kit::AgentDefinition {
+                    #definition
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::agent_stream_kit::AgentBoilerplate for #name #ty_generics #where_clause {
+            fn new_agent(
+                askit: ::agent_stream_kit::ASKit,
+                id: String,
+                def_name: String,
+                configs: Option<::agent_stream_kit::AgentConfigs>,
+            ) -> Result<Self, ::agent_stream_kit::AgentError> {
+                Ok(Self {
+                    #data_field: ::agent_stream_kit::AsAgentData::new(askit, id, def_name, configs),
+                    #(#other_fields: ::std::default::Default::default(),)*
+                })
+            }
+
+            fn agent_data(&self) -> &::agent_stream_kit::AsAgentData {
+                &self.#data_field
+            }
+
+            fn agent_data_mut(&mut self) -> &mut ::agent_stream_kit::AsAgentData {
+                &mut self.#data_field
+            }
+        }
+
+        #definition
+    })
+}
+
+fn is_as_agent_data(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "AsAgentData"),
+        _ => false,
+    }
+}
+
+#[derive(Default)]
+struct AgentAttrs {
+    kind: Option<Expr>,
+    name: Option<Expr>,
+    // (builder, value)
+    builders: Vec<(Ident, Expr)>,
+    inputs: Vec<Expr>,
+    outputs: Vec<Expr>,
+    configs: Vec<TokenStream2>,
+}
+
+impl AgentAttrs {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut attrs = AgentAttrs::default();
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("askit")) {
+            attr.parse_nested_meta(|meta| attrs.parse_meta(meta))?;
+        }
+        Ok(attrs)
+    }
+
+    fn parse_meta(&mut self, meta: ParseNestedMeta) -> syn::Result<()> {
+        let Some(ident) = meta.path.get_ident().cloned() else {
+            return Err(meta.error("unknown askit attribute"));
+        };
+        match ident.to_string().as_str() {
+            "kind" => self.kind = Some(meta.value()?.parse()?),
+            "name" => self.name = Some(meta.value()?.parse()?),
+            "title" | "description" | "category" | "icon" => {
+                self.builders.push((ident, meta.value()?.parse()?));
+            }
+            "inputs" => self.inputs.extend(parse_exprs(&meta)?),
+            "outputs" => self.outputs.extend(parse_exprs(&meta)?),
+            "config" => self.configs.push(parse_config(&meta, "config")?),
+            "global_config" => self.configs.push(parse_config(&meta, "global_config")?),
+            _ => return Err(meta.error("unknown askit attribute")),
+        }
+        Ok(())
+    }
+
+    fn definition(&self) -> syn::Result<Option<TokenStream2>> {
+        let (Some(kind), Some(name)) = (&self.kind, &self.name) else {
+            if self.kind.is_some() || self.name.is_some() || !self.builders.is_empty() {
+                return Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    "askit attributes need both kind and name",
+                ));
+            }
+            return Ok(None);
+        };
+        let builders = self
+            .builders
+            .iter()
+            .map(|(builder, value)| quote!(.#builder(#value)));
+        let inputs = &self.inputs;
+        let outputs = &self.outputs;
+        let ports = quote! {
+            .inputs(vec![#(#inputs),*])
+            .outputs(vec![#(#outputs),*])
+        };
+        let configs = &self.configs;
+        Ok(Some(quote! {
+            ::agent_stream_kit::AgentDefinition::new(
+                #kind,
+                #name,
+                Some(::agent_stream_kit::new_agent_boxed::<Self>),
+            )
+            #(#builders)*
+            #ports
+            #(#configs)*
+        }))
+    }
+}
+
+fn parse_exprs(meta: &ParseNestedMeta) -> syn::Result<Vec<Expr>> {
+    let content;
+    syn::parenthesized!(content in meta.input);
+    let exprs = Punctuated::<Expr, Token![,]>::parse_terminated(&content)?;
+    Ok(exprs.into_iter().collect())
+}
+
+// config(type, key) or config(type, key, default)
+fn parse_config(meta: &ParseNestedMeta, suffix: &str) -> syn::Result<TokenStream2> {
+    let content;
+    syn::parenthesized!(content in meta.input);
+    let ty: Ident = content.parse()?;
+    content.parse::<Token![,]>()?;
+    let key: Expr = content.parse()?;
+    let default = if content.is_empty() {
+        None
+    } else {
+        content.parse::<Token![,]>()?;
+        Some(content.parse::<Expr>()?)
+    };
+    if !content.is_empty() {
+        return Err(content.error("expected config(type, key) or config(type, key, default)"));
+    }
+
+    Ok(match default {
+        _ if ty == "unit" => {
+            let builder = format_ident!("unit_{}", suffix);
+            quote!(.#builder(#key))
+        }
+        Some(default) => {
+            let builder = format_ident!("{}_{}", ty, suffix);
+            quote!(.#builder(#key, #default))
+        }
+        None => {
+            let builder = format_ident!("{}_{}_default", ty, suffix);
+            quote!(.#builder(#key))
+        }
+    })
+}
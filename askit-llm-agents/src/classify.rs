@@ -0,0 +1,441 @@
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentUsage, AgentValue, AgentValueMap, AsAgent, AsAgentData,
+    async_trait, new_agent_boxed,
+};
+
+use crate::common::CONFIG_OPTIONS;
+use crate::message::Message;
+use crate::provider::{LlmProviderCache, options_from_configs};
+
+/// Label chosen by the model, with its confidence from 0 to 1 when the model gave one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Classification {
+    pub label: String,
+    pub confidence: Option<f64>,
+}
+
+/// System prompt asking the model to pick one of the labels and reply in JSON.
+pub fn classify_prompt(labels: &[String], instructions: &str) -> String {
+    let mut prompt = String::from(
+        "You are a text classifier. Classify the text given by the user into exactly one of the labels below.\n\nLabels:\n",
+    );
+    for label in labels {
+        prompt.push_str(&format!("- {}\n", label));
+    }
+    if !instructions.is_empty() {
+        prompt.push_str(&format!("\nInstructions:\n{}\n", instructions));
+    }
+    prompt.push_str(
+        "\nReply with only a JSON object, without any other text:\n{\"label\": \"<one of the labels>\", \"confidence\": <number from 0 to 1>}",
+    );
+    prompt
+}
+
+/// Reads the label out of the reply of the model.
+///
+/// The reply may wrap the JSON in a code block or in other text. Labels are matched case
+/// insensitively. A reply that is not JSON is accepted when it names exactly one label.
+pub fn parse_classification(reply: &str, labels: &[String]) -> Result<Classification, AgentError> {
+    let find_label = |name: &str| {
+        labels
+            .iter()
+            .find(|label| label.eq_ignore_ascii_case(name.trim()))
+            .cloned()
+    };
+
+    if let Some(json) = extract_json_object(reply) {
+        let label = json.get("label").and_then(|label| label.as_str());
+        let Some(label) = label.and_then(find_label) else {
+            return Err(AgentError::InvalidValue(format!(
+                "The model replied with an unknown label: {}",
+                reply
+            )));
+        };
+        let confidence = json
+            .get("confidence")
+            .and_then(|confidence| confidence.as_f64())
+            .map(|confidence| confidence.clamp(0.0, 1.0));
+        return Ok(Classification { label, confidence });
+    }
+
+    let lower = reply.to_lowercase();
+    let mut mentioned = labels
+        .iter()
+        .filter(|label| lower.contains(&label.to_lowercase()));
+    match (mentioned.next(), mentioned.next()) {
+        (Some(label), None) => Ok(Classification {
+            label: label.clone(),
+            confidence: None,
+        }),
+        _ => Err(AgentError::InvalidValue(format!(
+            "Failed to read a label from the reply: {}",
+            reply
+        ))),
+    }
+}
+
+pub(crate) fn extract_json_object(reply: &str) -> Option<serde_json::Value> {
+    let start = reply.find('{')?;
+    let end = reply.rfind('}')?;
+    if end < start {
+        return None;
+    }
+    serde_json::from_str::<serde_json::Value>(&reply[start..=end])
+        .ok()
+        .filter(|json| json.is_object())
+}
+
+/// Splits the labels config, given one per line or separated by commas.
+pub fn parse_labels(labels: &str) -> Vec<String> {
+    labels
+        .split([',', '\n'])
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Text of a string or a message
+fn input_text(data: &AgentData) -> Result<String, AgentError> {
+    if let Some(text) = data.as_str() {
+        return Ok(text.to_string());
+    }
+    let message: Message = data.clone().try_into().map_err(|e| {
+        AgentError::InvalidValue(format!("Failed to convert data to Message: {}", e))
+    })?;
+    Ok(message.content)
+}
+
+#[derive(Default)]
+struct Classifier {
+    providers: LlmProviderCache,
+}
+
+impl Classifier {
+    async fn classify(
+        &mut self,
+        askit: &ASKit,
+        configs: &AgentConfigs,
+        text: String,
+        labels: &[String],
+        instructions: &str,
+    ) -> Result<(Classification, Option<AgentUsage>), AgentError> {
+        let model = configs.get_string_or_default(CONFIG_MODEL);
+        if model.is_empty() {
+            return Err(AgentError::InvalidConfig("model is not set".to_string()));
+        }
+        let options = options_from_configs(configs)?;
+        let provider = self.providers.get(
+            askit,
+            &configs.get_string_or(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER),
+        )?;
+        let messages = vec![
+            Message::system(classify_prompt(labels, instructions)),
+            Message::user(text),
+        ];
+        let res = provider.chat(&model, messages, options.as_ref()).await?;
+        let classification = parse_classification(&res.message.content, labels)?;
+        Ok((classification, res.usage))
+    }
+}
+
+fn classification_value(classification: &Classification, score: Option<f64>) -> AgentValue {
+    let mut map = AgentValueMap::new();
+    map.insert(
+        "label".to_string(),
+        AgentValue::string(classification.label.clone()),
+    );
+    map.insert(
+        "confidence".to_string(),
+        classification
+            .confidence
+            .map(AgentValue::number)
+            .unwrap_or_default(),
+    );
+    if let Some(score) = score {
+        map.insert("score".to_string(), AgentValue::number(score));
+    }
+    AgentValue::object(map)
+}
+
+// LLM Classify Agent
+pub struct LlmClassifyAgent {
+    data: AsAgentData,
+    classifier: Classifier,
+}
+
+#[async_trait]
+impl AsAgent for LlmClassifyAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            classifier: Classifier::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let configs = self.configs()?.clone();
+        let labels = parse_labels(&configs.get_string_or_default(CONFIG_LABELS));
+        if labels.is_empty() {
+            return Err(AgentError::InvalidConfig("labels are not set".to_string()));
+        }
+        let instructions = configs.get_string_or_default(CONFIG_INSTRUCTIONS);
+        let text = input_text(&data)?;
+
+        self.check_capability(AgentCapability::Network)?;
+        let askit = self.askit().clone();
+        let _llm_call = askit.acquire_llm_call(self.flow_name()).await?;
+        let (classification, usage) = self
+            .classifier
+            .classify(&askit, &configs, text, &labels, &instructions)
+            .await?;
+        if let Some(usage) = usage {
+            self.emit_usage(usage);
+        }
+
+        self.try_output(
+            ctx.clone(),
+            PORT_LABEL,
+            AgentData::string(classification.label.clone()),
+        )?;
+        self.try_output(
+            ctx,
+            PORT_RESULT,
+            AgentData::from_value(classification_value(&classification, None)),
+        )
+    }
+}
+
+// LLM Sentiment Agent
+pub struct LlmSentimentAgent {
+    data: AsAgentData,
+    classifier: Classifier,
+}
+
+#[async_trait]
+impl AsAgent for LlmSentimentAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            classifier: Classifier::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let configs = self.configs()?.clone();
+        let labels = SENTIMENT_LABELS
+            .iter()
+            .map(|label| label.to_string())
+            .collect::<Vec<_>>();
+        let text = input_text(&data)?;
+
+        self.check_capability(AgentCapability::Network)?;
+        let askit = self.askit().clone();
+        let _llm_call = askit.acquire_llm_call(self.flow_name()).await?;
+        let (classification, usage) = self
+            .classifier
+            .classify(&askit, &configs, text, &labels, SENTIMENT_INSTRUCTIONS)
+            .await?;
+        if let Some(usage) = usage {
+            self.emit_usage(usage);
+        }
+
+        self.try_output(
+            ctx.clone(),
+            PORT_SENTIMENT,
+            AgentData::string(classification.label.clone()),
+        )?;
+        let score = sentiment_score(&classification);
+        self.try_output(
+            ctx,
+            PORT_RESULT,
+            AgentData::from_value(classification_value(&classification, Some(score))),
+        )
+    }
+}
+
+/// Score from -1 (negative) to 1 (positive), weighted by the confidence.
+pub fn sentiment_score(classification: &Classification) -> f64 {
+    let sign = match classification.label.as_str() {
+        "positive" => 1.0,
+        "negative" => -1.0,
+        _ => 0.0,
+    };
+    sign * classification.confidence.unwrap_or(1.0)
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static PORT_TEXT: &str = "text";
+static PORT_LABEL: &str = "label";
+static PORT_SENTIMENT: &str = "sentiment";
+static PORT_RESULT: &str = "result";
+
+static CONFIG_PROVIDER: &str = "provider";
+static CONFIG_MODEL: &str = "model";
+static CONFIG_LABELS: &str = "labels";
+static CONFIG_INSTRUCTIONS: &str = "instructions";
+
+const DEFAULT_CONFIG_PROVIDER: &str = "openai";
+
+static SENTIMENT_LABELS: [&str; 3] = ["positive", "negative", "neutral"];
+static SENTIMENT_INSTRUCTIONS: &str = "Classify the overall sentiment of the text. Use neutral for factual text or for text mixing positive and negative feelings equally.";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_classify",
+            Some(new_agent_boxed::<LlmClassifyAgent>),
+        )
+        .title("LLM Classify")
+        .description("Classifies the text into one of the labels")
+        .category(CATEGORY)
+        .inputs(vec![PORT_TEXT])
+        .outputs(vec![PORT_LABEL, PORT_RESULT])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER, |entry| {
+            entry.title("Provider")
+        })
+        .string_config_with(CONFIG_MODEL, "", |entry| entry.title("Model"))
+        .text_config_with(CONFIG_LABELS, "", |entry| {
+            entry
+                .title("Labels")
+                .description("One per line or separated by commas")
+        })
+        .text_config_with(CONFIG_INSTRUCTIONS, "", |entry| entry.title("Instructions"))
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+    );
+
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_sentiment",
+            Some(new_agent_boxed::<LlmSentimentAgent>),
+        )
+        .title("LLM Sentiment")
+        .description("Classifies the text as positive, negative or neutral")
+        .category(CATEGORY)
+        .inputs(vec![PORT_TEXT])
+        .outputs(vec![PORT_SENTIMENT, PORT_RESULT])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER, |entry| {
+            entry.title("Provider")
+        })
+        .string_config_with(CONFIG_MODEL, "", |entry| entry.title("Model"))
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels() -> Vec<String> {
+        parse_labels("spam, ham\nother")
+    }
+
+    #[test]
+    fn test_classify_prompt() {
+        let prompt = classify_prompt(&labels(), "Mail from friends is ham.");
+        assert!(prompt.contains("- spam\n- ham\n- other\n"));
+        assert!(prompt.contains("Mail from friends is ham."));
+        assert!(prompt.contains("{\"label\""));
+    }
+
+    #[test]
+    fn test_parse_classification() {
+        let labels = labels();
+        let parse = |reply: &str| parse_classification(reply, &labels);
+
+        assert_eq!(
+            parse(r#"{"label": "spam", "confidence": 0.9}"#).unwrap(),
+            Classification {
+                label: "spam".to_string(),
+                confidence: Some(0.9),
+            }
+        );
+        // in a code block, with another case and an out of range confidence
+        let classification =
+            parse("```json\n{\"label\": \"Ham\", \"confidence\": 2}\n```").unwrap();
+        assert_eq!(classification.label, "ham");
+        assert_eq!(classification.confidence, Some(1.0));
+
+        // plain text naming one label
+        let classification = parse("It is spam.").unwrap();
+        assert_eq!(classification.label, "spam");
+        assert_eq!(classification.confidence, None);
+
+        assert!(parse(r#"{"label": "eggs"}"#).is_err());
+        assert!(parse("spam or ham").is_err());
+    }
+
+    #[test]
+    fn test_classify_with_mock() {
+        let askit = ASKit::new();
+        let mut configs = AgentConfigs::new();
+        configs.set(CONFIG_PROVIDER.to_string(), AgentValue::string("mock"));
+        configs.set(CONFIG_MODEL.to_string(), AgentValue::string("test"));
+        configs.set(
+            CONFIG_OPTIONS.to_string(),
+            AgentValue::string(r#"{"response": "{\"label\": \"negative\", \"confidence\": 0.5}"}"#),
+        );
+
+        let labels = SENTIMENT_LABELS
+            .iter()
+            .map(|label| label.to_string())
+            .collect::<Vec<_>>();
+        let mut classifier = Classifier::default();
+        let (classification, usage) = futures::executor::block_on(classifier.classify(
+            &askit,
+            &configs,
+            "I hate it".to_string(),
+            &labels,
+            SENTIMENT_INSTRUCTIONS,
+        ))
+        .unwrap();
+        assert_eq!(classification.label, "negative");
+        assert_eq!(sentiment_score(&classification), -0.5);
+        assert!(usage.is_some());
+    }
+}
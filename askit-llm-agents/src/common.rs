@@ -1,5 +1,8 @@
 use std::{
-    sync::{Arc, Mutex},
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
     vec,
 };
 
@@ -8,6 +11,8 @@ use agent_stream_kit::{
     AgentValue, AsAgent, AsAgentData, async_trait, new_agent_boxed,
 };
 
+use serde::{Serialize, de::DeserializeOwned};
+
 use crate::message::{Message, MessageHistory};
 
 // Assistant Message Agent
@@ -186,6 +191,28 @@ impl AsAgent for MessageHistoryAgent {
         &mut self.data
     }
 
+    fn state(&self) -> Option<AgentValue> {
+        if self.first_run {
+            return None;
+        }
+        let history = self.history.lock().unwrap();
+        Some(AgentValue::array(
+            history.messages().into_iter().map(|m| m.into()).collect(),
+        ))
+    }
+
+    fn restore_state(&mut self, state: AgentValue) -> Result<(), AgentError> {
+        let messages = state
+            .as_array()
+            .ok_or_else(|| AgentError::InvalidValue("history must be an array".to_string()))?
+            .iter()
+            .map(|v| v.clone().try_into())
+            .collect::<Result<Vec<Message>, AgentError>>()?;
+        *self.history.lock().unwrap() = MessageHistory::new(messages, 0);
+        self.first_run = false;
+        Ok(())
+    }
+
     async fn process(
         &mut self,
         ctx: AgentContext,
@@ -266,6 +293,227 @@ pub fn is_message_history(data: &AgentData) -> bool {
     false
 }
 
+// Response Cache
+//
+// Opt-in cache of provider responses shared by all LLM agents,
+// so that re-running a flow on identical inputs does not repeat paid calls.
+// Streamed requests bypass the cache, as their chunks are output as they arrive.
+
+pub struct ResponseCacheSettings {
+    pub ttl: Duration,
+    pub max_size: usize,
+}
+
+impl ResponseCacheSettings {
+    /// Reads the cache settings of an agent. Returns `None` when caching is disabled or the
+    /// agent streams its responses.
+    pub fn from_configs(configs: &AgentConfigs) -> Option<Self> {
+        if !configs.get_bool_or_default(CONFIG_CACHE) || configs.get_bool_or_default(CONFIG_STREAM)
+        {
+            return None;
+        }
+        let ttl = configs.get_integer_or(CONFIG_CACHE_TTL, DEFAULT_CACHE_TTL);
+        let max_size = configs.get_integer_or(CONFIG_CACHE_MAX_SIZE, DEFAULT_CACHE_MAX_SIZE);
+        Some(Self {
+            ttl: Duration::from_secs(ttl.max(0) as u64),
+            max_size: max_size.max(0) as usize,
+        })
+    }
+}
+
+struct ResponseCacheEntry {
+    created_at: Instant,
+    outputs: Vec<(String, AgentData)>,
+}
+
+pub enum ResponseCacheLookup {
+    /// Caching is disabled for the agent.
+    Disabled,
+
+    /// Outputs of a previous identical request, as (port, data) pairs.
+    Hit(Vec<(String, AgentData)>),
+
+    /// No usable entry. Store the outputs in the slot once the provider responded.
+    Miss(ResponseCacheSlot),
+}
+
+pub struct ResponseCacheSlot {
+    key: u64,
+    max_size: usize,
+}
+
+impl ResponseCacheSlot {
+    pub fn store(self, outputs: Vec<(String, AgentData)>) {
+        ResponseCache::global().insert(self.key, outputs, self.max_size);
+    }
+}
+
+#[derive(Default)]
+pub struct ResponseCache {
+    entries: Mutex<HashMap<u64, ResponseCacheEntry>>,
+}
+
+impl ResponseCache {
+    pub fn global() -> &'static ResponseCache {
+        static CACHE: OnceLock<ResponseCache> = OnceLock::new();
+        CACHE.get_or_init(ResponseCache::default)
+    }
+
+    /// Computes the cache key of a request from the provider, the model and the request body.
+    pub fn key<T: serde::Serialize>(
+        provider: &str,
+        model: &str,
+        request: &T,
+    ) -> Result<u64, AgentError> {
+        let body = serde_json::to_string(request)
+            .map_err(|e| AgentError::InvalidValue(format!("Serialization error: {}", e)))?;
+        let mut hasher = DefaultHasher::new();
+        provider.hash(&mut hasher);
+        model.hash(&mut hasher);
+        body.hash(&mut hasher);
+        Ok(hasher.finish())
+    }
+
+    pub fn lookup<T: serde::Serialize>(
+        &self,
+        configs: &AgentConfigs,
+        provider: &str,
+        model: &str,
+        request: &T,
+    ) -> Result<ResponseCacheLookup, AgentError> {
+        let Some(settings) = ResponseCacheSettings::from_configs(configs) else {
+            return Ok(ResponseCacheLookup::Disabled);
+        };
+        let key = Self::key(provider, model, request)?;
+        if let Some(outputs) = self.get(key, settings.ttl) {
+            return Ok(ResponseCacheLookup::Hit(outputs));
+        }
+        Ok(ResponseCacheLookup::Miss(ResponseCacheSlot {
+            key,
+            max_size: settings.max_size,
+        }))
+    }
+
+    /// Returns the outputs stored for the key, as (port, data) pairs.
+    pub fn get(&self, key: u64, ttl: Duration) -> Option<Vec<(String, AgentData)>> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get(&key)?;
+        if entry.created_at.elapsed() > ttl {
+            entries.remove(&key);
+            return None;
+        }
+        Some(entry.outputs.clone())
+    }
+
+    pub fn insert(&self, key: u64, outputs: Vec<(String, AgentData)>, max_size: usize) {
+        if max_size == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap();
+        while entries.len() >= max_size && !entries.contains_key(&key) {
+            // evict the oldest entry
+            let Some(oldest) = entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.created_at)
+                .map(|(k, _)| *k)
+            else {
+                break;
+            };
+            entries.remove(&oldest);
+        }
+        entries.insert(
+            key,
+            ResponseCacheEntry {
+                created_at: Instant::now(),
+                outputs,
+            },
+        );
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+// Request Options
+
+/// Merges the options, a JSON object such as `{"top_p": 0.9}`, into the request. Empty options
+/// leave the request as it is.
+pub fn apply_options<T>(request: T, options: &str) -> Result<T, AgentError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let options = options.trim();
+    if options.is_empty() {
+        return Ok(request);
+    }
+    let options_json = serde_json::from_str::<serde_json::Value>(options)
+        .map_err(|e| AgentError::InvalidConfig(format!("Invalid JSON in options: {}", e)))?;
+    if !options_json.is_object() {
+        return Err(AgentError::InvalidConfig(
+            "Options must be a JSON object".to_string(),
+        ));
+    }
+    merge_options(request, &options_json)
+}
+
+/// Overwrites the fields of the request with the ones of the options object.
+pub fn merge_options<T>(request: T, options_json: &serde_json::Value) -> Result<T, AgentError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    if options_json.as_object().is_some_and(|obj| obj.is_empty()) {
+        return Ok(request);
+    }
+    let mut request_json = serde_json::to_value(&request)
+        .map_err(|e| AgentError::InvalidValue(format!("Serialization error: {}", e)))?;
+
+    if let (Some(request_obj), Some(options_obj)) =
+        (request_json.as_object_mut(), options_json.as_object())
+    {
+        for (key, value) in options_obj {
+            request_obj.insert(key.clone(), value.clone());
+        }
+    }
+    serde_json::from_value::<T>(request_json)
+        .map_err(|e| AgentError::InvalidValue(format!("Deserialization error: {}", e)))
+}
+
+/// Options set by the temperature and max tokens configs. Empty configs keep the model
+/// defaults. APIs name the max tokens field differently, e.g. `num_predict` for Ollama.
+pub fn preset_options(configs: &AgentConfigs, max_tokens_key: &str) -> serde_json::Value {
+    let mut options = serde_json::Map::new();
+    if let Ok(temperature) = configs.get_number(CONFIG_TEMPERATURE) {
+        options.insert(CONFIG_TEMPERATURE.to_string(), temperature.into());
+    }
+    if let Ok(max_tokens) = configs.get_integer(CONFIG_MAX_TOKENS) {
+        options.insert(max_tokens_key.to_string(), max_tokens.into());
+    }
+    serde_json::Value::Object(options)
+}
+
+/// Applies the preset configs, then the options JSON of the agent, which takes precedence.
+pub fn apply_configured_options<T>(
+    request: T,
+    configs: &AgentConfigs,
+    max_tokens_key: &str,
+) -> Result<T, AgentError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let request = merge_options(request, &preset_options(configs, max_tokens_key))?;
+    apply_options(request, &configs.get_string_or_default(CONFIG_OPTIONS))
+}
+
+/// The options `apply_configured_options` would merge into a request, for the agents that
+/// leave building the request to an `LlmProvider`.
+pub fn configured_options(
+    configs: &AgentConfigs,
+    max_tokens_key: &str,
+) -> Result<serde_json::Value, AgentError> {
+    apply_configured_options(serde_json::json!({}), configs, max_tokens_key)
+}
+
 static AGENT_KIND: &str = "agent";
 static CATEGORY: &str = "LLM";
 
@@ -279,6 +527,18 @@ static CONFIG_HISTORY_SIZE: &str = "history_size";
 static CONFIG_MESSAGE: &str = "message";
 static CONFIG_PREAMBLE: &str = "preamble";
 static CONFIG_INCLUDE_SYSTZEM: &str = "include_system";
+static CONFIG_STREAM: &str = "stream";
+
+pub(crate) static CONFIG_MAX_TOKENS: &str = "max_tokens";
+pub(crate) static CONFIG_OPTIONS: &str = "options";
+pub(crate) static CONFIG_TEMPERATURE: &str = "temperature";
+
+pub(crate) static CONFIG_CACHE: &str = "cache";
+pub(crate) static CONFIG_CACHE_TTL: &str = "cache_ttl";
+pub(crate) static CONFIG_CACHE_MAX_SIZE: &str = "cache_max_size";
+
+pub(crate) const DEFAULT_CACHE_TTL: i64 = 3600;
+pub(crate) const DEFAULT_CACHE_MAX_SIZE: i64 = 100;
 
 pub fn register_agents(askit: &ASKit) {
     askit.register_agent(
@@ -337,3 +597,134 @@ pub fn register_agents(askit: &ASKit) {
         .integer_config_default(CONFIG_HISTORY_SIZE),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    struct Request {
+        model: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        temperature: Option<f64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_completion_tokens: Option<u32>,
+    }
+
+    fn request() -> Request {
+        Request {
+            model: "m".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_apply_options() {
+        assert_eq!(apply_options(request(), "").unwrap(), request());
+        assert_eq!(apply_options(request(), "{}").unwrap(), request());
+
+        let applied = apply_options(request(), r#"{"temperature": 0.2}"#).unwrap();
+        assert_eq!(applied.temperature, Some(0.2));
+        assert_eq!(applied.model, "m");
+
+        assert!(apply_options(request(), "{").is_err());
+        assert!(apply_options(request(), "[1]").is_err());
+        assert!(apply_options(request(), r#"{"temperature": "hot"}"#).is_err());
+    }
+
+    #[test]
+    fn test_apply_configured_options() {
+        let mut configs = AgentConfigs::new();
+        configs.set(CONFIG_TEMPERATURE.into(), AgentValue::unit());
+        configs.set(CONFIG_MAX_TOKENS.into(), AgentValue::integer(100));
+        let applied =
+            apply_configured_options(request(), &configs, "max_completion_tokens").unwrap();
+        assert_eq!(applied.temperature, None);
+        assert_eq!(applied.max_completion_tokens, Some(100));
+
+        // the options JSON wins over the presets
+        configs.set(CONFIG_TEMPERATURE.into(), AgentValue::number(1.0));
+        configs.set(
+            CONFIG_OPTIONS.into(),
+            AgentValue::string(r#"{"temperature": 0.5}"#),
+        );
+        let applied =
+            apply_configured_options(request(), &configs, "max_completion_tokens").unwrap();
+        assert_eq!(applied.temperature, Some(0.5));
+
+        let options = configured_options(&configs, "num_predict").unwrap();
+        assert_eq!(
+            options,
+            serde_json::json!({"temperature": 0.5, "num_predict": 100})
+        );
+    }
+
+    fn cache_configs() -> AgentConfigs {
+        let mut configs = AgentConfigs::new();
+        configs.set(CONFIG_CACHE.into(), AgentValue::boolean(true));
+        configs
+    }
+
+    fn outputs(text: &str) -> Vec<(String, AgentData)> {
+        vec![(PORT_MESSAGE.to_string(), AgentData::string(text))]
+    }
+
+    #[test]
+    fn test_response_cache_key() {
+        let key = ResponseCache::key("p", "m", &request()).unwrap();
+        assert_eq!(key, ResponseCache::key("p", "m", &request()).unwrap());
+        assert_ne!(key, ResponseCache::key("q", "m", &request()).unwrap());
+        assert_ne!(key, ResponseCache::key("p", "n", &request()).unwrap());
+
+        let mut other = request();
+        other.temperature = Some(0.5);
+        assert_ne!(key, ResponseCache::key("p", "m", &other).unwrap());
+    }
+
+    #[test]
+    fn test_response_cache_ttl() {
+        let cache = ResponseCache::default();
+        cache.insert(1, outputs("a"), 10);
+        assert!(cache.get(1, Duration::from_secs(60)).is_some());
+
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(cache.get(1, Duration::from_millis(1)).is_none());
+        // the expired entry is dropped
+        assert!(cache.get(1, Duration::from_secs(60)).is_none());
+    }
+
+    #[test]
+    fn test_response_cache_eviction() {
+        let cache = ResponseCache::default();
+        let ttl = Duration::from_secs(60);
+        for key in 0..3 {
+            cache.insert(key, outputs(&key.to_string()), 2);
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(cache.get(0, ttl).is_none());
+        assert!(cache.get(1, ttl).is_some());
+        assert!(cache.get(2, ttl).is_some());
+
+        // replacing an entry evicts nothing
+        cache.insert(2, outputs("b"), 2);
+        assert!(cache.get(1, ttl).is_some());
+        assert_eq!(cache.get(2, ttl).unwrap(), outputs("b"));
+
+        cache.insert(3, outputs("c"), 0);
+        assert!(cache.get(3, ttl).is_none());
+    }
+
+    #[test]
+    fn test_response_cache_settings() {
+        assert!(ResponseCacheSettings::from_configs(&AgentConfigs::new()).is_none());
+
+        let mut configs = cache_configs();
+        let settings = ResponseCacheSettings::from_configs(&configs).unwrap();
+        assert_eq!(settings.ttl, Duration::from_secs(DEFAULT_CACHE_TTL as u64));
+
+        // streamed responses are not cached
+        configs.set(CONFIG_STREAM.into(), AgentValue::boolean(true));
+        assert!(ResponseCacheSettings::from_configs(&configs).is_none());
+    }
+}
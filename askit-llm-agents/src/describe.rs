@@ -0,0 +1,289 @@
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentUsage, AgentValue, AgentValueMap, AsAgent, AsAgentData,
+    async_trait, new_agent_boxed,
+};
+
+use crate::classify::extract_json_object;
+use crate::common::CONFIG_OPTIONS;
+use crate::message::Message;
+use crate::provider::{LlmProviderCache, options_from_configs};
+
+/// Description of an image by the model, with tags of what it shows.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageDescription {
+    pub description: String,
+    pub tags: Vec<String>,
+}
+
+/// System prompt asking the model to describe the image and reply in JSON.
+pub fn describe_prompt(max_tags: usize, instructions: &str) -> String {
+    let mut prompt = format!(
+        "You describe images. Describe the image given by the user: what it shows, any text in it, and anything unusual. Then give up to {} short tags of the main objects, activities and kind of the image, such as photo, screenshot or chart.\n",
+        max_tags
+    );
+    if !instructions.is_empty() {
+        prompt.push_str(&format!("\nInstructions:\n{}\n", instructions));
+    }
+    prompt.push_str(
+        "\nReply with only a JSON object, without any other text:\n{\"description\": \"<description>\", \"tags\": [\"<tag>\", ...]}",
+    );
+    prompt
+}
+
+/// Reads the description out of the reply of the model.
+///
+/// The reply may wrap the JSON in a code block or in other text. A reply that is not
+/// JSON is taken as the description, without tags. Tags are lowercased and deduplicated.
+pub fn parse_description(reply: &str, max_tags: usize) -> ImageDescription {
+    let Some(json) = extract_json_object(reply).filter(|json| json["description"].is_string())
+    else {
+        return ImageDescription {
+            description: reply.trim().to_string(),
+            tags: vec![],
+        };
+    };
+    let mut tags: Vec<String> = Vec::new();
+    for tag in json["tags"].as_array().into_iter().flatten() {
+        let Some(tag) = tag.as_str().map(|tag| tag.trim().to_lowercase()) else {
+            continue;
+        };
+        if !tag.is_empty() && !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags.truncate(max_tags);
+    ImageDescription {
+        description: json["description"]
+            .as_str()
+            .unwrap_or_default()
+            .trim()
+            .to_string(),
+        tags,
+    }
+}
+
+#[derive(Default)]
+struct Describer {
+    providers: LlmProviderCache,
+}
+
+impl Describer {
+    async fn describe(
+        &mut self,
+        askit: &ASKit,
+        configs: &AgentConfigs,
+        image: Message,
+    ) -> Result<(ImageDescription, Option<AgentUsage>), AgentError> {
+        let model = configs.get_string_or_default(CONFIG_MODEL);
+        if model.is_empty() {
+            return Err(AgentError::InvalidConfig("model is not set".to_string()));
+        }
+        let max_tags = configs
+            .get_integer_or(CONFIG_MAX_TAGS, DEFAULT_MAX_TAGS)
+            .max(0) as usize;
+        let instructions = configs.get_string_or_default(CONFIG_INSTRUCTIONS);
+        let options = options_from_configs(configs)?;
+        let provider = self.providers.get(
+            askit,
+            &configs.get_string_or(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER),
+        )?;
+        let messages = vec![
+            Message::system(describe_prompt(max_tags, &instructions)),
+            image,
+        ];
+        let res = provider.chat(&model, messages, options.as_ref()).await?;
+        Ok((parse_description(&res.message.content, max_tags), res.usage))
+    }
+}
+
+// User message of an image, or of a message with an image
+fn image_message(data: AgentData) -> Result<Message, AgentError> {
+    if let Some(image) = data.as_image() {
+        return Ok(Message::user(IMAGE_TEXT.to_string()).with_image(image));
+    }
+    let mut message: Message = data.try_into().map_err(|e| {
+        AgentError::InvalidValue(format!("Failed to convert data to Message: {}", e))
+    })?;
+    if message.image.is_none() {
+        return Err(AgentError::InvalidValue(
+            "The input has no image".to_string(),
+        ));
+    }
+    if message.content.is_empty() {
+        message.content = IMAGE_TEXT.to_string();
+    }
+    message.role = "user".to_string();
+    Ok(message)
+}
+
+// LLM Describe Image Agent
+pub struct LlmDescribeImageAgent {
+    data: AsAgentData,
+    describer: Describer,
+}
+
+#[async_trait]
+impl AsAgent for LlmDescribeImageAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            describer: Describer::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let configs = self.configs()?.clone();
+        let image = image_message(data)?;
+
+        self.check_capability(AgentCapability::Network)?;
+        let askit = self.askit().clone();
+        let _llm_call = askit.acquire_llm_call(self.flow_name()).await?;
+        let (description, usage) = self.describer.describe(&askit, &configs, image).await?;
+        if let Some(usage) = usage {
+            self.emit_usage(usage);
+        }
+
+        let tags = description
+            .tags
+            .iter()
+            .map(|tag| AgentValue::string(tag.clone()))
+            .collect::<Vec<_>>();
+        let mut result = AgentValueMap::new();
+        result.insert(
+            "description".to_string(),
+            AgentValue::string(description.description.clone()),
+        );
+        result.insert("tags".to_string(), AgentValue::array(tags.clone()));
+
+        self.try_output(
+            ctx.clone(),
+            PORT_DESCRIPTION,
+            AgentData::string(description.description),
+        )?;
+        self.try_output(ctx.clone(), PORT_TAGS, AgentData::array("string", tags))?;
+        self.try_output(ctx, PORT_RESULT, AgentData::object(result))
+    }
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static PORT_IMAGE: &str = "image";
+static PORT_DESCRIPTION: &str = "description";
+static PORT_TAGS: &str = "tags";
+static PORT_RESULT: &str = "result";
+
+static CONFIG_PROVIDER: &str = "provider";
+static CONFIG_MODEL: &str = "model";
+static CONFIG_MAX_TAGS: &str = "max_tags";
+static CONFIG_INSTRUCTIONS: &str = "instructions";
+
+const DEFAULT_CONFIG_PROVIDER: &str = "openai";
+const DEFAULT_MAX_TAGS: i64 = 10;
+
+static IMAGE_TEXT: &str = "Describe this image.";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_describe_image",
+            Some(new_agent_boxed::<LlmDescribeImageAgent>),
+        )
+        .title("LLM Describe Image")
+        .description("Describes an image with a multimodal model, and tags what it shows")
+        .category(CATEGORY)
+        .inputs(vec![PORT_IMAGE])
+        .outputs(vec![PORT_DESCRIPTION, PORT_TAGS, PORT_RESULT])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER, |entry| {
+            entry.title("Provider")
+        })
+        .string_config_with(CONFIG_MODEL, "", |entry| entry.title("Model"))
+        .integer_config_with(CONFIG_MAX_TAGS, DEFAULT_MAX_TAGS, |entry| {
+            entry.title("Max Tags")
+        })
+        .text_config_with(CONFIG_INSTRUCTIONS, "", |entry| {
+            entry
+                .title("Instructions")
+                .description("What to look for, such as errors shown on the screen")
+        })
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use photon_rs::PhotonImage;
+
+    #[test]
+    fn test_parse_description() {
+        let description = parse_description(
+            "```json\n{\"description\": \"A cat on a sofa.\", \"tags\": [\"Cat\", \"sofa\", \"cat\", 1, \"photo\"]}\n```",
+            2,
+        );
+        assert_eq!(
+            description,
+            ImageDescription {
+                description: "A cat on a sofa.".to_string(),
+                tags: vec!["cat".to_string(), "sofa".to_string()],
+            }
+        );
+
+        // plain text
+        let description = parse_description(" A terminal with an error. ", 10);
+        assert_eq!(description.description, "A terminal with an error.");
+        assert!(description.tags.is_empty());
+    }
+
+    #[test]
+    fn test_describe_with_mock() {
+        let askit = ASKit::new();
+        let mut configs = AgentConfigs::new();
+        configs.set(CONFIG_PROVIDER.to_string(), AgentValue::string("mock"));
+        configs.set(CONFIG_MODEL.to_string(), AgentValue::string("test"));
+        configs.set(
+            CONFIG_OPTIONS.to_string(),
+            AgentValue::string(
+                r#"{"response": "{\"description\": \"A blank screen.\", \"tags\": [\"screenshot\"]}"}"#,
+            ),
+        );
+
+        let image = PhotonImage::new(vec![0; 4], 1, 1);
+        let message = image_message(AgentData::image(image)).unwrap();
+        assert_eq!(message.role, "user");
+        assert!(message.image.is_some());
+
+        let mut describer = Describer::default();
+        let (description, usage) =
+            futures::executor::block_on(describer.describe(&askit, &configs, message)).unwrap();
+        assert_eq!(description.description, "A blank screen.");
+        assert_eq!(description.tags, vec!["screenshot"]);
+        assert!(usage.is_some());
+
+        assert!(image_message(AgentData::string("no image")).is_err());
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
+    AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::vcr::fnv1a;
+
+// Embedding Cache
+//
+// Embeddings of the texts already embedded, shared by the embeddings agents, so that
+// unchanged document chunks are not embedded again on every run. The key is a hash of the
+// provider, the model, the options and the text. With a directory set, the embeddings
+// are appended to a file there and loaded again by the next process.
+
+/// Lookups since the start of the process, and the embeddings in the cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct EmbeddingCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+#[derive(Default)]
+struct EmbeddingCacheState {
+    dir: Option<PathBuf>,
+    entries: HashMap<u64, Vec<f32>>,
+    loaded: bool,
+}
+
+// Line of the cache file
+#[derive(Serialize, Deserialize)]
+struct EmbeddingCacheLine {
+    key: String,
+    embedding: Vec<f32>,
+}
+
+#[derive(Default)]
+pub struct EmbeddingCache {
+    state: Mutex<EmbeddingCacheState>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl EmbeddingCache {
+    pub fn global() -> &'static EmbeddingCache {
+        static CACHE: OnceLock<EmbeddingCache> = OnceLock::new();
+        CACHE.get_or_init(EmbeddingCache::default)
+    }
+
+    /// Keeps the embeddings in a file of the directory. None keeps them only in memory.
+    pub fn set_dir(&self, dir: Option<PathBuf>) {
+        *self.state.lock().unwrap() = EmbeddingCacheState {
+            dir,
+            ..Default::default()
+        };
+    }
+
+    pub fn key(provider: &str, model: &str, options: &str, text: &str) -> u64 {
+        // as a JSON array, so that the parts cannot run into each other
+        let key = serde_json::json!([provider, model, options.trim(), text]);
+        fnv1a(key.to_string().as_bytes())
+    }
+
+    /// Looks up the embeddings of the inputs, counting a hit or a miss for each.
+    pub fn lookup(
+        &self,
+        provider: &str,
+        model: &str,
+        options: &str,
+        inputs: &[String],
+    ) -> Result<EmbeddingLookup<'_>, AgentError> {
+        let keys = inputs
+            .iter()
+            .map(|text| Self::key(provider, model, options, text))
+            .collect::<Vec<_>>();
+        let mut state = self.state.lock().unwrap();
+        load(&mut state)?;
+        let cached = keys
+            .iter()
+            .map(|key| state.entries.get(key).cloned())
+            .collect::<Vec<_>>();
+        drop(state);
+
+        let hits = cached.iter().filter(|e| e.is_some()).count() as u64;
+        self.hits.fetch_add(hits, Ordering::Relaxed);
+        self.misses
+            .fetch_add(keys.len() as u64 - hits, Ordering::Relaxed);
+        Ok(EmbeddingLookup {
+            cache: self,
+            keys,
+            cached,
+        })
+    }
+
+    pub fn insert(&self, entries: Vec<(u64, Vec<f32>)>) -> Result<(), AgentError> {
+        let mut state = self.state.lock().unwrap();
+        load(&mut state)?;
+        if let Some(dir) = &state.dir {
+            let mut text = String::new();
+            for (key, embedding) in entries.iter() {
+                let line = EmbeddingCacheLine {
+                    key: format!("{:016x}", key),
+                    embedding: embedding.clone(),
+                };
+                text.push_str(&serde_json::to_string(&line)?);
+                text.push('\n');
+            }
+            std::fs::create_dir_all(dir).map_err(|e| AgentError::IoError(e.to_string()))?;
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dir.join(CACHE_FILE))
+                .and_then(|mut file| file.write_all(text.as_bytes()))
+                .map_err(|e| AgentError::IoError(e.to_string()))?;
+        }
+        state.entries.extend(entries);
+        Ok(())
+    }
+
+    pub fn stats(&self) -> EmbeddingCacheStats {
+        EmbeddingCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.state.lock().unwrap().entries.len(),
+        }
+    }
+
+    /// Removes the embeddings, also from the file, and resets the counts.
+    pub fn clear(&self) -> Result<(), AgentError> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(path) = state.dir.as_ref().map(|dir| dir.join(CACHE_FILE))
+            && path.exists()
+        {
+            std::fs::remove_file(path).map_err(|e| AgentError::IoError(e.to_string()))?;
+        }
+        state.entries.clear();
+        state.loaded = true;
+        self.hits.store(0, Ordering::Relaxed);
+        self.misses.store(0, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// Reads the cache file once. Broken lines, such as one cut by a crash, are skipped.
+fn load(state: &mut EmbeddingCacheState) -> Result<(), AgentError> {
+    if state.loaded {
+        return Ok(());
+    }
+    state.loaded = true;
+    let Some(path) = state.dir.as_ref().map(|dir| dir.join(CACHE_FILE)) else {
+        return Ok(());
+    };
+    if !path.exists() {
+        return Ok(());
+    }
+    let file = std::fs::File::open(&path).map_err(|e| AgentError::IoError(e.to_string()))?;
+    for line in BufReader::new(file).lines() {
+        let line = line.map_err(|e| AgentError::IoError(e.to_string()))?;
+        let Ok(line) = serde_json::from_str::<EmbeddingCacheLine>(&line) else {
+            continue;
+        };
+        if let Ok(key) = u64::from_str_radix(&line.key, 16) {
+            state.entries.insert(key, line.embedding);
+        }
+    }
+    Ok(())
+}
+
+/// Embeddings found in the cache for the inputs of a request.
+pub struct EmbeddingLookup<'a> {
+    cache: &'a EmbeddingCache,
+    keys: Vec<u64>,
+    cached: Vec<Option<Vec<f32>>>,
+}
+
+impl EmbeddingLookup<'_> {
+    /// The inputs to embed, those not in the cache.
+    pub fn missing(&self, inputs: &[String]) -> Vec<String> {
+        inputs
+            .iter()
+            .zip(self.cached.iter())
+            .filter(|(_, cached)| cached.is_none())
+            .map(|(input, _)| input.clone())
+            .collect()
+    }
+
+    /// Stores the embeddings of the missing inputs, and returns the embeddings of all the
+    /// inputs in order.
+    pub fn merge(self, embeddings: Vec<Vec<f32>>) -> Result<Vec<Vec<f32>>, AgentError> {
+        let missing = self.cached.iter().filter(|e| e.is_none()).count();
+        if embeddings.len() != missing {
+            return Err(AgentError::InvalidValue(format!(
+                "Expected {} embeddings, got {}",
+                missing,
+                embeddings.len()
+            )));
+        }
+        let mut new_entries = Vec::new();
+        let mut embeddings = embeddings.into_iter();
+        let mut merged = Vec::with_capacity(self.cached.len());
+        for (key, cached) in self.keys.into_iter().zip(self.cached) {
+            let embedding = match cached {
+                Some(embedding) => embedding,
+                None => {
+                    let embedding = embeddings.next().unwrap_or_default();
+                    new_entries.push((key, embedding.clone()));
+                    embedding
+                }
+            };
+            merged.push(embedding);
+        }
+        if !new_entries.is_empty() {
+            self.cache.insert(new_entries)?;
+        }
+        Ok(merged)
+    }
+}
+
+// LLM Embedding Cache Agent
+pub struct LlmEmbeddingCacheAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for LlmEmbeddingCacheAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        let cache = EmbeddingCache::global();
+        if pin == PORT_CLEAR {
+            cache.clear()?;
+        }
+        self.try_output(ctx, PORT_STATS, AgentData::from_serialize(&cache.stats())?)
+    }
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static PORT_TRIGGER: &str = "trigger";
+static PORT_CLEAR: &str = "clear";
+static PORT_STATS: &str = "stats";
+
+const CACHE_FILE: &str = "embeddings.jsonl";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_embedding_cache",
+            Some(new_agent_boxed::<LlmEmbeddingCacheAgent>),
+        )
+        .title("LLM Embedding Cache")
+        .description("Outputs the hits and misses of the embedding cache, or clears it")
+        .category(CATEGORY)
+        .inputs(vec![PORT_TRIGGER, PORT_CLEAR])
+        .outputs(vec![PORT_STATS]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedding_cache() {
+        let dir = std::env::temp_dir().join(format!("askit-embeddings-{}", uuid::Uuid::new_v4()));
+        let cache = EmbeddingCache::default();
+        cache.set_dir(Some(dir.clone()));
+
+        let inputs = vec!["a".to_string(), "b".to_string()];
+        let lookup = cache.lookup("mock", "test", "{}", &inputs).unwrap();
+        assert_eq!(lookup.missing(&inputs), inputs);
+        let embeddings = lookup.merge(vec![vec![1.0, 0.0], vec![0.0, 1.0]]).unwrap();
+        assert_eq!(embeddings, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+
+        // only the new input is missing
+        let inputs = vec!["b".to_string(), "c".to_string()];
+        let lookup = cache.lookup("mock", "test", "{}", &inputs).unwrap();
+        assert_eq!(lookup.missing(&inputs), vec!["c".to_string()]);
+        assert!(lookup.merge(vec![]).is_err());
+        let lookup = cache.lookup("mock", "test", "{}", &inputs).unwrap();
+        let embeddings = lookup.merge(vec![vec![0.5, 0.5]]).unwrap();
+        assert_eq!(embeddings, vec![vec![0.0, 1.0], vec![0.5, 0.5]]);
+
+        // another model is another key
+        let lookup = cache.lookup("mock", "other", "{}", &inputs).unwrap();
+        assert_eq!(lookup.missing(&inputs).len(), 2);
+
+        assert_eq!(
+            cache.stats(),
+            EmbeddingCacheStats {
+                hits: 2,
+                misses: 6,
+                entries: 3,
+            }
+        );
+
+        // loaded again from the file
+        cache.set_dir(Some(dir.clone()));
+        let lookup = cache.lookup("mock", "test", "{}", &inputs).unwrap();
+        assert!(lookup.missing(&inputs).is_empty());
+
+        cache.clear().unwrap();
+        assert_eq!(cache.stats(), EmbeddingCacheStats::default());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
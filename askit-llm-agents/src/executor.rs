@@ -0,0 +1,456 @@
+use std::time::Duration;
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentUsage, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+
+use crate::classify::extract_json_object;
+use crate::common::CONFIG_OPTIONS;
+use crate::message::{Message, messages_from_data};
+use crate::provider::{LlmProvider, LlmProviderCache, options_from_configs};
+
+/// Tool the model may call, described to it in the system prompt.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToolSpec {
+    pub name: String,
+    pub description: String,
+}
+
+/// What the model wants to do next.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AgentStep {
+    /// Call the tool with the input and show the result to the model.
+    ToolCall {
+        name: String,
+        input: serde_json::Value,
+    },
+
+    /// Final answer to the messages.
+    Answer(String),
+}
+
+/// Reads the tools config, one `name: description` per line.
+pub fn parse_tools(tools: &str) -> Vec<ToolSpec> {
+    tools
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (name, description) = line.split_once(':').unwrap_or((line, ""));
+            ToolSpec {
+                name: name.trim().to_string(),
+                description: description.trim().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// System prompt describing the tools and asking the model to reply in JSON.
+pub fn executor_prompt(tools: &[ToolSpec], instructions: &str) -> String {
+    let mut prompt = String::new();
+    if !instructions.is_empty() {
+        prompt.push_str(&format!("{}\n\n", instructions));
+    }
+    prompt.push_str("You can use the tools below to answer the user.\n\nTools:\n");
+    for tool in tools {
+        if tool.description.is_empty() {
+            prompt.push_str(&format!("- {}\n", tool.name));
+        } else {
+            prompt.push_str(&format!("- {}: {}\n", tool.name, tool.description));
+        }
+    }
+    prompt.push_str(
+        "\nTo use a tool, reply with only a JSON object, without any other text:\n{\"tool\": \"<name of the tool>\", \"input\": <input of the tool>}\nThe result of the tool is given in the next message. When you know the answer, reply with only:\n{\"answer\": \"<your answer>\"}",
+    );
+    prompt
+}
+
+/// Reads the next step out of the reply of the model.
+///
+/// The reply may wrap the JSON in a code block or in other text. A reply that is not JSON
+/// is taken as the answer.
+pub fn parse_step(reply: &str, tools: &[ToolSpec]) -> Result<AgentStep, AgentError> {
+    let Some(json) = extract_json_object(reply) else {
+        return Ok(AgentStep::Answer(reply.trim().to_string()));
+    };
+    if let Some(name) = json.get("tool").and_then(|name| name.as_str()) {
+        if !tools.iter().any(|tool| tool.name == name) {
+            return Err(AgentError::InvalidValue(format!(
+                "The model called an unknown tool: {}",
+                name
+            )));
+        }
+        return Ok(AgentStep::ToolCall {
+            name: name.to_string(),
+            input: json.get("input").cloned().unwrap_or_default(),
+        });
+    }
+    match json.get("answer") {
+        Some(serde_json::Value::String(answer)) => Ok(AgentStep::Answer(answer.clone())),
+        Some(answer) => Ok(AgentStep::Answer(answer.to_string())),
+        None => Err(AgentError::InvalidValue(format!(
+            "Failed to read a tool call or an answer from the reply: {}",
+            reply
+        ))),
+    }
+}
+
+// Text of a tool result shown to the model
+fn tool_result_text(data: &AgentData) -> String {
+    match data.as_str() {
+        Some(text) => text.to_string(),
+        None => data.value.to_json().to_string(),
+    }
+}
+
+/// Loop of model calls and tool calls, until the model answers.
+pub struct ToolLoop<'a> {
+    pub askit: &'a ASKit,
+    pub flow_name: &'a str,
+    pub provider: &'a dyn LlmProvider,
+    pub model: &'a str,
+    pub options: Option<&'a serde_json::Value>,
+    pub tools: &'a [ToolSpec],
+    pub instructions: &'a str,
+    pub max_iterations: usize,
+}
+
+impl ToolLoop<'_> {
+    /// Runs the model on the messages, calling the tools it asks for in between.
+    ///
+    /// Each call of the model is an iteration. Fails when the model has not answered
+    /// within `max_iterations`. The LLM call quota of the flow is held only during the
+    /// model calls, so tools may call models too.
+    pub async fn run<F, Fut>(
+        &self,
+        messages: Vec<Message>,
+        mut call_tool: F,
+        mut on_usage: impl FnMut(AgentUsage),
+    ) -> Result<String, AgentError>
+    where
+        F: FnMut(String, serde_json::Value) -> Fut,
+        Fut: Future<Output = Result<AgentData, AgentError>>,
+    {
+        let mut messages = [
+            vec![Message::system(executor_prompt(
+                self.tools,
+                self.instructions,
+            ))],
+            messages,
+        ]
+        .concat();
+
+        for _ in 0..self.max_iterations {
+            let res = {
+                let _llm_call = self.askit.acquire_llm_call(self.flow_name).await?;
+                self.provider
+                    .chat(self.model, messages.clone(), self.options)
+                    .await?
+            };
+            if let Some(usage) = res.usage {
+                on_usage(usage);
+            }
+
+            let reply = res.message.content;
+            match parse_step(&reply, self.tools)? {
+                AgentStep::Answer(answer) => return Ok(answer),
+                AgentStep::ToolCall { name, input } => {
+                    let result = call_tool(name.clone(), input).await?;
+                    messages.push(Message::assistant(reply));
+                    messages.push(Message::user(format!(
+                        "Result of {}:\n{}",
+                        name,
+                        tool_result_text(&result)
+                    )));
+                }
+            }
+        }
+        Err(AgentError::Other(format!(
+            "The model did not answer within {} iterations",
+            self.max_iterations
+        )))
+    }
+}
+
+// LLM Agent Executor Agent
+pub struct LlmAgentExecutorAgent {
+    data: AsAgentData,
+    providers: LlmProviderCache,
+}
+
+#[async_trait]
+impl AsAgent for LlmAgentExecutorAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            providers: LlmProviderCache::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PORT_TOOL_RESULT {
+            // a result that came back after its call timed out
+            return Ok(());
+        }
+
+        let configs = self.configs()?.clone();
+        let model = configs.get_string_or_default(CONFIG_MODEL);
+        if model.is_empty() {
+            return Ok(());
+        }
+
+        let messages = messages_from_data(&data)?;
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let tools = parse_tools(&configs.get_string_or_default(CONFIG_TOOLS));
+        let instructions = configs.get_string_or_default(CONFIG_INSTRUCTIONS);
+        let max_iterations = configs
+            .get_integer_or(CONFIG_MAX_ITERATIONS, DEFAULT_MAX_ITERATIONS)
+            .max(1) as usize;
+        let tool_timeout = Duration::from_secs(
+            configs
+                .get_integer_or(CONFIG_TOOL_TIMEOUT, DEFAULT_TOOL_TIMEOUT)
+                .max(1) as u64,
+        );
+        let options = options_from_configs(&configs)?;
+        let provider = self.providers.get(
+            &self.data.askit,
+            &configs.get_string_or(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER),
+        )?;
+
+        self.check_capability(AgentCapability::Network)?;
+        let tool_loop = ToolLoop {
+            askit: self.askit(),
+            flow_name: self.flow_name(),
+            provider: provider.as_ref(),
+            model: &model,
+            options: options.as_ref(),
+            tools: &tools,
+            instructions: &instructions,
+            max_iterations,
+        };
+        // a tool call goes out on the tool_call port, and its result comes back on the
+        // tool_result port with the context of the call
+        let answer = tool_loop
+            .run(
+                messages,
+                |name, input| {
+                    let call = serde_json::json!({"name": name, "input": input});
+                    let request = AgentData::from_serialize(&call)
+                        .map(|call| self.request(ctx.clone(), PORT_TOOL_CALL, call, tool_timeout));
+                    async move { request?.await }
+                },
+                |usage| self.emit_usage(usage),
+            )
+            .await?;
+
+        self.try_output(
+            ctx.clone(),
+            PORT_MESSAGE,
+            Message::assistant(answer.clone()).into(),
+        )?;
+        self.try_output(ctx, PORT_ANSWER, AgentData::string(answer))
+    }
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static PORT_MESSAGE: &str = "message";
+static PORT_TOOL_CALL: &str = "tool_call";
+static PORT_TOOL_RESULT: &str = "tool_result";
+static PORT_ANSWER: &str = "answer";
+
+static CONFIG_PROVIDER: &str = "provider";
+static CONFIG_MODEL: &str = "model";
+static CONFIG_TOOLS: &str = "tools";
+static CONFIG_INSTRUCTIONS: &str = "instructions";
+static CONFIG_MAX_ITERATIONS: &str = "max_iterations";
+static CONFIG_TOOL_TIMEOUT: &str = "tool_timeout";
+
+const DEFAULT_CONFIG_PROVIDER: &str = "openai";
+const DEFAULT_MAX_ITERATIONS: i64 = 5;
+const DEFAULT_TOOL_TIMEOUT: i64 = 60;
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_agent_executor",
+            Some(new_agent_boxed::<LlmAgentExecutorAgent>),
+        )
+        .title("LLM Agent Executor")
+        .description("Answers the messages, calling the tools of the flow as the model asks")
+        .category(CATEGORY)
+        .inputs(vec![PORT_MESSAGE, PORT_TOOL_RESULT])
+        .outputs(vec![PORT_TOOL_CALL, PORT_MESSAGE, PORT_ANSWER])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER, |entry| {
+            entry.title("Provider")
+        })
+        .string_config_with(CONFIG_MODEL, "", |entry| entry.title("Model"))
+        .text_config_with(CONFIG_TOOLS, "", |entry| {
+            entry
+                .title("Tools")
+                .description("One `name: description` per line")
+        })
+        .text_config_with(CONFIG_INSTRUCTIONS, "", |entry| entry.title("Instructions"))
+        .integer_config_with(CONFIG_MAX_ITERATIONS, DEFAULT_MAX_ITERATIONS, |entry| {
+            entry.title("Max Iterations")
+        })
+        .integer_config_with(CONFIG_TOOL_TIMEOUT, DEFAULT_TOOL_TIMEOUT, |entry| {
+            entry.title("Tool Timeout").description("seconds")
+        })
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::mock::MockProvider;
+
+    fn tools() -> Vec<ToolSpec> {
+        parse_tools("add: adds a and b\n\nclock")
+    }
+
+    #[test]
+    fn test_parse_tools() {
+        assert_eq!(
+            tools(),
+            vec![
+                ToolSpec {
+                    name: "add".to_string(),
+                    description: "adds a and b".to_string(),
+                },
+                ToolSpec {
+                    name: "clock".to_string(),
+                    description: String::new(),
+                },
+            ]
+        );
+        let prompt = executor_prompt(&tools(), "Be brief.");
+        assert!(prompt.starts_with("Be brief.\n\n"));
+        assert!(prompt.contains("- add: adds a and b\n- clock\n"));
+    }
+
+    #[test]
+    fn test_parse_step() {
+        let tools = tools();
+        assert_eq!(
+            parse_step(
+                "```json\n{\"tool\": \"add\", \"input\": {\"a\": 1}}\n```",
+                &tools
+            )
+            .unwrap(),
+            AgentStep::ToolCall {
+                name: "add".to_string(),
+                input: serde_json::json!({"a": 1}),
+            }
+        );
+        assert_eq!(
+            parse_step(r#"{"tool": "clock"}"#, &tools).unwrap(),
+            AgentStep::ToolCall {
+                name: "clock".to_string(),
+                input: serde_json::Value::Null,
+            }
+        );
+        assert_eq!(
+            parse_step(r#"{"answer": "3"}"#, &tools).unwrap(),
+            AgentStep::Answer("3".to_string())
+        );
+        assert_eq!(
+            parse_step(r#"{"answer": 3}"#, &tools).unwrap(),
+            AgentStep::Answer("3".to_string())
+        );
+        assert_eq!(
+            parse_step(" It is 3. ", &tools).unwrap(),
+            AgentStep::Answer("It is 3.".to_string())
+        );
+        assert!(parse_step(r#"{"tool": "rm"}"#, &tools).is_err());
+        assert!(parse_step(r#"{"result": 3}"#, &tools).is_err());
+    }
+
+    type LoopResult = (
+        Result<String, AgentError>,
+        Vec<(String, serde_json::Value)>,
+        usize,
+    );
+
+    fn run_loop(responses: serde_json::Value, max_iterations: usize) -> LoopResult {
+        let askit = ASKit::new();
+        let provider = MockProvider::new(&askit).unwrap();
+        let options = serde_json::json!({ "responses": responses });
+        let tools = tools();
+        let tool_loop = ToolLoop {
+            askit: &askit,
+            flow_name: "flow",
+            provider: &provider,
+            model: "m",
+            options: Some(&options),
+            tools: &tools,
+            instructions: "",
+            max_iterations,
+        };
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut usages = 0;
+        let answer = futures::executor::block_on(tool_loop.run(
+            vec![Message::user("What is 1 + 2?".to_string())],
+            |name, input| {
+                calls.lock().unwrap().push((name, input));
+                async { Ok(AgentData::integer(3)) }
+            },
+            |_| usages += 1,
+        ));
+        let calls = calls.lock().unwrap().clone();
+        (answer, calls, usages)
+    }
+
+    #[test]
+    fn test_tool_loop() {
+        let (answer, calls, usages) = run_loop(
+            serde_json::json!([
+                r#"{"tool": "add", "input": {"a": 1, "b": 2}}"#,
+                r#"{"answer": "1 + 2 = 3"}"#,
+            ]),
+            5,
+        );
+        assert_eq!(answer.unwrap(), "1 + 2 = 3");
+        assert_eq!(
+            calls,
+            vec![("add".to_string(), serde_json::json!({"a": 1, "b": 2}))]
+        );
+        assert_eq!(usages, 2);
+
+        // the model keeps calling tools
+        let (answer, calls, usages) = run_loop(serde_json::json!([r#"{"tool": "clock"}"#]), 3);
+        assert!(answer.is_err());
+        assert_eq!(calls.len(), 3);
+        assert_eq!(usages, 3);
+    }
+}
@@ -1,11 +1,25 @@
 use agent_stream_kit::ASKit;
 
+pub mod classify;
 pub mod common;
+#[cfg(feature = "image")]
+pub mod describe;
+pub mod embedding_cache;
+pub mod executor;
 pub mod message;
+pub mod mock;
+pub mod provider;
+pub mod rerank;
+pub mod session;
+pub mod summarize;
+pub mod vcr;
 
 #[cfg(feature = "mcp")]
 pub mod mcp;
 
+#[cfg(feature = "mcp")]
+pub mod mcp_server;
+
 #[cfg(feature = "ollama")]
 pub mod ollama;
 
@@ -15,12 +29,30 @@ pub mod openai;
 #[cfg(feature = "sakura")]
 pub mod sakura_ai;
 
+#[cfg(feature = "server")]
+pub mod openai_server;
+
 pub fn register_agents(askit: &ASKit) {
+    message::register_kinds(askit);
+
+    classify::register_agents(askit);
     common::register_agents(askit);
+    #[cfg(feature = "image")]
+    describe::register_agents(askit);
+    embedding_cache::register_agents(askit);
+    executor::register_agents(askit);
+    mock::register_agents(askit);
+    provider::register_agents(askit);
+    rerank::register_agents(askit);
+    session::register_agents(askit);
+    summarize::register_agents(askit);
 
     #[cfg(feature = "mcp")]
     mcp::register_agents(askit);
 
+    #[cfg(feature = "mcp")]
+    mcp_server::register_agents(askit);
+
     #[cfg(feature = "ollama")]
     ollama::register_agents(askit);
 
@@ -29,4 +61,7 @@ pub fn register_agents(askit: &ASKit) {
 
     #[cfg(feature = "sakura")]
     sakura_ai::register_agents(askit);
+
+    #[cfg(feature = "server")]
+    openai_server::register_agents(askit);
 }
@@ -3,8 +3,8 @@
 use std::vec;
 
 use agent_stream_kit::{
-    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
-    AgentValue, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentValue, AsAgent, AsAgentData, async_trait, new_agent_boxed,
 };
 use rmcp::{
     model::{CallToolRequestParam, CallToolResult},
@@ -45,6 +45,8 @@ impl AsAgent for MCPCallAgent {
         _pin: String,
         data: AgentData,
     ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Exec)?;
+
         let command = self.configs()?.get_string_or_default(CONFIG_COMMAND);
         let args_str = self.configs()?.get_string_or_default(CONFIG_ARGS);
         let args: Vec<String> = serde_json::from_str(&args_str)
@@ -152,6 +154,7 @@ pub fn register_agents(askit: &ASKit) {
         .category(CATEGORY)
         .inputs(vec![PORT_OBJECT])
         .outputs(vec![PORT_OBJECT, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Exec])
         .string_config_default(CONFIG_COMMAND)
         .string_config_default(CONFIG_ARGS)
         .string_config_default(CONFIG_TOOL),
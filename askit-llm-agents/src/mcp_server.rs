@@ -0,0 +1,457 @@
+#![cfg(feature = "mcp")]
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentValue, AsAgent,
+    AsAgentData, async_trait, new_agent_boxed,
+};
+use rmcp::{
+    ErrorData as McpError, ServerHandler,
+    model::{
+        CallToolRequestParam, CallToolResult, Content, Implementation, ListToolsResult,
+        PaginatedRequestParam, ServerCapabilities, ServerInfo, Tool,
+    },
+    service::{RequestContext, RoleServer, ServiceExt},
+};
+use serde_json::{Map, Value, json};
+use tokio::sync::oneshot;
+
+// MCP Server
+//
+// Publishes flows as MCP tools. A flow is published by its `mcp_tool_input` node, which
+// outputs the arguments of each call, and answers with the data reaching its
+// `mcp_tool_output` node.
+
+/// Flow published as an MCP tool.
+#[derive(Clone, Debug)]
+pub struct FlowTool {
+    pub name: String,
+    pub description: String,
+    pub flow_name: String,
+
+    /// Id of the `mcp_tool_input` agent.
+    pub agent_id: String,
+
+    /// Kind of the data the arguments are converted to. Empty: the kind of the value.
+    pub kind: String,
+
+    pub input_schema: Map<String, Value>,
+
+    // the schema is not of an object, so the arguments hold the value in `value`
+    wrapped: bool,
+}
+
+/// Tools of the enabled `mcp_tool_input` nodes of the flows, sorted by name.
+///
+/// The input schema is the schema of the kind of the node when it is registered with one,
+/// or else the schema of its configs.
+pub fn flow_tools(askit: &ASKit) -> Vec<FlowTool> {
+    let mut flows = askit.get_agent_flows().into_values().collect::<Vec<_>>();
+    flows.sort_by(|a, b| a.name().cmp(b.name()));
+
+    let mut tools: Vec<FlowTool> = Vec::new();
+    for flow in flows.iter() {
+        for node in flow.nodes() {
+            if node.def_name != MCP_TOOL_INPUT || !node.enabled {
+                continue;
+            }
+            let configs = node.configs.clone().unwrap_or_default();
+            let name = match configs.get_string_or_default(CONFIG_NAME) {
+                name if name.is_empty() => tool_name(flow.name()),
+                name => tool_name(&name),
+            };
+            // the first of the flows by name wins
+            if tools.iter().any(|tool| tool.name == name) {
+                continue;
+            }
+            let kind = configs.get_string_or_default(CONFIG_KIND);
+            let schema = askit
+                .get_kind_definition(&kind)
+                .and_then(|def| def.schema)
+                .or_else(|| configs.get_parsed::<Value>(CONFIG_SCHEMA).ok())
+                .filter(|schema| schema.as_object().is_some_and(|s| !s.is_empty()));
+            let (input_schema, wrapped) = input_schema(schema);
+            let description = configs.get_string_or_default(CONFIG_DESCRIPTION);
+            tools.push(FlowTool {
+                description: if description.is_empty() {
+                    format!("Runs the flow {}", flow.name())
+                } else {
+                    description
+                },
+                name,
+                flow_name: flow.name().to_string(),
+                agent_id: node.id.clone(),
+                kind,
+                input_schema,
+                wrapped,
+            });
+        }
+    }
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
+    tools
+}
+
+// MCP tool names are letters, digits, `_` and `-`
+fn tool_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Tool input schemas must be objects. Other schemas are wrapped in a `value` property.
+fn input_schema(schema: Option<Value>) -> (Map<String, Value>, bool) {
+    match schema {
+        None => (
+            Map::from_iter([("type".to_string(), json!("object"))]),
+            false,
+        ),
+        Some(Value::Object(schema)) if schema.get("type") == Some(&json!("object")) => {
+            (schema, false)
+        }
+        Some(schema) => {
+            let wrapped = json!({
+                "type": "object",
+                "properties": {"value": schema},
+                "required": ["value"],
+            });
+            (wrapped.as_object().cloned().unwrap_or_default(), true)
+        }
+    }
+}
+
+/// Runs the flow of the tool with the arguments, and waits for the data reaching its
+/// `mcp_tool_output` node.
+pub async fn call_flow_tool(
+    askit: &ASKit,
+    tool: &FlowTool,
+    arguments: Value,
+    timeout: Duration,
+) -> Result<AgentData, AgentError> {
+    let value = if tool.wrapped {
+        arguments.get("value").cloned().unwrap_or(Value::Null)
+    } else {
+        arguments
+    };
+    let data = if tool.kind.is_empty() {
+        AgentData::from_json(value)?
+    } else {
+        askit.data_from_json_with_kind(&tool.kind, value)?
+    };
+
+    let call_id = CALL_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let (tx, rx) = oneshot::channel();
+    PENDING_CALLS.lock().unwrap().insert(call_id, tx);
+    let ctx = AgentContext::new().with_var(VAR_MCP_CALL.to_string(), AgentValue::integer(call_id));
+
+    let result = match askit
+        .send_agent_out(tool.agent_id.clone(), ctx, PORT_ARGS.to_string(), data)
+        .await
+    {
+        Ok(()) => match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => Ok(result),
+            Ok(Err(_)) => Err(AgentError::Other(format!(
+                "Call of MCP tool {} was cancelled",
+                tool.name
+            ))),
+            Err(_) => Err(AgentError::DeadlineExceeded(tool.name.clone())),
+        },
+        Err(e) => Err(e),
+    };
+    PENDING_CALLS.lock().unwrap().remove(&call_id);
+    result
+}
+
+// Answers the call the context comes from. False when it is not from a call,
+// or the call has timed out.
+fn resolve_call(ctx: &AgentContext, data: AgentData) -> bool {
+    let Some(call_id) = ctx.get_var(VAR_MCP_CALL).and_then(|id| id.as_i64()) else {
+        return false;
+    };
+    let Some(tx) = PENDING_CALLS.lock().unwrap().remove(&call_id) else {
+        return false;
+    };
+    tx.send(data).is_ok()
+}
+
+fn tool_result(data: AgentData) -> CallToolResult {
+    match data.value.to_json() {
+        Value::String(s) => CallToolResult::success(vec![Content::text(s)]),
+        value @ Value::Object(_) => CallToolResult::structured(value),
+        value => CallToolResult::success(vec![Content::text(value.to_string())]),
+    }
+}
+
+/// MCP server of the tools of [`flow_tools`]. The flows must be running to be called.
+#[derive(Clone)]
+pub struct FlowToolServer {
+    askit: ASKit,
+    timeout: Duration,
+}
+
+impl FlowToolServer {
+    pub fn new(askit: ASKit) -> Self {
+        Self {
+            askit,
+            timeout: DEFAULT_CALL_TIMEOUT,
+        }
+    }
+
+    /// Time to wait for the answer of a flow.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Serves the tools on stdin and stdout until the client disconnects.
+    pub async fn serve_stdio(self) -> Result<(), AgentError> {
+        let service = self
+            .serve(rmcp::transport::stdio())
+            .await
+            .map_err(|e| AgentError::Other(format!("Failed to start MCP server: {e}")))?;
+        service
+            .waiting()
+            .await
+            .map_err(|e| AgentError::Other(format!("MCP server failed: {e}")))?;
+        Ok(())
+    }
+}
+
+impl ServerHandler for FlowToolServer {
+    fn get_info(&self) -> ServerInfo {
+        ServerInfo {
+            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            server_info: Implementation {
+                name: "askit".to_string(),
+                version: env!("CARGO_PKG_VERSION").to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    async fn list_tools(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListToolsResult, McpError> {
+        let tools = flow_tools(&self.askit)
+            .into_iter()
+            .map(|tool| Tool::new(tool.name, tool.description, Arc::new(tool.input_schema)))
+            .collect();
+        Ok(ListToolsResult::with_all_items(tools))
+    }
+
+    async fn call_tool(
+        &self,
+        request: CallToolRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        let Some(tool) = flow_tools(&self.askit)
+            .into_iter()
+            .find(|tool| tool.name == request.name)
+        else {
+            return Err(McpError::invalid_params(
+                format!("Unknown tool {}", request.name),
+                None,
+            ));
+        };
+        let arguments = Value::Object(request.arguments.unwrap_or_default());
+        match call_flow_tool(&self.askit, &tool, arguments, self.timeout).await {
+            Ok(data) => Ok(tool_result(data)),
+            // errors of the flow go back to the model
+            Err(e) => Ok(CallToolResult::error(vec![Content::text(e.to_string())])),
+        }
+    }
+}
+
+// MCP Tool Input Agent
+//
+// Outputs the arguments of the calls of its flow. It takes no input.
+pub struct McpToolInputAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for McpToolInputAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        _ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        Ok(())
+    }
+}
+
+// MCP Tool Output Agent
+//
+// Answers the call its input comes from.
+pub struct McpToolOutputAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for McpToolOutputAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        // data not from a call, or of a call timed out, is dropped
+        resolve_call(&ctx, data);
+        Ok(())
+    }
+}
+
+// call id -> sender of the answer
+static PENDING_CALLS: LazyLock<Mutex<HashMap<i64, oneshot::Sender<AgentData>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+static CALL_ID_COUNTER: AtomicI64 = AtomicI64::new(1);
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static MCP_TOOL_INPUT: &str = "mcp_tool_input";
+static MCP_TOOL_OUTPUT: &str = "mcp_tool_output";
+
+static PORT_ARGS: &str = "args";
+static PORT_RESULT: &str = "result";
+
+static CONFIG_NAME: &str = "name";
+static CONFIG_DESCRIPTION: &str = "description";
+static CONFIG_KIND: &str = "kind";
+static CONFIG_SCHEMA: &str = "schema";
+
+static VAR_MCP_CALL: &str = "$mcp_call";
+
+const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            MCP_TOOL_INPUT,
+            Some(new_agent_boxed::<McpToolInputAgent>),
+        )
+        .title("MCP Tool Input")
+        .description("Publishes the flow as an MCP tool, and outputs the arguments of its calls")
+        .category(CATEGORY)
+        .outputs(vec![PORT_ARGS])
+        .string_config_with(CONFIG_NAME, "", |entry| {
+            entry
+                .title("Name")
+                .description("Tool name. Empty: the flow name")
+        })
+        .text_config_with(CONFIG_DESCRIPTION, "", |entry| entry.title("Description"))
+        .string_config_with(CONFIG_KIND, "", |entry| {
+            entry
+                .title("Kind")
+                .description("Kind of the arguments, whose schema is the input schema")
+        })
+        .object_config_with(CONFIG_SCHEMA, AgentValue::object_default(), |entry| {
+            entry
+                .title("Schema")
+                .description("JSON Schema of the arguments, when the kind has none")
+        }),
+    );
+
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            MCP_TOOL_OUTPUT,
+            Some(new_agent_boxed::<McpToolOutputAgent>),
+        )
+        .title("MCP Tool Output")
+        .description("Answers the MCP tool call of the flow")
+        .category(CATEGORY)
+        .inputs(vec![PORT_RESULT]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_schema() {
+        let (schema, wrapped) = input_schema(None);
+        assert_eq!(Value::Object(schema), json!({"type": "object"}));
+        assert!(!wrapped);
+
+        let object = json!({"type": "object", "properties": {"q": {"type": "string"}}});
+        let (schema, wrapped) = input_schema(Some(object.clone()));
+        assert_eq!(Value::Object(schema), object);
+        assert!(!wrapped);
+
+        let (schema, wrapped) = input_schema(Some(json!({"type": "string"})));
+        assert_eq!(schema["properties"]["value"], json!({"type": "string"}));
+        assert!(wrapped);
+
+        assert_eq!(tool_name("my flow/search"), "my_flow_search");
+    }
+
+    #[test]
+    fn test_resolve_call() {
+        let (tx, mut rx) = oneshot::channel();
+        PENDING_CALLS.lock().unwrap().insert(-1, tx);
+
+        assert!(!resolve_call(&AgentContext::new(), AgentData::integer(1)));
+
+        let ctx = AgentContext::new().with_var(VAR_MCP_CALL.to_string(), AgentValue::integer(-1));
+        assert!(resolve_call(&ctx, AgentData::integer(2)));
+        assert_eq!(rx.try_recv().unwrap().as_i64(), Some(2));
+
+        // answered once
+        assert!(!resolve_call(&ctx, AgentData::integer(3)));
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 
-use agent_stream_kit::{AgentData, AgentError, AgentValue};
+use agent_stream_kit::{ASKit, AgentData, AgentError, AgentKindDefinition, AgentValue};
 use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "image")]
@@ -47,6 +47,22 @@ impl Message {
         self.image = Some(image);
         self
     }
+
+    /// Returns the image as a `data:image/png;base64,...` URL.
+    #[cfg(feature = "image")]
+    pub fn image_data_url(&self) -> Option<String> {
+        self.image.as_ref().map(|img| img.get_base64())
+    }
+}
+
+/// Decodes a base64 image, with or without a `data:image/...;base64,` prefix.
+#[cfg(feature = "image")]
+fn image_from_base64(s: &str) -> PhotonImage {
+    let base64 = match s.split_once(";base64,") {
+        Some((prefix, data)) if prefix.starts_with("data:") => data,
+        _ => s,
+    };
+    PhotonImage::new_from_base64(base64)
 }
 
 impl TryFrom<AgentData> for Message {
@@ -77,15 +93,17 @@ impl TryFrom<AgentValue> for Message {
                     .and_then(|r| r.as_str())
                     .unwrap_or("user")
                     .to_string();
-                let content = obj
-                    .get("content")
-                    .and_then(|c| c.as_str())
-                    .ok_or_else(|| {
-                        AgentError::InvalidValue(
+                let content = match obj.get("content").and_then(|c| c.as_str()) {
+                    Some(c) => c.to_string(),
+                    // image-only messages have no text content
+                    #[cfg(feature = "image")]
+                    None if obj.contains_key("image") => String::new(),
+                    None => {
+                        return Err(AgentError::InvalidValue(
                             "Message object missing 'content' field".to_string(),
-                        )
-                    })?
-                    .to_string();
+                        ));
+                    }
+                };
                 let id = obj
                     .get("id")
                     .and_then(|i| i.as_str())
@@ -98,9 +116,7 @@ impl TryFrom<AgentValue> for Message {
                     if let Some(image_value) = obj.get("image") {
                         match image_value {
                             AgentValue::String(s) => {
-                                message.image = Some(Arc::new(PhotonImage::new_from_base64(
-                                    s.trim_start_matches("data:image/png;base64,"),
-                                )));
+                                message.image = Some(Arc::new(image_from_base64(s)));
                             }
                             AgentValue::Image(img) => {
                                 message.image = Some(img.clone());
@@ -157,6 +173,59 @@ impl From<Message> for AgentValue {
     }
 }
 
+/// Collects the messages of a chat input.
+///
+/// The input can be a string, a single message, or an object with `history` and `message`.
+pub fn register_kinds(askit: &ASKit) {
+    askit.register_kind(
+        AgentKindDefinition::new("message")
+            .title("Message")
+            .description("A chat message with a role such as system, user or assistant")
+            .schema(serde_json::json!({
+                "type": "object",
+                "required": ["role", "content"],
+                "properties": {
+                    "role": {"type": "string"},
+                    "content": {"type": "string"},
+                    "id": {"type": "string"},
+                    "image": {"type": "string"},
+                },
+            })),
+    );
+}
+
+pub fn messages_from_data(data: &AgentData) -> Result<Vec<Message>, AgentError> {
+    let mut messages: Vec<Message> = Vec::new();
+
+    if data.is_string() {
+        let message = data.as_str().unwrap_or("");
+        if !message.is_empty() {
+            messages.push(Message::user(message.to_string()));
+        }
+    } else if data.is_object() {
+        let obj = data.as_object().unwrap();
+        if obj.contains_key("role") && obj.contains_key("content") {
+            let msg: Message = data.clone().try_into()?;
+            messages.push(msg);
+        } else {
+            if let Some(history_data) = obj.get("history")
+                && let Some(arr) = history_data.as_array()
+            {
+                for item in arr {
+                    let msg: Message = item.clone().try_into()?;
+                    messages.push(msg);
+                }
+            }
+            if let Some(msg_data) = obj.get("message") {
+                let msg: Message = msg_data.clone().try_into()?;
+                messages.push(msg);
+            }
+        }
+    }
+
+    Ok(messages)
+}
+
 #[derive(Clone, Default, Debug)]
 pub struct MessageHistory {
     messages: Vec<Message>,
@@ -324,6 +393,56 @@ mod tests {
         assert_eq!(msg.content, "Here is some information.");
     }
 
+    #[test]
+    fn test_messages_from_data() {
+        let messages = messages_from_data(&AgentData::string("Hi")).unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].role, "user");
+
+        let data = AgentData::object(
+            [
+                (
+                    "history".to_string(),
+                    AgentValue::array(vec![
+                        Message::user("Hi".to_string()).into(),
+                        Message::assistant("Hello".to_string()).into(),
+                    ]),
+                ),
+                ("message".to_string(), AgentValue::string("How are you?")),
+            ]
+            .into(),
+        );
+        let messages = messages_from_data(&data).unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1].role, "assistant");
+        assert_eq!(messages[2].content, "How are you?");
+
+        assert!(
+            messages_from_data(&AgentData::string(""))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn test_message_from_image_object_value() {
+        let img = PhotonImage::new(vec![255, 0, 0, 255], 1, 1);
+        let value = AgentValue::object(
+            [
+                ("role".to_string(), AgentValue::string("user")),
+                ("image".to_string(), AgentValue::string(img.get_base64())),
+            ]
+            .into(),
+        );
+        let msg: Message = value.try_into().unwrap();
+        assert_eq!(msg.content, "");
+        let image = msg.image.as_ref().unwrap();
+        assert_eq!(image.get_width(), 1);
+        assert_eq!(image.get_height(), 1);
+        assert_eq!(msg.image_data_url().unwrap(), img.get_base64());
+    }
+
     #[test]
     fn test_message_history_from_json() {
         let value: serde_json::Value = serde_json::json!([
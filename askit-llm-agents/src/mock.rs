@@ -0,0 +1,418 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentClock, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError,
+    AgentOutput, AgentUsage, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use futures::StreamExt;
+use serde::Deserialize;
+
+use crate::message::{Message, messages_from_data};
+use crate::provider::{
+    LlmChatResponse, LlmChatStream, LlmEmbeddings, LlmProvider, LlmRerank, LlmRerankResult,
+};
+
+/// Options of the mock provider, given as the `options` of a chat.
+///
+/// `{{input}}` in a response is replaced with the last user message and `{{model}}`
+/// with the model name. `responses` are returned in turn and take precedence over `response`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct MockOptions {
+    pub response: Option<String>,
+    pub responses: Vec<String>,
+    pub latency_ms: u64,
+    /// Characters per chunk of a stream. 0 streams the whole reply at once.
+    pub chunk_size: usize,
+}
+
+impl MockOptions {
+    fn from_value(options: Option<&serde_json::Value>) -> Result<Self, AgentError> {
+        let Some(options) = options else {
+            return Ok(Self::default());
+        };
+        serde_json::from_value(options.clone())
+            .map_err(|e| AgentError::InvalidValue(format!("Invalid mock options: {}", e)))
+    }
+}
+
+/// LLM provider replying with canned or templated responses, for testing flows
+/// without network or API keys. Latency is waited on the clock of ASKit.
+pub struct MockProvider {
+    clock: AgentClock,
+    calls: AtomicUsize,
+}
+
+impl MockProvider {
+    pub fn new(askit: &ASKit) -> Result<Self, AgentError> {
+        Ok(Self {
+            clock: askit.clock(),
+            calls: AtomicUsize::new(0),
+        })
+    }
+
+    fn reply(&self, model: &str, messages: &[Message], options: &MockOptions) -> String {
+        let call = self.calls.fetch_add(1, Ordering::Relaxed);
+        let template = if options.responses.is_empty() {
+            options.response.as_deref().unwrap_or(DEFAULT_RESPONSE)
+        } else {
+            &options.responses[call % options.responses.len()]
+        };
+        let input = messages
+            .iter()
+            .rev()
+            .find(|message| message.role == "user")
+            .map(|message| message.content.as_str())
+            .unwrap_or_default();
+        template
+            .replace("{{input}}", input)
+            .replace("{{model}}", model)
+    }
+
+    async fn wait(clock: &AgentClock, latency_ms: u64) {
+        if latency_ms > 0 {
+            clock.sleep(Duration::from_millis(latency_ms)).await;
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MockProvider {
+    fn name(&self) -> &str {
+        PROVIDER
+    }
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatResponse, AgentError> {
+        let options = MockOptions::from_value(options)?;
+        let content = self.reply(model, &messages, &options);
+        Self::wait(&self.clock, options.latency_ms).await;
+
+        let mut message = Message::assistant(content.clone());
+        message.id = Some(uuid::Uuid::new_v4().to_string());
+        Ok(LlmChatResponse {
+            message,
+            response: mock_response(model, &content, true)?,
+            usage: Some(mock_usage(model, &messages, &content)),
+        })
+    }
+
+    async fn chat_stream(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatStream, AgentError> {
+        let options = MockOptions::from_value(options)?;
+        let content = self.reply(model, &messages, &options);
+        let chars = content.chars().collect::<Vec<_>>();
+        let chunks = if options.chunk_size == 0 || chars.is_empty() {
+            vec![content.clone()]
+        } else {
+            chars
+                .chunks(options.chunk_size)
+                .map(|chunk| chunk.iter().collect::<String>())
+                .collect()
+        };
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let model = model.to_string();
+        let usage = mock_usage(&model, &messages, &content);
+        let clock = self.clock.clone();
+        let latency_ms = options.latency_ms;
+        let last = chunks.len() - 1;
+        Ok(futures::stream::iter(chunks.into_iter().enumerate())
+            .then(move |(i, chunk)| {
+                let clock = clock.clone();
+                let id = id.clone();
+                let model = model.clone();
+                let usage = usage.clone();
+                async move {
+                    Self::wait(&clock, latency_ms).await;
+                    let done = i == last;
+                    let mut message = Message::assistant(chunk.clone());
+                    message.id = Some(id);
+                    Ok(LlmChatResponse {
+                        message,
+                        response: mock_response(&model, &chunk, done)?,
+                        usage: done.then_some(usage),
+                    })
+                }
+            })
+            .boxed())
+    }
+
+    async fn embed(
+        &self,
+        model: &str,
+        inputs: Vec<String>,
+        _options: Option<&serde_json::Value>,
+    ) -> Result<LlmEmbeddings, AgentError> {
+        let input_tokens = inputs.iter().map(|input| count_tokens(input)).sum();
+        Ok(LlmEmbeddings {
+            embeddings: inputs.iter().map(|input| mock_embedding(input)).collect(),
+            usage: Some(AgentUsage::new(PROVIDER, model, input_tokens, 0)),
+        })
+    }
+
+    async fn rerank(
+        &self,
+        model: &str,
+        query: &str,
+        documents: Vec<String>,
+        _options: Option<&serde_json::Value>,
+    ) -> Result<LlmRerank, AgentError> {
+        let input_tokens =
+            count_tokens(query) + documents.iter().map(|doc| count_tokens(doc)).sum::<u64>();
+        let mut results = documents
+            .iter()
+            .enumerate()
+            .map(|(index, doc)| LlmRerankResult {
+                index,
+                score: mock_relevance(query, doc),
+            })
+            .collect::<Vec<_>>();
+        results.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(LlmRerank {
+            results,
+            usage: Some(AgentUsage::new(PROVIDER, model, input_tokens, 0)),
+        })
+    }
+}
+
+// Shaped like the chat responses of Ollama
+fn mock_response(model: &str, content: &str, done: bool) -> Result<AgentData, AgentError> {
+    AgentData::from_json(serde_json::json!({
+        "model": model,
+        "message": {
+            "role": "assistant",
+            "content": content,
+        },
+        "done": done,
+    }))
+}
+
+fn mock_usage(model: &str, messages: &[Message], content: &str) -> AgentUsage {
+    let input_tokens = messages
+        .iter()
+        .map(|message| count_tokens(&message.content))
+        .sum();
+    AgentUsage::new(PROVIDER, model, input_tokens, count_tokens(content))
+}
+
+fn count_tokens(text: &str) -> u64 {
+    text.split_whitespace().count() as u64
+}
+
+// Deterministic unit vector derived from the bytes of the input
+fn mock_embedding(input: &str) -> Vec<f32> {
+    let mut embedding = vec![0.0f32; EMBEDDING_DIM];
+    for (i, byte) in input.bytes().enumerate() {
+        embedding[i % EMBEDDING_DIM] += byte as f32;
+    }
+    let norm = embedding.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|x| *x /= norm);
+    }
+    embedding
+}
+
+// Share of the words of the query found in the document
+fn mock_relevance(query: &str, document: &str) -> f64 {
+    let words = |text: &str| {
+        text.split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect::<std::collections::HashSet<_>>()
+    };
+    let query = words(query);
+    if query.is_empty() {
+        return 0.0;
+    }
+    let document = words(document);
+    query.intersection(&document).count() as f64 / query.len() as f64
+}
+
+// Mock Chat Agent
+pub struct MockChatAgent {
+    data: AsAgentData,
+    provider: MockProvider,
+}
+
+#[async_trait]
+impl AsAgent for MockChatAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        let provider = MockProvider::new(&askit)?;
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            provider,
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let messages = messages_from_data(&data)?;
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let configs = self.configs()?;
+        let model = configs.get_string_or(CONFIG_MODEL, PROVIDER);
+        let options = serde_json::json!({
+            "response": configs.get_string_or(CONFIG_RESPONSE, DEFAULT_RESPONSE),
+            "latency_ms": configs.get_integer_or_default(CONFIG_LATENCY_MS).max(0),
+            "chunk_size": configs.get_integer_or_default(CONFIG_CHUNK_SIZE).max(0),
+        });
+
+        if configs.get_bool_or_default(CONFIG_STREAM) {
+            let mut stream = self
+                .provider
+                .chat_stream(&model, messages, Some(&options))
+                .await?;
+            let mut content = String::new();
+            while let Some(res) = stream.next().await {
+                let res = res?;
+                if let Some(usage) = res.usage {
+                    self.emit_usage(usage);
+                }
+
+                content.push_str(&res.message.content);
+
+                let mut message = Message::assistant(content.clone());
+                message.id = res.message.id;
+                self.try_output(ctx.clone(), PORT_MESSAGE, message.into())?;
+
+                self.try_output(ctx.clone(), PORT_RESPONSE, res.response)?;
+            }
+        } else {
+            let res = self.provider.chat(&model, messages, Some(&options)).await?;
+            if let Some(usage) = res.usage {
+                self.emit_usage(usage);
+            }
+
+            self.try_output(ctx.clone(), PORT_MESSAGE, res.message.into())?;
+            self.try_output(ctx, PORT_RESPONSE, res.response)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) static PROVIDER: &str = "mock";
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static PORT_MESSAGE: &str = "message";
+static PORT_RESPONSE: &str = "response";
+
+static CONFIG_MODEL: &str = "model";
+static CONFIG_RESPONSE: &str = "response";
+static CONFIG_LATENCY_MS: &str = "latency_ms";
+static CONFIG_STREAM: &str = "stream";
+static CONFIG_CHUNK_SIZE: &str = "chunk_size";
+
+const DEFAULT_RESPONSE: &str = "{{input}}";
+const EMBEDDING_DIM: usize = 8;
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_mock",
+            Some(new_agent_boxed::<MockChatAgent>),
+        )
+        .title("Mock LLM")
+        .description("Replies with a canned or templated response, for testing flows")
+        .category(CATEGORY)
+        .inputs(vec![PORT_MESSAGE])
+        .outputs(vec![PORT_MESSAGE, PORT_RESPONSE])
+        .string_config_with(CONFIG_MODEL, PROVIDER, |entry| entry.title("Model"))
+        .text_config_with(CONFIG_RESPONSE, DEFAULT_RESPONSE, |entry| {
+            entry
+                .title("Response")
+                .description("{{input}} = last user message, {{model}} = model")
+        })
+        .integer_config_with(CONFIG_LATENCY_MS, 0, |entry| entry.title("Latency (ms)"))
+        .boolean_config_with(CONFIG_STREAM, false, |entry| entry.title("Stream"))
+        .integer_config_with(CONFIG_CHUNK_SIZE, 8, |entry| {
+            entry
+                .title("Chunk Size")
+                .description("characters per streamed chunk")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_chat() {
+        let provider = MockProvider::new(&ASKit::new()).unwrap();
+        let messages = vec![Message::user("hello world".to_string())];
+        let options = serde_json::json!({"response": "{{model}} says {{input}}"});
+        let res = futures::executor::block_on(provider.chat("m", messages.clone(), Some(&options)))
+            .unwrap();
+        assert_eq!(res.message.content, "m says hello world");
+        let usage = res.usage.unwrap();
+        assert_eq!((usage.input_tokens, usage.output_tokens), (2, 4));
+
+        // canned responses in turn
+        let provider = MockProvider::new(&ASKit::new()).unwrap();
+        let options = serde_json::json!({"responses": ["a", "b"]});
+        let contents = (0..3)
+            .map(|_| {
+                futures::executor::block_on(provider.chat("m", messages.clone(), Some(&options)))
+                    .unwrap()
+                    .message
+                    .content
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["a", "b", "a"]);
+    }
+
+    #[test]
+    fn test_mock_chat_stream() {
+        let provider = MockProvider::new(&ASKit::new()).unwrap();
+        let messages = vec![Message::user("abcde".to_string())];
+        let options = serde_json::json!({"chunk_size": 2});
+        let chunks = futures::executor::block_on(async {
+            let stream = provider
+                .chat_stream("m", messages, Some(&options))
+                .await
+                .unwrap();
+            stream.collect::<Vec<_>>().await
+        });
+        let contents = chunks
+            .iter()
+            .map(|res| res.as_ref().unwrap().message.content.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["ab", "cd", "e"]);
+        assert!(chunks[2].as_ref().unwrap().usage.is_some());
+        assert!(chunks[0].as_ref().unwrap().usage.is_none());
+    }
+}
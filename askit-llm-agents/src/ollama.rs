@@ -4,10 +4,11 @@ use std::sync::{Arc, Mutex};
 use std::vec;
 
 use agent_stream_kit::{
-    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
-    AsAgent, AsAgentData, async_trait, new_agent_boxed,
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentUsage, AsAgent, AsAgentData, async_trait, new_agent_boxed,
 };
 
+use futures::StreamExt;
 use ollama_rs::{
     Ollama,
     generation::{
@@ -18,9 +19,18 @@ use ollama_rs::{
     history::ChatHistory,
     models::ModelOptions,
 };
-use tokio_stream::StreamExt;
 
-use crate::message::{Message, MessageHistory};
+use crate::common::{
+    CONFIG_CACHE, CONFIG_CACHE_MAX_SIZE, CONFIG_CACHE_TTL, CONFIG_MAX_TOKENS, CONFIG_TEMPERATURE,
+    DEFAULT_CACHE_MAX_SIZE, DEFAULT_CACHE_TTL, ResponseCache, ResponseCacheLookup,
+    apply_configured_options, configured_options,
+};
+use crate::embedding_cache::EmbeddingCache;
+use crate::message::{Message, MessageHistory, messages_from_data};
+use crate::provider::{
+    LlmChatResponse, LlmChatStream, LlmEmbeddings, LlmProvider, LlmProviderCache,
+    options_from_configs, output_chat,
+};
 
 // Shared client management for Ollama agents
 struct OllamaManager {
@@ -34,17 +44,17 @@ impl OllamaManager {
         }
     }
 
-    fn get_ollama_url(global_config: Option<AgentConfigs>) -> String {
-        if let Some(ollama_url) =
-            global_config.and_then(|cfg| cfg.get_string(CONFIG_OLLAMA_URL).ok())
+    fn get_ollama_url(askit: &ASKit) -> String {
+        if let Some(ollama_url) = askit
+            .get_global_configs("ollama_completion")
+            .and_then(|cfg| cfg.get_string(CONFIG_OLLAMA_URL).ok())
+            && !ollama_url.is_empty()
         {
-            if !ollama_url.is_empty() {
-                return ollama_url;
-            }
+            return ollama_url;
         }
-        if let Ok(ollama_api_base_url) = std::env::var("OLLAMA_API_BASE_URL") {
+        if let Some(ollama_api_base_url) = askit.host_env_var("OLLAMA_API_BASE_URL") {
             return ollama_api_base_url;
-        } else if let Ok(ollama_host) = std::env::var("OLLAMA_HOST") {
+        } else if let Some(ollama_host) = askit.host_env_var("OLLAMA_HOST") {
             return format!("http://{}:11434", ollama_host);
         }
         DEFAULT_OLLAMA_URL.to_string()
@@ -57,8 +67,7 @@ impl OllamaManager {
             return Ok(client.clone());
         }
 
-        let global_config = askit.get_global_configs("ollama_completion");
-        let api_base_url = Self::get_ollama_url(global_config);
+        let api_base_url = Self::get_ollama_url(askit);
         let new_client = Ollama::try_new(api_base_url)
             .map_err(|e| AgentError::IoError(format!("Ollama Client Error: {}", e)))?;
         *client_guard = Some(new_client.clone());
@@ -118,28 +127,57 @@ impl AsAgent for OllamaCompletionAgent {
             request = request.system(config_system);
         }
 
-        let config_options = self.configs()?.get_string_or_default(CONFIG_OPTIONS);
-        if !config_options.is_empty() && config_options != "{}" {
-            if let Ok(options_json) = serde_json::from_str::<ModelOptions>(&config_options) {
-                request = request.options(options_json);
-            } else {
-                return Err(AgentError::InvalidValue(
-                    "Invalid JSON in options".to_string(),
-                ));
+        request = request.options(apply_configured_options(
+            ModelOptions::default(),
+            self.configs()?,
+            "num_predict",
+        )?);
+
+        let cache_slot = match ResponseCache::global().lookup(
+            self.configs()?,
+            PROVIDER,
+            config_model,
+            &request,
+        )? {
+            ResponseCacheLookup::Hit(outputs) => {
+                for (port, data) in outputs {
+                    self.try_output(ctx.clone(), port, data)?;
+                }
+                return Ok(());
             }
-        }
+            ResponseCacheLookup::Miss(slot) => Some(slot),
+            ResponseCacheLookup::Disabled => None,
+        };
 
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
         let client = self.manager.get_client(self.askit())?;
         let res = client
             .generate(request)
             .await
             .map_err(|e| AgentError::IoError(format!("Ollama Error: {}", e)))?;
 
-        let message = Message::assistant(res.response.clone());
-        self.try_output(ctx.clone(), PORT_MESSAGE, message.into())?;
+        if let (Some(input_tokens), Some(output_tokens)) = (res.prompt_eval_count, res.eval_count) {
+            self.emit_usage(AgentUsage::new(
+                PROVIDER,
+                config_model,
+                input_tokens,
+                output_tokens,
+            ));
+        }
+
+        let message: AgentData = Message::assistant(res.response.clone()).into();
+        self.try_output(ctx.clone(), PORT_MESSAGE, message.clone())?;
 
         let out_response = AgentData::from_serialize(&res)?;
-        self.try_output(ctx, PORT_RESPONSE, out_response)?;
+        self.try_output(ctx, PORT_RESPONSE, out_response.clone())?;
+
+        if let Some(slot) = cache_slot {
+            slot.store(vec![
+                (PORT_MESSAGE.to_string(), message),
+                (PORT_RESPONSE.to_string(), out_response.clone()),
+            ]);
+        }
 
         Ok(())
     }
@@ -148,7 +186,7 @@ impl AsAgent for OllamaCompletionAgent {
 // Ollama Chat Agent
 pub struct OllamaChatAgent {
     data: AsAgentData,
-    manager: OllamaManager,
+    providers: LlmProviderCache,
 }
 
 #[async_trait]
@@ -161,7 +199,7 @@ impl AsAgent for OllamaChatAgent {
     ) -> Result<Self, AgentError> {
         Ok(Self {
             data: AsAgentData::new(askit, id, def_name, config),
-            manager: OllamaManager::new(),
+            providers: LlmProviderCache::default(),
         })
     }
 
@@ -184,96 +222,46 @@ impl AsAgent for OllamaChatAgent {
             return Ok(());
         }
 
-        let mut messages: Vec<Message> = Vec::new();
-
-        if data.is_string() {
-            let message = data.as_str().unwrap_or("");
-            if message.is_empty() {
-                return Ok(());
-            }
-            messages.push(Message::user(message.to_string()));
-        } else if data.is_object() {
-            let obj = data.as_object().unwrap();
-            if obj.contains_key("role") && obj.contains_key("content") {
-                let msg: Message = data.clone().try_into()?;
-                messages.push(msg);
-            } else {
-                if obj.contains_key("history") {
-                    let history_data = obj.get("history").unwrap();
-                    if history_data.is_array() {
-                        let arr = history_data.as_array().unwrap();
-                        for item in arr {
-                            let msg: Message = item.clone().try_into()?;
-                            messages.push(msg);
-                        }
-                    }
-                }
-                if obj.contains_key("message") {
-                    let msg_data = obj.get("message").unwrap();
-                    let msg: Message = msg_data.clone().try_into()?;
-                    messages.push(msg);
-                }
-            }
-        }
-
+        let messages = messages_from_data(&data)?;
         if messages.is_empty() {
             return Ok(());
         }
 
-        let client = self.manager.get_client(self.askit())?;
-        let mut request = ChatMessageRequest::new(
-            config_model.to_string(),
-            messages.into_iter().map(|m| m.into()).collect(),
-        );
-
-        let config_options = self.configs()?.get_string_or_default(CONFIG_OPTIONS);
-        if !config_options.is_empty() && config_options != "{}" {
-            if let Ok(options_json) = serde_json::from_str::<ModelOptions>(&config_options) {
-                request = request.options(options_json);
-            } else {
-                return Err(AgentError::InvalidValue(
-                    "Invalid JSON in options".to_string(),
-                ));
-            }
-        }
-
-        let id = uuid::Uuid::new_v4().to_string();
+        let options = configured_options(self.configs()?, "num_predict")?;
         let use_stream = self.configs()?.get_bool_or_default(CONFIG_STREAM);
-        if use_stream {
-            let mut stream = client
-                .send_chat_messages_stream(request)
-                .await
-                .map_err(|e| AgentError::IoError(format!("Ollama Error: {}", e)))?;
 
-            let mut content = String::new();
-            while let Some(res) = stream.next().await {
-                let res = res.map_err(|_| AgentError::IoError(format!("Ollama Stream Error")))?;
-
-                content.push_str(&res.message.content);
-
-                let mut message = Message::assistant(content.clone());
-                message.id = Some(id.clone());
-                self.try_output(ctx.clone(), PORT_MESSAGE, message.into())?;
-
-                let out_response = AgentData::from_serialize(&res)?;
-                self.try_output(ctx.clone(), PORT_RESPONSE, out_response)?;
-
-                if res.done {
-                    break;
+        let cache_slot = match ResponseCache::global().lookup(
+            self.configs()?,
+            PROVIDER,
+            config_model,
+            &(&messages, &options),
+        )? {
+            ResponseCacheLookup::Hit(outputs) => {
+                for (port, data) in outputs {
+                    self.try_output(ctx.clone(), port, data)?;
                 }
+                return Ok(());
             }
-        } else {
-            let res = client
-                .send_chat_messages(request)
-                .await
-                .map_err(|e| AgentError::IoError(format!("Ollama Error: {}", e)))?;
-
-            let mut message: Message = res.message.clone().into();
-            message.id = Some(id.clone());
-            self.try_output(ctx.clone(), PORT_MESSAGE, message.into())?;
+            ResponseCacheLookup::Miss(slot) => Some(slot),
+            ResponseCacheLookup::Disabled => None,
+        };
 
-            let out_response = AgentData::from_serialize(&res)?;
-            self.try_output(ctx.clone(), PORT_RESPONSE, out_response)?;
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
+        let provider = self.providers.get(&self.data.askit, PROVIDER)?;
+
+        let outputs = output_chat(
+            self,
+            ctx,
+            provider.as_ref(),
+            config_model,
+            messages,
+            Some(&options),
+            use_stream,
+        )
+        .await?;
+        if let Some(slot) = cache_slot {
+            slot.store(outputs);
         }
 
         Ok(())
@@ -283,7 +271,7 @@ impl AsAgent for OllamaChatAgent {
 // Ollama Embeddings Agent
 pub struct OllamaEmbeddingsAgent {
     data: AsAgentData,
-    manager: OllamaManager,
+    providers: LlmProviderCache,
 }
 
 #[async_trait]
@@ -296,7 +284,7 @@ impl AsAgent for OllamaEmbeddingsAgent {
     ) -> Result<Self, AgentError> {
         Ok(Self {
             data: AsAgentData::new(askit, id, def_name, config),
-            manager: OllamaManager::new(),
+            providers: LlmProviderCache::default(),
         })
     }
 
@@ -324,29 +312,186 @@ impl AsAgent for OllamaEmbeddingsAgent {
             return Ok(());
         }
 
-        let client = self.manager.get_client(self.askit())?;
-        let mut request = GenerateEmbeddingsRequest::new(config_model.to_string(), input.into());
-
+        self.check_capability(AgentCapability::Network)?;
         let config_options = self.configs()?.get_string_or_default(CONFIG_OPTIONS);
-        if !config_options.is_empty() && config_options != "{}" {
-            if let Ok(options_json) = serde_json::from_str::<ModelOptions>(&config_options) {
-                request = request.options(options_json);
-            } else {
-                return Err(AgentError::InvalidValue(
-                    "Invalid JSON in options".to_string(),
-                ));
+        let inputs = vec![input.to_string()];
+        let lookup = if self.configs()?.get_bool_or_default(CONFIG_CACHE) {
+            Some(EmbeddingCache::global().lookup(
+                PROVIDER,
+                config_model,
+                &config_options,
+                &inputs,
+            )?)
+        } else {
+            None
+        };
+        let requested = match &lookup {
+            Some(lookup) => lookup.missing(&inputs),
+            None => inputs,
+        };
+
+        let mut embeddings = Vec::new();
+        if !requested.is_empty() {
+            let options = options_from_configs(self.configs()?)?;
+            let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
+            let provider = self.providers.get(&self.data.askit, PROVIDER)?;
+            let res = provider
+                .embed(config_model, requested, options.as_ref())
+                .await?;
+            if let Some(usage) = res.usage {
+                self.emit_usage(usage);
             }
+            embeddings = res.embeddings;
+        }
+        if let Some(lookup) = lookup {
+            embeddings = lookup.merge(embeddings)?;
         }
 
-        let res = client
-            .generate_embeddings(request)
+        let embeddings = AgentData::from_serialize(&embeddings)?;
+        self.try_output(ctx.clone(), PORT_EMBEDDINGS, embeddings)?;
+
+        Ok(())
+    }
+}
+
+// LLM provider backed by Ollama
+pub struct OllamaProvider {
+    client: Ollama,
+}
+
+impl OllamaProvider {
+    pub fn new(askit: &ASKit) -> Result<Self, AgentError> {
+        let client = OllamaManager::new().get_client(askit)?;
+        Ok(Self { client })
+    }
+
+    fn model_options(
+        options: Option<&serde_json::Value>,
+    ) -> Result<Option<ModelOptions>, AgentError> {
+        options
+            .map(|options_json| {
+                serde_json::from_value::<ModelOptions>(options_json.clone()).map_err(|e| {
+                    AgentError::InvalidValue(format!("Invalid JSON in options: {}", e))
+                })
+            })
+            .transpose()
+    }
+
+    fn chat_request(
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<ChatMessageRequest, AgentError> {
+        let mut request = ChatMessageRequest::new(
+            model.to_string(),
+            messages.into_iter().map(|m| m.into()).collect(),
+        );
+        if let Some(options) = Self::model_options(options)? {
+            request = request.options(options);
+        }
+        Ok(request)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OllamaProvider {
+    fn name(&self) -> &str {
+        PROVIDER
+    }
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatResponse, AgentError> {
+        let request = Self::chat_request(model, messages, options)?;
+        let res = self
+            .client
+            .send_chat_messages(request)
             .await
             .map_err(|e| AgentError::IoError(format!("Ollama Error: {}", e)))?;
 
-        let embeddings = AgentData::from_serialize(&res.embeddings)?;
-        self.try_output(ctx.clone(), PORT_EMBEDDINGS, embeddings)?;
+        let usage = res.final_data.as_ref().map(|final_data| {
+            AgentUsage::new(
+                PROVIDER,
+                model,
+                final_data.prompt_eval_count,
+                final_data.eval_count,
+            )
+        });
+
+        let mut message: Message = res.message.clone().into();
+        message.id = Some(uuid::Uuid::new_v4().to_string());
+
+        Ok(LlmChatResponse {
+            message,
+            response: AgentData::from_serialize(&res)?,
+            usage,
+        })
+    }
 
-        Ok(())
+    async fn chat_stream(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatStream, AgentError> {
+        let request = Self::chat_request(model, messages, options)?;
+        let stream = self
+            .client
+            .send_chat_messages_stream(request)
+            .await
+            .map_err(|e| AgentError::IoError(format!("Ollama Error: {}", e)))?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let model = model.to_string();
+        Ok(stream
+            .map(move |res| {
+                let res =
+                    res.map_err(|_| AgentError::IoError("Ollama Stream Error".to_string()))?;
+                let usage = res.final_data.as_ref().map(|final_data| {
+                    AgentUsage::new(
+                        PROVIDER,
+                        model.as_str(),
+                        final_data.prompt_eval_count,
+                        final_data.eval_count,
+                    )
+                });
+
+                let mut message = Message::assistant(res.message.content.clone());
+                message.id = Some(id.clone());
+
+                Ok(LlmChatResponse {
+                    message,
+                    response: AgentData::from_serialize(&res)?,
+                    usage,
+                })
+            })
+            .boxed())
+    }
+
+    async fn embed(
+        &self,
+        model: &str,
+        inputs: Vec<String>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmEmbeddings, AgentError> {
+        let mut request = GenerateEmbeddingsRequest::new(model.to_string(), inputs.into());
+        if let Some(options) = Self::model_options(options)? {
+            request = request.options(options);
+        }
+
+        let res = self
+            .client
+            .generate_embeddings(request)
+            .await
+            .map_err(|e| AgentError::IoError(format!("Ollama Error: {}", e)))?;
+
+        Ok(LlmEmbeddings {
+            embeddings: res.embeddings,
+            usage: None,
+        })
     }
 }
 
@@ -402,6 +547,7 @@ impl ChatHistory for MessageHistory {
 }
 
 static AGENT_KIND: &str = "agent";
+static PROVIDER: &str = "ollama";
 static CATEGORY: &str = "LLM";
 
 static PORT_EMBEDDINGS: &str = "embeddings";
@@ -430,14 +576,28 @@ pub fn register_agents(askit: &ASKit) {
         .category(CATEGORY)
         .inputs(vec![PORT_MESSAGE])
         .outputs(vec![PORT_MESSAGE, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Network])
         .string_global_config_with(CONFIG_OLLAMA_URL, DEFAULT_OLLAMA_URL, |entry| {
             entry.title("Ollama URL")
         })
         .string_config_with(CONFIG_MODEL, DEFAULT_CONFIG_MODEL, |entry| {
-            entry.title("Model")
+            entry.title("Model").required()
         })
         .text_config_with(CONFIG_SYSTEM, "", |entry| entry.title("System"))
-        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+        .custom_config_with(CONFIG_TEMPERATURE, (), "number", |entry| {
+            entry.title("Temperature")
+        })
+        .custom_config_with(CONFIG_MAX_TOKENS, (), "integer", |entry| {
+            entry.title("Max Tokens")
+        })
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options"))
+        .boolean_config_with(CONFIG_CACHE, false, |entry| entry.title("Cache"))
+        .integer_config_with(CONFIG_CACHE_TTL, DEFAULT_CACHE_TTL, |entry| {
+            entry.title("Cache TTL (sec)")
+        })
+        .integer_config_with(CONFIG_CACHE_MAX_SIZE, DEFAULT_CACHE_MAX_SIZE, |entry| {
+            entry.title("Cache Max Size")
+        }),
     );
 
     askit.register_agent(
@@ -451,11 +611,25 @@ pub fn register_agents(askit: &ASKit) {
         .category(CATEGORY)
         .inputs(vec![PORT_MESSAGE])
         .outputs(vec![PORT_MESSAGE, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Network])
         .string_config_with(CONFIG_MODEL, DEFAULT_CONFIG_MODEL, |entry| {
-            entry.title("Model")
+            entry.title("Model").required()
         })
         .boolean_config_with(CONFIG_STREAM, false, |entry| entry.title("Stream"))
-        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+        .custom_config_with(CONFIG_TEMPERATURE, (), "number", |entry| {
+            entry.title("Temperature")
+        })
+        .custom_config_with(CONFIG_MAX_TOKENS, (), "integer", |entry| {
+            entry.title("Max Tokens")
+        })
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options"))
+        .boolean_config_with(CONFIG_CACHE, false, |entry| entry.title("Cache"))
+        .integer_config_with(CONFIG_CACHE_TTL, DEFAULT_CACHE_TTL, |entry| {
+            entry.title("Cache TTL (sec)")
+        })
+        .integer_config_with(CONFIG_CACHE_MAX_SIZE, DEFAULT_CACHE_MAX_SIZE, |entry| {
+            entry.title("Cache Max Size")
+        }),
     );
 
     askit.register_agent(
@@ -469,9 +643,15 @@ pub fn register_agents(askit: &ASKit) {
         .category(CATEGORY)
         .inputs(vec![PORT_INPUT])
         .outputs(vec![PORT_EMBEDDINGS])
+        .capabilities(vec![AgentCapability::Network])
         .string_config_with(CONFIG_MODEL, DEFAULT_CONFIG_MODEL, |entry| {
-            entry.title("Model")
+            entry.title("Model").required()
         })
-        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options"))
+        .boolean_config_with(CONFIG_CACHE, false, |entry| {
+            entry
+                .title("Cache")
+                .description("Reuses the embeddings of texts already embedded")
+        }),
     );
 }
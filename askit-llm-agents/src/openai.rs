@@ -4,24 +4,43 @@ use std::sync::{Arc, Mutex};
 use std::vec;
 
 use agent_stream_kit::{
-    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
-    AsAgent, AsAgentData, async_trait, new_agent_boxed,
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentUsage, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+#[cfg(feature = "image")]
+use async_openai::types::{
+    CreateImageRequestArgs, Image, ImageModel, ImageQuality, ImageResponseFormat, ImageSize,
 };
 use async_openai::{
     Client,
-    config::OpenAIConfig,
+    config::{Config, OpenAIConfig},
     types::{
-        ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
+        AudioInput, ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
         ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestToolMessageArgs,
-        ChatCompletionRequestUserMessageArgs, ChatCompletionResponseMessage,
-        CreateChatCompletionRequest, CreateChatCompletionRequestArgs, CreateCompletionRequest,
-        CreateCompletionRequestArgs, CreateEmbeddingRequest, CreateEmbeddingRequestArgs, Role,
-        responses::{self, CreateResponse, CreateResponseArgs, OutputContent, OutputMessage},
+        ChatCompletionRequestUserMessageArgs, ChatCompletionRequestUserMessageContent,
+        ChatCompletionResponseMessage, ChatCompletionStreamOptions, CreateChatCompletionRequest,
+        CreateChatCompletionRequestArgs, CreateCompletionRequestArgs, CreateEmbeddingRequestArgs,
+        CreateSpeechRequestArgs, CreateTranscriptionRequestArgs, Embedding, Role, SpeechModel,
+        SpeechResponseFormat, Voice,
+        responses::{self, CreateResponseArgs, OutputContent, OutputMessage},
     },
 };
+use base64::{Engine, prelude::BASE64_STANDARD};
 use futures::StreamExt;
+#[cfg(feature = "image")]
+use photon_rs::PhotonImage;
 
-use crate::message::Message;
+use crate::common::{
+    CONFIG_CACHE, CONFIG_CACHE_MAX_SIZE, CONFIG_CACHE_TTL, CONFIG_MAX_TOKENS, CONFIG_TEMPERATURE,
+    DEFAULT_CACHE_MAX_SIZE, DEFAULT_CACHE_TTL, ResponseCache, ResponseCacheLookup,
+    apply_configured_options, configured_options, merge_options,
+};
+use crate::embedding_cache::EmbeddingCache;
+use crate::message::{Message, messages_from_data};
+use crate::provider::{
+    LlmChatResponse, LlmChatStream, LlmEmbeddings, LlmProvider, LlmProviderCache, LlmRerank,
+    LlmRerankResult, options_from_configs, output_chat,
+};
 
 // Shared client management for OpenAI agents
 struct OpenAIManager {
@@ -42,16 +61,15 @@ impl OpenAIManager {
             return Ok(client.clone());
         }
 
-        let mut new_client = Client::new();
-
-        if let Some(api_key) = askit
+        // OpenAIConfig::new() would read OPENAI_API_KEY whatever the host policy is
+        let api_key = askit
             .get_global_configs("openai_chat")
             .and_then(|cfg| cfg.get_string(CONFIG_OPENAI_API_KEY).ok())
             .filter(|key| !key.is_empty())
-        {
-            let config = OpenAIConfig::new().with_api_key(&api_key);
-            new_client = Client::with_config(config);
-        }
+            .or_else(|| askit.host_env_var(OPENAI_API_KEY_ENV))
+            .unwrap_or_default();
+        let config = OpenAIConfig::new().with_api_key(&api_key);
+        let new_client = Client::with_config(config);
 
         *client_guard = Some(new_client.clone());
 
@@ -133,26 +151,26 @@ impl AsAgent for OpenAICompletionAgent {
             .build()
             .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
 
-        let config_options = self.configs()?.get_string_or_default(CONFIG_OPTIONS);
-        if !config_options.is_empty() && config_options != "{}" {
-            // Merge options into request
-            let options_json = serde_json::from_str::<serde_json::Value>(&config_options)
-                .map_err(|e| AgentError::InvalidValue(format!("Invalid JSON in options: {}", e)))?;
-
-            let mut request_json = serde_json::to_value(&request)
-                .map_err(|e| AgentError::InvalidValue(format!("Serialization error: {}", e)))?;
-
-            if let (Some(request_obj), Some(options_obj)) =
-                (request_json.as_object_mut(), options_json.as_object())
-            {
-                for (key, value) in options_obj {
-                    request_obj.insert(key.clone(), value.clone());
+        request = apply_configured_options(request, self.configs()?, "max_tokens")?;
+
+        let cache_slot = match ResponseCache::global().lookup(
+            self.configs()?,
+            PROVIDER,
+            config_model,
+            &request,
+        )? {
+            ResponseCacheLookup::Hit(outputs) => {
+                for (port, data) in outputs {
+                    self.try_output(ctx.clone(), port, data)?;
                 }
+                return Ok(());
             }
-            request = serde_json::from_value::<CreateCompletionRequest>(request_json)
-                .map_err(|e| AgentError::InvalidValue(format!("Deserialization error: {}", e)))?;
-        }
+            ResponseCacheLookup::Miss(slot) => Some(slot),
+            ResponseCacheLookup::Disabled => None,
+        };
 
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
         let client = self.manager.get_client(self.askit())?;
         let res = client
             .completions()
@@ -160,11 +178,27 @@ impl AsAgent for OpenAICompletionAgent {
             .await
             .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
 
-        let message = Message::assistant(res.choices[0].text.clone());
-        self.try_output(ctx.clone(), PORT_MESSAGE, message.into())?;
+        if let Some(usage) = &res.usage {
+            self.emit_usage(AgentUsage::new(
+                PROVIDER,
+                config_model,
+                usage.prompt_tokens as u64,
+                usage.completion_tokens as u64,
+            ));
+        }
+
+        let message: AgentData = Message::assistant(res.choices[0].text.clone()).into();
+        self.try_output(ctx.clone(), PORT_MESSAGE, message.clone())?;
 
         let out_response = AgentData::from_serialize(&res)?;
-        self.try_output(ctx, PORT_RESPONSE, out_response)?;
+        self.try_output(ctx, PORT_RESPONSE, out_response.clone())?;
+
+        if let Some(slot) = cache_slot {
+            slot.store(vec![
+                (PORT_MESSAGE.to_string(), message),
+                (PORT_RESPONSE.to_string(), out_response.clone()),
+            ]);
+        }
 
         Ok(())
     }
@@ -173,7 +207,7 @@ impl AsAgent for OpenAICompletionAgent {
 // OpenAI Chat Agent
 pub struct OpenAIChatAgent {
     data: AsAgentData,
-    manager: OpenAIManager,
+    providers: LlmProviderCache,
 }
 
 #[async_trait]
@@ -186,7 +220,7 @@ impl AsAgent for OpenAIChatAgent {
     ) -> Result<Self, AgentError> {
         Ok(Self {
             data: AsAgentData::new(askit, id, def_name, config),
-            manager: OpenAIManager::new(),
+            providers: LlmProviderCache::default(),
         })
     }
 
@@ -209,120 +243,46 @@ impl AsAgent for OpenAIChatAgent {
             return Ok(());
         }
 
-        let mut messages: Vec<Message> = Vec::new();
-
-        if data.is_string() {
-            let message = data.as_str().unwrap_or("");
-            if message.is_empty() {
-                return Ok(());
-            }
-            messages.push(Message::user(message.to_string()));
-        } else if data.is_object() {
-            let obj = data.as_object().unwrap();
-            if obj.contains_key("role") && obj.contains_key("content") {
-                let msg: Message = data.clone().try_into()?;
-                messages.push(msg);
-            } else {
-                if obj.contains_key("history") {
-                    let history_data = obj.get("history").unwrap();
-                    if history_data.is_array() {
-                        let arr = history_data.as_array().unwrap();
-                        for item in arr {
-                            let msg: Message = item.clone().try_into()?;
-                            messages.push(msg);
-                        }
-                    }
-                }
-                if obj.contains_key("message") {
-                    let msg_data = obj.get("message").unwrap();
-                    let msg: Message = msg_data.clone().try_into()?;
-                    messages.push(msg);
-                }
-            }
-        }
-
+        let messages = messages_from_data(&data)?;
         if messages.is_empty() {
             return Ok(());
         }
 
-        let messages = messages
-            .into_iter()
-            .map(|m| m.into())
-            .collect::<Vec<ChatCompletionRequestMessage>>();
-
+        let options = configured_options(self.configs()?, "max_completion_tokens")?;
         let use_stream = self.configs()?.get_bool_or_default(CONFIG_STREAM);
 
-        let mut request = CreateChatCompletionRequestArgs::default()
-            .model(config_model)
-            .messages(messages)
-            .stream(use_stream)
-            .build()
-            .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
-
-        let config_options = self.configs()?.get_string_or_default(CONFIG_OPTIONS);
-        if !config_options.is_empty() && config_options != "{}" {
-            // Merge options into request
-            let options_json = serde_json::from_str::<serde_json::Value>(&config_options)
-                .map_err(|e| AgentError::InvalidValue(format!("Invalid JSON in options: {}", e)))?;
-
-            let mut request_json = serde_json::to_value(&request)
-                .map_err(|e| AgentError::InvalidValue(format!("Serialization error: {}", e)))?;
-
-            if let (Some(request_obj), Some(options_obj)) =
-                (request_json.as_object_mut(), options_json.as_object())
-            {
-                for (key, value) in options_obj {
-                    request_obj.insert(key.clone(), value.clone());
+        let cache_slot = match ResponseCache::global().lookup(
+            self.configs()?,
+            PROVIDER,
+            config_model,
+            &(&messages, &options),
+        )? {
+            ResponseCacheLookup::Hit(outputs) => {
+                for (port, data) in outputs {
+                    self.try_output(ctx.clone(), port, data)?;
                 }
+                return Ok(());
             }
-            request = serde_json::from_value::<CreateChatCompletionRequest>(request_json)
-                .map_err(|e| AgentError::InvalidValue(format!("Deserialization error: {}", e)))?;
-        }
-
-        let client = self.manager.get_client(self.askit())?;
-
-        if use_stream {
-            let mut stream = client
-                .chat()
-                .create_stream(request)
-                .await
-                .map_err(|e| AgentError::IoError(format!("OpenAI Stream Error: {}", e)))?;
-            let mut content = String::new();
-            while let Some(res) = stream.next().await {
-                let res = res.map_err(|_| AgentError::IoError(format!("OpenAI Stream Error")))?;
-                res.choices.iter().for_each(|c| {
-                    if let Some(ref delta_content) = c.delta.content {
-                        content.push_str(delta_content);
-                    }
-                });
-
-                let mut message = Message::assistant(content.clone());
-                message.id = Some(res.id.clone());
-                self.try_output(ctx.clone(), PORT_MESSAGE, message.into())?;
-
-                let out_response = AgentData::from_serialize(&res)?;
-                self.try_output(ctx.clone(), PORT_RESPONSE, out_response)?;
-            }
-        } else {
-            let res = client
-                .chat()
-                .create(request)
-                .await
-                .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
-
-            let mut content = String::new();
-            res.choices.iter().for_each(|c| {
-                if let Some(ref c) = c.message.content {
-                    content.push_str(c);
-                }
-            });
-
-            let mut res_message = Message::assistant(content);
-            res_message.id = Some(res.id.clone());
-            self.try_output(ctx.clone(), PORT_MESSAGE, res_message.clone().into())?;
+            ResponseCacheLookup::Miss(slot) => Some(slot),
+            ResponseCacheLookup::Disabled => None,
+        };
 
-            let out_response = AgentData::from_serialize(&res)?;
-            self.try_output(ctx.clone(), PORT_RESPONSE, out_response)?;
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
+        let provider = self.providers.get(&self.data.askit, PROVIDER)?;
+
+        let outputs = output_chat(
+            self,
+            ctx,
+            provider.as_ref(),
+            config_model,
+            messages,
+            Some(&options),
+            use_stream,
+        )
+        .await?;
+        if let Some(slot) = cache_slot {
+            slot.store(outputs);
         }
 
         Ok(())
@@ -332,7 +292,7 @@ impl AsAgent for OpenAIChatAgent {
 // OpenAI Embeddings Agent
 pub struct OpenAIEmbeddingsAgent {
     data: AsAgentData,
-    manager: OpenAIManager,
+    providers: LlmProviderCache,
 }
 
 #[async_trait]
@@ -345,7 +305,7 @@ impl AsAgent for OpenAIEmbeddingsAgent {
     ) -> Result<Self, AgentError> {
         Ok(Self {
             data: AsAgentData::new(askit, id, def_name, config),
-            manager: OpenAIManager::new(),
+            providers: LlmProviderCache::default(),
         })
     }
 
@@ -368,45 +328,101 @@ impl AsAgent for OpenAIEmbeddingsAgent {
             return Ok(());
         }
 
-        let input = data.as_str().unwrap_or(""); // TODO: other types
-        if input.is_empty() {
+        let inputs: Vec<String> = if data.is_array() {
+            data.as_array()
+                .unwrap()
+                .iter()
+                .map(|v| {
+                    v.as_str().map(|s| s.to_string()).ok_or_else(|| {
+                        AgentError::InvalidValue("Expected an array of strings".to_string())
+                    })
+                })
+                .collect::<Result<_, _>>()?
+        } else {
+            let input = data.as_str().unwrap_or("");
+            if input.is_empty() {
+                return Ok(());
+            }
+            vec![input.to_string()]
+        };
+        if inputs.is_empty() {
             return Ok(());
         }
 
-        let client = self.manager.get_client(self.askit())?;
-        let mut request = CreateEmbeddingRequestArgs::default()
-            .model(config_model.to_string())
-            .input(vec![input])
-            .build()
-            .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
+        let batch_size = self
+            .configs()?
+            .get_integer_or(CONFIG_BATCH_SIZE, DEFAULT_BATCH_SIZE)
+            .max(1) as usize;
+        let concurrency = self
+            .configs()?
+            .get_integer_or(CONFIG_CONCURRENCY, DEFAULT_CONCURRENCY)
+            .max(1) as usize;
 
         let config_options = self.configs()?.get_string_or_default(CONFIG_OPTIONS);
-        if !config_options.is_empty() && config_options != "{}" {
-            // Merge options into request
-            let options_json = serde_json::from_str::<serde_json::Value>(&config_options)
-                .map_err(|e| AgentError::InvalidValue(format!("Invalid JSON in options: {}", e)))?;
-
-            let mut request_json = serde_json::to_value(&request)
-                .map_err(|e| AgentError::InvalidValue(format!("Serialization error: {}", e)))?;
-
-            if let (Some(request_obj), Some(options_obj)) =
-                (request_json.as_object_mut(), options_json.as_object())
-            {
-                for (key, value) in options_obj {
-                    request_obj.insert(key.clone(), value.clone());
+
+        // only the inputs not in the cache are embedded
+        let lookup = if self.configs()?.get_bool_or_default(CONFIG_CACHE) {
+            Some(EmbeddingCache::global().lookup(
+                PROVIDER,
+                config_model,
+                &config_options,
+                &inputs,
+            )?)
+        } else {
+            None
+        };
+        let requested = match &lookup {
+            Some(lookup) => lookup.missing(&inputs),
+            None => inputs.clone(),
+        };
+
+        self.check_capability(AgentCapability::Network)?;
+        let mut vectors = Vec::with_capacity(requested.len());
+        if !requested.is_empty() {
+            let options = options_from_configs(self.configs()?)?;
+            let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
+            let provider = self.providers.get(&self.data.askit, PROVIDER)?;
+
+            // Batches run concurrently, but `buffered` yields the responses in batch order.
+            let batches = requested
+                .chunks(batch_size)
+                .map(|batch| batch.to_vec())
+                .collect::<Vec<_>>();
+            let responses = futures::stream::iter(batches.into_iter().map(|batch| {
+                let provider = provider.clone();
+                let model = config_model.clone();
+                let options = options.clone();
+                async move { provider.embed(&model, batch, options.as_ref()).await }
+            }))
+            .buffered(concurrency)
+            .collect::<Vec<_>>()
+            .await;
+
+            let mut prompt_tokens = 0;
+            for res in responses {
+                let res = res?;
+                if let Some(usage) = res.usage {
+                    prompt_tokens += usage.input_tokens;
                 }
+                vectors.extend(res.embeddings);
             }
-            request = serde_json::from_value::<CreateEmbeddingRequest>(request_json)
-                .map_err(|e| AgentError::InvalidValue(format!("Deserialization error: {}", e)))?;
-        }
 
-        let res = client
-            .embeddings()
-            .create(request)
-            .await
-            .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
+            self.emit_usage(AgentUsage::new(PROVIDER, config_model, prompt_tokens, 0));
+        }
+        if let Some(lookup) = lookup {
+            vectors = lookup.merge(vectors)?;
+        }
 
-        let data = AgentData::from_serialize(&res.data)?;
+        let embeddings = vectors
+            .into_iter()
+            .enumerate()
+            .map(|(index, embedding)| Embedding {
+                index: index as u32,
+                object: "embedding".to_string(),
+                embedding,
+            })
+            .collect::<Vec<_>>();
+        let data = AgentData::from_serialize(&embeddings)?;
         self.try_output(ctx.clone(), PORT_EMBEDDINGS, data)?;
 
         Ok(())
@@ -453,38 +469,7 @@ impl AsAgent for OpenAIResponsesAgent {
             return Ok(());
         }
 
-        let mut messages: Vec<Message> = Vec::new();
-
-        if data.is_string() {
-            let message = data.as_str().unwrap_or("");
-            if message.is_empty() {
-                return Ok(());
-            }
-            messages.push(Message::user(message.to_string()));
-        } else if data.is_object() {
-            let obj = data.as_object().unwrap();
-            if obj.contains_key("role") && obj.contains_key("content") {
-                let msg: Message = data.clone().try_into()?;
-                messages.push(msg);
-            } else {
-                if obj.contains_key("history") {
-                    let history_data = obj.get("history").unwrap();
-                    if history_data.is_array() {
-                        let arr = history_data.as_array().unwrap();
-                        for item in arr {
-                            let msg: Message = item.clone().try_into()?;
-                            messages.push(msg);
-                        }
-                    }
-                }
-                if obj.contains_key("message") {
-                    let msg_data = obj.get("message").unwrap();
-                    let msg: Message = msg_data.clone().try_into()?;
-                    messages.push(msg);
-                }
-            }
-        }
-
+        let messages = messages_from_data(&data)?;
         if messages.is_empty() {
             return Ok(());
         }
@@ -503,26 +488,26 @@ impl AsAgent for OpenAIResponsesAgent {
             .build()
             .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
 
-        let config_options = self.configs()?.get_string_or_default(CONFIG_OPTIONS);
-        if !config_options.is_empty() && config_options != "{}" {
-            // Merge options into request
-            let options_json = serde_json::from_str::<serde_json::Value>(&config_options)
-                .map_err(|e| AgentError::InvalidValue(format!("Invalid JSON in options: {}", e)))?;
-
-            let mut request_json = serde_json::to_value(&request)
-                .map_err(|e| AgentError::InvalidValue(format!("Serialization error: {}", e)))?;
-
-            if let (Some(request_obj), Some(options_obj)) =
-                (request_json.as_object_mut(), options_json.as_object())
-            {
-                for (key, value) in options_obj {
-                    request_obj.insert(key.clone(), value.clone());
+        request = apply_configured_options(request, self.configs()?, "max_output_tokens")?;
+
+        let cache_slot = match ResponseCache::global().lookup(
+            self.configs()?,
+            PROVIDER,
+            config_model,
+            &request,
+        )? {
+            ResponseCacheLookup::Hit(outputs) => {
+                for (port, data) in outputs {
+                    self.try_output(ctx.clone(), port, data)?;
                 }
+                return Ok(());
             }
-            request = serde_json::from_value::<CreateResponse>(request_json)
-                .map_err(|e| AgentError::InvalidValue(format!("Deserialization error: {}", e)))?;
-        }
+            ResponseCacheLookup::Miss(slot) => Some(slot),
+            ResponseCacheLookup::Disabled => None,
+        };
 
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
         let client = self.manager.get_client(self.askit())?;
 
         if use_stream {
@@ -541,7 +526,16 @@ impl AsAgent for OpenAIResponsesAgent {
                         id = Some(delta.item_id.clone());
                         content.push_str(&delta.delta);
                     }
-                    responses::ResponseEvent::ResponseCompleted(_) => {
+                    responses::ResponseEvent::ResponseCompleted(completed) => {
+                        if let Some(usage) = &completed.response.usage {
+                            self.emit_usage(AgentUsage::new(
+                                PROVIDER,
+                                config_model,
+                                usage.input_tokens as u64,
+                                usage.output_tokens as u64,
+                            ));
+                        }
+
                         let out_response = AgentData::from_serialize(&res_event)?;
                         self.try_output(ctx.clone(), PORT_RESPONSE, out_response)?;
                         break;
@@ -563,18 +557,622 @@ impl AsAgent for OpenAIResponsesAgent {
                 .await
                 .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
 
+            if let Some(usage) = &res.usage {
+                self.emit_usage(AgentUsage::new(
+                    PROVIDER,
+                    config_model,
+                    usage.input_tokens as u64,
+                    usage.output_tokens as u64,
+                ));
+            }
+
             let mut res_message: Message = Message::assistant(get_output_text(&res)); // TODO: better conversion
             res_message.id = Some(res.id.clone());
             self.try_output(ctx.clone(), PORT_MESSAGE, res_message.clone().into())?;
 
             let out_response = AgentData::from_serialize(&res)?;
-            self.try_output(ctx.clone(), PORT_RESPONSE, out_response)?;
+            self.try_output(ctx.clone(), PORT_RESPONSE, out_response.clone())?;
+
+            if let Some(slot) = cache_slot {
+                slot.store(vec![
+                    (PORT_MESSAGE.to_string(), res_message.into()),
+                    (PORT_RESPONSE.to_string(), out_response.clone()),
+                ]);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// OpenAI Audio Transcribe Agent
+// https://platform.openai.com/docs/api-reference/audio/createTranscription
+pub struct OpenAIAudioTranscribeAgent {
+    data: AsAgentData,
+    manager: OpenAIManager,
+}
+
+#[async_trait]
+impl AsAgent for OpenAIAudioTranscribeAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            manager: OpenAIManager::new(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let config_model = &self.configs()?.get_string_or_default(CONFIG_MODEL);
+        if config_model.is_empty() {
+            return Ok(());
+        }
+        let audio = data.as_str().unwrap_or("");
+        if audio.is_empty() {
+            return Ok(());
+        }
+
+        let file = if audio.starts_with(DATA_URL_PREFIX) {
+            audio_from_data_url(audio)?
+        } else {
+            self.check_capability(AgentCapability::Filesystem)?;
+            AudioInput::from(audio)
+        };
+        let mut request = CreateTranscriptionRequestArgs::default();
+        request.file(file).model(config_model);
+        let language = self.configs()?.get_string_or_default(CONFIG_LANGUAGE);
+        if !language.is_empty() {
+            request.language(language);
         }
+        let prompt = self.configs()?.get_string_or_default(CONFIG_PROMPT);
+        if !prompt.is_empty() {
+            request.prompt(prompt);
+        }
+        let request = request
+            .build()
+            .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
+
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
+        let client = self.manager.get_client(self.askit())?;
+        let res = client
+            .audio()
+            .transcribe(request)
+            .await
+            .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
+
+        self.try_output(ctx.clone(), PORT_TEXT, AgentData::string(res.text.clone()))?;
+        self.try_output(ctx, PORT_RESPONSE, AgentData::from_serialize(&res)?)?;
 
         Ok(())
     }
 }
 
+// OpenAI Audio Speech Agent
+// https://platform.openai.com/docs/api-reference/audio/createSpeech
+pub struct OpenAIAudioSpeechAgent {
+    data: AsAgentData,
+    manager: OpenAIManager,
+}
+
+#[async_trait]
+impl AsAgent for OpenAIAudioSpeechAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            manager: OpenAIManager::new(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let config_model = &self.configs()?.get_string_or_default(CONFIG_MODEL);
+        if config_model.is_empty() {
+            return Ok(());
+        }
+        let input = match data.as_str() {
+            Some(text) => text.to_string(),
+            None => {
+                let message: Message = data.try_into()?;
+                message.content
+            }
+        };
+        if input.is_empty() {
+            return Ok(());
+        }
+
+        let voice = self.configs()?.get_string_or(CONFIG_VOICE, DEFAULT_VOICE);
+        let format = self.configs()?.get_string_or(CONFIG_FORMAT, DEFAULT_FORMAT);
+        let mut request = CreateSpeechRequestArgs::default();
+        request
+            .input(input)
+            .model(match config_model.as_str() {
+                "tts-1" => SpeechModel::Tts1,
+                "tts-1-hd" => SpeechModel::Tts1Hd,
+                model => SpeechModel::Other(model.to_string()),
+            })
+            .voice(
+                serde_json::from_value::<Voice>(voice.as_str().into())
+                    .map_err(|_| AgentError::InvalidConfig(format!("Unknown voice {}", voice)))?,
+            )
+            .response_format(
+                serde_json::from_value::<SpeechResponseFormat>(format.as_str().into()).map_err(
+                    |_| AgentError::InvalidConfig(format!("Unknown audio format {}", format)),
+                )?,
+            )
+            .speed(self.configs()?.get_number_or(CONFIG_SPEED, 1.0) as f32);
+        let instructions = self.configs()?.get_string_or_default(CONFIG_INSTRUCTIONS);
+        if !instructions.is_empty() {
+            request.instructions(instructions);
+        }
+        let request = request
+            .build()
+            .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
+
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
+        let client = self.manager.get_client(self.askit())?;
+        let res = client
+            .audio()
+            .speech(request)
+            .await
+            .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
+
+        let audio = audio_to_data_url(&format, &res.bytes);
+        self.try_output(ctx, PORT_AUDIO, AgentData::string(audio))?;
+
+        Ok(())
+    }
+}
+
+// OpenAI Image Generate Agent
+// https://platform.openai.com/docs/api-reference/images/create
+#[cfg(feature = "image")]
+pub struct OpenAIImageGenerateAgent {
+    data: AsAgentData,
+    manager: OpenAIManager,
+}
+
+#[cfg(feature = "image")]
+#[async_trait]
+impl AsAgent for OpenAIImageGenerateAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            manager: OpenAIManager::new(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let config_model = &self.configs()?.get_string_or_default(CONFIG_MODEL);
+        if config_model.is_empty() {
+            return Ok(());
+        }
+        let prompt = match data.as_str() {
+            Some(text) => text.to_string(),
+            None => {
+                let message: Message = data.try_into()?;
+                message.content
+            }
+        };
+        if prompt.is_empty() {
+            return Ok(());
+        }
+
+        let model = match config_model.as_str() {
+            "dall-e-2" => ImageModel::DallE2,
+            "dall-e-3" => ImageModel::DallE3,
+            model => ImageModel::Other(model.to_string()),
+        };
+        let mut request = CreateImageRequestArgs::default();
+        request
+            .prompt(prompt)
+            .n(self.configs()?.get_integer_or(CONFIG_N, 1).clamp(1, 10) as u8);
+        // gpt-image models always return base64, and reject response_format
+        if matches!(model, ImageModel::DallE2 | ImageModel::DallE3) {
+            request.response_format(ImageResponseFormat::B64Json);
+        }
+        request.model(model);
+        let size = self.configs()?.get_string_or_default(CONFIG_SIZE);
+        if !size.is_empty() {
+            request.size(
+                serde_json::from_value::<ImageSize>(size.as_str().into()).map_err(|_| {
+                    AgentError::InvalidConfig(format!("Unknown image size {}", size))
+                })?,
+            );
+        }
+        let quality = self.configs()?.get_string_or_default(CONFIG_QUALITY);
+        if !quality.is_empty() {
+            request.quality(
+                serde_json::from_value::<ImageQuality>(quality.as_str().into()).map_err(|_| {
+                    AgentError::InvalidConfig(format!("Unknown image quality {}", quality))
+                })?,
+            );
+        }
+        let request = request
+            .build()
+            .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
+
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
+        let client = self.manager.get_client(self.askit())?;
+        let res = client
+            .images()
+            .create(request)
+            .await
+            .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
+
+        for image in res.data.iter() {
+            let Image::B64Json { b64_json, .. } = image.as_ref() else {
+                return Err(AgentError::InvalidValue(
+                    "OpenAI returned an image URL instead of the image".to_string(),
+                ));
+            };
+            self.try_output(
+                ctx.clone(),
+                PORT_IMAGE,
+                AgentData::image(PhotonImage::new_from_base64(b64_json)),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// Audio in a data URL, such as `data:audio/wav;base64,...`. The file name given to the
+// API tells the format of the audio.
+fn audio_from_data_url(url: &str) -> Result<AudioInput, AgentError> {
+    let invalid = || AgentError::InvalidValue("Invalid audio data URL".to_string());
+    let (head, data) = url
+        .strip_prefix(DATA_URL_PREFIX)
+        .and_then(|url| url.split_once(','))
+        .ok_or_else(invalid)?;
+    let mime = head.strip_suffix(";base64").ok_or_else(invalid)?;
+    let extension = match mime.strip_prefix("audio/").unwrap_or(mime) {
+        "mpeg" => "mp3",
+        "x-wav" | "wave" => "wav",
+        "x-flac" => "flac",
+        "x-m4a" => "m4a",
+        subtype => subtype,
+    };
+    let bytes = BASE64_STANDARD.decode(data).map_err(|_| invalid())?;
+    Ok(AudioInput::from_vec_u8(
+        format!("audio.{}", extension),
+        bytes,
+    ))
+}
+
+fn audio_to_data_url(format: &str, bytes: &[u8]) -> String {
+    let mime = match format {
+        "mp3" => "audio/mpeg".to_string(),
+        format => format!("audio/{}", format),
+    };
+    format!(
+        "{}{};base64,{}",
+        DATA_URL_PREFIX,
+        mime,
+        BASE64_STANDARD.encode(bytes)
+    )
+}
+
+// LLM provider backed by an OpenAI compatible API
+pub struct OpenAIProvider {
+    name: &'static str,
+    client: Client<OpenAIConfig>,
+    http: reqwest::Client,
+}
+
+impl OpenAIProvider {
+    pub fn new(askit: &ASKit) -> Result<Self, AgentError> {
+        let client = OpenAIManager::new().get_client(askit)?;
+        Ok(Self::with_client(PROVIDER, client))
+    }
+
+    pub fn with_client(name: &'static str, client: Client<OpenAIConfig>) -> Self {
+        Self {
+            name,
+            client,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    fn chat_request(
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+        stream: bool,
+    ) -> Result<CreateChatCompletionRequest, AgentError> {
+        let mut request = CreateChatCompletionRequestArgs::default()
+            .model(model)
+            .messages(
+                messages
+                    .into_iter()
+                    .map(|m| m.into())
+                    .collect::<Vec<ChatCompletionRequestMessage>>(),
+            )
+            .stream(stream)
+            .build()
+            .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
+        if stream {
+            request.stream_options = Some(stream_options());
+        }
+        if let Some(options_json) = options {
+            request = merge_options(request, options_json)?;
+        }
+        Ok(request)
+    }
+}
+
+#[async_trait]
+impl LlmProvider for OpenAIProvider {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatResponse, AgentError> {
+        let request = Self::chat_request(model, messages, options, false)?;
+        let res = self
+            .client
+            .chat()
+            .create(request)
+            .await
+            .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
+
+        let usage = res.usage.as_ref().map(|usage| {
+            AgentUsage::new(
+                self.name,
+                model,
+                usage.prompt_tokens as u64,
+                usage.completion_tokens as u64,
+            )
+        });
+
+        let mut content = String::new();
+        res.choices.iter().for_each(|c| {
+            if let Some(ref c) = c.message.content {
+                content.push_str(c);
+            }
+        });
+
+        let mut message = Message::assistant(content);
+        message.id = Some(res.id.clone());
+
+        Ok(LlmChatResponse {
+            message,
+            response: AgentData::from_serialize(&res)?,
+            usage,
+        })
+    }
+
+    async fn chat_stream(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatStream, AgentError> {
+        let request = Self::chat_request(model, messages, options, true)?;
+        let stream = self
+            .client
+            .chat()
+            .create_stream(request)
+            .await
+            .map_err(|e| AgentError::IoError(format!("OpenAI Stream Error: {}", e)))?;
+
+        let name = self.name;
+        let model = model.to_string();
+        Ok(stream
+            .map(move |res| {
+                let res =
+                    res.map_err(|e| AgentError::IoError(format!("OpenAI Stream Error: {}", e)))?;
+                let usage = res.usage.as_ref().map(|usage| {
+                    AgentUsage::new(
+                        name,
+                        model.as_str(),
+                        usage.prompt_tokens as u64,
+                        usage.completion_tokens as u64,
+                    )
+                });
+
+                let mut content = String::new();
+                res.choices.iter().for_each(|c| {
+                    if let Some(ref delta_content) = c.delta.content {
+                        content.push_str(delta_content);
+                    }
+                });
+
+                let mut message = Message::assistant(content);
+                message.id = Some(res.id.clone());
+
+                Ok(LlmChatResponse {
+                    message,
+                    response: AgentData::from_serialize(&res)?,
+                    usage,
+                })
+            })
+            .boxed())
+    }
+
+    async fn embed(
+        &self,
+        model: &str,
+        inputs: Vec<String>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmEmbeddings, AgentError> {
+        let mut request = CreateEmbeddingRequestArgs::default()
+            .model(model)
+            .input(inputs)
+            .build()
+            .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
+        if let Some(options_json) = options {
+            request = merge_options(request, options_json)?;
+        }
+
+        let res = self
+            .client
+            .embeddings()
+            .create(request)
+            .await
+            .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
+
+        let mut data = res.data;
+        data.sort_by_key(|e| e.index);
+        Ok(LlmEmbeddings {
+            embeddings: data.into_iter().map(|e| e.embedding).collect(),
+            usage: Some(AgentUsage::new(
+                self.name,
+                model,
+                res.usage.prompt_tokens as u64,
+                0,
+            )),
+        })
+    }
+
+    // The rerank API of Jina and Cohere, also served by vLLM and llama.cpp. OpenAI itself
+    // has none.
+    async fn rerank(
+        &self,
+        model: &str,
+        query: &str,
+        documents: Vec<String>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmRerank, AgentError> {
+        let mut request = serde_json::json!({
+            "model": model,
+            "query": query,
+            "documents": documents,
+        });
+        if let Some(options_json) = options {
+            request = merge_options(request, options_json)?;
+        }
+
+        let config = self.client.config();
+        let res = self
+            .http
+            .post(config.url("/rerank"))
+            .headers(config.headers())
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
+        let status = res.status();
+        let text = res
+            .text()
+            .await
+            .map_err(|e| AgentError::IoError(format!("OpenAI Error: {}", e)))?;
+        if !status.is_success() {
+            return Err(AgentError::IoError(format!(
+                "OpenAI Error: {} {}",
+                status, text
+            )));
+        }
+        let body = serde_json::from_str::<serde_json::Value>(&text)?;
+        rerank_from_json(self.name, model, &body)
+    }
+}
+
+// {"results": [{"index": 0, "relevance_score": 0.9}], "usage": {"total_tokens": 10}}, or
+// a bare array of {"index", "score"} like Text Embeddings Inference
+fn rerank_from_json(
+    provider: &str,
+    model: &str,
+    body: &serde_json::Value,
+) -> Result<LlmRerank, AgentError> {
+    let items = body
+        .get("results")
+        .unwrap_or(body)
+        .as_array()
+        .ok_or_else(|| AgentError::InvalidValue(format!("Invalid rerank response: {}", body)))?;
+    let mut results = items
+        .iter()
+        .map(|item| {
+            let index = item["index"].as_u64();
+            let score = item["relevance_score"].as_f64().or(item["score"].as_f64());
+            match (index, score) {
+                (Some(index), Some(score)) => Ok(LlmRerankResult {
+                    index: index as usize,
+                    score,
+                }),
+                _ => Err(AgentError::InvalidValue(format!(
+                    "Invalid rerank result: {}",
+                    item
+                ))),
+            }
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    results.sort_by(|a, b| b.score.total_cmp(&a.score));
+    let usage = &body["usage"];
+    let usage = usage["total_tokens"]
+        .as_u64()
+        .or(usage["prompt_tokens"].as_u64())
+        .map(|tokens| AgentUsage::new(provider, model, tokens, 0));
+    Ok(LlmRerank { results, usage })
+}
+
+// Asks for a last chunk with the usage of the whole request, which streams leave out otherwise.
+fn stream_options() -> ChatCompletionStreamOptions {
+    ChatCompletionStreamOptions {
+        include_usage: true,
+    }
+}
+
+// Merges the options of the agent config into a request
 fn get_output_text(response: &responses::Response) -> String {
     let mut output_text = String::new();
     response.output.iter().for_each(|msg| {
@@ -611,7 +1209,7 @@ impl From<Message> for ChatCompletionRequestMessage {
                 .unwrap()
                 .into(),
             "user" => ChatCompletionRequestUserMessageArgs::default()
-                .content(msg.content.clone())
+                .content(user_message_content(&msg))
                 .build()
                 .unwrap()
                 .into(),
@@ -626,7 +1224,7 @@ impl From<Message> for ChatCompletionRequestMessage {
                 .unwrap()
                 .into(),
             _ => ChatCompletionRequestUserMessageArgs::default()
-                .content(msg.content.clone())
+                .content(user_message_content(&msg))
                 .build()
                 .unwrap()
                 .into(),
@@ -634,6 +1232,56 @@ impl From<Message> for ChatCompletionRequestMessage {
     }
 }
 
+// Text and image parts of a user message
+fn user_message_content(msg: &Message) -> ChatCompletionRequestUserMessageContent {
+    #[cfg(feature = "image")]
+    {
+        use async_openai::types::{
+            ChatCompletionRequestMessageContentPartImage,
+            ChatCompletionRequestMessageContentPartText,
+            ChatCompletionRequestUserMessageContentPart, ImageUrl,
+        };
+
+        if let Some(url) = msg.image_data_url() {
+            let mut parts = Vec::new();
+            if !msg.content.is_empty() {
+                parts.push(ChatCompletionRequestUserMessageContentPart::Text(
+                    ChatCompletionRequestMessageContentPartText {
+                        text: msg.content.clone(),
+                    },
+                ));
+            }
+            parts.push(ChatCompletionRequestUserMessageContentPart::ImageUrl(
+                ChatCompletionRequestMessageContentPartImage {
+                    image_url: ImageUrl { url, detail: None },
+                },
+            ));
+            return ChatCompletionRequestUserMessageContent::Array(parts);
+        }
+    }
+    ChatCompletionRequestUserMessageContent::Text(msg.content.clone())
+}
+
+// Text and image parts of an input message
+fn input_content(msg: &Message) -> responses::InputContent {
+    #[cfg(feature = "image")]
+    {
+        if let Some(url) = msg.image_data_url() {
+            let mut parts = Vec::new();
+            if !msg.content.is_empty() {
+                parts.push(responses::ContentType::InputText(responses::InputText {
+                    text: msg.content.clone(),
+                }));
+            }
+            if let Ok(image) = responses::InputImageArgs::default().image_url(url).build() {
+                parts.push(responses::ContentType::InputImage(image));
+            }
+            return responses::InputContent::InputItemContentList(parts);
+        }
+    }
+    responses::InputContent::TextInput(msg.content.clone())
+}
+
 impl From<&Message> for responses::InputItem {
     fn from(msg: &Message) -> Self {
         responses::InputItem::Message(responses::InputMessage {
@@ -645,7 +1293,7 @@ impl From<&Message> for responses::InputItem {
                 "developer" => responses::Role::Developer,
                 _ => responses::Role::Developer,
             },
-            content: responses::InputContent::TextInput(msg.content.clone()),
+            content: input_content(msg),
         })
     }
 }
@@ -683,19 +1331,45 @@ impl From<OutputMessage> for Message {
 }
 
 static AGENT_KIND: &str = "agent";
+static PROVIDER: &str = "openai";
 static CATEGORY: &str = "LLM";
 
+static PORT_AUDIO: &str = "audio";
 static PORT_EMBEDDINGS: &str = "embeddings";
+#[cfg(feature = "image")]
+static PORT_IMAGE: &str = "image";
 static PORT_INPUT: &str = "input";
 static PORT_MESSAGE: &str = "message";
 static PORT_RESPONSE: &str = "response";
+static PORT_TEXT: &str = "text";
 
+static CONFIG_BATCH_SIZE: &str = "batch_size";
+static CONFIG_CONCURRENCY: &str = "concurrency";
+static CONFIG_FORMAT: &str = "format";
+static CONFIG_INSTRUCTIONS: &str = "instructions";
+static CONFIG_LANGUAGE: &str = "language";
 static CONFIG_MODEL: &str = "model";
+#[cfg(feature = "image")]
+static CONFIG_N: &str = "n";
 static CONFIG_OPENAI_API_KEY: &str = "openai_api_key";
+const OPENAI_API_KEY_ENV: &str = "OPENAI_API_KEY";
 static CONFIG_OPTIONS: &str = "options";
+static CONFIG_PROMPT: &str = "prompt";
+#[cfg(feature = "image")]
+static CONFIG_QUALITY: &str = "quality";
+#[cfg(feature = "image")]
+static CONFIG_SIZE: &str = "size";
+static CONFIG_SPEED: &str = "speed";
 static CONFIG_STREAM: &str = "stream";
+static CONFIG_VOICE: &str = "voice";
 
 const DEFAULT_CONFIG_MODEL: &str = "gpt-5-nano";
+const DEFAULT_BATCH_SIZE: i64 = 100;
+const DEFAULT_CONCURRENCY: i64 = 4;
+const DEFAULT_VOICE: &str = "alloy";
+const DEFAULT_FORMAT: &str = "mp3";
+
+const DATA_URL_PREFIX: &str = "data:";
 
 pub fn register_agents(askit: &ASKit) {
     askit.register_agent(
@@ -709,10 +1383,24 @@ pub fn register_agents(askit: &ASKit) {
         .category(CATEGORY)
         .inputs(vec![PORT_MESSAGE])
         .outputs(vec![PORT_MESSAGE, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Network])
         .string_config_with(CONFIG_MODEL, "gpt-3.5-turbo-instruct", |entry| {
-            entry.title("Model")
+            entry.title("Model").required()
+        })
+        .custom_config_with(CONFIG_TEMPERATURE, (), "number", |entry| {
+            entry.title("Temperature")
+        })
+        .custom_config_with(CONFIG_MAX_TOKENS, (), "integer", |entry| {
+            entry.title("Max Tokens")
+        })
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options"))
+        .boolean_config_with(CONFIG_CACHE, false, |entry| entry.title("Cache"))
+        .integer_config_with(CONFIG_CACHE_TTL, DEFAULT_CACHE_TTL, |entry| {
+            entry.title("Cache TTL (sec)")
         })
-        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+        .integer_config_with(CONFIG_CACHE_MAX_SIZE, DEFAULT_CACHE_MAX_SIZE, |entry| {
+            entry.title("Cache Max Size")
+        }),
     );
 
     askit.register_agent(
@@ -726,6 +1414,7 @@ pub fn register_agents(askit: &ASKit) {
         .category(CATEGORY)
         .inputs(vec![PORT_MESSAGE])
         .outputs(vec![PORT_MESSAGE, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Network])
         .custom_global_config_with(
             CONFIG_OPENAI_API_KEY,
             "",
@@ -733,10 +1422,23 @@ pub fn register_agents(askit: &ASKit) {
             |entry| entry.title("OpenAI API Key"),
         )
         .string_config_with(CONFIG_MODEL, DEFAULT_CONFIG_MODEL, |entry| {
-            entry.title("Model")
+            entry.title("Model").required()
         })
         .boolean_config_with(CONFIG_STREAM, false, |entry| entry.title("Stream"))
-        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+        .custom_config_with(CONFIG_TEMPERATURE, (), "number", |entry| {
+            entry.title("Temperature")
+        })
+        .custom_config_with(CONFIG_MAX_TOKENS, (), "integer", |entry| {
+            entry.title("Max Tokens")
+        })
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options"))
+        .boolean_config_with(CONFIG_CACHE, false, |entry| entry.title("Cache"))
+        .integer_config_with(CONFIG_CACHE_TTL, DEFAULT_CACHE_TTL, |entry| {
+            entry.title("Cache TTL (sec)")
+        })
+        .integer_config_with(CONFIG_CACHE_MAX_SIZE, DEFAULT_CACHE_MAX_SIZE, |entry| {
+            entry.title("Cache Max Size")
+        }),
     );
 
     askit.register_agent(
@@ -750,10 +1452,22 @@ pub fn register_agents(askit: &ASKit) {
         .category(CATEGORY)
         .inputs(vec![PORT_INPUT])
         .outputs(vec![PORT_EMBEDDINGS])
+        .capabilities(vec![AgentCapability::Network])
         .string_config_with(CONFIG_MODEL, "text-embedding-3-small", |entry| {
-            entry.title("Model")
+            entry.title("Model").required()
+        })
+        .integer_config_with(CONFIG_BATCH_SIZE, DEFAULT_BATCH_SIZE, |entry| {
+            entry.title("Batch Size")
         })
-        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+        .integer_config_with(CONFIG_CONCURRENCY, DEFAULT_CONCURRENCY, |entry| {
+            entry.title("Concurrency")
+        })
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options"))
+        .boolean_config_with(CONFIG_CACHE, false, |entry| {
+            entry
+                .title("Cache")
+                .description("Reuses the embeddings of texts already embedded")
+        }),
     );
 
     askit.register_agent(
@@ -767,10 +1481,160 @@ pub fn register_agents(askit: &ASKit) {
         .category(CATEGORY)
         .inputs(vec![PORT_MESSAGE])
         .outputs(vec![PORT_MESSAGE, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Network])
         .string_config_with(CONFIG_MODEL, DEFAULT_CONFIG_MODEL, |entry| {
-            entry.title("Model")
+            entry.title("Model").required()
         })
         .boolean_config_with(CONFIG_STREAM, false, |entry| entry.title("Stream"))
-        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+        .custom_config_with(CONFIG_TEMPERATURE, (), "number", |entry| {
+            entry.title("Temperature")
+        })
+        .custom_config_with(CONFIG_MAX_TOKENS, (), "integer", |entry| {
+            entry.title("Max Tokens")
+        })
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options"))
+        .boolean_config_with(CONFIG_CACHE, false, |entry| entry.title("Cache"))
+        .integer_config_with(CONFIG_CACHE_TTL, DEFAULT_CACHE_TTL, |entry| {
+            entry.title("Cache TTL (sec)")
+        })
+        .integer_config_with(CONFIG_CACHE_MAX_SIZE, DEFAULT_CACHE_MAX_SIZE, |entry| {
+            entry.title("Cache Max Size")
+        }),
+    );
+
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "openai_audio_transcribe",
+            Some(new_agent_boxed::<OpenAIAudioTranscribeAgent>),
+        )
+        .title("OpenAI Audio Transcribe")
+        .description("Transcribes an audio file, or a data URL of audio, into text")
+        .category(CATEGORY)
+        .inputs(vec![PORT_AUDIO])
+        .outputs(vec![PORT_TEXT, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Network, AgentCapability::Filesystem])
+        .string_config_with(CONFIG_MODEL, "whisper-1", |entry| {
+            entry.title("Model").required()
+        })
+        .string_config_with(CONFIG_LANGUAGE, "", |entry| {
+            entry
+                .title("Language")
+                .description("ISO-639-1 code of the audio language, such as en")
+        })
+        .text_config_with(CONFIG_PROMPT, "", |entry| entry.title("Prompt")),
+    );
+
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "openai_audio_speech",
+            Some(new_agent_boxed::<OpenAIAudioSpeechAgent>),
+        )
+        .title("OpenAI Audio Speech")
+        .description("Speaks the text, and outputs the audio as a data URL")
+        .category(CATEGORY)
+        .inputs(vec![PORT_TEXT])
+        .outputs(vec![PORT_AUDIO])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_MODEL, "tts-1", |entry| {
+            entry.title("Model").required()
+        })
+        .string_config_with(CONFIG_VOICE, DEFAULT_VOICE, |entry| entry.title("Voice"))
+        .string_config_with(CONFIG_FORMAT, DEFAULT_FORMAT, |entry| {
+            entry
+                .title("Format")
+                .description("mp3, opus, aac, flac, wav or pcm")
+        })
+        .number_config_with(CONFIG_SPEED, 1.0, |entry| entry.title("Speed"))
+        .text_config_with(CONFIG_INSTRUCTIONS, "", |entry| entry.title("Instructions")),
+    );
+
+    #[cfg(feature = "image")]
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "openai_image_generate",
+            Some(new_agent_boxed::<OpenAIImageGenerateAgent>),
+        )
+        .title("OpenAI Image Generate")
+        .description("Generates images from the prompt")
+        .category(CATEGORY)
+        .inputs(vec![PORT_TEXT])
+        .outputs(vec![PORT_IMAGE])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_MODEL, "dall-e-3", |entry| {
+            entry.title("Model").required()
+        })
+        .string_config_with(CONFIG_SIZE, "", |entry| {
+            entry
+                .title("Size")
+                .description("1024x1024, 1792x1024 or 1024x1792, or the default of the model")
+        })
+        .string_config_with(CONFIG_QUALITY, "", |entry| {
+            entry
+                .title("Quality")
+                .description("standard or hd for dall-e-3, low, medium or high for gpt-image-1")
+        })
+        .integer_config_with(CONFIG_N, 1, |entry| {
+            entry
+                .title("Number")
+                .description("Images to generate, only 1 for dall-e-3")
+        }),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_data_url() {
+        let url = audio_to_data_url("mp3", b"ID3");
+        assert_eq!(url, "data:audio/mpeg;base64,SUQz");
+
+        let audio = audio_from_data_url(&url).unwrap();
+        match audio.source {
+            async_openai::types::InputSource::VecU8 { filename, vec } => {
+                assert_eq!(filename, "audio.mp3");
+                assert_eq!(vec, b"ID3");
+            }
+            _ => panic!("expected bytes"),
+        }
+
+        assert!(audio_from_data_url("data:audio/wav,abc").is_err());
+    }
+
+    #[test]
+    fn test_rerank_from_json() {
+        let body = serde_json::json!({
+            "results": [
+                {"index": 0, "relevance_score": 0.1},
+                {"index": 1, "relevance_score": 0.9},
+            ],
+            "usage": {"total_tokens": 12},
+        });
+        let rerank = rerank_from_json(PROVIDER, "rerank", &body).unwrap();
+        let indices = rerank.results.iter().map(|r| r.index).collect::<Vec<_>>();
+        assert_eq!(indices, vec![1, 0]);
+        assert_eq!(rerank.usage.unwrap().input_tokens, 12);
+
+        // Text Embeddings Inference
+        let body = serde_json::json!([{"index": 0, "score": 0.5}]);
+        let rerank = rerank_from_json(PROVIDER, "rerank", &body).unwrap();
+        assert_eq!(rerank.results[0].score, 0.5);
+        assert!(rerank.usage.is_none());
+
+        assert!(rerank_from_json(PROVIDER, "rerank", &serde_json::json!({"error": "x"})).is_err());
+    }
+
+    #[test]
+    fn test_chat_request_stream_usage() {
+        let messages = vec![Message::user("hi".to_string())];
+        let request = OpenAIProvider::chat_request("gpt", messages.clone(), None, true).unwrap();
+        assert_eq!(request.stream_options.map(|o| o.include_usage), Some(true));
+
+        let request = OpenAIProvider::chat_request("gpt", messages, None, false).unwrap();
+        assert!(request.stream_options.is_none());
+    }
+}
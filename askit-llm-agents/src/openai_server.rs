@@ -0,0 +1,652 @@
+#![cfg(feature = "server")]
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentValue,
+    AgentValueMap, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use serde_json::{Value, json};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+use crate::message::Message;
+
+// OpenAI Server
+//
+// Serves flows on an OpenAI-compatible `/v1/chat/completions` endpoint. A flow is served
+// by its `openai_server_input` node, under the model name of the node, which outputs the
+// messages of each request. The reply is the messages reaching its `openai_server_output`
+// node, streamed as SSE chunks when the request asks for a stream.
+
+/// Flow served as a model.
+#[derive(Clone, Debug)]
+pub struct ChatFlow {
+    pub model: String,
+    pub flow_name: String,
+
+    /// Id of the `openai_server_input` agent.
+    pub agent_id: String,
+}
+
+/// Flows of the enabled `openai_server_input` nodes, sorted by model name.
+pub fn chat_flows(askit: &ASKit) -> Vec<ChatFlow> {
+    let mut flows = askit.get_agent_flows().into_values().collect::<Vec<_>>();
+    flows.sort_by(|a, b| a.name().cmp(b.name()));
+
+    let mut chat_flows: Vec<ChatFlow> = Vec::new();
+    for flow in flows.iter() {
+        for node in flow.nodes() {
+            if node.def_name != OPENAI_SERVER_INPUT || !node.enabled {
+                continue;
+            }
+            let model = node
+                .configs
+                .as_ref()
+                .map(|configs| configs.get_string_or_default(CONFIG_MODEL))
+                .filter(|model| !model.is_empty())
+                .unwrap_or_else(|| flow.name().to_string());
+            // the first of the flows by name wins
+            if chat_flows.iter().any(|chat_flow| chat_flow.model == model) {
+                continue;
+            }
+            chat_flows.push(ChatFlow {
+                model,
+                flow_name: flow.name().to_string(),
+                agent_id: node.id.clone(),
+            });
+        }
+    }
+    chat_flows.sort_by(|a, b| a.model.cmp(&b.model));
+    chat_flows
+}
+
+// Reply of a flow, sent by the openai_server_output agent
+enum ReplyEvent {
+    Message(String),
+    Done,
+}
+
+// Reply of a request being made. The call is forgotten when it is dropped.
+struct ReplyReceiver {
+    call_id: i64,
+    rx: mpsc::UnboundedReceiver<ReplyEvent>,
+}
+
+impl Drop for ReplyReceiver {
+    fn drop(&mut self) {
+        PENDING_CALLS.lock().unwrap().remove(&self.call_id);
+    }
+}
+
+// Sends the messages out of the input agent of the flow. They are the last message and
+// the history before it, as llm_chat takes them.
+async fn call_flow(
+    askit: &ASKit,
+    chat_flow: &ChatFlow,
+    messages: Vec<Message>,
+) -> Result<ReplyReceiver, AgentError> {
+    let mut messages = messages;
+    let Some(message) = messages.pop() else {
+        return Err(AgentError::InvalidValue("No messages".to_string()));
+    };
+    let history = messages.into_iter().map(|m| AgentData::from(m).value);
+    let data = AgentData::object(AgentValueMap::from([
+        ("history".to_string(), AgentValue::array(history.collect())),
+        ("message".to_string(), AgentData::from(message).value),
+    ]));
+
+    let call_id = CALL_ID_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let (tx, rx) = mpsc::unbounded_channel();
+    PENDING_CALLS.lock().unwrap().insert(call_id, tx);
+    let reply = ReplyReceiver { call_id, rx };
+
+    let ctx =
+        AgentContext::new().with_var(VAR_OPENAI_CALL.to_string(), AgentValue::integer(call_id));
+    askit
+        .send_agent_out(
+            chat_flow.agent_id.clone(),
+            ctx,
+            PORT_MESSAGE.to_string(),
+            data,
+        )
+        .await?;
+    Ok(reply)
+}
+
+// Sends the event to the call the context comes from. False when it is not from a call,
+// or the call is over.
+fn send_reply(ctx: &AgentContext, event: ReplyEvent) -> bool {
+    let Some(call_id) = ctx.get_var(VAR_OPENAI_CALL).and_then(|id| id.as_i64()) else {
+        return false;
+    };
+    let pending_calls = PENDING_CALLS.lock().unwrap();
+    let Some(tx) = pending_calls.get(&call_id) else {
+        return false;
+    };
+    tx.send(event).is_ok()
+}
+
+// Adds the content of a message to the reply, and returns the new part.
+//
+// A streaming llm_chat outputs the whole reply so far each time, so a content extending
+// the reply replaces it. Other contents are appended.
+fn append_content(reply: &mut String, content: &str) -> String {
+    let delta = match content.strip_prefix(reply.as_str()) {
+        Some(delta) => delta.to_string(),
+        None => content.to_string(),
+    };
+    reply.push_str(&delta);
+    delta
+}
+
+// Messages of the request body. Contents may be arrays of parts, whose texts are joined.
+fn request_messages(body: &Value) -> Result<Vec<Message>, String> {
+    let Some(messages) = body.get("messages").and_then(|m| m.as_array()) else {
+        return Err("messages is required".to_string());
+    };
+    let mut parsed = Vec::new();
+    for message in messages {
+        let role = message
+            .get("role")
+            .and_then(|r| r.as_str())
+            .ok_or_else(|| "message role is required".to_string())?;
+        let content = match message.get("content") {
+            Some(Value::String(s)) => s.clone(),
+            Some(Value::Array(parts)) => parts
+                .iter()
+                .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            _ => String::new(),
+        };
+        parsed.push(Message::new(role.to_string(), content));
+    }
+    if parsed.is_empty() {
+        return Err("messages is empty".to_string());
+    }
+    Ok(parsed)
+}
+
+/// OpenAI-compatible server of the flows of [`chat_flows`]. The flows must be running
+/// to be called.
+///
+/// A reply ends when the output node receives `done`, or its first message unless it is
+/// set as streamed, or when no message comes for the idle timeout.
+#[derive(Clone)]
+pub struct OpenAIServer {
+    askit: ASKit,
+    timeout: Duration,
+    idle_timeout: Duration,
+}
+
+impl OpenAIServer {
+    pub fn new(askit: ASKit) -> Self {
+        Self {
+            askit,
+            timeout: DEFAULT_TIMEOUT,
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        }
+    }
+
+    /// Time to wait for the first message of a reply.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Time without a message after which a streamed reply is over.
+    pub fn idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Listens on the address, and serves the requests in the background.
+    pub async fn serve(self, address: &str) -> Result<(), AgentError> {
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|e| AgentError::IoError(format!("Failed to listen on {}: {}", address, e)))?;
+
+        tokio::spawn(async move {
+            loop {
+                let stream = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(_) => continue,
+                };
+                let server = self.clone();
+                tokio::spawn(async move {
+                    // the client has gone
+                    let _ = server.handle_connection(stream).await;
+                });
+            }
+        });
+        Ok(())
+    }
+
+    async fn handle_connection(&self, stream: TcpStream) -> std::io::Result<()> {
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+        let Some(request) = read_request(&mut reader).await? else {
+            return Ok(());
+        };
+
+        match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/v1/models") => {
+                let models = chat_flows(&self.askit)
+                    .into_iter()
+                    .map(|chat_flow| {
+                        json!({"id": chat_flow.model, "object": "model", "owned_by": "askit"})
+                    })
+                    .collect::<Vec<_>>();
+                write_json(&mut writer, 200, &json!({"object": "list", "data": models})).await
+            }
+            ("POST", "/v1/chat/completions") => {
+                self.chat_completions(&mut writer, &request.body).await
+            }
+            _ => write_error(&mut writer, 404, "Not found").await,
+        }
+    }
+
+    async fn chat_completions<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        body: &[u8],
+    ) -> std::io::Result<()> {
+        let body: Value = match serde_json::from_slice(body) {
+            Ok(body) => body,
+            Err(e) => return write_error(writer, 400, &format!("Invalid JSON: {}", e)).await,
+        };
+        let messages = match request_messages(&body) {
+            Ok(messages) => messages,
+            Err(e) => return write_error(writer, 400, &e).await,
+        };
+        let model = body.get("model").and_then(|m| m.as_str()).unwrap_or("");
+        let Some(chat_flow) = chat_flows(&self.askit)
+            .into_iter()
+            .find(|chat_flow| chat_flow.model == model)
+        else {
+            return write_error(writer, 404, &format!("Model {} not found", model)).await;
+        };
+        let stream = body
+            .get("stream")
+            .and_then(|s| s.as_bool())
+            .unwrap_or(false);
+
+        let mut reply = match call_flow(&self.askit, &chat_flow, messages).await {
+            Ok(reply) => reply,
+            Err(e) => return write_error(writer, 500, &e.to_string()).await,
+        };
+        let id = format!("chatcmpl-{}", uuid::Uuid::new_v4().simple());
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let chunk = |delta: Value, finish_reason: Value| {
+            json!({
+                "id": id,
+                "object": "chat.completion.chunk",
+                "created": created,
+                "model": model,
+                "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}],
+            })
+        };
+
+        let mut content = String::new();
+        let mut timeout = self.timeout;
+        let mut started = false;
+        loop {
+            let event = match tokio::time::timeout(timeout, reply.rx.recv()).await {
+                Ok(Some(ReplyEvent::Message(message))) => message,
+                Ok(Some(ReplyEvent::Done)) | Ok(None) => break,
+                Err(_) if started => break,
+                Err(_) => {
+                    return write_error(writer, 504, "The flow did not reply in time").await;
+                }
+            };
+            let delta = append_content(&mut content, &event);
+            if stream {
+                if !started {
+                    write_sse_head(writer).await?;
+                    let role = chunk(json!({"role": "assistant"}), Value::Null);
+                    write_sse(writer, &role.to_string()).await?;
+                }
+                if !delta.is_empty() {
+                    let data = chunk(json!({"content": delta}), Value::Null);
+                    write_sse(writer, &data.to_string()).await?;
+                }
+            }
+            started = true;
+            timeout = self.idle_timeout;
+        }
+
+        if !stream {
+            let completion = json!({
+                "id": id,
+                "object": "chat.completion",
+                "created": created,
+                "model": model,
+                "choices": [{
+                    "index": 0,
+                    "message": {"role": "assistant", "content": content},
+                    "finish_reason": "stop",
+                }],
+            });
+            return write_json(writer, 200, &completion).await;
+        }
+        if !started {
+            write_sse_head(writer).await?;
+        }
+        write_sse(writer, &chunk(json!({}), json!("stop")).to_string()).await?;
+        write_sse(writer, "[DONE]").await
+    }
+}
+
+// HTTP
+//
+// Just enough HTTP/1.1 for the OpenAI clients: one request per connection, with the body
+// of its Content-Length.
+struct HttpRequest {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+async fn read_request<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+) -> std::io::Result<Option<HttpRequest>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let target = parts.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            break;
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':')
+            && name.trim().eq_ignore_ascii_case("content-length")
+        {
+            content_length = value.trim().parse().unwrap_or(0);
+        }
+    }
+    if content_length > MAX_BODY_SIZE {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "request body too large",
+        ));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+    Ok(Some(HttpRequest { method, path, body }))
+}
+
+fn status_text(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        504 => "Gateway Timeout",
+        _ => "Internal Server Error",
+    }
+}
+
+async fn write_json<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    status: u16,
+    body: &Value,
+) -> std::io::Result<()> {
+    let body = body.to_string();
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        status_text(status),
+        body.len()
+    );
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(body.as_bytes()).await?;
+    writer.flush().await
+}
+
+// Errors in the format of the OpenAI API
+async fn write_error<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    status: u16,
+    message: &str,
+) -> std::io::Result<()> {
+    let error_type = if status == 400 || status == 404 {
+        "invalid_request_error"
+    } else {
+        "server_error"
+    };
+    let body = json!({"error": {"message": message, "type": error_type}});
+    write_json(writer, status, &body).await
+}
+
+async fn write_sse_head<W: AsyncWrite + Unpin>(writer: &mut W) -> std::io::Result<()> {
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n";
+    writer.write_all(head.as_bytes()).await
+}
+
+async fn write_sse<W: AsyncWrite + Unpin>(writer: &mut W, data: &str) -> std::io::Result<()> {
+    writer
+        .write_all(format!("data: {}\n\n", data).as_bytes())
+        .await?;
+    writer.flush().await
+}
+
+// OpenAI Server Input Agent
+//
+// Outputs the messages of the requests for its flow. It takes no input.
+pub struct OpenAIServerInputAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for OpenAIServerInputAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        _ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        Ok(())
+    }
+}
+
+// OpenAI Server Output Agent
+//
+// Sends the messages to the request they come from.
+pub struct OpenAIServerOutputAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for OpenAIServerOutputAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PORT_DONE {
+            send_reply(&ctx, ReplyEvent::Done);
+            return Ok(());
+        }
+
+        let content = match data.as_str() {
+            Some(content) => content.to_string(),
+            None => {
+                let message: Message = data.try_into().map_err(|e| {
+                    AgentError::InvalidValue(format!("Failed to convert data to Message: {}", e))
+                })?;
+                message.content
+            }
+        };
+        // data not from a request, or of a request over, is dropped
+        send_reply(&ctx, ReplyEvent::Message(content));
+        if !self.configs()?.get_bool_or_default(CONFIG_STREAMED) {
+            send_reply(&ctx, ReplyEvent::Done);
+        }
+        Ok(())
+    }
+}
+
+// call id -> sender of the reply
+static PENDING_CALLS: LazyLock<Mutex<HashMap<i64, mpsc::UnboundedSender<ReplyEvent>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+static CALL_ID_COUNTER: AtomicI64 = AtomicI64::new(1);
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static OPENAI_SERVER_INPUT: &str = "openai_server_input";
+static OPENAI_SERVER_OUTPUT: &str = "openai_server_output";
+
+static PORT_MESSAGE: &str = "message";
+static PORT_DONE: &str = "done";
+
+static CONFIG_MODEL: &str = "model";
+static CONFIG_STREAMED: &str = "streamed";
+
+static VAR_OPENAI_CALL: &str = "$openai_call";
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            OPENAI_SERVER_INPUT,
+            Some(new_agent_boxed::<OpenAIServerInputAgent>),
+        )
+        .title("OpenAI Server Input")
+        .description("Serves the flow as a model of the OpenAI API, and outputs the messages of its requests")
+        .category(CATEGORY)
+        .outputs(vec![PORT_MESSAGE])
+        .string_config_with(CONFIG_MODEL, "", |entry| {
+            entry
+                .title("Model")
+                .description("Model name. Empty: the flow name")
+        }),
+    );
+
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            OPENAI_SERVER_OUTPUT,
+            Some(new_agent_boxed::<OpenAIServerOutputAgent>),
+        )
+        .title("OpenAI Server Output")
+        .description("Replies to the OpenAI API request of the flow")
+        .category(CATEGORY)
+        .inputs(vec![PORT_MESSAGE, PORT_DONE])
+        .boolean_config_with(CONFIG_STREAMED, false, |entry| {
+            entry
+                .title("Streamed")
+                .description("The reply is the messages until done, not the first one")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_content() {
+        let mut reply = String::new();
+        assert_eq!(append_content(&mut reply, "Hel"), "Hel");
+        // the whole reply so far
+        assert_eq!(append_content(&mut reply, "Hello"), "lo");
+        // a new part
+        assert_eq!(append_content(&mut reply, " world"), " world");
+        assert_eq!(reply, "Hello world");
+    }
+
+    #[test]
+    fn test_request_messages() {
+        let body = json!({
+            "model": "chat",
+            "messages": [
+                {"role": "system", "content": "Be brief."},
+                {"role": "user", "content": [{"type": "text", "text": "Hi"}]},
+            ],
+        });
+        let messages = request_messages(&body).unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, "system");
+        assert_eq!(messages[1].content, "Hi");
+
+        assert!(request_messages(&json!({"messages": []})).is_err());
+        assert!(request_messages(&json!({"model": "chat"})).is_err());
+    }
+
+    #[test]
+    fn test_read_request() {
+        let raw =
+            b"POST /v1/chat/completions?x=1 HTTP/1.1\r\nHost: a\r\nContent-Length: 2\r\n\r\n{}";
+        let request = futures::executor::block_on(read_request(&mut &raw[..]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/v1/chat/completions");
+        assert_eq!(request.body, b"{}");
+    }
+}
@@ -0,0 +1,308 @@
+use std::sync::Arc;
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentUsage, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use futures::{StreamExt, stream::BoxStream};
+use serde::{Deserialize, Serialize};
+
+use crate::message::{Message, messages_from_data};
+use crate::vcr::{self, VcrProvider};
+
+/// Reply of an LLM provider.
+///
+/// In a stream, `message` holds only the newly generated part of the reply.
+#[derive(Clone, Debug)]
+pub struct LlmChatResponse {
+    pub message: Message,
+    pub response: AgentData,
+    pub usage: Option<AgentUsage>,
+}
+
+pub type LlmChatStream = BoxStream<'static, Result<LlmChatResponse, AgentError>>;
+
+/// Embeddings in the order of the inputs.
+#[derive(Clone, Debug)]
+pub struct LlmEmbeddings {
+    pub embeddings: Vec<Vec<f32>>,
+    pub usage: Option<AgentUsage>,
+}
+
+/// Relevance of a document to the query of a rerank.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LlmRerankResult {
+    /// Index of the document in the request.
+    pub index: usize,
+    pub score: f64,
+}
+
+/// Documents from the most relevant to the query.
+#[derive(Clone, Debug)]
+pub struct LlmRerank {
+    pub results: Vec<LlmRerankResult>,
+    pub usage: Option<AgentUsage>,
+}
+
+/// Common interface of the LLM backends.
+///
+/// `options` are provider specific and are merged into the request.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatResponse, AgentError>;
+
+    async fn chat_stream(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatStream, AgentError>;
+
+    async fn embed(
+        &self,
+        model: &str,
+        inputs: Vec<String>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmEmbeddings, AgentError>;
+
+    /// Scores the documents by their relevance to the query. Fails on the providers without
+    /// a rerank API.
+    async fn rerank(
+        &self,
+        model: &str,
+        query: &str,
+        documents: Vec<String>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmRerank, AgentError> {
+        let _ = (model, query, documents, options);
+        Err(AgentError::InvalidConfig(format!(
+            "{} has no rerank API",
+            self.name()
+        )))
+    }
+}
+
+/// Creates the provider with the given name (`mock`, `ollama`, `openai` or `sakura_ai`).
+///
+/// The provider records or replays its calls when `vcr::set_vcr` is set.
+pub fn new_llm_provider(askit: &ASKit, name: &str) -> Result<Arc<dyn LlmProvider>, AgentError> {
+    let provider = new_llm_provider_inner(askit, name)?;
+    match vcr::vcr() {
+        Some(config) => Ok(Arc::new(VcrProvider::new(provider, config))),
+        None => Ok(provider),
+    }
+}
+
+fn new_llm_provider_inner(askit: &ASKit, name: &str) -> Result<Arc<dyn LlmProvider>, AgentError> {
+    match name {
+        "mock" => Ok(Arc::new(crate::mock::MockProvider::new(askit)?)),
+        #[cfg(feature = "ollama")]
+        "ollama" => Ok(Arc::new(crate::ollama::OllamaProvider::new(askit)?)),
+        #[cfg(feature = "openai")]
+        "openai" => Ok(Arc::new(crate::openai::OpenAIProvider::new(askit)?)),
+        #[cfg(feature = "sakura")]
+        "sakura_ai" => Ok(Arc::new(crate::sakura_ai::new_provider(askit)?)),
+        _ => Err(AgentError::InvalidConfig(format!(
+            "Unknown LLM provider: {}",
+            name
+        ))),
+    }
+}
+
+/// Provider of an agent, created again when the provider name changes.
+#[derive(Default)]
+pub struct LlmProviderCache {
+    provider: Option<Arc<dyn LlmProvider>>,
+}
+
+impl LlmProviderCache {
+    pub fn get(&mut self, askit: &ASKit, name: &str) -> Result<Arc<dyn LlmProvider>, AgentError> {
+        if let Some(provider) = &self.provider
+            && provider.name() == name
+        {
+            return Ok(provider.clone());
+        }
+        let provider = new_llm_provider(askit, name)?;
+        self.provider = Some(provider.clone());
+        Ok(provider)
+    }
+}
+
+/// The options config as JSON, `None` when it is empty.
+pub fn options_from_configs(
+    configs: &AgentConfigs,
+) -> Result<Option<serde_json::Value>, AgentError> {
+    let config_options = configs.get_string_or_default(CONFIG_OPTIONS);
+    if config_options.is_empty() || config_options == "{}" {
+        return Ok(None);
+    }
+    configs
+        .get_parsed::<serde_json::Value>(CONFIG_OPTIONS)
+        .map(Some)
+}
+
+/// Sends the messages to the provider and outputs the reply on the `message` and `response`
+/// ports, emitting the usage of the call.
+///
+/// In a stream, `message` carries the content generated so far. Chunks that only report the
+/// usage, like the last one of an OpenAI stream, are not output. Returns the outputs of a
+/// reply that is not streamed, for the response cache.
+pub(crate) async fn output_chat(
+    agent: &impl AgentOutput,
+    ctx: AgentContext,
+    provider: &dyn LlmProvider,
+    model: &str,
+    messages: Vec<Message>,
+    options: Option<&serde_json::Value>,
+    stream: bool,
+) -> Result<Vec<(String, AgentData)>, AgentError> {
+    if stream {
+        let mut stream = provider.chat_stream(model, messages, options).await?;
+        let mut content = String::new();
+        while let Some(res) = stream.next().await {
+            let res = res?;
+            let usage_only = res.usage.is_some() && res.message.content.is_empty();
+            if let Some(usage) = res.usage {
+                agent.emit_usage(usage);
+            }
+            if usage_only {
+                continue;
+            }
+
+            content.push_str(&res.message.content);
+
+            let mut message = Message::assistant(content.clone());
+            message.id = res.message.id;
+            agent.try_output(ctx.clone(), PORT_MESSAGE, message.into())?;
+
+            agent.try_output(ctx.clone(), PORT_RESPONSE, res.response)?;
+        }
+        return Ok(Vec::new());
+    }
+
+    let res = provider.chat(model, messages, options).await?;
+    if let Some(usage) = res.usage {
+        agent.emit_usage(usage);
+    }
+
+    let message: AgentData = res.message.into();
+    agent.try_output(ctx.clone(), PORT_MESSAGE, message.clone())?;
+    agent.try_output(ctx, PORT_RESPONSE, res.response.clone())?;
+
+    Ok(vec![
+        (PORT_MESSAGE.to_string(), message),
+        (PORT_RESPONSE.to_string(), res.response),
+    ])
+}
+
+// LLM Chat Agent
+pub struct LlmChatAgent {
+    data: AsAgentData,
+    providers: LlmProviderCache,
+}
+
+#[async_trait]
+impl AsAgent for LlmChatAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            providers: LlmProviderCache::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let config_model = &self.configs()?.get_string_or_default(CONFIG_MODEL);
+        if config_model.is_empty() {
+            return Ok(());
+        }
+
+        let messages = messages_from_data(&data)?;
+        if messages.is_empty() {
+            return Ok(());
+        }
+
+        let options = options_from_configs(self.configs()?)?;
+
+        let config_provider = self
+            .configs()?
+            .get_string_or(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER);
+        let provider = self.providers.get(&self.data.askit, &config_provider)?;
+
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
+
+        let use_stream = self.configs()?.get_bool_or_default(CONFIG_STREAM);
+        output_chat(
+            self,
+            ctx,
+            provider.as_ref(),
+            config_model,
+            messages,
+            options.as_ref(),
+            use_stream,
+        )
+        .await?;
+
+        Ok(())
+    }
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static PORT_MESSAGE: &str = "message";
+static PORT_RESPONSE: &str = "response";
+
+static CONFIG_MODEL: &str = "model";
+static CONFIG_OPTIONS: &str = "options";
+static CONFIG_PROVIDER: &str = "provider";
+static CONFIG_STREAM: &str = "stream";
+
+const DEFAULT_CONFIG_PROVIDER: &str = "openai";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_chat",
+            Some(new_agent_boxed::<LlmChatAgent>),
+        )
+        .title("LLM Chat")
+        .category(CATEGORY)
+        .inputs(vec![PORT_MESSAGE])
+        .outputs(vec![PORT_MESSAGE, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER, |entry| {
+            entry.title("Provider")
+        })
+        .string_config_with(CONFIG_MODEL, "", |entry| entry.title("Model"))
+        .boolean_config_with(CONFIG_STREAM, false, |entry| entry.title("Stream"))
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+    );
+}
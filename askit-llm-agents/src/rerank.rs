@@ -0,0 +1,243 @@
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentUsage, AgentValue, AgentValueMap, AsAgent, AsAgentData,
+    async_trait, new_agent_boxed,
+};
+
+use crate::common::CONFIG_OPTIONS;
+use crate::provider::{LlmProviderCache, LlmRerankResult, options_from_configs};
+
+// Text of a document: a string, or an object with a `text` or `content`
+fn document_text(value: &AgentValue) -> Result<String, AgentError> {
+    value
+        .as_str()
+        .or_else(|| value.get_str("text"))
+        .or_else(|| value.get_str("content"))
+        .map(|text| text.to_string())
+        .ok_or_else(|| AgentError::InvalidValue("document has no text".to_string()))
+}
+
+fn documents_of(value: &AgentValue) -> Result<Vec<AgentValue>, AgentError> {
+    value
+        .as_array()
+        .cloned()
+        .ok_or_else(|| AgentError::InvalidValue("documents are not an array".to_string()))
+}
+
+#[derive(Default)]
+struct Reranker {
+    providers: LlmProviderCache,
+}
+
+impl Reranker {
+    async fn rerank(
+        &mut self,
+        askit: &ASKit,
+        configs: &AgentConfigs,
+        query: &str,
+        documents: &[AgentValue],
+    ) -> Result<(Vec<LlmRerankResult>, Option<AgentUsage>), AgentError> {
+        let model = configs.get_string_or_default(CONFIG_MODEL);
+        if model.is_empty() {
+            return Err(AgentError::InvalidConfig("model is not set".to_string()));
+        }
+        let texts = documents
+            .iter()
+            .map(document_text)
+            .collect::<Result<Vec<_>, _>>()?;
+        let options = options_from_configs(configs)?;
+        let provider = self.providers.get(
+            askit,
+            &configs.get_string_or(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER),
+        )?;
+        let res = provider
+            .rerank(&model, query, texts, options.as_ref())
+            .await?;
+
+        let mut results = res.results;
+        if results.iter().any(|result| result.index >= documents.len()) {
+            return Err(AgentError::InvalidValue(
+                "The provider ranked an unknown document".to_string(),
+            ));
+        }
+        let top_n = configs.get_integer_or_default(CONFIG_TOP_N).max(0) as usize;
+        if top_n > 0 {
+            results.truncate(top_n);
+        }
+        Ok((results, res.usage))
+    }
+}
+
+// LLM Rerank Agent
+//
+// Orders the documents by their relevance to each query. The documents are kept until
+// others are given, so that one set of retrieved documents can serve many queries.
+pub struct LlmRerankAgent {
+    data: AsAgentData,
+    reranker: Reranker,
+    documents: Vec<AgentValue>,
+}
+
+#[async_trait]
+impl AsAgent for LlmRerankAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            reranker: Reranker::default(),
+            documents: Vec::new(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PORT_DOCUMENTS {
+            self.documents = documents_of(&data.value)?;
+            return Ok(());
+        }
+
+        // {query, documents} gives both at once
+        let (query, documents) = match (data.get_str("query"), data.get("documents")) {
+            (Some(query), Some(documents)) => (query.to_string(), documents_of(documents)?),
+            _ => {
+                let query = data
+                    .as_str()
+                    .ok_or_else(|| AgentError::InvalidValue("query is not a string".to_string()))?;
+                (query.to_string(), self.documents.clone())
+            }
+        };
+        if query.trim().is_empty() || documents.is_empty() {
+            return Ok(());
+        }
+
+        let configs = self.configs()?.clone();
+        self.check_capability(AgentCapability::Network)?;
+        let askit = self.askit().clone();
+        let _llm_call = askit.acquire_llm_call(self.flow_name()).await?;
+        let (results, usage) = self
+            .reranker
+            .rerank(&askit, &configs, &query, &documents)
+            .await?;
+        if let Some(usage) = usage {
+            self.emit_usage(usage);
+        }
+
+        let mut ranked = Vec::with_capacity(results.len());
+        let mut ranked_documents = Vec::with_capacity(results.len());
+        for result in results {
+            let document = documents[result.index].clone();
+            let mut map = AgentValueMap::new();
+            map.insert(
+                "index".to_string(),
+                AgentValue::integer(result.index as i64),
+            );
+            map.insert("score".to_string(), AgentValue::number(result.score));
+            map.insert("document".to_string(), document.clone());
+            ranked.push(AgentValue::object(map));
+            ranked_documents.push(document);
+        }
+
+        self.try_output(
+            ctx.clone(),
+            PORT_DOCUMENTS,
+            AgentData::from_value(AgentValue::array(ranked_documents)),
+        )?;
+        self.try_output(ctx, PORT_RANKED, AgentData::array("object", ranked))
+    }
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static PORT_QUERY: &str = "query";
+static PORT_DOCUMENTS: &str = "documents";
+static PORT_RANKED: &str = "ranked";
+
+static CONFIG_PROVIDER: &str = "provider";
+static CONFIG_MODEL: &str = "model";
+static CONFIG_TOP_N: &str = "top_n";
+
+const DEFAULT_CONFIG_PROVIDER: &str = "openai";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_rerank",
+            Some(new_agent_boxed::<LlmRerankAgent>),
+        )
+        .title("LLM Rerank")
+        .description("Orders the documents by relevance to the query with a rerank model")
+        .category(CATEGORY)
+        .inputs(vec![PORT_QUERY, PORT_DOCUMENTS])
+        .outputs(vec![PORT_DOCUMENTS, PORT_RANKED])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER, |entry| {
+            entry.title("Provider")
+        })
+        .string_config_with(CONFIG_MODEL, "", |entry| entry.title("Model"))
+        .integer_config_with(CONFIG_TOP_N, 0, |entry| {
+            entry.title("Top N").description("All the documents when 0")
+        })
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rerank_with_mock() {
+        let askit = ASKit::new();
+        let mut configs = AgentConfigs::new();
+        configs.set(CONFIG_PROVIDER.to_string(), AgentValue::string("mock"));
+        configs.set(CONFIG_MODEL.to_string(), AgentValue::string("test"));
+        configs.set(CONFIG_TOP_N.to_string(), AgentValue::integer(2));
+
+        let documents = documents_of(
+            &AgentValue::from_json(serde_json::json!([
+                "Rust is a language.",
+                {"text": "The weather is fine."},
+                {"content": "Agents stream data in Rust."},
+            ]))
+            .unwrap(),
+        )
+        .unwrap();
+        let mut reranker = Reranker::default();
+        let (results, usage) = futures::executor::block_on(reranker.rerank(
+            &askit,
+            &configs,
+            "stream data with Rust",
+            &documents,
+        ))
+        .unwrap();
+        let indices = results.iter().map(|r| r.index).collect::<Vec<_>>();
+        assert_eq!(indices, vec![2, 0]);
+        assert!(usage.is_some());
+
+        let documents = vec![AgentValue::integer(1)];
+        assert!(
+            futures::executor::block_on(reranker.rerank(&askit, &configs, "q", &documents))
+                .is_err()
+        );
+    }
+}
@@ -4,19 +4,28 @@ use std::sync::{Arc, Mutex};
 use std::vec;
 
 use agent_stream_kit::{
-    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
-    AsAgent, AsAgentData, async_trait, new_agent_boxed,
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentUsage, AgentValue, AsAgent, AsAgentData, async_trait,
+    new_agent_boxed,
 };
+use async_openai::{
+    Client,
+    config::OpenAIConfig,
+    types::{ChatCompletionRequestMessage, CreateChatCompletionRequestArgs},
+};
+use futures::StreamExt;
 
-use ollama_rs::{generation::chat::request::ChatMessageRequest, models::ModelOptions};
-use sakura_ai_rs::SakuraAI;
-use tokio_stream::StreamExt;
+use crate::common::{CONFIG_MAX_TOKENS, CONFIG_TEMPERATURE, apply_configured_options};
+use crate::message::{Message, messages_from_data};
+use crate::openai::OpenAIProvider;
 
-use crate::message::Message;
+// Sakura AI Engine exposes an OpenAI compatible API
+const SAKURA_AI_API_BASE: &str = "https://api.ai.sakura.ad.jp/v1";
+const SAKURA_AI_API_KEY_ENV: &str = "SAKURA_AI_ENGINE_API_KEY";
 
 // Shared client management for SakuraAI agents
 struct SakuraAIManager {
-    client: Arc<Mutex<Option<SakuraAI>>>,
+    client: Arc<Mutex<Option<Client<OpenAIConfig>>>>,
 }
 
 impl SakuraAIManager {
@@ -26,22 +35,24 @@ impl SakuraAIManager {
         }
     }
 
-    fn get_client(&self, askit: &ASKit) -> Result<SakuraAI, AgentError> {
+    fn get_client(&self, askit: &ASKit) -> Result<Client<OpenAIConfig>, AgentError> {
         let mut client_guard = self.client.lock().unwrap();
 
         if let Some(client) = client_guard.as_ref() {
             return Ok(client.clone());
         }
 
-        let mut new_client = SakuraAI::default();
-
-        if let Some(api_key) = askit
+        let api_key = askit
             .get_global_configs("sakura_ai_chat")
             .and_then(|cfg| cfg.get_string(CONFIG_SAKURA_AI_API_KEY).ok())
             .filter(|key| !key.is_empty())
-        {
-            new_client = new_client.with_api_key(&api_key);
-        }
+            .or_else(|| askit.host_env_var(SAKURA_AI_API_KEY_ENV))
+            .unwrap_or_default();
+
+        let config = OpenAIConfig::new()
+            .with_api_base(SAKURA_AI_API_BASE)
+            .with_api_key(api_key);
+        let new_client = Client::with_config(config);
 
         *client_guard = Some(new_client.clone());
 
@@ -49,6 +60,11 @@ impl SakuraAIManager {
     }
 }
 
+pub(crate) fn new_provider(askit: &ASKit) -> Result<OpenAIProvider, AgentError> {
+    let client = SakuraAIManager::new().get_client(askit)?;
+    Ok(OpenAIProvider::with_client(PROVIDER, client))
+}
+
 // SakuraAI Chat Agent
 pub struct SakuraAIChatAgent {
     data: AsAgentData,
@@ -88,92 +104,87 @@ impl AsAgent for SakuraAIChatAgent {
             return Ok(());
         }
 
-        let mut messages: Vec<Message> = Vec::new();
-
-        if data.is_string() {
-            let message = data.as_str().unwrap_or("");
-            if message.is_empty() {
-                return Ok(());
-            }
-            messages.push(Message::user(message.to_string()));
-        } else if data.is_object() {
-            let obj = data.as_object().unwrap();
-            if obj.contains_key("role") && obj.contains_key("content") {
-                let msg: Message = data.clone().try_into()?;
-                messages.push(msg);
-            } else {
-                if obj.contains_key("history") {
-                    let history_data = obj.get("history").unwrap();
-                    if history_data.is_array() {
-                        let arr = history_data.as_array().unwrap();
-                        for item in arr {
-                            let msg: Message = item.clone().try_into()?;
-                            messages.push(msg);
-                        }
-                    }
-                }
-                if obj.contains_key("message") {
-                    let msg_data = obj.get("message").unwrap();
-                    let msg: Message = msg_data.clone().try_into()?;
-                    messages.push(msg);
-                }
-            }
-        }
-
+        let messages = messages_from_data(&data)?;
         if messages.is_empty() {
             return Ok(());
         }
 
-        let client = self.manager.get_client(self.askit())?;
-        let mut request = ChatMessageRequest::new(
-            config_model.to_string(),
-            messages.into_iter().map(|m| m.into()).collect(),
-        );
-
-        let config_options = self.configs()?.get_string_or_default(CONFIG_OPTIONS);
-        if !config_options.is_empty() && config_options != "{}" {
-            if let Ok(options_json) = serde_json::from_str::<ModelOptions>(&config_options) {
-                request = request.options(options_json);
-            } else {
-                return Err(AgentError::InvalidValue(
-                    "Invalid JSON in options".to_string(),
-                ));
-            }
-        }
+        let messages = messages
+            .into_iter()
+            .map(|m| m.into())
+            .collect::<Vec<ChatCompletionRequestMessage>>();
 
-        let id = uuid::Uuid::new_v4().to_string();
         let use_stream = self.configs()?.get_bool_or_default(CONFIG_STREAM);
+
+        let mut request = CreateChatCompletionRequestArgs::default()
+            .model(config_model)
+            .messages(messages)
+            .stream(use_stream)
+            .build()
+            .map_err(|e| AgentError::InvalidValue(format!("Failed to build request: {}", e)))?;
+
+        request = apply_configured_options(request, self.configs()?, "max_tokens")?;
+
+        self.check_capability(AgentCapability::Network)?;
+        let _llm_call = self.askit().acquire_llm_call(self.flow_name()).await?;
+        let client = self.manager.get_client(self.askit())?;
+
         if use_stream {
             let mut stream = client
-                .send_chat_messages_stream(request)
+                .chat()
+                .create_stream(request)
                 .await
-                .map_err(|e| AgentError::IoError(format!("Ollama Error: {}", e)))?;
-
+                .map_err(|e| AgentError::IoError(format!("SakuraAI Stream Error: {}", e)))?;
             let mut content = String::new();
             while let Some(res) = stream.next().await {
-                let res = res.map_err(|_| AgentError::IoError(format!("Ollama Stream Error")))?;
-
-                content.push_str(&res.message.content);
+                let res =
+                    res.map_err(|e| AgentError::IoError(format!("SakuraAI Stream Error: {}", e)))?;
+                if let Some(usage) = &res.usage {
+                    self.emit_usage(AgentUsage::new(
+                        PROVIDER,
+                        config_model,
+                        usage.prompt_tokens as u64,
+                        usage.completion_tokens as u64,
+                    ));
+                }
+                res.choices.iter().for_each(|c| {
+                    if let Some(ref delta_content) = c.delta.content {
+                        content.push_str(delta_content);
+                    }
+                });
 
                 let mut message = Message::assistant(content.clone());
-                message.id = Some(id.clone());
+                message.id = Some(res.id.clone());
                 self.try_output(ctx.clone(), PORT_MESSAGE, message.into())?;
 
                 let out_response = AgentData::from_serialize(&res)?;
                 self.try_output(ctx.clone(), PORT_RESPONSE, out_response)?;
-
-                if res.done {
-                    break;
-                }
             }
         } else {
             let res = client
-                .send_chat_messages(request)
+                .chat()
+                .create(request)
                 .await
-                .map_err(|e| AgentError::IoError(format!("Ollama Error: {}", e)))?;
+                .map_err(|e| AgentError::IoError(format!("SakuraAI Error: {}", e)))?;
+
+            if let Some(usage) = &res.usage {
+                self.emit_usage(AgentUsage::new(
+                    PROVIDER,
+                    config_model,
+                    usage.prompt_tokens as u64,
+                    usage.completion_tokens as u64,
+                ));
+            }
 
-            let mut message = Message::assistant(res.message.content.clone());
-            message.id = Some(id.clone());
+            let mut content = String::new();
+            res.choices.iter().for_each(|c| {
+                if let Some(ref c) = c.message.content {
+                    content.push_str(c);
+                }
+            });
+
+            let mut message = Message::assistant(content);
+            message.id = Some(res.id.clone());
             self.try_output(ctx.clone(), PORT_MESSAGE, message.into())?;
 
             let out_response = AgentData::from_serialize(&res)?;
@@ -184,11 +195,71 @@ impl AsAgent for SakuraAIChatAgent {
     }
 }
 
+// SakuraAI Models Agent
+pub struct SakuraAIModelsAgent {
+    data: AsAgentData,
+    manager: SakuraAIManager,
+}
+
+#[async_trait]
+impl AsAgent for SakuraAIModelsAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            manager: SakuraAIManager::new(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let client = self.manager.get_client(self.askit())?;
+
+        let res = client
+            .models()
+            .list()
+            .await
+            .map_err(|e| AgentError::IoError(format!("SakuraAI Error: {}", e)))?;
+
+        let models = res
+            .data
+            .iter()
+            .map(|m| AgentValue::string(m.id.clone()))
+            .collect::<Vec<_>>();
+        self.try_output(ctx.clone(), PORT_MODELS, AgentData::array("string", models))?;
+
+        let out_response = AgentData::from_serialize(&res)?;
+        self.try_output(ctx, PORT_RESPONSE, out_response)?;
+
+        Ok(())
+    }
+}
+
 static AGENT_KIND: &str = "agent";
+static PROVIDER: &str = "sakura_ai";
 static CATEGORY: &str = "LLM";
 
 static PORT_MESSAGE: &str = "message";
+static PORT_MODELS: &str = "models";
 static PORT_RESPONSE: &str = "response";
+static PORT_UNIT: &str = "unit";
 
 static CONFIG_SAKURA_AI_API_KEY: &str = "sakura_ai_api_key";
 static CONFIG_STREAM: &str = "stream";
@@ -209,6 +280,7 @@ pub fn register_agents(askit: &ASKit) {
         .category(CATEGORY)
         .inputs(vec![PORT_MESSAGE])
         .outputs(vec![PORT_MESSAGE, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Network])
         .custom_global_config_with(
             CONFIG_SAKURA_AI_API_KEY,
             "",
@@ -216,9 +288,28 @@ pub fn register_agents(askit: &ASKit) {
             |entry| entry.title("Sakura AI API Key"),
         )
         .string_config_with(CONFIG_MODEL, DEFAULT_CONFIG_MODEL, |entry| {
-            entry.title("Model")
+            entry.title("Model").required()
         })
         .boolean_config_with(CONFIG_STREAM, false, |entry| entry.title("Stream"))
+        .custom_config_with(CONFIG_TEMPERATURE, (), "number", |entry| {
+            entry.title("Temperature")
+        })
+        .custom_config_with(CONFIG_MAX_TOKENS, (), "integer", |entry| {
+            entry.title("Max Tokens")
+        })
         .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
     );
+
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "sakura_ai_models",
+            Some(new_agent_boxed::<SakuraAIModelsAgent>),
+        )
+        .title("SakuraAI Models")
+        .category(CATEGORY)
+        .inputs(vec![PORT_UNIT])
+        .outputs(vec![PORT_MODELS, PORT_RESPONSE])
+        .capabilities(vec![AgentCapability::Network]),
+    );
 }
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
+    AgentValue, AgentValueMap, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+
+use crate::message::{Message, MessageHistory};
+
+// Session Store
+//
+// Histories of concurrent conversations, evicted when idle for too long
+// or when the store is full (least recently used first).
+
+struct Session {
+    history: MessageHistory,
+    last_used: Instant,
+}
+
+pub struct SessionStore {
+    sessions: HashMap<String, Session>,
+    max_sessions: usize,
+    ttl: Option<Duration>,
+}
+
+impl SessionStore {
+    /// `max_sessions` of 0 and a `ttl` of `None` keep the sessions forever.
+    pub fn new(max_sessions: usize, ttl: Option<Duration>) -> Self {
+        Self {
+            sessions: HashMap::new(),
+            max_sessions,
+            ttl,
+        }
+    }
+
+    pub fn set_limits(&mut self, max_sessions: usize, ttl: Option<Duration>) {
+        self.max_sessions = max_sessions;
+        self.ttl = ttl;
+    }
+
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    pub fn contains(&self, session_id: &str) -> bool {
+        self.sessions.contains_key(session_id)
+    }
+
+    /// Returns the history of the session, creating it if needed.
+    pub fn history(&mut self, session_id: &str) -> &mut MessageHistory {
+        self.evict(session_id);
+        let session = self
+            .sessions
+            .entry(session_id.to_string())
+            .or_insert_with(|| Session {
+                history: MessageHistory::new(vec![], 0),
+                last_used: Instant::now(),
+            });
+        session.last_used = Instant::now();
+        &mut session.history
+    }
+
+    pub fn remove(&mut self, session_id: &str) {
+        self.sessions.remove(session_id);
+    }
+
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+    }
+
+    // Drops the expired sessions, then the least recently used ones to make room.
+    fn evict(&mut self, session_id: &str) {
+        if let Some(ttl) = self.ttl {
+            self.sessions
+                .retain(|_, session| session.last_used.elapsed() <= ttl);
+        }
+        if self.max_sessions == 0 || self.sessions.contains_key(session_id) {
+            return;
+        }
+        while self.sessions.len() >= self.max_sessions {
+            let Some(oldest) = self
+                .sessions
+                .iter()
+                .min_by_key(|(_, session)| session.last_used)
+                .map(|(id, _)| id.clone())
+            else {
+                break;
+            };
+            self.sessions.remove(&oldest);
+        }
+    }
+
+    /// The histories keyed by session id.
+    pub fn to_value(&self) -> AgentValue {
+        let mut map = AgentValueMap::new();
+        for (id, session) in &self.sessions {
+            map.insert(
+                id.clone(),
+                AgentValue::array(
+                    session
+                        .history
+                        .messages()
+                        .into_iter()
+                        .map(|m| m.into())
+                        .collect(),
+                ),
+            );
+        }
+        AgentValue::object(map)
+    }
+
+    /// Replaces the sessions with the ones saved by `to_value`.
+    pub fn restore(&mut self, value: &AgentValue) -> Result<(), AgentError> {
+        let map = value
+            .as_object()
+            .ok_or_else(|| AgentError::InvalidValue("sessions must be an object".to_string()))?;
+        let mut sessions = HashMap::new();
+        for (id, messages) in map.iter() {
+            let messages = messages
+                .as_array()
+                .ok_or_else(|| AgentError::InvalidValue("history must be an array".to_string()))?
+                .iter()
+                .map(|v| v.clone().try_into())
+                .collect::<Result<Vec<Message>, AgentError>>()?;
+            sessions.insert(
+                id.clone(),
+                Session {
+                    history: MessageHistory::new(messages, 0),
+                    last_used: Instant::now(),
+                },
+            );
+        }
+        self.sessions = sessions;
+        Ok(())
+    }
+}
+
+// Session Agent
+//
+// Keeps a history per session, so that one flow serves many conversations.
+// The session id is read from the context variable named by the session_key config,
+// or else from the field of the same name in the message.
+pub struct SessionAgent {
+    data: AsAgentData,
+    store: SessionStore,
+}
+
+impl SessionAgent {
+    fn update_limits(&mut self) -> Result<(), AgentError> {
+        let configs = self.configs()?;
+        let max_sessions = configs.get_integer_or(CONFIG_MAX_SESSIONS, DEFAULT_MAX_SESSIONS);
+        let ttl = configs.get_integer_or(CONFIG_SESSION_TTL, DEFAULT_SESSION_TTL);
+        let ttl = (ttl > 0).then(|| Duration::from_secs(ttl as u64));
+        self.store.set_limits(max_sessions.max(0) as usize, ttl);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsAgent for SessionAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            store: SessionStore::new(
+                DEFAULT_MAX_SESSIONS as usize,
+                Some(Duration::from_secs(DEFAULT_SESSION_TTL as u64)),
+            ),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn state(&self) -> Option<AgentValue> {
+        if self.store.is_empty() {
+            return None;
+        }
+        Some(self.store.to_value())
+    }
+
+    fn restore_state(&mut self, state: AgentValue) -> Result<(), AgentError> {
+        self.store.restore(&state)
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PORT_RESET {
+            // a session id resets that session, anything else all of them
+            match data.as_str() {
+                Some(session_id) => self.store.remove(session_id),
+                None => self.store.clear(),
+            }
+            return Ok(());
+        }
+
+        self.update_limits()?;
+        let key = self
+            .configs()?
+            .get_string_or(CONFIG_SESSION_KEY, DEFAULT_SESSION_KEY);
+        let session_id = session_id(&key, &ctx, &data);
+        let ctx = ctx.with_var(key, AgentValue::string(session_id.clone()));
+
+        let message: Message = data.try_into().map_err(|e| {
+            AgentError::InvalidValue(format!("Failed to convert data to Message: {}", e))
+        })?;
+
+        let history_size = self.configs()?.get_integer_or_default(CONFIG_HISTORY_SIZE);
+        let history = self.store.history(&session_id);
+        history.set_size(history_size);
+        history.push(message.clone());
+        let history = history.clone();
+        self.try_output(ctx.clone(), PORT_HISTORY, history.clone().into())?;
+
+        if message.role != "user" {
+            return Ok(());
+        }
+
+        let messages = AgentData::object(
+            [
+                ("message".to_string(), message.into()),
+                (
+                    "history".to_string(),
+                    AgentValue::array(history.messages().into_iter().map(|m| m.into()).collect()),
+                ),
+            ]
+            .into(),
+        );
+        self.try_output(ctx, PORT_MESSAGE_HISTORY, messages)?;
+
+        Ok(())
+    }
+}
+
+fn session_id(key: &str, ctx: &AgentContext, data: &AgentData) -> String {
+    let value = ctx
+        .get_var(key)
+        .or_else(|| data.as_object().and_then(|obj| obj.get(key)));
+    match value {
+        Some(AgentValue::String(s)) => s.to_string(),
+        Some(AgentValue::Integer(i)) => i.to_string(),
+        Some(AgentValue::Unsigned(u)) => u.to_string(),
+        _ => DEFAULT_SESSION_ID.to_string(),
+    }
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static PORT_MESSAGE: &str = "message";
+static PORT_MESSAGE_HISTORY: &str = "message_history";
+static PORT_HISTORY: &str = "history";
+static PORT_RESET: &str = "reset";
+
+static CONFIG_SESSION_KEY: &str = "session_key";
+static CONFIG_HISTORY_SIZE: &str = "history_size";
+static CONFIG_MAX_SESSIONS: &str = "max_sessions";
+static CONFIG_SESSION_TTL: &str = "session_ttl";
+
+const DEFAULT_SESSION_KEY: &str = "session_id";
+const DEFAULT_SESSION_ID: &str = "default";
+const DEFAULT_MAX_SESSIONS: i64 = 1000;
+const DEFAULT_SESSION_TTL: i64 = 3600;
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_session",
+            Some(new_agent_boxed::<SessionAgent>),
+        )
+        .title("Session")
+        .category(CATEGORY)
+        .inputs(vec![PORT_MESSAGE, PORT_RESET])
+        .outputs(vec![PORT_MESSAGE_HISTORY, PORT_HISTORY])
+        .string_config_with(CONFIG_SESSION_KEY, DEFAULT_SESSION_KEY, |entry| {
+            entry
+                .title("Session Key")
+                .description("Context variable or message field holding the session id")
+        })
+        .integer_config_with(CONFIG_HISTORY_SIZE, 0, |entry| entry.title("History Size"))
+        .integer_config_with(CONFIG_MAX_SESSIONS, DEFAULT_MAX_SESSIONS, |entry| {
+            entry.title("Max Sessions")
+        })
+        .integer_config_with(CONFIG_SESSION_TTL, DEFAULT_SESSION_TTL, |entry| {
+            entry.title("Session TTL (sec)")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_store() {
+        let mut store = SessionStore::new(2, None);
+        store.history("a").push(Message::user("hi a".to_string()));
+        store.history("b").push(Message::user("hi b".to_string()));
+        assert_eq!(store.history("a").messages().len(), 1);
+
+        // "b" is the least recently used one
+        store.history("c");
+        assert!(store.contains("a"));
+        assert!(!store.contains("b"));
+        assert!(store.contains("c"));
+
+        let value = store.to_value();
+        let mut restored = SessionStore::new(2, None);
+        restored.restore(&value).unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.history("a").messages()[0].content, "hi a");
+
+        let mut store = SessionStore::new(0, Some(Duration::ZERO));
+        store.history("a");
+        std::thread::sleep(Duration::from_millis(2));
+        store.history("b");
+        assert!(!store.contains("a"));
+    }
+}
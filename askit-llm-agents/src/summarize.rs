@@ -0,0 +1,368 @@
+use std::sync::Arc;
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentUsage, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use futures::{StreamExt, TryStreamExt};
+
+use crate::common::CONFIG_OPTIONS;
+use crate::message::Message;
+use crate::provider::{LlmProvider, LlmProviderCache, options_from_configs};
+
+/// Rough number of tokens of the text, about 4 characters per token.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Splits the text into chunks of at most `max_chars` characters.
+///
+/// Chunks end at paragraph breaks where possible, then at line or sentence ends,
+/// and are cut mid-sentence only when a sentence alone is too long.
+pub fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for piece in pieces(text, max_chars) {
+        let len = current.chars().count();
+        if len > 0 && len + piece.chars().count() > max_chars {
+            chunks.push(current.trim().to_string());
+            current.clear();
+        }
+        current.push_str(&piece);
+    }
+    if !current.trim().is_empty() {
+        chunks.push(current.trim().to_string());
+    }
+    chunks.retain(|chunk| !chunk.is_empty());
+    chunks
+}
+
+// Paragraphs, or the sentences of the paragraphs too long for a chunk
+fn pieces(text: &str, max_chars: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    for paragraph in text.split_inclusive("\n\n") {
+        if paragraph.chars().count() <= max_chars {
+            pieces.push(paragraph.to_string());
+            continue;
+        }
+        for sentence in split_sentences(paragraph) {
+            if sentence.chars().count() <= max_chars {
+                pieces.push(sentence);
+                continue;
+            }
+            let chars = sentence.chars().collect::<Vec<_>>();
+            pieces.extend(chars.chunks(max_chars).map(|c| c.iter().collect()));
+        }
+    }
+    pieces
+}
+
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        current.push(c);
+        let end = match c {
+            '\n' | '。' | '！' | '？' => true,
+            '.' | '!' | '?' => chars.peek().is_none_or(|next| next.is_whitespace()),
+            _ => false,
+        };
+        if end {
+            sentences.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current);
+    }
+    sentences
+}
+
+/// Settings of a map-reduce summarization.
+#[derive(Clone, Debug)]
+pub struct SummarizeOptions {
+    pub model: String,
+    pub options: Option<serde_json::Value>,
+
+    /// Tokens of input a single call may take. Longer text is chunked.
+    pub chunk_tokens: usize,
+
+    /// Chunks summarized at the same time.
+    pub concurrency: usize,
+
+    pub instructions: String,
+}
+
+/// Summarizes the text, chunking it when it is over the token budget of a call.
+///
+/// The chunks are summarized in parallel, and the partial summaries are combined into
+/// one, themselves chunked again while they are still over the budget.
+pub async fn summarize(
+    askit: &ASKit,
+    flow_name: &str,
+    provider: Arc<dyn LlmProvider>,
+    text: &str,
+    options: &SummarizeOptions,
+) -> Result<(String, Vec<AgentUsage>), AgentError> {
+    let chunk_tokens = options.chunk_tokens.max(1);
+    let mut usages = Vec::new();
+    let mut text = text.to_string();
+    let mut prompt = SUMMARIZE_PROMPT;
+
+    for _ in 0..MAX_ROUNDS {
+        if estimate_tokens(&text) <= chunk_tokens {
+            let (summary, usage) =
+                summarize_one(askit, flow_name, &provider, prompt, text, options).await?;
+            usages.extend(usage);
+            return Ok((summary, usages));
+        }
+
+        let chunks = split_text(&text, chunk_tokens * 4);
+        let partials = futures::stream::iter(chunks)
+            .map(|chunk| summarize_one(askit, flow_name, &provider, MAP_PROMPT, chunk, options))
+            .buffered(options.concurrency.max(1))
+            .try_collect::<Vec<_>>()
+            .await?;
+        let mut summaries = Vec::new();
+        for (summary, usage) in partials {
+            summaries.push(summary);
+            usages.extend(usage);
+        }
+
+        let combined = summaries.join("\n\n");
+        if estimate_tokens(&combined) >= estimate_tokens(&text) {
+            return Err(AgentError::InvalidValue(
+                "The partial summaries are not shorter than the text".to_string(),
+            ));
+        }
+        text = combined;
+        prompt = REDUCE_PROMPT;
+    }
+
+    Err(AgentError::InvalidValue(format!(
+        "The text is still over {} tokens after {} rounds of summarization",
+        chunk_tokens, MAX_ROUNDS
+    )))
+}
+
+async fn summarize_one(
+    askit: &ASKit,
+    flow_name: &str,
+    provider: &Arc<dyn LlmProvider>,
+    prompt: &str,
+    text: String,
+    options: &SummarizeOptions,
+) -> Result<(String, Option<AgentUsage>), AgentError> {
+    let mut system = prompt.to_string();
+    if !options.instructions.is_empty() {
+        system.push_str("\n\n");
+        system.push_str(&options.instructions);
+    }
+    let _llm_call = askit.acquire_llm_call(flow_name).await?;
+    let res = provider
+        .chat(
+            &options.model,
+            vec![Message::system(system), Message::user(text)],
+            options.options.as_ref(),
+        )
+        .await?;
+    Ok((res.message.content.trim().to_string(), res.usage))
+}
+
+// LLM Summarize Agent
+pub struct LlmSummarizeAgent {
+    data: AsAgentData,
+    providers: LlmProviderCache,
+}
+
+#[async_trait]
+impl AsAgent for LlmSummarizeAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            providers: LlmProviderCache::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let text = match data.as_str() {
+            Some(text) => text.to_string(),
+            None => {
+                let message: Message = data.try_into().map_err(|e| {
+                    AgentError::InvalidValue(format!("Failed to convert data to Message: {}", e))
+                })?;
+                message.content
+            }
+        };
+        if text.trim().is_empty() {
+            return Ok(());
+        }
+
+        let configs = self.configs()?.clone();
+        let options = SummarizeOptions {
+            model: configs.get_string_or_default(CONFIG_MODEL),
+            options: options_from_configs(&configs)?,
+            chunk_tokens: configs
+                .get_integer_or(CONFIG_CHUNK_TOKENS, DEFAULT_CHUNK_TOKENS)
+                .max(1) as usize,
+            concurrency: configs
+                .get_integer_or(CONFIG_CONCURRENCY, DEFAULT_CONCURRENCY)
+                .max(1) as usize,
+            instructions: configs.get_string_or_default(CONFIG_INSTRUCTIONS),
+        };
+        if options.model.is_empty() {
+            return Err(AgentError::InvalidConfig("model is not set".to_string()));
+        }
+
+        self.check_capability(AgentCapability::Network)?;
+        let askit = self.askit().clone();
+        let provider = self.providers.get(
+            &askit,
+            &configs.get_string_or(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER),
+        )?;
+        let (summary, usages) =
+            summarize(&askit, self.flow_name(), provider, &text, &options).await?;
+        for usage in usages {
+            self.emit_usage(usage);
+        }
+
+        self.try_output(ctx, PORT_SUMMARY, AgentData::string(summary))
+    }
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "LLM";
+
+static PORT_TEXT: &str = "text";
+static PORT_SUMMARY: &str = "summary";
+
+static CONFIG_PROVIDER: &str = "provider";
+static CONFIG_MODEL: &str = "model";
+static CONFIG_CHUNK_TOKENS: &str = "chunk_tokens";
+static CONFIG_CONCURRENCY: &str = "concurrency";
+static CONFIG_INSTRUCTIONS: &str = "instructions";
+
+const DEFAULT_CONFIG_PROVIDER: &str = "openai";
+const DEFAULT_CHUNK_TOKENS: i64 = 2000;
+const DEFAULT_CONCURRENCY: i64 = 4;
+
+const MAX_ROUNDS: usize = 5;
+
+static SUMMARIZE_PROMPT: &str = "Summarize the text given by the user. Keep the key facts, names and numbers. Reply with only the summary.";
+static MAP_PROMPT: &str = "The text given by the user is one part of a longer document. Summarize this part, keeping the key facts, names and numbers. Reply with only the summary.";
+static REDUCE_PROMPT: &str = "The text given by the user is a series of summaries of consecutive parts of a document. Combine them into a single coherent summary of the whole document, without repeating points. Reply with only the summary.";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "llm_summarize",
+            Some(new_agent_boxed::<LlmSummarizeAgent>),
+        )
+        .title("LLM Summarize")
+        .description("Summarizes long text by chunks, then combines the partial summaries")
+        .category(CATEGORY)
+        .inputs(vec![PORT_TEXT])
+        .outputs(vec![PORT_SUMMARY])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROVIDER, DEFAULT_CONFIG_PROVIDER, |entry| {
+            entry.title("Provider")
+        })
+        .string_config_with(CONFIG_MODEL, "", |entry| entry.title("Model"))
+        .integer_config_with(CONFIG_CHUNK_TOKENS, DEFAULT_CHUNK_TOKENS, |entry| {
+            entry
+                .title("Chunk Tokens")
+                .description("Input tokens of a single call")
+        })
+        .integer_config_with(CONFIG_CONCURRENCY, DEFAULT_CONCURRENCY, |entry| {
+            entry.title("Concurrency")
+        })
+        .text_config_with(CONFIG_INSTRUCTIONS, "", |entry| entry.title("Instructions"))
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| entry.title("Options")),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mock::MockProvider;
+
+    #[test]
+    fn test_split_text() {
+        let text = "First paragraph.\n\nSecond one. It has two sentences.\n\nThird.";
+        let chunks = split_text(text, 40);
+        assert_eq!(
+            chunks,
+            vec![
+                "First paragraph.",
+                "Second one. It has two sentences.",
+                "Third."
+            ]
+        );
+        assert!(chunks.iter().all(|chunk| chunk.chars().count() <= 40));
+
+        // a long sentence is cut
+        let chunks = split_text(&"a".repeat(25), 10);
+        assert_eq!(chunks.len(), 3);
+
+        assert_eq!(split_text("short", 100), vec!["short"]);
+        assert!(split_text("  ", 100).is_empty());
+    }
+
+    #[test]
+    fn test_summarize_map_reduce() {
+        let askit = ASKit::new();
+        let provider: Arc<dyn LlmProvider> = Arc::new(MockProvider::new(&askit).unwrap());
+        let options = SummarizeOptions {
+            model: "test".to_string(),
+            options: Some(serde_json::json!({"response": "summary"})),
+            chunk_tokens: 25,
+            concurrency: 2,
+            instructions: String::new(),
+        };
+
+        let text = "This sentence has some words. ".repeat(10);
+        let (summary, usages) = futures::executor::block_on(summarize(
+            &askit,
+            "flow",
+            provider.clone(),
+            &text,
+            &options,
+        ))
+        .unwrap();
+        assert_eq!(summary, "summary");
+        // the chunks, then the reduce call
+        assert_eq!(usages.len(), split_text(&text, 100).len() + 1);
+
+        let (_, usages) = futures::executor::block_on(summarize(
+            &askit,
+            "flow",
+            provider,
+            "Short text.",
+            &options,
+        ))
+        .unwrap();
+        assert_eq!(usages.len(), 1);
+    }
+}
@@ -0,0 +1,316 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use agent_stream_kit::{AgentData, AgentError, AgentUsage, async_trait};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::message::Message;
+use crate::provider::{
+    LlmChatResponse, LlmChatStream, LlmEmbeddings, LlmProvider, LlmRerank, LlmRerankResult,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VcrMode {
+    /// Calls the provider and saves every reply.
+    Record,
+    /// Serves saved replies and fails when a request has none.
+    Replay,
+    /// Serves saved replies and records the requests that have none.
+    Auto,
+}
+
+/// Record-and-replay setting of the LLM providers.
+#[derive(Clone, Debug)]
+pub struct VcrConfig {
+    pub mode: VcrMode,
+    /// Directory of the recordings, one JSON file per request.
+    pub dir: PathBuf,
+}
+
+impl VcrConfig {
+    pub fn new(mode: VcrMode, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            mode,
+            dir: dir.into(),
+        }
+    }
+}
+
+/// Makes the providers created by `new_llm_provider` record or replay their calls.
+/// None turns it off. Providers already created are not affected.
+pub fn set_vcr(config: Option<VcrConfig>) {
+    *VCR.lock().unwrap() = config;
+}
+
+pub(crate) fn vcr() -> Option<VcrConfig> {
+    VCR.lock().unwrap().clone()
+}
+
+static VCR: Mutex<Option<VcrConfig>> = Mutex::new(None);
+
+// Reply saved for a request
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct RecordedResponse {
+    message: Message,
+    response: AgentData,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usage: Option<AgentUsage>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Recording {
+    request: serde_json::Value,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    responses: Vec<RecordedResponse>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    embeddings: Option<Vec<Vec<f32>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rerank: Option<Vec<LlmRerankResult>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usage: Option<AgentUsage>,
+}
+
+/// Provider wrapper saving replies to disk keyed by a hash of the request, and serving them back.
+pub struct VcrProvider {
+    inner: Arc<dyn LlmProvider>,
+    config: VcrConfig,
+}
+
+impl VcrProvider {
+    pub fn new(inner: Arc<dyn LlmProvider>, config: VcrConfig) -> Self {
+        Self { inner, config }
+    }
+
+    fn request(
+        &self,
+        call: &str,
+        model: &str,
+        input: serde_json::Value,
+        options: Option<&serde_json::Value>,
+    ) -> serde_json::Value {
+        serde_json::json!({
+            "provider": self.inner.name(),
+            "call": call,
+            "model": model,
+            "input": input,
+            "options": options,
+        })
+    }
+
+    fn path(&self, request: &serde_json::Value) -> PathBuf {
+        // serde_json sorts the keys of objects, so the text is stable
+        let key = fnv1a(request.to_string().as_bytes());
+        self.config.dir.join(format!("{:016x}.json", key))
+    }
+
+    // Some(recording) when it should be replayed, None when the call should be recorded.
+    fn load(&self, request: &serde_json::Value) -> Result<Option<Recording>, AgentError> {
+        let path = self.path(request);
+        match self.config.mode {
+            VcrMode::Record => Ok(None),
+            VcrMode::Auto if !path.exists() => Ok(None),
+            _ => {
+                let json = std::fs::read_to_string(&path).map_err(|e| {
+                    AgentError::IoError(format!(
+                        "No recording of {} request at {}: {}",
+                        self.inner.name(),
+                        path.display(),
+                        e
+                    ))
+                })?;
+                let recording = serde_json::from_str(&json)
+                    .map_err(|e| AgentError::SerializationError(e.to_string()))?;
+                Ok(Some(recording))
+            }
+        }
+    }
+
+    fn save(&self, recording: &Recording) -> Result<(), AgentError> {
+        let path = self.path(&recording.request);
+        std::fs::create_dir_all(&self.config.dir)
+            .map_err(|e| AgentError::IoError(e.to_string()))?;
+        let json = serde_json::to_string_pretty(recording)
+            .map_err(|e| AgentError::SerializationError(e.to_string()))?;
+        std::fs::write(&path, json).map_err(|e| AgentError::IoError(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl LlmProvider for VcrProvider {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn chat(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatResponse, AgentError> {
+        let request = self.request("chat", model, to_json(&messages)?, options);
+        if let Some(recording) = self.load(&request)? {
+            let Some(res) = recording.responses.into_iter().next() else {
+                return Err(AgentError::InvalidValue("Empty recording".to_string()));
+            };
+            return Ok(res.into());
+        }
+
+        let res = self.inner.chat(model, messages, options).await?;
+        self.save(&Recording {
+            request,
+            responses: vec![res.clone().into()],
+            embeddings: None,
+            rerank: None,
+            usage: None,
+        })?;
+        Ok(res)
+    }
+
+    async fn chat_stream(
+        &self,
+        model: &str,
+        messages: Vec<Message>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmChatStream, AgentError> {
+        let request = self.request("chat_stream", model, to_json(&messages)?, options);
+        let responses = match self.load(&request)? {
+            Some(recording) => recording.responses,
+            None => {
+                // the whole stream is read before it is saved and served
+                let mut stream = self.inner.chat_stream(model, messages, options).await?;
+                let mut responses = Vec::new();
+                while let Some(res) = stream.next().await {
+                    responses.push(RecordedResponse::from(res?));
+                }
+                self.save(&Recording {
+                    request,
+                    responses: responses.clone(),
+                    embeddings: None,
+                    rerank: None,
+                    usage: None,
+                })?;
+                responses
+            }
+        };
+        Ok(futures::stream::iter(responses.into_iter().map(|res| Ok(res.into()))).boxed())
+    }
+
+    async fn embed(
+        &self,
+        model: &str,
+        inputs: Vec<String>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmEmbeddings, AgentError> {
+        let request = self.request("embed", model, to_json(&inputs)?, options);
+        if let Some(recording) = self.load(&request)? {
+            return Ok(LlmEmbeddings {
+                embeddings: recording.embeddings.unwrap_or_default(),
+                usage: recording.usage,
+            });
+        }
+
+        let res = self.inner.embed(model, inputs, options).await?;
+        self.save(&Recording {
+            request,
+            responses: Vec::new(),
+            embeddings: Some(res.embeddings.clone()),
+            rerank: None,
+            usage: res.usage.clone(),
+        })?;
+        Ok(res)
+    }
+
+    async fn rerank(
+        &self,
+        model: &str,
+        query: &str,
+        documents: Vec<String>,
+        options: Option<&serde_json::Value>,
+    ) -> Result<LlmRerank, AgentError> {
+        let input = serde_json::json!({"query": query, "documents": documents});
+        let request = self.request("rerank", model, input, options);
+        if let Some(recording) = self.load(&request)? {
+            return Ok(LlmRerank {
+                results: recording.rerank.unwrap_or_default(),
+                usage: recording.usage,
+            });
+        }
+
+        let res = self.inner.rerank(model, query, documents, options).await?;
+        self.save(&Recording {
+            request,
+            responses: Vec::new(),
+            embeddings: None,
+            rerank: Some(res.results.clone()),
+            usage: res.usage.clone(),
+        })?;
+        Ok(res)
+    }
+}
+
+impl From<LlmChatResponse> for RecordedResponse {
+    fn from(res: LlmChatResponse) -> Self {
+        Self {
+            message: res.message,
+            response: res.response,
+            usage: res.usage,
+        }
+    }
+}
+
+impl From<RecordedResponse> for LlmChatResponse {
+    fn from(res: RecordedResponse) -> Self {
+        Self {
+            message: res.message,
+            response: res.response,
+            usage: res.usage,
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<serde_json::Value, AgentError> {
+    serde_json::to_value(value).map_err(|e| AgentError::SerializationError(e.to_string()))
+}
+
+// Stable across builds, unlike the hasher of std
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use agent_stream_kit::ASKit;
+
+    use super::*;
+    use crate::mock::MockProvider;
+
+    #[test]
+    fn test_record_and_replay() {
+        let dir = std::env::temp_dir().join(format!("askit-vcr-{}", uuid::Uuid::new_v4()));
+        let inner: Arc<dyn LlmProvider> = Arc::new(MockProvider::new(&ASKit::new()).unwrap());
+        let messages = vec![Message::user("hello".to_string())];
+        let options = serde_json::json!({"responses": ["first", "second"]});
+
+        let recorder = VcrProvider::new(inner.clone(), VcrConfig::new(VcrMode::Record, &dir));
+        let res = futures::executor::block_on(recorder.chat("m", messages.clone(), Some(&options)))
+            .unwrap();
+        assert_eq!(res.message.content, "first");
+
+        // the mock would answer "second" now
+        let player = VcrProvider::new(inner, VcrConfig::new(VcrMode::Replay, &dir));
+        let res = futures::executor::block_on(player.chat("m", messages.clone(), Some(&options)))
+            .unwrap();
+        assert_eq!(res.message.content, "first");
+
+        let other = vec![Message::user("bye".to_string())];
+        assert!(futures::executor::block_on(player.chat("m", other, Some(&options))).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,575 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+use std::time::Duration;
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use fantoccini::elements::Element;
+use fantoccini::error::{CmdError, NewSessionError};
+use fantoccini::wd::{Capabilities, TimeoutConfiguration};
+use fantoccini::{Client, ClientBuilder, Locator};
+use serde_json::json;
+use tokio::sync::Mutex;
+
+// Browser
+//
+// The agents drive a browser with fantoccini through a WebDriver server, such as
+// chromedriver or geckodriver, at the URL of the global config of std_browser_navigate. Agents with the
+// same session name share one browser, so a flow can navigate with one agent and extract
+// with another. The browser is closed when the last agent of its session stops.
+
+// Browser Navigate Agent
+struct BrowserNavigateAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for BrowserNavigateAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        release_session(self);
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let url = data
+            .as_str()
+            .ok_or_else(|| AgentError::InvalidValue("url is not a string".to_string()))?;
+
+        let client = acquire_session(self).await?;
+        client.goto(url).await.map_err(webdriver_error)?;
+        let page = page(&client).await?;
+        self.try_output(ctx, PIN_PAGE, page)
+    }
+}
+
+// Browser Extract Text Agent
+struct BrowserExtractTextAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for BrowserExtractTextAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        release_session(self);
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let selector = configs.get_string_or(CONFIG_SELECTOR, SELECTOR_DEFAULT);
+        let html = configs.get_bool_or_default(CONFIG_HTML);
+
+        let client = acquire_session(self).await?;
+        let elements = client
+            .find_all(Locator::Css(&selector))
+            .await
+            .map_err(webdriver_error)?;
+        let mut texts = Vec::new();
+        for element in elements {
+            let text = if html {
+                element.html(false).await
+            } else {
+                element.text().await
+            };
+            texts.push(text.map_err(webdriver_error)?);
+        }
+        self.try_output(ctx, PIN_TEXT, AgentData::string(texts.join("\n")))
+    }
+}
+
+// Browser Screenshot Agent
+struct BrowserScreenshotAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for BrowserScreenshotAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        release_session(self);
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let selector = self.configs()?.get_string_or_default(CONFIG_SELECTOR);
+
+        let client = acquire_session(self).await?;
+        let png = if selector.is_empty() {
+            client.screenshot().await
+        } else {
+            find(&client, &selector).await?.screenshot().await
+        }
+        .map_err(webdriver_error)?;
+        let data = AgentData::string(format!("data:image/png;base64,{}", STANDARD.encode(png)));
+        self.try_output(ctx, PIN_IMAGE, data)
+    }
+}
+
+// Browser Click Agent
+struct BrowserClickAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for BrowserClickAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        release_session(self);
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let selector = self.configs()?.get_string(CONFIG_SELECTOR)?;
+
+        let client = acquire_session(self).await?;
+        find(&client, &selector)
+            .await?
+            .click()
+            .await
+            .map_err(webdriver_error)?;
+        let page = page(&client).await?;
+        self.try_output(ctx, PIN_PAGE, page)
+    }
+}
+
+// Browser Fill Form Agent
+struct BrowserFillFormAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for BrowserFillFormAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        release_session(self);
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let submit = self.configs()?.get_string_or_default(CONFIG_SUBMIT);
+        let fields = data.value.iter_object().map_err(|_| {
+            AgentError::InvalidValue("values must be an object of selector to text".to_string())
+        })?;
+
+        let client = acquire_session(self).await?;
+        for (selector, value) in fields {
+            let text = match value.as_str() {
+                Some(s) => s.to_string(),
+                None => value.to_json().to_string(),
+            };
+            let element = find(&client, selector).await?;
+            element.clear().await.map_err(webdriver_error)?;
+            element.send_keys(&text).await.map_err(webdriver_error)?;
+        }
+        if !submit.is_empty() {
+            find(&client, &submit)
+                .await?
+                .click()
+                .await
+                .map_err(webdriver_error)?;
+        }
+        let page = page(&client).await?;
+        self.try_output(ctx, PIN_PAGE, page)
+    }
+}
+
+// Session of the agent, started if it is the first user of its session name.
+async fn acquire_session(agent: &impl Agent) -> Result<Client, AgentError> {
+    let name = agent
+        .configs()?
+        .get_string_or(CONFIG_SESSION, SESSION_DEFAULT);
+    let global_configs = agent
+        .askit()
+        .get_global_configs(NAVIGATE_DEF_NAME)
+        .unwrap_or_default();
+    let url = global_configs.get_string_or(CONFIG_WEBDRIVER_URL, WEBDRIVER_URL_DEFAULT);
+    let headless = global_configs.get_bool_or(CONFIG_HEADLESS, true);
+
+    let mut sessions = SESSIONS.lock().await;
+    if !sessions.contains_key(&name) {
+        let client = start_session(&url, headless).await?;
+        log::info!("Started browser session {}", name);
+        sessions.insert(
+            name.clone(),
+            SharedSession {
+                client,
+                agents: HashSet::new(),
+            },
+        );
+    }
+    let shared = sessions.get_mut(&name).unwrap();
+    shared.agents.insert(agent.id().to_string());
+    Ok(shared.client.clone())
+}
+
+// Releases the session used by the agent, and closes it if no other agent uses it.
+fn release_session(agent: &impl Agent) {
+    let agent_id = agent.id().to_string();
+    agent.runtime().spawn(async move {
+        let mut sessions = SESSIONS.lock().await;
+        let Some(name) = sessions
+            .iter()
+            .find(|(_, shared)| shared.agents.contains(&agent_id))
+            .map(|(name, _)| name.clone())
+        else {
+            return;
+        };
+        let shared = sessions.get_mut(&name).unwrap();
+        shared.agents.remove(&agent_id);
+        if shared.agents.is_empty() {
+            let shared = sessions.remove(&name).unwrap();
+            if let Err(e) = shared.client.close().await {
+                log::warn!("Failed to close browser session {}: {}", name, e);
+            }
+        }
+    });
+}
+
+struct SharedSession {
+    client: Client,
+
+    // ids of the agents using the session
+    agents: HashSet<String>,
+}
+
+async fn start_session(webdriver_url: &str, headless: bool) -> Result<Client, AgentError> {
+    let chrome_args = if headless {
+        vec!["--headless=new"]
+    } else {
+        vec![]
+    };
+    let firefox_args = if headless { vec!["-headless"] } else { vec![] };
+    let mut capabilities = Capabilities::new();
+    capabilities.insert(
+        "goog:chromeOptions".to_string(),
+        json!({ "args": chrome_args }),
+    );
+    capabilities.insert(
+        "moz:firefoxOptions".to_string(),
+        json!({ "args": firefox_args }),
+    );
+
+    let client = ClientBuilder::rustls()
+        .map_err(|e| AgentError::external("Failed to create the WebDriver client", e))?
+        .capabilities(capabilities)
+        .connect(webdriver_url)
+        .await
+        .map_err(|e| match e {
+            NewSessionError::Failed(_) | NewSessionError::FailedC(_) | NewSessionError::Lost(_) => {
+                AgentError::transient("Failed to start the browser session", e)
+            }
+            _ => AgentError::external("Failed to start the browser session", e),
+        })?;
+    client
+        .update_timeouts(TimeoutConfiguration::new(
+            Some(COMMAND_TIMEOUT),
+            Some(COMMAND_TIMEOUT),
+            None,
+        ))
+        .await
+        .map_err(webdriver_error)?;
+    Ok(client)
+}
+
+// {url, title} of the current page
+async fn page(client: &Client) -> Result<AgentData, AgentError> {
+    let url = client.current_url().await.map_err(webdriver_error)?;
+    let title = client.title().await.map_err(webdriver_error)?;
+    AgentData::from_json(json!({ "url": url.as_str(), "title": title }))
+}
+
+async fn find(client: &Client, selector: &str) -> Result<Element, AgentError> {
+    client
+        .find(Locator::Css(selector))
+        .await
+        .map_err(webdriver_error)
+}
+
+// Failures to reach the WebDriver server may pass, errors of the browser will not.
+fn webdriver_error(e: CmdError) -> AgentError {
+    match e {
+        CmdError::Failed(_) | CmdError::FailedC(_) | CmdError::Lost(_) => {
+            AgentError::transient("WebDriver command failed", e)
+        }
+        _ => AgentError::external("WebDriver command failed", e),
+    }
+}
+
+// session name -> session shared by the agents
+static SESSIONS: LazyLock<Mutex<HashMap<String, SharedSession>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// timeouts of page loads and scripts in the browser
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+
+static AGENT_KIND: &str = "Agent";
+static CATEGORY: &str = "Core/Browser";
+
+static NAVIGATE_DEF_NAME: &str = "std_browser_navigate";
+
+static PIN_URL: &str = "url";
+static PIN_PAGE: &str = "page";
+static PIN_TEXT: &str = "text";
+static PIN_IMAGE: &str = "image";
+static PIN_VALUES: &str = "values";
+
+static CONFIG_WEBDRIVER_URL: &str = "webdriver_url";
+static CONFIG_HEADLESS: &str = "headless";
+static CONFIG_SESSION: &str = "session";
+static CONFIG_SELECTOR: &str = "selector";
+static CONFIG_HTML: &str = "html";
+static CONFIG_SUBMIT: &str = "submit";
+
+static WEBDRIVER_URL_DEFAULT: &str = "http://localhost:4444";
+static SESSION_DEFAULT: &str = "default";
+static SELECTOR_DEFAULT: &str = "body";
+
+pub fn register_agents(askit: &ASKit) {
+    // Browser Navigate Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            NAVIGATE_DEF_NAME,
+            Some(new_agent_boxed::<BrowserNavigateAgent>),
+        )
+        .title("Browser Navigate")
+        .description("Opens the received URL in the browser and outputs the url and title")
+        .category(CATEGORY)
+        .inputs(vec![PIN_URL])
+        .outputs(vec![PIN_PAGE])
+        .capabilities(vec![AgentCapability::Network])
+        .string_global_config_with(CONFIG_WEBDRIVER_URL, WEBDRIVER_URL_DEFAULT, |entry| {
+            entry
+                .title("WebDriver URL")
+                .description("chromedriver or geckodriver")
+        })
+        .boolean_global_config_with(CONFIG_HEADLESS, true, |entry| entry.title("Headless"))
+        .string_config_with(CONFIG_SESSION, SESSION_DEFAULT, |entry| {
+            entry.title("Session")
+        }),
+    );
+
+    // Browser Extract Text Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_browser_extract_text",
+            Some(new_agent_boxed::<BrowserExtractTextAgent>),
+        )
+        .title("Browser Extract Text")
+        .description("Outputs the text of the elements matching the selector on the current page")
+        .category(CATEGORY)
+        .inputs(vec!["*"])
+        .outputs(vec![PIN_TEXT])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_SELECTOR, SELECTOR_DEFAULT, |entry| {
+            entry.title("Selector").description("CSS selector")
+        })
+        .boolean_config_with(CONFIG_HTML, false, |entry| {
+            entry
+                .title("HTML")
+                .description("Outputs the HTML instead of the text")
+        })
+        .string_config_with(CONFIG_SESSION, SESSION_DEFAULT, |entry| {
+            entry.title("Session")
+        }),
+    );
+
+    // Browser Screenshot Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_browser_screenshot",
+            Some(new_agent_boxed::<BrowserScreenshotAgent>),
+        )
+        .title("Browser Screenshot")
+        .description("Outputs a screenshot of the current page or of an element")
+        .category(CATEGORY)
+        .inputs(vec!["*"])
+        .outputs(vec![PIN_IMAGE])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_SELECTOR, "", |entry| {
+            entry
+                .title("Selector")
+                .description("CSS selector of the element. The whole page when empty")
+        })
+        .string_config_with(CONFIG_SESSION, SESSION_DEFAULT, |entry| {
+            entry.title("Session")
+        }),
+    );
+
+    // Browser Click Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_browser_click",
+            Some(new_agent_boxed::<BrowserClickAgent>),
+        )
+        .title("Browser Click")
+        .description("Clicks the element matching the selector and outputs the url and title")
+        .category(CATEGORY)
+        .inputs(vec!["*"])
+        .outputs(vec![PIN_PAGE])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_SELECTOR, "", |entry| {
+            entry
+                .title("Selector")
+                .description("CSS selector")
+                .required()
+        })
+        .string_config_with(CONFIG_SESSION, SESSION_DEFAULT, |entry| {
+            entry.title("Session")
+        }),
+    );
+
+    // Browser Fill Form Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_browser_fill_form",
+            Some(new_agent_boxed::<BrowserFillFormAgent>),
+        )
+        .title("Browser Fill Form")
+        .description(
+            "Types the received values, an object of CSS selector to text, into the form fields",
+        )
+        .category(CATEGORY)
+        .inputs(vec![PIN_VALUES])
+        .outputs(vec![PIN_PAGE])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_SUBMIT, "", |entry| {
+            entry
+                .title("Submit")
+                .description("CSS selector of the button clicked after filling. None when empty")
+        })
+        .string_config_with(CONFIG_SESSION, SESSION_DEFAULT, |entry| {
+            entry.title("Session")
+        }),
+    );
+}
@@ -0,0 +1,990 @@
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use serde_json::{Value, json};
+
+use crate::rest;
+use crate::time::parse_duration_to_ms;
+
+// Calendar
+//
+// Events of a CalDAV calendar, or of Google Calendar. The credentials are the global configs
+// of calendar_events; Google Calendar takes an OAuth access token.
+//
+// Times without an offset, such as the ones extracted by an LLM, are in the local time zone.
+
+// Calendar Events Agent
+struct CalendarEventsAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for CalendarEventsAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let calendar = Calendar::new(self.askit(), configs)?;
+        let (start, end) = range_of(&data, configs)?;
+
+        let events = calendar
+            .events(start, end)
+            .await?
+            .iter()
+            .map(Event::to_json)
+            .collect::<Vec<_>>();
+        self.try_output(ctx, PIN_EVENTS, AgentData::from_json(Value::Array(events))?)
+    }
+}
+
+// Calendar Availability Agent
+struct CalendarAvailabilityAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for CalendarAvailabilityAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let calendar = Calendar::new(self.askit(), configs)?;
+        let (start, end) = range_of(&data, configs)?;
+        let min_duration =
+            parse_duration_to_ms(&configs.get_string_or(CONFIG_DURATION, DURATION_DEFAULT))?;
+
+        let events = calendar.events(start, end).await?;
+        let slots = free_slots(
+            &events,
+            start,
+            end,
+            Duration::milliseconds(min_duration as i64),
+        )
+        .into_iter()
+        .map(|(start, end)| {
+            json!({
+                "start": format_time(start, false),
+                "end": format_time(end, false),
+            })
+        })
+        .collect::<Vec<_>>();
+        self.try_output(ctx, PIN_FREE, AgentData::from_json(Value::Array(slots))?)
+    }
+}
+
+// Calendar Create Event Agent
+struct CalendarCreateEventAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for CalendarCreateEventAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let calendar = Calendar::new(self.askit(), self.configs()?)?;
+        let event = Event::from_data(&data)?;
+
+        let event = calendar.create(event).await?;
+        self.try_output(ctx, PIN_EVENT, AgentData::from_json(event.to_json())?)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Event {
+    uid: String,
+    summary: String,
+    description: String,
+    location: String,
+    start: DateTime<Utc>,
+    // exclusive, the next day of the last day for an all-day event
+    end: DateTime<Utc>,
+    all_day: bool,
+    // false for the events marked as free
+    busy: bool,
+}
+
+impl Event {
+    // The event of an object of summary, start, and optionally end, description, location,
+    // all_day and uid
+    fn from_data(data: &AgentData) -> Result<Self, AgentError> {
+        let summary = data
+            .get_str("summary")
+            .ok_or_else(|| AgentError::InvalidValue("event has no summary".to_string()))?;
+        let start = data
+            .get_str("start")
+            .ok_or_else(|| AgentError::InvalidValue("event has no start".to_string()))?;
+        let value = data.value.to_json();
+        let all_day = value["all_day"]
+            .as_bool()
+            .unwrap_or_else(|| NaiveDate::parse_from_str(start, "%Y-%m-%d").is_ok());
+        let start = parse_time(start)?;
+        let end = match data.get_str("end") {
+            Some(end) => parse_time(end)?,
+            None if all_day => start + Duration::days(1),
+            None => start + Duration::hours(1),
+        };
+        if end < start {
+            return Err(AgentError::InvalidValue(
+                "event ends before the start".to_string(),
+            ));
+        }
+        Ok(Self {
+            uid: data
+                .get_str("uid")
+                .map(|uid| uid.to_string())
+                .unwrap_or_else(|| format!("{}@askit", uuid::Uuid::new_v4())),
+            summary: summary.to_string(),
+            description: value["description"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            location: value["location"].as_str().unwrap_or_default().to_string(),
+            start,
+            end,
+            all_day,
+            busy: true,
+        })
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "uid": self.uid,
+            "summary": self.summary,
+            "description": self.description,
+            "location": self.location,
+            "start": format_time(self.start, self.all_day),
+            "end": format_time(self.end, self.all_day),
+            "all_day": self.all_day,
+            "busy": self.busy,
+        })
+    }
+}
+
+enum Calendar {
+    CalDav {
+        url: String,
+        username: String,
+        password: String,
+    },
+    Google {
+        calendar_id: String,
+        token: String,
+    },
+}
+
+impl Calendar {
+    fn new(askit: &ASKit, configs: &AgentConfigs) -> Result<Self, AgentError> {
+        let global_configs = askit
+            .get_global_configs(EVENTS_DEF_NAME)
+            .unwrap_or_default();
+        let calendar = configs.get_string_or_default(CONFIG_CALENDAR);
+        let provider = configs.get_string_or(CONFIG_PROVIDER, PROVIDER_CALDAV);
+        match provider.as_str() {
+            PROVIDER_CALDAV => {
+                if calendar.is_empty() {
+                    return Err(AgentError::InvalidConfig(
+                        "calendar is not set to the URL of the CalDAV calendar".to_string(),
+                    ));
+                }
+                Ok(Self::CalDav {
+                    url: calendar,
+                    username: global_configs.get_string_or_default(CONFIG_CALDAV_USERNAME),
+                    password: global_configs.get_string_or_default(CONFIG_CALDAV_PASSWORD),
+                })
+            }
+            PROVIDER_GOOGLE => Ok(Self::Google {
+                calendar_id: if calendar.is_empty() {
+                    GOOGLE_CALENDAR_DEFAULT.to_string()
+                } else {
+                    calendar
+                },
+                token: global_configs.get_string_or_default(CONFIG_GOOGLE_TOKEN),
+            }),
+            _ => Err(AgentError::InvalidConfig(format!(
+                "Unknown calendar provider: {}",
+                provider
+            ))),
+        }
+    }
+
+    // The events overlapping [start, end), in the order of the start. Recurring events are
+    // expanded into their instances.
+    async fn events(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<Event>, AgentError> {
+        let mut events = match self {
+            Self::CalDav { url, .. } => {
+                let body = CALENDAR_QUERY
+                    .replace("{start}", &format_ics_utc(start))
+                    .replace("{end}", &format_ics_utc(end));
+                let method = reqwest::Method::from_bytes(b"REPORT").unwrap();
+                let request = rest::client()
+                    .request(method, url)
+                    .header("Depth", "1")
+                    .header(
+                        reqwest::header::CONTENT_TYPE,
+                        "application/xml; charset=utf-8",
+                    )
+                    .body(body);
+                let response = rest::send_text(self.auth(request)).await?;
+                calendar_data(&response)
+                    .iter()
+                    .flat_map(|ics| parse_ics(ics))
+                    .collect::<Vec<_>>()
+            }
+            Self::Google { .. } => {
+                let request = rest::client().get(self.google_events_url()?).query(&[
+                    ("timeMin", start.to_rfc3339()),
+                    ("timeMax", end.to_rfc3339()),
+                    ("singleEvents", "true".to_string()),
+                    ("orderBy", "startTime".to_string()),
+                    ("maxResults", "2500".to_string()),
+                ]);
+                let response = rest::send_json(self.auth(request)).await?;
+                response["items"]
+                    .as_array()
+                    .map(|items| items.iter().filter_map(google_event).collect::<Vec<_>>())
+                    .unwrap_or_default()
+            }
+        };
+        events.retain(|event| event.start < end && (event.end > start || event.start >= start));
+        events.sort_by_key(|event| event.start);
+        Ok(events)
+    }
+
+    async fn create(&self, event: Event) -> Result<Event, AgentError> {
+        match self {
+            Self::CalDav { url, .. } => {
+                let mut url = reqwest::Url::parse(url).map_err(|e| {
+                    AgentError::InvalidConfig(format!("Invalid calendar URL: {}", e))
+                })?;
+                url.path_segments_mut()
+                    .map_err(|_| AgentError::InvalidConfig("Invalid calendar URL".to_string()))?
+                    .pop_if_empty()
+                    .push(&format!("{}.ics", event.uid));
+                let request = rest::client()
+                    .put(url)
+                    .header(reqwest::header::IF_NONE_MATCH, "*")
+                    .header(
+                        reqwest::header::CONTENT_TYPE,
+                        "text/calendar; charset=utf-8",
+                    )
+                    .body(to_ics(&event, Utc::now()));
+                rest::send_text(self.auth(request)).await?;
+                Ok(event)
+            }
+            Self::Google { .. } => {
+                let request = rest::client().post(self.google_events_url()?).json(&json!({
+                    "iCalUID": event.uid,
+                    "summary": event.summary,
+                    "description": event.description,
+                    "location": event.location,
+                    "start": google_time(event.start, event.all_day),
+                    "end": google_time(event.end, event.all_day),
+                }));
+                let response = rest::send_json(self.auth(request)).await?;
+                Ok(google_event(&response).unwrap_or(event))
+            }
+        }
+    }
+
+    fn auth(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match self {
+            Self::CalDav {
+                username, password, ..
+            } if !username.is_empty() => request.basic_auth(username, Some(password)),
+            Self::Google { token, .. } if !token.is_empty() => request.bearer_auth(token),
+            _ => request,
+        }
+    }
+
+    fn google_events_url(&self) -> Result<reqwest::Url, AgentError> {
+        let Self::Google { calendar_id, .. } = self else {
+            unreachable!()
+        };
+        let mut url = reqwest::Url::parse(GOOGLE_API_URL).unwrap();
+        url.path_segments_mut()
+            .map_err(|_| AgentError::Other("Invalid Google Calendar URL".to_string()))?
+            .extend(["calendars", calendar_id, "events"]);
+        Ok(url)
+    }
+}
+
+// The start and the end of the input, an object of start and end. The range starts now
+// and spans the period config by default.
+fn range_of(
+    data: &AgentData,
+    configs: &AgentConfigs,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), AgentError> {
+    let period = parse_duration_to_ms(&configs.get_string_or(CONFIG_PERIOD, PERIOD_DEFAULT))?;
+    let start = match data.get_str("start") {
+        Some(start) => parse_time(start)?,
+        None => Utc::now(),
+    };
+    let end = match data.get_str("end") {
+        Some(end) => parse_time(end)?,
+        None => start + Duration::milliseconds(period as i64),
+    };
+    if end <= start {
+        return Err(AgentError::InvalidValue(
+            "range ends before the start".to_string(),
+        ));
+    }
+    Ok((start, end))
+}
+
+// The gaps between the busy events in [start, end), at least `min_duration` long
+fn free_slots(
+    events: &[Event],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    min_duration: Duration,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut busy = events
+        .iter()
+        .filter(|event| event.busy && event.end > start && event.start < end)
+        .map(|event| (event.start.max(start), event.end.min(end)))
+        .collect::<Vec<_>>();
+    busy.sort();
+
+    let mut slots = Vec::new();
+    let mut cursor = start;
+    for (busy_start, busy_end) in busy {
+        if busy_start > cursor && busy_start - cursor >= min_duration {
+            slots.push((cursor, busy_start));
+        }
+        cursor = cursor.max(busy_end);
+    }
+    if end > cursor && end - cursor >= min_duration {
+        slots.push((cursor, end));
+    }
+    slots
+}
+
+// RFC 3339, or a date for an all-day event, in the local time zone
+fn format_time(time: DateTime<Utc>, all_day: bool) -> String {
+    let time = time.with_timezone(&Local);
+    if all_day {
+        time.date_naive().to_string()
+    } else {
+        time.to_rfc3339()
+    }
+}
+
+// RFC 3339, a local date and time, or a local date
+fn parse_time(s: &str) -> Result<DateTime<Utc>, AgentError> {
+    let s = s.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(s, format) {
+            return local_time(time);
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+        return local_time(date.and_hms_opt(0, 0, 0).unwrap());
+    }
+    Err(AgentError::InvalidValue(format!("Invalid time: {}", s)))
+}
+
+fn local_time(time: NaiveDateTime) -> Result<DateTime<Utc>, AgentError> {
+    Local
+        .from_local_datetime(&time)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| AgentError::InvalidValue(format!("Invalid local time: {}", time)))
+}
+
+fn google_time(time: DateTime<Utc>, all_day: bool) -> Value {
+    if all_day {
+        json!({ "date": format_time(time, true) })
+    } else {
+        json!({ "dateTime": time.to_rfc3339() })
+    }
+}
+
+fn google_event(item: &Value) -> Option<Event> {
+    if item["status"].as_str() == Some("cancelled") {
+        return None;
+    }
+    let (start, all_day) = google_time_of(&item["start"])?;
+    let (end, _) = google_time_of(&item["end"]).unwrap_or((start, all_day));
+    Some(Event {
+        uid: item["iCalUID"]
+            .as_str()
+            .or(item["id"].as_str())
+            .unwrap_or_default()
+            .to_string(),
+        summary: item["summary"].as_str().unwrap_or_default().to_string(),
+        description: item["description"].as_str().unwrap_or_default().to_string(),
+        location: item["location"].as_str().unwrap_or_default().to_string(),
+        start,
+        end,
+        all_day,
+        busy: item["transparency"].as_str() != Some("transparent"),
+    })
+}
+
+fn google_time_of(time: &Value) -> Option<(DateTime<Utc>, bool)> {
+    if let Some(date_time) = time["dateTime"].as_str() {
+        return parse_time(date_time).ok().map(|time| (time, false));
+    }
+    parse_time(time["date"].as_str()?)
+        .ok()
+        .map(|time| (time, true))
+}
+
+// The iCalendar texts in the calendar-data of a CalDAV multistatus response
+fn calendar_data(xml: &str) -> Vec<String> {
+    let re = Regex::new(r"(?s)<(?:\w+:)?calendar-data\b[^>]*>(.*?)</(?:\w+:)?calendar-data>")
+        .expect("Failed to compile regex");
+    re.captures_iter(xml)
+        .map(|captures| {
+            let data = captures[1].trim();
+            match data
+                .strip_prefix("<![CDATA[")
+                .and_then(|data| data.strip_suffix("]]>"))
+            {
+                Some(data) => data.to_string(),
+                None => data
+                    .replace("&lt;", "<")
+                    .replace("&gt;", ">")
+                    .replace("&quot;", "\"")
+                    .replace("&apos;", "'")
+                    .replace("&#13;", "\r")
+                    .replace("&#xD;", "\r")
+                    .replace("&#10;", "\n")
+                    .replace("&amp;", "&"),
+            }
+        })
+        .collect()
+}
+
+// The VEVENTs of an iCalendar text. Cancelled events are skipped.
+fn parse_ics(ics: &str) -> Vec<Event> {
+    let mut events = Vec::new();
+    let mut properties: Option<Vec<IcsProperty>> = None;
+    // depth of the components in a VEVENT, such as VALARM
+    let mut nested = 0;
+    for line in unfold_ics(ics) {
+        if line.eq_ignore_ascii_case("BEGIN:VEVENT") {
+            properties = Some(Vec::new());
+            nested = 0;
+        } else if line.eq_ignore_ascii_case("END:VEVENT") {
+            if let Some(event) = properties.take().and_then(|p| event_of(&p)) {
+                events.push(event);
+            }
+        } else if let Some(properties) = properties.as_mut() {
+            let upper = line.to_ascii_uppercase();
+            if upper.starts_with("BEGIN:") {
+                nested += 1;
+            } else if upper.starts_with("END:") {
+                nested -= 1;
+            } else if nested == 0
+                && let Some(property) = IcsProperty::parse(&line)
+            {
+                properties.push(property);
+            }
+        }
+    }
+    events
+}
+
+fn unfold_ics(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.split('\n') {
+        let line = line.trim_end_matches('\r');
+        if let Some(rest) = line.strip_prefix([' ', '\t'])
+            && let Some(last) = lines.last_mut()
+        {
+            last.push_str(rest);
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+    lines
+}
+
+struct IcsProperty {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl IcsProperty {
+    // NAME;PARAM=VALUE:VALUE, where quoted parameter values may have colons
+    fn parse(line: &str) -> Option<Self> {
+        let mut quoted = false;
+        let colon = line.char_indices().find_map(|(i, c)| match c {
+            '"' => {
+                quoted = !quoted;
+                None
+            }
+            ':' if !quoted => Some(i),
+            _ => None,
+        })?;
+        let mut head = line[..colon].split(';');
+        let name = head.next()?.to_ascii_uppercase();
+        let params = head
+            .filter_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                Some((
+                    key.to_ascii_uppercase(),
+                    value.trim_matches('"').to_string(),
+                ))
+            })
+            .collect();
+        Some(Self {
+            name,
+            params,
+            value: line[colon + 1..].to_string(),
+        })
+    }
+
+    fn param(&self, key: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn text(&self) -> String {
+        let mut text = String::new();
+        let mut chars = self.value.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                text.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('n') | Some('N') => text.push('\n'),
+                Some(c) => text.push(c),
+                None => {}
+            }
+        }
+        text
+    }
+
+    // (time, all_day) of a DATE or DATE-TIME value
+    fn time(&self) -> Option<(DateTime<Utc>, bool)> {
+        let value = self.value.trim();
+        if self.param("VALUE") == Some("DATE") || value.len() == 8 {
+            let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+            return local_time(date.and_hms_opt(0, 0, 0)?)
+                .ok()
+                .map(|time| (time, true));
+        }
+        if let Some(value) = value.strip_suffix('Z') {
+            let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+            return Some((time.and_utc(), false));
+        }
+        let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+        // the time zones unknown to the tz database, such as the Windows ones, are local
+        if let Some(tz) = self
+            .param("TZID")
+            .and_then(|tzid| tzid.parse::<chrono_tz::Tz>().ok())
+        {
+            return tz
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|time| (time.with_timezone(&Utc), false));
+        }
+        local_time(time).ok().map(|time| (time, false))
+    }
+}
+
+fn event_of(properties: &[IcsProperty]) -> Option<Event> {
+    let property = |name: &str| properties.iter().find(|p| p.name == name);
+    if property("STATUS").is_some_and(|p| p.value.eq_ignore_ascii_case("CANCELLED")) {
+        return None;
+    }
+    let (start, all_day) = property("DTSTART")?.time()?;
+    let end = match property("DTEND").and_then(|p| p.time()) {
+        Some((end, _)) => end,
+        None if all_day => start + Duration::days(1),
+        None => start,
+    };
+    Some(Event {
+        uid: property("UID").map(|p| p.text()).unwrap_or_default(),
+        summary: property("SUMMARY").map(|p| p.text()).unwrap_or_default(),
+        description: property("DESCRIPTION")
+            .map(|p| p.text())
+            .unwrap_or_default(),
+        location: property("LOCATION").map(|p| p.text()).unwrap_or_default(),
+        start,
+        end,
+        all_day,
+        busy: !property("TRANSP").is_some_and(|p| p.value.eq_ignore_ascii_case("TRANSPARENT")),
+    })
+}
+
+fn to_ics(event: &Event, now: DateTime<Utc>) -> String {
+    let time = |name: &str, time: DateTime<Utc>| {
+        if event.all_day {
+            let date = time.with_timezone(&Local).date_naive();
+            format!("{};VALUE=DATE:{}", name, date.format("%Y%m%d"))
+        } else {
+            format!("{}:{}", name, format_ics_utc(time))
+        }
+    };
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//Agent Stream Kit//askit-std-agents//EN".to_string(),
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", event.uid),
+        format!("DTSTAMP:{}", format_ics_utc(now)),
+        time("DTSTART", event.start),
+        time("DTEND", event.end),
+        format!("SUMMARY:{}", escape_ics_text(&event.summary)),
+    ];
+    if !event.description.is_empty() {
+        lines.push(format!(
+            "DESCRIPTION:{}",
+            escape_ics_text(&event.description)
+        ));
+    }
+    if !event.location.is_empty() {
+        lines.push(format!("LOCATION:{}", escape_ics_text(&event.location)));
+    }
+    if !event.busy {
+        lines.push("TRANSP:TRANSPARENT".to_string());
+    }
+    lines.push("END:VEVENT".to_string());
+    lines.push("END:VCALENDAR".to_string());
+    lines
+        .iter()
+        .map(|line| fold_ics_line(line))
+        .collect::<Vec<_>>()
+        .join("\r\n")
+        + "\r\n"
+}
+
+fn format_ics_utc(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn escape_ics_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\r', "")
+        .replace('\n', "\\n")
+}
+
+// Lines longer than 75 octets are folded, without splitting a character
+fn fold_ics_line(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+static AGENT_KIND: &str = "Agent";
+static CATEGORY: &str = "Core/Calendar";
+
+static EVENTS_DEF_NAME: &str = "calendar_events";
+
+static PIN_RANGE: &str = "range";
+static PIN_EVENTS: &str = "events";
+static PIN_EVENT: &str = "event";
+static PIN_FREE: &str = "free";
+
+static CONFIG_CALDAV_USERNAME: &str = "caldav_username";
+static CONFIG_CALDAV_PASSWORD: &str = "caldav_password";
+static CONFIG_GOOGLE_TOKEN: &str = "google_token";
+static CONFIG_PROVIDER: &str = "provider";
+static CONFIG_CALENDAR: &str = "calendar";
+static CONFIG_PERIOD: &str = "period";
+static CONFIG_DURATION: &str = "duration";
+
+const PROVIDER_CALDAV: &str = "caldav";
+const PROVIDER_GOOGLE: &str = "google";
+
+static PERIOD_DEFAULT: &str = "7d";
+static DURATION_DEFAULT: &str = "30m";
+static GOOGLE_CALENDAR_DEFAULT: &str = "primary";
+static GOOGLE_API_URL: &str = "https://www.googleapis.com/calendar/v3";
+
+static CALENDAR_QUERY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<C:calendar-query xmlns:D="DAV:" xmlns:C="urn:ietf:params:xml:ns:caldav">
+  <D:prop>
+    <C:calendar-data>
+      <C:expand start="{start}" end="{end}"/>
+    </C:calendar-data>
+  </D:prop>
+  <C:filter>
+    <C:comp-filter name="VCALENDAR">
+      <C:comp-filter name="VEVENT">
+        <C:time-range start="{start}" end="{end}"/>
+      </C:comp-filter>
+    </C:comp-filter>
+  </C:filter>
+</C:calendar-query>"#;
+
+pub fn register_agents(askit: &ASKit) {
+    // Calendar Events Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            EVENTS_DEF_NAME,
+            Some(new_agent_boxed::<CalendarEventsAgent>),
+        )
+        .title("Calendar Events")
+        .description("Lists the events in the range of the input, an object of start and end")
+        .category(CATEGORY)
+        .inputs(vec![PIN_RANGE])
+        .outputs(vec![PIN_EVENTS])
+        .capabilities(vec![AgentCapability::Network])
+        .string_global_config_with(CONFIG_CALDAV_USERNAME, "", |entry| {
+            entry.title("CalDAV Username")
+        })
+        .custom_global_config_with(CONFIG_CALDAV_PASSWORD, "", "password", |entry| {
+            entry.title("CalDAV Password")
+        })
+        .custom_global_config_with(CONFIG_GOOGLE_TOKEN, "", "password", |entry| {
+            entry
+                .title("Google Access Token")
+                .description("OAuth access token of Google Calendar")
+        })
+        .string_config_with(CONFIG_PROVIDER, PROVIDER_CALDAV, |entry| {
+            entry.title("Provider").description("caldav or google")
+        })
+        .string_config_with(CONFIG_CALENDAR, "", |entry| {
+            entry
+                .title("Calendar")
+                .description("URL of the CalDAV calendar, or the Google calendar id (primary)")
+        })
+        .string_config_with(CONFIG_PERIOD, PERIOD_DEFAULT, |entry| {
+            entry
+                .title("Period")
+                .description("Length of the range when the input has no end (ex. 1d, 12h)")
+        }),
+    );
+
+    // Calendar Availability Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "calendar_availability",
+            Some(new_agent_boxed::<CalendarAvailabilityAgent>),
+        )
+        .title("Calendar Availability")
+        .description("Outputs the free slots between the busy events in the range of the input")
+        .category(CATEGORY)
+        .inputs(vec![PIN_RANGE])
+        .outputs(vec![PIN_FREE])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROVIDER, PROVIDER_CALDAV, |entry| {
+            entry.title("Provider").description("caldav or google")
+        })
+        .string_config_with(CONFIG_CALENDAR, "", |entry| {
+            entry
+                .title("Calendar")
+                .description("URL of the CalDAV calendar, or the Google calendar id (primary)")
+        })
+        .string_config_with(CONFIG_PERIOD, PERIOD_DEFAULT, |entry| {
+            entry
+                .title("Period")
+                .description("Length of the range when the input has no end (ex. 1d, 12h)")
+        })
+        .string_config_with(CONFIG_DURATION, DURATION_DEFAULT, |entry| {
+            entry
+                .title("Duration")
+                .description("Shortest free slot (ex. 30m, 1h)")
+        }),
+    );
+
+    // Calendar Create Event Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "calendar_create_event",
+            Some(new_agent_boxed::<CalendarCreateEventAgent>),
+        )
+        .title("Calendar Create Event")
+        .description(
+            "Creates an event from an object of summary, start, end, description and location",
+        )
+        .category(CATEGORY)
+        .inputs(vec![PIN_EVENT])
+        .outputs(vec![PIN_EVENT])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROVIDER, PROVIDER_CALDAV, |entry| {
+            entry.title("Provider").description("caldav or google")
+        })
+        .string_config_with(CONFIG_CALENDAR, "", |entry| {
+            entry
+                .title("Calendar")
+                .description("URL of the CalDAV calendar, or the Google calendar id (primary)")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ics() {
+        let xml = "<d:multistatus xmlns:d=\"DAV:\" xmlns:cal=\"urn:ietf:params:xml:ns:caldav\">\
+            <d:response><d:propstat><d:prop><cal:calendar-data>BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\nUID:1@example.com\r\nSUMMARY:Design review\\, part 1\r\n\
+            DESCRIPTION:Agenda:\\nslides &amp; \r\n demo\r\n\
+            DTSTART;TZID=\"Asia/Tokyo\":20250310T100000\r\nDTEND:20250310T020000Z\r\n\
+            BEGIN:VALARM\r\nDESCRIPTION:Reminder\r\nEND:VALARM\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:2\r\nSTATUS:CANCELLED\r\nDTSTART:20250310T030000Z\r\nEND:VEVENT\r\n\
+            BEGIN:VEVENT\r\nUID:3\r\nTRANSP:TRANSPARENT\r\nDTSTART;VALUE=DATE:20250311\r\nEND:VEVENT\r\n\
+            END:VCALENDAR</cal:calendar-data></d:prop></d:propstat></d:response></d:multistatus>";
+        let data = calendar_data(xml);
+        assert_eq!(data.len(), 1);
+        let events = parse_ics(&data[0]);
+        assert_eq!(events.len(), 2);
+
+        let event = &events[0];
+        assert_eq!(event.summary, "Design review, part 1");
+        assert_eq!(event.description, "Agenda:\nslides & demo");
+        assert_eq!(event.start, parse_time("2025-03-10T01:00:00Z").unwrap());
+        assert_eq!(event.end, parse_time("2025-03-10T02:00:00Z").unwrap());
+        assert!(event.busy && !event.all_day);
+
+        let event = &events[1];
+        assert!(event.all_day && !event.busy);
+        assert_eq!(event.end - event.start, Duration::days(1));
+
+        // the event written is read back
+        let data = AgentData::from_json(json!({
+            "summary": "Lunch; with a long name that is folded over the lines of the iCalendar text",
+            "start": "2025-03-12T12:00:00+09:00",
+            "location": "Café",
+        }))
+        .unwrap();
+        let event = Event::from_data(&data).unwrap();
+        let ics = to_ics(&event, Utc::now());
+        assert!(ics.lines().all(|line| line.len() <= 76));
+        assert_eq!(parse_ics(&ics), vec![event]);
+    }
+
+    #[test]
+    fn test_free_slots() {
+        let time = |s: &str| parse_time(s).unwrap();
+        let event = |start: &str, end: &str, busy: bool| Event {
+            uid: String::new(),
+            summary: String::new(),
+            description: String::new(),
+            location: String::new(),
+            start: time(start),
+            end: time(end),
+            all_day: false,
+            busy,
+        };
+        let events = vec![
+            event("2025-03-10T08:00:00Z", "2025-03-10T09:30:00Z", true),
+            event("2025-03-10T09:00:00Z", "2025-03-10T10:00:00Z", true),
+            event("2025-03-10T10:10:00Z", "2025-03-10T11:00:00Z", true),
+            event("2025-03-10T12:00:00Z", "2025-03-10T15:00:00Z", false),
+        ];
+        let slots = free_slots(
+            &events,
+            time("2025-03-10T09:00:00Z"),
+            time("2025-03-10T17:00:00Z"),
+            Duration::minutes(30),
+        );
+        assert_eq!(
+            slots,
+            vec![(time("2025-03-10T11:00:00Z"), time("2025-03-10T17:00:00Z"))]
+        );
+    }
+}
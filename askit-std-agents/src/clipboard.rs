@@ -0,0 +1,364 @@
+use std::process::Stdio;
+use std::time::Duration;
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AgentStatus, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tokio::task::AbortHandle;
+
+use crate::time::parse_duration_to_ms;
+
+// Clipboard
+//
+// The clipboard is accessed through the tools of the platform: pbcopy/pbpaste on macOS,
+// PowerShell on Windows, and wl-clipboard, xclip or xsel on Linux.
+
+// Clipboard Watch Agent
+struct ClipboardWatchAgent {
+    data: AsAgentData,
+    watcher: Option<AbortHandle>,
+}
+
+impl ClipboardWatchAgent {
+    fn start_watcher(&mut self) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Exec)?;
+        let interval = self
+            .configs()?
+            .get_string_or(CONFIG_INTERVAL, INTERVAL_DEFAULT);
+        let interval_ms = parse_duration_to_ms(&interval)?;
+
+        let askit = self.askit().clone();
+        let clock = askit.clock();
+        let agent_id = self.id().to_string();
+        let handle = self.data.spawn_task(async move {
+            // the text copied before the start is not a change
+            let mut last = read_clipboard().await.ok();
+            let mut next = clock.now();
+            loop {
+                next += Duration::from_millis(interval_ms);
+                clock.sleep_until(next).await;
+
+                let text = match read_clipboard().await {
+                    Ok(text) => text,
+                    Err(e) => {
+                        log::debug!("Failed to read the clipboard: {}", e);
+                        continue;
+                    }
+                };
+                if text.is_empty() || last.as_ref() == Some(&text) {
+                    continue;
+                }
+                last = Some(text.clone());
+                if let Err(e) = askit.try_send_agent_out(
+                    agent_id.clone(),
+                    AgentContext::new(),
+                    PIN_TEXT.to_string(),
+                    AgentData::string(text),
+                ) {
+                    log::error!("Failed to send clipboard text: {}", e);
+                }
+            }
+        });
+        self.watcher = Some(handle);
+        Ok(())
+    }
+
+    fn stop_watcher(&mut self) {
+        if let Some(handle) = self.watcher.take() {
+            handle.abort();
+        }
+    }
+}
+
+impl AsAgent for ClipboardWatchAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            watcher: None,
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        self.start_watcher()
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        if *self.status() == AgentStatus::Start {
+            self.stop_watcher();
+            self.start_watcher()?;
+        }
+        Ok(())
+    }
+}
+
+// Clipboard Set Agent
+struct ClipboardSetAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for ClipboardSetAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Exec)?;
+
+        let text = match data.as_str() {
+            Some(s) => s.to_string(),
+            None => data.value.to_json().to_string(),
+        };
+        write_clipboard(&text).await?;
+        self.try_output(ctx, pin, data)
+    }
+}
+
+async fn read_clipboard() -> Result<String, AgentError> {
+    let mut last_error = None;
+    for (program, args) in paste_commands() {
+        match Command::new(program).args(args).output().await {
+            Ok(output) if output.status.success() => {
+                return Ok(paste_output(program, &output.stdout));
+            }
+            Ok(output) => {
+                last_error = Some(format!(
+                    "{} failed: {}",
+                    program,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            Err(e) => last_error = Some(format!("{}: {}", program, e)),
+        }
+    }
+    Err(AgentError::IoError(
+        last_error.unwrap_or_else(|| "No clipboard tool found".to_string()),
+    ))
+}
+
+async fn write_clipboard(text: &str) -> Result<(), AgentError> {
+    let mut last_error = None;
+    for (program, args) in copy_commands() {
+        let child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                last_error = Some(format!("{}: {}", program, e));
+                continue;
+            }
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes()).await?;
+        }
+        let output = child.wait_with_output().await?;
+        if output.status.success() {
+            return Ok(());
+        }
+        last_error = Some(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Err(AgentError::IoError(
+        last_error.unwrap_or_else(|| "No clipboard tool found".to_string()),
+    ))
+}
+
+// PowerShell ends its output with a newline that is not in the clipboard
+fn paste_output(program: &str, stdout: &[u8]) -> String {
+    let text = String::from_utf8_lossy(stdout);
+    if program == "powershell" {
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+        return text.strip_suffix('\r').unwrap_or(text).to_string();
+    }
+    text.into_owned()
+}
+
+// (program, args) to try in order
+type ClipboardCommand = (&'static str, &'static [&'static str]);
+
+fn paste_commands() -> Vec<ClipboardCommand> {
+    paste_commands_for(std::env::consts::OS, is_wayland())
+}
+
+fn copy_commands() -> Vec<ClipboardCommand> {
+    copy_commands_for(std::env::consts::OS, is_wayland())
+}
+
+fn is_wayland() -> bool {
+    std::env::var_os("WAYLAND_DISPLAY").is_some()
+}
+
+fn paste_commands_for(os: &str, wayland: bool) -> Vec<ClipboardCommand> {
+    match os {
+        "macos" => vec![("pbpaste", &[])],
+        "windows" => vec![(
+            "powershell",
+            &[
+                "-NoProfile",
+                "-Command",
+                "[Console]::OutputEncoding = [Text.Encoding]::UTF8; Get-Clipboard -Raw",
+            ],
+        )],
+        "linux" => {
+            let mut commands: Vec<ClipboardCommand> = Vec::new();
+            if wayland {
+                commands.push(("wl-paste", &["--no-newline"]));
+            }
+            commands.push(("xclip", &["-selection", "clipboard", "-out"]));
+            commands.push(("xsel", &["--clipboard", "--output"]));
+            commands
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn copy_commands_for(os: &str, wayland: bool) -> Vec<ClipboardCommand> {
+    match os {
+        "macos" => vec![("pbcopy", &[])],
+        "windows" => vec![(
+            "powershell",
+            &[
+                "-NoProfile",
+                "-Command",
+                "[Console]::InputEncoding = [Text.Encoding]::UTF8; Set-Clipboard -Value ([Console]::In.ReadToEnd())",
+            ],
+        )],
+        "linux" => {
+            let mut commands: Vec<ClipboardCommand> = Vec::new();
+            if wayland {
+                commands.push(("wl-copy", &[]));
+            }
+            commands.push(("xclip", &["-selection", "clipboard", "-in"]));
+            commands.push(("xsel", &["--clipboard", "--input"]));
+            commands
+        }
+        _ => Vec::new(),
+    }
+}
+
+static AGENT_KIND: &str = "Agent";
+static CATEGORY: &str = "Core/Clipboard";
+
+static PIN_TEXT: &str = "text";
+
+static CONFIG_INTERVAL: &str = "interval";
+
+static INTERVAL_DEFAULT: &str = "500ms";
+
+pub fn register_agents(askit: &ASKit) {
+    // Clipboard Watch Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_clipboard_watch",
+            Some(new_agent_boxed::<ClipboardWatchAgent>),
+        )
+        .title("Clipboard Watch")
+        .description("Outputs the text copied to the clipboard")
+        .category(CATEGORY)
+        .outputs(vec![PIN_TEXT])
+        .string_config_with(CONFIG_INTERVAL, INTERVAL_DEFAULT, |entry| {
+            entry.title("check interval").description("(ex. 500ms, 1s)")
+        })
+        .capabilities(vec![AgentCapability::Exec]),
+    );
+
+    // Clipboard Set Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_clipboard_set",
+            Some(new_agent_boxed::<ClipboardSetAgent>),
+        )
+        .title("Clipboard Set")
+        .description("Copies the received text to the clipboard and passes the data through")
+        .category(CATEGORY)
+        .inputs(vec!["*"])
+        .outputs(vec!["*"])
+        .capabilities(vec![AgentCapability::Exec]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn programs(commands: Vec<ClipboardCommand>) -> Vec<&'static str> {
+        commands.into_iter().map(|(program, _)| program).collect()
+    }
+
+    #[test]
+    fn test_clipboard_commands() {
+        assert_eq!(programs(paste_commands_for("macos", false)), ["pbpaste"]);
+        assert_eq!(programs(copy_commands_for("macos", true)), ["pbcopy"]);
+        assert_eq!(
+            programs(paste_commands_for("windows", false)),
+            ["powershell"]
+        );
+        assert_eq!(
+            programs(paste_commands_for("linux", false)),
+            ["xclip", "xsel"]
+        );
+        assert_eq!(
+            programs(copy_commands_for("linux", true)),
+            ["wl-copy", "xclip", "xsel"]
+        );
+        assert_eq!(
+            paste_commands_for("linux", true)[0],
+            ("wl-paste", &["--no-newline"][..])
+        );
+        assert!(copy_commands_for("freebsd", false).is_empty());
+    }
+
+    #[test]
+    fn test_paste_output() {
+        assert_eq!(paste_output("powershell", b"text\r\n"), "text");
+        assert_eq!(paste_output("powershell", b"line\n\r\n"), "line\n");
+        assert_eq!(paste_output("xclip", b"text\n"), "text\n");
+        assert_eq!(paste_output("pbpaste", b"caf\xc3\xa9"), "caf\u{e9}");
+    }
+}
@@ -2,7 +2,7 @@ use std::vec;
 
 use agent_stream_kit::{
     ASKit, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentDisplayConfigEntry,
-    AgentError, AgentOutput, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+    AgentError, AgentOutput, AgentValue, AsAgent, AsAgentData, async_trait, new_agent_boxed,
 };
 
 /// Counter
@@ -39,6 +39,18 @@ impl AsAgent for CounterAgent {
         Ok(())
     }
 
+    fn state(&self) -> Option<AgentValue> {
+        Some(AgentValue::integer(self.count))
+    }
+
+    fn restore_state(&mut self, state: AgentValue) -> Result<(), AgentError> {
+        self.count = state
+            .as_i64()
+            .ok_or_else(|| AgentError::InvalidValue("count must be an integer".to_string()))?;
+        self.emit_display(DISPLAY_COUNT, AgentData::integer(self.count));
+        Ok(())
+    }
+
     async fn process(
         &mut self,
         ctx: AgentContext,
@@ -1,3 +1,5 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::vec;
 
 use agent_stream_kit::{
@@ -178,13 +180,80 @@ impl AsAgent for GetPropertyAgent {
     }
 }
 
+// Partition
+struct PartitionAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for PartitionAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let configs = self.configs()?;
+        let partitions = configs.get_integer_or(CONFIG_PARTITIONS, DEFAULT_PARTITIONS);
+        if partitions < 1 || partitions as usize > PIN_PARTITIONS.len() {
+            return Err(AgentError::InvalidConfig(format!(
+                "Number of partitions must be 1 to {}",
+                PIN_PARTITIONS.len()
+            )));
+        }
+        let key = configs.get_string_or_default(CONFIG_KEY);
+        let partition = partition_of(&data.value, &key, partitions as u64)?;
+        self.try_output(ctx, PIN_PARTITIONS[partition], data)
+    }
+}
+
+// The same key always goes to the same partition.
+fn partition_of(value: &AgentValue, key: &str, partitions: u64) -> Result<usize, AgentError> {
+    let key_value = if key.is_empty() {
+        value
+    } else {
+        let pointer = format!("/{}", key.replace('.', "/"));
+        value
+            .pointer(&pointer)
+            .ok_or_else(|| AgentError::InvalidValue(format!("partition key {}", key)))?
+    };
+    let mut hasher = DefaultHasher::new();
+    key_value.hash(&mut hasher);
+    Ok((hasher.finish() % partitions) as usize)
+}
+
 static AGENT_KIND: &str = "agent";
 static CATEGORY: &str = "Core/Data";
 
 static PIN_DATA: &str = "data";
 static PIN_JSON: &str = "json";
 
+static PIN_PARTITIONS: [&str; 4] = ["0", "1", "2", "3"];
+
 static CONFIG_PROPERTY: &str = "property";
+static CONFIG_KEY: &str = "key";
+static CONFIG_PARTITIONS: &str = "partitions";
+
+const DEFAULT_PARTITIONS: i64 = 2;
 
 pub fn register_agents(askit: &ASKit) {
     askit.register_agent(
@@ -223,4 +292,50 @@ pub fn register_agents(askit: &ASKit) {
         .outputs(vec![PIN_DATA])
         .string_config_default(CONFIG_PROPERTY),
     );
+
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_partition",
+            Some(new_agent_boxed::<PartitionAgent>),
+        )
+        .title("Partition")
+        .description("Route data to a branch chosen by the hash of a key, keeping data with the same key on the same branch")
+        .category(CATEGORY)
+        .inputs(vec![PIN_DATA])
+        .outputs(PIN_PARTITIONS.to_vec())
+        .string_config_with(CONFIG_KEY, "", |entry| {
+            entry
+                .title("Key")
+                .description("Property path such as user.id. The whole data when empty")
+        })
+        .integer_config_with(CONFIG_PARTITIONS, DEFAULT_PARTITIONS, |entry| {
+            entry.title("Partitions").description("1 to 4")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partition_of() {
+        let value = |user: &str| {
+            AgentValue::from_json(serde_json::json!({"user": {"id": user}, "text": user.len()}))
+                .unwrap()
+        };
+        let a = partition_of(&value("alice"), "user.id", 4).unwrap();
+        assert_eq!(partition_of(&value("alice"), "user.id", 4).unwrap(), a);
+        assert!(a < 4);
+
+        let spread = ["alice", "bob", "carol", "dave", "erin", "frank", "grace"]
+            .iter()
+            .map(|user| partition_of(&value(user), "user.id", 4).unwrap())
+            .collect::<std::collections::HashSet<_>>();
+        assert!(spread.len() > 1);
+
+        assert!(partition_of(&value("alice"), "user.name", 4).is_err());
+        assert_eq!(partition_of(&value("alice"), "", 1).unwrap(), 0);
+    }
 }
@@ -1,9 +1,13 @@
+use std::collections::VecDeque;
 use std::vec;
 
 use agent_stream_kit::{
-    ASKit, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentDisplayConfigEntry,
-    AgentError, AgentOutput, AgentValue, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentDisplayConfigEntry,
+    AgentError, AgentOutput, AgentValue, AgentValueMap, AsAgent, AsAgentData, async_trait,
+    new_agent_boxed,
 };
+use chrono::{DateTime, Utc};
+use serde_json::json;
 
 // Display Data
 struct DisplayDataAgent {
@@ -95,10 +99,231 @@ impl AsAgent for DebugDataAgent {
     }
 }
 
+// Display Chart
+struct DisplayChartAgent {
+    data: AsAgentData,
+    history: ChartHistory,
+}
+
+// Rolling points of each series, in the order the series first appeared
+#[derive(Default)]
+struct ChartHistory {
+    series: Vec<(String, VecDeque<(i64, f64)>)>,
+}
+
+impl ChartHistory {
+    fn push(&mut self, name: &str, timestamp: i64, value: f64, capacity: usize) {
+        let points = match self.series.iter().position(|(n, _)| n == name) {
+            Some(i) => &mut self.series[i].1,
+            None => {
+                self.series.push((name.to_string(), VecDeque::new()));
+                &mut self.series.last_mut().unwrap().1
+            }
+        };
+        points.push_back((timestamp, value));
+        while points.len() > capacity {
+            points.pop_front();
+        }
+    }
+
+    fn to_data(&self) -> Result<AgentData, AgentError> {
+        let series = self
+            .series
+            .iter()
+            .map(|(name, points)| {
+                json!({
+                    "name": name,
+                    "points": points
+                        .iter()
+                        .map(|(t, y)| json!({"t": t, "y": y}))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>();
+        AgentData::from_json(json!({ "series": series }))
+    }
+}
+
+#[async_trait]
+impl AsAgent for DisplayChartAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            history: ChartHistory::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        _ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PIN_RESET {
+            self.history = ChartHistory::default();
+        } else {
+            let capacity = history_size(self.configs()?);
+            let timestamp = DateTime::<Utc>::from(self.askit().clock().now()).timestamp_millis();
+            if let Some(value) = data.as_f64() {
+                self.history.push(&pin, timestamp, value, capacity);
+            } else if let Some(obj) = data.as_object() {
+                // one point per numeric field
+                for (name, value) in obj.iter() {
+                    if let Some(value) = value.as_f64() {
+                        self.history.push(name, timestamp, value, capacity);
+                    }
+                }
+            } else {
+                return Err(AgentError::InvalidValue(format!(
+                    "chart value of kind {}",
+                    data.kind
+                )));
+            }
+        }
+        self.emit_display(DISPLAY_CHART, self.history.to_data()?);
+        Ok(())
+    }
+}
+
+// Display Table
+struct DisplayTableAgent {
+    data: AsAgentData,
+    history: TableHistory,
+}
+
+// Rolling rows with the columns in the order they first appeared
+#[derive(Default)]
+struct TableHistory {
+    columns: Vec<String>,
+    rows: VecDeque<AgentValueMap<String, AgentValue>>,
+}
+
+impl TableHistory {
+    fn push(&mut self, row: AgentValueMap<String, AgentValue>, capacity: usize) {
+        for key in row.keys() {
+            if !self.columns.contains(key) {
+                self.columns.push(key.clone());
+            }
+        }
+        self.rows.push_back(row);
+        while self.rows.len() > capacity {
+            self.rows.pop_front();
+        }
+    }
+
+    fn to_data(&self) -> AgentData {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| AgentValue::string(column.as_str()))
+            .collect();
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| {
+                AgentValue::array(
+                    self.columns
+                        .iter()
+                        .map(|column| row.get(column).cloned().unwrap_or_default())
+                        .collect(),
+                )
+            })
+            .collect();
+        AgentData::object(
+            [
+                ("columns".to_string(), AgentValue::array(columns)),
+                ("rows".to_string(), AgentValue::array(rows)),
+            ]
+            .into_iter()
+            .collect(),
+        )
+    }
+}
+
+#[async_trait]
+impl AsAgent for DisplayTableAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            history: TableHistory::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        _ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PIN_RESET {
+            self.history = TableHistory::default();
+        } else {
+            let capacity = history_size(self.configs()?);
+            if let Some(row) = data.as_object() {
+                self.history.push(row.clone(), capacity);
+            } else if let Some(arr) = data.as_array() {
+                for row in arr {
+                    let Some(row) = row.as_object() else {
+                        return Err(AgentError::InvalidArrayValue("object".to_string()));
+                    };
+                    self.history.push(row.clone(), capacity);
+                }
+            } else {
+                return Err(AgentError::InvalidValue(format!(
+                    "table row of kind {}",
+                    data.kind
+                )));
+            }
+        }
+        self.emit_display(DISPLAY_TABLE, self.history.to_data());
+        Ok(())
+    }
+}
+
+fn history_size(configs: &AgentConfigs) -> usize {
+    configs
+        .get_integer_or(CONFIG_HISTORY, DEFAULT_HISTORY)
+        .max(1) as usize
+}
+
 static KIND: &str = "agent";
 static CATEGORY: &str = "Core/Display";
 
 static DISPLAY_DATA: &str = "data";
+static DISPLAY_CHART: &str = "chart";
+static DISPLAY_TABLE: &str = "table";
+
+static PIN_RESET: &str = "reset";
+
+static CONFIG_HISTORY: &str = "history";
+
+const DEFAULT_HISTORY: i64 = 100;
 
 pub fn register_agents(askit: &ASKit) {
     // Display Data Agent
@@ -132,4 +357,73 @@ pub fn register_agents(askit: &ASKit) {
             AgentDisplayConfigEntry::new("object").hide_title(),
         )]),
     );
+
+    // Display Chart Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            KIND,
+            "std_display_chart",
+            Some(new_agent_boxed::<DisplayChartAgent>),
+        )
+        .title("Display Chart")
+        .description(
+            "Plot numbers as series named after the input port, or the fields of an object",
+        )
+        .category(CATEGORY)
+        .inputs(vec!["*"])
+        .integer_config_with(CONFIG_HISTORY, DEFAULT_HISTORY, |entry| {
+            entry
+                .title("History")
+                .description("Number of points kept per series")
+        })
+        .display_configs(vec![(
+            DISPLAY_CHART,
+            AgentDisplayConfigEntry::new("chart").hide_title(),
+        )]),
+    );
+
+    // Display Table Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            KIND,
+            "std_display_table",
+            Some(new_agent_boxed::<DisplayTableAgent>),
+        )
+        .title("Display Table")
+        .description("Show objects, or arrays of objects, as the rows of a table")
+        .category(CATEGORY)
+        .inputs(vec!["*"])
+        .integer_config_with(CONFIG_HISTORY, DEFAULT_HISTORY, |entry| {
+            entry.title("History").description("Number of rows kept")
+        })
+        .display_configs(vec![(
+            DISPLAY_TABLE,
+            AgentDisplayConfigEntry::new("table").hide_title(),
+        )]),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_table_history() {
+        let mut history = TableHistory::default();
+        let row = |pairs: &[(&str, i64)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), AgentValue::integer(*v)))
+                .collect::<AgentValueMap<_, _>>()
+        };
+        history.push(row(&[("a", 1)]), 2);
+        history.push(row(&[("a", 2), ("b", 3)]), 2);
+        history.push(row(&[("b", 4)]), 2);
+
+        let data = history.to_data().value.to_json();
+        assert_eq!(
+            data,
+            json!({"columns": ["a", "b"], "rows": [[2, 3], [null, 4]]})
+        );
+    }
 }
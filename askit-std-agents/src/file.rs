@@ -2,8 +2,8 @@ use std::fs;
 use std::path::Path;
 
 use agent_stream_kit::{
-    ASKit, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput, AsAgent,
-    AsAgentData, async_trait, new_agent_boxed,
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AsAgent, AsAgentData, async_trait, new_agent_boxed,
 };
 
 // List Files Agent
@@ -38,6 +38,8 @@ impl AsAgent for ListFilesAgent {
         _pin: String,
         data: AgentData,
     ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Filesystem)?;
+
         let path = data
             .as_str()
             .ok_or_else(|| AgentError::InvalidValue("path is not a string".to_string()))?;
@@ -111,6 +113,8 @@ impl AsAgent for ReadTextFileAgent {
         _pin: String,
         data: AgentData,
     ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Filesystem)?;
+
         let path = data
             .as_str()
             .ok_or_else(|| AgentError::InvalidValue("path is not a string".into()))?;
@@ -170,6 +174,8 @@ impl AsAgent for WriteTextFileAgent {
         _pin: String,
         data: AgentData,
     ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Filesystem)?;
+
         let input = data
             .as_object()
             .ok_or_else(|| AgentError::InvalidValue("Input is not an object".into()))?;
@@ -224,7 +230,8 @@ pub fn register_agents(askit: &ASKit) {
         .title("List Files")
         .category(CATEGORY)
         .inputs(vec![PIN_PATH])
-        .outputs(vec![PIN_FILES]),
+        .outputs(vec![PIN_FILES])
+        .capabilities(vec![AgentCapability::Filesystem]),
     );
 
     // Read Text File Agent
@@ -237,7 +244,8 @@ pub fn register_agents(askit: &ASKit) {
         .title("Read Text File")
         .category(CATEGORY)
         .inputs(vec![PIN_PATH])
-        .outputs(vec![PIN_TEXT]),
+        .outputs(vec![PIN_TEXT])
+        .capabilities(vec![AgentCapability::Filesystem]),
     );
 
     // Write Text File Agent
@@ -250,6 +258,7 @@ pub fn register_agents(askit: &ASKit) {
         .title("Write Text File")
         .category(CATEGORY)
         .inputs(vec![PIN_DATA])
-        .outputs(vec![PIN_DATA]),
+        .outputs(vec![PIN_DATA])
+        .capabilities(vec![AgentCapability::Filesystem]),
     );
 }
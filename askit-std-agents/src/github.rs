@@ -0,0 +1,638 @@
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use serde_json::{Value, json};
+
+use crate::rest::{self, action_allowed, comment_of, integer_of};
+
+// GitHub
+//
+// Issues, pull requests and comments of a repository, through the REST API. The token and
+// the API URL, for GitHub Enterprise, are the global configs of github_issue.
+
+// GitHub Issue Agent
+struct GitHubIssueAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for GitHubIssueAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let repo = configs.get_string(CONFIG_REPO)?;
+        let with_comments = configs.get_bool_or_default(CONFIG_COMMENTS);
+        let with_diff = configs.get_bool_or_default(CONFIG_DIFF);
+        let number = integer_of(&data, "number").ok_or_else(|| {
+            AgentError::InvalidValue("issue number is not an integer".to_string())
+        })?;
+
+        let api = GitHubApi::new(self.askit());
+        let issue = api
+            .get_json(&format!("/repos/{}/issues/{}", repo, number))
+            .await?;
+        let mut summary = issue_summary(&issue);
+        if with_comments {
+            let comments = api
+                .get_json(&format!("/repos/{}/issues/{}/comments", repo, number))
+                .await?;
+            summary["comments"] = comments_summary(&comments);
+        }
+        if with_diff && issue.get("pull_request").is_some() {
+            let diff = api
+                .get(&format!("/repos/{}/pulls/{}", repo, number))
+                .header(reqwest::header::ACCEPT, "application/vnd.github.diff");
+            summary["diff"] = Value::String(rest::send_text(diff).await?);
+        }
+        self.try_output(ctx, PIN_ISSUE, AgentData::from_json(summary)?)
+    }
+}
+
+// GitHub Issues Agent
+struct GitHubIssuesAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for GitHubIssuesAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let repo = configs.get_string(CONFIG_REPO)?;
+        let state = configs.get_string_or(CONFIG_STATE, STATE_DEFAULT);
+        let pulls = configs.get_bool_or_default(CONFIG_PULL_REQUESTS);
+        let limit = configs
+            .get_integer_or(CONFIG_LIMIT, LIMIT_DEFAULT)
+            .clamp(1, 100);
+
+        let path = if pulls { "pulls" } else { "issues" };
+        let items = GitHubApi::new(self.askit())
+            .get_json(&format!(
+                "/repos/{}/{}?state={}&per_page={}",
+                repo, path, state, limit
+            ))
+            .await?;
+        let items = items
+            .as_array()
+            .map(|items| {
+                items
+                    .iter()
+                    // the issues API lists pull requests too
+                    .filter(|item| pulls || item.get("pull_request").is_none())
+                    .map(issue_summary)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        self.try_output(ctx, PIN_ISSUES, AgentData::from_json(Value::Array(items))?)
+    }
+}
+
+// GitHub Comment Agent
+struct GitHubCommentAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for GitHubCommentAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let repo = configs.get_string(CONFIG_REPO)?;
+        let (number, body) = comment_of(&data, configs.get_integer_or_default(CONFIG_NUMBER))?;
+
+        let api = GitHubApi::new(self.askit());
+        let request = api
+            .post(&format!("/repos/{}/issues/{}/comments", repo, number))
+            .json(&json!({ "body": body }));
+        let comment = rest::send_json(request).await?;
+        let summary = json!({
+            "id": comment["id"],
+            "url": comment["html_url"],
+            "number": number,
+        });
+        self.try_output(ctx, PIN_COMMENT, AgentData::from_json(summary)?)
+    }
+}
+
+// GitHub Webhook Agent
+struct GitHubWebhookAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for GitHubWebhookAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let actions = self.configs()?.get_string_or_default(CONFIG_ACTIONS);
+        let payload = data.value.to_json();
+        let Some((pin, event)) = webhook_event(&payload) else {
+            return Ok(());
+        };
+        if !action_allowed(&actions, event["action"].as_str()) {
+            return Ok(());
+        }
+        self.try_output(ctx, pin, AgentData::from_json(event)?)
+    }
+}
+
+struct GitHubApi {
+    api_url: String,
+    token: String,
+}
+
+impl GitHubApi {
+    fn new(askit: &ASKit) -> Self {
+        let configs = askit.get_global_configs(ISSUE_DEF_NAME).unwrap_or_default();
+        Self {
+            api_url: configs
+                .get_string_or(CONFIG_API_URL, API_URL_DEFAULT)
+                .trim_end_matches('/')
+                .to_string(),
+            token: configs.get_string_or_default(CONFIG_GITHUB_TOKEN),
+        }
+    }
+
+    fn get(&self, path: &str) -> reqwest::RequestBuilder {
+        self.request(rest::client().get(format!("{}{}", self.api_url, path)))
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.request(rest::client().post(format!("{}{}", self.api_url, path)))
+    }
+
+    async fn get_json(&self, path: &str) -> Result<Value, AgentError> {
+        rest::send_json(self.get(path)).await
+    }
+
+    fn request(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let request = request
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", API_VERSION);
+        if self.token.is_empty() {
+            request
+        } else {
+            request.bearer_auth(&self.token)
+        }
+    }
+}
+
+// Fields of an issue or a pull request worth giving to an LLM
+fn issue_summary(issue: &Value) -> Value {
+    let labels = issue["labels"]
+        .as_array()
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|label| label["name"].as_str())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    let mut summary = json!({
+        "number": issue["number"],
+        "title": issue["title"],
+        "body": issue["body"].as_str().unwrap_or_default(),
+        "state": issue["state"],
+        "author": issue["user"]["login"],
+        "url": issue["html_url"],
+        "labels": labels,
+        "pull_request": issue.get("pull_request").is_some() || issue.get("head").is_some(),
+    });
+    if let Some(head) = issue["head"]["ref"].as_str() {
+        summary["head"] = json!(head);
+        summary["base"] = issue["base"]["ref"].clone();
+    }
+    summary
+}
+
+fn comments_summary(comments: &Value) -> Value {
+    let comments = comments
+        .as_array()
+        .map(|comments| {
+            comments
+                .iter()
+                .map(|comment| {
+                    json!({
+                        "author": comment["user"]["login"],
+                        "body": comment["body"],
+                        "created_at": comment["created_at"],
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    Value::Array(comments)
+}
+
+// (pin, event) of a webhook payload. The payload is the request body, or an object of
+// `headers` and `body`, whose X-GitHub-Event header names the event.
+fn webhook_event(payload: &Value) -> Option<(&'static str, Value)> {
+    let (event_name, body) = match (payload.get("headers"), payload.get("body")) {
+        (Some(headers), Some(body)) => {
+            let event_name = headers.as_object().and_then(|headers| {
+                headers
+                    .iter()
+                    .find(|(key, _)| key.eq_ignore_ascii_case("x-github-event"))
+                    .and_then(|(_, value)| value.as_str())
+            });
+            (event_name, body)
+        }
+        _ => (None, payload),
+    };
+    // without the header, the event is told by its fields
+    let event_name = event_name.unwrap_or(if body.get("pull_request").is_some() {
+        "pull_request"
+    } else if body.get("comment").is_some() {
+        "issue_comment"
+    } else if body.get("issue").is_some() {
+        "issues"
+    } else if body.get("commits").is_some() {
+        "push"
+    } else {
+        return None;
+    });
+
+    let repo = body["repository"]["full_name"].clone();
+    let sender = body["sender"]["login"].clone();
+    let action = body["action"].clone();
+    match event_name {
+        "pull_request" | "pull_request_review" => {
+            let mut event = issue_summary(&body["pull_request"]);
+            event["event"] = json!(event_name);
+            event["action"] = action;
+            event["repo"] = repo;
+            event["sender"] = sender;
+            Some((PIN_PULL_REQUEST, event))
+        }
+        "issues" => {
+            let mut event = issue_summary(&body["issue"]);
+            event["event"] = json!(event_name);
+            event["action"] = action;
+            event["repo"] = repo;
+            event["sender"] = sender;
+            Some((PIN_ISSUE, event))
+        }
+        "issue_comment" | "pull_request_review_comment" => {
+            let issue = if body.get("issue").is_some() {
+                &body["issue"]
+            } else {
+                &body["pull_request"]
+            };
+            Some((
+                PIN_COMMENT,
+                json!({
+                    "event": event_name,
+                    "action": action,
+                    "repo": repo,
+                    "sender": sender,
+                    "number": issue["number"],
+                    "title": issue["title"],
+                    "pull_request": issue.get("pull_request").is_some()
+                        || issue.get("head").is_some(),
+                    "author": body["comment"]["user"]["login"],
+                    "body": body["comment"]["body"],
+                    "url": body["comment"]["html_url"],
+                }),
+            ))
+        }
+        "push" => {
+            let commits = body["commits"]
+                .as_array()
+                .map(|commits| {
+                    commits
+                        .iter()
+                        .map(|commit| {
+                            json!({
+                                "id": commit["id"],
+                                "message": commit["message"],
+                                "author": commit["author"]["name"],
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            Some((
+                PIN_PUSH,
+                json!({
+                    "event": event_name,
+                    "repo": repo,
+                    "sender": sender,
+                    "ref": body["ref"],
+                    "before": body["before"],
+                    "after": body["after"],
+                    "commits": commits,
+                }),
+            ))
+        }
+        _ => Some((
+            PIN_OTHER,
+            json!({
+                "event": event_name,
+                "action": action,
+                "repo": repo,
+                "sender": sender,
+                "payload": body,
+            }),
+        )),
+    }
+}
+
+static AGENT_KIND: &str = "Agent";
+static CATEGORY: &str = "Core/GitHub";
+
+static ISSUE_DEF_NAME: &str = "github_issue";
+
+static PIN_NUMBER: &str = "number";
+static PIN_ISSUE: &str = "issue";
+static PIN_ISSUES: &str = "issues";
+static PIN_COMMENT: &str = "comment";
+static PIN_PAYLOAD: &str = "payload";
+static PIN_PULL_REQUEST: &str = "pull_request";
+static PIN_PUSH: &str = "push";
+static PIN_OTHER: &str = "other";
+
+static CONFIG_GITHUB_TOKEN: &str = "github_token";
+static CONFIG_API_URL: &str = "api_url";
+static CONFIG_REPO: &str = "repo";
+static CONFIG_COMMENTS: &str = "comments";
+static CONFIG_DIFF: &str = "diff";
+static CONFIG_STATE: &str = "state";
+static CONFIG_PULL_REQUESTS: &str = "pull_requests";
+static CONFIG_LIMIT: &str = "limit";
+static CONFIG_NUMBER: &str = "number";
+static CONFIG_ACTIONS: &str = "actions";
+
+static API_URL_DEFAULT: &str = "https://api.github.com";
+static API_VERSION: &str = "2022-11-28";
+static STATE_DEFAULT: &str = "open";
+const LIMIT_DEFAULT: i64 = 30;
+
+pub fn register_agents(askit: &ASKit) {
+    // GitHub Issue Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            ISSUE_DEF_NAME,
+            Some(new_agent_boxed::<GitHubIssueAgent>),
+        )
+        .title("GitHub Issue")
+        .description("Fetches the issue or pull request of the received number")
+        .category(CATEGORY)
+        .inputs(vec![PIN_NUMBER])
+        .outputs(vec![PIN_ISSUE])
+        .capabilities(vec![AgentCapability::Network])
+        .custom_global_config_with(CONFIG_GITHUB_TOKEN, "", "password", |entry| {
+            entry.title("GitHub Token")
+        })
+        .string_global_config_with(CONFIG_API_URL, API_URL_DEFAULT, |entry| {
+            entry.title("API URL")
+        })
+        .string_config_with(CONFIG_REPO, "", |entry| {
+            entry
+                .title("Repository")
+                .description("owner/repo")
+                .required()
+        })
+        .boolean_config_with(CONFIG_COMMENTS, false, |entry| entry.title("With Comments"))
+        .boolean_config_with(CONFIG_DIFF, false, |entry| {
+            entry
+                .title("With Diff")
+                .description("The diff of a pull request")
+        }),
+    );
+
+    // GitHub Issues Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "github_issues",
+            Some(new_agent_boxed::<GitHubIssuesAgent>),
+        )
+        .title("GitHub Issues")
+        .description("Lists the issues or pull requests of the repository")
+        .category(CATEGORY)
+        .inputs(vec!["*"])
+        .outputs(vec![PIN_ISSUES])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_REPO, "", |entry| {
+            entry
+                .title("Repository")
+                .description("owner/repo")
+                .required()
+        })
+        .string_config_with(CONFIG_STATE, STATE_DEFAULT, |entry| {
+            entry.title("State").description("open, closed or all")
+        })
+        .boolean_config_with(CONFIG_PULL_REQUESTS, false, |entry| {
+            entry.title("Pull Requests")
+        })
+        .integer_config_with(CONFIG_LIMIT, LIMIT_DEFAULT, |entry| {
+            entry.title("Limit").description("up to 100")
+        }),
+    );
+
+    // GitHub Comment Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "github_comment",
+            Some(new_agent_boxed::<GitHubCommentAgent>),
+        )
+        .title("GitHub Comment")
+        .description(
+            "Comments on an issue or pull request. The input is the text, or an object of number and body",
+        )
+        .category(CATEGORY)
+        .inputs(vec![PIN_COMMENT])
+        .outputs(vec![PIN_COMMENT])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_REPO, "", |entry| {
+            entry.title("Repository").description("owner/repo").required()
+        })
+        .integer_config_with(CONFIG_NUMBER, 0, |entry| {
+            entry
+                .title("Number")
+                .description("Used when the input has no number")
+        }),
+    );
+
+    // GitHub Webhook Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "github_webhook",
+            Some(new_agent_boxed::<GitHubWebhookAgent>),
+        )
+        .title("GitHub Webhook")
+        .description(
+            "Outputs the event of a webhook payload, the body or an object of headers and body",
+        )
+        .category(CATEGORY)
+        .inputs(vec![PIN_PAYLOAD])
+        .outputs(vec![
+            PIN_PULL_REQUEST,
+            PIN_ISSUE,
+            PIN_COMMENT,
+            PIN_PUSH,
+            PIN_OTHER,
+        ])
+        .string_config_with(CONFIG_ACTIONS, "", |entry| {
+            entry.title("Actions").description(
+                "Comma-separated actions to pass, such as opened,synchronize. All when empty",
+            )
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_event() {
+        let payload = json!({
+            "headers": {"X-GitHub-Event": "pull_request"},
+            "body": {
+                "action": "opened",
+                "pull_request": {
+                    "number": 7,
+                    "title": "Fix",
+                    "body": null,
+                    "state": "open",
+                    "user": {"login": "alice"},
+                    "html_url": "https://github.com/o/r/pull/7",
+                    "labels": [{"name": "bug"}],
+                    "head": {"ref": "fix"},
+                    "base": {"ref": "main"},
+                },
+                "repository": {"full_name": "o/r"},
+                "sender": {"login": "alice"},
+            },
+        });
+        let (pin, event) = webhook_event(&payload).unwrap();
+        assert_eq!(pin, PIN_PULL_REQUEST);
+        assert_eq!(event["number"], 7);
+        assert_eq!(event["body"], "");
+        assert_eq!(event["labels"], json!(["bug"]));
+        assert_eq!(event["pull_request"], true);
+        assert_eq!(event["base"], "main");
+        assert_eq!(event["repo"], "o/r");
+
+        // without headers
+        let payload = json!({
+            "action": "created",
+            "issue": {"number": 3, "title": "Bug"},
+            "comment": {"body": "+1", "user": {"login": "bob"}},
+        });
+        let (pin, event) = webhook_event(&payload).unwrap();
+        assert_eq!(pin, PIN_COMMENT);
+        assert_eq!(event["author"], "bob");
+        assert_eq!(event["pull_request"], false);
+        assert!(webhook_event(&json!({"zen": "hi"})).is_none());
+
+        assert!(action_allowed("", None));
+        assert!(action_allowed("opened, synchronize", Some("synchronize")));
+        assert!(!action_allowed("opened", Some("closed")));
+
+        let (number, body) = comment_of(&AgentData::string("LGTM"), 7).unwrap();
+        assert_eq!((number, body.as_str()), (7, "LGTM"));
+        assert!(comment_of(&AgentData::string("LGTM"), 0).is_err());
+    }
+}
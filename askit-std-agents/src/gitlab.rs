@@ -0,0 +1,649 @@
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use serde_json::{Value, json};
+
+use crate::rest::{self, action_allowed, comment_of, integer_of};
+
+// GitLab
+//
+// Issues, merge requests and notes of a project, through the REST API v4. The token and the
+// URL of the instance are the global configs of gitlab_issue.
+
+// GitLab Issue Agent
+struct GitLabIssueAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for GitLabIssueAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let project = project_id(&configs.get_string(CONFIG_PROJECT)?);
+        let kind = kind_path(configs.get_bool_or_default(CONFIG_MERGE_REQUEST));
+        let with_comments = configs.get_bool_or_default(CONFIG_COMMENTS);
+        let with_diff = configs.get_bool_or_default(CONFIG_DIFF);
+        let iid = integer_of(&data, "number").ok_or_else(|| {
+            AgentError::InvalidValue("issue number is not an integer".to_string())
+        })?;
+
+        let api = GitLabApi::new(self.askit());
+        let path = format!("/projects/{}/{}/{}", project, kind, iid);
+        let mut summary = issue_summary(&api.get_json(&path).await?);
+        if with_comments {
+            let notes = api
+                .get_json(&format!("{}/notes?sort=asc&per_page=100", path))
+                .await?;
+            summary["comments"] = notes_summary(&notes);
+        }
+        if with_diff && kind == MERGE_REQUESTS {
+            let diffs = api.get_json(&format!("{}/diffs", path)).await?;
+            summary["diff"] = Value::String(unified_diff(&diffs));
+        }
+        self.try_output(ctx, PIN_ISSUE, AgentData::from_json(summary)?)
+    }
+}
+
+// GitLab Issues Agent
+struct GitLabIssuesAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for GitLabIssuesAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let project = project_id(&configs.get_string(CONFIG_PROJECT)?);
+        let kind = kind_path(configs.get_bool_or_default(CONFIG_MERGE_REQUEST));
+        let state = configs.get_string_or(CONFIG_STATE, STATE_DEFAULT);
+        let limit = configs
+            .get_integer_or(CONFIG_LIMIT, LIMIT_DEFAULT)
+            .clamp(1, 100);
+
+        let items = GitLabApi::new(self.askit())
+            .get_json(&format!(
+                "/projects/{}/{}?state={}&per_page={}",
+                project, kind, state, limit
+            ))
+            .await?;
+        let items = items
+            .as_array()
+            .map(|items| items.iter().map(issue_summary).collect::<Vec<_>>())
+            .unwrap_or_default();
+        self.try_output(ctx, PIN_ISSUES, AgentData::from_json(Value::Array(items))?)
+    }
+}
+
+// GitLab Comment Agent
+struct GitLabCommentAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for GitLabCommentAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let project = project_id(&configs.get_string(CONFIG_PROJECT)?);
+        let kind = kind_path(configs.get_bool_or_default(CONFIG_MERGE_REQUEST));
+        let (iid, body) = comment_of(&data, configs.get_integer_or_default(CONFIG_NUMBER))?;
+
+        let api = GitLabApi::new(self.askit());
+        let request = api
+            .post(&format!("/projects/{}/{}/{}/notes", project, kind, iid))
+            .json(&json!({ "body": body }));
+        let note = rest::send_json(request).await?;
+        let summary = json!({
+            "id": note["id"],
+            "number": iid,
+        });
+        self.try_output(ctx, PIN_COMMENT, AgentData::from_json(summary)?)
+    }
+}
+
+// GitLab Webhook Agent
+struct GitLabWebhookAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for GitLabWebhookAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let actions = self.configs()?.get_string_or_default(CONFIG_ACTIONS);
+        let payload = data.value.to_json();
+        let Some((pin, event)) = webhook_event(&payload) else {
+            return Ok(());
+        };
+        if !action_allowed(&actions, event["action"].as_str()) {
+            return Ok(());
+        }
+        self.try_output(ctx, pin, AgentData::from_json(event)?)
+    }
+}
+
+struct GitLabApi {
+    api_url: String,
+    token: String,
+}
+
+impl GitLabApi {
+    fn new(askit: &ASKit) -> Self {
+        let configs = askit.get_global_configs(ISSUE_DEF_NAME).unwrap_or_default();
+        let url = configs.get_string_or(CONFIG_GITLAB_URL, GITLAB_URL_DEFAULT);
+        Self {
+            api_url: format!("{}/api/v4", url.trim_end_matches('/')),
+            token: configs.get_string_or_default(CONFIG_GITLAB_TOKEN),
+        }
+    }
+
+    fn post(&self, path: &str) -> reqwest::RequestBuilder {
+        self.request(rest::client().post(format!("{}{}", self.api_url, path)))
+    }
+
+    async fn get_json(&self, path: &str) -> Result<Value, AgentError> {
+        let request = self.request(rest::client().get(format!("{}{}", self.api_url, path)));
+        rest::send_json(request).await
+    }
+
+    fn request(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        if self.token.is_empty() {
+            request
+        } else {
+            request.header("PRIVATE-TOKEN", &self.token)
+        }
+    }
+}
+
+// The path of a project, such as group/project, escaped for the URL
+fn project_id(project: &str) -> String {
+    project.trim().replace('/', "%2F")
+}
+
+fn kind_path(merge_request: bool) -> &'static str {
+    if merge_request {
+        MERGE_REQUESTS
+    } else {
+        "issues"
+    }
+}
+
+// Fields of an issue or a merge request, named as in the GitHub agents
+fn issue_summary(issue: &Value) -> Value {
+    let mut summary = json!({
+        "number": issue["iid"],
+        "title": issue["title"],
+        "body": issue["description"].as_str().unwrap_or_default(),
+        "state": issue["state"],
+        "author": issue["author"]["username"],
+        "url": issue["web_url"],
+        "labels": issue["labels"].as_array().cloned().unwrap_or_default(),
+        "merge_request": issue.get("source_branch").is_some(),
+    });
+    if issue.get("source_branch").is_some() {
+        summary["head"] = issue["source_branch"].clone();
+        summary["base"] = issue["target_branch"].clone();
+    }
+    summary
+}
+
+fn notes_summary(notes: &Value) -> Value {
+    let notes = notes
+        .as_array()
+        .map(|notes| {
+            notes
+                .iter()
+                // notes of the system, such as "changed the description"
+                .filter(|note| !note["system"].as_bool().unwrap_or(false))
+                .map(|note| {
+                    json!({
+                        "author": note["author"]["username"],
+                        "body": note["body"],
+                        "created_at": note["created_at"],
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    Value::Array(notes)
+}
+
+// The diffs of the files of a merge request, joined into a unified diff
+fn unified_diff(diffs: &Value) -> String {
+    diffs
+        .as_array()
+        .map(|diffs| {
+            diffs
+                .iter()
+                .map(|diff| {
+                    format!(
+                        "--- a/{}\n+++ b/{}\n{}",
+                        diff["old_path"].as_str().unwrap_or_default(),
+                        diff["new_path"].as_str().unwrap_or_default(),
+                        diff["diff"].as_str().unwrap_or_default()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("")
+        })
+        .unwrap_or_default()
+}
+
+// (pin, event) of a webhook payload, the request body or an object of `headers` and `body`.
+// The event is told by the object_kind of the body.
+fn webhook_event(payload: &Value) -> Option<(&'static str, Value)> {
+    let body = match (payload.get("headers"), payload.get("body")) {
+        (Some(_), Some(body)) => body,
+        _ => payload,
+    };
+    let kind = body["object_kind"].as_str()?;
+    let attributes = &body["object_attributes"];
+    let project = body["project"]["path_with_namespace"].clone();
+    let sender = body["user"]["username"].clone();
+    let labels = body["labels"]
+        .as_array()
+        .map(|labels| {
+            labels
+                .iter()
+                .filter_map(|label| label["title"].as_str())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    match kind {
+        "merge_request" | "issue" => {
+            let merge_request = kind == "merge_request";
+            let mut event = json!({
+                "event": kind,
+                "action": attributes["action"],
+                "project": project,
+                "sender": sender,
+                "number": attributes["iid"],
+                "title": attributes["title"],
+                "body": attributes["description"].as_str().unwrap_or_default(),
+                "state": attributes["state"],
+                "url": attributes["url"],
+                "labels": labels,
+                "merge_request": merge_request,
+            });
+            if merge_request {
+                event["head"] = attributes["source_branch"].clone();
+                event["base"] = attributes["target_branch"].clone();
+                Some((PIN_MERGE_REQUEST, event))
+            } else {
+                Some((PIN_ISSUE, event))
+            }
+        }
+        "note" => {
+            let (number, title, merge_request) = if body.get("merge_request").is_some() {
+                let mr = &body["merge_request"];
+                (mr["iid"].clone(), mr["title"].clone(), true)
+            } else {
+                let issue = &body["issue"];
+                (issue["iid"].clone(), issue["title"].clone(), false)
+            };
+            Some((
+                PIN_COMMENT,
+                json!({
+                    "event": kind,
+                    "action": attributes["action"],
+                    "project": project,
+                    "sender": sender,
+                    "number": number,
+                    "title": title,
+                    "merge_request": merge_request,
+                    "author": sender,
+                    "body": attributes["note"],
+                    "url": attributes["url"],
+                }),
+            ))
+        }
+        "push" => {
+            let commits = body["commits"]
+                .as_array()
+                .map(|commits| {
+                    commits
+                        .iter()
+                        .map(|commit| {
+                            json!({
+                                "id": commit["id"],
+                                "message": commit["message"],
+                                "author": commit["author"]["name"],
+                            })
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            Some((
+                PIN_PUSH,
+                json!({
+                    "event": kind,
+                    "project": project,
+                    "sender": body["user_username"],
+                    "ref": body["ref"],
+                    "before": body["before"],
+                    "after": body["after"],
+                    "commits": commits,
+                }),
+            ))
+        }
+        _ => Some((
+            PIN_OTHER,
+            json!({
+                "event": kind,
+                "action": attributes["action"],
+                "project": project,
+                "sender": sender,
+                "payload": body,
+            }),
+        )),
+    }
+}
+
+static AGENT_KIND: &str = "Agent";
+static CATEGORY: &str = "Core/GitLab";
+
+static ISSUE_DEF_NAME: &str = "gitlab_issue";
+static MERGE_REQUESTS: &str = "merge_requests";
+
+static PIN_NUMBER: &str = "number";
+static PIN_ISSUE: &str = "issue";
+static PIN_ISSUES: &str = "issues";
+static PIN_COMMENT: &str = "comment";
+static PIN_PAYLOAD: &str = "payload";
+static PIN_MERGE_REQUEST: &str = "merge_request";
+static PIN_PUSH: &str = "push";
+static PIN_OTHER: &str = "other";
+
+static CONFIG_GITLAB_TOKEN: &str = "gitlab_token";
+static CONFIG_GITLAB_URL: &str = "gitlab_url";
+static CONFIG_PROJECT: &str = "project";
+static CONFIG_MERGE_REQUEST: &str = "merge_request";
+static CONFIG_COMMENTS: &str = "comments";
+static CONFIG_DIFF: &str = "diff";
+static CONFIG_STATE: &str = "state";
+static CONFIG_LIMIT: &str = "limit";
+static CONFIG_NUMBER: &str = "number";
+static CONFIG_ACTIONS: &str = "actions";
+
+static GITLAB_URL_DEFAULT: &str = "https://gitlab.com";
+static STATE_DEFAULT: &str = "opened";
+const LIMIT_DEFAULT: i64 = 30;
+
+pub fn register_agents(askit: &ASKit) {
+    // GitLab Issue Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            ISSUE_DEF_NAME,
+            Some(new_agent_boxed::<GitLabIssueAgent>),
+        )
+        .title("GitLab Issue")
+        .description("Fetches the issue or merge request of the received number (iid)")
+        .category(CATEGORY)
+        .inputs(vec![PIN_NUMBER])
+        .outputs(vec![PIN_ISSUE])
+        .capabilities(vec![AgentCapability::Network])
+        .custom_global_config_with(CONFIG_GITLAB_TOKEN, "", "password", |entry| {
+            entry.title("GitLab Token")
+        })
+        .string_global_config_with(CONFIG_GITLAB_URL, GITLAB_URL_DEFAULT, |entry| {
+            entry.title("GitLab URL")
+        })
+        .string_config_with(CONFIG_PROJECT, "", |entry| {
+            entry
+                .title("Project")
+                .description("group/project")
+                .required()
+        })
+        .boolean_config_with(CONFIG_MERGE_REQUEST, false, |entry| {
+            entry.title("Merge Request")
+        })
+        .boolean_config_with(CONFIG_COMMENTS, false, |entry| entry.title("With Comments"))
+        .boolean_config_with(CONFIG_DIFF, false, |entry| {
+            entry
+                .title("With Diff")
+                .description("The diff of a merge request")
+        }),
+    );
+
+    // GitLab Issues Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "gitlab_issues",
+            Some(new_agent_boxed::<GitLabIssuesAgent>),
+        )
+        .title("GitLab Issues")
+        .description("Lists the issues or merge requests of the project")
+        .category(CATEGORY)
+        .inputs(vec!["*"])
+        .outputs(vec![PIN_ISSUES])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROJECT, "", |entry| {
+            entry
+                .title("Project")
+                .description("group/project")
+                .required()
+        })
+        .boolean_config_with(CONFIG_MERGE_REQUEST, false, |entry| {
+            entry.title("Merge Requests")
+        })
+        .string_config_with(CONFIG_STATE, STATE_DEFAULT, |entry| {
+            entry
+                .title("State")
+                .description("opened, closed, merged or all")
+        })
+        .integer_config_with(CONFIG_LIMIT, LIMIT_DEFAULT, |entry| {
+            entry.title("Limit").description("up to 100")
+        }),
+    );
+
+    // GitLab Comment Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "gitlab_comment",
+            Some(new_agent_boxed::<GitLabCommentAgent>),
+        )
+        .title("GitLab Comment")
+        .description(
+            "Comments on an issue or merge request. The input is the text, or an object of number and body",
+        )
+        .category(CATEGORY)
+        .inputs(vec![PIN_COMMENT])
+        .outputs(vec![PIN_COMMENT])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_PROJECT, "", |entry| {
+            entry
+                .title("Project")
+                .description("group/project")
+                .required()
+        })
+        .boolean_config_with(CONFIG_MERGE_REQUEST, false, |entry| {
+            entry.title("Merge Request")
+        })
+        .integer_config_with(CONFIG_NUMBER, 0, |entry| {
+            entry
+                .title("Number")
+                .description("Used when the input has no number")
+        }),
+    );
+
+    // GitLab Webhook Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "gitlab_webhook",
+            Some(new_agent_boxed::<GitLabWebhookAgent>),
+        )
+        .title("GitLab Webhook")
+        .description(
+            "Outputs the event of a webhook payload, the body or an object of headers and body",
+        )
+        .category(CATEGORY)
+        .inputs(vec![PIN_PAYLOAD])
+        .outputs(vec![
+            PIN_MERGE_REQUEST,
+            PIN_ISSUE,
+            PIN_COMMENT,
+            PIN_PUSH,
+            PIN_OTHER,
+        ])
+        .string_config_with(CONFIG_ACTIONS, "", |entry| {
+            entry
+                .title("Actions")
+                .description("Comma-separated actions to pass, such as open,update. All when empty")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_webhook_event() {
+        let payload = json!({
+            "headers": {"X-Gitlab-Event": "Merge Request Hook"},
+            "body": {
+                "object_kind": "merge_request",
+                "user": {"username": "alice"},
+                "project": {"path_with_namespace": "g/p"},
+                "object_attributes": {
+                    "iid": 7,
+                    "title": "Fix",
+                    "description": null,
+                    "state": "opened",
+                    "action": "open",
+                    "url": "https://gitlab.com/g/p/-/merge_requests/7",
+                    "source_branch": "fix",
+                    "target_branch": "main",
+                },
+                "labels": [{"title": "bug"}],
+            },
+        });
+        let (pin, event) = webhook_event(&payload).unwrap();
+        assert_eq!(pin, PIN_MERGE_REQUEST);
+        assert_eq!(event["number"], 7);
+        assert_eq!(event["body"], "");
+        assert_eq!(event["labels"], json!(["bug"]));
+        assert_eq!(event["base"], "main");
+        assert_eq!(event["project"], "g/p");
+
+        // without headers
+        let payload = json!({
+            "object_kind": "note",
+            "user": {"username": "bob"},
+            "object_attributes": {"note": "+1"},
+            "issue": {"iid": 3, "title": "Bug"},
+        });
+        let (pin, event) = webhook_event(&payload).unwrap();
+        assert_eq!(pin, PIN_COMMENT);
+        assert_eq!(event["number"], 3);
+        assert_eq!(event["merge_request"], false);
+        assert!(webhook_event(&json!({"zen": "hi"})).is_none());
+
+        assert_eq!(project_id("group/sub/project"), "group%2Fsub%2Fproject");
+    }
+}
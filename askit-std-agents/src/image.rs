@@ -1,8 +1,8 @@
 use std::sync::Arc;
 
 use agent_stream_kit::{
-    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
-    AsAgent, AsAgentData, async_trait, new_agent_boxed,
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AsAgent, AsAgentData, async_trait, new_agent_boxed,
 };
 
 #[cfg(feature = "image")]
@@ -394,6 +394,8 @@ impl AsAgent for OpenImageAgent {
         _pin: String,
         data: AgentData,
     ) -> Result<(), AgentError> {
+        self.check_capability(AgentCapability::Filesystem)?;
+
         let filename = data
             .as_str()
             .ok_or_else(|| AgentError::InvalidValue("Expected filename string".into()))?;
@@ -450,6 +452,8 @@ impl AsAgent for SaveImageAgent {
             ));
         };
 
+        self.check_capability(AgentCapability::Filesystem)?;
+
         photon_rs::native::save_image((*image).clone(), std::path::Path::new(filename)).map_err(
             |e| AgentError::InvalidValue(format!("Failed to save image {}: {}", filename, e)),
         )?;
@@ -557,7 +561,8 @@ pub fn register_agents(askit: &ASKit) {
         .title("Open Image")
         .category(CATEGORY)
         .inputs(vec![PIN_FILENAME])
-        .outputs(vec![PIN_IMAGE]),
+        .outputs(vec![PIN_IMAGE])
+        .capabilities(vec![AgentCapability::Filesystem]),
     );
 
     askit.register_agent(
@@ -569,6 +574,7 @@ pub fn register_agents(askit: &ASKit) {
         .title("Save Image")
         .category(CATEGORY)
         .inputs(vec![PIN_IMAGE_FILENAME])
-        .outputs(vec![PIN_RESULT]),
+        .outputs(vec![PIN_RESULT])
+        .capabilities(vec![AgentCapability::Filesystem]),
     );
 }
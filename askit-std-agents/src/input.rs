@@ -1,9 +1,12 @@
+use std::path::Path;
 use std::vec;
 
 use agent_stream_kit::{
-    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
-    AgentStatus, AsAgent, AsAgentData, new_agent_boxed,
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentDisplayConfigEntry,
+    AgentError, AgentKindDefinition, AgentOutput, AgentStatus, AgentValue, AsAgent, AsAgentData,
+    SUBMIT_PIN, async_trait, new_agent_boxed,
 };
+use serde_json::json;
 
 /// Unit Input
 struct UnitInputAgent {
@@ -270,6 +273,430 @@ impl AsAgent for ObjectInputAgent {
     }
 }
 
+// Select Input
+struct SelectInputAgent {
+    data: AsAgentData,
+}
+
+impl SelectInputAgent {
+    // One option per line
+    fn options(&self) -> Result<Vec<String>, AgentError> {
+        let text = self.configs()?.get_string_or_default(CONFIG_OPTIONS);
+        Ok(text
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .map(|line| line.to_string())
+            .collect())
+    }
+
+    fn show_widget(&self, value: Option<&str>) -> Result<(), AgentError> {
+        let widget = AgentData::from_json(json!({
+            "options": self.options()?,
+            "value": value,
+        }))?;
+        self.emit_display(DISPLAY_WIDGET, widget);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsAgent for SelectInputAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        configs: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, configs),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        self.show_widget(None)
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        if *self.status() == AgentStatus::Start {
+            self.show_widget(None)?;
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin != SUBMIT_PIN {
+            return Ok(());
+        }
+        let value = data
+            .as_str()
+            .ok_or_else(|| AgentError::InvalidValue("selected option".to_string()))?;
+        if !self.options()?.iter().any(|option| option == value) {
+            return Err(AgentError::InvalidValue(format!("option \"{}\"", value)));
+        }
+        self.show_widget(Some(value))?;
+        self.try_output(ctx, PORT_VALUE, AgentData::string(value))
+    }
+}
+
+// Slider Input
+struct SliderInputAgent {
+    data: AsAgentData,
+}
+
+impl SliderInputAgent {
+    fn range(&self) -> Result<(f64, f64, f64), AgentError> {
+        let configs = self.configs()?;
+        let min = configs.get_number_or(CONFIG_MIN, DEFAULT_MIN);
+        let max = configs.get_number_or(CONFIG_MAX, DEFAULT_MAX);
+        let step = configs.get_number_or(CONFIG_STEP, DEFAULT_STEP);
+        if min > max || step < 0.0 {
+            return Err(AgentError::InvalidConfig(format!(
+                "Invalid slider range {}..{} step {}",
+                min, max, step
+            )));
+        }
+        Ok((min, max, step))
+    }
+
+    fn show_widget(&self, value: Option<f64>) -> Result<(), AgentError> {
+        let (min, max, step) = self.range()?;
+        let widget = AgentData::from_json(json!({
+            "min": min,
+            "max": max,
+            "step": step,
+            "value": value,
+        }))?;
+        self.emit_display(DISPLAY_WIDGET, widget);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsAgent for SliderInputAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        configs: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, configs),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        self.show_widget(None)
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        if *self.status() == AgentStatus::Start {
+            self.show_widget(None)?;
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin != SUBMIT_PIN {
+            return Ok(());
+        }
+        let value = data
+            .as_f64()
+            .ok_or_else(|| AgentError::InvalidValue("slider value".to_string()))?;
+        let (min, max, step) = self.range()?;
+        if value < min || value > max {
+            return Err(AgentError::InvalidValue(format!(
+                "slider value {} out of {}..{}",
+                value, min, max
+            )));
+        }
+        // snap to the nearest step
+        let value = if step > 0.0 {
+            (min + ((value - min) / step).round() * step).min(max)
+        } else {
+            value
+        };
+        self.show_widget(Some(value))?;
+        self.try_output(ctx, PORT_VALUE, AgentData::number(value))
+    }
+}
+
+// File Picker
+struct FilePickerAgent {
+    data: AsAgentData,
+}
+
+impl FilePickerAgent {
+    fn extensions(&self) -> Result<Vec<String>, AgentError> {
+        let text = self.configs()?.get_string_or_default(CONFIG_EXTENSIONS);
+        Ok(text
+            .split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect())
+    }
+
+    fn show_widget(&self, paths: &[String]) -> Result<(), AgentError> {
+        let widget = AgentData::from_json(json!({
+            "extensions": self.extensions()?,
+            "multiple": self.configs()?.get_bool_or_default(CONFIG_MULTIPLE),
+            "value": paths,
+        }))?;
+        self.emit_display(DISPLAY_WIDGET, widget);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsAgent for FilePickerAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        configs: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, configs),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        self.show_widget(&[])
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        if *self.status() == AgentStatus::Start {
+            self.show_widget(&[])?;
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin != SUBMIT_PIN {
+            return Ok(());
+        }
+        let paths = if let Some(path) = data.as_str() {
+            vec![path.to_string()]
+        } else if let Some(arr) = data.as_array() {
+            arr.iter()
+                .map(|v| v.as_str().map(|s| s.to_string()))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| AgentError::InvalidArrayValue("path".to_string()))?
+        } else {
+            return Err(AgentError::InvalidValue("file path".to_string()));
+        };
+
+        let multiple = self.configs()?.get_bool_or_default(CONFIG_MULTIPLE);
+        if !multiple && paths.len() != 1 {
+            return Err(AgentError::InvalidValue(
+                "file path, only one file can be picked".to_string(),
+            ));
+        }
+        let extensions = self.extensions()?;
+        if !extensions.is_empty()
+            && let Some(path) = paths.iter().find(|path| {
+                let ext = Path::new(path)
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                !extensions.contains(&ext)
+            })
+        {
+            return Err(AgentError::InvalidValue(format!(
+                "file extension of {}",
+                path
+            )));
+        }
+
+        self.show_widget(&paths)?;
+        let out = if multiple {
+            AgentData::array(
+                "string",
+                paths.into_iter().map(AgentValue::string).collect(),
+            )
+        } else {
+            AgentData::string(paths.into_iter().next().unwrap_or_default())
+        };
+        self.try_output(ctx, PORT_PATH, out)
+    }
+}
+
+// Form Input
+struct FormInputAgent {
+    data: AsAgentData,
+}
+
+impl FormInputAgent {
+    // JSON Schema of the form fields
+    fn form_schema(&self) -> Result<serde_json::Value, AgentError> {
+        Ok(self.configs()?.get(CONFIG_SCHEMA)?.to_json())
+    }
+
+    fn show_widget(&self, value: Option<serde_json::Value>) -> Result<(), AgentError> {
+        let widget = AgentData::from_json(json!({
+            "schema": self.form_schema()?,
+            "value": value,
+        }))?;
+        self.emit_display(DISPLAY_WIDGET, widget);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsAgent for FormInputAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        configs: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, configs),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        self.show_widget(None)
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        if *self.status() == AgentStatus::Start {
+            self.show_widget(None)?;
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin != SUBMIT_PIN {
+            return Ok(());
+        }
+        let Some(fields) = data.as_object() else {
+            return Err(AgentError::InvalidValue("form fields".to_string()));
+        };
+        let value = data.value.to_json();
+        AgentKindDefinition::new("form")
+            .schema(self.form_schema()?)
+            .validate(&value)?;
+        self.show_widget(Some(value))?;
+        self.try_output(ctx, PORT_DATA, AgentData::object(fields.clone()))
+    }
+}
+
+// Manual Trigger
+struct ManualTriggerAgent {
+    data: AsAgentData,
+}
+
+impl ManualTriggerAgent {
+    // The configured data is parsed as JSON, or sent as a string if it is not valid JSON.
+    fn trigger_data(&self) -> Result<AgentData, AgentError> {
+        let text = self.configs()?.get_string_or_default(CONFIG_DATA);
+        if text.trim().is_empty() {
+            return Ok(AgentData::unit());
+        }
+        match serde_json::from_str::<serde_json::Value>(&text) {
+            Ok(json) => AgentData::from_json(json),
+            Err(_) => Ok(AgentData::string(text)),
+        }
+    }
+
+    fn trigger(&mut self, ctx: AgentContext) -> Result<(), AgentError> {
+        let data = self.trigger_data()?;
+        self.emit_display(DISPLAY_DATA, data.clone());
+        self.try_output(ctx, PORT_DATA, data)
+    }
+}
+
+#[async_trait]
+impl AsAgent for ManualTriggerAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        configs: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, configs),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        if *self.status() == AgentStatus::Start {
+            self.trigger(AgentContext::new())?;
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        self.trigger(ctx)
+    }
+}
+
 // Register Agents
 
 static KIND: &str = "agent";
@@ -282,6 +709,27 @@ static CONFIG_NUMBER: &str = "number";
 static CONFIG_STRING: &str = "string";
 static CONFIG_TEXT: &str = "text";
 static CONFIG_OBJECT: &str = "object";
+static CONFIG_TRIGGER: &str = "trigger";
+static CONFIG_DATA: &str = "data";
+static CONFIG_OPTIONS: &str = "options";
+static CONFIG_MIN: &str = "min";
+static CONFIG_MAX: &str = "max";
+static CONFIG_STEP: &str = "step";
+static CONFIG_EXTENSIONS: &str = "extensions";
+static CONFIG_MULTIPLE: &str = "multiple";
+static CONFIG_SCHEMA: &str = "schema";
+
+static PORT_TRIGGER: &str = "trigger";
+static PORT_DATA: &str = "data";
+static PORT_VALUE: &str = "value";
+static PORT_PATH: &str = "path";
+
+static DISPLAY_DATA: &str = "data";
+static DISPLAY_WIDGET: &str = "widget";
+
+const DEFAULT_MIN: f64 = 0.0;
+const DEFAULT_MAX: f64 = 100.0;
+const DEFAULT_STEP: f64 = 1.0;
 
 pub fn register_agents(askit: &ASKit) {
     // Unit Input Agent
@@ -374,4 +822,114 @@ pub fn register_agents(askit: &ASKit) {
         .outputs(vec![CONFIG_OBJECT])
         .object_config_default(CONFIG_OBJECT),
     );
+
+    // Select Input
+    askit.register_agent(
+        AgentDefinition::new(
+            KIND,
+            "std_select_input",
+            Some(new_agent_boxed::<SelectInputAgent>),
+        )
+        .title("Select Input")
+        .description("Output the option selected in the dropdown")
+        .category(CATEGORY)
+        .outputs(vec![PORT_VALUE])
+        .text_config_with(CONFIG_OPTIONS, "", |entry| {
+            entry.title("Options").description("One option per line")
+        })
+        .custom_display_config_with(DISPLAY_WIDGET, "select", |entry| {
+            entry.hide_title().schema(json!({"type": "string"}))
+        }),
+    );
+
+    // Slider Input
+    askit.register_agent(
+        AgentDefinition::new(
+            KIND,
+            "std_slider_input",
+            Some(new_agent_boxed::<SliderInputAgent>),
+        )
+        .title("Slider Input")
+        .description("Output the number set with the slider")
+        .category(CATEGORY)
+        .outputs(vec![PORT_VALUE])
+        .number_config_with(CONFIG_MIN, DEFAULT_MIN, |entry| entry.title("Min"))
+        .number_config_with(CONFIG_MAX, DEFAULT_MAX, |entry| entry.title("Max"))
+        .number_config_with(CONFIG_STEP, DEFAULT_STEP, |entry| {
+            entry.title("Step").description("0 = continuous")
+        })
+        .custom_display_config_with(DISPLAY_WIDGET, "slider", |entry| {
+            entry.hide_title().schema(json!({"type": "number"}))
+        }),
+    );
+
+    // File Picker
+    askit.register_agent(
+        AgentDefinition::new(
+            KIND,
+            "std_file_picker",
+            Some(new_agent_boxed::<FilePickerAgent>),
+        )
+        .title("File Picker")
+        .description("Output the path of the picked file, or an array of paths")
+        .category(CATEGORY)
+        .outputs(vec![PORT_PATH])
+        .string_config_with(CONFIG_EXTENSIONS, "", |entry| {
+            entry
+                .title("Extensions")
+                .description("Comma separated, e.g. png,jpg. Empty for any file")
+        })
+        .boolean_config_with(CONFIG_MULTIPLE, false, |entry| entry.title("Multiple"))
+        .custom_display_config_with(DISPLAY_WIDGET, "file_picker", |entry| {
+            entry.hide_title().schema(json!({
+                "type": ["string", "array"],
+                "items": {"type": "string"},
+            }))
+        }),
+    );
+
+    // Form Input
+    askit.register_agent(
+        AgentDefinition::new(
+            KIND,
+            "std_form_input",
+            Some(new_agent_boxed::<FormInputAgent>),
+        )
+        .title("Form Input")
+        .description("Output the fields of the submitted form as an object")
+        .category(CATEGORY)
+        .outputs(vec![PORT_DATA])
+        .object_config_with(
+            CONFIG_SCHEMA,
+            AgentValue::from_json(json!({"type": "object", "properties": {}})).unwrap_or_default(),
+            |entry| {
+                entry
+                    .title("Schema")
+                    .description("JSON Schema of the form fields")
+            },
+        )
+        .custom_display_config_with(DISPLAY_WIDGET, "form", |entry| {
+            entry.hide_title().schema(json!({"type": "object"}))
+        }),
+    );
+
+    // Manual Trigger
+    askit.register_agent(
+        AgentDefinition::new(
+            KIND,
+            "std_manual_trigger",
+            Some(new_agent_boxed::<ManualTriggerAgent>),
+        )
+        .title("Manual Trigger")
+        .description("Send the configured data when the trigger button is pressed")
+        .category(CATEGORY)
+        .inputs(vec![PORT_TRIGGER])
+        .outputs(vec![PORT_DATA])
+        .unit_config_with(CONFIG_TRIGGER, |entry| entry.title("Trigger"))
+        .text_config_with(CONFIG_DATA, "", |entry| entry.title("Data (JSON)"))
+        .display_configs(vec![(
+            DISPLAY_DATA,
+            AgentDisplayConfigEntry::new("*").hide_title(),
+        )]),
+    );
 }
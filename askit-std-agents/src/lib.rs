@@ -1,28 +1,91 @@
 use agent_stream_kit::ASKit;
 
+#[cfg(feature = "browser")]
+pub mod browser;
+#[cfg(feature = "calendar")]
+pub mod calendar;
+#[cfg(all(
+    feature = "clipboard",
+    any(target_os = "linux", target_os = "macos", target_os = "windows")
+))]
+pub mod clipboard;
 pub mod counter;
 pub mod data;
 pub mod display;
 pub mod file;
+#[cfg(feature = "github")]
+pub mod github;
+#[cfg(feature = "gitlab")]
+pub mod gitlab;
 pub mod image;
 pub mod input;
+pub mod mux;
+pub mod notify;
+pub mod queue;
+pub mod redact;
+#[cfg(any(
+    feature = "calendar",
+    feature = "github",
+    feature = "gitlab",
+    feature = "s3",
+    feature = "sd_webui"
+))]
+mod rest;
+#[cfg(feature = "s3")]
+pub mod s3;
+#[cfg(feature = "sd_webui")]
+pub mod sd_webui;
+pub mod similarity;
+#[cfg(any(feature = "mysql", feature = "postgres"))]
+pub mod sql;
 pub mod stream;
 pub mod string;
+#[cfg(all(feature = "system", target_os = "linux"))]
+pub mod system;
 pub mod time;
+pub mod validate;
 
 #[cfg(feature = "yaml")]
 pub mod yaml;
 
 pub fn register_agents(askit: &ASKit) {
+    #[cfg(feature = "browser")]
+    browser::register_agents(askit);
+    #[cfg(feature = "calendar")]
+    calendar::register_agents(askit);
+    #[cfg(all(
+        feature = "clipboard",
+        any(target_os = "linux", target_os = "macos", target_os = "windows")
+    ))]
+    clipboard::register_agents(askit);
     counter::register_agents(askit);
     data::register_agents(askit);
     display::register_agents(askit);
     file::register_agents(askit);
+    #[cfg(feature = "github")]
+    github::register_agents(askit);
+    #[cfg(feature = "gitlab")]
+    gitlab::register_agents(askit);
     image::register_agents(askit);
     input::register_agents(askit);
+    mux::register_agents(askit);
+    notify::register_agents(askit);
+    queue::register_agents(askit);
+    redact::register_agents(askit);
+    #[cfg(feature = "s3")]
+    s3::register_agents(askit);
+    #[cfg(feature = "sd_webui")]
+    sd_webui::register_agents(askit);
+    similarity::register_agents(askit);
+    #[cfg(any(feature = "mysql", feature = "postgres"))]
+    sql::register_agents(askit);
     stream::register_agents(askit);
     string::register_agents(askit);
     time::register_agents(askit);
+    validate::register_agents(askit);
+
+    #[cfg(all(feature = "system", target_os = "linux"))]
+    system::register_agents(askit);
 
     #[cfg(feature = "yaml")]
     yaml::register_agents(askit);
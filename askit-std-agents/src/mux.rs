@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentFlow,
+    AgentFlowNode, AgentValue, AsAgent, AsAgentData, BoardObserver, BoardUpdate,
+    SubFlowCopyOptions, async_trait, new_agent_boxed,
+};
+
+// Demux Agent
+//
+// Routes each object to an instance of the template flow chosen by a key field,
+// creating the instance the first time the key is seen. The template reads its input
+// from the in_board and writes its replies to the out_board; in every instance these
+// boards are renamed to `{in_board}/{key}` and `{out_board}/{key}`.
+struct DemuxAgent {
+    data: AsAgentData,
+    // key -> (instance flow name, last used)
+    instances: HashMap<String, (String, Instant)>,
+}
+
+impl DemuxAgent {
+    async fn instance(&mut self, key: &str) -> Result<(), AgentError> {
+        if let Some(instance) = self.instances.get_mut(key) {
+            instance.1 = Instant::now();
+            return Ok(());
+        }
+
+        let configs = self.configs()?;
+        let template = configs.get_string(CONFIG_FLOW)?;
+        if template.is_empty() {
+            return Err(AgentError::InvalidConfig("flow is not set".to_string()));
+        }
+        let in_board = configs.get_string_or(CONFIG_IN_BOARD, DEFAULT_IN_BOARD);
+        let out_board = configs.get_string_or(CONFIG_OUT_BOARD, DEFAULT_OUT_BOARD);
+        let max_instances = configs.get_integer_or(CONFIG_MAX_INSTANCES, DEFAULT_MAX_INSTANCES);
+
+        if max_instances > 0 {
+            while self.instances.len() >= max_instances as usize {
+                let Some(oldest) = self
+                    .instances
+                    .iter()
+                    .min_by_key(|(_, (_, last_used))| *last_used)
+                    .map(|(key, _)| key.clone())
+                else {
+                    break;
+                };
+                if let Some((flow_name, _)) = self.instances.remove(&oldest) {
+                    self.askit().remove_agent_flow(&flow_name).await?;
+                }
+            }
+        }
+
+        let askit = self.askit().clone();
+        let Some(template_flow) = askit.get_agent_flows().remove(&template) else {
+            return Err(AgentError::FlowNotFound(template));
+        };
+        let copy = askit.copy_sub_flow_with(
+            template_flow.nodes(),
+            template_flow.edges(),
+            &SubFlowCopyOptions {
+                include_boundary_edges: false,
+                id_prefix: Some(format!("{}/{}/", self.id(), key)),
+            },
+        );
+
+        let mut flow = AgentFlow::new(askit.unique_flow_name(&format!("{}/{}", template, key)));
+        flow.set_nodes(rename_boards(copy.nodes, &in_board, &out_board, key));
+        flow.set_edges(copy.edges);
+        askit.add_agent_flow(&flow)?;
+        if let Err(e) = askit.start_agent_flow(flow.name()).await {
+            askit.remove_agent_flow(flow.name()).await.ok();
+            return Err(e);
+        }
+
+        self.instances
+            .insert(key.to_string(), (flow.name().to_string(), Instant::now()));
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsAgent for DemuxAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            instances: HashMap::new(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn stop_async(&mut self) -> Result<(), AgentError> {
+        for (_, (flow_name, _)) in self.instances.drain() {
+            self.data.askit.remove_agent_flow(&flow_name).await?;
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let key_field = self.configs()?.get_string_or(CONFIG_KEY, DEFAULT_KEY);
+        let Some(key) = key_value(&key_field, &ctx, &data) else {
+            return Err(AgentError::InvalidValue(format!(
+                "{} is missing in the data",
+                key_field
+            )));
+        };
+
+        self.instance(&key).await?;
+
+        let in_board = self
+            .configs()?
+            .get_string_or(CONFIG_IN_BOARD, DEFAULT_IN_BOARD);
+        self.askit()
+            .write_board_data(format!("{}/{}", in_board, key), data)
+    }
+}
+
+// Mux Agent
+//
+// Outputs the replies written by the instances created by a demux agent,
+// with the key set in the data and in the context.
+struct MuxAgent {
+    data: AsAgentData,
+    subscriber_id: Option<usize>,
+}
+
+struct MuxObserver {
+    askit: ASKit,
+    agent_id: String,
+    prefix: String,
+    key_field: String,
+}
+
+impl BoardObserver for MuxObserver {
+    fn notify(&self, update: &BoardUpdate) {
+        let Some(key) = update.name.strip_prefix(&self.prefix) else {
+            return;
+        };
+        let key = AgentValue::string(key);
+
+        let data = match update.data.as_object() {
+            Some(obj) if !obj.contains_key(&self.key_field) => {
+                let mut obj = obj.clone();
+                obj.insert(self.key_field.clone(), key.clone());
+                AgentData::object_with_kind(update.data.kind.clone(), obj)
+            }
+            _ => update.data.clone(),
+        };
+        let ctx = AgentContext::new().with_var(self.key_field.clone(), key);
+
+        self.askit
+            .try_send_agent_out(self.agent_id.clone(), ctx, PORT_OUT.to_string(), data)
+            .unwrap_or_else(|e| {
+                log::error!("Failed to send mux output: {}", e);
+            });
+    }
+}
+
+#[async_trait]
+impl AsAgent for MuxAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            subscriber_id: None,
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        let configs = self.configs()?;
+        let out_board = configs.get_string_or(CONFIG_OUT_BOARD, DEFAULT_OUT_BOARD);
+        let observer = MuxObserver {
+            askit: self.askit().clone(),
+            agent_id: self.id().to_string(),
+            prefix: format!("{}/", out_board),
+            key_field: configs.get_string_or(CONFIG_KEY, DEFAULT_KEY),
+        };
+        self.subscriber_id = Some(
+            self.askit()
+                .subscribe_board(&format!("{}/*", out_board), Box::new(observer)),
+        );
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        if let Some(subscriber_id) = self.subscriber_id.take() {
+            self.askit().unsubscribe_board(subscriber_id);
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        _ctx: AgentContext,
+        _pin: String,
+        _data: AgentData,
+    ) -> Result<(), AgentError> {
+        Ok(())
+    }
+}
+
+fn key_value(key_field: &str, ctx: &AgentContext, data: &AgentData) -> Option<String> {
+    let value = data
+        .as_object()
+        .and_then(|obj| obj.get(key_field))
+        .or_else(|| ctx.get_var(key_field));
+    match value {
+        Some(AgentValue::String(s)) if !s.is_empty() => Some(s.to_string()),
+        Some(AgentValue::Integer(i)) => Some(i.to_string()),
+        Some(AgentValue::Unsigned(u)) => Some(u.to_string()),
+        _ => None,
+    }
+}
+
+// Points the board agents of an instance at the boards of its key.
+fn rename_boards(
+    mut nodes: Vec<AgentFlowNode>,
+    in_board: &str,
+    out_board: &str,
+    key: &str,
+) -> Vec<AgentFlowNode> {
+    for node in nodes.iter_mut() {
+        let Some(configs) = node.configs.as_mut() else {
+            continue;
+        };
+        let Ok(board) = configs.get_string(CONFIG_BOARD_NAME) else {
+            continue;
+        };
+        if board == in_board || board == out_board {
+            configs.set(
+                CONFIG_BOARD_NAME.to_string(),
+                AgentValue::string(format!("{}/{}", board, key)),
+            );
+        }
+    }
+    nodes
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "Core/Flow";
+
+static PORT_IN: &str = "in";
+static PORT_OUT: &str = "out";
+
+static CONFIG_FLOW: &str = "flow";
+static CONFIG_KEY: &str = "key";
+static CONFIG_IN_BOARD: &str = "in_board";
+static CONFIG_OUT_BOARD: &str = "out_board";
+static CONFIG_MAX_INSTANCES: &str = "max_instances";
+
+// config of core_board_in and core_board_out
+static CONFIG_BOARD_NAME: &str = "$board";
+
+const DEFAULT_KEY: &str = "user_id";
+const DEFAULT_IN_BOARD: &str = "demux_in";
+const DEFAULT_OUT_BOARD: &str = "demux_out";
+const DEFAULT_MAX_INSTANCES: i64 = 100;
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(AGENT_KIND, "std_demux", Some(new_agent_boxed::<DemuxAgent>))
+            .title("Demux")
+            .category(CATEGORY)
+            .inputs(vec![PORT_IN])
+            .string_config_with(CONFIG_FLOW, "", |entry| {
+                entry
+                    .title("Flow")
+                    .description("Template flow instantiated for each key")
+            })
+            .string_config_with(CONFIG_KEY, DEFAULT_KEY, |entry| {
+                entry
+                    .title("Key")
+                    .description("Field or context variable selecting the instance")
+            })
+            .string_config_with(CONFIG_IN_BOARD, DEFAULT_IN_BOARD, |entry| {
+                entry.title("In Board")
+            })
+            .string_config_with(CONFIG_OUT_BOARD, DEFAULT_OUT_BOARD, |entry| {
+                entry.title("Out Board")
+            })
+            .integer_config_with(CONFIG_MAX_INSTANCES, DEFAULT_MAX_INSTANCES, |entry| {
+                entry.title("Max Instances")
+            }),
+    );
+
+    askit.register_agent(
+        AgentDefinition::new(AGENT_KIND, "std_mux", Some(new_agent_boxed::<MuxAgent>))
+            .title("Mux")
+            .category(CATEGORY)
+            .outputs(vec![PORT_OUT])
+            .string_config_with(CONFIG_KEY, DEFAULT_KEY, |entry| entry.title("Key"))
+            .string_config_with(CONFIG_OUT_BOARD, DEFAULT_OUT_BOARD, |entry| {
+                entry.title("Out Board")
+            }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_boards() {
+        let mut configs = AgentConfigs::new();
+        configs.set(
+            CONFIG_BOARD_NAME.to_string(),
+            AgentValue::string("demux_in"),
+        );
+        let mut other = AgentConfigs::new();
+        other.set(CONFIG_BOARD_NAME.to_string(), AgentValue::string("shared"));
+        let nodes = vec![
+            AgentFlowNode {
+                id: "a".to_string(),
+                configs: Some(configs),
+                ..Default::default()
+            },
+            AgentFlowNode {
+                id: "b".to_string(),
+                configs: Some(other),
+                ..Default::default()
+            },
+        ];
+
+        let nodes = rename_boards(nodes, "demux_in", "demux_out", "u1");
+        let board = |i: usize| {
+            nodes[i]
+                .configs
+                .as_ref()
+                .unwrap()
+                .get_string(CONFIG_BOARD_NAME)
+                .unwrap()
+        };
+        assert_eq!(board(0), "demux_in/u1");
+        assert_eq!(board(1), "shared");
+    }
+}
@@ -0,0 +1,108 @@
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
+    AsAgent, AsAgentData, Notification, NotificationLevel, async_trait, new_agent_boxed,
+};
+
+// Notify
+struct NotifyAgent {
+    data: AsAgentData,
+}
+
+impl NotifyAgent {
+    fn notification(&self, data: &AgentData) -> Result<Notification, AgentError> {
+        let configs = self.configs()?;
+        let level = configs
+            .get_string_or(CONFIG_LEVEL, DEFAULT_LEVEL)
+            .parse::<NotificationLevel>()
+            .map_err(AgentError::InvalidConfig)?;
+
+        // the received data is the body unless it is configured
+        let mut body = configs.get_string_or_default(CONFIG_BODY);
+        if body.is_empty() {
+            body = match data.as_str() {
+                Some(s) => s.to_string(),
+                None if data.is_unit() => String::new(),
+                None => data.value.to_json().to_string(),
+            };
+        }
+
+        let mut notification =
+            Notification::new(level, configs.get_string_or_default(CONFIG_TITLE)).body(body);
+        let dismiss_after = configs.get_integer_or_default(CONFIG_DISMISS_AFTER);
+        if dismiss_after > 0 {
+            notification = notification.dismiss_after_ms(dismiss_after as u64);
+        }
+        Ok(notification)
+    }
+}
+
+#[async_trait]
+impl AsAgent for NotifyAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        _ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let notification = self.notification(&data)?;
+        self.emit_notification(notification);
+        Ok(())
+    }
+}
+
+static KIND: &str = "agent";
+static CATEGORY: &str = "Core/Display";
+
+static CONFIG_LEVEL: &str = "level";
+static CONFIG_TITLE: &str = "title";
+static CONFIG_BODY: &str = "body";
+static CONFIG_DISMISS_AFTER: &str = "dismiss_after";
+
+static DEFAULT_LEVEL: &str = "info";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(KIND, "std_notify", Some(new_agent_boxed::<NotifyAgent>))
+            .title("Notify")
+            .description("Raise a notification for the user when data is received")
+            .category(CATEGORY)
+            .inputs(vec!["*"])
+            .string_config_with(CONFIG_LEVEL, DEFAULT_LEVEL, |entry| {
+                entry
+                    .title("Level")
+                    .description("info, success, warning or error")
+            })
+            .string_config_with(CONFIG_TITLE, "", |entry| entry.title("Title"))
+            .text_config_with(CONFIG_BODY, "", |entry| {
+                entry
+                    .title("Body")
+                    .description("The received data when empty")
+            })
+            .integer_config_with(CONFIG_DISMISS_AFTER, 0, |entry| {
+                entry
+                    .title("Dismiss After (ms)")
+                    .description("0 = until dismissed")
+            }),
+    );
+}
@@ -0,0 +1,300 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
+    AgentStatus, AgentValue, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use tokio::task::AbortHandle;
+
+use crate::time::parse_duration_to_ms;
+
+// Priority Queue
+//
+// Items with the highest priority come out first, items of the same priority in arrival order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Priority(f64);
+
+impl Eq for Priority {}
+
+impl PartialOrd for Priority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Priority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+#[derive(Default)]
+struct PriorityQueue {
+    items: BTreeMap<(Reverse<Priority>, u64), (AgentContext, AgentData)>,
+    seq: u64,
+    // pulls waiting for an item
+    pending_pulls: usize,
+}
+
+impl PriorityQueue {
+    // Drops the lowest priority item when the queue is over `max_size` (0 for no limit).
+    fn push(&mut self, priority: f64, ctx: AgentContext, data: AgentData, max_size: usize) {
+        self.seq += 1;
+        self.items
+            .insert((Reverse(Priority(priority)), self.seq), (ctx, data));
+        if max_size > 0 {
+            while self.items.len() > max_size {
+                self.items.pop_last();
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<(AgentContext, AgentData)> {
+        self.items.pop_first().map(|(_, item)| item)
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+}
+
+struct PriorityQueueAgent {
+    data: AsAgentData,
+    queue: Arc<Mutex<PriorityQueue>>,
+    interval_ms: Option<u64>,
+    timer: Option<AbortHandle>,
+}
+
+impl PriorityQueueAgent {
+    fn start_timer(&mut self) {
+        let Some(interval_ms) = self.interval_ms else {
+            return;
+        };
+        let queue = self.queue.clone();
+        let askit = self.askit().clone();
+        let clock = askit.clock();
+        let agent_id = self.id().to_string();
+
+        let timer = self.mut_data().spawn_task(async move {
+            loop {
+                clock.sleep(Duration::from_millis(interval_ms)).await;
+                let Some((ctx, data)) = queue.lock().unwrap().pop() else {
+                    continue;
+                };
+                askit
+                    .try_send_agent_out(agent_id.clone(), ctx, PIN_OUT.to_string(), data)
+                    .unwrap_or_else(|e| {
+                        log::error!("Failed to send priority queue output: {}", e);
+                    });
+            }
+        });
+        self.timer = Some(timer);
+    }
+
+    fn stop_timer(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            timer.abort();
+        }
+    }
+
+    fn read_interval(&self) -> Result<Option<u64>, AgentError> {
+        let interval = self.configs()?.get_string_or_default(CONFIG_INTERVAL);
+        if interval.is_empty() {
+            return Ok(None);
+        }
+        parse_duration_to_ms(&interval).map(Some)
+    }
+
+    fn priority_of(&self, data: &AgentData) -> Result<f64, AgentError> {
+        let configs = self.configs()?;
+        let property = configs.get_string_or(CONFIG_PRIORITY, DEFAULT_PRIORITY);
+        let priority = priority_value(&data.value, &property);
+        Ok(if configs.get_bool_or(CONFIG_LOWEST_FIRST, false) {
+            -priority
+        } else {
+            priority
+        })
+    }
+}
+
+#[async_trait]
+impl AsAgent for PriorityQueueAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        let mut agent = Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            queue: Default::default(),
+            interval_ms: None,
+            timer: None,
+        };
+        agent.interval_ms = agent.read_interval()?;
+        Ok(agent)
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        *self.queue.lock().unwrap() = PriorityQueue::default();
+        self.start_timer();
+        Ok(())
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        self.stop_timer();
+        Ok(())
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        let interval_ms = self.read_interval()?;
+        if interval_ms != self.interval_ms {
+            self.interval_ms = interval_ms;
+            if *self.status() == AgentStatus::Start {
+                self.stop_timer();
+                self.start_timer();
+            }
+        }
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PIN_NEXT {
+            let item = {
+                let mut queue = self.queue.lock().unwrap();
+                let item = queue.pop();
+                if item.is_none() {
+                    queue.pending_pulls += 1;
+                }
+                item
+            };
+            if let Some((ctx, data)) = item {
+                self.try_output(ctx, PIN_OUT, data)?;
+            }
+            return Ok(());
+        }
+
+        // a waiting pull takes the item right away
+        {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.pending_pulls > 0 && queue.len() == 0 {
+                queue.pending_pulls -= 1;
+                drop(queue);
+                return self.try_output(ctx, PIN_OUT, data);
+            }
+        }
+
+        let priority = self.priority_of(&data)?;
+        let max_size = self.configs()?.get_integer_or(CONFIG_MAX_SIZE, 0).max(0) as usize;
+        self.queue
+            .lock()
+            .unwrap()
+            .push(priority, ctx, data, max_size);
+        Ok(())
+    }
+}
+
+// Numeric value at the dot separated property path, 0 when missing.
+fn priority_value(value: &AgentValue, property: &str) -> f64 {
+    if property.is_empty() {
+        return value.as_f64().unwrap_or(0.0);
+    }
+    let pointer = format!("/{}", property.replace('.', "/"));
+    value
+        .pointer(&pointer)
+        .and_then(|v| v.as_f64())
+        .unwrap_or(0.0)
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "Core/Flow";
+
+static PIN_IN: &str = "in";
+static PIN_NEXT: &str = "next";
+static PIN_OUT: &str = "out";
+
+static CONFIG_PRIORITY: &str = "priority";
+static CONFIG_LOWEST_FIRST: &str = "lowest_first";
+static CONFIG_INTERVAL: &str = "interval";
+static CONFIG_MAX_SIZE: &str = "max_size";
+
+const DEFAULT_PRIORITY: &str = "priority";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_priority_queue",
+            Some(new_agent_boxed::<PriorityQueueAgent>),
+        )
+        .title("Priority Queue")
+        .description("Releases the buffered items in priority order")
+        .category(CATEGORY)
+        .inputs(vec![PIN_IN, PIN_NEXT])
+        .outputs(vec![PIN_OUT])
+        .string_config_with(CONFIG_PRIORITY, DEFAULT_PRIORITY, |entry| {
+            entry
+                .title("Priority")
+                .description("Property holding the priority (ex. priority, meta.rank)")
+        })
+        .boolean_config_with(CONFIG_LOWEST_FIRST, false, |entry| {
+            entry.title("Lowest First")
+        })
+        .string_config_with(CONFIG_INTERVAL, "", |entry| {
+            entry
+                .title("Interval")
+                .description("Releases an item every interval (ex. 1s, 100ms). Empty: on next only")
+        })
+        .integer_config_with(CONFIG_MAX_SIZE, 0, |entry| {
+            entry
+                .title("Max Size")
+                .description("Drops the lowest priority items beyond this size. 0: no limit")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_priority_queue() {
+        let mut queue = PriorityQueue::default();
+        for (priority, n) in [(1.0, 1), (5.0, 2), (1.0, 3), (3.0, 4)] {
+            queue.push(priority, AgentContext::new(), AgentData::integer(n), 3);
+        }
+        // the last item of the lowest priority was dropped
+        assert_eq!(queue.len(), 3);
+        let order: Vec<i64> = std::iter::from_fn(|| queue.pop())
+            .map(|(_, data)| data.as_i64().unwrap())
+            .collect();
+        assert_eq!(order, vec![2, 4, 1]);
+
+        let value = AgentData::object(
+            [(
+                "meta".to_string(),
+                AgentValue::object([("rank".to_string(), AgentValue::integer(7))].into()),
+            )]
+            .into(),
+        )
+        .value;
+        assert_eq!(priority_value(&value, "meta.rank"), 7.0);
+        assert_eq!(priority_value(&value, "missing"), 0.0);
+    }
+}
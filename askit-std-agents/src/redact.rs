@@ -0,0 +1,325 @@
+use agent_stream_kit::{
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
+    AgentValue, AgentValueMap, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use regex::{Captures, Regex};
+
+// Redactor
+//
+// Masks personal information in the strings of a value, objects and arrays included.
+struct Rule {
+    label: &'static str,
+    re: Regex,
+    check: fn(&str) -> bool,
+}
+
+struct Redactor {
+    rules: Vec<Rule>,
+    mask: String,
+}
+
+// What was redacted where, without the redacted text itself
+struct Redaction {
+    path: String,
+    label: &'static str,
+}
+
+impl Redactor {
+    fn new(configs: Option<&AgentConfigs>) -> Result<Self, AgentError> {
+        let enabled = |key: &str| configs.is_none_or(|c| c.get_bool_or(key, true));
+        let mut rules = Vec::new();
+        // cards before phones, whose pattern also matches runs of digits
+        if enabled(CONFIG_CREDIT_CARDS) {
+            rules.push(Rule {
+                label: "credit_card",
+                re: Regex::new(CREDIT_CARD_PATTERN).unwrap(),
+                check: luhn_valid,
+            });
+        }
+        if enabled(CONFIG_EMAILS) {
+            rules.push(Rule {
+                label: "email",
+                re: Regex::new(EMAIL_PATTERN).unwrap(),
+                check: |_| true,
+            });
+        }
+        if enabled(CONFIG_PHONES) {
+            rules.push(Rule {
+                label: "phone",
+                re: Regex::new(PHONE_PATTERN).unwrap(),
+                check: |s| (9..=15).contains(&s.chars().filter(|c| c.is_ascii_digit()).count()),
+            });
+        }
+
+        let patterns = configs
+            .map(|c| c.get_string_or_default(CONFIG_PATTERNS))
+            .unwrap_or_default();
+        for pattern in patterns.lines().map(str::trim).filter(|p| !p.is_empty()) {
+            let re = Regex::new(pattern)
+                .map_err(|e| AgentError::InvalidConfig(format!("pattern {}: {}", pattern, e)))?;
+            rules.push(Rule {
+                label: "custom",
+                re,
+                check: |_| true,
+            });
+        }
+
+        let mask = configs
+            .map(|c| c.get_string_or_default(CONFIG_MASK))
+            .unwrap_or_default();
+        Ok(Self { rules, mask })
+    }
+
+    fn redact_str(&self, text: &str, path: &str, redactions: &mut Vec<Redaction>) -> String {
+        let mut text = text.to_string();
+        for rule in &self.rules {
+            let replaced = rule.re.replace_all(&text, |caps: &Captures| {
+                let found = &caps[0];
+                if !(rule.check)(found) {
+                    return found.to_string();
+                }
+                redactions.push(Redaction {
+                    path: path.to_string(),
+                    label: rule.label,
+                });
+                if self.mask.is_empty() {
+                    format!("[{}]", rule.label.to_uppercase())
+                } else {
+                    self.mask.clone()
+                }
+            });
+            text = replaced.into_owned();
+        }
+        text
+    }
+
+    fn redact(
+        &self,
+        value: &AgentValue,
+        path: &str,
+        redactions: &mut Vec<Redaction>,
+    ) -> AgentValue {
+        match value {
+            AgentValue::String(s) => {
+                let at = if path.is_empty() { "/" } else { path };
+                AgentValue::string(self.redact_str(s, at, redactions))
+            }
+            AgentValue::Array(arr) => AgentValue::array(
+                arr.iter()
+                    .enumerate()
+                    .map(|(i, v)| self.redact(v, &format!("{}/{}", path, i), redactions))
+                    .collect(),
+            ),
+            AgentValue::Object(obj) => {
+                let mut map = AgentValueMap::new();
+                for (key, v) in obj.iter() {
+                    map.insert(
+                        key.clone(),
+                        self.redact(v, &format!("{}/{}", path, key), redactions),
+                    );
+                }
+                AgentValue::object(map)
+            }
+            _ => value.clone(),
+        }
+    }
+}
+
+fn luhn_valid(number: &str) -> bool {
+    let digits: Vec<u32> = number.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() < 13 {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| {
+            if !i.is_multiple_of(2) {
+                let d = d * 2;
+                if d > 9 { d - 9 } else { d }
+            } else {
+                d
+            }
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+// Redact
+struct RedactAgent {
+    data: AsAgentData,
+    redactor: Redactor,
+}
+
+#[async_trait]
+impl AsAgent for RedactAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        let redactor = Redactor::new(config.as_ref())?;
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            redactor,
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        self.redactor = Redactor::new(Some(self.configs()?))?;
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let mut redactions = Vec::new();
+        let value = self.redactor.redact(&data.value, "", &mut redactions);
+        self.try_output(
+            ctx.clone(),
+            PIN_DATA,
+            AgentData {
+                kind: data.kind,
+                value,
+            },
+        )?;
+
+        if redactions.is_empty() {
+            return Ok(());
+        }
+        let report = AgentValueMap::from([
+            (
+                "count".to_string(),
+                AgentValue::integer(redactions.len() as i64),
+            ),
+            (
+                "redactions".to_string(),
+                AgentValue::array(
+                    redactions
+                        .into_iter()
+                        .map(|r| {
+                            AgentValue::object(AgentValueMap::from([
+                                ("path".to_string(), AgentValue::string(r.path)),
+                                ("type".to_string(), AgentValue::string(r.label)),
+                            ]))
+                        })
+                        .collect(),
+                ),
+            ),
+        ]);
+        self.try_output(ctx, PIN_REPORT, AgentData::object(report))
+    }
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "Core/String";
+
+static PIN_DATA: &str = "data";
+static PIN_REPORT: &str = "report";
+
+static CONFIG_EMAILS: &str = "emails";
+static CONFIG_PHONES: &str = "phones";
+static CONFIG_CREDIT_CARDS: &str = "credit_cards";
+static CONFIG_PATTERNS: &str = "patterns";
+static CONFIG_MASK: &str = "mask";
+
+static EMAIL_PATTERN: &str = r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}";
+static PHONE_PATTERN: &str =
+    r"(?:\+\d{1,3}[\s.-]?)?(?:\(\d{1,4}\)[\s.-]?|\b)\d{2,4}[\s.-]?\d{3,4}[\s.-]?\d{3,4}\b";
+static CREDIT_CARD_PATTERN: &str = r"\b(?:\d[ -]?){12,18}\d\b";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_redact",
+            Some(new_agent_boxed::<RedactAgent>),
+        )
+        .title("Redact")
+        .description("Masks emails, phone numbers, credit cards and custom patterns")
+        .category(CATEGORY)
+        .inputs(vec![PIN_DATA])
+        .outputs(vec![PIN_DATA, PIN_REPORT])
+        .boolean_config_with(CONFIG_EMAILS, true, |entry| entry.title("Emails"))
+        .boolean_config_with(CONFIG_PHONES, true, |entry| entry.title("Phone Numbers"))
+        .boolean_config_with(CONFIG_CREDIT_CARDS, true, |entry| {
+            entry.title("Credit Cards")
+        })
+        .text_config_with(CONFIG_PATTERNS, "", |entry| {
+            entry
+                .title("Patterns")
+                .description("Regular expressions to mask, one per line")
+        })
+        .string_config_with(CONFIG_MASK, "", |entry| {
+            entry
+                .title("Mask")
+                .description("Replacement text. Empty: the type, such as [EMAIL]")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let mut configs = AgentConfigs::new();
+        configs.set(CONFIG_PATTERNS.to_string(), AgentValue::string(r"ID-\d{4}"));
+        let redactor = Redactor::new(Some(&configs)).unwrap();
+
+        let value = AgentValue::object(AgentValueMap::from([
+            (
+                "text".to_string(),
+                AgentValue::string("mail a.b@example.com or call +1 555-123-4567"),
+            ),
+            (
+                "items".to_string(),
+                AgentValue::array(vec![
+                    AgentValue::string("card 4111 1111 1111 1111, ID-1234"),
+                    AgentValue::string("order 1234567890123"),
+                ]),
+            ),
+        ]));
+        let mut redactions = Vec::new();
+        let redacted = redactor.redact(&value, "", &mut redactions);
+
+        assert_eq!(
+            redacted.get_str("text"),
+            Some("mail [EMAIL] or call [PHONE]")
+        );
+        let items = redacted.get("items").unwrap().as_array().unwrap();
+        assert_eq!(items[0].as_str(), Some("card [CREDIT_CARD], [CUSTOM]"));
+        // not a card number, nor a phone number
+        assert_eq!(items[1].as_str(), Some("order 1234567890123"));
+
+        // in the order of the keys, which depends on the map of the objects
+        let mut found: Vec<(&str, &str)> = redactions
+            .iter()
+            .map(|r| (r.path.as_str(), r.label))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("/items/0", "credit_card"),
+                ("/items/0", "custom"),
+                ("/text", "email"),
+                ("/text", "phone"),
+            ]
+        );
+    }
+}
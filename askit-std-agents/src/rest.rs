@@ -0,0 +1,141 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+#[cfg(any(feature = "github", feature = "gitlab"))]
+use agent_stream_kit::AgentData;
+use agent_stream_kit::AgentError;
+use serde_json::Value;
+
+// Helpers of the agents calling REST APIs
+
+/// Client shared by the agents, so that connections are reused.
+pub(crate) fn client() -> reqwest::Client {
+    CLIENT.clone()
+}
+
+/// Sends the request and parses the JSON response.
+#[cfg(any(feature = "calendar", feature = "github", feature = "gitlab"))]
+pub(crate) async fn send_json(request: reqwest::RequestBuilder) -> Result<Value, AgentError> {
+    let text = send_text(request).await?;
+    if text.is_empty() {
+        return Ok(Value::Null);
+    }
+    Ok(serde_json::from_str(&text)?)
+}
+
+pub(crate) async fn send_text(request: reqwest::RequestBuilder) -> Result<String, AgentError> {
+    send(request)
+        .await?
+        .text()
+        .await
+        .map_err(|e| AgentError::external("Failed to read the response", e))
+}
+
+/// Sends the request. Error statuses become errors with the message of the response, in
+/// JSON or XML; 429 and 5xx are retryable.
+pub(crate) async fn send(
+    request: reqwest::RequestBuilder,
+) -> Result<reqwest::Response, AgentError> {
+    let response = request.send().await.map_err(|e| {
+        if e.is_timeout() || e.is_connect() {
+            AgentError::transient("Request failed", e)
+        } else {
+            AgentError::external("Request failed", e)
+        }
+    })?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    let error = StatusError {
+        status,
+        message: error_message(&text).unwrap_or(text),
+    };
+    if status.as_u16() == 429 || status.is_server_error() {
+        Err(AgentError::transient("Request failed", error))
+    } else {
+        Err(AgentError::external("Request failed", error))
+    }
+}
+
+// {"message": ..} of GitHub and GitLab, {"error": {"message": ..}} of Google, and
+// <Message>..</Message> of S3
+fn error_message(text: &str) -> Option<String> {
+    if let Ok(body) = serde_json::from_str::<Value>(text) {
+        return body["message"]
+            .as_str()
+            .or(body["error"]["message"].as_str())
+            .map(|s| s.to_string());
+    }
+    let (_, rest) = text.split_once("<Message>")?;
+    let (message, _) = rest.split_once("</Message>")?;
+    Some(message.to_string())
+}
+
+/// The integer of the data, or the integer at `key` of an object.
+#[cfg(any(feature = "github", feature = "gitlab"))]
+pub(crate) fn integer_of(data: &AgentData, key: &str) -> Option<i64> {
+    data.as_i64()
+        .or_else(|| data.get_i64(key))
+        .or_else(|| data.as_str().and_then(|s| s.trim().parse().ok()))
+}
+
+/// (number, body) of a comment on an issue: the text for the configured number, or an
+/// object of `number` and `body`.
+#[cfg(any(feature = "github", feature = "gitlab"))]
+pub(crate) fn comment_of(data: &AgentData, number: i64) -> Result<(i64, String), AgentError> {
+    if let Some(body) = data.as_str() {
+        if number <= 0 {
+            return Err(AgentError::InvalidConfig(
+                "number is not set, and the comment has no number".to_string(),
+            ));
+        }
+        return Ok((number, body.to_string()));
+    }
+    let number = data.get_i64("number").unwrap_or(number);
+    let body = data
+        .get_str("body")
+        .ok_or_else(|| AgentError::InvalidValue("comment has no body".to_string()))?;
+    if number <= 0 {
+        return Err(AgentError::InvalidValue(
+            "comment has no number".to_string(),
+        ));
+    }
+    Ok((number, body.to_string()))
+}
+
+/// Whether the action of a webhook event is in the comma-separated `actions`. All pass
+/// when `actions` is empty.
+#[cfg(any(feature = "github", feature = "gitlab"))]
+pub(crate) fn action_allowed(actions: &str, action: Option<&str>) -> bool {
+    if actions.trim().is_empty() {
+        return true;
+    }
+    action.is_some_and(|action| actions.split(',').any(|a| a.trim() == action))
+}
+
+#[derive(Debug)]
+struct StatusError {
+    status: reqwest::StatusCode,
+    message: String,
+}
+
+impl std::fmt::Display for StatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.status, self.message)
+    }
+}
+
+impl std::error::Error for StatusError {}
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("askit-std-agents/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .expect("Failed to create the HTTP client")
+});
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
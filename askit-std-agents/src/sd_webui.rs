@@ -0,0 +1,198 @@
+use std::time::Duration;
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentCapability, AgentConfigs, AgentContext, AgentData, AgentDefinition,
+    AgentError, AgentOutput, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+use photon_rs::PhotonImage;
+use serde_json::{Map, Value, json};
+
+use crate::rest;
+
+// Stable Diffusion WebUI
+//
+// Text to image through the API of AUTOMATIC1111's Stable Diffusion WebUI, also served by
+// Forge and SD.Next when started with --api.
+
+// SD WebUI Txt2Img Agent
+struct SdWebUiTxt2ImgAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for SdWebUiTxt2ImgAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let prompt = data.as_str().unwrap_or_default().trim();
+        if prompt.is_empty() {
+            return Ok(());
+        }
+
+        self.check_capability(AgentCapability::Network)?;
+        let configs = self.configs()?;
+        let url = configs
+            .get_string_or(CONFIG_URL, URL_DEFAULT)
+            .trim_end_matches('/')
+            .to_string();
+        let payload = txt2img_payload(prompt, configs)?;
+
+        let request = rest::client()
+            .post(format!("{}/sdapi/v1/txt2img", url))
+            .timeout(TXT2IMG_TIMEOUT)
+            .json(&payload);
+        let text = rest::send_text(request).await?;
+        let res: Value = serde_json::from_str(&text)?;
+
+        for image in images_of(&res)? {
+            self.try_output(ctx.clone(), PIN_IMAGE, AgentData::image(image))?;
+        }
+        Ok(())
+    }
+}
+
+// Body of a txt2img request, with the options merged over the configs
+fn txt2img_payload(prompt: &str, configs: &AgentConfigs) -> Result<Value, AgentError> {
+    let mut payload = json!({
+        "prompt": prompt,
+        "negative_prompt": configs.get_string_or_default(CONFIG_NEGATIVE_PROMPT),
+        "steps": configs.get_integer_or(CONFIG_STEPS, STEPS_DEFAULT),
+        "width": configs.get_integer_or(CONFIG_WIDTH, SIZE_DEFAULT),
+        "height": configs.get_integer_or(CONFIG_HEIGHT, SIZE_DEFAULT),
+    });
+    let options = configs.get_string_or_default(CONFIG_OPTIONS);
+    if options.trim().is_empty() {
+        return Ok(payload);
+    }
+    let options: Map<String, Value> = serde_json::from_str(&options)
+        .map_err(|e| AgentError::InvalidConfig(format!("options is not a JSON object: {}", e)))?;
+    if let Some(obj) = payload.as_object_mut() {
+        obj.extend(options);
+    }
+    Ok(payload)
+}
+
+// Images of a txt2img response, which are PNG in base64
+fn images_of(res: &Value) -> Result<Vec<PhotonImage>, AgentError> {
+    let images = res["images"]
+        .as_array()
+        .ok_or_else(|| AgentError::InvalidValue("response has no images".to_string()))?;
+    images
+        .iter()
+        .map(|image| {
+            image
+                .as_str()
+                .map(PhotonImage::new_from_base64)
+                .ok_or_else(|| AgentError::InvalidValue("image is not a string".to_string()))
+        })
+        .collect()
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "Core/Image";
+
+static PIN_PROMPT: &str = "prompt";
+static PIN_IMAGE: &str = "image";
+
+static CONFIG_URL: &str = "url";
+static CONFIG_NEGATIVE_PROMPT: &str = "negative_prompt";
+static CONFIG_STEPS: &str = "steps";
+static CONFIG_WIDTH: &str = "width";
+static CONFIG_HEIGHT: &str = "height";
+static CONFIG_OPTIONS: &str = "options";
+
+const URL_DEFAULT: &str = "http://127.0.0.1:7860";
+const STEPS_DEFAULT: i64 = 20;
+const SIZE_DEFAULT: i64 = 512;
+
+// Generation takes longer than the usual requests
+const TXT2IMG_TIMEOUT: Duration = Duration::from_secs(600);
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "sd_webui_txt2img",
+            Some(new_agent_boxed::<SdWebUiTxt2ImgAgent>),
+        )
+        .title("SD WebUI Txt2Img")
+        .description("Generates images from the prompt with Stable Diffusion WebUI")
+        .category(CATEGORY)
+        .inputs(vec![PIN_PROMPT])
+        .outputs(vec![PIN_IMAGE])
+        .capabilities(vec![AgentCapability::Network])
+        .string_config_with(CONFIG_URL, URL_DEFAULT, |entry| entry.title("URL"))
+        .text_config_with(CONFIG_NEGATIVE_PROMPT, "", |entry| {
+            entry.title("Negative Prompt")
+        })
+        .integer_config_with(CONFIG_STEPS, STEPS_DEFAULT, |entry| entry.title("Steps"))
+        .integer_config_with(CONFIG_WIDTH, SIZE_DEFAULT, |entry| entry.title("Width"))
+        .integer_config_with(CONFIG_HEIGHT, SIZE_DEFAULT, |entry| entry.title("Height"))
+        .text_config_with(CONFIG_OPTIONS, "{}", |entry| {
+            entry
+                .title("Options")
+                .description("Other parameters of txt2img, such as sampler_name or seed")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use agent_stream_kit::AgentValue;
+
+    #[test]
+    fn test_txt2img_payload() {
+        let mut configs = AgentConfigs::new();
+        configs.set(CONFIG_STEPS.to_string(), AgentValue::integer(30));
+        configs.set(
+            CONFIG_OPTIONS.to_string(),
+            AgentValue::string(r#"{"seed": 1, "width": 768}"#),
+        );
+        let payload = txt2img_payload("a cat", &configs).unwrap();
+        assert_eq!(payload["prompt"], "a cat");
+        assert_eq!(payload["negative_prompt"], "");
+        assert_eq!(payload["steps"], 30);
+        assert_eq!(payload["width"], 768);
+        assert_eq!(payload["height"], 512);
+        assert_eq!(payload["seed"], 1);
+
+        configs.set(CONFIG_OPTIONS.to_string(), AgentValue::string("[]"));
+        assert!(txt2img_payload("a cat", &configs).is_err());
+    }
+
+    #[test]
+    fn test_images_of() {
+        // 1x1 PNG
+        let png = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mP8z8DwHwAFBQIAX8jx0gAAAABJRU5ErkJggg==";
+        let images = images_of(&json!({"images": [png], "info": "{}"})).unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].get_width(), 1);
+
+        assert!(images_of(&json!({"detail": "Not Found"})).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
+    AgentValue, AgentValueMap, AsAgent, AsAgentData, async_trait, new_agent_boxed,
+};
+
+// Similarity Agent
+//
+// Ranks the candidate vectors by their similarity to each vector given. The candidates are
+// kept until others are given, so a set of document embeddings can serve many queries.
+struct SimilarityAgent {
+    data: AsAgentData,
+    candidates: Vec<Vec<f64>>,
+}
+
+#[async_trait]
+impl AsAgent for SimilarityAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            candidates: Vec::new(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PIN_CANDIDATES {
+            self.candidates = candidates_of(&data.value)?;
+            return Ok(());
+        }
+
+        // {vector, candidates} gives both at once
+        let (vector, candidates) = match (data.get("vector"), data.get("candidates")) {
+            (Some(vector), Some(candidates)) => (vector_of(vector)?, candidates_of(candidates)?),
+            _ => (vector_of(&data.value)?, self.candidates.clone()),
+        };
+
+        let configs = self.configs()?;
+        let metric = Metric::parse(&configs.get_string_or(CONFIG_METRIC, METRIC_DEFAULT))?;
+        let top_k = configs.get_integer_or_default(CONFIG_TOP_K).max(0) as usize;
+        let ranked = rank(&vector, &candidates, metric, top_k)?;
+
+        let indices = ranked
+            .iter()
+            .map(|(index, _)| AgentValue::integer(*index as i64))
+            .collect::<Vec<_>>();
+        let ranked = ranked
+            .into_iter()
+            .map(|(index, score)| {
+                let mut map = AgentValueMap::new();
+                map.insert("index".to_string(), AgentValue::integer(index as i64));
+                map.insert("score".to_string(), AgentValue::number(score));
+                AgentValue::object(map)
+            })
+            .collect::<Vec<_>>();
+        self.try_output(ctx.clone(), PIN_RANKED, AgentData::array("object", ranked))?;
+        self.try_output(ctx, PIN_INDICES, AgentData::array("integer", indices))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Metric {
+    Cosine,
+    Dot,
+}
+
+impl Metric {
+    fn parse(s: &str) -> Result<Self, AgentError> {
+        match s {
+            "cosine" => Ok(Metric::Cosine),
+            "dot" => Ok(Metric::Dot),
+            _ => Err(AgentError::InvalidConfig(format!("Unknown metric: {}", s))),
+        }
+    }
+
+    fn score(&self, a: &[f64], b: &[f64]) -> f64 {
+        let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f64>();
+        match self {
+            Metric::Dot => dot,
+            Metric::Cosine => {
+                let norm = |v: &[f64]| v.iter().map(|x| x * x).sum::<f64>().sqrt();
+                let norms = norm(a) * norm(b);
+                if norms == 0.0 { 0.0 } else { dot / norms }
+            }
+        }
+    }
+}
+
+// (index, score) of the candidates from the most similar, the first top_k of them unless 0
+fn rank(
+    vector: &[f64],
+    candidates: &[Vec<f64>],
+    metric: Metric,
+    top_k: usize,
+) -> Result<Vec<(usize, f64)>, AgentError> {
+    let mut ranked = Vec::with_capacity(candidates.len());
+    for (index, candidate) in candidates.iter().enumerate() {
+        if candidate.len() != vector.len() {
+            return Err(AgentError::InvalidValue(format!(
+                "candidate {} has {} dimensions, but the vector has {}",
+                index,
+                candidate.len(),
+                vector.len()
+            )));
+        }
+        ranked.push((index, metric.score(vector, candidate)));
+    }
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    if top_k > 0 {
+        ranked.truncate(top_k);
+    }
+    Ok(ranked)
+}
+
+// An array of numbers, an object with an `embedding` like the items of openai_embeddings,
+// or an array of one of them like the output of ollama_embeddings
+fn vector_of(value: &AgentValue) -> Result<Vec<f64>, AgentError> {
+    if let Some(embedding) = value.get("embedding") {
+        return vector_of(embedding);
+    }
+    let Some(arr) = value.as_array() else {
+        return Err(AgentError::InvalidValue(
+            "vector is not an array".to_string(),
+        ));
+    };
+    if arr.len() == 1 && arr[0].as_f64().is_none() {
+        return vector_of(&arr[0]);
+    }
+    arr.iter()
+        .map(|v| {
+            v.as_f64()
+                .ok_or_else(|| AgentError::InvalidValue("vector is not of numbers".to_string()))
+        })
+        .collect()
+}
+
+fn candidates_of(value: &AgentValue) -> Result<Vec<Vec<f64>>, AgentError> {
+    let Some(arr) = value.as_array() else {
+        return Err(AgentError::InvalidValue(
+            "candidates are not an array".to_string(),
+        ));
+    };
+    arr.iter().map(vector_of).collect()
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "Core/Data";
+
+static PIN_VECTOR: &str = "vector";
+static PIN_CANDIDATES: &str = "candidates";
+static PIN_RANKED: &str = "ranked";
+static PIN_INDICES: &str = "indices";
+
+static CONFIG_METRIC: &str = "metric";
+static CONFIG_TOP_K: &str = "top_k";
+
+const METRIC_DEFAULT: &str = "cosine";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_similarity",
+            Some(new_agent_boxed::<SimilarityAgent>),
+        )
+        .title("Similarity")
+        .description("Ranks the candidate vectors by their similarity to the vector")
+        .category(CATEGORY)
+        .inputs(vec![PIN_VECTOR, PIN_CANDIDATES])
+        .outputs(vec![PIN_RANKED, PIN_INDICES])
+        .string_config_with(CONFIG_METRIC, METRIC_DEFAULT, |entry| {
+            entry.title("Metric").description("cosine or dot")
+        })
+        .integer_config_with(CONFIG_TOP_K, 0, |entry| {
+            entry
+                .title("Top K")
+                .description("All the candidates when 0")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank() {
+        let candidates = vec![
+            vec![0.0, 1.0],
+            vec![2.0, 0.0],
+            vec![1.0, 1.0],
+            vec![0.0, 0.0],
+        ];
+        let ranked = rank(&[1.0, 0.0], &candidates, Metric::Cosine, 0).unwrap();
+        let indices = ranked.iter().map(|(i, _)| *i).collect::<Vec<_>>();
+        assert_eq!(indices, vec![1, 2, 0, 3]);
+        assert!((ranked[0].1 - 1.0).abs() < 1e-9);
+        assert!((ranked[1].1 - 0.5f64.sqrt()).abs() < 1e-9);
+
+        // unlike cosine, dot product favors the longer vector
+        let ranked = rank(&[1.0, 0.5], &candidates, Metric::Cosine, 2).unwrap();
+        assert_eq!(ranked[0].0, 2);
+        let ranked = rank(&[1.0, 0.5], &candidates, Metric::Dot, 2).unwrap();
+        assert_eq!(ranked, vec![(1, 2.0), (2, 1.5)]);
+
+        assert!(rank(&[1.0], &candidates, Metric::Cosine, 0).is_err());
+        assert!(Metric::parse("euclid").is_err());
+    }
+
+    #[test]
+    fn test_vector_of() {
+        let value = AgentValue::from_json(serde_json::json!([1, 0.5])).unwrap();
+        assert_eq!(vector_of(&value).unwrap(), vec![1.0, 0.5]);
+
+        // ollama_embeddings and openai_embeddings
+        let value = AgentValue::from_json(serde_json::json!([[1, 0.5]])).unwrap();
+        assert_eq!(vector_of(&value).unwrap(), vec![1.0, 0.5]);
+        let value = AgentValue::from_json(serde_json::json!([
+            {"index": 0, "object": "embedding", "embedding": [1, 0.5]},
+            {"index": 1, "object": "embedding", "embedding": [0, 1]},
+        ]))
+        .unwrap();
+        assert_eq!(
+            candidates_of(&value).unwrap(),
+            vec![vec![1.0, 0.5], vec![0.0, 1.0]]
+        );
+
+        let value = AgentValue::from_json(serde_json::json!(["a"])).unwrap();
+        assert!(vector_of(&value).is_err());
+    }
+}
@@ -1,7 +1,10 @@
+use std::sync::{Arc, Mutex};
+
 use agent_stream_kit::{
     ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentOutput,
     AgentValue, AgentValueMap, AsAgent, AsAgentData, async_trait, new_agent_boxed,
 };
+use chrono::TimeDelta;
 
 // Zip agent
 struct ZipAgent {
@@ -122,10 +125,431 @@ impl AsAgent for ZipAgent {
     }
 }
 
+// Splits chunks of text into lines, keeping the partial last line until it is complete.
+#[derive(Default)]
+struct LineBuffer {
+    buf: Vec<u8>,
+}
+
+impl LineBuffer {
+    fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buf.extend_from_slice(chunk);
+        let Some(last) = self.buf.iter().rposition(|b| *b == b'\n') else {
+            return Vec::new();
+        };
+        let rest = self.buf.split_off(last + 1);
+        let complete = std::mem::replace(&mut self.buf, rest);
+        complete[..last]
+            .split(|b| *b == b'\n')
+            .map(|line| {
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                String::from_utf8_lossy(line).into_owned()
+            })
+            .collect()
+    }
+
+    // Takes the partial last line.
+    fn flush(&mut self) -> Option<String> {
+        if self.buf.is_empty() {
+            return None;
+        }
+        let line = std::mem::take(&mut self.buf);
+        let line = line.strip_suffix(b"\r").unwrap_or(&line);
+        Some(String::from_utf8_lossy(line).into_owned())
+    }
+}
+
+// Chunks are strings, or arrays of byte values which may split UTF-8 sequences.
+fn chunk_bytes(data: &AgentData) -> Result<Vec<u8>, AgentError> {
+    if let Some(s) = data.as_str() {
+        return Ok(s.as_bytes().to_vec());
+    }
+    if let Some(arr) = data.as_array() {
+        return arr
+            .iter()
+            .map(|v| {
+                v.as_i64()
+                    .and_then(|b| u8::try_from(b).ok())
+                    .ok_or_else(|| AgentError::InvalidArrayValue("byte".into()))
+            })
+            .collect();
+    }
+    Err(AgentError::InvalidValue("chunk".into()))
+}
+
+// NDJSON Parse Agent
+struct NdjsonParseAgent {
+    data: AsAgentData,
+    lines: LineBuffer,
+}
+
+impl NdjsonParseAgent {
+    fn output_records(&mut self, ctx: &AgentContext, lines: Vec<String>) -> Result<(), AgentError> {
+        let mut first_error = None;
+        for line in lines {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<serde_json::Value>(line) {
+                Ok(json) => self.try_output(ctx.clone(), PIN_DATA, AgentData::from_json(json)?)?,
+                Err(e) => {
+                    first_error.get_or_insert(AgentError::InvalidValue(format!("NDJSON: {}", e)));
+                }
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl AsAgent for NdjsonParseAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            lines: LineBuffer::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        self.lines = LineBuffer::default();
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let lines = if pin == PIN_FLUSH {
+            self.lines.flush().into_iter().collect()
+        } else {
+            let chunk = chunk_bytes(&data)?;
+            self.lines.push(&chunk)
+        };
+        self.output_records(&ctx, lines)
+    }
+}
+
+// SSE Parse Agent
+struct SseParseAgent {
+    data: AsAgentData,
+    lines: LineBuffer,
+    event: SseEvent,
+}
+
+// Fields of the event being received
+#[derive(Default)]
+struct SseEvent {
+    event: Option<String>,
+    data: Vec<String>,
+    id: Option<String>,
+}
+
+impl SseParseAgent {
+    fn process_lines(&mut self, ctx: &AgentContext, lines: Vec<String>) -> Result<(), AgentError> {
+        for line in lines {
+            if line.is_empty() {
+                self.dispatch(ctx)?;
+                continue;
+            }
+            if line.starts_with(':') {
+                // comment
+                continue;
+            }
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line.as_str(), ""),
+            };
+            match field {
+                "event" => self.event.event = Some(value.to_string()),
+                "data" => self.event.data.push(value.to_string()),
+                "id" => self.event.id = Some(value.to_string()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn dispatch(&mut self, ctx: &AgentContext) -> Result<(), AgentError> {
+        let event = std::mem::take(&mut self.event);
+        if event.data.is_empty() {
+            return Ok(());
+        }
+        let data = event.data.join("\n");
+        let data_value = if self.configs()?.get_bool_or_default(CONFIG_PARSE_JSON) {
+            serde_json::from_str::<serde_json::Value>(&data)
+                .ok()
+                .map(AgentValue::from_json)
+                .transpose()?
+                .unwrap_or_else(|| AgentValue::string(data))
+        } else {
+            AgentValue::string(data)
+        };
+
+        let mut map = AgentValueMap::new();
+        map.insert(
+            "event".to_string(),
+            AgentValue::string(event.event.unwrap_or_else(|| "message".to_string())),
+        );
+        map.insert("data".to_string(), data_value);
+        if let Some(id) = event.id {
+            map.insert("id".to_string(), AgentValue::string(id));
+        }
+        self.try_output(ctx.clone(), PIN_EVENT, AgentData::object(map))
+    }
+}
+
+#[async_trait]
+impl AsAgent for SseParseAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            lines: LineBuffer::default(),
+            event: SseEvent::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        self.lines = LineBuffer::default();
+        self.event = SseEvent::default();
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PIN_FLUSH {
+            let lines = self.lines.flush().into_iter().collect();
+            self.process_lines(&ctx, lines)?;
+            return self.dispatch(&ctx);
+        }
+        let chunk = chunk_bytes(&data)?;
+        let lines = self.lines.push(&chunk);
+        self.process_lines(&ctx, lines)
+    }
+}
+
+// Lines Agent
+struct LinesAgent {
+    data: AsAgentData,
+    lines: LineBuffer,
+}
+
+#[async_trait]
+impl AsAgent for LinesAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            lines: LineBuffer::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        self.lines = LineBuffer::default();
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let lines: Vec<String> = if pin == PIN_FLUSH {
+            self.lines.flush().into_iter().collect()
+        } else {
+            let chunk = chunk_bytes(&data)?;
+            self.lines.push(&chunk)
+        };
+        let skip_empty = self.configs()?.get_bool_or_default(CONFIG_SKIP_EMPTY);
+        for line in lines {
+            if skip_empty && line.trim().is_empty() {
+                continue;
+            }
+            self.try_output(ctx.clone(), PIN_LINE, AgentData::string(line))?;
+        }
+        Ok(())
+    }
+}
+
+// Batch Agent
+struct BatchAgent {
+    data: AsAgentData,
+    batch: Arc<Mutex<Batch>>,
+}
+
+#[derive(Default)]
+struct Batch {
+    ctx: Option<AgentContext>,
+    kind: String,
+    items: Vec<AgentValue>,
+
+    // incremented whenever the batch is taken, so that stale timers do nothing
+    generation: usize,
+}
+
+impl Batch {
+    fn take(&mut self) -> Option<(AgentContext, AgentData)> {
+        self.generation += 1;
+        let ctx = self.ctx.take()?;
+        let items = std::mem::take(&mut self.items);
+        Some((ctx, AgentData::array(std::mem::take(&mut self.kind), items)))
+    }
+}
+
+#[async_trait]
+impl AsAgent for BatchAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            batch: Default::default(),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn stop(&mut self) -> Result<(), AgentError> {
+        let mut batch = self.batch.lock().unwrap();
+        batch.take();
+        Ok(())
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        if pin == PIN_FLUSH {
+            let taken = self.batch.lock().unwrap().take();
+            if let Some((ctx, out)) = taken {
+                self.try_output(ctx, PIN_ARRAY, out)?;
+            }
+            return Ok(());
+        }
+
+        let configs = self.configs()?;
+        let size = configs.get_integer_or(CONFIG_SIZE, DEFAULT_SIZE).max(1) as usize;
+        let time = configs.get_duration_or_default(CONFIG_TIME);
+
+        let (full, timer_generation) = {
+            let mut batch = self.batch.lock().unwrap();
+            let first = batch.ctx.is_none();
+            if first {
+                batch.ctx = Some(ctx);
+                batch.kind = data.kind.clone();
+            }
+            batch.items.push(data.value);
+            if batch.items.len() >= size {
+                (batch.take(), None)
+            } else {
+                (None, first.then_some(batch.generation))
+            }
+        };
+
+        if let Some((ctx, out)) = full {
+            self.try_output(ctx, PIN_ARRAY, out)?;
+        }
+
+        // Emit a partial batch when the time runs out
+        if let Some(generation) = timer_generation
+            && let Ok(time) = time.to_std()
+            && !time.is_zero()
+        {
+            let batch = self.batch.clone();
+            let askit = self.askit().clone();
+            let agent_id = self.id().to_string();
+            self.runtime().spawn(async move {
+                askit.clock().sleep(time).await;
+                let taken = {
+                    let mut batch = batch.lock().unwrap();
+                    if batch.generation != generation {
+                        return;
+                    }
+                    batch.take()
+                };
+                if let Some((ctx, out)) = taken {
+                    askit
+                        .try_send_agent_out(agent_id, ctx, PIN_ARRAY.to_string(), out)
+                        .unwrap_or_else(|e| {
+                            log::error!("Failed to send batch output: {}", e);
+                        });
+                }
+            });
+        }
+
+        Ok(())
+    }
+}
+
 static AGENT_KIND: &str = "agent";
 static CATEGORY: &str = "Core/Stream";
 
+static PIN_ARRAY: &str = "array";
+static PIN_CHUNK: &str = "chunk";
 static PIN_DATA: &str = "data";
+static PIN_EVENT: &str = "event";
+static PIN_FLUSH: &str = "flush";
+static PIN_LINE: &str = "line";
+static PIN_TEXT: &str = "text";
 static PIN_IN1: &str = "in1";
 static PIN_IN2: &str = "in2";
 static PIN_IN3: &str = "in3";
@@ -136,6 +560,12 @@ static CONFIG_KEY2: &str = "key2";
 static CONFIG_KEY3: &str = "key3";
 static CONFIG_KEY4: &str = "key4";
 static CONFIG_N: &str = "n";
+static CONFIG_PARSE_JSON: &str = "parse_json";
+static CONFIG_SIZE: &str = "size";
+static CONFIG_SKIP_EMPTY: &str = "skip_empty";
+static CONFIG_TIME: &str = "time";
+
+const DEFAULT_SIZE: i64 = 10;
 
 pub fn register_agents(askit: &ASKit) {
     askit.register_agent(
@@ -173,4 +603,80 @@ pub fn register_agents(askit: &ASKit) {
             .string_config_default(CONFIG_KEY3)
             .string_config_default(CONFIG_KEY4),
     );
+
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_ndjson_parse",
+            Some(new_agent_boxed::<NdjsonParseAgent>),
+        )
+        .title("NDJSON Parse")
+        .description("Parses newline-delimited JSON arriving in chunks")
+        .category(CATEGORY)
+        .inputs(vec![PIN_CHUNK, PIN_FLUSH])
+        .outputs(vec![PIN_DATA]),
+    );
+
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_sse_parse",
+            Some(new_agent_boxed::<SseParseAgent>),
+        )
+        .title("SSE Parse")
+        .description("Parses Server-Sent Events arriving in chunks")
+        .category(CATEGORY)
+        .inputs(vec![PIN_CHUNK, PIN_FLUSH])
+        .outputs(vec![PIN_EVENT])
+        .boolean_config_with(CONFIG_PARSE_JSON, false, |entry| entry.title("Parse JSON")),
+    );
+
+    askit.register_agent(
+        AgentDefinition::new(AGENT_KIND, "std_lines", Some(new_agent_boxed::<LinesAgent>))
+            .title("Lines")
+            .description("Splits incoming text into lines")
+            .category(CATEGORY)
+            .inputs(vec![PIN_TEXT, PIN_FLUSH])
+            .outputs(vec![PIN_LINE])
+            .boolean_config_with(CONFIG_SKIP_EMPTY, false, |entry| {
+                entry.title("Skip Empty Lines")
+            }),
+    );
+
+    askit.register_agent(
+        AgentDefinition::new(AGENT_KIND, "std_batch", Some(new_agent_boxed::<BatchAgent>))
+            .title("Batch")
+            .description(
+                "Collects up to size items, or the items received within time, into an array",
+            )
+            .category(CATEGORY)
+            .inputs(vec![PIN_DATA, PIN_FLUSH])
+            .outputs(vec![PIN_ARRAY])
+            .integer_config_with(CONFIG_SIZE, DEFAULT_SIZE, |entry| entry.title("Size"))
+            .duration_config_with(CONFIG_TIME, TimeDelta::zero(), |entry| {
+                entry
+                    .title("Time")
+                    .description("Emits a partial batch after this time. Zero waits for the size.")
+            }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_buffer() {
+        let mut lines = LineBuffer::default();
+        assert!(lines.push(b"{\"a\":").is_empty());
+        assert_eq!(lines.push(b"1}\r\n{\"b\"").as_slice(), [r#"{"a":1}"#]);
+        assert_eq!(lines.push(b":2}\n\n").as_slice(), [r#"{"b":2}"#, ""]);
+        assert_eq!(lines.flush(), None);
+
+        // a multibyte character split across chunks
+        let bytes = "é\nx".as_bytes();
+        assert!(lines.push(&bytes[..1]).is_empty());
+        assert_eq!(lines.push(&bytes[1..]).as_slice(), ["é"]);
+        assert_eq!(lines.flush(), Some("x".to_string()));
+    }
 }
@@ -0,0 +1,649 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::time::{Duration, Instant};
+use std::vec;
+
+use agent_stream_kit::{
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError, AgentStatus,
+    AsAgent, AsAgentData, new_agent_boxed,
+};
+use serde_json::{Value, json};
+use tokio::task::AbortHandle;
+
+use crate::time::parse_duration_to_ms;
+
+// System monitoring
+//
+// The stats are read from /proc, so the agents are available on Linux only. Rates, such as the
+// CPU usage and the network throughput, are measured between two samples, so the first output
+// comes one interval after the start.
+
+// System Stats Agent
+struct SystemStatsAgent {
+    data: AsAgentData,
+    sampler: Option<AbortHandle>,
+}
+
+impl SystemStatsAgent {
+    fn start_sampler(&mut self) -> Result<(), AgentError> {
+        let interval_ms = interval_ms(self.configs()?)?;
+        let mut stats = SystemStats::new();
+        self.sampler = Some(spawn_sampler(&mut self.data, interval_ms, move || {
+            stats.sample()
+        }));
+        Ok(())
+    }
+}
+
+impl AsAgent for SystemStatsAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            sampler: None,
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        self.start_sampler()
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        if *self.status() == AgentStatus::Start {
+            stop_sampler(&mut self.sampler);
+            self.start_sampler()?;
+        }
+        Ok(())
+    }
+}
+
+// System Processes Agent
+struct SystemProcessesAgent {
+    data: AsAgentData,
+    sampler: Option<AbortHandle>,
+}
+
+impl SystemProcessesAgent {
+    fn start_sampler(&mut self) -> Result<(), AgentError> {
+        let configs = self.configs()?;
+        let interval_ms = interval_ms(configs)?;
+        let limit = configs.get_integer_or(CONFIG_LIMIT, LIMIT_DEFAULT).max(0) as usize;
+        let sort_by = configs.get_string_or(CONFIG_SORT_BY, SORT_BY_DEFAULT);
+        if sort_by != "cpu" && sort_by != "memory" {
+            return Err(AgentError::InvalidConfig(format!(
+                "sort_by must be cpu or memory: {}",
+                sort_by
+            )));
+        }
+
+        let mut processes = ProcessStats::new();
+        self.sampler = Some(spawn_sampler(&mut self.data, interval_ms, move || {
+            vec![(PIN_PROCESSES, processes.sample(&sort_by, limit))]
+        }));
+        Ok(())
+    }
+}
+
+impl AsAgent for SystemProcessesAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+            sampler: None,
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    fn start(&mut self) -> Result<(), AgentError> {
+        self.start_sampler()
+    }
+
+    fn configs_changed(&mut self) -> Result<(), AgentError> {
+        if *self.status() == AgentStatus::Start {
+            stop_sampler(&mut self.sampler);
+            self.start_sampler()?;
+        }
+        Ok(())
+    }
+}
+
+fn interval_ms(configs: &AgentConfigs) -> Result<u64, AgentError> {
+    parse_duration_to_ms(&configs.get_string_or(CONFIG_INTERVAL, INTERVAL_DEFAULT))
+}
+
+// Outputs the values of `sample` to their pins every interval, until the agent stops.
+fn spawn_sampler<F>(data: &mut AsAgentData, interval_ms: u64, mut sample: F) -> AbortHandle
+where
+    F: FnMut() -> Vec<(&'static str, Option<Value>)> + Send + 'static,
+{
+    let askit = data.askit.clone();
+    let clock = askit.clock();
+    let agent_id = data.id.clone();
+    data.spawn_task(async move {
+        let mut next = clock.now();
+        loop {
+            next += Duration::from_millis(interval_ms);
+            clock.sleep_until(next).await;
+
+            for (pin, value) in sample() {
+                let Some(value) = value else {
+                    continue;
+                };
+                let data = match AgentData::from_json(value) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        log::error!("Failed to convert {} stats: {}", pin, e);
+                        continue;
+                    }
+                };
+                if let Err(e) = askit.try_send_agent_out(
+                    agent_id.clone(),
+                    AgentContext::new(),
+                    pin.to_string(),
+                    data,
+                ) {
+                    log::error!("Failed to send system stats: {}", e);
+                }
+            }
+        }
+    })
+}
+
+fn stop_sampler(sampler: &mut Option<AbortHandle>) {
+    if let Some(handle) = sampler.take() {
+        handle.abort();
+    }
+}
+
+// Counters of the previous sample, for the rates
+struct SystemStats {
+    cpu: Vec<CpuTimes>,
+    network: HashMap<String, (u64, u64)>,
+    sampled: Instant,
+}
+
+impl SystemStats {
+    fn new() -> Self {
+        Self {
+            cpu: read_proc("stat")
+                .map(|stat| parse_cpu_times(&stat))
+                .unwrap_or_default(),
+            network: read_proc("net/dev")
+                .map(|dev| parse_net_dev(&dev))
+                .unwrap_or_default(),
+            sampled: Instant::now(),
+        }
+    }
+
+    fn sample(&mut self) -> Vec<(&'static str, Option<Value>)> {
+        let elapsed = self.sampled.elapsed().as_secs_f64();
+        self.sampled = Instant::now();
+        vec![
+            (PIN_CPU, self.cpu()),
+            (PIN_MEMORY, memory()),
+            (PIN_DISKS, disks()),
+            (PIN_NETWORK, self.network(elapsed)),
+        ]
+    }
+
+    fn cpu(&mut self) -> Option<Value> {
+        let cpu = parse_cpu_times(&read_proc("stat")?);
+        let usages = cpu
+            .iter()
+            .enumerate()
+            .map(|(i, times)| {
+                let prev = self.cpu.get(i).copied().unwrap_or_default();
+                times.usage_since(&prev)
+            })
+            .collect::<Vec<_>>();
+        self.cpu = cpu;
+
+        let load = read_proc("loadavg")
+            .map(|loadavg| {
+                loadavg
+                    .split_whitespace()
+                    .take(3)
+                    .filter_map(|load| load.parse::<f64>().ok())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let (usage, cores) = usages.split_first()?;
+        Some(json!({
+            "usage": usage,
+            "cores": cores,
+            "load": load,
+        }))
+    }
+
+    fn network(&mut self, elapsed: f64) -> Option<Value> {
+        let network = parse_net_dev(&read_proc("net/dev")?);
+        let mut interfaces = network
+            .iter()
+            .map(|(name, (received, transmitted))| {
+                let (prev_received, prev_transmitted) = self
+                    .network
+                    .get(name)
+                    .copied()
+                    .unwrap_or((*received, *transmitted));
+                json!({
+                    "interface": name,
+                    "received": received,
+                    "transmitted": transmitted,
+                    "receive_rate": rate(received.saturating_sub(prev_received), elapsed),
+                    "transmit_rate": rate(transmitted.saturating_sub(prev_transmitted), elapsed),
+                })
+            })
+            .collect::<Vec<_>>();
+        interfaces.sort_by(|a, b| a["interface"].as_str().cmp(&b["interface"].as_str()));
+        self.network = network;
+        Some(Value::Array(interfaces))
+    }
+}
+
+fn memory() -> Option<Value> {
+    let meminfo = parse_meminfo(&read_proc("meminfo")?);
+    let total = *meminfo.get("MemTotal")?;
+    let available = meminfo.get("MemAvailable").copied().unwrap_or(0);
+    let swap_total = meminfo.get("SwapTotal").copied().unwrap_or(0);
+    let swap_free = meminfo.get("SwapFree").copied().unwrap_or(0);
+    let used = total.saturating_sub(available);
+    Some(json!({
+        "total": total,
+        "used": used,
+        "available": available,
+        "usage": percent(used, total),
+        "swap_total": swap_total,
+        "swap_used": swap_total.saturating_sub(swap_free),
+    }))
+}
+
+fn disks() -> Option<Value> {
+    let mounts = read_proc("mounts")?;
+    let mut devices = Vec::new();
+    let mut disks = Vec::new();
+    for (device, mount_point, file_system) in parse_mounts(&mounts) {
+        if !device.starts_with("/dev/") || devices.contains(&device) {
+            continue;
+        }
+        let Some((total, free, available)) = statvfs(&mount_point) else {
+            continue;
+        };
+        let used = total.saturating_sub(free);
+        disks.push(json!({
+            "device": device,
+            "mount_point": mount_point,
+            "file_system": file_system,
+            "total": total,
+            "used": used,
+            "available": available,
+            "usage": percent(used, total),
+        }));
+        devices.push(device);
+    }
+    Some(Value::Array(disks))
+}
+
+// CPU time counters of /proc/pid/stat, for the usage of the processes
+struct ProcessStats {
+    ticks: HashMap<u32, u64>,
+    sampled: Instant,
+}
+
+impl ProcessStats {
+    fn new() -> Self {
+        let ticks = read_processes()
+            .into_iter()
+            .map(|process| (process.pid, process.ticks))
+            .collect();
+        Self {
+            ticks,
+            sampled: Instant::now(),
+        }
+    }
+
+    fn sample(&mut self, sort_by: &str, limit: usize) -> Option<Value> {
+        let elapsed = self.sampled.elapsed().as_secs_f64();
+        self.sampled = Instant::now();
+        let ticks_per_sec = sysconf(libc::_SC_CLK_TCK).unwrap_or(100) as f64;
+        let page_size = sysconf(libc::_SC_PAGESIZE).unwrap_or(4096);
+
+        let processes = read_processes();
+        let mut rows = processes
+            .iter()
+            .map(|process| {
+                let prev = self
+                    .ticks
+                    .get(&process.pid)
+                    .copied()
+                    .unwrap_or(process.ticks);
+                let cpu = if elapsed > 0.0 {
+                    process.ticks.saturating_sub(prev) as f64 / ticks_per_sec / elapsed * 100.0
+                } else {
+                    0.0
+                };
+                (process, cpu, process.rss_pages * page_size)
+            })
+            .collect::<Vec<_>>();
+        if sort_by == "memory" {
+            rows.sort_by_key(|row| std::cmp::Reverse(row.2));
+        } else {
+            rows.sort_by(|a, b| b.1.total_cmp(&a.1));
+        }
+        self.ticks = processes
+            .iter()
+            .map(|process| (process.pid, process.ticks))
+            .collect();
+
+        let rows = rows
+            .into_iter()
+            .take(limit)
+            .map(|(process, cpu, memory)| {
+                json!({
+                    "pid": process.pid,
+                    "name": process.name,
+                    "cpu": cpu,
+                    "memory": memory,
+                })
+            })
+            .collect();
+        Some(Value::Array(rows))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct ProcessStat {
+    pid: u32,
+    name: String,
+    ticks: u64,
+    rss_pages: u64,
+}
+
+fn read_processes() -> Vec<ProcessStat> {
+    let Ok(entries) = std::fs::read_dir("/proc") else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.bytes().all(|b| b.is_ascii_digit()))
+        })
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("stat")).ok())
+        .filter_map(|stat| parse_process_stat(&stat))
+        .collect()
+}
+
+fn read_proc(path: &str) -> Option<String> {
+    std::fs::read_to_string(format!("/proc/{}", path))
+        .inspect_err(|e| log::warn!("Failed to read /proc/{}: {}", path, e))
+        .ok()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct CpuTimes {
+    busy: u64,
+    total: u64,
+}
+
+impl CpuTimes {
+    // percent of the time busy since `prev`
+    fn usage_since(&self, prev: &CpuTimes) -> f64 {
+        percent(
+            self.busy.saturating_sub(prev.busy),
+            self.total.saturating_sub(prev.total),
+        )
+    }
+}
+
+// The "cpu" line of /proc/stat followed by the "cpuN" lines.
+fn parse_cpu_times(stat: &str) -> Vec<CpuTimes> {
+    stat.lines()
+        .filter(|line| line.starts_with("cpu"))
+        .map(|line| {
+            // user nice system idle iowait irq softirq steal
+            let times = line
+                .split_whitespace()
+                .skip(1)
+                .take(8)
+                .map(|n| n.parse::<u64>().unwrap_or(0))
+                .collect::<Vec<_>>();
+            let total = times.iter().sum::<u64>();
+            let idle = times.get(3).unwrap_or(&0) + times.get(4).unwrap_or(&0);
+            CpuTimes {
+                busy: total.saturating_sub(idle),
+                total,
+            }
+        })
+        .collect()
+}
+
+// key -> bytes
+fn parse_meminfo(meminfo: &str) -> HashMap<String, u64> {
+    meminfo
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let mut parts = value.split_whitespace();
+            let n = parts.next()?.parse::<u64>().ok()?;
+            let bytes = if parts.next() == Some("kB") {
+                n * 1024
+            } else {
+                n
+            };
+            Some((key.to_string(), bytes))
+        })
+        .collect()
+}
+
+// interface -> (received bytes, transmitted bytes)
+fn parse_net_dev(dev: &str) -> HashMap<String, (u64, u64)> {
+    dev.lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, counters) = line.split_once(':')?;
+            let counters = counters
+                .split_whitespace()
+                .map(|n| n.parse::<u64>().unwrap_or(0))
+                .collect::<Vec<_>>();
+            Some((
+                name.trim().to_string(),
+                (*counters.first()?, *counters.get(8)?),
+            ))
+        })
+        .collect()
+}
+
+// (device, mount point, file system)
+fn parse_mounts(mounts: &str) -> Vec<(String, String, String)> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let device = fields.next()?;
+            let mount_point = fields.next()?.replace("\\040", " ");
+            let file_system = fields.next()?;
+            Some((device.to_string(), mount_point, file_system.to_string()))
+        })
+        .collect()
+}
+
+fn parse_process_stat(stat: &str) -> Option<ProcessStat> {
+    // the name is in parentheses and may contain spaces
+    let (pid, rest) = stat.split_once(" (")?;
+    let (name, rest) = rest.rsplit_once(") ")?;
+    // fields from the state on: utime and stime are 11 and 12, rss is 21
+    let fields = rest.split_whitespace().collect::<Vec<_>>();
+    let field = |i: usize| fields.get(i).and_then(|n| n.parse::<u64>().ok());
+    Some(ProcessStat {
+        pid: pid.trim().parse().ok()?,
+        name: name.to_string(),
+        ticks: field(11)? + field(12)?,
+        rss_pages: field(21)?,
+    })
+}
+
+// (total, free, available to unprivileged users) in bytes
+fn statvfs(path: &str) -> Option<(u64, u64, u64)> {
+    let path = CString::new(path).ok()?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: path is a valid C string and stat is written by statvfs on success
+    let stat = unsafe {
+        if libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) != 0 {
+            return None;
+        }
+        stat.assume_init()
+    };
+    let block = stat.f_frsize as u64;
+    Some((
+        stat.f_blocks as u64 * block,
+        stat.f_bfree as u64 * block,
+        stat.f_bavail as u64 * block,
+    ))
+}
+
+fn sysconf(name: libc::c_int) -> Option<u64> {
+    // SAFETY: sysconf has no preconditions
+    let value = unsafe { libc::sysconf(name) };
+    (value > 0).then_some(value as u64)
+}
+
+fn percent(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64 * 100.0
+    }
+}
+
+// bytes per second
+fn rate(bytes: u64, elapsed: f64) -> f64 {
+    if elapsed > 0.0 {
+        bytes as f64 / elapsed
+    } else {
+        0.0
+    }
+}
+
+static AGENT_KIND: &str = "Agent";
+static CATEGORY: &str = "Core/System";
+
+static PIN_CPU: &str = "cpu";
+static PIN_MEMORY: &str = "memory";
+static PIN_DISKS: &str = "disks";
+static PIN_NETWORK: &str = "network";
+static PIN_PROCESSES: &str = "processes";
+
+static CONFIG_INTERVAL: &str = "interval";
+static CONFIG_LIMIT: &str = "limit";
+static CONFIG_SORT_BY: &str = "sort_by";
+
+static INTERVAL_DEFAULT: &str = "10s";
+const LIMIT_DEFAULT: i64 = 10;
+static SORT_BY_DEFAULT: &str = "cpu";
+
+pub fn register_agents(askit: &ASKit) {
+    // System Stats Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_system_stats",
+            Some(new_agent_boxed::<SystemStatsAgent>),
+        )
+        .title("System Stats")
+        .description("Outputs CPU, memory, disk and network stats at specified intervals")
+        .category(CATEGORY)
+        .outputs(vec![PIN_CPU, PIN_MEMORY, PIN_DISKS, PIN_NETWORK])
+        .string_config_with(CONFIG_INTERVAL, INTERVAL_DEFAULT, |entry| {
+            entry.description("(ex. 10s, 5m, 100ms, 1h, 1d)")
+        }),
+    );
+
+    // System Processes Agent
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_system_processes",
+            Some(new_agent_boxed::<SystemProcessesAgent>),
+        )
+        .title("System Processes")
+        .description("Outputs the top processes by CPU or memory at specified intervals")
+        .category(CATEGORY)
+        .outputs(vec![PIN_PROCESSES])
+        .string_config_with(CONFIG_INTERVAL, INTERVAL_DEFAULT, |entry| {
+            entry.description("(ex. 10s, 5m, 100ms, 1h, 1d)")
+        })
+        .integer_config_with(CONFIG_LIMIT, LIMIT_DEFAULT, |entry| entry.title("limit"))
+        .string_config_with(CONFIG_SORT_BY, SORT_BY_DEFAULT, |entry| {
+            entry.title("sort by").description("cpu or memory")
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proc() {
+        let stat = "cpu  100 0 100 700 100 0 0 0 0 0\ncpu0 50 0 50 350 50 0 0 0 0 0\nintr 1\n";
+        let cpu = parse_cpu_times(stat);
+        assert_eq!(cpu.len(), 2);
+        assert_eq!(
+            cpu[0],
+            CpuTimes {
+                busy: 200,
+                total: 1000
+            }
+        );
+        let later = CpuTimes {
+            busy: 250,
+            total: 1100,
+        };
+        assert_eq!(later.usage_since(&cpu[0]), 50.0);
+
+        let meminfo = parse_meminfo("MemTotal:       2048 kB\nMemAvailable:   1024 kB\n");
+        assert_eq!(meminfo["MemTotal"], 2048 * 1024);
+
+        let dev = "Inter-|   Receive\n face |bytes\n  eth0: 100 1 0 0 0 0 0 0 200 2 0 0 0 0 0 0\n";
+        assert_eq!(parse_net_dev(dev)["eth0"], (100, 200));
+
+        let mounts = parse_mounts("/dev/sda1 /mnt/my\\040disk ext4 rw 0 0\n");
+        assert_eq!(mounts[0].1, "/mnt/my disk");
+
+        let stat = "42 (my app) S 1 42 42 0 -1 0 0 0 0 0 30 12 0 0 20 0 1 0 100 4096 250 0";
+        assert_eq!(
+            parse_process_stat(stat),
+            Some(ProcessStat {
+                pid: 42,
+                name: "my app".to_string(),
+                ticks: 42,
+                rss_pages: 250,
+            })
+        );
+    }
+}
@@ -11,7 +11,7 @@ use chrono::{DateTime, Local, Utc};
 use cron::Schedule;
 use log;
 use regex::Regex;
-use tokio::task::JoinHandle;
+use tokio::task::AbortHandle;
 
 // Delay Agent
 struct DelayAgent {
@@ -61,7 +61,10 @@ impl AsAgent for DelayAgent {
             *num_waiting_data += 1;
         }
 
-        tokio::time::sleep(Duration::from_millis(delay_ms as u64)).await;
+        self.askit()
+            .clock()
+            .sleep(Duration::from_millis(delay_ms as u64))
+            .await;
 
         self.try_output(ctx.clone(), pin, data.clone())?;
 
@@ -75,28 +78,23 @@ impl AsAgent for DelayAgent {
 // Interval Timer Agent
 struct IntervalTimerAgent {
     data: AsAgentData,
-    timer_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+    timer_handle: Option<AbortHandle>,
     interval_ms: u64,
 }
 
 impl IntervalTimerAgent {
     fn start_timer(&mut self) -> Result<(), AgentError> {
-        let timer_handle = self.timer_handle.clone();
         let interval_ms = self.interval_ms;
 
         let askit = self.askit().clone();
+        let clock = askit.clock();
         let agent_id = self.id().to_string();
-        let handle = self.runtime().spawn(async move {
+        let handle = self.data.spawn_task(async move {
+            let mut next = clock.now();
             loop {
                 // Sleep for the configured interval
-                tokio::time::sleep(tokio::time::Duration::from_millis(interval_ms)).await;
-
-                // Check if we've been stopped
-                if let Ok(handle) = timer_handle.lock() {
-                    if handle.is_none() {
-                        break;
-                    }
-                }
+                next += Duration::from_millis(interval_ms);
+                clock.sleep_until(next).await;
 
                 // Create a unit output
                 if let Err(e) = askit.try_send_agent_out(
@@ -109,21 +107,15 @@ impl IntervalTimerAgent {
                 }
             }
         });
-
-        // Store the timer handle
-        if let Ok(mut timer_handle) = self.timer_handle.lock() {
-            *timer_handle = Some(handle);
-        }
+        self.timer_handle = Some(handle);
 
         Ok(())
     }
 
     fn stop_timer(&mut self) -> Result<(), AgentError> {
         // Cancel the timer
-        if let Ok(mut timer_handle) = self.timer_handle.lock() {
-            if let Some(handle) = timer_handle.take() {
-                handle.abort();
-            }
+        if let Some(handle) = self.timer_handle.take() {
+            handle.abort();
         }
         Ok(())
     }
@@ -144,7 +136,7 @@ impl AsAgent for IntervalTimerAgent {
 
         Ok(Self {
             data: AsAgentData::new(askit, id, def_name, config),
-            timer_handle: Default::default(),
+            timer_handle: None,
             interval_ms,
         })
     }
@@ -161,10 +153,6 @@ impl AsAgent for IntervalTimerAgent {
         self.start_timer()
     }
 
-    fn stop(&mut self) -> Result<(), AgentError> {
-        self.stop_timer()
-    }
-
     fn configs_changed(&mut self) -> Result<(), AgentError> {
         // Check if interval has changed
         let interval = self.configs()?.get_string(CONFIG_INTERVAL)?;
@@ -213,8 +201,11 @@ impl AsAgent for OnStartAgent {
         let askit = self.askit().clone();
         let agent_id = self.id().to_string();
 
-        self.runtime().spawn(async move {
-            tokio::time::sleep(Duration::from_millis(delay_ms as u64)).await;
+        self.data.spawn_task(async move {
+            askit
+                .clock()
+                .sleep(Duration::from_millis(delay_ms as u64))
+                .await;
 
             if let Err(e) = askit.try_send_agent_out(
                 agent_id,
@@ -234,7 +225,7 @@ impl AsAgent for OnStartAgent {
 struct ScheduleTimerAgent {
     data: AsAgentData,
     cron_schedule: Option<Schedule>,
-    timer_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+    timer_handle: Option<AbortHandle>,
 }
 
 impl ScheduleTimerAgent {
@@ -244,15 +235,15 @@ impl ScheduleTimerAgent {
         };
 
         let askit = self.askit().clone();
+        let clock = askit.clock();
         let agent_id = self.id().to_string();
-        let timer_handle = self.timer_handle.clone();
         let schedule = schedule.clone();
 
-        let handle = self.runtime().spawn(async move {
+        let handle = self.data.spawn_task(async move {
             loop {
                 // Calculate the next time this schedule should run
-                let now: DateTime<Utc> = Utc::now();
-                let next = match schedule.upcoming(Utc).next() {
+                let now: DateTime<Utc> = clock.now().into();
+                let next = match schedule.after(&now).next() {
                     Some(next_time) => next_time,
                     None => {
                         log::error!("No upcoming schedule times found");
@@ -266,7 +257,7 @@ impl ScheduleTimerAgent {
                     Err(e) => {
                         log::error!("Failed to calculate duration until next schedule: {}", e);
                         // If we can't calculate the duration, sleep for a short time and try again
-                        tokio::time::sleep(Duration::from_secs(60)).await;
+                        clock.sleep(Duration::from_secs(60)).await;
                         continue;
                     }
                 };
@@ -280,17 +271,10 @@ impl ScheduleTimerAgent {
                 );
 
                 // Sleep until the next scheduled time
-                tokio::time::sleep(duration).await;
-
-                // Check if we've been stopped
-                if let Ok(handle) = timer_handle.lock() {
-                    if handle.is_none() {
-                        break;
-                    }
-                }
+                clock.sleep_until(next.into()).await;
 
                 // Get the current local timestamp (in seconds)
-                let current_local_time = Local::now().timestamp();
+                let current_local_time = DateTime::<Utc>::from(clock.now()).timestamp();
 
                 // Output the timestamp as an integer
                 if let Err(e) = askit.try_send_agent_out(
@@ -303,21 +287,15 @@ impl ScheduleTimerAgent {
                 }
             }
         });
-
-        // Store the timer handle
-        if let Ok(mut timer_handle) = self.timer_handle.lock() {
-            *timer_handle = Some(handle);
-        }
+        self.timer_handle = Some(handle);
 
         Ok(())
     }
 
     fn stop_timer(&mut self) -> Result<(), AgentError> {
         // Cancel the timer
-        if let Ok(mut timer_handle) = self.timer_handle.lock() {
-            if let Some(handle) = timer_handle.take() {
-                handle.abort();
-            }
+        if let Some(handle) = self.timer_handle.take() {
+            handle.abort();
         }
         Ok(())
     }
@@ -346,7 +324,7 @@ impl AsAgent for ScheduleTimerAgent {
         let mut agent = Self {
             data: AsAgentData::new(askit, id, def_name, config.clone()),
             cron_schedule: None,
-            timer_handle: Default::default(),
+            timer_handle: None,
         };
 
         if let Some(config) = config {
@@ -374,10 +352,6 @@ impl AsAgent for ScheduleTimerAgent {
         Ok(())
     }
 
-    fn stop(&mut self) -> Result<(), AgentError> {
-        self.stop_timer()
-    }
-
     fn configs_changed(&mut self) -> Result<(), AgentError> {
         // Check if schedule has changed
         let schedule_str = self.configs()?.get_string(CONFIG_SCHEDULE)?;
@@ -397,7 +371,7 @@ impl AsAgent for ScheduleTimerAgent {
 // Throttle agent
 struct ThrottleTimeAgent {
     data: AsAgentData,
-    timer_handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+    timer_handle: Arc<Mutex<Option<AbortHandle>>>,
     time_ms: u64,
     max_num_data: i64,
     waiting_data: Arc<Mutex<Vec<(AgentContext, String, AgentData)>>>,
@@ -410,18 +384,15 @@ impl ThrottleTimeAgent {
 
         let waiting_data = self.waiting_data.clone();
         let askit = self.askit().clone();
+        let clock = askit.clock();
         let agent_id = self.id().to_string();
 
-        let handle = self.runtime().spawn(async move {
+        let handle = self.data.spawn_task(async move {
             loop {
                 // Sleep for the configured interval
-                tokio::time::sleep(tokio::time::Duration::from_millis(time_ms)).await;
+                clock.sleep(Duration::from_millis(time_ms)).await;
 
-                // Check if we've been stopped
                 let mut handle = timer_handle.lock().unwrap();
-                if handle.is_none() {
-                    break;
-                }
 
                 // process the waiting data
                 let mut wd = waiting_data.lock().unwrap();
@@ -451,14 +422,13 @@ impl ThrottleTimeAgent {
         Ok(())
     }
 
-    fn stop_timer(&mut self) -> Result<(), AgentError> {
-        // Cancel the timer
-        if let Ok(mut timer_handle) = self.timer_handle.lock() {
-            if let Some(handle) = timer_handle.take() {
-                handle.abort();
-            }
-        }
-        Ok(())
+    // The timer stops by itself once no data is waiting, and is aborted when the agent stops
+    fn is_timer_running(&self) -> bool {
+        self.timer_handle
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|handle| !handle.is_finished())
     }
 }
 
@@ -498,10 +468,6 @@ impl AsAgent for ThrottleTimeAgent {
         &mut self.data
     }
 
-    fn stop(&mut self) -> Result<(), AgentError> {
-        self.stop_timer()
-    }
-
     fn configs_changed(&mut self) -> Result<(), AgentError> {
         // Check if interval has changed
         let time = self.configs()?.get_string(CONFIG_TIME)?;
@@ -530,7 +496,7 @@ impl AsAgent for ThrottleTimeAgent {
         pin: String,
         data: AgentData,
     ) -> Result<(), AgentError> {
-        if self.timer_handle.lock().unwrap().is_some() {
+        if self.is_timer_running() {
             // If the timer is running, we just add the data to the waiting list
             let mut wd = self.waiting_data.lock().unwrap();
 
@@ -559,7 +525,7 @@ impl AsAgent for ThrottleTimeAgent {
 }
 
 // Parse time duration strings like "2s", "10m", "200ms"
-fn parse_duration_to_ms(duration_str: &str) -> Result<u64, AgentError> {
+pub(crate) fn parse_duration_to_ms(duration_str: &str) -> Result<u64, AgentError> {
     const MIN_DURATION: u64 = 10;
 
     // Regular expression to match number followed by optional unit
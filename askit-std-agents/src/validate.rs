@@ -0,0 +1,179 @@
+use agent_stream_kit::{
+    ASKit, Agent, AgentConfigs, AgentContext, AgentData, AgentDefinition, AgentError,
+    AgentKindDefinition, AgentOutput, AgentValue, AsAgent, AsAgentData, SchemaViolation,
+    async_trait, new_agent_boxed,
+};
+use regex::Regex;
+use serde_json::{Value, json};
+
+// Validate
+//
+// The rules are a JSON Schema, checked as the schemas of data kinds are,
+// plus the `pattern` keyword for strings.
+struct ValidateAgent {
+    data: AsAgentData,
+}
+
+#[async_trait]
+impl AsAgent for ValidateAgent {
+    fn new(
+        askit: ASKit,
+        id: String,
+        def_name: String,
+        config: Option<AgentConfigs>,
+    ) -> Result<Self, AgentError> {
+        Ok(Self {
+            data: AsAgentData::new(askit, id, def_name, config),
+        })
+    }
+
+    fn data(&self) -> &AsAgentData {
+        &self.data
+    }
+
+    fn mut_data(&mut self) -> &mut AsAgentData {
+        &mut self.data
+    }
+
+    async fn process(
+        &mut self,
+        ctx: AgentContext,
+        _pin: String,
+        data: AgentData,
+    ) -> Result<(), AgentError> {
+        let rules: Value = self.configs()?.get_parsed(CONFIG_RULES)?;
+        let value = data.value.to_json();
+        let violations = violations(&rules, &value)?;
+        if violations.is_empty() {
+            return self.try_output(ctx, PIN_DATA, data);
+        }
+
+        let report = json!({
+            "kind": data.kind,
+            "data": value,
+            "violations": violations,
+        });
+        self.try_output(
+            ctx,
+            PIN_INVALID,
+            AgentData::from_json_with_kind(KIND_REPORT, report)?,
+        )
+    }
+}
+
+fn violations(rules: &Value, value: &Value) -> Result<Vec<SchemaViolation>, AgentError> {
+    let mut violations = AgentKindDefinition::new("rules")
+        .schema(rules.clone())
+        .violations(value);
+    check_patterns(rules, value, "", &mut violations)?;
+    Ok(violations)
+}
+
+// Follows the properties and items of the rules down to the strings with a pattern.
+fn check_patterns(
+    rules: &Value,
+    value: &Value,
+    path: &str,
+    violations: &mut Vec<SchemaViolation>,
+) -> Result<(), AgentError> {
+    if let (Some(pattern), Some(s)) = (
+        rules.get("pattern").and_then(|p| p.as_str()),
+        value.as_str(),
+    ) {
+        let re = Regex::new(pattern)
+            .map_err(|e| AgentError::InvalidConfig(format!("pattern {}: {}", pattern, e)))?;
+        if !re.is_match(s) {
+            let at = if path.is_empty() { "/" } else { path };
+            violations.push(SchemaViolation {
+                path: at.to_string(),
+                message: format!("{} does not match {}", at, pattern),
+            });
+        }
+    }
+
+    if let (Some(properties), Some(obj)) = (
+        rules.get("properties").and_then(|p| p.as_object()),
+        value.as_object(),
+    ) {
+        for (key, property) in properties {
+            if let Some(v) = obj.get(key) {
+                check_patterns(property, v, &format!("{}/{}", path, key), violations)?;
+            }
+        }
+    }
+
+    if let (Some(items), Some(arr)) = (rules.get("items"), value.as_array()) {
+        for (i, item) in arr.iter().enumerate() {
+            check_patterns(items, item, &format!("{}/{}", path, i), violations)?;
+        }
+    }
+
+    Ok(())
+}
+
+static AGENT_KIND: &str = "agent";
+static CATEGORY: &str = "Core/Data";
+
+static PIN_DATA: &str = "data";
+static PIN_INVALID: &str = "invalid";
+
+static CONFIG_RULES: &str = "rules";
+
+static KIND_REPORT: &str = "validation_report";
+
+pub fn register_agents(askit: &ASKit) {
+    askit.register_agent(
+        AgentDefinition::new(
+            AGENT_KIND,
+            "std_validate",
+            Some(new_agent_boxed::<ValidateAgent>),
+        )
+        .title("Validate")
+        .description("Outputs the data breaking the rules to invalid, with the violations")
+        .category(CATEGORY)
+        .inputs(vec![PIN_DATA])
+        .outputs(vec![PIN_DATA, PIN_INVALID])
+        .object_config_with(
+            CONFIG_RULES,
+            AgentValue::from_json(json!({"type": "object", "required": [], "properties": {}}))
+                .unwrap_or_default(),
+            |entry| {
+                entry.title("Rules").description(
+                    "JSON Schema (type, required, properties, minimum, maximum, pattern, ...)",
+                )
+            },
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_violations() {
+        let rules = json!({
+            "type": "object",
+            "required": ["name", "age"],
+            "properties": {
+                "name": {"type": "string"},
+                "age": {"type": "integer", "minimum": 0, "maximum": 150},
+                "email": {"type": "string", "pattern": "^[^@]+@[^@]+$"},
+            },
+        });
+
+        let valid = json!({"name": "a", "age": 20, "email": "a@example.com"});
+        assert!(violations(&rules, &valid).unwrap().is_empty());
+
+        let invalid = json!({"age": 200, "email": "a"});
+        let paths: Vec<String> = violations(&rules, &invalid)
+            .unwrap()
+            .into_iter()
+            .map(|v| v.path)
+            .collect();
+        assert_eq!(paths, vec!["/name", "/age", "/email"]);
+
+        let bad_pattern = json!({"type": "string", "pattern": "("});
+        assert!(violations(&bad_pattern, &json!("a")).is_err());
+    }
+}